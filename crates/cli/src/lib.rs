@@ -49,6 +49,8 @@ pub const CMD_GLUE: &str = "glue";
 pub const CMD_GEN_STUB_LIB: &str = "gen-stub-lib";
 
 pub const FLAG_DEBUG: &str = "debug";
+pub const FLAG_PATCHABLE_ENTRIES: &str = "patchable-entries";
+pub const FLAG_PROC_STATS: &str = "proc-stats";
 pub const FLAG_BUNDLE: &str = "bundle";
 pub const FLAG_DEV: &str = "dev";
 pub const FLAG_OPTIMIZE: &str = "optimize";
@@ -62,6 +64,20 @@ pub const FLAG_LINKER: &str = "linker";
 pub const FLAG_PREBUILT: &str = "prebuilt-platform";
 pub const FLAG_CHECK: &str = "check";
 pub const FLAG_WASM_STACK_SIZE_KB: &str = "wasm-stack-size-kb";
+pub const FLAG_CANONICAL_URL: &str = "canonical-url";
+pub const FLAG_EXTRACT_EXAMPLES: &str = "extract-examples";
+pub const FLAG_DOCS_VERSION: &str = "docs-version";
+pub const FLAG_PUBLISHED_VERSIONS: &str = "published-versions";
+pub const FLAG_STRICT: &str = "strict";
+pub const FLAG_ENTRY_ORDER: &str = "entry-order";
+pub const FLAG_DIFF: &str = "diff";
+pub const FLAG_SINGLE_FILE: &str = "single-file";
+pub const FLAG_EMBED_SOURCE: &str = "embed-source";
+pub const FLAG_INCLUDE_MODULES: &str = "include-modules";
+pub const FLAG_EXCLUDE_MODULES: &str = "exclude-modules";
+pub const FLAG_MATH: &str = "math";
+pub const FLAG_PREVIOUS_API: &str = "previous-api";
+pub const FLAG_REDIRECTS: &str = "redirects";
 pub const ROC_FILE: &str = "ROC_FILE";
 pub const ROC_DIR: &str = "ROC_DIR";
 pub const GLUE_DIR: &str = "GLUE_DIR";
@@ -96,7 +112,17 @@ pub fn build_app<'a>() -> Command<'a> {
 
     let flag_debug = Arg::new(FLAG_DEBUG)
         .long(FLAG_DEBUG)
-        .help("Store LLVM debug information in the generated program")
+        .help("Store LLVM debug information in the generated program\n(With --dev, write an assembly listing of the app next to its .roc file instead.)")
+        .required(false);
+
+    let flag_patchable_entries = Arg::new(FLAG_PATCHABLE_ENTRIES)
+        .long(FLAG_PATCHABLE_ENTRIES)
+        .help("With --dev, start every function with an entry that calls to it can be redirected through\n(This lets an incremental dev loop swap in recompiled functions without relinking.)")
+        .required(false);
+
+    let flag_proc_stats = Arg::new(FLAG_PROC_STATS)
+        .long(FLAG_PROC_STATS)
+        .help("With --dev, print the size, spills, relocations, and build time of every function\n(They're also written as JSON next to the app's .roc file, for comparing builds.)")
         .required(false);
 
     let flag_time = Arg::new(FLAG_TIME)
@@ -147,6 +173,8 @@ pub fn build_app<'a>() -> Command<'a> {
             .arg(flag_opt_size.clone())
             .arg(flag_dev.clone())
             .arg(flag_debug.clone())
+            .arg(flag_patchable_entries.clone())
+            .arg(flag_proc_stats.clone())
             .arg(flag_time.clone())
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
@@ -196,6 +224,8 @@ pub fn build_app<'a>() -> Command<'a> {
             .arg(flag_opt_size.clone())
             .arg(flag_dev.clone())
             .arg(flag_debug.clone())
+            .arg(flag_patchable_entries.clone())
+            .arg(flag_proc_stats.clone())
             .arg(flag_time.clone())
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
@@ -218,6 +248,8 @@ pub fn build_app<'a>() -> Command<'a> {
             .arg(flag_opt_size.clone())
             .arg(flag_dev.clone())
             .arg(flag_debug.clone())
+            .arg(flag_patchable_entries.clone())
+            .arg(flag_proc_stats.clone())
             .arg(flag_time.clone())
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
@@ -231,6 +263,8 @@ pub fn build_app<'a>() -> Command<'a> {
             .arg(flag_opt_size.clone())
             .arg(flag_dev.clone())
             .arg(flag_debug.clone())
+            .arg(flag_patchable_entries.clone())
+            .arg(flag_proc_stats.clone())
             .arg(flag_time.clone())
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
@@ -276,6 +310,103 @@ pub fn build_app<'a>() -> Command<'a> {
                     .required(false)
                     .default_value(DEFAULT_ROC_FILENAME),
                 )
+                .arg(Arg::new(FLAG_CANONICAL_URL)
+                    .long(FLAG_CANONICAL_URL)
+                    .help("The URL the docs will be published at, e.g. https://example.com/my-package\n(Used for canonical links, link previews, and sitemap.xml.)")
+                    .takes_value(true)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_DOCS_VERSION)
+                    .long(FLAG_DOCS_VERSION)
+                    .help("The version being documented, e.g. 1.2.0\n(The docs go in a directory for that version, with links to the other published versions.)")
+                    .value_name("VERSION")
+                    .validator(roc_docs::check_version)
+                    .takes_value(true)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_PUBLISHED_VERSIONS)
+                    .long(FLAG_PUBLISHED_VERSIONS)
+                    .help("The versions of these docs that are already published, newest first, e.g. 1.1.0,1.0.0")
+                    .value_name("VERSIONS")
+                    .validator(roc_docs::check_version)
+                    .use_value_delimiter(true)
+                    .multiple_values(true)
+                    .requires(FLAG_DOCS_VERSION)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_ENTRY_ORDER)
+                    .long(FLAG_ENTRY_ORDER)
+                    .help("The order of the entries in each module\n(A module can choose its own order with an `@order` line in its doc comment, e.g. `@order alphabetical` or `@order map, walk, keepIf`.)")
+                    .possible_values(["source", "alphabetical"])
+                    .default_value("source")
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_STRICT)
+                    .long(FLAG_STRICT)
+                    .help("Exit with a nonzero code if the generated docs have any broken links\n(Links to modules, entries, and headings within the docs are all checked.)")
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_SINGLE_FILE)
+                    .long(FLAG_SINGLE_FILE)
+                    .help("Also write the docs to one self-contained docs.html\n(Everything is inlined, so it can be read offline or attached to a release.)")
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_EMBED_SOURCE)
+                    .long(FLAG_EMBED_SOURCE)
+                    .help("Show each entry's source code in a collapsed section under it\n(This makes the generated pages noticeably bigger.)")
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_INCLUDE_MODULES)
+                    .long(FLAG_INCLUDE_MODULES)
+                    .help("Only document the modules whose names match one of these glob patterns, e.g. Json.*,Csv\n(`*` matches any characters, dots included, and `?` matches one.)")
+                    .value_name("PATTERNS")
+                    .use_value_delimiter(true)
+                    .multiple_values(true)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_EXCLUDE_MODULES)
+                    .long(FLAG_EXCLUDE_MODULES)
+                    .help("Leave out the modules whose names match one of these glob patterns, e.g. Internal*\n(Links to their entries in doc comments are rendered without a link.)")
+                    .value_name("PATTERNS")
+                    .use_value_delimiter(true)
+                    .multiple_values(true)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_MATH)
+                    .long(FLAG_MATH)
+                    .help("Render TeX math in doc comments, written like $x^2$, $$x^2$$, or in a ```math fence\n(Pages with math load KaTeX to render it.)")
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_PREVIOUS_API)
+                    .long(FLAG_PREVIOUS_API)
+                    .help("The api.json of an earlier release of these docs\n(Entries that have moved to another module since then are listed in redirects.json, and links to where they used to be lead to where they are now. With --canonical-url, the changes since then also go in an Atom feed, changes.xml.)")
+                    .value_name("API_JSON")
+                    .allow_invalid_utf8(true)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_REDIRECTS)
+                    .long(FLAG_REDIRECTS)
+                    .help("A JSON file of moves to redirect that --previous-api can't find, e.g. renames\n(It maps old qualified names to new ones, like {\"Json.decode\": \"Decode.fromJson\"}.)")
+                    .value_name("JSON_FILE")
+                    .allow_invalid_utf8(true)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_EXTRACT_EXAMPLES)
+                    .long(FLAG_EXTRACT_EXAMPLES)
+                    .help("Instead of generating docs, write each code block under an `# Examples` heading to its own .roc file in this directory\n(Lets CI check that documented examples still compile.)")
+                    .value_name("DIR")
+                    .allow_invalid_utf8(true)
+                    .takes_value(true)
+                    .required(false),
+                )
+                .arg(Arg::new(FLAG_DIFF)
+                    .long(FLAG_DIFF)
+                    .help("Instead of generating docs, compare two api.json files from earlier runs and list what was added, removed, or changed\n(Removals and signature changes are listed as breaking.)")
+                    .value_names(&["OLD", "NEW"])
+                    .number_of_values(2)
+                    .allow_invalid_utf8(true)
+                    .required(false),
+                )
         )
         .subcommand(Command::new(CMD_GLUE)
             .about("Generate glue code between a platform's Roc API and its host language")
@@ -324,6 +455,8 @@ pub fn build_app<'a>() -> Command<'a> {
         .arg(flag_opt_size)
         .arg(flag_dev)
         .arg(flag_debug)
+        .arg(flag_patchable_entries)
+        .arg(flag_proc_stats)
         .arg(flag_time)
         .arg(flag_linker)
         .arg(flag_prebuilt)
@@ -628,6 +761,8 @@ pub fn build(
     };
 
     let emit_debug_info = matches.is_present(FLAG_DEBUG);
+    let patchable_entries = matches.is_present(FLAG_PATCHABLE_ENTRIES);
+    let emit_proc_stats = matches.is_present(FLAG_PROC_STATS);
     let emit_timings = matches.is_present(FLAG_TIME);
 
     let threading = match matches
@@ -677,6 +812,8 @@ pub fn build(
         backend: code_gen_backend,
         opt_level,
         emit_debug_info,
+        patchable_entries,
+        emit_proc_stats,
     };
 
     let load_config = standard_load_config(&triple, build_ordering, threading);
@@ -1197,28 +1334,15 @@ fn roc_run_native<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
 
 #[cfg(feature = "run-wasm32")]
 fn run_wasm<I: Iterator<Item = S>, S: AsRef<[u8]>>(wasm_path: &std::path::Path, args: I) {
-    use bumpalo::collections::Vec;
-    use roc_wasm_interp::{DefaultImportDispatcher, Instance};
-
     let bytes = std::fs::read(wasm_path).unwrap();
-    let arena = Bump::new();
+    let args: Vec<S> = args.collect();
+    let argv: Vec<&[u8]> = args.iter().map(|arg| arg.as_ref()).collect();
 
-    let mut argv = Vec::<&[u8]>::new_in(&arena);
-    for arg in args {
-        let mut arg_copy = Vec::<u8>::new_in(&arena);
-        arg_copy.extend_from_slice(arg.as_ref());
-        argv.push(arg_copy.into_bump_slice());
-    }
-    let import_dispatcher = DefaultImportDispatcher::new(&argv);
+    let status = roc_wasm_interp::run_wasi(&bytes, &argv, &[], &[]).unwrap();
 
-    let mut instance = Instance::from_bytes(&arena, &bytes, import_dispatcher, false).unwrap();
-
-    instance
-        .call_export("_start", [])
-        .unwrap()
-        .unwrap()
-        .expect_i32()
-        .unwrap();
+    if !status.success() {
+        std::process::exit(status.0);
+    }
 }
 
 #[cfg(not(feature = "run-wasm32"))]
@@ -4,10 +4,16 @@ use roc_build::program::check_file;
 use roc_cli::{
     build_app, format, test, BuildConfig, FormatMode, Target, CMD_BUILD, CMD_CHECK, CMD_DEV,
     CMD_DOCS, CMD_EDIT, CMD_FORMAT, CMD_GEN_STUB_LIB, CMD_GLUE, CMD_REPL, CMD_RUN, CMD_TEST,
-    CMD_VERSION, DIRECTORY_OR_FILES, FLAG_CHECK, FLAG_LIB, FLAG_NO_LINK, FLAG_TARGET, FLAG_TIME,
+    CMD_VERSION, DIRECTORY_OR_FILES, FLAG_CANONICAL_URL, FLAG_CHECK, FLAG_DIFF, FLAG_DOCS_VERSION,
+    FLAG_EMBED_SOURCE, FLAG_ENTRY_ORDER, FLAG_EXCLUDE_MODULES, FLAG_EXTRACT_EXAMPLES,
+    FLAG_INCLUDE_MODULES, FLAG_LIB, FLAG_MATH, FLAG_NO_LINK, FLAG_PREVIOUS_API,
+    FLAG_PUBLISHED_VERSIONS, FLAG_REDIRECTS, FLAG_SINGLE_FILE, FLAG_STRICT, FLAG_TARGET, FLAG_TIME,
     GLUE_DIR, GLUE_SPEC, ROC_FILE,
 };
-use roc_docs::generate_docs_html;
+use roc_docs::{
+    diff_api, extract_examples, generate_docs_html, lint_docs, read_redirects, render_api_diff,
+    ApiSnapshot, DocsConfig,
+};
 use roc_error_macros::user_error;
 use roc_load::{LoadingProblem, Threading};
 use roc_packaging::cache::{self, RocCacheDir};
@@ -151,7 +157,15 @@ fn main() -> io::Result<()> {
                 RocCacheDir::Persistent(cache::roc_cache_dir().as_path()),
                 threading,
             ) {
-                Ok((problems, total_time)) => {
+                Ok((mut problems, total_time, loaded)) => {
+                    // Problems in doc comments don't stop the code from running,
+                    // so they're warnings rather than errors.
+                    let doc_problems = lint_docs(&loaded);
+                    for doc_problem in doc_problems.iter() {
+                        println!("\x1B[33mDoc comment problem\x1B[39m in {}", doc_problem);
+                    }
+                    problems.warnings += doc_problems.len();
+
                     println!(
                         "\x1B[{}m{}\x1B[39m {} and \x1B[{}m{}\x1B[39m {} found in {} ms.",
                         if problems.errors == 0 {
@@ -212,9 +226,78 @@ fn main() -> io::Result<()> {
         Some((CMD_DOCS, matches)) => {
             let root_filename = matches.value_of_os(ROC_FILE).unwrap();
 
-            generate_docs_html(PathBuf::from(root_filename));
+            if let Some(mut paths) = matches.values_of_os(FLAG_DIFF) {
+                let old = ApiSnapshot::read(Path::new(paths.next().unwrap()));
+                let new = ApiSnapshot::read(Path::new(paths.next().unwrap()));
 
-            Ok(0)
+                match (old, new) {
+                    (Ok(old), Ok(new)) => {
+                        print!("{}", render_api_diff(&diff_api(&old, &new)));
+
+                        Ok(0)
+                    }
+                    (Err(problem), _) | (_, Err(problem)) => {
+                        eprintln!("{problem}");
+
+                        Ok(1)
+                    }
+                }
+            } else if let Some(examples_dir) = matches.value_of_os(FLAG_EXTRACT_EXAMPLES) {
+                extract_examples(PathBuf::from(root_filename), Path::new(examples_dir));
+
+                Ok(0)
+            } else {
+                let previous_api = matches
+                    .value_of_os(FLAG_PREVIOUS_API)
+                    .map(|path| ApiSnapshot::read(Path::new(path)))
+                    .transpose();
+                let redirects = matches
+                    .value_of_os(FLAG_REDIRECTS)
+                    .map(|path| read_redirects(Path::new(path)))
+                    .transpose();
+                let (previous_api, redirects) = match (previous_api, redirects) {
+                    (Ok(previous_api), Ok(redirects)) => {
+                        (previous_api, redirects.unwrap_or_default())
+                    }
+                    (Err(problem), _) | (_, Err(problem)) => {
+                        eprintln!("{problem}");
+
+                        return Ok(1);
+                    }
+                };
+
+                let config = DocsConfig {
+                    canonical_base_url: matches.value_of(FLAG_CANONICAL_URL).map(String::from),
+                    version: matches.value_of(FLAG_DOCS_VERSION).map(String::from),
+                    published_versions: matches
+                        .values_of(FLAG_PUBLISHED_VERSIONS)
+                        .map(|versions| versions.map(String::from).collect())
+                        .unwrap_or_default(),
+                    entry_order: matches.value_of_t(FLAG_ENTRY_ORDER).unwrap_or_default(),
+                    single_file: matches.is_present(FLAG_SINGLE_FILE),
+                    embed_source: matches.is_present(FLAG_EMBED_SOURCE),
+                    include_modules: matches
+                        .values_of(FLAG_INCLUDE_MODULES)
+                        .map(|patterns| patterns.map(String::from).collect())
+                        .unwrap_or_default(),
+                    exclude_modules: matches
+                        .values_of(FLAG_EXCLUDE_MODULES)
+                        .map(|patterns| patterns.map(String::from).collect())
+                        .unwrap_or_default(),
+                    math: matches.is_present(FLAG_MATH),
+                    previous_api,
+                    redirects,
+                    ..DocsConfig::default()
+                };
+
+                let broken_links = generate_docs_html(PathBuf::from(root_filename), config);
+
+                if matches.is_present(FLAG_STRICT) && !broken_links.is_empty() {
+                    Ok(1)
+                } else {
+                    Ok(0)
+                }
+            }
         }
         Some((CMD_FORMAT, matches)) => {
             let maybe_values = matches.values_of_os(DIRECTORY_OR_FILES);
@@ -1182,6 +1182,18 @@ mod cli_run {
             test_benchmark("CFold.roc", "cfold", &["3"], "11 & 11\n", UseValgrind::Yes)
         }
 
+        #[test]
+        #[cfg_attr(windows, ignore)]
+        fn empty_checks() {
+            test_benchmark(
+                "EmptyChecks.roc",
+                "emptychecks",
+                &["1000"],
+                "4000\n",
+                UseValgrind::Yes,
+            )
+        }
+
         #[test]
         #[cfg_attr(windows, ignore)]
         fn deriv() {
@@ -1522,18 +1534,14 @@ fn run_wasm(wasm_path: &std::path::Path, stdin: &[&str]) -> String {
 
     let mut instance = {
         let mut fake_stdin = vec![];
-        let fake_stdout = vec![];
-        let fake_stderr = vec![];
         for s in stdin {
             fake_stdin.extend_from_slice(s.as_bytes())
         }
 
         let mut dispatcher = DefaultImportDispatcher::default();
-        dispatcher.wasi.files = vec![
-            WasiFile::ReadOnly(fake_stdin),
-            WasiFile::WriteOnly(fake_stdout),
-            WasiFile::WriteOnly(fake_stderr),
-        ];
+        dispatcher.wasi.set_stdin(WasiFile::ReadOnly(fake_stdin));
+        dispatcher.wasi.set_stdout(WasiFile::WriteOnly(vec![]));
+        dispatcher.wasi.set_stderr(WasiFile::WriteOnly(vec![]));
 
         Instance::from_bytes(&arena, &wasm_bytes, dispatcher, false).unwrap()
     };
@@ -1541,10 +1549,10 @@ fn run_wasm(wasm_path: &std::path::Path, stdin: &[&str]) -> String {
     let result = instance.call_export("_start", []);
 
     match result {
-        Ok(Some(Value::I32(0))) => match &instance.import_dispatcher.wasi.files[1] {
-            WasiFile::WriteOnly(fake_stdout) => String::from_utf8(fake_stdout.clone())
+        Ok(Some(Value::I32(0))) => match instance.import_dispatcher.wasi.file_contents(1) {
+            Some(fake_stdout) => String::from_utf8(fake_stdout.to_vec())
                 .unwrap_or_else(|_| "Wasm test printed invalid UTF-8".into()),
-            _ => unreachable!(),
+            None => unreachable!(),
         },
         Ok(Some(Value::I32(exit_code))) => {
             format!("WASI app exit code {}", exit_code)
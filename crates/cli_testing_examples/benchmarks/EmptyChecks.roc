@@ -0,0 +1,28 @@
+app "emptychecks"
+    packages { pf: "platform/main.roc" }
+    imports [pf.Task]
+    provides [main] to pf
+
+# Calls Str.isEmpty and List.isEmpty in a hot loop, to measure the overhead of these
+# small builtins. Use `roc build --dev` to benchmark the development backend.
+main : Task.Task {} []
+main =
+    Task.after
+        Task.getInt
+        \n ->
+            strings = ["", "short", "a string that is too long to be a small string", ""]
+            lists = [[], [1], [1, 2, 3], []]
+
+            countEmpty n strings lists 0
+            |> Num.toStr
+            |> Task.putLine
+
+countEmpty : I64, List Str, List (List I64), Nat -> Nat
+countEmpty = \n, strings, lists, count ->
+    if n <= 0 then
+        count
+    else
+        emptyStrings = List.countIf strings Str.isEmpty
+        emptyLists = List.countIf lists List.isEmpty
+
+        countEmpty (n - 1) strings lists (count + emptyStrings + emptyLists)
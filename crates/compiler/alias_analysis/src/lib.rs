@@ -1075,7 +1075,7 @@ fn lowlevel_spec<'a>(
             // just dream up a unit value
             builder.add_make_tuple(block, &[])
         }
-        NumLte | NumLt | NumGt | NumGte | NumCompare => {
+        NumLte | NumLt | NumGt | NumGte | NumCompare | NumCompareTotal => {
             // just dream up a unit value
             builder.add_make_tuple(block, &[])
         }
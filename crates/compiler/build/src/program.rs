@@ -84,6 +84,12 @@ pub struct CodeGenOptions {
     pub backend: CodeGenBackend,
     pub opt_level: OptLevel,
     pub emit_debug_info: bool,
+    /// With the dev backend, start every proc with a patchable entry, so that an incremental dev
+    /// loop can redirect calls to a recompiled proc without relinking
+    pub patchable_entries: bool,
+    /// With the dev backend, print the size, spills, relocations, helper procs, and build time of
+    /// every proc, and write them as JSON next to the app's .roc file
+    pub emit_proc_stats: bool,
 }
 
 type GenFromMono<'a> = (CodeObject, CodeGenTiming, ExpectMetadata<'a>);
@@ -103,13 +109,24 @@ pub fn gen_from_mono_module<'a>(
     let opt = code_gen_options.opt_level;
 
     match code_gen_options.backend {
-        CodeGenBackend::Assembly | CodeGenBackend::Wasm => gen_from_mono_module_dev(
-            arena,
-            loaded,
-            target,
-            preprocessed_host_path,
-            wasm_dev_stack_bytes,
-        ),
+        CodeGenBackend::Assembly | CodeGenBackend::Wasm => {
+            // Like the .ll file for LLVM, the listing goes next to the app's .roc file
+            let assembly_listing_path = debug.then(|| path.with_extension("s"));
+            let proc_stats_path = code_gen_options
+                .emit_proc_stats
+                .then(|| path.with_extension("proc-stats.json"));
+
+            gen_from_mono_module_dev(
+                arena,
+                loaded,
+                target,
+                preprocessed_host_path,
+                wasm_dev_stack_bytes,
+                assembly_listing_path.as_deref(),
+                code_gen_options.patchable_entries,
+                proc_stats_path.as_deref(),
+            )
+        }
         CodeGenBackend::Llvm(backend_mode) => {
             gen_from_mono_module_llvm(arena, loaded, path, target, opt, backend_mode, debug)
         }
@@ -443,12 +460,16 @@ fn gen_from_mono_module_llvm<'a>(
 }
 
 #[cfg(feature = "target-wasm32")]
+#[allow(clippy::too_many_arguments)]
 fn gen_from_mono_module_dev<'a>(
     arena: &'a bumpalo::Bump,
     loaded: MonomorphizedModule<'a>,
     target: &target_lexicon::Triple,
     preprocessed_host_path: &Path,
     wasm_dev_stack_bytes: Option<u32>,
+    assembly_listing_path: Option<&Path>,
+    patchable_entries: bool,
+    proc_stats_path: Option<&Path>,
 ) -> GenFromMono<'a> {
     use target_lexicon::Architecture;
 
@@ -459,27 +480,41 @@ fn gen_from_mono_module_dev<'a>(
             preprocessed_host_path,
             wasm_dev_stack_bytes,
         ),
-        Architecture::X86_64 | Architecture::Aarch64(_) => {
-            gen_from_mono_module_dev_assembly(arena, loaded, target)
-        }
+        Architecture::X86_64 | Architecture::Aarch64(_) => gen_from_mono_module_dev_assembly(
+            arena,
+            loaded,
+            target,
+            assembly_listing_path,
+            patchable_entries,
+            proc_stats_path,
+        ),
         _ => todo!(),
     }
 }
 
 #[cfg(not(feature = "target-wasm32"))]
+#[allow(clippy::too_many_arguments)]
 pub fn gen_from_mono_module_dev<'a>(
     arena: &'a bumpalo::Bump,
     loaded: MonomorphizedModule<'a>,
     target: &target_lexicon::Triple,
     _host_input_path: &Path,
     _wasm_dev_stack_bytes: Option<u32>,
+    assembly_listing_path: Option<&Path>,
+    patchable_entries: bool,
+    proc_stats_path: Option<&Path>,
 ) -> GenFromMono<'a> {
     use target_lexicon::Architecture;
 
     match target.architecture {
-        Architecture::X86_64 | Architecture::Aarch64(_) => {
-            gen_from_mono_module_dev_assembly(arena, loaded, target)
-        }
+        Architecture::X86_64 | Architecture::Aarch64(_) => gen_from_mono_module_dev_assembly(
+            arena,
+            loaded,
+            target,
+            assembly_listing_path,
+            patchable_entries,
+            proc_stats_path,
+        ),
         _ => todo!(),
     }
 }
@@ -555,11 +590,15 @@ fn gen_from_mono_module_dev_assembly<'a>(
     arena: &'a bumpalo::Bump,
     loaded: MonomorphizedModule<'a>,
     target: &target_lexicon::Triple,
+    assembly_listing_path: Option<&Path>,
+    patchable_entries: bool,
+    proc_stats_path: Option<&Path>,
 ) -> GenFromMono<'a> {
     let code_gen_start = Instant::now();
 
     let lazy_literals = true;
     let generate_allocators = false; // provided by the platform
+    let log_refcounts = roc_debug_flags::dbg_set!(roc_debug_flags::ROC_LOG_DEV_REFCOUNTS); // the platform must provide roc_log_refcount
 
     let MonomorphizedModule {
         module_id,
@@ -576,11 +615,40 @@ fn gen_from_mono_module_dev_assembly<'a>(
         exposed_to_host: exposed_to_host.top_level_values.keys().copied().collect(),
         lazy_literals,
         generate_allocators,
+        log_refcounts,
+        unsupported: Default::default(),
+        emit_assembly: assembly_listing_path.is_some(),
+        assembly: Default::default(),
+        collect_proc_stats: proc_stats_path.is_some(),
+        proc_stats: Default::default(),
+        patchable_prologues: patchable_entries,
     };
 
     let module_object =
         roc_gen_dev::build_module(&env, &mut interns, &mut layout_interner, target, procedures);
 
+    let unsupported = env.unsupported.into_inner();
+    if !unsupported.is_empty() {
+        eprintln!(
+            "{}",
+            roc_gen_dev::report_unsupported(&unsupported, &interns)
+        );
+    }
+
+    if let Some(path) = assembly_listing_path {
+        std::fs::write(path, env.assembly.into_inner()).unwrap_or_else(|err| {
+            eprintln!(
+                "Could not write the assembly listing to {}: {}",
+                path.display(),
+                err
+            )
+        });
+    }
+
+    if let Some(path) = proc_stats_path {
+        write_proc_stats(&env.proc_stats.into_inner(), path);
+    }
+
     let code_gen = code_gen_start.elapsed();
 
     let module_out = module_object
@@ -598,6 +666,23 @@ fn gen_from_mono_module_dev_assembly<'a>(
     )
 }
 
+/// Print the stats of the procs the dev backend built, and write them to `path` for other tools
+fn write_proc_stats(proc_stats: &[roc_gen_dev::ProcStats], path: &Path) {
+    use roc_gen_dev::{report_proc_stats, ProcStatsFormat};
+
+    eprint!("{}", report_proc_stats(proc_stats, ProcStatsFormat::Text));
+
+    let report = report_proc_stats(proc_stats, ProcStatsFormat::for_path(path));
+
+    std::fs::write(path, report).unwrap_or_else(|err| {
+        eprintln!(
+            "Could not write the dev backend's proc stats to {}: {}",
+            path.display(),
+            err
+        )
+    });
+}
+
 fn report_timing(buf: &mut String, label: &str, duration: Duration) {
     use std::fmt::Write;
 
@@ -1152,6 +1237,8 @@ fn build_and_preprocess_host_lowlevel(
     )
 }
 
+/// Typecheck the file and report its problems. The loaded module is returned too, so that the
+/// caller can do further checks, like on its doc comments.
 #[allow(clippy::too_many_arguments)]
 pub fn check_file<'a>(
     arena: &'a Bump,
@@ -1159,7 +1246,7 @@ pub fn check_file<'a>(
     emit_timings: bool,
     roc_cache_dir: RocCacheDir<'_>,
     threading: Threading,
-) -> Result<(Problems, Duration), LoadingProblem<'a>> {
+) -> Result<(Problems, Duration, LoadedModule), LoadingProblem<'a>> {
     let compilation_start = Instant::now();
 
     // only used for generating errors. We don't do code generation, so hardcoding should be fine
@@ -1222,7 +1309,9 @@ pub fn check_file<'a>(
         println!("Finished checking in {} ms\n", compilation_end.as_millis(),);
     }
 
-    Ok((report_problems_typechecked(&mut loaded), compilation_end))
+    let problems = report_problems_typechecked(&mut loaded);
+
+    Ok((problems, compilation_end, loaded))
 }
 
 pub fn build_str_test<'a>(
@@ -1237,6 +1326,8 @@ pub fn build_str_test<'a>(
         backend: CodeGenBackend::Llvm(LlvmBackendMode::Binary),
         opt_level: OptLevel::Normal,
         emit_debug_info: false,
+        patchable_entries: false,
+        emit_proc_stats: false,
     };
 
     let emit_timings = false;
@@ -33,6 +33,15 @@ fn main() {
 
     generate_object_file(&bitcode_path, "object", BUILTINS_HOST_FILE);
 
+    // the builtins plus the allocators and refcount log that the dev backend's tests use
+    #[cfg(windows)]
+    const BUILTINS_HOST_TEST_FILE: &str = "builtins-host-test.obj";
+
+    #[cfg(not(windows))]
+    const BUILTINS_HOST_TEST_FILE: &str = "builtins-host-test.o";
+
+    generate_object_file(&bitcode_path, "test-object", BUILTINS_HOST_TEST_FILE);
+
     generate_object_file(
         &bitcode_path,
         "windows-x86_64-object",
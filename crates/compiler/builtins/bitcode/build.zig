@@ -14,9 +14,17 @@ pub fn build(b: *Builder) void {
     const main_path_desc = b.fmt("Override path to main.zig. Used by \"ir\" and \"test\". Defaults to \"{s}\". ", .{fallback_main_path});
     const main_path = b.option([]const u8, "main-path", main_path_desc) orelse fallback_main_path;
 
+    // The allocators and refcount log that the dev backend's tests link against (see refcount_log.zig)
+    // are only exported from the "test-object" build, never from the builtins that ship with roc.
+    const release_options = b.addOptions();
+    release_options.addOption(bool, "test_exports", false);
+    const test_options = b.addOptions();
+    test_options.addOption(bool, "test_exports", true);
+
     // Tests
     var main_tests = b.addTest(main_path);
     main_tests.setBuildMode(mode);
+    main_tests.addOptions("build_options", test_options);
     main_tests.linkSystemLibrary("c");
     const test_step = b.step("test", "Run tests");
     test_step.dependOn(&main_tests.step);
@@ -34,16 +42,17 @@ pub fn build(b: *Builder) void {
     const wasm32_target = makeWasm32Target();
 
     // LLVM IR
-    generateLlvmIrFile(b, mode, host_target, main_path, "ir", "builtins-host");
-    generateLlvmIrFile(b, mode, linux32_target, main_path, "ir-i386", "builtins-i386");
-    generateLlvmIrFile(b, mode, linux64_target, main_path, "ir-x86_64", "builtins-x86_64");
-    generateLlvmIrFile(b, mode, windows64_target, main_path, "ir-windows-x86_64", "builtins-windows-x86_64");
-    generateLlvmIrFile(b, mode, wasm32_target, main_path, "ir-wasm32", "builtins-wasm32");
+    generateLlvmIrFile(b, mode, host_target, main_path, "ir", "builtins-host", release_options);
+    generateLlvmIrFile(b, mode, linux32_target, main_path, "ir-i386", "builtins-i386", release_options);
+    generateLlvmIrFile(b, mode, linux64_target, main_path, "ir-x86_64", "builtins-x86_64", release_options);
+    generateLlvmIrFile(b, mode, windows64_target, main_path, "ir-windows-x86_64", "builtins-windows-x86_64", release_options);
+    generateLlvmIrFile(b, mode, wasm32_target, main_path, "ir-wasm32", "builtins-wasm32", release_options);
 
     // Generate Object Files
-    generateObjectFile(b, mode, host_target, main_path, "object", "builtins-host");
-    generateObjectFile(b, mode, windows64_target, main_path, "windows-x86_64-object", "builtins-windows-x86_64");
-    generateObjectFile(b, mode, wasm32_target, main_path, "wasm32-object", "builtins-wasm32");
+    generateObjectFile(b, mode, host_target, main_path, "object", "builtins-host", release_options);
+    generateObjectFile(b, mode, host_target, main_path, "test-object", "builtins-host-test", test_options);
+    generateObjectFile(b, mode, windows64_target, main_path, "windows-x86_64-object", "builtins-windows-x86_64", release_options);
+    generateObjectFile(b, mode, wasm32_target, main_path, "wasm32-object", "builtins-wasm32", release_options);
 
     removeInstallSteps(b);
 }
@@ -56,9 +65,11 @@ fn generateLlvmIrFile(
     main_path: []const u8,
     step_name: []const u8,
     object_name: []const u8,
+    options: *std.build.OptionsStep,
 ) void {
     const obj = b.addObject(object_name, main_path);
     obj.setBuildMode(mode);
+    obj.addOptions("build_options", options);
     obj.strip = true;
     obj.emit_llvm_ir = .emit;
     obj.emit_llvm_bc = .emit;
@@ -81,9 +92,11 @@ fn generateObjectFile(
     main_path: []const u8,
     step_name: []const u8,
     object_name: []const u8,
+    options: *std.build.OptionsStep,
 ) void {
     const obj = b.addObject(object_name, main_path);
     obj.setBuildMode(mode);
+    obj.addOptions("build_options", options);
     obj.linkSystemLibrary("c");
     obj.setOutputDir(".");
     obj.strip = true;
@@ -44,6 +44,10 @@ pub const RocDec = extern struct {
         return ret;
     }
 
+    pub fn toF64(dec: RocDec) f64 {
+        return @intToFloat(f64, dec.num) / comptime @intToFloat(f64, one_point_zero_i128);
+    }
+
     pub fn fromStr(roc_str: RocStr) ?RocDec {
         if (roc_str.isEmpty()) {
             return null;
@@ -737,6 +741,12 @@ test "fromF64" {
     try expectEqual(RocDec{ .num = 25500000000000000000 }, dec.?);
 }
 
+test "toF64" {
+    var dec = RocDec.fromF64(25.5).?;
+
+    try expectEqual(@as(f64, 25.5), dec.toF64());
+}
+
 test "fromF64 overflow" {
     var dec = RocDec.fromF64(1e308);
     try expectEqual(dec, null);
@@ -1108,6 +1118,14 @@ pub fn fromF64C(arg: f64) callconv(.C) i128 {
     return if (@call(.{ .modifier = always_inline }, RocDec.fromF64, .{arg})) |dec| dec.num else @panic("TODO runtime exception failing convert f64 to RocDec");
 }
 
+pub fn toF32C(arg: RocDec) callconv(.C) f32 {
+    return @floatCast(f32, @call(.{ .modifier = always_inline }, RocDec.toF64, .{arg}));
+}
+
+pub fn toF64C(arg: RocDec) callconv(.C) f64 {
+    return @call(.{ .modifier = always_inline }, RocDec.toF64, .{arg});
+}
+
 pub fn eqC(arg1: RocDec, arg2: RocDec) callconv(.C) bool {
     return @call(.{ .modifier = always_inline }, RocDec.eq, .{ arg1, arg2 });
 }
@@ -1159,3 +1177,47 @@ pub fn mulOrPanicC(arg1: RocDec, arg2: RocDec) callconv(.C) RocDec {
 pub fn mulSaturatedC(arg1: RocDec, arg2: RocDec) callconv(.C) RocDec {
     return @call(.{ .modifier = always_inline }, RocDec.mulSaturated, .{ arg1, arg2 });
 }
+
+// Dec rounds to an integer of any width, so these are exported once per width like the float
+// versions in num.zig
+
+pub fn exportRound(comptime T: type, comptime name: []const u8) void {
+    comptime var f = struct {
+        fn func(arg: RocDec) callconv(.C) T {
+            const one = RocDec.one_point_zero_i128;
+            var whole = @divTrunc(arg.num, one);
+            const remainder = @rem(arg.num, one);
+
+            // Halfway rounds away from zero, like @round does for floats
+            if (remainder >= @divExact(one, 2)) {
+                whole += 1;
+            } else if (remainder <= -@divExact(one, 2)) {
+                whole -= 1;
+            }
+
+            return @intCast(T, whole);
+        }
+    }.func;
+    @export(f, .{ .name = name ++ @typeName(T), .linkage = .Strong });
+}
+
+pub fn exportFloor(comptime T: type, comptime name: []const u8) void {
+    comptime var f = struct {
+        fn func(arg: RocDec) callconv(.C) T {
+            return @intCast(T, @divFloor(arg.num, RocDec.one_point_zero_i128));
+        }
+    }.func;
+    @export(f, .{ .name = name ++ @typeName(T), .linkage = .Strong });
+}
+
+pub fn exportCeiling(comptime T: type, comptime name: []const u8) void {
+    comptime var f = struct {
+        fn func(arg: RocDec) callconv(.C) T {
+            const one = RocDec.one_point_zero_i128;
+            const whole = @divTrunc(arg.num, one);
+
+            return @intCast(T, if (@rem(arg.num, one) > 0) whole + 1 else whole);
+        }
+    }.func;
+    @export(f, .{ .name = name ++ @typeName(T), .linkage = .Strong });
+}
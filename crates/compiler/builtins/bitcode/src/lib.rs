@@ -5,6 +5,11 @@ const HOST_WASM: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/builtins-wasm
 // for all targets, so that we can do cross-compilation!
 #[cfg(unix)]
 const HOST_UNIX: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/builtins-host.o"));
+#[cfg(unix)]
+const HOST_UNIX_TEST: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/builtins-host-test.o"));
+#[cfg(windows)]
+const HOST_WINDOWS_TEST: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/builtins-host-test.obj"));
 #[cfg(windows)]
 const HOST_WINDOWS: &[u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/builtins-windows-x86_64.obj"));
@@ -34,6 +39,29 @@ fn host_unix_tempfile() -> std::io::Result<NamedTempFile> {
     Ok(tempfile)
 }
 
+/// The host builtins, plus the allocators and refcount log that the dev backend uses when it's
+/// built with `log_refcounts`. Only for tests, roc never links these into an app.
+pub fn host_test_tempfile() -> std::io::Result<NamedTempFile> {
+    #[cfg(unix)]
+    let (suffix, bytes) = (".o", HOST_UNIX_TEST);
+
+    #[cfg(windows)]
+    let (suffix, bytes) = (".obj", HOST_WINDOWS_TEST);
+
+    #[cfg(not(any(windows, unix)))]
+    let (suffix, bytes): (&str, &[u8]) = unreachable!();
+
+    let tempfile = tempfile::Builder::new()
+        .prefix("host_bitcode_test")
+        .suffix(suffix)
+        .rand_bytes(8)
+        .tempfile()?;
+
+    std::fs::write(tempfile.path(), bytes)?;
+
+    Ok(tempfile)
+}
+
 #[cfg(windows)]
 fn host_windows_tempfile() -> std::io::Result<NamedTempFile> {
     let tempfile = tempfile::Builder::new()
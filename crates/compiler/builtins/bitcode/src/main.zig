@@ -3,11 +3,14 @@ const builtin = @import("builtin");
 const math = std.math;
 const utils = @import("utils.zig");
 const expect = @import("expect.zig");
+const build_options = @import("build_options");
+const refcount_log = @import("refcount_log.zig");
 const panic_utils = @import("panic.zig");
 
 const ROC_BUILTINS = "roc_builtins";
 const NUM = "num";
 const STR = "str";
+const DEC = "dec";
 
 // Dec Module
 const dec = @import("dec.zig");
@@ -16,6 +19,8 @@ comptime {
     exportDecFn(dec.fromStr, "from_str");
     exportDecFn(dec.toStr, "to_str");
     exportDecFn(dec.fromF64C, "from_f64");
+    exportDecFn(dec.toF32C, "to_f32");
+    exportDecFn(dec.toF64C, "to_f64");
     exportDecFn(dec.eqC, "eq");
     exportDecFn(dec.neqC, "neq");
     exportDecFn(dec.negateC, "negate");
@@ -32,6 +37,12 @@ comptime {
     exportDecFn(dec.mulC, "mul_with_overflow");
     exportDecFn(dec.mulOrPanicC, "mul_or_panic");
     exportDecFn(dec.mulSaturatedC, "mul_saturated");
+
+    inline for (INTEGERS) |T| {
+        dec.exportRound(T, ROC_BUILTINS ++ "." ++ DEC ++ ".round.");
+        dec.exportFloor(T, ROC_BUILTINS ++ "." ++ DEC ++ ".floor.");
+        dec.exportCeiling(T, ROC_BUILTINS ++ "." ++ DEC ++ ".ceiling.");
+    }
 }
 
 // List Module
@@ -77,8 +88,15 @@ comptime {
         num.exportPow(T, ROC_BUILTINS ++ "." ++ NUM ++ ".pow_int.");
         num.exportDivCeil(T, ROC_BUILTINS ++ "." ++ NUM ++ ".div_ceil.");
 
+        num.exportIntToFloat(T, f32, ROC_BUILTINS ++ "." ++ NUM ++ ".int_to_f32.");
+        num.exportIntToFloat(T, f64, ROC_BUILTINS ++ "." ++ NUM ++ ".int_to_f64.");
+
         num.exportRoundF32(T, ROC_BUILTINS ++ "." ++ NUM ++ ".round_f32.");
         num.exportRoundF64(T, ROC_BUILTINS ++ "." ++ NUM ++ ".round_f64.");
+        num.exportFloorF32(T, ROC_BUILTINS ++ "." ++ NUM ++ ".floor_f32.");
+        num.exportFloorF64(T, ROC_BUILTINS ++ "." ++ NUM ++ ".floor_f64.");
+        num.exportCeilingF32(T, ROC_BUILTINS ++ "." ++ NUM ++ ".ceiling_f32.");
+        num.exportCeilingF64(T, ROC_BUILTINS ++ "." ++ NUM ++ ".ceiling_f64.");
 
         num.exportAddWithOverflow(T, ROC_BUILTINS ++ "." ++ NUM ++ ".add_with_overflow.");
         num.exportAddOrPanic(T, ROC_BUILTINS ++ "." ++ NUM ++ ".add_or_panic.");
@@ -190,6 +208,15 @@ comptime {
         @export(expect.setSharedBuffer, .{ .name = "set_shared_buffer", .linkage = .Weak });
 
         exportUtilsFn(expect.readSharedBufferEnv, "read_env_shared_buffer");
+
+        // allocators and a refcount hook for the dev backend, for checking refcounts in tests
+        if (build_options.test_exports) {
+            exportUtilsFn(refcount_log.testAlloc, "test_alloc");
+            exportUtilsFn(refcount_log.testRealloc, "test_realloc");
+            exportUtilsFn(refcount_log.testDealloc, "test_dealloc");
+            exportUtilsFn(refcount_log.testLogRefcount, "test_log_refcount");
+            exportUtilsFn(refcount_log.testRefcountLog, "test_refcount_log");
+        }
     }
 
     if (builtin.target.cpu.arch == .aarch64) {
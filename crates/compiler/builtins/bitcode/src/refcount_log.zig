@@ -0,0 +1,76 @@
+// A log of allocations and refcount changes, so that tests can check that a program frees
+// everything it allocates, exactly once. The dev backend uses these as its allocators, and calls
+// testLogRefcount before every inc and dec, when it's asked to log refcounts.
+const std = @import("std");
+
+extern fn malloc(size: usize) callconv(.C) ?*anyopaque;
+extern fn realloc(c_ptr: *anyopaque, new_size: usize) callconv(.C) ?*anyopaque;
+extern fn free(c_ptr: *anyopaque) callconv(.C) void;
+
+pub const EventKind = enum(u64) {
+    alloc = 0,
+    dealloc = 1,
+    refcount = 2,
+};
+
+pub const Event = extern struct {
+    kind: EventKind,
+    // The start of the allocation, or the refcount's address for a refcount change
+    address: usize,
+    // The size of an allocation, or how much a refcount changed by
+    value: isize,
+};
+
+const CAPACITY: usize = 4096;
+
+var events: [CAPACITY]Event = undefined;
+var events_len: usize = 0;
+
+fn push(kind: EventKind, address: usize, value: isize) void {
+    if (events_len < CAPACITY) {
+        events[events_len] = Event{ .kind = kind, .address = address, .value = value };
+    }
+
+    // Keep counting, so that the reader can tell that some events didn't fit
+    events_len += 1;
+}
+
+pub fn testAlloc(size: usize, _: u32) callconv(.C) ?*anyopaque {
+    const ptr = malloc(size);
+
+    if (ptr) |p| {
+        push(EventKind.alloc, @ptrToInt(p), @intCast(isize, size));
+    }
+
+    return ptr;
+}
+
+pub fn testRealloc(c_ptr: *anyopaque, new_size: usize, _: usize, _: u32) callconv(.C) ?*anyopaque {
+    push(EventKind.dealloc, @ptrToInt(c_ptr), 0);
+
+    const ptr = realloc(c_ptr, new_size);
+
+    if (ptr) |p| {
+        push(EventKind.alloc, @ptrToInt(p), @intCast(isize, new_size));
+    }
+
+    return ptr;
+}
+
+pub fn testDealloc(c_ptr: *anyopaque, _: u32) callconv(.C) void {
+    push(EventKind.dealloc, @ptrToInt(c_ptr), 0);
+
+    free(c_ptr);
+}
+
+pub fn testLogRefcount(ptr_to_refcount: *isize, delta: isize) callconv(.C) void {
+    push(EventKind.refcount, @ptrToInt(ptr_to_refcount), delta);
+}
+
+// Returns the events so far. The length can be more than the capacity of the log, if some of
+// them didn't fit.
+pub fn testRefcountLog(len: *usize) callconv(.C) [*]const Event {
+    len.* = events_len;
+
+    return &events;
+}
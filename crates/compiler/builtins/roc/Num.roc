@@ -67,6 +67,7 @@ interface Num
         ceiling,
         floor,
         compare,
+        compareTotal,
         pow,
         powInt,
         countLeadingZeroBits,
@@ -556,6 +557,16 @@ bytesToU128 = \bytes, index ->
 
 compare : Num a, Num a -> [LT, EQ, GT]
 
+## Compares two fractional numbers by IEEE 754 `totalOrder`, which puts every value in order,
+## unlike [compare]:
+##
+## * [*NaN*](Num.isNaN) is greater than ∞, and *NaN* with its sign bit set is less than -∞.
+## * `-0.0` is less than `0.0`.
+##
+## This is useful for sorting floats, or using them as keys. Other than those two cases, it's the
+## same as [compare].
+compareTotal : Frac a, Frac a -> [LT, EQ, GT]
+
 ## Returns `Bool.true` if the first number is less than the second.
 ##
 ## `a < b` is shorthand for `Num.isLt a b`.
@@ -168,6 +168,7 @@ map_symbol_to_lowlevel_and_arity! {
     NumLt; NUM_LT; 2,
     NumLte; NUM_LTE; 2,
     NumCompare; NUM_COMPARE; 2,
+    NumCompareTotal; NUM_COMPARE_TOTAL; 2,
     NumDivFrac; NUM_DIV_FRAC; 2,
     NumDivTruncUnchecked; NUM_DIV_TRUNC; 2,
     NumDivCeilUnchecked; NUM_DIV_CEIL; 2,
@@ -139,6 +139,10 @@ flags! {
     /// Print to stderr when a runtime error function is generated.
     ROC_PRINT_RUNTIME_ERROR_GEN
 
+    /// Prints how much the simplification pass the dev and wasm backends run before code
+    /// generation did, for each module.
+    ROC_PRINT_SIMPLIFY_STATS
+
     /// Generate a layout error when an unbound type variable is found, rather than generating the
     /// void layout.
     ROC_NO_UNBOUND_LAYOUT
@@ -148,6 +152,24 @@ flags! {
     /// Prints LLVM function verification output.
     ROC_PRINT_LLVM_FN_VERIFICATION
 
+    // ===Dev Gen===
+
+    /// Prints how many calls to small procs the dev backend inlined, for each module.
+    ROC_PRINT_DEV_INLINE_STATS
+
+    /// Prints how many cold blocks (like branches that crash and overflow checks) the dev backend
+    /// moved after the return of their proc, how many bytes that took out of the hot paths, and
+    /// how big the hot paths are, for each module.
+    ROC_PRINT_DEV_CODE_LAYOUT
+
+    /// Prints each proc's machine code, listed under the mono IR statement it was emitted for.
+    /// Set it to a file path instead of 1 to append the listings to that file.
+    ROC_LOG_DEV_EMIT
+
+    /// Makes the dev backend call the host's `roc_log_refcount(ptr_to_refcount, delta)` before
+    /// every refcount increment and decrement it emits, so the host can check they're balanced.
+    ROC_LOG_DEV_REFCOUNTS
+
     // ===WASM Gen===
 
     /// Writes a `final.wasm` file to /tmp
@@ -0,0 +1,211 @@
+//! Textual listings of generated machine code, for debugging and reviewing the dev backend.
+//!
+//! The code is disassembled when gen_dev is built with the `disassemble` feature, and shown as
+//! hex bytes otherwise.
+use crate::{DataAccess, Relocation};
+use roc_target::Architecture;
+use std::fmt::Write;
+
+/// One line of a listing: an instruction, or up to 8 bytes of a hex dump
+pub(crate) struct Line {
+    /// The offset of its first byte in the code
+    pub offset: usize,
+    pub len: usize,
+    pub text: String,
+}
+
+/// The instructions from `start` to `end` in the code
+#[cfg(feature = "disassemble")]
+pub(crate) fn disassemble(
+    architecture: Architecture,
+    code: &[u8],
+    start: usize,
+    end: usize,
+) -> std::vec::Vec<Line> {
+    use capstone::prelude::*;
+
+    let cs = match architecture {
+        Architecture::X86_64 => Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .syntax(arch::x86::ArchSyntax::Intel)
+            .build(),
+        Architecture::Aarch64 => Capstone::new()
+            .arm64()
+            .mode(arch::arm64::ArchMode::Arm)
+            .build(),
+        _ => return hex_dump(code, start, end),
+    }
+    .expect("Failed to create Capstone object");
+
+    match cs.disasm_all(&code[start..end], start as u64) {
+        Ok(instructions) => instructions
+            .iter()
+            .map(|inst| Line {
+                offset: inst.address() as usize,
+                len: inst.bytes().len(),
+                text: format!(
+                    "{} {}",
+                    inst.mnemonic().unwrap_or("?"),
+                    inst.op_str().unwrap_or("")
+                ),
+            })
+            .collect(),
+        Err(_) => hex_dump(code, start, end),
+    }
+}
+
+#[cfg(not(feature = "disassemble"))]
+pub(crate) fn disassemble(
+    _architecture: Architecture,
+    code: &[u8],
+    start: usize,
+    end: usize,
+) -> std::vec::Vec<Line> {
+    hex_dump(code, start, end)
+}
+
+fn hex_dump(code: &[u8], start: usize, end: usize) -> std::vec::Vec<Line> {
+    code[start..end]
+        .chunks(8)
+        .enumerate()
+        .map(|(index, chunk)| Line {
+            offset: start + index * 8,
+            len: chunk.len(),
+            text: chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<std::vec::Vec<_>>()
+                .join(" "),
+        })
+        .collect()
+}
+
+/// Add a proc's listing to `listing`, labelled with its symbol name. The placeholder bytes that
+/// the linker fills in are marked with what they'll refer to.
+pub(crate) fn write_proc_listing(
+    listing: &mut String,
+    architecture: Architecture,
+    roc_name: &str,
+    symbol_name: &str,
+    code: &[u8],
+    relocs: &[Relocation],
+) {
+    let mut relocs: std::vec::Vec<(u64, String)> = relocs.iter().filter_map(describe).collect();
+    relocs.sort_by_key(|(offset, _)| *offset);
+    let mut relocs = relocs.into_iter().peekable();
+
+    writeln!(listing, "# {}", roc_name).unwrap();
+    writeln!(listing, "{}:", symbol_name).unwrap();
+
+    for line in disassemble(architecture, code, 0, code.len()) {
+        writeln!(listing, "    {:06x}  {}", line.offset, line.text).unwrap();
+
+        let line_end = (line.offset + line.len) as u64;
+        while let Some((_, description)) = relocs.next_if(|(offset, _)| *offset < line_end) {
+            writeln!(listing, "            # relocation: {}", description).unwrap();
+        }
+    }
+
+    for (offset, description) in relocs {
+        writeln!(listing, "    {:06x}  # relocation: {}", offset, description).unwrap();
+    }
+
+    listing.push('\n');
+}
+
+/// The offset of the bytes that a relocation fills in, and what it fills them in with
+fn describe(reloc: &Relocation) -> Option<(u64, String)> {
+    match reloc {
+        Relocation::LocalData {
+            offset,
+            data,
+            access,
+        } => {
+            let how = match access {
+                DataAccess::RipRelative | DataAccess::PageAdd => "address of",
+                DataAccess::PageLoad32 | DataAccess::PageLoad64 => "load from",
+            };
+            Some((*offset, format!("{} {} bytes of data", how, data.len())))
+        }
+        Relocation::LinkedFunction { offset, name } => Some((*offset, format!("call {}", name))),
+        Relocation::LinkedData { offset, name } => {
+            Some((*offset, format!("address of {} from the GOT", name)))
+        }
+        // These are resolved before the proc is finished
+        Relocation::JmpToReturn { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hex_dump, write_proc_listing};
+    use crate::{DataAccess, Relocation};
+    use roc_target::Architecture;
+
+    #[test]
+    fn hex_dump_in_rows_of_8_bytes() {
+        let code: std::vec::Vec<u8> = (0..20).collect();
+        let lines = hex_dump(&code, 2, 12);
+
+        let rows: std::vec::Vec<_> = lines
+            .iter()
+            .map(|line| (line.offset, line.len, line.text.as_str()))
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![(2, 8, "02 03 04 05 06 07 08 09"), (10, 2, "0a 0b")]
+        );
+    }
+
+    #[test]
+    fn listing_marks_relocations_after_their_line() {
+        let code: std::vec::Vec<u8> = (0..10).collect();
+        let relocs = [
+            Relocation::LinkedData {
+                offset: 12,
+                name: "roc_panic".to_string(),
+            },
+            Relocation::LocalData {
+                offset: 9,
+                data: vec![0; 4],
+                access: DataAccess::PageLoad64,
+            },
+            Relocation::JmpToReturn {
+                inst_loc: 4,
+                inst_size: 5,
+                offset: 5,
+            },
+            Relocation::LinkedFunction {
+                offset: 3,
+                name: "roc_alloc".to_string(),
+            },
+        ];
+
+        let mut listing = String::new();
+        // No disassembler for wasm, so the listing is a hex dump even with `disassemble`
+        write_proc_listing(
+            &mut listing,
+            Architecture::Wasm32,
+            "Num.add",
+            "Num_add_1",
+            &code,
+            &relocs,
+        );
+
+        assert_eq!(
+            listing,
+            concat!(
+                "# Num.add\n",
+                "Num_add_1:\n",
+                "    000000  00 01 02 03 04 05 06 07\n",
+                "            # relocation: call roc_alloc\n",
+                "    000008  08 09\n",
+                "            # relocation: load from 4 bytes of data\n",
+                "    00000c  # relocation: address of roc_panic from the GOT\n",
+                "\n",
+            )
+        );
+    }
+}
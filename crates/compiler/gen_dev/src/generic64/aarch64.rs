@@ -1,11 +1,14 @@
 use crate::generic64::{storage::StorageManager, Assembler, CallConv, RegTrait};
-use crate::Relocation;
+use crate::{
+    pointer_layouts, single_register_floats, single_register_int_builtins,
+    single_register_integers, single_register_layouts, unwind::UnwindStep, DataAccess, Relocation,
+};
 use bumpalo::collections::Vec;
 use packed_struct::prelude::*;
-use roc_builtins::bitcode::FloatWidth;
+use roc_builtins::bitcode::{FloatWidth, IntWidth};
 use roc_error_macros::internal_error;
 use roc_module::symbol::Symbol;
-use roc_mono::layout::{InLayout, STLayoutInterner};
+use roc_mono::layout::{Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, UnionLayout};
 
 use super::{CompareOperation, RegisterWidth};
 
@@ -51,6 +54,11 @@ impl RegTrait for AArch64GeneralReg {
     fn value(&self) -> u8 {
         *self as u8
     }
+
+    fn dwarf_number(&self) -> u16 {
+        // Register 31 is always SP in unwind tables
+        *self as u16
+    }
 }
 impl std::fmt::Display for AArch64GeneralReg {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -142,6 +150,10 @@ impl RegTrait for AArch64FloatReg {
     fn value(&self) -> u8 {
         *self as u8
     }
+
+    fn dwarf_number(&self) -> u16 {
+        64 + *self as u16
+    }
 }
 impl std::fmt::Display for AArch64FloatReg {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -203,6 +215,9 @@ pub struct AArch64Call {}
 
 const STACK_ALIGNMENT: u8 = 16;
 
+/// Stack frames larger than this must be probed one page at a time, or we may skip the guard page.
+const STACK_PROBE_SIZE: i32 = 4096;
+
 impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64Call {
     const BASE_PTR_REG: AArch64GeneralReg = AArch64GeneralReg::FP;
     const STACK_PTR_REG: AArch64GeneralReg = AArch64GeneralReg::ZRSP;
@@ -255,7 +270,7 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         AArch64GeneralReg::X13,
         AArch64GeneralReg::X14,
         AArch64GeneralReg::X15,
-        AArch64GeneralReg::IP0,
+        // Don't use IP0: it's scratch for stack probes and for reaching data with ADRP.
         AArch64GeneralReg::IP1,
     ];
     const FLOAT_PARAM_REGS: &'static [AArch64FloatReg] = &[];
@@ -288,6 +303,7 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
     #[inline(always)]
     fn setup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         saved_general_regs: &[AArch64GeneralReg],
         saved_float_regs: &[AArch64FloatReg],
         requested_stack_size: i32,
@@ -313,24 +329,53 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         };
         if let Some(aligned_stack_size) = full_stack_size.checked_add(offset as i32) {
             if aligned_stack_size > 0 {
-                AArch64Assembler::mov_reg64_reg64(
-                    buf,
-                    AArch64GeneralReg::FP,
-                    AArch64GeneralReg::ZRSP,
-                );
-                AArch64Assembler::sub_reg64_reg64_imm32(
-                    buf,
-                    AArch64GeneralReg::ZRSP,
-                    AArch64GeneralReg::ZRSP,
-                    aligned_stack_size,
-                );
+                if aligned_stack_size > STACK_PROBE_SIZE {
+                    aarch64_probe_stack(buf, aligned_stack_size);
+                } else {
+                    AArch64Assembler::sub_reg64_reg64_imm32(
+                        buf,
+                        AArch64GeneralReg::ZRSP,
+                        AArch64GeneralReg::ZRSP,
+                        aligned_stack_size,
+                    );
+                }
+                unwind.push(UnwindStep::Cfa {
+                    code_offset: buf.len() as u32,
+                    register: AArch64GeneralReg::ZRSP.dwarf_number(),
+                    offset: aligned_stack_size as u32,
+                });
 
+                // Save the caller's frame pointer before pointing FP at this frame.
                 // All the following stores could be optimized by using `STP` to store pairs.
                 let mut offset = aligned_stack_size;
                 offset -= 8;
                 AArch64Assembler::mov_stack32_reg64(buf, offset, AArch64GeneralReg::LR);
+                unwind.push(UnwindStep::Saved {
+                    code_offset: buf.len() as u32,
+                    register: AArch64GeneralReg::LR.dwarf_number(),
+                    cfa_offset: -8,
+                });
                 offset -= 8;
                 AArch64Assembler::mov_stack32_reg64(buf, offset, AArch64GeneralReg::FP);
+                unwind.push(UnwindStep::Saved {
+                    code_offset: buf.len() as u32,
+                    register: AArch64GeneralReg::FP.dwarf_number(),
+                    cfa_offset: -16,
+                });
+
+                // FP gets the stack pointer from before the call. This can't use `MOV FP, SP`,
+                // because that is an ORR, and register 31 is the zero register for ORR.
+                AArch64Assembler::add_reg64_reg64_imm32(
+                    buf,
+                    AArch64GeneralReg::FP,
+                    AArch64GeneralReg::ZRSP,
+                    aligned_stack_size,
+                );
+                unwind.push(UnwindStep::Cfa {
+                    code_offset: buf.len() as u32,
+                    register: AArch64GeneralReg::FP.dwarf_number(),
+                    offset: 0,
+                });
 
                 offset = aligned_stack_size - fn_call_stack_size;
                 for reg in saved_general_regs {
@@ -353,20 +398,15 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
     #[inline(always)]
     fn cleanup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         saved_general_regs: &[AArch64GeneralReg],
         saved_float_regs: &[AArch64FloatReg],
         aligned_stack_size: i32,
         fn_call_stack_size: i32,
     ) {
         if aligned_stack_size > 0 {
-            // All the following stores could be optimized by using `STP` to store pairs.
-            let mut offset = aligned_stack_size;
-            offset -= 8;
-            AArch64Assembler::mov_reg64_stack32(buf, AArch64GeneralReg::LR, offset);
-            offset -= 8;
-            AArch64Assembler::mov_reg64_stack32(buf, AArch64GeneralReg::FP, offset);
-
-            offset = aligned_stack_size - fn_call_stack_size;
+            // The saved registers are found from FP, so they have to be restored before it is.
+            let mut offset = aligned_stack_size - fn_call_stack_size;
             for reg in saved_general_regs {
                 offset -= 8;
                 AArch64Assembler::mov_reg64_base32(buf, *reg, offset);
@@ -375,19 +415,47 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
                 offset -= 8;
                 AArch64Assembler::mov_freg64_base32(buf, *reg, offset);
             }
+
+            // FP is about to be restored, so find the frame from SP instead
+            unwind.push(UnwindStep::Cfa {
+                code_offset: buf.len() as u32,
+                register: AArch64GeneralReg::ZRSP.dwarf_number(),
+                offset: aligned_stack_size as u32,
+            });
+
+            // All the following loads could be optimized by using `LDP` to load pairs.
+            offset = aligned_stack_size;
+            offset -= 8;
+            AArch64Assembler::mov_reg64_stack32(buf, AArch64GeneralReg::LR, offset);
+            unwind.push(UnwindStep::Restored {
+                code_offset: buf.len() as u32,
+                register: AArch64GeneralReg::LR.dwarf_number(),
+            });
+            offset -= 8;
+            AArch64Assembler::mov_reg64_stack32(buf, AArch64GeneralReg::FP, offset);
+            unwind.push(UnwindStep::Restored {
+                code_offset: buf.len() as u32,
+                register: AArch64GeneralReg::FP.dwarf_number(),
+            });
+
             AArch64Assembler::add_reg64_reg64_imm32(
                 buf,
                 AArch64GeneralReg::ZRSP,
                 AArch64GeneralReg::ZRSP,
                 aligned_stack_size,
             );
+            unwind.push(UnwindStep::Cfa {
+                code_offset: buf.len() as u32,
+                register: AArch64GeneralReg::ZRSP.dwarf_number(),
+                offset: 0,
+            });
         }
     }
 
     #[inline(always)]
     fn load_args<'a>(
-        _buf: &mut Vec<'a, u8>,
-        _storage_manager: &mut StorageManager<
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<
             'a,
             '_,
             AArch64GeneralReg,
@@ -395,17 +463,33 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
             AArch64Assembler,
             AArch64Call,
         >,
-        _layout_interner: &mut STLayoutInterner<'a>,
-        _args: &'a [(InLayout<'a>, Symbol)],
-        _ret_layout: &InLayout<'a>,
+        layout_interner: &mut STLayoutInterner<'a>,
+        args: &'a [(InLayout<'a>, Symbol)],
+        ret_layout: &InLayout<'a>,
     ) {
-        todo!("Loading args for AArch64");
+        if Self::returns_via_arg_pointer(layout_interner, ret_layout) {
+            storage_manager.ret_pointer_arg(AArch64GeneralReg::XR);
+        }
+
+        // FP is the stack pointer from before the call, so stack arguments start right at it.
+        let mut locations = AArch64ArgLocations::default();
+
+        for (in_layout, sym) in args.iter() {
+            load_arg(
+                buf,
+                storage_manager,
+                layout_interner,
+                &mut locations,
+                *sym,
+                *in_layout,
+            );
+        }
     }
 
     #[inline(always)]
     fn store_args<'a>(
-        _buf: &mut Vec<'a, u8>,
-        _storage_manager: &mut StorageManager<
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<
             'a,
             '_,
             AArch64GeneralReg,
@@ -413,18 +497,37 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
             AArch64Assembler,
             AArch64Call,
         >,
-        _layout_interner: &mut STLayoutInterner<'a>,
-        _dst: &Symbol,
-        _args: &[Symbol],
-        _arg_layouts: &[InLayout<'a>],
-        _ret_layout: &InLayout<'a>,
+        layout_interner: &mut STLayoutInterner<'a>,
+        dst: &Symbol,
+        args: &[Symbol],
+        arg_layouts: &[InLayout<'a>],
+        ret_layout: &InLayout<'a>,
     ) {
-        todo!("Storing args for AArch64");
+        let mut state = AArch64CallStoreArgs {
+            locations: AArch64ArgLocations::default(),
+            references: std::vec::Vec::new(),
+        };
+
+        for (sym, in_layout) in args.iter().zip(arg_layouts.iter()) {
+            state.store_arg(buf, storage_manager, layout_interner, *sym, *in_layout);
+        }
+
+        let stack_size = state.store_references(buf, storage_manager);
+        storage_manager.update_fn_call_stack_size(stack_size as u32);
+
+        if Self::returns_via_arg_pointer(layout_interner, ret_layout) {
+            // Save space on the stack for the value that will be returned.
+            // The callee may rely on its alignment, e.g. zig's parse results with an I128 in them.
+            let (size, alignment) = layout_interner.stack_size_and_alignment(*ret_layout);
+            let base_offset = storage_manager.claim_stack_area_with_alignment(dst, size, alignment);
+            // Its address goes in XR rather than an argument register.
+            base_offset_address(buf, AArch64GeneralReg::XR, base_offset);
+        }
     }
 
     fn return_complex_symbol<'a>(
-        _buf: &mut Vec<'a, u8>,
-        _storage_manager: &mut StorageManager<
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<
             'a,
             '_,
             AArch64GeneralReg,
@@ -432,16 +535,36 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
             AArch64Assembler,
             AArch64Call,
         >,
-        _layout_interner: &mut STLayoutInterner<'a>,
-        _sym: &Symbol,
-        _layout: &InLayout<'a>,
+        layout_interner: &mut STLayoutInterner<'a>,
+        sym: &Symbol,
+        layout: &InLayout<'a>,
     ) {
-        todo!("Returning complex symbols for AArch64");
+        match *layout {
+            single_register_layouts!() => {
+                internal_error!("single register layouts are not complex symbols");
+            }
+            x if layout_interner.stack_size(x) == 0 => {}
+            x if !Self::returns_via_arg_pointer(layout_interner, &x) => {
+                // TODO: structs of only floats are returned in float registers.
+                let (base_offset, size) = storage_manager.stack_offset_and_size(sym);
+                debug_assert_eq!(base_offset % 8, 0);
+                for (i, reg) in Self::GENERAL_RETURN_REGS[..register_count(size)]
+                    .iter()
+                    .enumerate()
+                {
+                    AArch64Assembler::mov_reg64_base32(buf, *reg, base_offset + 8 * i as i32);
+                }
+            }
+            _ => {
+                // This is a large type returned via the arg pointer.
+                storage_manager.copy_symbol_to_arg_pointer(buf, sym, layout);
+            }
+        }
     }
 
     fn load_returned_complex_symbol<'a>(
-        _buf: &mut Vec<'a, u8>,
-        _storage_manager: &mut StorageManager<
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<
             'a,
             '_,
             AArch64GeneralReg,
@@ -449,11 +572,394 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
             AArch64Assembler,
             AArch64Call,
         >,
-        _layout_interner: &mut STLayoutInterner<'a>,
-        _sym: &Symbol,
-        _layout: &InLayout<'a>,
+        layout_interner: &mut STLayoutInterner<'a>,
+        sym: &Symbol,
+        layout: &InLayout<'a>,
     ) {
-        todo!("Loading returned complex symbols for AArch64");
+        match *layout {
+            single_register_layouts!() => {
+                internal_error!("single register layouts are not complex symbols");
+            }
+            x if layout_interner.stack_size(x) == 0 => {}
+            x if !Self::returns_via_arg_pointer(layout_interner, &x) => {
+                let size = layout_interner.stack_size(x);
+                let offset = storage_manager.claim_stack_area(sym, size);
+                for (i, reg) in Self::GENERAL_RETURN_REGS[..register_count(size)]
+                    .iter()
+                    .enumerate()
+                {
+                    AArch64Assembler::mov_base32_reg64(buf, offset + 8 * i as i32, *reg);
+                }
+            }
+            _ => {
+                // This should have been recieved via an arg pointer.
+                // That means the value is already loaded onto the stack area we allocated before the call.
+                // Nothing to do.
+            }
+        }
+    }
+}
+
+impl AArch64Call {
+    fn returns_via_arg_pointer<'a>(
+        interner: &STLayoutInterner<'a>,
+        ret_layout: &InLayout<'a>,
+    ) -> bool {
+        // Anything up to 16 bytes comes back in X0 and X1, like an argument would be passed.
+        interner.stack_size(*ret_layout) > 16
+    }
+}
+
+type AArch64StorageManager<'a, 'r> =
+    StorageManager<'a, 'r, AArch64GeneralReg, AArch64FloatReg, AArch64Assembler, AArch64Call>;
+
+/// How many 64-bit registers a value of `size` bytes takes up
+fn register_count(size: u32) -> usize {
+    (size as usize + 7) / 8
+}
+
+/// Points `dst` at a base offset, which is below FP for the values in this function's frame.
+fn base_offset_address(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, offset: i32) {
+    if offset < 0 {
+        AArch64Assembler::sub_reg64_reg64_imm32(buf, dst, AArch64GeneralReg::FP, -offset);
+    } else {
+        AArch64Assembler::add_reg64_reg64_imm32(buf, dst, AArch64GeneralReg::FP, offset);
+    }
+}
+
+/// Where an argument is passed: in consecutive registers, or at an offset into the stack arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArgLocation {
+    Regs(&'static [AArch64GeneralReg]),
+    Stack(i32),
+}
+
+/// Hands out registers and stack slots to arguments in order, following the rules for general
+/// registers in section 6.8.2 of https://github.com/ARM-software/abi-aa/blob/main/aapcs64/aapcs64.rst
+/// The caller and callee both go through this, so they agree on where every argument is.
+#[derive(Debug, Default)]
+struct AArch64ArgLocations {
+    general_i: usize,
+    stack_offset: i32,
+}
+
+impl AArch64ArgLocations {
+    const GENERAL_PARAM_REGS: &'static [AArch64GeneralReg] = AArch64Call::GENERAL_PARAM_REGS;
+
+    /// A 64-bit integer or pointer
+    fn general(&mut self) -> ArgLocation {
+        self.composite(8, 8)
+    }
+
+    /// A 128-bit integer, or a struct or union of at most 16 bytes. One that's 16-byte aligned
+    /// starts at an even register. If it doesn't fit in the registers that are left, it goes on
+    /// the stack, and so does every argument after it.
+    fn composite(&mut self, size: u32, alignment: u32) -> ArgLocation {
+        let count = register_count(size);
+
+        if alignment == 16 {
+            self.general_i += self.general_i % 2;
+        }
+
+        if self.general_i + count <= Self::GENERAL_PARAM_REGS.len() {
+            let regs = &Self::GENERAL_PARAM_REGS[self.general_i..self.general_i + count];
+            self.general_i += count;
+            ArgLocation::Regs(regs)
+        } else {
+            self.general_i = Self::GENERAL_PARAM_REGS.len();
+            if alignment == 16 {
+                self.stack_offset = (self.stack_offset + 15) & !15;
+            }
+            let offset = self.stack_offset;
+            self.stack_offset += 8 * count as i32;
+            ArgLocation::Stack(offset)
+        }
+    }
+}
+
+struct AArch64CallStoreArgs {
+    locations: AArch64ArgLocations,
+    /// Arguments bigger than 16 bytes, with where the pointer to each one's copy goes. The copies
+    /// go after the stack arguments, so they're made once all of those are placed.
+    references: std::vec::Vec<(Symbol, u32, ArgLocation)>,
+}
+
+impl AArch64CallStoreArgs {
+    // IP0 is free to use between loading arguments, since nothing else is live in it.
+    const TMP_REG: AArch64GeneralReg = AArch64GeneralReg::IP0;
+
+    fn store_arg<'a>(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut AArch64StorageManager<'a, '_>,
+        layout_interner: &mut STLayoutInterner<'a>,
+        sym: Symbol,
+        in_layout: InLayout<'a>,
+    ) {
+        match in_layout {
+            single_register_integers!() => self.store_arg_general(buf, storage_manager, sym),
+            single_register_floats!() => internal_error!(
+                "AArch64 doesn't pass floats in V registers yet, so {:?} can't be an argument",
+                layout_interner.dbg(in_layout)
+            ),
+            x if layout_interner.stack_size(x) == 0 => {}
+            x if layout_interner.stack_size(x) > 16 => {
+                // The callee gets a pointer to a copy, in place of the value.
+                let location = self.locations.general();
+                self.references
+                    .push((sym, layout_interner.stack_size(x), location));
+            }
+            other => match layout_interner.get(other) {
+                pointer_layouts!() => self.store_arg_general(buf, storage_manager, sym),
+                Layout::LambdaSet(lambda_set) => self.store_arg(
+                    buf,
+                    storage_manager,
+                    layout_interner,
+                    sym,
+                    lambda_set.runtime_representation(),
+                ),
+                // TODO: structs of only floats are passed in float registers.
+                Layout::Builtin(Builtin::Int(IntWidth::I128 | IntWidth::U128))
+                | Layout::Builtin(Builtin::Decimal)
+                | Layout::Struct { .. }
+                | Layout::Union(UnionLayout::NonRecursive(_)) => {
+                    let (size, alignment) = layout_interner.stack_size_and_alignment(other);
+                    let (base_offset, _) = storage_manager.stack_offset_and_size(&sym);
+                    debug_assert_eq!(base_offset % 8, 0);
+
+                    match self.locations.composite(size, alignment) {
+                        ArgLocation::Regs(regs) => {
+                            for (i, reg) in regs.iter().enumerate() {
+                                AArch64Assembler::mov_reg64_base32(
+                                    buf,
+                                    *reg,
+                                    base_offset + 8 * i as i32,
+                                );
+                            }
+                        }
+                        ArgLocation::Stack(offset) => {
+                            Self::copy_to_stack(buf, base_offset, offset, size);
+                        }
+                    }
+                }
+                _ => {
+                    todo!("calling with arg type, {:?}", layout_interner.dbg(other));
+                }
+            },
+        }
+    }
+
+    fn store_arg_general<'a>(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut AArch64StorageManager<'a, '_>,
+        sym: Symbol,
+    ) {
+        match self.locations.general() {
+            ArgLocation::Regs(regs) => {
+                storage_manager.load_to_specified_general_reg(buf, &sym, regs[0]);
+            }
+            ArgLocation::Stack(offset) => {
+                storage_manager.load_to_specified_general_reg(buf, &sym, Self::TMP_REG);
+                AArch64Assembler::mov_stack32_reg64(buf, offset, Self::TMP_REG);
+            }
+        }
+    }
+
+    /// Copies the arguments that are passed by reference to the end of the stack arguments, and
+    /// passes pointers to them. Returns how much stack all the arguments need.
+    fn store_references<'a>(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut AArch64StorageManager<'a, '_>,
+    ) -> i32 {
+        let mut copy_offset = self.locations.stack_offset;
+
+        for (sym, size, location) in self.references.drain(..) {
+            // The copy may hold an I128, so keep it 16-byte aligned like the stack is.
+            copy_offset = (copy_offset + 15) & !15;
+
+            let (base_offset, _) = storage_manager.stack_offset_and_size(&sym);
+            debug_assert_eq!(base_offset % 8, 0);
+            Self::copy_to_stack(buf, base_offset, copy_offset, size);
+
+            match location {
+                ArgLocation::Regs(regs) => {
+                    AArch64Assembler::add_reg64_reg64_imm32(
+                        buf,
+                        regs[0],
+                        AArch64GeneralReg::ZRSP,
+                        copy_offset,
+                    );
+                }
+                ArgLocation::Stack(offset) => {
+                    AArch64Assembler::add_reg64_reg64_imm32(
+                        buf,
+                        Self::TMP_REG,
+                        AArch64GeneralReg::ZRSP,
+                        copy_offset,
+                    );
+                    AArch64Assembler::mov_stack32_reg64(buf, offset, Self::TMP_REG);
+                }
+            }
+
+            copy_offset += 8 * register_count(size) as i32;
+        }
+
+        copy_offset
+    }
+
+    fn copy_to_stack(buf: &mut Vec<'_, u8>, base_offset: i32, stack_offset: i32, size: u32) {
+        for i in (0..size as i32).step_by(8) {
+            AArch64Assembler::mov_reg64_base32(buf, Self::TMP_REG, base_offset + i);
+            AArch64Assembler::mov_stack32_reg64(buf, stack_offset + i, Self::TMP_REG);
+        }
+    }
+}
+
+fn load_arg<'a>(
+    buf: &mut Vec<'a, u8>,
+    storage_manager: &mut AArch64StorageManager<'a, '_>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    locations: &mut AArch64ArgLocations,
+    sym: Symbol,
+    in_layout: InLayout<'a>,
+) {
+    let stack_size = layout_interner.stack_size(in_layout);
+    match in_layout {
+        single_register_integers!() => load_arg_general(storage_manager, locations, sym),
+        single_register_floats!() => internal_error!(
+            "AArch64 doesn't pass floats in V registers yet, so {:?} can't be an argument",
+            layout_interner.dbg(in_layout)
+        ),
+        _ if stack_size == 0 => {
+            storage_manager.no_data(&sym);
+        }
+        _ if stack_size > 16 => {
+            // We got a pointer to a copy that's ours to keep. Values are found by their offset
+            // from FP though, so it's copied again into this frame.
+            let (pointer, data) = (AArch64GeneralReg::IP1, AArch64GeneralReg::IP0);
+            match locations.general() {
+                ArgLocation::Regs(regs) => {
+                    AArch64Assembler::mov_reg64_reg64(buf, pointer, regs[0]);
+                }
+                ArgLocation::Stack(offset) => {
+                    AArch64Assembler::mov_reg64_base32(buf, pointer, offset);
+                }
+            }
+
+            let base_offset = storage_manager.claim_stack_area(&sym, stack_size);
+            for i in (0..stack_size as i32).step_by(8) {
+                AArch64Assembler::mov_reg64_mem64_offset32(buf, data, pointer, i);
+                AArch64Assembler::mov_base32_reg64(buf, base_offset + i, data);
+            }
+        }
+        other => match layout_interner.get(other) {
+            pointer_layouts!() => {
+                // boxes and recursive unions are pointers, which we treat as 64-bit integers
+                load_arg_general(storage_manager, locations, sym)
+            }
+            Layout::LambdaSet(lambda_set) => load_arg(
+                buf,
+                storage_manager,
+                layout_interner,
+                locations,
+                sym,
+                lambda_set.runtime_representation(),
+            ),
+            Layout::Builtin(Builtin::Int(IntWidth::I128 | IntWidth::U128))
+            | Layout::Builtin(Builtin::Decimal)
+            | Layout::Struct { .. }
+            | Layout::Union(UnionLayout::NonRecursive(_)) => {
+                let alignment = layout_interner.alignment_bytes(other);
+                match locations.composite(stack_size, alignment) {
+                    ArgLocation::Regs(regs) => {
+                        // Complex values live on the stack, so spill the registers to it.
+                        let base_offset = storage_manager.claim_stack_area(&sym, stack_size);
+                        for (i, reg) in regs.iter().enumerate() {
+                            AArch64Assembler::mov_base32_reg64(
+                                buf,
+                                base_offset + 8 * i as i32,
+                                *reg,
+                            );
+                        }
+                    }
+                    ArgLocation::Stack(offset) => {
+                        storage_manager.complex_stack_arg(&sym, offset, stack_size);
+                    }
+                }
+            }
+            _ => {
+                todo!("Loading args with layout {:?}", layout_interner.dbg(other));
+            }
+        },
+    }
+}
+
+fn load_arg_general(
+    storage_manager: &mut AArch64StorageManager<'_, '_>,
+    locations: &mut AArch64ArgLocations,
+    sym: Symbol,
+) {
+    match locations.general() {
+        ArgLocation::Regs(regs) => storage_manager.general_reg_arg(&sym, regs[0]),
+        ArgLocation::Stack(offset) => storage_manager.primitive_stack_arg(&sym, offset),
+    }
+}
+
+/// Reach data in the data section, which could be anywhere within 4GiB of the code.
+/// An ADRP puts the data's 4KiB page in IP0, and `access_page` emits the instruction that uses
+/// IP0 with the offset in the page, which is patched in along with the ADRP when linking.
+fn load_local_data<F>(
+    buf: &mut Vec<'_, u8>,
+    relocs: &mut Vec<'_, Relocation>,
+    data: std::vec::Vec<u8>,
+    access: DataAccess,
+    access_page: F,
+) where
+    F: FnOnce(&mut Vec<'_, u8>, AArch64GeneralReg),
+{
+    let offset = buf.len() as u64;
+    adrp_reg64_imm21(buf, AArch64GeneralReg::IP0, 0);
+    access_page(buf, AArch64GeneralReg::IP0);
+    relocs.push(Relocation::LocalData {
+        offset,
+        data,
+        access,
+    });
+}
+
+/// Allocate a large stack frame, writing to each page as we go.
+/// The OS only maps more stack when we touch the guard page at the end of it,
+/// so moving SP down by more than a page at once could step right over it.
+fn aarch64_probe_stack(buf: &mut Vec<'_, u8>, stack_size: i32) {
+    // IP0 is reserved for scratch use in function prologues, veneers, and data access.
+    let counter = AArch64GeneralReg::IP0;
+    let pages = stack_size / STACK_PROBE_SIZE;
+    let remainder = stack_size % STACK_PROBE_SIZE;
+
+    AArch64Assembler::mov_reg64_imm64(buf, counter, pages as i64);
+
+    let loop_start = buf.len();
+    sub_reg64_reg64_imm12_lsl12(
+        buf,
+        AArch64GeneralReg::ZRSP,
+        AArch64GeneralReg::ZRSP,
+        (STACK_PROBE_SIZE >> 12) as u16,
+    );
+    // Register 31 is XZR when it's the value being stored, so this is `STR XZR, [SP]`.
+    str_reg64_reg64_imm12(buf, AArch64GeneralReg::ZRSP, AArch64GeneralReg::ZRSP, 0);
+    subs_reg64_reg64_imm12(buf, counter, counter, 1);
+    // AArch64 branch offsets are relative to the branch instruction itself
+    b_cond_imm19(buf, ConditionCode::NE, loop_start as i32 - buf.len() as i32);
+
+    if remainder > 0 {
+        AArch64Assembler::sub_reg64_reg64_imm32(
+            buf,
+            AArch64GeneralReg::ZRSP,
+            AArch64GeneralReg::ZRSP,
+            remainder,
+        );
     }
 }
 
@@ -485,6 +991,11 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
             todo!("immediate addition with values less than 0");
         } else if imm32 < 0xFFF {
             add_reg64_reg64_imm12(buf, dst, src, imm32 as u16);
+        } else if imm32 < (1 << 24) {
+            add_reg64_reg64_imm12_lsl12(buf, dst, src, (imm32 >> 12) as u16);
+            if imm32 & 0xFFF != 0 {
+                add_reg64_reg64_imm12(buf, dst, dst, (imm32 & 0xFFF) as u16);
+            }
         } else {
             todo!("immediate additions with values greater than 12bits");
         }
@@ -522,6 +1033,57 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("calling functions literal for AArch64");
     }
 
+    fn copy_base32_to_mem_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        dst_offset: i32,
+        src_offset: i32,
+        size: u32,
+    ) {
+        use AArch64GeneralReg::*;
+
+        debug_assert_eq!(size % 8, 0);
+        debug_assert_ne!(dst, ZRSP);
+
+        // Save the registers the loop uses. The stack pointer must stay 16-byte aligned.
+        const SAVED_REGS: [AArch64GeneralReg; 4] = [X0, X1, X2, X3];
+        sub_reg64_reg64_imm12(buf, ZRSP, ZRSP, 32);
+        for (index, reg) in SAVED_REGS.iter().enumerate() {
+            str_reg64_reg64_imm12(buf, *reg, ZRSP, index as u16);
+        }
+
+        // dst may be one of those registers, so read it before any of them are overwritten.
+        fn add_offset(
+            buf: &mut Vec<'_, u8>,
+            dst: AArch64GeneralReg,
+            base: AArch64GeneralReg,
+            offset: i32,
+        ) {
+            if offset < 0 {
+                AArch64Assembler::sub_reg64_reg64_imm32(buf, dst, base, -offset);
+            } else {
+                AArch64Assembler::add_reg64_reg64_imm32(buf, dst, base, offset);
+            }
+        }
+        add_offset(buf, X1, dst, dst_offset);
+        add_offset(buf, X0, FP, src_offset);
+        Self::mov_reg64_imm64(buf, X2, (size / 8) as i64);
+
+        // Copy 8 bytes at a time, counting X2 down to zero.
+        let loop_start = buf.len();
+        ldr_reg64_reg64_imm12(buf, X3, X0, 0);
+        str_reg64_reg64_imm12(buf, X3, X1, 0);
+        add_reg64_reg64_imm12(buf, X0, X0, 8);
+        add_reg64_reg64_imm12(buf, X1, X1, 8);
+        subs_reg64_reg64_imm12(buf, X2, X2, 1);
+        b_cond_imm19(buf, ConditionCode::NE, loop_start as i32 - buf.len() as i32);
+
+        for (index, reg) in SAVED_REGS.iter().enumerate() {
+            ldr_reg64_reg64_imm12(buf, *reg, ZRSP, index as u16);
+        }
+        add_reg64_reg64_imm12(buf, ZRSP, ZRSP, 32);
+    }
+
     #[inline(always)]
     fn function_pointer(
         _buf: &mut Vec<'_, u8>,
@@ -661,10 +1223,35 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         buf.len()
     }
 
+    #[inline(always)]
+    fn je_reg64_imm64_imm32(
+        buf: &mut Vec<'_, u8>,
+        reg: AArch64GeneralReg,
+        imm: u64,
+        offset: i32,
+    ) -> usize {
+        if imm < (1 << 12) {
+            cmp_reg64_imm12(buf, reg, imm as u16);
+        } else {
+            todo!(
+                "cmp immediate with value over 12 bits for AArch64: {:#x}",
+                imm
+            );
+        }
+
+        if (-(1 << 20)..(1 << 20)).contains(&offset) {
+            b_cond_imm19(buf, ConditionCode::EQ, offset);
+        } else {
+            todo!("jump offsets over 20 bits for AArch64: {:#x}", offset);
+        }
+
+        buf.len()
+    }
+
     #[inline(always)]
     fn mov_freg32_imm32(
         buf: &mut Vec<'_, u8>,
-        _relocs: &mut Vec<'_, Relocation>,
+        relocs: &mut Vec<'_, Relocation>,
         dst: AArch64FloatReg,
         imm: f32,
     ) {
@@ -678,14 +1265,20 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
                 fmov_freg_imm8(buf, FloatWidth::F32, dst, imm8);
             }
             None => {
-                todo!("loading f32 literal over 8 bits for AArch64");
+                load_local_data(
+                    buf,
+                    relocs,
+                    imm.to_le_bytes().to_vec(),
+                    DataAccess::PageLoad32,
+                    |buf, page| ldr_freg_reg64_imm12(buf, FloatWidth::F32, dst, page, 0),
+                );
             }
         }
     }
     #[inline(always)]
     fn mov_freg64_imm64(
         buf: &mut Vec<'_, u8>,
-        _relocs: &mut Vec<'_, Relocation>,
+        relocs: &mut Vec<'_, Relocation>,
         dst: AArch64FloatReg,
         imm: f64,
     ) {
@@ -699,7 +1292,13 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
                 fmov_freg_imm8(buf, FloatWidth::F64, dst, imm8);
             }
             None => {
-                todo!("loading f64 literal over 8 bits for AArch64");
+                load_local_data(
+                    buf,
+                    relocs,
+                    imm.to_le_bytes().to_vec(),
+                    DataAccess::PageLoad64,
+                    |buf, page| ldr_freg_reg64_imm12(buf, FloatWidth::F64, dst, page, 0),
+                );
             }
         }
     }
@@ -728,12 +1327,24 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
     fn mov_reg64_reg64(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, src: AArch64GeneralReg) {
         mov_reg64_reg64(buf, dst, src);
     }
+    #[inline(always)]
+    fn mov_reg64_freg64(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, src: AArch64FloatReg) {
+        fmov_reg_freg(buf, FloatWidth::F64, dst, src);
+    }
+    #[inline(always)]
+    fn mov_reg32_freg32(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, src: AArch64FloatReg) {
+        fmov_reg_freg(buf, FloatWidth::F32, dst, src);
+    }
 
     #[inline(always)]
     fn mov_freg64_base32(_buf: &mut Vec<'_, u8>, _dst: AArch64FloatReg, _offset: i32) {
         todo!("loading floating point reg from base offset for AArch64");
     }
     #[inline(always)]
+    fn mov_freg32_base32(_buf: &mut Vec<'_, u8>, _dst: AArch64FloatReg, _offset: i32) {
+        todo!("loading floating point reg from base offset for AArch64");
+    }
+    #[inline(always)]
     fn mov_reg64_base32(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, offset: i32) {
         if offset < 0 {
             todo!("negative base offsets for AArch64");
@@ -761,6 +1372,10 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("saving floating point reg to base offset for AArch64");
     }
     #[inline(always)]
+    fn mov_base32_freg32(_buf: &mut Vec<'_, u8>, _offset: i32, _src: AArch64FloatReg) {
+        todo!("saving floating point reg to base offset for AArch64");
+    }
+    #[inline(always)]
     fn movesd_mem64_offset32_freg64(
         _buf: &mut Vec<'_, u8>,
         _ptr: AArch64GeneralReg,
@@ -769,6 +1384,47 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
     ) {
         todo!()
     }
+    #[inline(always)]
+    fn mov_mem32_offset32_freg32(
+        _buf: &mut Vec<'_, u8>,
+        _ptr: AArch64GeneralReg,
+        _offset: i32,
+        _src: AArch64FloatReg,
+    ) {
+        todo!()
+    }
+    #[inline(always)]
+    fn mov_freg64_mem64_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64FloatReg,
+        ptr: AArch64GeneralReg,
+        offset: i32,
+    ) {
+        if offset < 0 {
+            todo!("negative mem offsets for AArch64");
+        } else if offset < (0xFFF << 8) {
+            debug_assert!(offset % 8 == 0);
+            ldr_freg_reg64_imm12(buf, FloatWidth::F64, dst, ptr, (offset as u16) >> 3);
+        } else {
+            todo!("mem offsets over 32k for AArch64");
+        }
+    }
+    #[inline(always)]
+    fn mov_freg32_mem32_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64FloatReg,
+        ptr: AArch64GeneralReg,
+        offset: i32,
+    ) {
+        if offset < 0 {
+            todo!("negative mem offsets for AArch64");
+        } else if offset < (0xFFF << 8) {
+            debug_assert!(offset % 4 == 0);
+            ldr_freg_reg64_imm12(buf, FloatWidth::F32, dst, ptr, (offset as u16) >> 2);
+        } else {
+            todo!("mem offsets over 32k for AArch64");
+        }
+    }
 
     #[inline(always)]
     fn mov_base32_reg64(buf: &mut Vec<'_, u8>, offset: i32, src: AArch64GeneralReg) {
@@ -924,6 +1580,31 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         }
     }
 
+    #[inline(always)]
+    fn movsx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        register_width: RegisterWidth,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+    ) {
+        match register_width {
+            RegisterWidth::W64 => mov_reg64_reg64(buf, dst, src),
+            _ => sbfm_reg64_reg64(buf, dst, src, 0, register_width_bits(register_width) - 1),
+        }
+    }
+    #[inline(always)]
+    fn movzx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        register_width: RegisterWidth,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+    ) {
+        match register_width {
+            RegisterWidth::W64 => mov_reg64_reg64(buf, dst, src),
+            _ => ubfm_reg64_reg64(buf, dst, src, 0, register_width_bits(register_width) - 1),
+        }
+    }
+
     #[inline(always)]
     fn mov_freg64_stack32(_buf: &mut Vec<'_, u8>, _dst: AArch64FloatReg, _offset: i32) {
         todo!("loading floating point reg from stack for AArch64");
@@ -959,6 +1640,38 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         neg_reg64_reg64(buf, dst, src);
     }
 
+    #[inline(always)]
+    fn lzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, src: AArch64GeneralReg) {
+        clz_reg64_reg64(buf, dst, src);
+    }
+
+    #[inline(always)]
+    fn tzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, src: AArch64GeneralReg) {
+        // The trailing zeros are the leading zeros once the bits are reversed
+        rbit_reg64_reg64(buf, dst, src);
+        clz_reg64_reg64(buf, dst, dst);
+    }
+
+    #[inline(always)]
+    fn popcnt_reg64_reg64<'a, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<'a, '_, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+    ) where
+        ASM: Assembler<AArch64GeneralReg, AArch64FloatReg>,
+        CC: CallConv<AArch64GeneralReg, AArch64FloatReg, ASM>,
+    {
+        // There's no general purpose register version of CNT, so count the bits of each byte in a
+        // SIMD register and add those up.
+        storage_manager.with_tmp_float_reg(buf, |_storage_manager, buf, tmp_reg| {
+            fmov_freg_reg64(buf, tmp_reg, src);
+            cnt_freg_freg_8b(buf, tmp_reg, tmp_reg);
+            addv_freg_freg_8b(buf, tmp_reg, tmp_reg);
+            fmov_reg_freg(buf, FloatWidth::F64, dst, tmp_reg);
+        });
+    }
+
     #[inline(always)]
     fn sub_reg64_reg64_imm32(
         buf: &mut Vec<'_, u8>,
@@ -970,6 +1683,11 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
             todo!("immediate subtractions with values less than 0");
         } else if imm32 < 0xFFF {
             sub_reg64_reg64_imm12(buf, dst, src, imm32 as u16);
+        } else if imm32 < (1 << 24) {
+            sub_reg64_reg64_imm12_lsl12(buf, dst, src, (imm32 >> 12) as u16);
+            if imm32 & 0xFFF != 0 {
+                sub_reg64_reg64_imm12(buf, dst, dst, (imm32 & 0xFFF) as u16);
+            }
         } else {
             todo!("immediate subtractions with values greater than 12bits");
         }
@@ -1033,6 +1751,20 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         scvtf_freg_reg64(buf, FloatWidth::F64, dst, src);
     }
 
+    #[inline(always)]
+    fn unsigned_to_float_freg_reg64<'a, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        _storage_manager: &mut StorageManager<'a, '_, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
+        width: FloatWidth,
+        dst: AArch64FloatReg,
+        src: AArch64GeneralReg,
+    ) where
+        ASM: Assembler<AArch64GeneralReg, AArch64FloatReg>,
+        CC: CallConv<AArch64GeneralReg, AArch64FloatReg, ASM>,
+    {
+        ucvtf_freg_reg64(buf, width, dst, src);
+    }
+
     #[inline(always)]
     fn to_float_freg32_reg64(buf: &mut Vec<'_, u8>, dst: AArch64FloatReg, src: AArch64GeneralReg) {
         scvtf_freg_reg64(buf, FloatWidth::F32, dst, src);
@@ -1057,6 +1789,32 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         ret_reg64(buf, AArch64GeneralReg::LR)
     }
 
+    fn nop(buf: &mut Vec<'_, u8>) {
+        nop(buf)
+    }
+
+    fn trap(buf: &mut Vec<'_, u8>) {
+        brk(buf)
+    }
+
+    const PATCHABLE_ENTRY_SIZE: usize = 24;
+
+    fn patchable_entry(buf: &mut Vec<'_, u8>) {
+        b_imm26(buf, 24);
+        // IP0 is free to clobber at the start of a function, since veneers use it too
+        ldr_reg64_literal(buf, AArch64GeneralReg::IP0, 12);
+        br_reg64(buf, AArch64GeneralReg::IP0);
+        // Keeps the address 8 byte aligned
+        nop(buf);
+        buf.extend(0u64.to_le_bytes());
+    }
+
+    fn redirect_patchable_entry(code: &mut [u8], target: u64) {
+        code[16..24].copy_from_slice(&target.to_le_bytes());
+        // Run into the indirect jump instead of branching over it
+        code[0..4].copy_from_slice(&NOP.to_le_bytes());
+    }
+
     fn and_reg64_reg64_reg64(
         buf: &mut Vec<'_, u8>,
         dst: AArch64GeneralReg,
@@ -1084,6 +1842,36 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         eor_reg64_reg64_reg64(buf, dst, src1, src2);
     }
 
+    #[inline(always)]
+    fn shl_reg64_reg64_imm8(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+        imm8: u8,
+    ) {
+        lsl_reg64_reg64_imm6(buf, dst, src, imm8);
+    }
+
+    #[inline(always)]
+    fn shr_reg64_reg64_imm8(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+        imm8: u8,
+    ) {
+        lsr_reg64_reg64_imm6(buf, dst, src, imm8);
+    }
+
+    #[inline(always)]
+    fn sar_reg64_reg64_imm8(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+        imm8: u8,
+    ) {
+        asr_reg64_reg64_imm6(buf, dst, src, imm8);
+    }
+
     fn shl_reg64_reg64_reg64<'a, ASM, CC>(
         buf: &mut Vec<'a, u8>,
         _storage_manager: &mut StorageManager<'a, '_, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
@@ -1588,6 +2376,47 @@ impl DataProcessingTwoSource {
     }
 }
 
+#[derive(PackedStruct)]
+#[packed_struct(endian = "msb")]
+pub struct DataProcessingOneSource {
+    sf: bool,
+    fixed: bool,
+    s: bool,
+    fixed2: Integer<u8, packed_bits::Bits<8>>,
+    opcode2: Integer<u8, packed_bits::Bits<5>>,
+    opcode: Integer<u8, packed_bits::Bits<6>>,
+    reg_n: Integer<u8, packed_bits::Bits<5>>,
+    reg_d: Integer<u8, packed_bits::Bits<5>>,
+}
+
+impl Aarch64Bytes for DataProcessingOneSource {}
+
+pub struct DataProcessingOneSourceParams {
+    opcode: u8,
+    rn: AArch64GeneralReg,
+    rd: AArch64GeneralReg,
+}
+
+impl DataProcessingOneSource {
+    #[inline(always)]
+    fn new(
+        DataProcessingOneSourceParams { opcode, rn, rd }: DataProcessingOneSourceParams,
+    ) -> Self {
+        debug_assert!(opcode <= 0b111111);
+
+        Self {
+            sf: true,
+            fixed: true,
+            s: false,
+            fixed2: 0b11010110.into(),
+            opcode2: 0b00000.into(),
+            opcode: opcode.into(),
+            reg_n: rn.id().into(),
+            reg_d: rd.id().into(),
+        }
+    }
+}
+
 #[derive(PackedStruct)]
 #[packed_struct(endian = "msb")]
 pub struct DataProcessingThreeSource {
@@ -1776,16 +2605,45 @@ pub struct UnconditionalBranchImmediateParams {
     imm26: u32,
 }
 
-impl UnconditionalBranchImmediate {
+impl UnconditionalBranchImmediate {
+    #[inline(always)]
+    fn new(
+        UnconditionalBranchImmediateParams { op, imm26 }: UnconditionalBranchImmediateParams,
+    ) -> Self {
+        debug_assert!(imm26 <= 0b11_1111_1111_1111_1111_1111_1111);
+        Self {
+            op,
+            fixed: 0b00101.into(),
+            imm26: imm26.into(),
+        }
+    }
+}
+
+#[derive(PackedStruct, Debug)]
+#[packed_struct(endian = "msb")]
+pub struct PcRelativeAddressing {
+    op: bool, // adr or adrp
+    immlo: Integer<u8, packed_bits::Bits<2>>,
+    fixed: Integer<u8, packed_bits::Bits<5>>, // = 0b10000,
+    immhi: Integer<u32, packed_bits::Bits<19>>,
+    rd: Integer<u8, packed_bits::Bits<5>>,
+}
+
+impl Aarch64Bytes for PcRelativeAddressing {}
+
+impl PcRelativeAddressing {
     #[inline(always)]
-    fn new(
-        UnconditionalBranchImmediateParams { op, imm26 }: UnconditionalBranchImmediateParams,
-    ) -> Self {
-        debug_assert!(imm26 <= 0b11_1111_1111_1111_1111_1111_1111);
+    fn new_adrp(rd: AArch64GeneralReg, imm21: i32) -> Self {
+        debug_assert!((-(1 << 20)..(1 << 20)).contains(&imm21));
+
+        let imm21 = imm21 as u32;
+
         Self {
-            op,
-            fixed: 0b00101.into(),
-            imm26: imm26.into(),
+            rd: rd.id().into(),
+            immhi: ((imm21 >> 2) & 0x7FFFF).into(),
+            fixed: 0b10000.into(),
+            immlo: ((imm21 & 0b11) as u8).into(),
+            op: true,
         }
     }
 }
@@ -1850,6 +2708,33 @@ impl LoadStoreRegisterImmediate {
     fn new_store(params: LoadStoreRegisterImmediateParams) -> Self {
         Self::new(0b00, params)
     }
+
+    /// Load into a SIMD&FP register, whose size is that of the float
+    #[inline(always)]
+    fn new_load_float(
+        ftype: FloatWidth,
+        rt: AArch64FloatReg,
+        rn: AArch64GeneralReg,
+        imm12: u16,
+    ) -> Self {
+        debug_assert!(imm12 <= 0xFFF);
+
+        let size = match ftype {
+            FloatWidth::F32 => 0b10,
+            FloatWidth::F64 => 0b11,
+        };
+
+        Self {
+            rt: rt.id().into(),
+            rn: rn.id().into(),
+            imm12: imm12.into(),
+            opc: 0b01.into(),
+            fixed3: 0b01.into(),
+            fixed2: true,
+            fixed: 0b111.into(),
+            size: size.into(),
+        }
+    }
 }
 
 #[derive(PackedStruct)]
@@ -1906,6 +2791,58 @@ fn encode_float_width(width: FloatWidth) -> u8 {
     }
 }
 
+#[derive(PackedStruct)]
+#[packed_struct(endian = "msb")]
+pub struct Bitfield {
+    sf: bool,
+    opc: Integer<u8, packed_bits::Bits<2>>,
+    fixed: Integer<u8, packed_bits::Bits<6>>, // = 0b100110,
+    n: bool,
+    immr: Integer<u8, packed_bits::Bits<6>>,
+    imms: Integer<u8, packed_bits::Bits<6>>,
+    reg_n: Integer<u8, packed_bits::Bits<5>>,
+    reg_d: Integer<u8, packed_bits::Bits<5>>,
+}
+
+impl Aarch64Bytes for Bitfield {}
+
+pub struct BitfieldParams {
+    opc: u8,
+    immr: u8,
+    imms: u8,
+    rn: AArch64GeneralReg,
+    rd: AArch64GeneralReg,
+}
+
+impl Bitfield {
+    #[inline(always)]
+    fn new(
+        BitfieldParams {
+            opc,
+            immr,
+            imms,
+            rn,
+            rd,
+        }: BitfieldParams,
+    ) -> Self {
+        debug_assert!(opc <= 0b11);
+        debug_assert!(immr <= 0b111111);
+        debug_assert!(imms <= 0b111111);
+
+        Self {
+            // Always the 64-bit variant, which requires N to be set
+            sf: true,
+            opc: opc.into(),
+            fixed: 0b100110.into(),
+            n: true,
+            immr: immr.into(),
+            imms: imms.into(),
+            reg_n: rn.id().into(),
+            reg_d: rd.id().into(),
+        }
+    }
+}
+
 #[derive(PackedStruct)]
 #[packed_struct(endian = "msb")]
 pub struct ConversionBetweenFloatingPointAndInteger {
@@ -2191,6 +3128,26 @@ fn add_reg64_reg64_imm12(
     buf.extend(inst.bytes());
 }
 
+/// `ADD Xd, Xn, imm12, LSL #12` -> Add Xn and (imm12 << 12) and place the result into Xd.
+#[inline(always)]
+fn add_reg64_reg64_imm12_lsl12(
+    buf: &mut Vec<'_, u8>,
+    dst: AArch64GeneralReg,
+    src: AArch64GeneralReg,
+    imm12: u16,
+) {
+    let inst = ArithmeticImmediate::new(ArithmeticImmediateParams {
+        op: false,
+        s: false,
+        rd: dst,
+        rn: src,
+        imm12,
+        sh: true,
+    });
+
+    buf.extend(inst.bytes());
+}
+
 /// `ADD Xd, Xm, Xn` -> Add Xm and Xn and place the result into Xd.
 #[inline(always)]
 fn add_reg64_reg64_reg64(
@@ -2212,6 +3169,14 @@ fn add_reg64_reg64_reg64(
     buf.extend(inst.bytes());
 }
 
+/// `ADRP Xd, label` -> Put the address of the 4KiB page imm21 pages from this instruction's page in Xd.
+#[inline(always)]
+fn adrp_reg64_imm21(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, imm21: i32) {
+    let inst = PcRelativeAddressing::new_adrp(dst, imm21);
+
+    buf.extend(inst.bytes());
+}
+
 /// `AND Xd, Xn, Xm` -> Bitwise AND Xn and Xm and place the result into Xd.
 #[inline(always)]
 fn and_reg64_reg64_reg64(
@@ -2302,6 +3267,18 @@ fn b_imm26(buf: &mut Vec<'_, u8>, imm26: i32) {
     buf.extend(inst.bytes());
 }
 
+/// `CLZ Xd, Xn` -> Count the leading zero bits of Xn and place the count into Xd.
+#[inline(always)]
+fn clz_reg64_reg64(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, src: AArch64GeneralReg) {
+    let inst = DataProcessingOneSource::new(DataProcessingOneSourceParams {
+        opcode: 0b000100,
+        rn: src,
+        rd: dst,
+    });
+
+    buf.extend(inst.bytes());
+}
+
 /// `CMP Xn, imm12` -> Compare Xn and imm12, setting condition flags.
 #[inline(always)]
 fn cmp_reg64_imm12(buf: &mut Vec<'_, u8>, src: AArch64GeneralReg, imm12: u16) {
@@ -2420,6 +3397,21 @@ fn ldr_reg64_reg64_imm12(
     buf.extend(inst.bytes());
 }
 
+/// `LDR Sd/Dd, [Xn, #offset]` -> Load Xn + Offset into Sd/Dd. ZRSP is SP.
+/// Note: imm12 is the offset divided by the size of the float.
+#[inline(always)]
+fn ldr_freg_reg64_imm12(
+    buf: &mut Vec<'_, u8>,
+    ftype: FloatWidth,
+    dst: AArch64FloatReg,
+    base: AArch64GeneralReg,
+    imm12: u16,
+) {
+    let inst = LoadStoreRegisterImmediate::new_load_float(ftype, dst, base, imm12);
+
+    buf.extend(inst.bytes());
+}
+
 /// `LSL Xd, Xn, Xm` -> Logical shift Xn left by Xm and place the result into Xd.
 #[inline(always)]
 fn lsl_reg64_reg64_reg64(
@@ -2548,6 +3540,18 @@ fn orr_reg64_reg64_reg64(
     buf.extend(inst.bytes());
 }
 
+/// `RBIT Xd, Xn` -> Reverse the bits of Xn and place the result into Xd.
+#[inline(always)]
+fn rbit_reg64_reg64(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, src: AArch64GeneralReg) {
+    let inst = DataProcessingOneSource::new(DataProcessingOneSourceParams {
+        opcode: 0b000000,
+        rn: src,
+        rd: dst,
+    });
+
+    buf.extend(inst.bytes());
+}
+
 /// `SDIV Xd, Xn, Xm` -> Divide Xn by Xm and place the result into Xd.
 /// Xn, Xm, and Xd are signed integers.
 #[inline(always)]
@@ -2606,6 +3610,26 @@ fn sub_reg64_reg64_imm12(
     buf.extend(inst.bytes());
 }
 
+/// `SUB Xd, Xn, imm12, LSL #12` -> Subtract (imm12 << 12) from Xn and place the result into Xd.
+#[inline(always)]
+fn sub_reg64_reg64_imm12_lsl12(
+    buf: &mut Vec<'_, u8>,
+    dst: AArch64GeneralReg,
+    src: AArch64GeneralReg,
+    imm12: u16,
+) {
+    let inst = ArithmeticImmediate::new(ArithmeticImmediateParams {
+        op: true,
+        s: false,
+        rd: dst,
+        rn: src,
+        imm12,
+        sh: true,
+    });
+
+    buf.extend(inst.bytes());
+}
+
 /// `SUB Xd, Xm, Xn` -> Subtract Xm and Xn and place the result into Xd.
 #[inline(always)]
 fn sub_reg64_reg64_reg64(
@@ -2668,6 +3692,43 @@ fn subs_reg64_reg64_reg64(
     buf.extend(inst.bytes());
 }
 
+/// `NOP` -> No operation.
+#[inline(always)]
+fn nop(buf: &mut Vec<'_, u8>) {
+    buf.extend(NOP.to_le_bytes());
+}
+
+const NOP: u32 = 0xD503201F;
+
+/// `BRK #0` -> Raise a breakpoint exception.
+#[inline(always)]
+fn brk(buf: &mut Vec<'_, u8>) {
+    buf.extend(BRK.to_le_bytes());
+}
+
+const BRK: u32 = 0xD4200000;
+
+/// `LDR Xt, label` -> Load Xt with the 8 bytes at PC + imm19.
+#[inline(always)]
+fn ldr_reg64_literal(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, imm19: i32) {
+    debug_assert!(imm19 & 0b11 == 0, "literal location must be 4-byte aligned");
+    debug_assert!((-(1 << 20)..(1 << 20)).contains(&imm19));
+
+    let offset = ((imm19 >> 2) as u32) & 0x7FFFF;
+    let inst = 0x5800_0000 | (offset << 5) | dst.id() as u32;
+
+    buf.extend(inst.to_le_bytes());
+}
+
+/// `BR Xn` -> Jump to the address stored in Xn.
+#[inline(always)]
+fn br_reg64(buf: &mut Vec<'_, u8>, xn: AArch64GeneralReg) {
+    let inst =
+        UnconditionalBranchRegister::new(UnconditionalBranchRegisterParams { op: 0b00, rn: xn });
+
+    buf.extend(inst.bytes());
+}
+
 /// `RET Xn` -> Return to the address stored in Xn.
 #[inline(always)]
 fn ret_reg64(buf: &mut Vec<'_, u8>, xn: AArch64GeneralReg) {
@@ -2825,6 +3886,51 @@ fn fmov_freg_freg(
     buf.extend(inst.bytes());
 }
 
+/// `FMOV Wd/Xd, Sn/Dn` -> Move the bits of Sn/Dn to Wd/Xd, without converting them.
+/// Writing Wd zeroes the upper half of Xd.
+#[inline(always)]
+fn fmov_reg_freg(
+    buf: &mut Vec<'_, u8>,
+    ftype: FloatWidth,
+    dst: AArch64GeneralReg,
+    src: AArch64FloatReg,
+) {
+    // This is the only conversion we need from a float register to a general one, and its `sf`
+    // bit depends on the width, so it's built here rather than with
+    // ConversionBetweenFloatingPointAndInteger::new
+    let inst = ConversionBetweenFloatingPointAndInteger {
+        sf: matches!(ftype, FloatWidth::F64),
+        fixed: false,
+        s: false,
+        fixed2: 0b11110.into(),
+        ptype: encode_float_width(ftype).into(),
+        fixed3: true,
+        rmode: 0b00.into(),
+        opcode: 0b110.into(),
+        fixed4: 0b000000.into(),
+        rn: src.id().into(),
+        rd: dst.id().into(),
+    };
+
+    buf.extend(inst.bytes());
+}
+
+/// `FMOV Dd, Xn` -> Move the bits of Xn to Dd, without converting them.
+#[inline(always)]
+fn fmov_freg_reg64(buf: &mut Vec<'_, u8>, dst: AArch64FloatReg, src: AArch64GeneralReg) {
+    let inst = ConversionBetweenFloatingPointAndInteger::new(
+        ConversionBetweenFloatingPointAndIntegerParams {
+            opcode: 0b111,
+            rmode: 0b00,
+            ptype: FloatWidth::F64,
+            rd: dst,
+            rn: src,
+        },
+    );
+
+    buf.extend(inst.bytes());
+}
+
 /// Encode a 32-bit float into an 8-bit immediate for FMOV.
 /// See Table C2-1 in the ARM manual for a table of every float that can be encoded in 8 bits.
 /// If the float cannot be encoded, return None.
@@ -2961,29 +4067,159 @@ fn fsqrt_freg_freg(
     dst: AArch64FloatReg,
     src: AArch64FloatReg,
 ) {
-    let inst =
-        FloatingPointDataProcessingOneSource::new(FloatingPointDataProcessingOneSourceParams {
-            opcode: 0b000011,
+    let inst =
+        FloatingPointDataProcessingOneSource::new(FloatingPointDataProcessingOneSourceParams {
+            opcode: 0b000011,
+            ptype: ftype,
+            rd: dst,
+            rn: src,
+        });
+
+    buf.extend(inst.bytes());
+}
+
+/// Currently, we're only using MOVI to set a float register to 0.0.
+/// `MOVI Dd, #0.0` -> Move 0.0 to Dd
+#[inline(always)]
+fn movi_freg_zero(buf: &mut Vec<'_, u8>, dst: AArch64FloatReg) {
+    let inst = AdvancedSimdModifiedImmediate::new(dst);
+
+    buf.extend(inst.bytes());
+}
+
+/// `CNT Vd.8B, Vn.8B` -> Count the one bits in each of the low 8 bytes of Vn, and place the
+/// counts in the bytes of Vd.
+#[inline(always)]
+fn cnt_freg_freg_8b(buf: &mut Vec<'_, u8>, dst: AArch64FloatReg, src: AArch64FloatReg) {
+    let inst = 0x0E20_5800 | ((src.id() as u32) << 5) | dst.id() as u32;
+
+    buf.extend(inst.to_le_bytes());
+}
+
+/// `ADDV Bd, Vn.8B` -> Add up the low 8 bytes of Vn, and place the sum in Bd.
+/// The rest of Vd is zeroed.
+#[inline(always)]
+fn addv_freg_freg_8b(buf: &mut Vec<'_, u8>, dst: AArch64FloatReg, src: AArch64FloatReg) {
+    let inst = 0x0E31_B800 | ((src.id() as u32) << 5) | dst.id() as u32;
+
+    buf.extend(inst.to_le_bytes());
+}
+
+/// `SBFM Xd, Xn, #immr, #imms` -> Copy a bitfield from Xn to Xd, sign extending it.
+/// With `immr` 0, this sign extends the low `imms + 1` bits of Xn (`SXTB`, `SXTH`, `SXTW`).
+#[inline(always)]
+fn sbfm_reg64_reg64(
+    buf: &mut Vec<'_, u8>,
+    dst: AArch64GeneralReg,
+    src: AArch64GeneralReg,
+    immr: u8,
+    imms: u8,
+) {
+    let inst = Bitfield::new(BitfieldParams {
+        opc: 0b00,
+        immr,
+        imms,
+        rn: src,
+        rd: dst,
+    });
+
+    buf.extend(inst.bytes());
+}
+
+/// `UBFM Xd, Xn, #immr, #imms` -> Copy a bitfield from Xn to Xd, zero extending it.
+/// With `immr` 0, this zero extends the low `imms + 1` bits of Xn (`UBFX Xd, Xn, #0, #bits`).
+#[inline(always)]
+fn ubfm_reg64_reg64(
+    buf: &mut Vec<'_, u8>,
+    dst: AArch64GeneralReg,
+    src: AArch64GeneralReg,
+    immr: u8,
+    imms: u8,
+) {
+    let inst = Bitfield::new(BitfieldParams {
+        opc: 0b10,
+        immr,
+        imms,
+        rn: src,
+        rd: dst,
+    });
+
+    buf.extend(inst.bytes());
+}
+
+/// `LSL Xd, Xn, #shift` -> Logical shift Xn left by an immediate and place the result into Xd.
+/// An alias of `UBFM Xd, Xn, #(-shift MOD 64), #(63 - shift)`.
+#[inline(always)]
+fn lsl_reg64_reg64_imm6(
+    buf: &mut Vec<'_, u8>,
+    dst: AArch64GeneralReg,
+    src: AArch64GeneralReg,
+    shift: u8,
+) {
+    debug_assert!(shift < 64);
+    ubfm_reg64_reg64(buf, dst, src, (64 - shift) % 64, 63 - shift);
+}
+
+/// `LSR Xd, Xn, #shift` -> Logical shift Xn right by an immediate and place the result into Xd.
+/// An alias of `UBFM Xd, Xn, #shift, #63`.
+#[inline(always)]
+fn lsr_reg64_reg64_imm6(
+    buf: &mut Vec<'_, u8>,
+    dst: AArch64GeneralReg,
+    src: AArch64GeneralReg,
+    shift: u8,
+) {
+    debug_assert!(shift < 64);
+    ubfm_reg64_reg64(buf, dst, src, shift, 63);
+}
+
+/// `ASR Xd, Xn, #shift` -> Arithmetic shift Xn right by an immediate and place the result into Xd.
+/// An alias of `SBFM Xd, Xn, #shift, #63`.
+#[inline(always)]
+fn asr_reg64_reg64_imm6(
+    buf: &mut Vec<'_, u8>,
+    dst: AArch64GeneralReg,
+    src: AArch64GeneralReg,
+    shift: u8,
+) {
+    debug_assert!(shift < 64);
+    sbfm_reg64_reg64(buf, dst, src, shift, 63);
+}
+
+#[inline(always)]
+fn register_width_bits(register_width: RegisterWidth) -> u8 {
+    match register_width {
+        RegisterWidth::W8 => 8,
+        RegisterWidth::W16 => 16,
+        RegisterWidth::W32 => 32,
+        RegisterWidth::W64 => 64,
+    }
+}
+
+/// `SCVTF Sd/Dd, Xn` -> Convert Xn to a float and store the result in Sd/Dd.
+#[inline(always)]
+fn scvtf_freg_reg64(
+    buf: &mut Vec<'_, u8>,
+    ftype: FloatWidth,
+    dst: AArch64FloatReg,
+    src: AArch64GeneralReg,
+) {
+    let inst = ConversionBetweenFloatingPointAndInteger::new(
+        ConversionBetweenFloatingPointAndIntegerParams {
+            opcode: 0b010,
+            rmode: 0b00,
             ptype: ftype,
             rd: dst,
             rn: src,
-        });
-
-    buf.extend(inst.bytes());
-}
-
-/// Currently, we're only using MOVI to set a float register to 0.0.
-/// `MOVI Dd, #0.0` -> Move 0.0 to Dd
-#[inline(always)]
-fn movi_freg_zero(buf: &mut Vec<'_, u8>, dst: AArch64FloatReg) {
-    let inst = AdvancedSimdModifiedImmediate::new(dst);
+        },
+    );
 
     buf.extend(inst.bytes());
 }
 
-/// `SCVTF Sd/Dd, Xn` -> Convert Xn to a float and store the result in Sd/Dd.
+/// `UCVTF Sd/Dd, Xn` -> Convert Xn, which is unsigned, to a float and store the result in Sd/Dd.
 #[inline(always)]
-fn scvtf_freg_reg64(
+fn ucvtf_freg_reg64(
     buf: &mut Vec<'_, u8>,
     ftype: FloatWidth,
     dst: AArch64FloatReg,
@@ -2991,7 +4227,7 @@ fn scvtf_freg_reg64(
 ) {
     let inst = ConversionBetweenFloatingPointAndInteger::new(
         ConversionBetweenFloatingPointAndIntegerParams {
-            opcode: 0b010,
+            opcode: 0b011,
             rmode: 0b00,
             ptype: ftype,
             rd: dst,
@@ -3185,6 +4421,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_add_reg64_reg64_imm12_lsl12() {
+        disassembler_test!(
+            add_reg64_reg64_imm12_lsl12,
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg, imm| format!(
+                "add {}, {}, #0x{:x}, lsl #12",
+                reg1.capstone_string(UsesSP),
+                reg2.capstone_string(UsesSP),
+                imm
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [0x123]
+        );
+    }
+
+    #[test]
+    fn test_adrp_reg64_imm21() {
+        disassembler_test!(
+            adrp_reg64_imm21,
+            |reg1: AArch64GeneralReg, imm| format!(
+                "adrp {}, #0x{:x}",
+                reg1.capstone_string(UsesZR),
+                imm << 12
+            ),
+            ALL_GENERAL_REGS,
+            [0x123]
+        );
+    }
+
     #[test]
     fn test_and_reg64_reg64_reg64() {
         disassembler_test!(
@@ -3236,6 +4502,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_br_reg64() {
+        disassembler_test!(
+            br_reg64,
+            |reg1: AArch64GeneralReg| format!("br {}", reg1.capstone_string(UsesZR)),
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_ldr_reg64_literal() {
+        disassembler_test!(
+            ldr_reg64_literal,
+            |reg1: AArch64GeneralReg, imm| format!(
+                "ldr {}, #0x{:x}",
+                reg1.capstone_string(UsesZR),
+                imm
+            ),
+            ALL_GENERAL_REGS,
+            [0x8, 0xc, 0x120, (1 << 20) - 4]
+        );
+    }
+
+    #[test]
+    fn test_redirect_patchable_entry() {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+
+        AArch64Assembler::patchable_entry(&mut buf);
+        assert_eq!(buf.len(), AArch64Assembler::PATCHABLE_ENTRY_SIZE);
+
+        AArch64Assembler::redirect_patchable_entry(&mut buf, 0x1122_3344_5566_7788);
+
+        let cs = Capstone::new()
+            .arm64()
+            .mode(arch::arm64::ArchMode::Arm)
+            .build()
+            .expect("Failed to create Capstone object");
+        let instructions = cs.disasm_all(&buf[..12], 0).unwrap();
+        assert_eq!(
+            crate::generic64::disassembler_test_macro::merge_instructions_without_line_numbers(
+                instructions
+            ),
+            "nop\nldr x16, #0x10\nbr x16"
+        );
+        assert_eq!(buf[16..], 0x1122_3344_5566_7788u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_clz_reg64_reg64() {
+        disassembler_test!(
+            clz_reg64_reg64,
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg| format!(
+                "clz {}, {}",
+                reg1.capstone_string(UsesZR),
+                reg2.capstone_string(UsesZR)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
     #[test]
     fn test_cmp_reg64_imm12() {
         disassembler_test!(
@@ -3417,6 +4745,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lsl_reg64_reg64_imm6() {
+        disassembler_test!(
+            lsl_reg64_reg64_imm6,
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg, shift| format!(
+                "lsl {}, {}, #{}",
+                reg1.capstone_string(UsesZR),
+                reg2.capstone_string(UsesZR),
+                shift
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [1, 7]
+        );
+    }
+
+    #[test]
+    fn test_lsr_reg64_reg64_imm6() {
+        disassembler_test!(
+            lsr_reg64_reg64_imm6,
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg, shift| format!(
+                "lsr {}, {}, #{}",
+                reg1.capstone_string(UsesZR),
+                reg2.capstone_string(UsesZR),
+                shift
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [1, 7]
+        );
+    }
+
+    #[test]
+    fn test_asr_reg64_reg64_imm6() {
+        disassembler_test!(
+            asr_reg64_reg64_imm6,
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg, shift| format!(
+                "asr {}, {}, #{}",
+                reg1.capstone_string(UsesZR),
+                reg2.capstone_string(UsesZR),
+                shift
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [1, 7]
+        );
+    }
+
     #[test]
     fn test_lsl_reg64_reg64_reg64() {
         disassembler_test!(
@@ -3587,6 +4963,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rbit_reg64_reg64() {
+        disassembler_test!(
+            rbit_reg64_reg64,
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg| format!(
+                "rbit {}, {}",
+                reg1.capstone_string(UsesZR),
+                reg2.capstone_string(UsesZR)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
     #[test]
     fn test_sdiv_reg64_reg64_reg64() {
         disassembler_test!(
@@ -3635,6 +5025,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sub_reg64_reg64_imm12_lsl12() {
+        disassembler_test!(
+            sub_reg64_reg64_imm12_lsl12,
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg, imm| format!(
+                "sub {}, {}, #0x{:x}, lsl #12",
+                reg1.capstone_string(UsesSP),
+                reg2.capstone_string(UsesSP),
+                imm
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [0x123]
+        );
+    }
+
     #[test]
     fn test_sub_reg64_reg64_reg64() {
         disassembler_test!(
@@ -3724,6 +5130,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_nop() {
+        disassembler_test!(nop, || "nop");
+    }
+
+    #[test]
+    fn test_brk() {
+        disassembler_test!(brk, || "brk #0");
+    }
+
     #[test]
     fn test_ret_reg64() {
         disassembler_test!(
@@ -3868,6 +5284,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_fmov_reg_freg() {
+        disassembler_test!(
+            fmov_reg_freg,
+            |ftype: FloatWidth, reg1: AArch64GeneralReg, reg2: AArch64FloatReg| format!(
+                "fmov {}, {}",
+                match ftype {
+                    FloatWidth::F32 => reg1.capstone_string(UsesZR).replacen('x', "w", 1),
+                    FloatWidth::F64 => reg1.capstone_string(UsesZR),
+                },
+                reg2.capstone_string(ftype)
+            ),
+            ALL_FLOAT_TYPES,
+            ALL_GENERAL_REGS,
+            ALL_FLOAT_REGS
+        );
+    }
+
+    #[test]
+    fn test_fmov_freg_reg64() {
+        disassembler_test!(
+            fmov_freg_reg64,
+            |reg1: AArch64FloatReg, reg2: AArch64GeneralReg| format!(
+                "fmov {}, {}",
+                reg1.capstone_string(FloatWidth::F64),
+                reg2.capstone_string(UsesZR)
+            ),
+            ALL_FLOAT_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
     #[test]
     #[allow(clippy::unusual_byte_groupings)]
     fn test_encode_f32_to_imm8() {
@@ -3968,6 +5416,56 @@ mod tests {
         assert_eq!(encode_f64_to_imm8(-0.3), None);
     }
 
+    #[test]
+    fn test_ldr_freg_reg64_imm12() {
+        disassembler_test!(
+            ldr_freg_reg64_imm12,
+            |ftype: FloatWidth, reg1: AArch64FloatReg, reg2: AArch64GeneralReg, imm| format!(
+                "ldr {}, [{}, #0x{:x}]",
+                reg1.capstone_string(ftype),
+                reg2.capstone_string(UsesSP),
+                match ftype {
+                    FloatWidth::F32 => imm << 2,
+                    FloatWidth::F64 => imm << 3,
+                }
+            ),
+            ALL_FLOAT_TYPES,
+            ALL_FLOAT_REGS,
+            ALL_GENERAL_REGS,
+            [0x123]
+        );
+    }
+
+    #[test]
+    fn test_mov_freg64_imm64_from_data() {
+        let arena = bumpalo::Bump::new();
+        let (mut buf, cs) = setup_capstone_and_arena(&arena);
+        let mut relocs = bumpalo::vec![in &arena];
+
+        // 1.1 can't be encoded as an 8-bit float immediate, so it goes in the data section
+        AArch64Assembler::mov_freg64_imm64(&mut buf, &mut relocs, AArch64FloatReg::V3, 1.1);
+
+        let instructions = cs.disasm_all(&buf, 0).unwrap();
+        let text: std::vec::Vec<String> = instructions
+            .iter()
+            .map(|inst| format!("{} {}", inst.mnemonic().unwrap(), inst.op_str().unwrap()))
+            .collect();
+        assert_eq!(text, ["adrp x16, #0", "ldr d3, [x16]"]);
+
+        match relocs.as_slice() {
+            [Relocation::LocalData {
+                offset,
+                data,
+                access,
+            }] => {
+                assert_eq!(*offset, 0);
+                assert_eq!(data, &1.1f64.to_le_bytes());
+                assert_eq!(*access, DataAccess::PageLoad64);
+            }
+            _ => panic!("expected one LocalData relocation"),
+        }
+    }
+
     #[test]
     fn test_fmov_freg_imm8() {
         disassembler_test!(
@@ -4065,6 +5563,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cnt_freg_freg_8b() {
+        disassembler_test!(
+            cnt_freg_freg_8b,
+            |reg1: AArch64FloatReg, reg2: AArch64FloatReg| format!(
+                "cnt v{}.8b, v{}.8b",
+                reg1.id(),
+                reg2.id()
+            ),
+            ALL_FLOAT_REGS,
+            ALL_FLOAT_REGS
+        );
+    }
+
+    #[test]
+    fn test_addv_freg_freg_8b() {
+        disassembler_test!(
+            addv_freg_freg_8b,
+            |reg1: AArch64FloatReg, reg2: AArch64FloatReg| format!(
+                "addv b{}, v{}.8b",
+                reg1.id(),
+                reg2.id()
+            ),
+            ALL_FLOAT_REGS,
+            ALL_FLOAT_REGS
+        );
+    }
+
     #[test]
     fn test_scvtf_freg_reg64() {
         disassembler_test!(
@@ -4079,4 +5605,106 @@ mod tests {
             ALL_GENERAL_REGS
         );
     }
+
+    #[test]
+    fn test_ucvtf_freg_reg64() {
+        disassembler_test!(
+            ucvtf_freg_reg64,
+            |ftype: FloatWidth, reg1: AArch64FloatReg, reg2: AArch64GeneralReg| format!(
+                "ucvtf {}, {}",
+                reg1.capstone_string(ftype),
+                reg2.capstone_string(UsesZR)
+            ),
+            ALL_FLOAT_TYPES,
+            ALL_FLOAT_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_sbfm_reg64_reg64() {
+        disassembler_test!(
+            |buf, reg1, reg2, bits: u8| sbfm_reg64_reg64(buf, reg1, reg2, 0, bits - 1),
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg, bits| format!(
+                "sxt{} {}, {}",
+                match bits {
+                    8 => "b",
+                    16 => "h",
+                    _ => "w",
+                },
+                reg1.capstone_string(UsesZR),
+                reg2.capstone_string(UsesZR).replacen('x', "w", 1)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [8, 16, 32]
+        );
+    }
+
+    #[test]
+    fn test_ubfm_reg64_reg64() {
+        disassembler_test!(
+            |buf, reg1, reg2, bits: u8| ubfm_reg64_reg64(buf, reg1, reg2, 0, bits - 1),
+            |reg1: AArch64GeneralReg, reg2: AArch64GeneralReg, bits| format!(
+                "ubfx {}, {}, #0, #{:#x}",
+                reg1.capstone_string(UsesZR),
+                reg2.capstone_string(UsesZR),
+                bits
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [16, 32]
+        );
+    }
+
+    #[test]
+    fn test_arg_locations_general_then_stack() {
+        let mut locations = AArch64ArgLocations::default();
+
+        for reg in AArch64ArgLocations::GENERAL_PARAM_REGS.chunks(1) {
+            assert_eq!(locations.general(), ArgLocation::Regs(reg));
+        }
+
+        assert_eq!(locations.general(), ArgLocation::Stack(0));
+        assert_eq!(locations.general(), ArgLocation::Stack(8));
+    }
+
+    #[test]
+    fn test_arg_locations_aligned_composite_starts_at_even_reg() {
+        use AArch64GeneralReg::*;
+        let mut locations = AArch64ArgLocations::default();
+
+        assert_eq!(locations.general(), ArgLocation::Regs(&[X0]));
+        // an I128 skips X1
+        assert_eq!(locations.composite(16, 16), ArgLocation::Regs(&[X2, X3]));
+        // a 16 byte struct of U64s doesn't
+        assert_eq!(locations.general(), ArgLocation::Regs(&[X4]));
+        assert_eq!(locations.composite(16, 8), ArgLocation::Regs(&[X5, X6]));
+        assert_eq!(locations.composite(4, 4), ArgLocation::Regs(&[X7]));
+    }
+
+    #[test]
+    fn test_arg_locations_composite_that_doesnt_fit() {
+        let mut locations = AArch64ArgLocations::default();
+
+        for _ in 0..7 {
+            locations.general();
+        }
+
+        // It doesn't get split between X7 and the stack, and nothing after it uses X7.
+        assert_eq!(locations.composite(16, 8), ArgLocation::Stack(0));
+        assert_eq!(locations.general(), ArgLocation::Stack(16));
+        assert_eq!(locations.composite(12, 4), ArgLocation::Stack(24));
+        // 16-byte aligned on the stack too
+        assert_eq!(locations.composite(16, 16), ArgLocation::Stack(48));
+        assert_eq!(locations.general(), ArgLocation::Stack(64));
+    }
+
+    #[test]
+    fn test_register_count() {
+        assert_eq!(register_count(1), 1);
+        assert_eq!(register_count(8), 1);
+        assert_eq!(register_count(9), 2);
+        assert_eq!(register_count(16), 2);
+    }
 }
@@ -0,0 +1,228 @@
+//! The machine code of a proc while it's being built. It derefs to the underlying bytes, so the
+//! assemblers can keep appending to it directly, and adds labels for jumps whose targets aren't
+//! built yet, so that nothing has to remember where each jump is to fix it up later by hand.
+use bumpalo::collections::Vec;
+use bumpalo::Bump;
+use std::ops::{Deref, DerefMut};
+
+/// A position in the code that jumps can go to, before or after it's known where that is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// Encodes a jump by the given offset, relative to the base offset that it returns, like
+/// `Assembler::jmp_imm32`. It must encode to the same number of bytes whatever the offset is.
+type JumpEncoder<'a> = &'a dyn Fn(&mut Vec<'_, u8>, i32) -> usize;
+
+/// A jump to a label that isn't bound yet
+struct Fixup<'a> {
+    label: Label,
+    location: usize,
+    base_offset: usize,
+    encode: JumpEncoder<'a>,
+}
+
+pub struct CodeBuffer<'a> {
+    arena: &'a Bump,
+    bytes: Vec<'a, u8>,
+    /// Where each label is, once it's bound
+    labels: Vec<'a, Option<usize>>,
+    fixups: Vec<'a, Fixup<'a>>,
+    /// Scratch space for re-encoding instructions
+    tmp: Vec<'a, u8>,
+}
+
+impl<'a> CodeBuffer<'a> {
+    pub fn new_in(arena: &'a Bump) -> Self {
+        CodeBuffer {
+            arena,
+            bytes: Vec::new_in(arena),
+            labels: Vec::new_in(arena),
+            fixups: Vec::new_in(arena),
+            tmp: Vec::new_in(arena),
+        }
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Put the label at the current end of the code, and point the jumps to it there
+    pub fn bind(&mut self, label: Label) {
+        debug_assert!(self.labels[label.0].is_none(), "label bound twice");
+
+        let target = self.bytes.len();
+        self.labels[label.0] = Some(target);
+
+        let mut index = 0;
+        while index < self.fixups.len() {
+            if self.fixups[index].label == label {
+                let fixup = self.fixups.swap_remove(index);
+                let offset = target as i32 - fixup.base_offset as i32;
+                self.patch(fixup.location, |buf| {
+                    (fixup.encode)(buf, offset);
+                });
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Add a jump to the label. If the label isn't bound yet, the jump gets its offset when it is.
+    /// Returns the base offset of the jump.
+    pub fn jump_to<F>(&mut self, label: Label, encode: F) -> usize
+    where
+        F: Fn(&mut Vec<'_, u8>, i32) -> usize + 'a,
+    {
+        let location = self.bytes.len();
+        let base_offset = encode(&mut self.bytes, 0);
+
+        match self.labels[label.0] {
+            Some(target) => {
+                let offset = target as i32 - base_offset as i32;
+                self.patch(location, |buf| {
+                    encode(buf, offset);
+                });
+            }
+            None => self.fixups.push(Fixup {
+                label,
+                location,
+                base_offset,
+                encode: self.arena.alloc(encode),
+            }),
+        }
+
+        base_offset
+    }
+
+    /// Reserve `len` zeroed bytes, to be filled in by `patch` once their contents are known.
+    /// Returns where they start.
+    pub fn reserve(&mut self, len: usize) -> usize {
+        let location = self.bytes.len();
+        self.bytes.resize(location + len, 0);
+        location
+    }
+
+    /// Overwrite the code at `location` with what `encode` writes to the empty buffer it's given,
+    /// e.g. to re-encode a jump with its final offset.
+    pub fn patch<F>(&mut self, location: usize, encode: F)
+    where
+        F: FnOnce(&mut Vec<'a, u8>),
+    {
+        self.tmp.clear();
+        encode(&mut self.tmp);
+
+        let end = location + self.tmp.len();
+        debug_assert!(end <= self.bytes.len(), "patch past the end of the code");
+        self.bytes[location..end].copy_from_slice(&self.tmp);
+    }
+
+    /// Pad the code with `nop` until its length is a multiple of `alignment`
+    pub fn align(&mut self, alignment: usize, nop: impl Fn(&mut Vec<'_, u8>)) {
+        while self.bytes.len() % alignment != 0 {
+            nop(&mut self.bytes);
+        }
+    }
+
+    /// Remove the code from `len` onwards, along with any jumps in it that are still waiting
+    /// for their label. Labels that were bound past the new end are unbound again.
+    pub fn truncate(&mut self, len: usize) {
+        self.bytes.truncate(len);
+        self.fixups.retain(|fixup| fixup.location < len);
+
+        for target in self.labels.iter_mut() {
+            if matches!(target, Some(location) if *location > len) {
+                *target = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.labels.clear();
+        self.fixups.clear();
+    }
+
+    /// Whether any jumps are still waiting for a label to be bound
+    pub fn has_unbound_jumps(&self) -> bool {
+        !self.fixups.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<'a, u8> {
+        debug_assert!(
+            !self.has_unbound_jumps(),
+            "jump to a label that was never bound"
+        );
+        self.bytes
+    }
+}
+
+impl<'a> Deref for CodeBuffer<'a> {
+    type Target = Vec<'a, u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.bytes
+    }
+}
+
+impl<'a> DerefMut for CodeBuffer<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A two byte jump, like x86's `jmp rel8`, which returns its base offset
+    fn jmp8(buf: &mut Vec<'_, u8>, offset: i32) -> usize {
+        buf.extend([0xEB, offset as i8 as u8]);
+        buf.len()
+    }
+
+    #[test]
+    fn forward_and_backward_jumps() {
+        let arena = Bump::new();
+        let mut buf = CodeBuffer::new_in(&arena);
+
+        let start = buf.new_label();
+        let end = buf.new_label();
+        buf.bind(start);
+        buf.push(0x90);
+        buf.jump_to(end, jmp8);
+        buf.jump_to(start, jmp8);
+        assert!(buf.has_unbound_jumps());
+        buf.bind(end);
+
+        assert_eq!(buf.into_bytes().as_slice(), &[0x90, 0xEB, 2, 0xEB, 0xFB]);
+    }
+
+    #[test]
+    fn truncate_drops_jumps() {
+        let arena = Bump::new();
+        let mut buf = CodeBuffer::new_in(&arena);
+
+        let end = buf.new_label();
+        buf.jump_to(end, jmp8);
+        let len = buf.len();
+        buf.jump_to(end, jmp8);
+        buf.truncate(len);
+        buf.bind(end);
+
+        assert_eq!(buf.into_bytes().as_slice(), &[0xEB, 0]);
+    }
+
+    #[test]
+    fn reserve_patch_and_align() {
+        let arena = Bump::new();
+        let mut buf = CodeBuffer::new_in(&arena);
+
+        buf.push(0xC3);
+        let location = buf.reserve(2);
+        buf.align(4, |buf| buf.push(0x90));
+        buf.patch(location, |buf| buf.extend([1, 2]));
+
+        assert_eq!(buf.into_bytes().as_slice(), &[0xC3, 1, 2, 0x90]);
+    }
+}
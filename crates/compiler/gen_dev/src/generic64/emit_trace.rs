@@ -0,0 +1,129 @@
+//! Listings of the machine code emitted for each mono IR statement, for hunting down miscompiles.
+//!
+//! Set `ROC_LOG_DEV_EMIT=1` to print a listing of every proc to stderr, or set it to a file path
+//! to append the listings to that file instead. The code is disassembled when gen_dev is built
+//! with the `disassemble` feature, and shown as hex bytes otherwise.
+use crate::assembly;
+use roc_mono::ir::Stmt;
+use roc_mono::layout::STLayoutInterner;
+use roc_target::Architecture;
+use std::fmt::Write;
+use std::io::Write as _;
+
+pub struct EmitTrace {
+    architecture: Architecture,
+    /// Where to write listings: stderr for "1", otherwise a file path
+    destination: String,
+    /// The offset in the proc body where each statement's code starts, and the statement
+    marks: std::vec::Vec<(usize, String)>,
+}
+
+impl EmitTrace {
+    /// Returns None unless the ROC_LOG_DEV_EMIT debug flag is set
+    pub fn from_env(architecture: Architecture) -> Option<Self> {
+        if !roc_debug_flags::dbg_set!(roc_debug_flags::ROC_LOG_DEV_EMIT) {
+            return None;
+        }
+
+        let destination = std::env::var(roc_debug_flags::ROC_LOG_DEV_EMIT).ok()?;
+
+        Some(EmitTrace {
+            architecture,
+            destination,
+            marks: std::vec::Vec::new(),
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.marks.clear();
+    }
+
+    /// Record that the code for `stmt` starts at `offset` in the proc body
+    pub fn mark<'a>(&mut self, offset: usize, stmt: &Stmt<'a>, interner: &STLayoutInterner<'a>) {
+        self.marks.push((offset, describe_stmt(stmt, interner)));
+    }
+
+    /// Write out the listing for a finished proc. The body starts at `body_offset` in `code`,
+    /// after the stack setup, and is `body_len` bytes long. It is followed by the stack cleanup,
+    /// and any cold blocks start at `cold_offset`.
+    pub fn write_listing(
+        &self,
+        proc_name: &str,
+        code: &[u8],
+        body_offset: usize,
+        body_len: usize,
+        cold_offset: usize,
+    ) {
+        let body_end = body_offset + body_len;
+        let mut listing = String::new();
+
+        writeln!(listing, "=== {} ===", proc_name).unwrap();
+
+        writeln!(listing, "# stack setup").unwrap();
+        self.disassemble(&mut listing, code, 0, body_offset);
+
+        for (index, (offset, description)) in self.marks.iter().enumerate() {
+            let next_offset = match self.marks.get(index + 1) {
+                Some((next_offset, _)) => *next_offset,
+                None => body_len,
+            };
+            let start = body_offset + (*offset).min(body_len);
+            let end = (body_offset + next_offset.min(body_len)).max(start);
+
+            writeln!(listing, "# {}", description).unwrap();
+            self.disassemble(&mut listing, code, start, end);
+        }
+
+        writeln!(listing, "# stack cleanup").unwrap();
+        self.disassemble(&mut listing, code, body_end, cold_offset);
+
+        if cold_offset < code.len() {
+            writeln!(listing, "# cold blocks").unwrap();
+            self.disassemble(&mut listing, code, cold_offset, code.len());
+        }
+        listing.push('\n');
+
+        if self.destination == "1" {
+            eprint!("{}", listing);
+        } else {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.destination)
+                .and_then(|mut file| file.write_all(listing.as_bytes()))
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Could not write ROC_LOG_DEV_EMIT listing to {}: {}",
+                        self.destination, e
+                    )
+                });
+        }
+    }
+
+    fn disassemble(&self, listing: &mut String, code: &[u8], start: usize, end: usize) {
+        for line in assembly::disassemble(self.architecture, code, start, end) {
+            writeln!(listing, "    {:06x}  {}", line.offset, line.text).unwrap();
+        }
+    }
+}
+
+/// The statement itself, without the statements that follow it or are nested in it
+fn describe_stmt<'a>(stmt: &Stmt<'a>, interner: &STLayoutInterner<'a>) -> String {
+    match stmt {
+        Stmt::Let(sym, expr, _, _) => {
+            format!("let {:?} = {}", sym, expr.to_pretty(200, false).trim_end())
+        }
+        Stmt::Refcounting(modify, _) => format!("{:?}", modify),
+        Stmt::Switch { cond_symbol, .. } => format!("switch {:?}", cond_symbol),
+        Stmt::Join { id, parameters, .. } => {
+            let symbols: std::vec::Vec<_> = parameters.iter().map(|param| param.symbol).collect();
+            format!("joinpoint {:?} {:?}", id, symbols)
+        }
+        _ => stmt
+            .to_pretty(interner, 200, false)
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_string(),
+    }
+}
@@ -1,32 +1,47 @@
+use crate::unwind::UnwindStep;
 use crate::{
-    single_register_floats, single_register_int_builtins, single_register_integers, Backend, Env,
-    Relocation,
+    pointer_layouts, single_register_floats, single_register_int_builtins,
+    single_register_integers, Backend, Env, FloatOrdering, Relocation,
 };
 use bumpalo::collections::{CollectIn, Vec};
 use roc_builtins::bitcode::{self, FloatWidth, IntWidth};
-use roc_collections::all::MutMap;
+use roc_collections::all::{MutMap, MutSet};
 use roc_error_macros::internal_error;
+use roc_module::low_level::LowLevel;
 use roc_module::symbol::{Interns, ModuleId, Symbol};
 use roc_mono::code_gen_help::{CallerProc, CodeGenHelp, HelperOp};
 use roc_mono::ir::{
-    BranchInfo, HigherOrderLowLevel, JoinPointId, ListLiteralElement, Literal, Param, ProcLayout,
-    SelfRecursive, Stmt,
+    BranchInfo, CrashTag, HigherOrderLowLevel, JoinPointId, ListLiteralElement, Literal, Param,
+    ProcLayout, SelfRecursive, Stmt,
 };
 use roc_mono::layout::{
-    Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, TagIdIntType, UnionLayout,
+    Builtin, InLayout, LambdaName, Layout, LayoutInterner, STLayoutInterner, TagIdIntType,
+    UnionLayout,
 };
 use roc_mono::low_level::HigherOrder;
 use roc_target::TargetInfo;
+use std::fmt;
 use std::marker::PhantomData;
 
 pub(crate) mod aarch64;
+mod code_buffer;
 #[cfg(test)]
 mod disassembler_test_macro;
+mod emit_trace;
 pub(crate) mod storage;
 pub(crate) mod x86_64;
 
+use code_buffer::{CodeBuffer, Label};
+use emit_trace::EmitTrace;
 use storage::{RegStorage, StorageManager};
 
+/// Refcounts are stored as negative numbers, where `isize::MIN` represents a refcount of 1.
+const REFCOUNT_ONE: i64 = i64::MIN;
+
+/// Copies of more bytes than this use `Assembler::copy_base32_to_mem_offset32` instead of a pair
+/// of movs per 8 bytes, which would blow up the code size of functions handling big records.
+const UNROLLED_COPY_LIMIT: u32 = 64;
+
 // TODO: on all number functions double check and deal with over/underflow.
 
 #[derive(Debug, Clone, Copy)]
@@ -37,6 +52,19 @@ pub enum RegisterWidth {
     W64,
 }
 
+impl RegisterWidth {
+    /// The register width that holds an int of this width, which must fit in a register
+    fn of_int(int_width: IntWidth) -> Self {
+        match int_width.stack_size() {
+            8 => RegisterWidth::W64,
+            4 => RegisterWidth::W32,
+            2 => RegisterWidth::W16,
+            1 => RegisterWidth::W8,
+            _ => internal_error!("{int_width:?} does not fit in a register"),
+        }
+    }
+}
+
 pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<GeneralReg, FloatReg>>:
     Sized + Copy
 {
@@ -64,15 +92,20 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
         !Self::float_callee_saved(reg)
     }
 
+    /// Emit the function's prologue, recording in `unwind` how each instruction changes the frame.
     fn setup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         general_saved_regs: &[GeneralReg],
         float_saved_regs: &[FloatReg],
         requested_stack_size: i32,
         fn_call_stack_size: i32,
     ) -> i32;
+    /// Emit the function's epilogue, up to the return instruction, recording in `unwind` how it
+    /// changes the frame.
     fn cleanup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         general_saved_regs: &[GeneralReg],
         float_saved_regs: &[FloatReg],
         aligned_stack_size: i32,
@@ -216,8 +249,23 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         ASM: Assembler<GeneralReg, FloatReg>,
         CC: CallConv<GeneralReg, FloatReg, ASM>;
 
+    fn shl_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, imm8: u8);
+    fn shr_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, imm8: u8);
+    fn sar_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, imm8: u8);
+
     fn call(buf: &mut Vec<'_, u8>, relocs: &mut Vec<'_, Relocation>, fn_name: String);
 
+    /// Copies `size` bytes, a multiple of 8, from `src_offset` in the stack frame to `dst + dst_offset`.
+    /// The two must not overlap. This emits a loop or similar, so it is only worth it for copies too
+    /// large to unroll. Every register keeps its value, but the flags may be clobbered.
+    fn copy_base32_to_mem_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: GeneralReg,
+        dst_offset: i32,
+        src_offset: i32,
+        size: u32,
+    );
+
     fn function_pointer(
         buf: &mut Vec<'_, u8>,
         relocs: &mut Vec<'_, Relocation>,
@@ -242,6 +290,12 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         offset: i32,
     ) -> usize;
 
+    /// Jumps by an offset of offset bytes if reg is equal to imm.
+    /// It should always generate the same number of bytes to enable replacement if offset changes.
+    /// It returns the base offset to calculate the jump from (generally the instruction after the jump).
+    fn je_reg64_imm64_imm32(buf: &mut Vec<'_, u8>, reg: GeneralReg, imm: u64, offset: i32)
+        -> usize;
+
     fn mov_freg32_imm32(
         buf: &mut Vec<'_, u8>,
         relocs: &mut Vec<'_, Relocation>,
@@ -257,9 +311,14 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     fn mov_reg64_imm64(buf: &mut Vec<'_, u8>, dst: GeneralReg, imm: i64);
     fn mov_freg64_freg64(buf: &mut Vec<'_, u8>, dst: FloatReg, src: FloatReg);
     fn mov_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg);
+    /// Copies the bits of a float, rather than converting it to an integer
+    fn mov_reg64_freg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: FloatReg);
+    /// Copies the bits of a 32-bit float into the low half of `dst`, zeroing the upper half
+    fn mov_reg32_freg32(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: FloatReg);
 
     // base32 is similar to stack based instructions but they reference the base/frame pointer.
     fn mov_freg64_base32(buf: &mut Vec<'_, u8>, dst: FloatReg, offset: i32);
+    fn mov_freg32_base32(buf: &mut Vec<'_, u8>, dst: FloatReg, offset: i32);
 
     fn mov_reg64_base32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32);
     fn mov_reg32_base32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32);
@@ -267,6 +326,7 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     fn mov_reg8_base32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32);
 
     fn mov_base32_freg64(buf: &mut Vec<'_, u8>, offset: i32, src: FloatReg);
+    fn mov_base32_freg32(buf: &mut Vec<'_, u8>, offset: i32, src: FloatReg);
 
     fn mov_base32_reg64(buf: &mut Vec<'_, u8>, offset: i32, src: GeneralReg);
     fn mov_base32_reg32(buf: &mut Vec<'_, u8>, offset: i32, src: GeneralReg);
@@ -321,6 +381,26 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         offset: i32,
         src: FloatReg,
     );
+    fn mov_mem32_offset32_freg32(
+        buf: &mut Vec<'_, u8>,
+        ptr: GeneralReg,
+        offset: i32,
+        src: FloatReg,
+    );
+
+    // move from memory (a pointer) to float register
+    fn mov_freg64_mem64_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: FloatReg,
+        ptr: GeneralReg,
+        offset: i32,
+    );
+    fn mov_freg32_mem32_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: FloatReg,
+        ptr: GeneralReg,
+        offset: i32,
+    );
 
     /// Sign extends the data at `offset` with `size` as it copies it to `dst`
     /// size must be less than or equal to 8.
@@ -329,6 +409,21 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     /// size must be less than or equal to 8.
     fn movzx_reg64_base32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32, size: u8);
 
+    /// Sign extends the low `register_width` bits of `src` as it copies them to `dst`
+    fn movsx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        register_width: RegisterWidth,
+        dst: GeneralReg,
+        src: GeneralReg,
+    );
+    /// Zero extends the low `register_width` bits of `src` as it copies them to `dst`
+    fn movzx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        register_width: RegisterWidth,
+        dst: GeneralReg,
+        src: GeneralReg,
+    );
+
     fn mov_freg64_stack32(buf: &mut Vec<'_, u8>, dst: FloatReg, offset: i32);
     fn mov_reg64_stack32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32);
     fn mov_stack32_freg64(buf: &mut Vec<'_, u8>, offset: i32, src: FloatReg);
@@ -338,6 +433,23 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     fn sqrt_freg32_freg32(buf: &mut Vec<'_, u8>, dst: FloatReg, src: FloatReg);
 
     fn neg_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg);
+
+    /// Counts the leading zero bits of `src`, which is 64 when it's zero.
+    fn lzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg);
+
+    /// Counts the trailing zero bits of `src`, which is 64 when it's zero.
+    fn tzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg);
+
+    /// Counts the one bits of `src`.
+    fn popcnt_reg64_reg64<'a, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<'a, '_, GeneralReg, FloatReg, ASM, CC>,
+        dst: GeneralReg,
+        src: GeneralReg,
+    ) where
+        ASM: Assembler<GeneralReg, FloatReg>,
+        CC: CallConv<GeneralReg, FloatReg, ASM>;
+
     fn mul_freg32_freg32_freg32(
         buf: &mut Vec<'_, u8>,
         dst: FloatReg,
@@ -452,6 +564,18 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
 
     fn to_float_freg64_reg64(buf: &mut Vec<'_, u8>, dst: FloatReg, src: GeneralReg);
 
+    /// Like `to_float_freg64_reg64` and `to_float_freg32_reg64`, but `src` is unsigned, so it
+    /// may be above `i64::MAX`.
+    fn unsigned_to_float_freg_reg64<'a, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<'a, '_, GeneralReg, FloatReg, ASM, CC>,
+        width: FloatWidth,
+        dst: FloatReg,
+        src: GeneralReg,
+    ) where
+        ASM: Assembler<GeneralReg, FloatReg>,
+        CC: CallConv<GeneralReg, FloatReg, ASM>;
+
     fn to_float_freg32_freg64(buf: &mut Vec<'_, u8>, dst: FloatReg, src: FloatReg);
 
     fn to_float_freg64_freg32(buf: &mut Vec<'_, u8>, dst: FloatReg, src: FloatReg);
@@ -459,12 +583,127 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     fn set_if_overflow(buf: &mut Vec<'_, u8>, dst: GeneralReg);
 
     fn ret(buf: &mut Vec<'_, u8>);
+
+    /// An instruction that does nothing, e.g. for padding code to an alignment
+    fn nop(buf: &mut Vec<'_, u8>);
+
+    /// An instruction that stops the program, for code that must never be reached
+    fn trap(buf: &mut Vec<'_, u8>);
+
+    /// The size of [Assembler::patchable_entry]
+    const PATCHABLE_ENTRY_SIZE: usize;
+
+    /// The start of a proc built with [Env::patchable_prologues], before its prologue. It jumps
+    /// over an indirect jump to the 8 byte address at its end, which starts out as 0 and is
+    /// aligned to 8 bytes, since procs are aligned to 16.
+    fn patchable_entry(buf: &mut Vec<'_, u8>);
+
+    /// Make the [Assembler::patchable_entry] at the start of `code` jump to `target`, e.g. a
+    /// recompiled version of the proc. The address is written before the jump over it is
+    /// removed, so a thread running the entry meanwhile goes to either the old proc or the new one.
+    fn redirect_patchable_entry(code: &mut [u8], target: u64);
 }
 
 pub trait RegTrait:
     Copy + PartialEq + Eq + std::hash::Hash + std::fmt::Debug + std::fmt::Display + 'static
 {
     fn value(&self) -> u8;
+    /// The register's number in DWARF debug info and unwind tables
+    fn dwarf_number(&self) -> u16;
+}
+
+/// The passed function, its argument and return layouts, and the layout of its captures
+type CallerProcKey<'a> = (
+    LambdaName<'a>,
+    &'a [InLayout<'a>],
+    InLayout<'a>,
+    Option<InLayout<'a>>,
+);
+
+/// Code that hardly ever runs, like a switch branch that crashes. It is built on its own and put
+/// after the return by finalize, so that the code that does run falls through instead of jumping
+/// over it.
+struct ColdBlock<'a, GeneralReg: RegTrait> {
+    code: Vec<'a, u8>,
+    relocs: Vec<'a, Relocation>,
+    jump: ColdJump<GeneralReg>,
+}
+
+/// The jump in the proc body to a cold block. Its offset is only known once the proc body is done.
+struct ColdJump<GeneralReg: RegTrait> {
+    location: usize,
+    base_offset: usize,
+    kind: ColdJumpKind<GeneralReg>,
+}
+
+#[derive(Clone, Copy)]
+enum ColdJumpKind<GeneralReg: RegTrait> {
+    Equal(GeneralReg, u64),
+    NotEqual(GeneralReg, u64),
+    Always,
+}
+
+#[derive(Debug, Default)]
+pub struct CodeLayoutStats {
+    pub cold_blocks: usize,
+    pub cold_bytes: usize,
+    /// The size of the procs up to their cold blocks, with their setup and cleanup
+    pub hot_bytes: usize,
+}
+
+impl fmt::Display for CodeLayoutStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total_bytes = self.hot_bytes + self.cold_bytes;
+        let cold_percent = match total_bytes {
+            0 => 0.0,
+            _ => 100.0 * self.cold_bytes as f64 / total_bytes as f64,
+        };
+
+        write!(
+            f,
+            "moved {} cold blocks after the return, taking {} bytes out of the hot paths, \
+            which are {} bytes ({:.1}% of the code)",
+            self.cold_blocks,
+            self.cold_bytes,
+            self.hot_bytes,
+            100.0 - cold_percent
+        )
+    }
+}
+
+/// The relocation, for code that has been moved `by` bytes further into the proc
+fn offset_reloc(reloc: Relocation, by: u64) -> Relocation {
+    match reloc {
+        Relocation::LocalData {
+            offset,
+            data,
+            access,
+        } => Relocation::LocalData {
+            offset: offset + by,
+            data,
+            access,
+        },
+        Relocation::LinkedData { offset, name } => Relocation::LinkedData {
+            offset: offset + by,
+            name,
+        },
+        Relocation::LinkedFunction { offset, name } => Relocation::LinkedFunction {
+            offset: offset + by,
+            name,
+        },
+        Relocation::JmpToReturn { .. } => unreachable!(),
+    }
+}
+
+/// Whether running the statement always ends in a crash, without branching or returning.
+fn always_crashes(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Crash(..) => true,
+        Stmt::Let(_, _, _, following) | Stmt::Refcounting(_, following) => {
+            always_crashes(following)
+        }
+        _ => false,
+    }
 }
 
 pub struct Backend64Bit<
@@ -485,7 +724,10 @@ pub struct Backend64Bit<
     helper_proc_gen: CodeGenHelp<'a>,
     helper_proc_symbols: Vec<'a, (Symbol, ProcLayout<'a>)>,
     caller_procs: Vec<'a, CallerProc<'a>>,
-    buf: Vec<'a, u8>,
+    // A caller proc only depends on the passed function and its layouts,
+    // so every higher order call passing the same function shares one.
+    caller_proc_symbols: MutMap<CallerProcKey<'a>, Symbol>,
+    buf: CodeBuffer<'a>,
     relocs: Vec<'a, Relocation>,
     proc_name: Option<String>,
     is_self_recursive: Option<SelfRecursive>,
@@ -493,11 +735,21 @@ pub struct Backend64Bit<
     last_seen_map: MutMap<Symbol, *const Stmt<'a>>,
     layout_map: MutMap<Symbol, InLayout<'a>>,
     free_map: MutMap<*const Stmt<'a>, Vec<'a, Symbol>>,
+    // The symbols that the statement currently being built uses for the last time.
+    dying_symbols: Vec<'a, Symbol>,
 
     literal_map: MutMap<Symbol, (*const Literal<'a>, *const InLayout<'a>)>,
-    join_map: MutMap<JoinPointId, Vec<'a, (u64, u64)>>,
+    join_map: MutMap<JoinPointId, Label>,
 
     storage_manager: StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
+
+    cold_blocks: Vec<'a, ColdBlock<'a, GeneralReg>>,
+    // Cold blocks are not nested: a switch inside one is built like any other code.
+    building_cold_block: bool,
+    code_layout_stats: CodeLayoutStats,
+
+    // Only set when the ROC_LOG_DEV_EMIT debug flag is on.
+    emit_trace: Option<EmitTrace>,
 }
 
 /// new creates a new backend that will output to the specific Object.
@@ -523,16 +775,22 @@ pub fn new_backend_64bit<
         helper_proc_gen: CodeGenHelp::new(env.arena, target_info, env.module_id),
         helper_proc_symbols: bumpalo::vec![in env.arena],
         caller_procs: bumpalo::vec![in env.arena],
+        caller_proc_symbols: MutMap::default(),
         proc_name: None,
         is_self_recursive: None,
-        buf: bumpalo::vec![in env.arena],
+        buf: CodeBuffer::new_in(env.arena),
         relocs: bumpalo::vec![in env.arena],
         last_seen_map: MutMap::default(),
         layout_map: MutMap::default(),
         free_map: MutMap::default(),
+        dying_symbols: bumpalo::vec![in env.arena],
         literal_map: MutMap::default(),
         join_map: MutMap::default(),
         storage_manager: storage::new_storage_manager(env, target_info),
+        cold_blocks: bumpalo::vec![in env.arena],
+        building_cold_block: false,
+        code_layout_stats: CodeLayoutStats::default(),
+        emit_trace: EmitTrace::from_env(target_info.architecture),
     }
 }
 
@@ -604,8 +862,13 @@ impl<
         self.layout_map.clear();
         self.join_map.clear();
         self.free_map.clear();
+        self.dying_symbols.clear();
         self.buf.clear();
+        self.cold_blocks.clear();
         self.storage_manager.reset();
+        if let Some(trace) = self.emit_trace.as_mut() {
+            trace.reset();
+        }
     }
 
     fn literal_map(&mut self) -> &mut MutMap<Symbol, (*const Literal<'a>, *const InLayout<'a>)> {
@@ -628,20 +891,54 @@ impl<
         &mut self.free_map
     }
 
-    fn finalize(&mut self) -> (Vec<u8>, Vec<Relocation>) {
-        let mut out = bumpalo::vec![in self.env.arena];
+    fn mark_dying_symbols(&mut self, stmt: &Stmt<'a>) {
+        self.dying_symbols.clear();
+        if let Some(syms) = self.free_map.get(&(stmt as *const Stmt<'a>)) {
+            self.dying_symbols.extend_from_slice(syms);
+        }
+    }
+
+    fn live_across_calls(&mut self) -> &mut MutSet<Symbol> {
+        self.storage_manager.live_across_calls_mut()
+    }
+
+    fn trace_stmt(&mut self, stmt: &Stmt<'a>) {
+        if let Some(trace) = self.emit_trace.as_mut() {
+            trace.mark(self.buf.len(), stmt, self.layout_interner);
+        }
+    }
+
+    fn code_layout_stats(&self) -> &CodeLayoutStats {
+        &self.code_layout_stats
+    }
+
+    fn spill_count(&self) -> u32 {
+        self.storage_manager.spill_count()
+    }
+
+    fn finalize(&mut self) -> (Vec<u8>, Vec<Relocation>, Vec<UnwindStep>) {
+        let mut out = CodeBuffer::new_in(self.env.arena);
+        let mut unwind = bumpalo::vec![in self.env.arena];
+
+        // A redirected call leaves before the prologue, so the new version gets the caller's
+        // registers and stack just as they were.
+        if self.env.patchable_prologues {
+            ASM::patchable_entry(&mut out);
+        }
 
         // Setup stack.
         let used_general_regs = self.storage_manager.general_used_callee_saved_regs();
         let used_float_regs = self.storage_manager.float_used_callee_saved_regs();
         let aligned_stack_size = CC::setup_stack(
             &mut out,
+            &mut unwind,
             &used_general_regs,
             &used_float_regs,
             self.storage_manager.stack_size() as i32,
             self.storage_manager.fn_call_stack_size() as i32,
         );
         let setup_offset = out.len();
+        let setup_unwind_len = unwind.len();
 
         // Deal with jumps to the return address.
         let old_relocs = std::mem::replace(&mut self.relocs, bumpalo::vec![in self.env.arena]);
@@ -667,7 +964,6 @@ impl<
 
         // Update jumps to returns.
         let ret_offset = self.buf.len() - end_jmp_size;
-        let mut tmp = bumpalo::vec![in self.env.arena];
         for reloc in old_relocs
             .iter()
             .filter(|reloc| matches!(reloc, Relocation::JmpToReturn { .. }))
@@ -679,7 +975,10 @@ impl<
             } = reloc
             {
                 if *inst_loc as usize + *inst_size as usize != self.buf.len() {
-                    self.update_jmp_imm32_offset(&mut tmp, *inst_loc, *offset, ret_offset as u64);
+                    let jmp_offset = ret_offset as i32 - *offset as i32;
+                    self.buf.patch(*inst_loc as usize, |buf| {
+                        ASM::jmp_imm32(buf, jmp_offset);
+                    });
                 }
             }
         }
@@ -690,6 +989,7 @@ impl<
         // Cleanup stack.
         CC::cleanup_stack(
             &mut out,
+            &mut unwind,
             &used_general_regs,
             &used_float_regs,
             aligned_stack_size,
@@ -703,23 +1003,55 @@ impl<
             old_relocs
                 .into_iter()
                 .filter(|reloc| !matches!(reloc, Relocation::JmpToReturn { .. }))
-                .map(|reloc| match reloc {
-                    Relocation::LocalData { offset, data } => Relocation::LocalData {
-                        offset: offset + setup_offset as u64,
-                        data,
-                    },
-                    Relocation::LinkedData { offset, name } => Relocation::LinkedData {
-                        offset: offset + setup_offset as u64,
-                        name,
-                    },
-                    Relocation::LinkedFunction { offset, name } => Relocation::LinkedFunction {
-                        offset: offset + setup_offset as u64,
-                        name,
-                    },
-                    Relocation::JmpToReturn { .. } => unreachable!(),
-                }),
+                .map(|reloc| offset_reloc(reloc, setup_offset as u64)),
         );
-        (out, out_relocs)
+
+        // Add cold blocks after the return. They run with the stack set up, so the unwind steps
+        // of the setup apply to them again.
+        let cold_offset = out.len();
+        self.code_layout_stats.hot_bytes += cold_offset;
+        if !self.cold_blocks.is_empty() {
+            let setup_unwind: std::vec::Vec<_> = unwind[..setup_unwind_len]
+                .iter()
+                .map(|step| step.at_code_offset(cold_offset as u32))
+                .collect();
+            unwind.extend(setup_unwind);
+        }
+        let cold_blocks =
+            std::mem::replace(&mut self.cold_blocks, bumpalo::vec![in self.env.arena]);
+        for block in cold_blocks {
+            let block_offset = out.len();
+            let jump = block.jump;
+            let jump_offset = (block_offset - (setup_offset + jump.base_offset)) as i32;
+
+            out.patch(setup_offset + jump.location, |buf| match jump.kind {
+                ColdJumpKind::Equal(reg, imm) => {
+                    ASM::je_reg64_imm64_imm32(buf, reg, imm, jump_offset);
+                }
+                ColdJumpKind::NotEqual(reg, imm) => {
+                    ASM::jne_reg64_imm64_imm32(buf, reg, imm, jump_offset);
+                }
+                ColdJumpKind::Always => {
+                    ASM::jmp_imm32(buf, jump_offset);
+                }
+            });
+
+            out.extend(block.code);
+            out_relocs.extend(
+                block
+                    .relocs
+                    .into_iter()
+                    .map(|reloc| offset_reloc(reloc, block_offset as u64)),
+            );
+        }
+
+        if let Some(trace) = &self.emit_trace {
+            let proc_name = self.proc_name.as_deref().unwrap_or("");
+            let body_len = self.buf.len() - end_jmp_size;
+            trace.write_listing(proc_name, &out, setup_offset, body_len, cold_offset);
+        }
+
+        (out.into_bytes(), out_relocs, unwind)
     }
 
     fn load_args(&mut self, args: &'a [(InLayout<'a>, Symbol)], ret_layout: &InLayout<'a>) {
@@ -756,7 +1088,7 @@ impl<
     ) {
         // Save used caller saved regs.
         self.storage_manager
-            .push_used_caller_saved_regs_to_stack(&mut self.buf);
+            .push_used_caller_saved_regs_to_stack(&mut self.buf, &[]);
 
         // Put values in param regs or on top of the stack.
         CC::store_args(
@@ -775,9 +1107,55 @@ impl<
         self.move_return_value(dst, ret_layout)
     }
 
+    fn build_proc_call(
+        &mut self,
+        dst: &Symbol,
+        fn_name: String,
+        args: &[Symbol],
+        arg_layouts: &[InLayout<'a>],
+        ret_layout: &InLayout<'a>,
+    ) {
+        let dying = std::mem::replace(&mut self.dying_symbols, bumpalo::vec![in self.env.arena]);
+
+        // Save used caller saved regs, except the ones that are dead once the call returns.
+        self.storage_manager
+            .push_used_caller_saved_regs_to_stack(&mut self.buf, &dying);
+
+        CC::store_args(
+            &mut self.buf,
+            &mut self.storage_manager,
+            self.layout_interner,
+            dst,
+            args,
+            arg_layouts,
+            ret_layout,
+        );
+
+        ASM::call(&mut self.buf, &mut self.relocs, fn_name);
+
+        self.storage_manager.free_clobbered_symbols(&dying);
+
+        self.move_return_value(dst, ret_layout)
+    }
+
     fn move_return_value(&mut self, dst: &Symbol, ret_layout: &InLayout<'a>) {
         // move return value to dst.
         match *ret_layout {
+            other if self.layout_interner.stack_size(other) == 0 => {
+                // Nothing was returned, but dst still needs storage so that it can be used.
+                self.storage_manager.no_data(dst);
+            }
+            Layout::BOOL => {
+                // The C ABI only defines the lowest byte of a returned bool, and zig leaves garbage
+                // in the rest of the register. Clear it so booleans are always 0 or 1.
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+                ASM::movzx_reg64_reg64(
+                    &mut self.buf,
+                    RegisterWidth::W8,
+                    dst_reg,
+                    CC::GENERAL_RETURN_REGS[0],
+                );
+            }
             single_register_integers!() => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
                 ASM::mov_reg64_reg64(&mut self.buf, dst_reg, CC::GENERAL_RETURN_REGS[0]);
@@ -789,7 +1167,7 @@ impl<
             other => {
                 //
                 match self.layout_interner.get(other) {
-                    Layout::Boxed(_) => {
+                    pointer_layouts!() => {
                         let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
                         ASM::mov_reg64_reg64(&mut self.buf, dst_reg, CC::GENERAL_RETURN_REGS[0]);
                     }
@@ -818,9 +1196,8 @@ impl<
         default_branch: &(BranchInfo<'a>, &'a Stmt<'a>),
         ret_layout: &InLayout<'a>,
     ) {
-        // Switches are a little complex due to keeping track of jumps.
-        // In general I am trying to not have to loop over things multiple times or waste memory.
-        // The basic plan is to make jumps to nowhere and then correct them once we know the correct address.
+        // Each branch jumps to the end of the switch, and is skipped by jumping to the next one.
+        // Neither is built yet when the jumps are, so they go to labels that are bound later.
         let cond_reg = self
             .storage_manager
             .load_to_general_reg(&mut self.buf, cond_symbol);
@@ -831,15 +1208,44 @@ impl<
         let base_literal_map = self.literal_map.clone();
 
         let mut max_branch_stack_size = 0;
-        let mut ret_jumps = bumpalo::vec![in self.env.arena];
-        let mut tmp = bumpalo::vec![in self.env.arena];
+        let end = self.buf.new_label();
+        // The jne of the last branch and where its jump to the end starts, if it was built inline.
+        // A default branch that crashes can go straight to its cold block from there.
+        let mut last_jne = None;
         for (val, _branch_info, stmt) in branches.iter() {
             // TODO: look into branch info and if it matters here.
-            tmp.clear();
-            // Create jump to next branch if cond_sym not equal to value.
-            // Since we don't know the offset yet, set it to 0 and overwrite later.
+
+            // Branches that crash go after the return, so that the others don't have to jump over them.
+            if !self.building_cold_block && always_crashes(stmt) {
+                let location = self.buf.len();
+                let base_offset = ASM::je_reg64_imm64_imm32(&mut self.buf, cond_reg, *val, 0);
+
+                self.storage_manager = base_storage.clone();
+                self.literal_map = base_literal_map.clone();
+                self.build_cold_block(
+                    ColdJump {
+                        location,
+                        base_offset,
+                        kind: ColdJumpKind::Equal(cond_reg, *val),
+                    },
+                    |backend| backend.build_stmt(stmt, ret_layout),
+                );
+
+                max_branch_stack_size =
+                    std::cmp::max(max_branch_stack_size, self.storage_manager.stack_size());
+                base_storage.update_fn_call_stack_size(self.storage_manager.fn_call_stack_size());
+                base_storage.update_used_callee_saved_regs(&self.storage_manager);
+                last_jne = None;
+                continue;
+            }
+
+            // Jump to the next branch if cond_sym is not equal to value.
+            let val = *val;
+            let next_branch = self.buf.new_label();
             let jne_location = self.buf.len();
-            let start_offset = ASM::jne_reg64_imm64_imm32(&mut self.buf, cond_reg, *val, 0);
+            let start_offset = self.buf.jump_to(next_branch, move |buf, offset| {
+                ASM::jne_reg64_imm64_imm32(buf, cond_reg, val, offset)
+            });
 
             // Build all statements in this branch. Using storage as from before any branch.
             self.storage_manager = base_storage.clone();
@@ -847,41 +1253,56 @@ impl<
             self.build_stmt(stmt, ret_layout);
 
             // Build unconditional jump to the end of this switch.
-            // Since we don't know the offset yet, set it to 0 and overwrite later.
             let jmp_location = self.buf.len();
-            let jmp_offset = ASM::jmp_imm32(&mut self.buf, 0x1234_5678);
-            ret_jumps.push((jmp_location, jmp_offset));
+            self.buf
+                .jump_to(end, |buf, offset| ASM::jmp_imm32(buf, offset));
 
-            // Overwrite the original jne with the correct offset.
-            let end_offset = self.buf.len();
-            let jne_offset = end_offset - start_offset;
-            ASM::jne_reg64_imm64_imm32(&mut tmp, cond_reg, *val, jne_offset as i32);
-            for (i, byte) in tmp.iter().enumerate() {
-                self.buf[jne_location + i] = *byte;
-            }
+            self.buf.bind(next_branch);
+            last_jne = Some((jne_location, start_offset, val, jmp_location));
 
             // Update important storage information to avoid overwrites.
             max_branch_stack_size =
                 std::cmp::max(max_branch_stack_size, self.storage_manager.stack_size());
             base_storage.update_fn_call_stack_size(self.storage_manager.fn_call_stack_size());
+            base_storage.update_used_callee_saved_regs(&self.storage_manager);
         }
         self.storage_manager = base_storage;
         self.literal_map = base_literal_map;
         self.storage_manager
             .update_stack_size(max_branch_stack_size);
         let (_branch_info, stmt) = default_branch;
-        self.build_stmt(stmt, ret_layout);
-
-        // Update all return jumps to jump past the default case.
-        let ret_offset = self.buf.len();
-        for (jmp_location, start_offset) in ret_jumps.into_iter() {
-            self.update_jmp_imm32_offset(
-                &mut tmp,
-                jmp_location as u64,
-                start_offset as u64,
-                ret_offset as u64,
-            );
+        if !self.building_cold_block && !branches.is_empty() && always_crashes(stmt) {
+            let jump = match last_jne {
+                Some((location, base_offset, val, jmp_location)) => {
+                    // The last branch no longer needs to jump over the default branch.
+                    self.buf.truncate(jmp_location);
+
+                    ColdJump {
+                        location,
+                        base_offset,
+                        kind: ColdJumpKind::NotEqual(cond_reg, val),
+                    }
+                }
+                None => {
+                    let location = self.buf.len();
+                    let base_offset = ASM::jmp_imm32(&mut self.buf, 0x1234_5678);
+
+                    ColdJump {
+                        location,
+                        base_offset,
+                        kind: ColdJumpKind::Always,
+                    }
+                }
+            };
+
+            self.build_cold_block_keeping_storage(jump, |backend| {
+                backend.build_stmt(stmt, ret_layout)
+            });
+        } else {
+            self.build_stmt(stmt, ret_layout);
         }
+
+        self.buf.bind(end);
     }
 
     fn build_join(
@@ -901,25 +1322,18 @@ impl<
         self.storage_manager
             .setup_joinpoint(self.layout_interner, &mut self.buf, id, parameters);
 
-        self.join_map.insert(*id, bumpalo::vec![in self.env.arena]);
+        let label = self.buf.new_label();
+        self.join_map.insert(*id, label);
 
         // Build remainder of function first. It is what gets run and jumps to join.
         self.build_stmt(remainder, ret_layout);
 
-        let join_location = self.buf.len() as u64;
-
         // Build all statements in body.
+        self.buf.bind(label);
         self.build_stmt(body, ret_layout);
 
-        // Overwrite the all jumps to the joinpoint with the correct offset.
-        let mut tmp = bumpalo::vec![in self.env.arena];
-        for (jmp_location, start_offset) in self
-            .join_map
-            .remove(id)
-            .unwrap_or_else(|| internal_error!("join point not defined"))
-        {
-            tmp.clear();
-            self.update_jmp_imm32_offset(&mut tmp, jmp_location, start_offset, join_location);
+        if self.join_map.remove(id).is_none() {
+            internal_error!("join point not defined");
         }
     }
 
@@ -933,16 +1347,121 @@ impl<
         self.storage_manager
             .setup_jump(self.layout_interner, &mut self.buf, id, args, arg_layouts);
 
-        let jmp_location = self.buf.len();
-        let start_offset = ASM::jmp_imm32(&mut self.buf, 0x1234_5678);
-
-        if let Some(vec) = self.join_map.get_mut(id) {
-            vec.push((jmp_location as u64, start_offset as u64))
+        if let Some(&label) = self.join_map.get(id) {
+            self.buf
+                .jump_to(label, |buf, offset| ASM::jmp_imm32(buf, offset));
         } else {
             internal_error!("Jump: unknown point specified to jump to: {:?}", id);
         }
     }
 
+    fn build_crash(&mut self, msg: &Symbol, tag: CrashTag) {
+        // roc_panic takes the message by pointer, so put it on the stack and load its address.
+        self.storage_manager
+            .ensure_symbol_on_stack(&mut self.buf, msg);
+        let (msg_offset, _) = self.storage_manager.stack_offset_and_size(msg);
+
+        let reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP);
+        ASM::add_reg64_reg64_imm32(&mut self.buf, reg, CC::BASE_PTR_REG, msg_offset);
+
+        let tag_literal = Literal::Int((tag as u32 as i128).to_ne_bytes());
+        self.load_literal(&Symbol::DEV_TMP2, &Layout::U32, &tag_literal);
+
+        self.build_fn_call(
+            &Symbol::DEV_TMP3,
+            "roc_panic".to_string(),
+            &[Symbol::DEV_TMP, Symbol::DEV_TMP2],
+            &[Layout::U64, Layout::U32],
+            &Layout::UNIT,
+        );
+
+        // roc_panic must not return. If it does anyway, stop here instead of running whatever
+        // code comes next.
+        ASM::trap(&mut self.buf);
+
+        self.free_symbol(&Symbol::DEV_TMP);
+        self.free_symbol(&Symbol::DEV_TMP2);
+        self.free_symbol(&Symbol::DEV_TMP3);
+    }
+
+    fn build_panic_on_overflow(
+        &mut self,
+        lowlevel: LowLevel,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) {
+        let int_width = match self.layout_interner.get(*layout) {
+            Layout::Builtin(Builtin::Int(int_width)) if int_width.stack_size() <= 8 => int_width,
+            // Floats don't overflow, and 128-bit integers are added by the builtins
+            _ => return,
+        };
+        let message = match lowlevel {
+            LowLevel::NumAdd => "integer addition overflowed!",
+            LowLevel::NumSub => "integer subtraction overflowed!",
+            _ => internal_error!("no overflow check for {:?}", lowlevel),
+        };
+
+        let buf = &mut self.buf;
+        let overflowed = self
+            .storage_manager
+            .claim_general_reg(buf, &Symbol::DEV_TMP5);
+        let result = self.storage_manager.load_to_general_reg(buf, dst);
+        let src1 = self.storage_manager.load_to_general_reg(buf, src1);
+        let src2 = self.storage_manager.load_to_general_reg(buf, src2);
+
+        self.storage_manager
+            .with_tmp_general_reg(buf, |_, buf, tmp| match int_width.stack_size() {
+                8 if int_width.is_signed() => {
+                    // The sign of the result is wrong: for a sum, it differs from both operands,
+                    // and for a difference, the operands' signs differ and the result's isn't a's.
+                    if lowlevel == LowLevel::NumAdd {
+                        ASM::xor_reg64_reg64_reg64(buf, overflowed, src1, result);
+                        ASM::xor_reg64_reg64_reg64(buf, tmp, src2, result);
+                    } else {
+                        ASM::xor_reg64_reg64_reg64(buf, overflowed, src1, src2);
+                        ASM::xor_reg64_reg64_reg64(buf, tmp, src1, result);
+                    }
+                    ASM::and_reg64_reg64_reg64(buf, overflowed, overflowed, tmp);
+                    ASM::shr_reg64_reg64_imm8(buf, overflowed, overflowed, 63);
+                }
+                8 => {
+                    // The result wrapped around past zero
+                    let (lhs, rhs) = match lowlevel {
+                        LowLevel::NumAdd => (result, src1),
+                        _ => (src1, src2),
+                    };
+                    let op = CompareOperation::LessThan;
+                    ASM::unsigned_compare_reg64(buf, RegisterWidth::W64, op, overflowed, lhs, rhs);
+                }
+                _ => {
+                    // The operation can't overflow 64 bits, so redo it there and check that the
+                    // result still fits in the integer's width.
+                    Self::extend_int_reg64(buf, int_width, overflowed, src1);
+                    Self::extend_int_reg64(buf, int_width, tmp, src2);
+                    if lowlevel == LowLevel::NumAdd {
+                        ASM::add_reg64_reg64_reg64(buf, overflowed, overflowed, tmp);
+                    } else {
+                        ASM::sub_reg64_reg64_reg64(buf, overflowed, overflowed, tmp);
+                    }
+                    Self::extend_int_reg64(buf, int_width, tmp, overflowed);
+                    ASM::neq_reg64_reg64_reg64(
+                        buf,
+                        RegisterWidth::W64,
+                        overflowed,
+                        overflowed,
+                        tmp,
+                    );
+                }
+            });
+
+        self.build_panic_if(ColdJumpKind::NotEqual(overflowed, 0), message);
+        self.free_symbol(&Symbol::DEV_TMP5);
+    }
+
     fn build_num_abs(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>) {
         match self.interner().get(*layout) {
             Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
@@ -987,6 +1506,12 @@ impl<
         }
     }
 
+    fn build_int_add_imm32(&mut self, dst: &Symbol, src: &Symbol, imm32: i32) {
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+        let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
+        ASM::add_reg64_reg64_imm32(&mut self.buf, dst_reg, src_reg, imm32);
+    }
+
     fn build_num_add_checked(
         &mut self,
         dst: &Symbol,
@@ -1112,7 +1637,7 @@ impl<
     fn build_num_div(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>) {
         match self.layout_interner.get(*layout) {
             Layout::Builtin(Builtin::Int(
-                IntWidth::I64 | IntWidth::I32 | IntWidth::I16 | IntWidth::I8,
+                int_width @ (IntWidth::I64 | IntWidth::I32 | IntWidth::I16 | IntWidth::I8),
             )) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
                 let src1_reg = self
@@ -1121,6 +1646,7 @@ impl<
                 let src2_reg = self
                     .storage_manager
                     .load_to_general_reg(&mut self.buf, src2);
+                self.build_panic_on_zero_divisor(src2_reg, int_width);
 
                 ASM::idiv_reg64_reg64_reg64(
                     &mut self.buf,
@@ -1131,7 +1657,7 @@ impl<
                 );
             }
             Layout::Builtin(Builtin::Int(
-                IntWidth::U64 | IntWidth::U32 | IntWidth::U16 | IntWidth::U8,
+                int_width @ (IntWidth::U64 | IntWidth::U32 | IntWidth::U16 | IntWidth::U8),
             )) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
                 let src1_reg = self
@@ -1140,6 +1666,7 @@ impl<
                 let src2_reg = self
                     .storage_manager
                     .load_to_general_reg(&mut self.buf, src2);
+                self.build_panic_on_zero_divisor(src2_reg, int_width);
 
                 ASM::udiv_reg64_reg64_reg64(
                     &mut self.buf,
@@ -1177,8 +1704,7 @@ impl<
     }
 
     fn build_num_sub(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>) {
-        // for the time being, `num_sub` is implemented as wrapping subtraction. In roc, the normal
-        // `sub` should panic on overflow, but we just don't do that yet
+        // The check for overflow comes after, see build_panic_on_overflow
         self.build_num_sub_wrap(dst, src1, src2, layout)
     }
 
@@ -1236,16 +1762,6 @@ impl<
                     &[Layout::STR, Layout::STR],
                     &Layout::BOOL,
                 );
-
-                // mask the result; we pass booleans around as 64-bit values, but branch on 0x0 and 0x1.
-                // Zig gives back values where not all of the upper bits are zero, so we must clear them ourselves
-                let tmp = &Symbol::DEV_TMP;
-                let tmp_reg = self.storage_manager.claim_general_reg(&mut self.buf, tmp);
-                ASM::mov_reg64_imm64(&mut self.buf, tmp_reg, true as i64);
-
-                let width = RegisterWidth::W8; // we're comparing booleans
-                let dst_reg = self.storage_manager.load_to_general_reg(&mut self.buf, dst);
-                ASM::eq_reg64_reg64_reg64(&mut self.buf, width, dst_reg, dst_reg, tmp_reg);
             }
             other => {
                 let ident_ids = self
@@ -1306,14 +1822,15 @@ impl<
                     &Layout::BOOL,
                 );
 
-                // negate the result
+                // negate the result; the returned boolean is already 0 or 1
                 let tmp = &Symbol::DEV_TMP;
                 let tmp_reg = self.storage_manager.claim_general_reg(&mut self.buf, tmp);
                 ASM::mov_reg64_imm64(&mut self.buf, tmp_reg, true as i64);
 
-                let width = RegisterWidth::W8; // we're comparing booleans
                 let dst_reg = self.storage_manager.load_to_general_reg(&mut self.buf, dst);
-                ASM::neq_reg64_reg64_reg64(&mut self.buf, width, dst_reg, dst_reg, tmp_reg);
+                ASM::xor_reg64_reg64_reg64(&mut self.buf, dst_reg, dst_reg, tmp_reg);
+
+                self.free_symbol(tmp);
             }
             x => todo!("NumNeq: layout, {:?}", x),
         }
@@ -1325,12 +1842,10 @@ impl<
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
                 let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
 
-                // Not would usually be implemented as `xor src, -1` followed by `and src, 1`
-                // but since our booleans are represented as `0x101010101010101` currently, we can simply XOR with that
-                let bool_val = [true as u8; 8];
-                ASM::mov_reg64_imm64(&mut self.buf, dst_reg, i64::from_ne_bytes(bool_val));
-                ASM::xor_reg64_reg64_reg64(&mut self.buf, src_reg, src_reg, dst_reg);
-                ASM::mov_reg64_reg64(&mut self.buf, dst_reg, src_reg);
+                // Booleans are always 0 or 1 in a register (see `move_return_value`),
+                // so flipping the lowest bit is enough. This leaves `src` intact for later uses.
+                ASM::mov_reg64_imm64(&mut self.buf, dst_reg, true as i64);
+                ASM::xor_reg64_reg64_reg64(&mut self.buf, dst_reg, dst_reg, src_reg);
             }
             x => todo!("Not: layout, {:?}", x),
         }
@@ -1349,18 +1864,35 @@ impl<
             self.layout_interner.get(*ret_layout),
         ) {
             (
-                Layout::Builtin(Builtin::Int(IntWidth::I32 | IntWidth::I64)),
-                Layout::Builtin(Builtin::Float(FloatWidth::F64)),
+                Layout::Builtin(Builtin::Int(IntWidth::U64)),
+                Layout::Builtin(Builtin::Float(float_width)),
             ) => {
                 let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
-                ASM::to_float_freg64_reg64(&mut self.buf, dst_reg, src_reg);
+                ASM::unsigned_to_float_freg_reg64(
+                    &mut self.buf,
+                    &mut self.storage_manager,
+                    float_width,
+                    dst_reg,
+                    src_reg,
+                );
             }
             (
-                Layout::Builtin(Builtin::Int(IntWidth::I32 | IntWidth::I64)),
-                Layout::Builtin(Builtin::Float(FloatWidth::F32)),
-            ) => {
+                Layout::Builtin(Builtin::Int(int_width)),
+                Layout::Builtin(Builtin::Float(float_width)),
+            ) if int_width.stack_size() <= 8 => {
+                // Every other integer that fits in a register also fits in an I64 once it's
+                // extended, so the signed conversion works for it.
                 let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
-                ASM::to_float_freg32_reg64(&mut self.buf, dst_reg, src_reg);
+
+                self.storage_manager
+                    .with_tmp_general_reg(&mut self.buf, |_, buf, tmp| {
+                        Self::extend_int_reg64(buf, int_width, tmp, src_reg);
+
+                        match float_width {
+                            FloatWidth::F64 => ASM::to_float_freg64_reg64(buf, dst_reg, tmp),
+                            FloatWidth::F32 => ASM::to_float_freg32_reg64(buf, dst_reg, tmp),
+                        }
+                    });
             }
             (
                 Layout::Builtin(Builtin::Float(FloatWidth::F64)),
@@ -1446,34 +1978,290 @@ impl<
         )
     }
 
-    fn build_higher_order_lowlevel(
+    fn build_num_compare(
         &mut self,
         dst: &Symbol,
-        higher_order: &HigherOrderLowLevel<'a>,
-        ret_layout: InLayout<'a>,
+        src1: &Symbol,
+        src2: &Symbol,
+        arg_layout: &InLayout<'a>,
+        float_ordering: FloatOrdering,
     ) {
-        let ident_ids = self
-            .interns
-            .all_ident_ids
-            .get_mut(&self.env.module_id)
-            .unwrap();
-
-        let (inc_n_data_symbol, inc_n_data_linker_data) = self.helper_proc_gen.gen_refcount_proc(
-            ident_ids,
+        // Like in the wasm backend, the order is (src1 != src2) + (src1 < src2),
+        // which is 0 for EQ, 1 for GT and 2 for LT.
+        // The temporary registers are claimed first, so that loading the arguments can't take them.
+        match *arg_layout {
+            single_register_integers!() => {
+                let int_width = arg_layout.try_int_width().unwrap();
+                let register_width = match int_width.stack_size() {
+                    8 => RegisterWidth::W64,
+                    4 => RegisterWidth::W32,
+                    2 => RegisterWidth::W16,
+                    1 => RegisterWidth::W8,
+                    _ => unreachable!(),
+                };
+
+                self.storage_manager.with_tmp_general_reg(
+                    &mut self.buf,
+                    |storage_manager, buf, tmp| {
+                        let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                        let src1_reg = storage_manager.load_to_general_reg(buf, src1);
+                        let src2_reg = storage_manager.load_to_general_reg(buf, src2);
+
+                        ASM::neq_reg64_reg64_reg64(
+                            buf,
+                            register_width,
+                            dst_reg,
+                            src1_reg,
+                            src2_reg,
+                        );
+                        if int_width.is_signed() {
+                            ASM::signed_compare_reg64(
+                                buf,
+                                register_width,
+                                CompareOperation::LessThan,
+                                tmp,
+                                src1_reg,
+                                src2_reg,
+                            );
+                        } else {
+                            ASM::unsigned_compare_reg64(
+                                buf,
+                                register_width,
+                                CompareOperation::LessThan,
+                                tmp,
+                                src1_reg,
+                                src2_reg,
+                            );
+                        }
+                        ASM::add_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp);
+                    },
+                );
+            }
+            Layout::F32 | Layout::F64 => {
+                let float_width = match *arg_layout {
+                    Layout::F32 => FloatWidth::F32,
+                    _ => FloatWidth::F64,
+                };
+
+                match float_ordering {
+                    FloatOrdering::Ieee => self.storage_manager.with_tmp_general_reg(
+                        &mut self.buf,
+                        |storage_manager, buf, tmp| {
+                            let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                            let src1_reg = storage_manager.load_to_float_reg(buf, src1);
+                            let src2_reg = storage_manager.load_to_float_reg(buf, src2);
+
+                            // The float comparisons are all false for NaN, so src1 != src2 is
+                            // computed as !(src1 <= src2 && src1 >= src2)
+                            ASM::cmp_freg_freg_reg64(
+                                buf,
+                                dst_reg,
+                                src1_reg,
+                                src2_reg,
+                                float_width,
+                                CompareOperation::LessThanOrEqual,
+                            );
+                            ASM::cmp_freg_freg_reg64(
+                                buf,
+                                tmp,
+                                src1_reg,
+                                src2_reg,
+                                float_width,
+                                CompareOperation::GreaterThanOrEqual,
+                            );
+                            ASM::and_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp);
+                            ASM::mov_reg64_imm64(buf, tmp, 1);
+                            ASM::xor_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp);
+
+                            ASM::cmp_freg_freg_reg64(
+                                buf,
+                                tmp,
+                                src1_reg,
+                                src2_reg,
+                                float_width,
+                                CompareOperation::LessThan,
+                            );
+                            ASM::add_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp);
+                        },
+                    ),
+                    FloatOrdering::Total => self.storage_manager.with_tmp_general_reg(
+                        &mut self.buf,
+                        |storage_manager, buf, key1| {
+                            storage_manager.with_tmp_general_reg(
+                                buf,
+                                |storage_manager, buf, key2| {
+                                    let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                                    let src1_reg = storage_manager.load_to_float_reg(buf, src1);
+                                    let src2_reg = storage_manager.load_to_float_reg(buf, src2);
+
+                                    Self::total_order_key(
+                                        buf,
+                                        key1,
+                                        src1_reg,
+                                        float_width,
+                                        dst_reg,
+                                    );
+                                    Self::total_order_key(
+                                        buf,
+                                        key2,
+                                        src2_reg,
+                                        float_width,
+                                        dst_reg,
+                                    );
+
+                                    ASM::neq_reg64_reg64_reg64(
+                                        buf,
+                                        RegisterWidth::W64,
+                                        dst_reg,
+                                        key1,
+                                        key2,
+                                    );
+                                    ASM::signed_compare_reg64(
+                                        buf,
+                                        RegisterWidth::W64,
+                                        CompareOperation::LessThan,
+                                        key1,
+                                        key1,
+                                        key2,
+                                    );
+                                    ASM::add_reg64_reg64_reg64(buf, dst_reg, dst_reg, key1);
+                                },
+                            )
+                        },
+                    ),
+                }
+            }
+            Layout::I128 | Layout::U128 | Layout::DEC => {
+                // Dec has no NaN or negative zero, so both orderings are the one of its i128.
+                // The high halves are compared with their sign, and only decide the order when
+                // they differ. Otherwise the low halves, compared without a sign, decide it.
+                let is_signed = *arg_layout != Layout::U128;
+
+                self.storage_manager
+                    .ensure_symbol_on_stack(&mut self.buf, src1);
+                self.storage_manager
+                    .ensure_symbol_on_stack(&mut self.buf, src2);
+                let (offset1, _) = self.storage_manager.stack_offset_and_size(src1);
+                let (offset2, _) = self.storage_manager.stack_offset_and_size(src2);
+
+                self.storage_manager.with_tmp_general_reg(
+                    &mut self.buf,
+                    |storage_manager, buf, half1| {
+                        storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, half2| {
+                            storage_manager.with_tmp_general_reg(
+                                buf,
+                                |storage_manager, buf, is_lt| {
+                                    let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                                    let width = RegisterWidth::W64;
+
+                                    // dst = low halves equal, is_lt = low halves less than
+                                    ASM::mov_reg64_base32(buf, half1, offset1);
+                                    ASM::mov_reg64_base32(buf, half2, offset2);
+                                    ASM::eq_reg64_reg64_reg64(buf, width, dst_reg, half1, half2);
+                                    ASM::unsigned_compare_reg64(
+                                        buf,
+                                        width,
+                                        CompareOperation::LessThan,
+                                        is_lt,
+                                        half1,
+                                        half2,
+                                    );
+
+                                    // Both of those only count if the high halves are equal
+                                    ASM::mov_reg64_base32(buf, half1, offset1 + 8);
+                                    ASM::mov_reg64_base32(buf, half2, offset2 + 8);
+                                    ASM::eq_reg64_reg64_reg64(buf, width, half1, half1, half2);
+                                    ASM::and_reg64_reg64_reg64(buf, dst_reg, dst_reg, half1);
+                                    ASM::and_reg64_reg64_reg64(buf, is_lt, is_lt, half1);
+
+                                    // is_lt |= high halves less than
+                                    ASM::mov_reg64_base32(buf, half1, offset1 + 8);
+                                    if is_signed {
+                                        ASM::signed_compare_reg64(
+                                            buf,
+                                            width,
+                                            CompareOperation::LessThan,
+                                            half1,
+                                            half1,
+                                            half2,
+                                        );
+                                    } else {
+                                        ASM::unsigned_compare_reg64(
+                                            buf,
+                                            width,
+                                            CompareOperation::LessThan,
+                                            half1,
+                                            half1,
+                                            half2,
+                                        );
+                                    }
+                                    ASM::or_reg64_reg64_reg64(buf, is_lt, is_lt, half1);
+
+                                    // dst = (src1 != src2) + (src1 < src2)
+                                    ASM::mov_reg64_imm64(buf, half1, 1);
+                                    ASM::xor_reg64_reg64_reg64(buf, dst_reg, dst_reg, half1);
+                                    ASM::add_reg64_reg64_reg64(buf, dst_reg, dst_reg, is_lt);
+                                },
+                            )
+                        })
+                    },
+                );
+            }
+            x => internal_error!(
+                "NumCompare: not a number layout, {:?}",
+                self.layout_interner.dbg(x)
+            ),
+        }
+    }
+
+    fn build_higher_order_lowlevel(
+        &mut self,
+        dst: &Symbol,
+        higher_order: &HigherOrderLowLevel<'a>,
+        ret_layout: InLayout<'a>,
+    ) {
+        let ident_ids = self
+            .interns
+            .all_ident_ids
+            .get_mut(&self.env.module_id)
+            .unwrap();
+
+        let (inc_n_data_symbol, inc_n_data_linker_data) = self.helper_proc_gen.gen_refcount_proc(
+            ident_ids,
             self.layout_interner,
             Layout::UNIT,
             HelperOp::Inc,
         );
 
-        let caller_proc = CallerProc::new(
-            self.env.arena,
-            self.env.module_id,
-            ident_ids,
-            self.layout_interner,
-            &higher_order.passed_function,
+        let caller_proc_key = (
+            higher_order.passed_function.name,
+            higher_order.passed_function.argument_layouts,
+            higher_order.passed_function.return_layout,
             higher_order.closure_env_layout,
         );
 
+        let caller_proc_symbol = match self.caller_proc_symbols.get(&caller_proc_key) {
+            Some(symbol) => *symbol,
+            None => {
+                let caller_proc = CallerProc::new(
+                    self.env.arena,
+                    self.env.module_id,
+                    ident_ids,
+                    self.layout_interner,
+                    &higher_order.passed_function,
+                    higher_order.closure_env_layout,
+                );
+
+                let symbol = caller_proc.proc_symbol;
+                self.helper_proc_symbols
+                    .push((symbol, caller_proc.proc_layout));
+                self.caller_procs.push(caller_proc);
+                self.caller_proc_symbols.insert(caller_proc_key, symbol);
+
+                symbol
+            }
+        };
+
         match higher_order.op {
             HigherOrder::ListMap { xs } => {
                 let old_element_layout = higher_order.passed_function.argument_layouts[0];
@@ -1494,8 +2282,6 @@ impl<
                 self.load_layout_stack_size(new_element_layout, new_element_width);
 
                 self.helper_proc_symbols.extend(inc_n_data_linker_data);
-                self.helper_proc_symbols
-                    .extend([(caller_proc.proc_symbol, caller_proc.proc_layout)]);
 
                 let inc_n_data_string = self.function_symbol_to_string(
                     inc_n_data_symbol,
@@ -1505,14 +2291,12 @@ impl<
                 );
 
                 let caller_string = self.function_symbol_to_string(
-                    caller_proc.proc_symbol,
+                    caller_proc_symbol,
                     std::iter::empty(),
                     None,
                     Layout::UNIT,
                 );
 
-                self.caller_procs.push(caller_proc);
-
                 let inc_n_data = Symbol::DEV_TMP5;
                 self.build_fn_pointer(&inc_n_data, inc_n_data_string);
 
@@ -1616,6 +2400,83 @@ impl<
         self.storage_manager.list_len(&mut self.buf, dst, list);
     }
 
+    fn build_list_get_capacity(&mut self, dst: &Symbol, list: &Symbol) {
+        // For a seamless slice, the capacity field holds the (negative) refcount pointer
+        // and the slice's capacity is its length. Branch-free, that is:
+        // capacity ^ ((capacity ^ length) & (if capacity < 0 then -1 else 0))
+        self.storage_manager
+            .ensure_symbol_on_stack(&mut self.buf, list);
+        let (base_offset, _) = self.storage_manager.stack_offset_and_size(list);
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+
+        self.storage_manager.with_tmp_general_reg(
+            &mut self.buf,
+            |storage_manager, buf, mask_reg| {
+                storage_manager.with_tmp_general_reg(buf, |_, buf, tmp_reg| {
+                    ASM::mov_reg64_base32(buf, dst_reg, base_offset + 16);
+                    ASM::mov_reg64_imm64(buf, mask_reg, 0);
+                    ASM::signed_compare_reg64(
+                        buf,
+                        RegisterWidth::W64,
+                        CompareOperation::LessThan,
+                        mask_reg,
+                        dst_reg,
+                        mask_reg,
+                    );
+                    ASM::neg_reg64_reg64(buf, mask_reg, mask_reg);
+
+                    ASM::mov_reg64_base32(buf, tmp_reg, base_offset + 8);
+                    ASM::xor_reg64_reg64_reg64(buf, tmp_reg, tmp_reg, dst_reg);
+                    ASM::and_reg64_reg64_reg64(buf, tmp_reg, tmp_reg, mask_reg);
+                    ASM::xor_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp_reg);
+                });
+            },
+        );
+    }
+
+    fn build_str_is_empty(&mut self, dst: &Symbol, string: &Symbol) {
+        // Empty if it's a small string of length zero, which means its last byte is exactly 0x80,
+        // or if it's a big string whose length (ignoring the seamless slice bit) is zero.
+        self.storage_manager
+            .ensure_symbol_on_stack(&mut self.buf, string);
+        let (base_offset, _) = self.storage_manager.stack_offset_and_size(string);
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+
+        self.storage_manager
+            .with_tmp_general_reg(&mut self.buf, |storage_manager, buf, tmp1| {
+                storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, tmp2| {
+                    storage_manager.with_tmp_general_reg(buf, |_, buf, tmp3| {
+                        // small string check: the top byte of the capacity field is 0x80
+                        ASM::mov_reg64_base32(buf, tmp1, base_offset + 16);
+                        ASM::mov_reg64_imm64(buf, tmp2, 0xFF00_0000_0000_0000_u64 as i64);
+                        ASM::and_reg64_reg64_reg64(buf, tmp1, tmp1, tmp2);
+                        ASM::mov_reg64_imm64(buf, tmp2, i64::MIN);
+                        ASM::eq_reg64_reg64_reg64(buf, RegisterWidth::W64, dst_reg, tmp1, tmp2);
+
+                        // big string check: the capacity is not negative, and the length is zero
+                        ASM::mov_reg64_base32(buf, tmp1, base_offset + 16);
+                        ASM::mov_reg64_imm64(buf, tmp2, 0);
+                        ASM::signed_compare_reg64(
+                            buf,
+                            RegisterWidth::W64,
+                            CompareOperation::GreaterThanOrEqual,
+                            tmp1,
+                            tmp1,
+                            tmp2,
+                        );
+                        ASM::mov_reg64_base32(buf, tmp2, base_offset + 8);
+                        ASM::mov_reg64_imm64(buf, tmp3, i64::MAX);
+                        ASM::and_reg64_reg64_reg64(buf, tmp2, tmp2, tmp3);
+                        ASM::mov_reg64_imm64(buf, tmp3, 0);
+                        ASM::eq_reg64_reg64_reg64(buf, RegisterWidth::W64, tmp2, tmp2, tmp3);
+                        ASM::and_reg64_reg64_reg64(buf, tmp1, tmp1, tmp2);
+
+                        ASM::or_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp1);
+                    });
+                });
+            });
+    }
+
     fn build_list_with_capacity(
         &mut self,
         dst: &Symbol,
@@ -1850,33 +2711,16 @@ impl<
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
     ) {
-        // We want to delegate to the zig builtin, but it takes some extra parameters.
-        // Firstly, it takes the alignment of the list.
-        // Secondly, it takes the stack size of an element.
-        // Thirdly, it takes a pointer that it will write the output element to.
         let list = args[0];
-        let list_layout = arg_layouts[0];
         let index = args[1];
-        let index_layout = arg_layouts[1];
         let elem = args[2];
         let elem_layout = arg_layouts[2];
 
-        // Load list alignment argument (u32).
-        self.load_layout_alignment(list_layout, Symbol::DEV_TMP);
-
-        // Have to pass the input element by pointer, so put it on the stack and load it's address.
+        // Both the in place update and the zig builtin read these from the stack.
         self.storage_manager
             .ensure_symbol_on_stack(&mut self.buf, &elem);
-        let u64_layout = Layout::U64;
-        let (new_elem_offset, _) = self.storage_manager.stack_offset_and_size(&elem);
-        // Load address of output element into register.
-        let reg = self
-            .storage_manager
-            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP2);
-        ASM::add_reg64_reg64_imm32(&mut self.buf, reg, CC::BASE_PTR_REG, new_elem_offset);
-
-        // Load the elements size.
-        self.load_layout_stack_size(elem_layout, Symbol::DEV_TMP3);
+        self.storage_manager
+            .ensure_symbol_on_stack(&mut self.buf, &index);
 
         // Setup the return location.
         let base_offset = self
@@ -1908,33 +2752,78 @@ impl<
             )
         };
 
-        // Load address of output element into register.
-        let reg = self
+        self.build_if_list_unique(
+            &list,
+            |backend| {
+                backend.list_replace_in_place(
+                    &list,
+                    &index,
+                    &elem,
+                    elem_layout,
+                    out_list_offset,
+                    out_elem_offset,
+                )
+            },
+            |backend| {
+                backend.list_replace_with_bitcode(
+                    args,
+                    arg_layouts,
+                    out_list_offset,
+                    out_elem_offset,
+                )
+            },
+        );
+    }
+
+    fn build_list_swap(
+        &mut self,
+        dst: &Symbol,
+        args: &'a [Symbol],
+        arg_layouts: &[InLayout<'a>],
+        elem_layout: InLayout<'a>,
+        ret_layout: &InLayout<'a>,
+    ) {
+        let list = args[0];
+        let list_layout = arg_layouts[0];
+        let index_1 = args[1];
+        let index_2 = args[2];
+
+        // The zig builtin bounds checks both indices, so all that is left to do here is
+        // to tell it whether it may skip copying the list.
+        self.build_list_update_mode(Symbol::DEV_TMP, &list);
+
+        // Load list alignment argument (u32).
+        self.load_layout_alignment(list_layout, Symbol::DEV_TMP2);
+
+        // Load the elements size.
+        self.load_layout_stack_size(elem_layout, Symbol::DEV_TMP3);
+
+        // Setup the return location.
+        let base_offset = self
             .storage_manager
-            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP4);
-        ASM::add_reg64_reg64_imm32(&mut self.buf, reg, CC::BASE_PTR_REG, out_elem_offset);
+            .claim_stack_area(dst, self.layout_interner.stack_size(*ret_layout));
 
         let lowlevel_args = bumpalo::vec![
         in self.env.arena;
             list,
-            Symbol::DEV_TMP,
-            index,
             Symbol::DEV_TMP2,
             Symbol::DEV_TMP3,
-            Symbol::DEV_TMP4,
+            index_1,
+            index_2,
+            Symbol::DEV_TMP,
          ];
         let lowlevel_arg_layouts = [
             list_layout,
             Layout::U32,
-            index_layout,
-            u64_layout,
-            u64_layout,
-            u64_layout,
+            Layout::U64,
+            arg_layouts[1],
+            arg_layouts[2],
+            Layout::U8,
         ];
 
         self.build_fn_call(
-            &Symbol::DEV_TMP5,
-            bitcode::LIST_REPLACE.to_string(),
+            &Symbol::DEV_TMP4,
+            bitcode::LIST_SWAP.to_string(),
             &lowlevel_args,
             &lowlevel_arg_layouts,
             &list_layout,
@@ -1942,18 +2831,17 @@ impl<
         self.free_symbol(&Symbol::DEV_TMP);
         self.free_symbol(&Symbol::DEV_TMP2);
         self.free_symbol(&Symbol::DEV_TMP3);
-        self.free_symbol(&Symbol::DEV_TMP4);
 
-        // Copy from list to the output record.
+        // Return list value from fn call
         self.storage_manager.copy_symbol_to_stack_offset(
             self.layout_interner,
             &mut self.buf,
-            out_list_offset,
-            &Symbol::DEV_TMP5,
-            &list_layout,
+            base_offset,
+            &Symbol::DEV_TMP4,
+            ret_layout,
         );
 
-        self.free_symbol(&Symbol::DEV_TMP5);
+        self.free_symbol(&Symbol::DEV_TMP4);
     }
 
     fn build_list_concat(
@@ -2083,11 +2971,19 @@ impl<
     }
 
     fn build_ptr_cast(&mut self, dst: &Symbol, src: &Symbol) {
-        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
-        self.storage_manager
-            .ensure_symbol_on_stack(&mut self.buf, src);
-        let (offset, _) = self.storage_manager.stack_offset_and_size(src);
-        ASM::add_reg64_reg64_imm32(&mut self.buf, dst_reg, CC::BASE_PTR_REG, offset);
+        if self.storage_manager.is_stored_primitive(src) {
+            let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
+            let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+            ASM::mov_reg64_reg64(&mut self.buf, dst_reg, src_reg);
+        } else {
+            // Structs, lists and unions don't fit in a register, so their address stands in for
+            // them, e.g. when the equality helpers check whether both sides are the same value.
+            let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+            self.storage_manager
+                .ensure_symbol_on_stack(&mut self.buf, src);
+            let (offset, _) = self.storage_manager.stack_offset_and_size(src);
+            ASM::add_reg64_reg64_imm32(&mut self.buf, dst_reg, CC::BASE_PTR_REG, offset);
+        }
     }
 
     fn create_empty_array(&mut self, sym: &Symbol) {
@@ -2107,7 +3003,6 @@ impl<
         element_in_layout: &InLayout<'a>,
         elements: &[ListLiteralElement<'a>],
     ) {
-        let element_layout = self.layout_interner.get(*element_in_layout);
         let element_width = self.layout_interner.stack_size(*element_in_layout) as u64;
 
         // load the total size of the data we want to store (excludes refcount)
@@ -2156,10 +3051,11 @@ impl<
             Self::ptr_write(
                 &mut self.buf,
                 &mut self.storage_manager,
+                self.layout_interner,
                 ptr_reg,
                 element_offset,
                 element_width,
-                element_layout,
+                *element_in_layout,
                 element_symbol,
             );
 
@@ -2229,11 +3125,42 @@ impl<
                 );
             }
             _ => {
-                let union_in_layout = self.layout_interner.insert(Layout::Union(*union_layout));
-                todo!(
-                    "loading from union type: {:?}",
-                    self.layout_interner.dbg(union_in_layout)
-                )
+                // The fields are on the heap, behind the pointer
+                let field_layouts = recursive_tag_fields(union_layout, tag_id);
+                let field_layout = field_layouts[index as usize];
+                let field_offset: u32 = field_layouts[..index as usize]
+                    .iter()
+                    .map(|layout| self.layout_interner.stack_size(*layout))
+                    .sum();
+                let clear_tag_id = union_layout.stores_tag_id_in_pointer(self.target_info);
+                let tag_id_mask = tag_id_pointer_mask(self.target_info);
+
+                let ptr_reg = self
+                    .storage_manager
+                    .load_to_general_reg(&mut self.buf, structure);
+                let layout_interner = &*self.layout_interner;
+
+                self.storage_manager.with_tmp_general_reg(
+                    &mut self.buf,
+                    |storage_manager, buf, tmp_reg| {
+                        if clear_tag_id {
+                            ASM::mov_reg64_imm64(buf, tmp_reg, !tag_id_mask);
+                            ASM::and_reg64_reg64_reg64(buf, tmp_reg, ptr_reg, tmp_reg);
+                            ASM::add_reg64_reg64_imm32(buf, tmp_reg, tmp_reg, field_offset as i32);
+                        } else {
+                            ASM::add_reg64_reg64_imm32(buf, tmp_reg, ptr_reg, field_offset as i32);
+                        }
+
+                        Self::ptr_read(
+                            buf,
+                            storage_manager,
+                            layout_interner,
+                            tmp_reg,
+                            field_layout,
+                            *sym,
+                        );
+                    },
+                );
             }
         }
     }
@@ -2255,10 +3182,11 @@ impl<
         Self::ptr_write(
             &mut self.buf,
             &mut self.storage_manager,
+            self.layout_interner,
             ptr_reg,
             element_offset,
             element_width,
-            self.layout_interner.get(element_layout),
+            element_layout,
             value,
         );
 
@@ -2266,9 +3194,10 @@ impl<
             self.free_symbol(&value);
         }
 
-        // box is just a pointer on the stack
-        let base_offset = self.storage_manager.claim_stack_area(&sym, 8);
-        ASM::mov_base32_reg64(&mut self.buf, base_offset, ptr_reg);
+        // the box is just the pointer. It's kept in a register like any other pointer, so that it
+        // can be stored in a struct, a union or another box, or passed to a function.
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, &sym);
+        ASM::mov_reg64_reg64(&mut self.buf, dst_reg, ptr_reg);
     }
 
     fn expr_box(&mut self, sym: Symbol, value: Symbol, element_layout: InLayout<'a>) {
@@ -2309,13 +3238,70 @@ impl<
     }
 
     fn get_tag_id(&mut self, sym: &Symbol, structure: &Symbol, union_layout: &UnionLayout<'a>) {
-        self.storage_manager.load_union_tag_id(
-            self.layout_interner,
-            &mut self.buf,
-            sym,
-            structure,
-            union_layout,
-        );
+        match union_layout {
+            UnionLayout::NonRecursive(_) => {
+                self.storage_manager.load_union_tag_id(
+                    self.layout_interner,
+                    &mut self.buf,
+                    sym,
+                    structure,
+                    union_layout,
+                );
+            }
+            UnionLayout::NonNullableUnwrapped(_) => {
+                // there is only one tag
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
+                ASM::mov_reg64_imm64(&mut self.buf, dst_reg, 0);
+            }
+            UnionLayout::NullableUnwrapped { nullable_id, .. } => {
+                // the tag is whether the pointer is null, so there's nothing to load
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
+                let ptr_reg = self
+                    .storage_manager
+                    .load_to_general_reg(&mut self.buf, structure);
+
+                ASM::mov_reg64_imm64(&mut self.buf, dst_reg, 0);
+                if *nullable_id {
+                    ASM::eq_reg64_reg64_reg64(
+                        &mut self.buf,
+                        RegisterWidth::W64,
+                        dst_reg,
+                        ptr_reg,
+                        dst_reg,
+                    );
+                } else {
+                    ASM::neq_reg64_reg64_reg64(
+                        &mut self.buf,
+                        RegisterWidth::W64,
+                        dst_reg,
+                        ptr_reg,
+                        dst_reg,
+                    );
+                }
+            }
+            UnionLayout::Recursive(_) => {
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
+                let ptr_reg = self
+                    .storage_manager
+                    .load_to_general_reg(&mut self.buf, structure);
+
+                self.load_pointer_tag_id(dst_reg, ptr_reg, union_layout);
+            }
+            UnionLayout::NullableWrapped { nullable_id, .. } => {
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
+                let ptr_reg = self
+                    .storage_manager
+                    .load_to_general_reg(&mut self.buf, structure);
+                let end = self.buf.new_label();
+
+                ASM::mov_reg64_imm64(&mut self.buf, dst_reg, *nullable_id as i64);
+                self.buf.jump_to(end, move |buf, offset| {
+                    ASM::je_reg64_imm64_imm32(buf, ptr_reg, 0, offset)
+                });
+                self.load_pointer_tag_id(dst_reg, ptr_reg, union_layout);
+                self.buf.bind(end);
+            }
+        }
     }
 
     fn tag(
@@ -2325,14 +3311,21 @@ impl<
         union_layout: &UnionLayout<'a>,
         tag_id: TagIdIntType,
     ) {
-        self.storage_manager.create_union(
-            self.layout_interner,
-            &mut self.buf,
-            sym,
-            union_layout,
-            fields,
-            tag_id,
-        )
+        match union_layout {
+            UnionLayout::NonRecursive(_) => self.storage_manager.create_union(
+                self.layout_interner,
+                &mut self.buf,
+                sym,
+                union_layout,
+                fields,
+                tag_id,
+            ),
+            _ if union_layout.tag_is_null(tag_id) => {
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
+                ASM::mov_reg64_imm64(&mut self.buf, dst_reg, 0);
+            }
+            _ => self.allocate_recursive_tag(sym, fields, union_layout, tag_id),
+        }
     }
 
     fn load_literal(&mut self, sym: &Symbol, layout: &InLayout<'a>, lit: &Literal<'a>) {
@@ -2470,7 +3463,9 @@ impl<
     }
 
     fn return_symbol(&mut self, sym: &Symbol, layout: &InLayout<'a>) {
-        if self.storage_manager.is_stored_primitive(sym) {
+        if self.layout_interner.stack_size(*layout) == 0 {
+            // There's nothing to put in the return registers.
+        } else if self.storage_manager.is_stored_primitive(sym) {
             // Just load it to the correct type of reg as a stand alone value.
             match *layout {
                 single_register_integers!() => {
@@ -2488,7 +3483,7 @@ impl<
                     );
                 }
                 other => match self.layout_interner.get(other) {
-                    Layout::Boxed(_) => {
+                    pointer_layouts!() => {
                         // treat like a 64-bit integer
                         self.storage_manager.load_to_specified_general_reg(
                             &mut self.buf,
@@ -2630,47 +3625,21 @@ impl<
                 // Then we arithmetic shift right, getting the correct sign extension behavior,
                 // then shift logical right to get the bits back into the position they should
                 // be for our particular integer width
-                let sign_extend_shift_amount = 64 - (int_width.stack_size() as i64 * 8);
+                let sign_extend_shift_amount = 64 - (int_width.stack_size() as u8 * 8);
 
-                if sign_extend_shift_amount > 0 {
-                    self.storage_manager.with_tmp_general_reg(
-                        buf,
-                        |storage_manager, buf, tmp_reg| {
-                            ASM::mov_reg64_imm64(buf, tmp_reg, sign_extend_shift_amount);
-                            ASM::shl_reg64_reg64_reg64(
-                                buf,
-                                storage_manager,
-                                src1_reg,
-                                src1_reg,
-                                tmp_reg,
-                            );
-                        },
-                    )
-                }
+                ASM::shl_reg64_reg64_imm8(buf, dst_reg, src1_reg, sign_extend_shift_amount);
 
                 ASM::sar_reg64_reg64_reg64(
                     buf,
                     &mut self.storage_manager,
                     dst_reg,
-                    src1_reg,
+                    dst_reg,
                     src2_reg,
                 );
 
                 if sign_extend_shift_amount > 0 {
                     // shift back if needed
-                    self.storage_manager.with_tmp_general_reg(
-                        &mut self.buf,
-                        |storage_manager, buf, tmp_reg| {
-                            ASM::mov_reg64_imm64(buf, tmp_reg, sign_extend_shift_amount);
-                            ASM::shr_reg64_reg64_reg64(
-                                buf,
-                                storage_manager,
-                                dst_reg,
-                                dst_reg,
-                                tmp_reg,
-                            );
-                        },
-                    )
+                    ASM::shr_reg64_reg64_imm8(buf, dst_reg, dst_reg, sign_extend_shift_amount);
                 }
             }
         }
@@ -2703,6 +3672,168 @@ impl<
         }
     }
 
+    fn build_int_shift_left_imm(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        amount: u8,
+        int_width: IntWidth,
+    ) {
+        let buf = &mut self.buf;
+
+        match int_width {
+            IntWidth::U128 | IntWidth::I128 => internal_error!(
+                "NumShiftLeftBy by an immediate isn't implemented for {:?}",
+                int_width
+            ),
+            _ => {
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+                ASM::shl_reg64_reg64_imm8(buf, dst_reg, src_reg, amount);
+            }
+        }
+    }
+
+    fn build_int_shift_right_imm(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        amount: u8,
+        int_width: IntWidth,
+    ) {
+        let buf = &mut self.buf;
+
+        match int_width {
+            IntWidth::U128 | IntWidth::I128 => internal_error!(
+                "NumShiftRightBy by an immediate isn't implemented for {:?}",
+                int_width
+            ),
+            _ => {
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+                // Sign extend the same way as build_int_shift_right
+                let sign_extend_shift_amount = 64 - (int_width.stack_size() as u8 * 8);
+
+                ASM::shl_reg64_reg64_imm8(buf, dst_reg, src_reg, sign_extend_shift_amount);
+                ASM::sar_reg64_reg64_imm8(buf, dst_reg, dst_reg, amount);
+
+                if sign_extend_shift_amount > 0 {
+                    ASM::shr_reg64_reg64_imm8(buf, dst_reg, dst_reg, sign_extend_shift_amount);
+                }
+            }
+        }
+    }
+
+    fn build_int_shift_right_zero_fill_imm(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        amount: u8,
+        int_width: IntWidth,
+    ) {
+        let buf = &mut self.buf;
+
+        match int_width {
+            IntWidth::U128 | IntWidth::I128 => internal_error!(
+                "NumShiftRightZfBy by an immediate isn't implemented for {:?}",
+                int_width
+            ),
+            _ => {
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+                ASM::shr_reg64_reg64_imm8(buf, dst_reg, src_reg, amount);
+            }
+        }
+    }
+
+    fn build_int_count_leading_zero_bits(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        int_width: IntWidth,
+    ) {
+        let buf = &mut self.buf;
+
+        match int_width {
+            IntWidth::U128 | IntWidth::I128 => internal_error!(
+                "NumCountLeadingZeroBits isn't implemented for {:?}",
+                int_width
+            ),
+            _ => {
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+                // Only the int's own bits count, whatever is in the rest of the register
+                ASM::movzx_reg64_reg64(buf, RegisterWidth::of_int(int_width), dst_reg, src_reg);
+                ASM::lzcnt_reg64_reg64(buf, dst_reg, dst_reg);
+
+                // ...and the zero extension added a leading zero for each bit above them
+                let extension_bits = 64 - 8 * int_width.stack_size() as i32;
+                if extension_bits > 0 {
+                    ASM::sub_reg64_reg64_imm32(buf, dst_reg, dst_reg, extension_bits);
+                }
+            }
+        }
+    }
+
+    fn build_int_count_trailing_zero_bits(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        int_width: IntWidth,
+    ) {
+        let buf = &mut self.buf;
+
+        match int_width {
+            IntWidth::U128 | IntWidth::I128 => internal_error!(
+                "NumCountTrailingZeroBits isn't implemented for {:?}",
+                int_width
+            ),
+            _ => {
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+                ASM::movzx_reg64_reg64(buf, RegisterWidth::of_int(int_width), dst_reg, src_reg);
+
+                // Setting the bit just above the int's own bits stops the count there when the
+                // int is zero
+                let int_bits = 8 * int_width.stack_size();
+                if int_bits < 64 {
+                    self.storage_manager.with_tmp_general_reg(
+                        buf,
+                        |_storage_manager, buf, tmp_reg| {
+                            ASM::mov_reg64_imm64(buf, tmp_reg, 1 << int_bits);
+                            ASM::or_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp_reg);
+                        },
+                    );
+                }
+
+                ASM::tzcnt_reg64_reg64(buf, dst_reg, dst_reg);
+            }
+        }
+    }
+
+    fn build_int_count_one_bits(&mut self, dst: &Symbol, src: &Symbol, int_width: IntWidth) {
+        let buf = &mut self.buf;
+
+        match int_width {
+            IntWidth::U128 | IntWidth::I128 => {
+                internal_error!("NumCountOneBits isn't implemented for {:?}", int_width)
+            }
+            _ => {
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+                // Only the int's own bits count, whatever is in the rest of the register
+                ASM::movzx_reg64_reg64(buf, RegisterWidth::of_int(int_width), dst_reg, src_reg);
+                ASM::popcnt_reg64_reg64(buf, &mut self.storage_manager, dst_reg, dst_reg);
+            }
+        }
+    }
+
     fn build_num_sqrt(&mut self, dst: Symbol, src: Symbol, float_width: FloatWidth) {
         let buf = &mut self.buf;
 
@@ -2724,16 +3855,53 @@ impl<
     ) {
         let buf = &mut self.buf;
 
-        let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
-        let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+        match (source.stack_size(), target.stack_size()) {
+            (16, 16) => {
+                let (src_offset, _) = self.storage_manager.stack_offset_and_size(src);
+                let dst_offset = self.storage_manager.claim_stack_area(dst, 16);
+
+                self.storage_manager
+                    .with_tmp_general_reg(buf, |_, buf, tmp| {
+                        for word in [0, 8] {
+                            ASM::mov_reg64_base32(buf, tmp, src_offset + word);
+                            ASM::mov_base32_reg64(buf, dst_offset + word, tmp);
+                        }
+                    });
+            }
+            (16, _) => {
+                // Only the low word is left after truncating
+                let (src_offset, _) = self.storage_manager.stack_offset_and_size(src);
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
 
-        if source.stack_size() == target.stack_size() {
-            match source.stack_size() {
-                8 => ASM::mov_reg64_reg64(buf, dst_reg, src_reg),
-                _ => todo!("int cast from {source:?} to {target:?}"),
+                ASM::mov_reg64_base32(buf, dst_reg, src_offset);
+                Self::extend_int_reg64(buf, target, dst_reg, dst_reg);
+            }
+            (_, 16) if !source.is_signed() => {
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+                let dst_offset = self.storage_manager.claim_stack_area(dst, 16);
+
+                self.storage_manager
+                    .with_tmp_general_reg(buf, |_, buf, tmp| {
+                        Self::extend_int_reg64(buf, source, tmp, src_reg);
+                        ASM::mov_base32_reg64(buf, dst_offset, tmp);
+                        ASM::mov_reg64_imm64(buf, tmp, 0);
+                        ASM::mov_base32_reg64(buf, dst_offset + 8, tmp);
+                    });
+            }
+            (_, 16) => internal_error!(
+                "NumIntCast from the signed {:?} to the 128-bit {:?} isn't implemented",
+                source,
+                target
+            ),
+            _ => {
+                let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+                // Extending by the source keeps the value when widening, and extending by the
+                // target then wraps it when narrowing, or changes how the sign bit is read.
+                Self::extend_int_reg64(buf, source, dst_reg, src_reg);
+                Self::extend_int_reg64(buf, target, dst_reg, dst_reg);
             }
-        } else {
-            todo!("int cast from {source:?} to {target:?}");
         }
     }
 }
@@ -2749,6 +3917,23 @@ impl<
         CC: CallConv<GeneralReg, FloatReg, ASM>,
     > Backend64Bit<'a, 'r, GeneralReg, FloatReg, ASM, CC>
 {
+    /// Copies the integer of the given width in `src` to `dst`, sign or zero extending it to
+    /// 64 bits so that the whole register holds its value.
+    fn extend_int_reg64(
+        buf: &mut Vec<'a, u8>,
+        int_width: IntWidth,
+        dst: GeneralReg,
+        src: GeneralReg,
+    ) {
+        let register_width = RegisterWidth::of_int(int_width);
+
+        if int_width.is_signed() {
+            ASM::movsx_reg64_reg64(buf, register_width, dst, src);
+        } else {
+            ASM::movzx_reg64_reg64(buf, register_width, dst, src);
+        }
+    }
+
     fn compare(
         &mut self,
         op: CompareOperation,
@@ -2804,6 +3989,138 @@ impl<
         }
     }
 
+    /// Loads the bits of a float into `dst_reg`, in a form where comparing them as signed 64-bit
+    /// integers orders them like IEEE 754 totalOrder: all the bits but the sign are flipped for a
+    /// negative float, so that the more negative it is, the lower it is. A 32-bit float is moved
+    /// to the upper half of the register. `tmp_reg` is overwritten.
+    fn total_order_key(
+        buf: &mut Vec<'a, u8>,
+        dst_reg: GeneralReg,
+        src_reg: FloatReg,
+        float_width: FloatWidth,
+        tmp_reg: GeneralReg,
+    ) {
+        match float_width {
+            FloatWidth::F64 => ASM::mov_reg64_freg64(buf, dst_reg, src_reg),
+            FloatWidth::F32 => {
+                ASM::mov_reg32_freg32(buf, dst_reg, src_reg);
+                ASM::shl_reg64_reg64_imm8(buf, dst_reg, dst_reg, 32);
+            }
+        }
+
+        // dst ^= (dst >> 63 arithmetic) >> 1 logical, which is all ones but the sign if negative
+        ASM::sar_reg64_reg64_imm8(buf, tmp_reg, dst_reg, 63);
+        ASM::shr_reg64_reg64_imm8(buf, tmp_reg, tmp_reg, 1);
+        ASM::xor_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp_reg);
+    }
+
+    /// Loads the tag id of a non-null recursive union into `dst_reg`. It's in the low bits of the
+    /// pointer if there's room for it there, and only otherwise loaded from the heap.
+    fn load_pointer_tag_id(
+        &mut self,
+        dst_reg: GeneralReg,
+        ptr_reg: GeneralReg,
+        union_layout: &UnionLayout<'a>,
+    ) {
+        if union_layout.stores_tag_id_in_pointer(self.target_info) {
+            let tag_id_mask = tag_id_pointer_mask(self.target_info);
+
+            ASM::mov_reg64_imm64(&mut self.buf, dst_reg, tag_id_mask);
+            ASM::and_reg64_reg64_reg64(&mut self.buf, dst_reg, ptr_reg, dst_reg);
+        } else {
+            let id_offset = union_layout
+                .tag_id_offset(self.layout_interner, self.target_info)
+                .unwrap() as i32;
+
+            ASM::mov_reg64_imm64(&mut self.buf, dst_reg, 0);
+            match union_layout.discriminant().stack_size() {
+                1 => ASM::mov_reg8_mem8_offset32(&mut self.buf, dst_reg, ptr_reg, id_offset),
+                _ => ASM::mov_reg16_mem16_offset32(&mut self.buf, dst_reg, ptr_reg, id_offset),
+            }
+        }
+    }
+
+    /// Allocates a tag of a recursive union on the heap and moves its fields there. The tag id
+    /// goes in the low bits of the pointer if there's room for it.
+    fn allocate_recursive_tag(
+        &mut self,
+        sym: &Symbol,
+        fields: &'a [Symbol],
+        union_layout: &UnionLayout<'a>,
+        tag_id: TagIdIntType,
+    ) {
+        let (data_size, _) =
+            union_layout.data_size_and_alignment(self.layout_interner, self.target_info);
+        let alignment =
+            union_layout.allocation_alignment_bytes(self.layout_interner, self.target_info);
+
+        let data_size_symbol = Symbol::DEV_TMP;
+        let data_size_literal = Literal::Int((data_size as i128).to_ne_bytes());
+        self.load_literal(&data_size_symbol, &Layout::U64, &data_size_literal);
+
+        let alignment_symbol = Symbol::DEV_TMP2;
+        let alignment_literal = Literal::Int((alignment as i128).to_ne_bytes());
+        self.load_literal(&alignment_symbol, &Layout::U32, &alignment_literal);
+
+        self.allocate_with_refcount(Symbol::DEV_TMP3, data_size_symbol, alignment_symbol);
+
+        self.free_symbol(&data_size_symbol);
+        self.free_symbol(&alignment_symbol);
+
+        let ptr_reg = self
+            .storage_manager
+            .load_to_general_reg(&mut self.buf, &Symbol::DEV_TMP3);
+
+        let mut field_offset = 0;
+        for (field, field_layout) in fields
+            .iter()
+            .zip(recursive_tag_fields(union_layout, tag_id))
+        {
+            let field_width = self.layout_interner.stack_size(*field_layout);
+
+            Self::ptr_write(
+                &mut self.buf,
+                &mut self.storage_manager,
+                self.layout_interner,
+                ptr_reg,
+                field_offset as i32,
+                field_width as u64,
+                *field_layout,
+                *field,
+            );
+
+            field_offset += field_width;
+        }
+
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
+        ASM::mov_reg64_reg64(&mut self.buf, dst_reg, ptr_reg);
+
+        if union_layout.stores_tag_id_in_pointer(self.target_info) {
+            self.storage_manager
+                .with_tmp_general_reg(&mut self.buf, |_, buf, tmp_reg| {
+                    ASM::mov_reg64_imm64(buf, tmp_reg, tag_id as i64);
+                    ASM::or_reg64_reg64_reg64(buf, dst_reg, dst_reg, tmp_reg);
+                });
+        } else if union_layout.stores_tag_id_as_data(self.target_info) {
+            let id_offset = union_layout
+                .tag_id_offset(self.layout_interner, self.target_info)
+                .unwrap() as i32;
+            let id_size = union_layout.discriminant().stack_size();
+
+            self.storage_manager
+                .with_tmp_general_reg(&mut self.buf, |_, buf, tmp_reg| {
+                    ASM::mov_reg64_imm64(buf, tmp_reg, tag_id as i64);
+
+                    match id_size {
+                        1 => ASM::mov_mem8_offset32_reg8(buf, dst_reg, id_offset, tmp_reg),
+                        _ => ASM::mov_mem16_offset32_reg16(buf, dst_reg, id_offset, tmp_reg),
+                    }
+                });
+        }
+
+        self.free_symbol(&Symbol::DEV_TMP3);
+    }
+
     fn allocate_with_refcount(
         &mut self,
         dst: Symbol,
@@ -2819,6 +4136,315 @@ impl<
         );
     }
 
+    /// Loads whether `list` is uniquely referenced into `cond_reg` (1 if it is, 0 otherwise).
+    /// Lists without an allocation and seamless slices never count as unique.
+    /// Jumps to `not_unique` as soon as it's clear that the list is not unique.
+    fn list_is_unique(
+        buf: &mut CodeBuffer<'a>,
+        cond_reg: GeneralReg,
+        tmp_reg: GeneralReg,
+        list_offset: i32,
+        not_unique: Label,
+    ) {
+        let jne = move |buf: &mut Vec<'_, u8>, offset| {
+            ASM::jne_reg64_imm64_imm32(buf, cond_reg, 1, offset)
+        };
+
+        // A positive capacity rules out both empty lists and seamless slices,
+        // which store a shifted pointer to their allocation there instead.
+        ASM::mov_reg64_base32(buf, tmp_reg, list_offset + 16);
+        ASM::mov_reg64_imm64(buf, cond_reg, 0);
+        ASM::signed_compare_reg64(
+            buf,
+            RegisterWidth::W64,
+            CompareOperation::GreaterThan,
+            cond_reg,
+            tmp_reg,
+            cond_reg,
+        );
+        buf.jump_to(not_unique, jne);
+
+        // The refcount is stored right before the first element.
+        ASM::mov_reg64_base32(buf, tmp_reg, list_offset);
+        ASM::sub_reg64_reg64_imm32(buf, tmp_reg, tmp_reg, 8);
+        ASM::mov_reg64_mem64_offset32(buf, tmp_reg, tmp_reg, 0);
+        ASM::mov_reg64_imm64(buf, cond_reg, REFCOUNT_ONE);
+        ASM::eq_reg64_reg64_reg64(buf, RegisterWidth::W64, cond_reg, tmp_reg, cond_reg);
+        buf.jump_to(not_unique, jne);
+    }
+
+    /// Loads the zig `UpdateMode` for `list` into `dst`: `InPlace` (1) if the list is unique
+    /// and `Immutable` (0) otherwise.
+    fn build_list_update_mode(&mut self, dst: Symbol, list: &Symbol) {
+        let (list_offset, _) = self.storage_manager.stack_offset_and_size(list);
+        let cond_reg = self.storage_manager.claim_general_reg(&mut self.buf, &dst);
+        let tmp_reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP2);
+
+        // Bailing out early leaves `cond_reg` at 0, which is exactly `Immutable`.
+        let end = self.buf.new_label();
+        Self::list_is_unique(&mut self.buf, cond_reg, tmp_reg, list_offset, end);
+        self.buf.bind(end);
+
+        self.free_symbol(&Symbol::DEV_TMP2);
+    }
+
+    /// Builds `in_place` for when `list` is uniquely referenced and `fallback` otherwise.
+    /// Both start from the same storage state, which is restored once they rejoin,
+    /// so any symbol they define must also be freed before they return.
+    fn build_if_list_unique(
+        &mut self,
+        list: &Symbol,
+        in_place: impl FnOnce(&mut Self),
+        fallback: impl FnOnce(&mut Self),
+    ) {
+        // The fallback calls into zig, so get caller saved registers out of the way up front.
+        // Otherwise only one of the paths would spill them.
+        self.storage_manager
+            .push_used_caller_saved_regs_to_stack(&mut self.buf, &[]);
+
+        let (list_offset, _) = self.storage_manager.stack_offset_and_size(list);
+        let cond_reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP);
+        let tmp_reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP2);
+        let fallback_label = self.buf.new_label();
+        Self::list_is_unique(
+            &mut self.buf,
+            cond_reg,
+            tmp_reg,
+            list_offset,
+            fallback_label,
+        );
+        self.free_symbol(&Symbol::DEV_TMP);
+        self.free_symbol(&Symbol::DEV_TMP2);
+
+        let mut base_storage = self.storage_manager.clone();
+
+        in_place(self);
+        base_storage.update_used_callee_saved_regs(&self.storage_manager);
+        let mut max_stack_size = self.storage_manager.stack_size();
+        let mut max_fn_call_stack_size = self.storage_manager.fn_call_stack_size();
+
+        // Jump over the fallback once done.
+        let end = self.buf.new_label();
+        self.buf
+            .jump_to(end, |buf, offset| ASM::jmp_imm32(buf, offset));
+
+        self.buf.bind(fallback_label);
+        self.storage_manager = base_storage.clone();
+        fallback(self);
+        base_storage.update_used_callee_saved_regs(&self.storage_manager);
+        max_stack_size = std::cmp::max(max_stack_size, self.storage_manager.stack_size());
+        max_fn_call_stack_size = std::cmp::max(
+            max_fn_call_stack_size,
+            self.storage_manager.fn_call_stack_size(),
+        );
+
+        self.storage_manager = base_storage;
+        self.storage_manager.update_stack_size(max_stack_size);
+        self.storage_manager
+            .update_fn_call_stack_size(max_fn_call_stack_size);
+
+        self.buf.bind(end);
+    }
+
+    /// Swaps the element at `index` of a unique `list` with `elem` without calling into zig.
+    /// The list and the old element are written to the return struct at the given offsets.
+    fn list_replace_in_place(
+        &mut self,
+        list: &Symbol,
+        index: &Symbol,
+        elem: &Symbol,
+        elem_layout: InLayout<'a>,
+        out_list_offset: i32,
+        out_elem_offset: i32,
+    ) {
+        let (list_offset, _) = self.storage_manager.stack_offset_and_size(list);
+        let (index_offset, _) = self.storage_manager.stack_offset_and_size(index);
+        let (elem_offset, _) = self.storage_manager.stack_offset_and_size(elem);
+        let elem_width = self.layout_interner.stack_size(elem_layout);
+
+        self.storage_manager.with_tmp_general_reg(
+            &mut self.buf,
+            |storage_manager, buf, elem_ptr| {
+                storage_manager.with_tmp_general_reg(buf, |_storage_manager, buf, tmp| {
+                    // calculate `list_ptr + element_width * index`
+                    ASM::mov_reg64_base32(buf, elem_ptr, index_offset);
+                    ASM::mov_reg64_imm64(buf, tmp, elem_width as i64);
+                    ASM::imul_reg64_reg64_reg64(buf, elem_ptr, elem_ptr, tmp);
+                    ASM::mov_reg64_base32(buf, tmp, list_offset);
+                    ASM::add_reg64_reg64_reg64(buf, elem_ptr, elem_ptr, tmp);
+
+                    Self::copy_mem_to_stack(buf, tmp, elem_ptr, out_elem_offset, elem_width);
+                    Self::copy_stack_to_mem(buf, tmp, elem_offset, elem_ptr, elem_width);
+
+                    // The list itself is reused as is.
+                    for i in (0..24).step_by(8) {
+                        ASM::mov_reg64_base32(buf, tmp, list_offset + i);
+                        ASM::mov_base32_reg64(buf, out_list_offset + i, tmp);
+                    }
+                });
+            },
+        );
+    }
+
+    /// Replaces an element by calling the zig builtin, which copies the list if it is shared.
+    /// The list and the old element are written to the return struct at the given offsets.
+    fn list_replace_with_bitcode(
+        &mut self,
+        args: &'a [Symbol],
+        arg_layouts: &[InLayout<'a>],
+        out_list_offset: i32,
+        out_elem_offset: i32,
+    ) {
+        // We want to delegate to the zig builtin, but it takes some extra parameters.
+        // Firstly, it takes the alignment of the list.
+        // Secondly, it takes the stack size of an element.
+        // Thirdly, it takes a pointer that it will write the output element to.
+        let list = args[0];
+        let list_layout = arg_layouts[0];
+        let index = args[1];
+        let index_layout = arg_layouts[1];
+        let elem = args[2];
+        let elem_layout = arg_layouts[2];
+
+        // Load list alignment argument (u32).
+        self.load_layout_alignment(list_layout, Symbol::DEV_TMP);
+
+        // Have to pass the input element by pointer, it is already on the stack so load it's address.
+        let u64_layout = Layout::U64;
+        let (new_elem_offset, _) = self.storage_manager.stack_offset_and_size(&elem);
+        // Load address of output element into register.
+        let reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP2);
+        ASM::add_reg64_reg64_imm32(&mut self.buf, reg, CC::BASE_PTR_REG, new_elem_offset);
+
+        // Load the elements size.
+        self.load_layout_stack_size(elem_layout, Symbol::DEV_TMP3);
+
+        // Load address of output element into register.
+        let reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP4);
+        ASM::add_reg64_reg64_imm32(&mut self.buf, reg, CC::BASE_PTR_REG, out_elem_offset);
+
+        let lowlevel_args = bumpalo::vec![
+        in self.env.arena;
+            list,
+            Symbol::DEV_TMP,
+            index,
+            Symbol::DEV_TMP2,
+            Symbol::DEV_TMP3,
+            Symbol::DEV_TMP4,
+         ];
+        let lowlevel_arg_layouts = [
+            list_layout,
+            Layout::U32,
+            index_layout,
+            u64_layout,
+            u64_layout,
+            u64_layout,
+        ];
+
+        self.build_fn_call(
+            &Symbol::DEV_TMP5,
+            bitcode::LIST_REPLACE.to_string(),
+            &lowlevel_args,
+            &lowlevel_arg_layouts,
+            &list_layout,
+        );
+        self.free_symbol(&Symbol::DEV_TMP);
+        self.free_symbol(&Symbol::DEV_TMP2);
+        self.free_symbol(&Symbol::DEV_TMP3);
+        self.free_symbol(&Symbol::DEV_TMP4);
+
+        // Copy from list to the output record.
+        self.storage_manager.copy_symbol_to_stack_offset(
+            self.layout_interner,
+            &mut self.buf,
+            out_list_offset,
+            &Symbol::DEV_TMP5,
+            &list_layout,
+        );
+
+        self.free_symbol(&Symbol::DEV_TMP5);
+    }
+
+    /// Copies `size` bytes from the memory `ptr_reg` points to, to the stack at `base_offset`.
+    fn copy_mem_to_stack(
+        buf: &mut Vec<'a, u8>,
+        tmp_reg: GeneralReg,
+        ptr_reg: GeneralReg,
+        base_offset: i32,
+        size: u32,
+    ) {
+        let size = size as i32;
+        let mut copied = 0;
+
+        while size - copied >= 8 {
+            ASM::mov_reg64_mem64_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg64(buf, base_offset + copied, tmp_reg);
+            copied += 8;
+        }
+
+        if size - copied >= 4 {
+            ASM::mov_reg32_mem32_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg32(buf, base_offset + copied, tmp_reg);
+            copied += 4;
+        }
+
+        if size - copied >= 2 {
+            ASM::mov_reg16_mem16_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg16(buf, base_offset + copied, tmp_reg);
+            copied += 2;
+        }
+
+        if size - copied >= 1 {
+            ASM::mov_reg8_mem8_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg8(buf, base_offset + copied, tmp_reg);
+        }
+    }
+
+    /// Copies `size` bytes from the stack at `base_offset` to the memory `ptr_reg` points to.
+    fn copy_stack_to_mem(
+        buf: &mut Vec<'a, u8>,
+        tmp_reg: GeneralReg,
+        base_offset: i32,
+        ptr_reg: GeneralReg,
+        size: u32,
+    ) {
+        let size = size as i32;
+        let mut copied = 0;
+
+        while size - copied >= 8 {
+            ASM::mov_reg64_base32(buf, tmp_reg, base_offset + copied);
+            ASM::mov_mem64_offset32_reg64(buf, ptr_reg, copied, tmp_reg);
+            copied += 8;
+        }
+
+        if size - copied >= 4 {
+            ASM::mov_reg32_base32(buf, tmp_reg, base_offset + copied);
+            ASM::mov_mem32_offset32_reg32(buf, ptr_reg, copied, tmp_reg);
+            copied += 4;
+        }
+
+        if size - copied >= 2 {
+            ASM::mov_reg16_base32(buf, tmp_reg, base_offset + copied);
+            ASM::mov_mem16_offset32_reg16(buf, ptr_reg, copied, tmp_reg);
+            copied += 2;
+        }
+
+        if size - copied >= 1 {
+            ASM::mov_reg8_base32(buf, tmp_reg, base_offset + copied);
+            ASM::mov_mem8_offset32_reg8(buf, ptr_reg, copied, tmp_reg);
+        }
+    }
+
     fn unbox_str_or_list(
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
@@ -2851,47 +4477,79 @@ impl<
 
         let base_offset = storage_manager.claim_stack_area(&dst, stack_size);
 
-        if size - copied >= 8 {
-            for _ in (0..(size - copied)).step_by(8) {
-                ASM::mov_reg64_mem64_offset32(buf, tmp_reg, ptr_reg, copied);
-                ASM::mov_base32_reg64(buf, base_offset, tmp_reg);
+        while size - copied >= 8 {
+            ASM::mov_reg64_mem64_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg64(buf, base_offset + copied, tmp_reg);
 
-                copied += 8;
-            }
+            copied += 8;
         }
 
-        if size - copied > 0 {
-            panic!("value only partially copied");
-        }
-
-        /*
         if size - copied >= 4 {
-            for _ in (0..(size - copied)).step_by(4) {
-                ASM::mov_reg32_base32(buf, reg, from_offset + copied);
-                ASM::mov_base32_reg32(buf, to_offset + copied, reg);
+            ASM::mov_reg32_mem32_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg32(buf, base_offset + copied, tmp_reg);
 
-                copied += 4;
-            }
+            copied += 4;
         }
 
         if size - copied >= 2 {
-            for _ in (0..(size - copied)).step_by(2) {
-                ASM::mov_reg16_base32(buf, reg, from_offset + copied);
-                ASM::mov_base32_reg16(buf, to_offset + copied, reg);
+            ASM::mov_reg16_mem16_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg16(buf, base_offset + copied, tmp_reg);
 
-                copied += 2;
-            }
+            copied += 2;
         }
 
         if size - copied >= 1 {
-            for _ in (0..(size - copied)).step_by(1) {
-                ASM::mov_reg8_base32(buf, reg, from_offset + copied);
-                ASM::mov_base32_reg8(buf, to_offset + copied, reg);
+            ASM::mov_reg8_mem8_offset32(buf, tmp_reg, ptr_reg, copied);
+            ASM::mov_base32_reg8(buf, base_offset + copied, tmp_reg);
+        }
+    }
 
-                copied += 1;
-            }
+    /// Copies `size` bytes from the stack at `from_offset` to memory at `ptr_reg + to_offset`
+    fn copy_stack_to_mem(
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
+        ptr_reg: GeneralReg,
+        to_offset: i32,
+        from_offset: i32,
+        size: u32,
+    ) {
+        let mut copied = 0;
+
+        if size > UNROLLED_COPY_LIMIT {
+            copied = (size - size % 8) as i32;
+            ASM::copy_base32_to_mem_offset32(buf, ptr_reg, to_offset, from_offset, copied as u32);
         }
-        */
+
+        let size = size as i32;
+
+        storage_manager.with_tmp_general_reg(buf, |_storage_manager, buf, tmp_reg| {
+            // a crude memcpy
+            while size - copied >= 8 {
+                ASM::mov_reg64_base32(buf, tmp_reg, from_offset + copied);
+                ASM::mov_mem64_offset32_reg64(buf, ptr_reg, to_offset + copied, tmp_reg);
+
+                copied += 8;
+            }
+
+            if size - copied >= 4 {
+                ASM::mov_reg32_base32(buf, tmp_reg, from_offset + copied);
+                ASM::mov_mem32_offset32_reg32(buf, ptr_reg, to_offset + copied, tmp_reg);
+
+                copied += 4;
+            }
+
+            if size - copied >= 2 {
+                ASM::mov_reg16_base32(buf, tmp_reg, from_offset + copied);
+                ASM::mov_mem16_offset32_reg16(buf, ptr_reg, to_offset + copied, tmp_reg);
+
+                copied += 2;
+            }
+
+            if size - copied >= 1 {
+                ASM::mov_reg8_base32(buf, tmp_reg, from_offset + copied);
+                ASM::mov_mem8_offset32_reg8(buf, ptr_reg, to_offset + copied, tmp_reg);
+            }
+        });
     }
 
     fn ptr_read(
@@ -2906,8 +4564,20 @@ impl<
             Layout::Builtin(builtin) => match builtin {
                 Builtin::Int(int_width) => match int_width {
                     IntWidth::I128 | IntWidth::U128 => {
-                        // can we treat this as 2 u64's?
-                        todo!()
+                        // on the stack, like any other 16-byte value
+                        storage_manager.with_tmp_general_reg(
+                            buf,
+                            |storage_manager, buf, tmp_reg| {
+                                Self::unbox_to_stack(
+                                    buf,
+                                    storage_manager,
+                                    dst,
+                                    16,
+                                    ptr_reg,
+                                    tmp_reg,
+                                );
+                            },
+                        );
                     }
                     IntWidth::I64 | IntWidth::U64 => {
                         let dst_reg = storage_manager.claim_general_reg(buf, &dst);
@@ -2926,9 +4596,13 @@ impl<
                         ASM::mov_reg8_mem8_offset32(buf, dst_reg, ptr_reg, 0);
                     }
                 },
-                Builtin::Float(_) => {
+                Builtin::Float(FloatWidth::F64) => {
+                    let dst_reg = storage_manager.claim_float_reg(buf, &dst);
+                    ASM::mov_freg64_mem64_offset32(buf, dst_reg, ptr_reg, 0);
+                }
+                Builtin::Float(FloatWidth::F32) => {
                     let dst_reg = storage_manager.claim_float_reg(buf, &dst);
-                    ASM::mov_freg64_freg64(buf, dst_reg, CC::FLOAT_RETURN_REGS[0]);
+                    ASM::mov_freg32_mem32_offset32(buf, dst_reg, ptr_reg, 0);
                 }
                 Builtin::Bool => {
                     // the same as an 8-bit integer
@@ -2937,6 +4611,9 @@ impl<
                 }
                 Builtin::Decimal => {
                     // same as 128-bit integer
+                    storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, tmp_reg| {
+                        Self::unbox_to_stack(buf, storage_manager, dst, 16, ptr_reg, tmp_reg);
+                    });
                 }
                 Builtin::Str | Builtin::List(_) => {
                     storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, tmp_reg| {
@@ -2945,36 +4622,56 @@ impl<
                 }
             },
 
-            Layout::Boxed(_) => {
+            pointer_layouts!() => {
                 // the same as 64-bit integer (for 64-bit targets)
                 let dst_reg = storage_manager.claim_general_reg(buf, &dst);
                 ASM::mov_reg64_mem64_offset32(buf, dst_reg, ptr_reg, 0);
             }
 
-            Layout::Struct { .. } => {
+            Layout::LambdaSet(lambda_set) => Self::ptr_read(
+                buf,
+                storage_manager,
+                layout_interner,
+                ptr_reg,
+                lambda_set.runtime_representation(),
+                dst,
+            ),
+
+            Layout::Struct { .. } | Layout::Union(UnionLayout::NonRecursive(_)) => {
                 // put it on the stack
                 let stack_size = layout_interner.stack_size(element_in_layout);
 
-                storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, tmp_reg| {
-                    Self::unbox_to_stack(buf, storage_manager, dst, stack_size, ptr_reg, tmp_reg);
-                });
+                if stack_size == 0 {
+                    storage_manager.no_data(&dst);
+                } else {
+                    storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, tmp_reg| {
+                        Self::unbox_to_stack(
+                            buf,
+                            storage_manager,
+                            dst,
+                            stack_size,
+                            ptr_reg,
+                            tmp_reg,
+                        );
+                    });
+                }
             }
-
-            _ => todo!("unboxing of {:?}", layout_interner.dbg(element_in_layout)),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn ptr_write(
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
+        layout_interner: &STLayoutInterner<'a>,
         ptr_reg: GeneralReg,
         element_offset: i32,
         element_width: u64,
-        element_layout: Layout<'a>,
+        element_in_layout: InLayout<'a>,
         value: Symbol,
     ) {
-        match element_layout {
-            Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
+        match layout_interner.get(element_in_layout) {
+            Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) | pointer_layouts!() => {
                 let sym_reg = storage_manager.load_to_general_reg(buf, &value);
                 ASM::mov_mem64_offset32_reg64(buf, ptr_reg, element_offset, sym_reg);
             }
@@ -2990,41 +4687,163 @@ impl<
                 let sym_reg = storage_manager.load_to_general_reg(buf, &value);
                 ASM::mov_mem8_offset32_reg8(buf, ptr_reg, element_offset, sym_reg);
             }
-            Layout::Builtin(Builtin::Float(FloatWidth::F64 | FloatWidth::F32)) => {
+            Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
                 let sym_reg = storage_manager.load_to_float_reg(buf, &value);
                 ASM::movesd_mem64_offset32_freg64(buf, ptr_reg, element_offset, sym_reg);
             }
+            Layout::Builtin(Builtin::Float(FloatWidth::F32)) => {
+                let sym_reg = storage_manager.load_to_float_reg(buf, &value);
+                ASM::mov_mem32_offset32_freg32(buf, ptr_reg, element_offset, sym_reg);
+            }
+            Layout::LambdaSet(lambda_set) => Self::ptr_write(
+                buf,
+                storage_manager,
+                layout_interner,
+                ptr_reg,
+                element_offset,
+                element_width,
+                lambda_set.runtime_representation(),
+                value,
+            ),
             _ if element_width == 0 => {}
-            _ if element_width > 8 => {
+            _ => {
+                // 128-bit numbers, strings, lists, records and tag unions all live on the stack
                 let (from_offset, size) = storage_manager.stack_offset_and_size(&value);
-                debug_assert!(from_offset % 8 == 0);
-                debug_assert!(size % 8 == 0);
                 debug_assert_eq!(size as u64, element_width);
-                storage_manager.with_tmp_general_reg(buf, |_storage_manager, buf, tmp_reg| {
-                    // a crude memcpy
-                    for i in (0..size as i32).step_by(8) {
-                        ASM::mov_reg64_base32(buf, tmp_reg, from_offset + i);
-                        ASM::mov_mem64_offset32_reg64(buf, ptr_reg, element_offset + i, tmp_reg);
-                    }
-                });
+
+                Self::copy_stack_to_mem(
+                    buf,
+                    storage_manager,
+                    ptr_reg,
+                    element_offset,
+                    from_offset,
+                    size,
+                );
             }
-            x => todo!("copying data to list with layout, {:?}", x),
         }
     }
 
-    /// Updates a jump instruction to a new offset and returns the number of bytes written.
-    fn update_jmp_imm32_offset(
+    /// Build code into a cold block, which finalize puts after the return.
+    /// The jump to the block must already be in the proc body.
+    fn build_cold_block(&mut self, jump: ColdJump<GeneralReg>, build: impl FnOnce(&mut Self)) {
+        let hot_buf = std::mem::replace(&mut self.buf, CodeBuffer::new_in(self.env.arena));
+        let hot_relocs = std::mem::replace(&mut self.relocs, bumpalo::vec![in self.env.arena]);
+        // Trace marks are offsets in the proc body, so the listing just shows cold blocks as a whole.
+        let emit_trace = self.emit_trace.take();
+
+        self.building_cold_block = true;
+        build(self);
+        self.building_cold_block = false;
+
+        self.emit_trace = emit_trace;
+        let code = std::mem::replace(&mut self.buf, hot_buf).into_bytes();
+        let relocs = std::mem::replace(&mut self.relocs, hot_relocs);
+
+        if relocs
+            .iter()
+            .any(|reloc| matches!(reloc, Relocation::JmpToReturn { .. }))
+        {
+            internal_error!("a cold block must crash instead of returning");
+        }
+
+        self.code_layout_stats.cold_blocks += 1;
+        self.code_layout_stats.cold_bytes += code.len();
+        self.cold_blocks.push(ColdBlock { code, relocs, jump });
+    }
+
+    /// Like build_cold_block, but the code after the jump to the block gets the storage from
+    /// before it, as if the block wasn't there.
+    fn build_cold_block_keeping_storage(
         &mut self,
-        tmp: &mut Vec<'a, u8>,
-        jmp_location: u64,
-        base_offset: u64,
-        target_offset: u64,
+        jump: ColdJump<GeneralReg>,
+        build: impl FnOnce(&mut Self),
     ) {
-        tmp.clear();
-        let jmp_offset = target_offset as i32 - base_offset as i32;
-        ASM::jmp_imm32(tmp, jmp_offset);
-        for (i, byte) in tmp.iter().enumerate() {
-            self.buf[jmp_location as usize + i] = *byte;
+        self.build_keeping_storage(|backend| backend.build_cold_block(jump, build));
+    }
+
+    /// Build code that the code after it never runs after, so the code after it gets the storage
+    /// from before it. The stack and callee saved registers it used still count for the proc.
+    fn build_keeping_storage(&mut self, build: impl FnOnce(&mut Self)) {
+        let base_storage = self.storage_manager.clone();
+        let base_literal_map = self.literal_map.clone();
+        build(self);
+
+        let built_storage = std::mem::replace(&mut self.storage_manager, base_storage);
+        self.literal_map = base_literal_map;
+        self.storage_manager
+            .update_stack_size(built_storage.stack_size());
+        self.storage_manager
+            .update_fn_call_stack_size(built_storage.fn_call_stack_size());
+        self.storage_manager
+            .update_used_callee_saved_regs(&built_storage);
+    }
+
+    /// Panics with `message` if the jump is taken, from a cold block so that the code that
+    /// doesn't panic falls through.
+    fn build_panic_if(&mut self, kind: ColdJumpKind<GeneralReg>, message: &'a str) {
+        let panic = |backend: &mut Self| {
+            let msg = Symbol::DEV_TMP4;
+            backend.load_literal(&msg, &Layout::STR, &Literal::Str(message));
+            backend.build_crash(&msg, CrashTag::Roc);
+            backend.free_symbol(&msg);
+        };
+
+        if self.building_cold_block {
+            // Cold blocks aren't nested, so this one jumps over the panic instead
+            let skip = self.buf.new_label();
+            match kind {
+                ColdJumpKind::Equal(reg, imm) => {
+                    self.buf.jump_to(skip, move |buf, offset| {
+                        ASM::jne_reg64_imm64_imm32(buf, reg, imm, offset)
+                    });
+                }
+                ColdJumpKind::NotEqual(reg, imm) => {
+                    self.buf.jump_to(skip, move |buf, offset| {
+                        ASM::je_reg64_imm64_imm32(buf, reg, imm, offset)
+                    });
+                }
+                ColdJumpKind::Always => {}
+            }
+
+            self.build_keeping_storage(panic);
+
+            self.buf.bind(skip);
+        } else {
+            let location = self.buf.len();
+            let base_offset = match kind {
+                ColdJumpKind::Equal(reg, imm) => {
+                    ASM::je_reg64_imm64_imm32(&mut self.buf, reg, imm, 0)
+                }
+                ColdJumpKind::NotEqual(reg, imm) => {
+                    ASM::jne_reg64_imm64_imm32(&mut self.buf, reg, imm, 0)
+                }
+                ColdJumpKind::Always => ASM::jmp_imm32(&mut self.buf, 0x1234_5678),
+            };
+
+            let jump = ColdJump {
+                location,
+                base_offset,
+                kind,
+            };
+            self.build_cold_block_keeping_storage(jump, panic);
+        }
+    }
+
+    /// Panics if the integer in `divisor` is zero, rather than letting the division trap
+    fn build_panic_on_zero_divisor(&mut self, divisor: GeneralReg, int_width: IntWidth) {
+        if int_width.stack_size() == 8 {
+            self.build_panic_if(ColdJumpKind::Equal(divisor, 0), "integer division by zero!");
+        } else {
+            // The bits above the integer's width could be anything
+            let extended = self
+                .storage_manager
+                .claim_general_reg(&mut self.buf, &Symbol::DEV_TMP5);
+            Self::extend_int_reg64(&mut self.buf, int_width, extended, divisor);
+            self.build_panic_if(
+                ColdJumpKind::Equal(extended, 0),
+                "integer division by zero!",
+            );
+            self.free_symbol(&Symbol::DEV_TMP5);
         }
     }
 
@@ -3047,6 +4866,40 @@ impl<
     }
 }
 
+/// The fields of a tag of a recursive union, which are on the heap behind its pointer
+fn recursive_tag_fields<'a>(
+    union_layout: &UnionLayout<'a>,
+    tag_id: TagIdIntType,
+) -> &'a [InLayout<'a>] {
+    match union_layout {
+        UnionLayout::Recursive(tag_layouts) => tag_layouts[tag_id as usize],
+        UnionLayout::NonNullableUnwrapped(field_layouts) => field_layouts,
+        UnionLayout::NullableWrapped {
+            nullable_id,
+            other_tags,
+        } => {
+            debug_assert_ne!(*nullable_id, tag_id);
+
+            if tag_id < *nullable_id {
+                other_tags[tag_id as usize]
+            } else {
+                other_tags[tag_id as usize - 1]
+            }
+        }
+        UnionLayout::NullableUnwrapped { other_fields, .. } => other_fields,
+        UnionLayout::NonRecursive(_) => {
+            internal_error!("a non-recursive union keeps its fields on the stack")
+        }
+    }
+}
+
+/// The low bits of a recursive union's pointer which hold its tag id, when it has few enough tags
+fn tag_id_pointer_mask(target_info: TargetInfo) -> i64 {
+    let (tag_id_bits, _) = UnionLayout::tag_id_pointer_bits_and_mask(target_info);
+
+    (1 << tag_id_bits) - 1
+}
+
 #[macro_export]
 macro_rules! sign_extended_int_builtins {
     () => {
@@ -3095,3 +4948,19 @@ macro_rules! single_register_layouts {
         single_register_integers!() | single_register_floats!()
     };
 }
+
+/// Layouts which are a single pointer at runtime, so they're passed around like a 64-bit integer.
+/// The tag of a recursive union is in the pointer, or on the heap next to the tag's fields.
+#[macro_export]
+macro_rules! pointer_layouts {
+    () => {
+        Layout::Boxed(_)
+            | Layout::RecursivePointer(_)
+            | Layout::Union(
+                UnionLayout::Recursive(_)
+                    | UnionLayout::NonNullableUnwrapped(_)
+                    | UnionLayout::NullableWrapped { .. }
+                    | UnionLayout::NullableUnwrapped { .. },
+            )
+    };
+}
@@ -1,7 +1,7 @@
 use crate::{
-    generic64::{Assembler, CallConv, RegTrait},
-    sign_extended_int_builtins, single_register_floats, single_register_int_builtins,
-    single_register_integers, single_register_layouts, Env,
+    generic64::{Assembler, CallConv, RegTrait, UNROLLED_COPY_LIMIT},
+    pointer_layouts, sign_extended_int_builtins, single_register_floats,
+    single_register_int_builtins, single_register_integers, single_register_layouts, Env,
 };
 use bumpalo::collections::Vec;
 use roc_builtins::bitcode::{FloatWidth, IntWidth};
@@ -121,11 +121,18 @@ pub struct StorageManager<
     general_used_callee_saved_regs: MutSet<GeneralReg>,
     float_used_callee_saved_regs: MutSet<FloatReg>,
 
+    // Symbols that are still needed after some call in the function.
+    // They are given callee saved regs when possible so they don't have to be spilled around calls.
+    live_across_calls: MutSet<Symbol>,
+
     free_stack_chunks: Vec<'a, (i32, u32)>,
     stack_size: u32,
 
     // The amount of extra stack space needed to pass args for function calling.
     fn_call_stack_size: u32,
+
+    // How many values were moved from a register to the stack to free the register.
+    spill_count: u32,
 }
 
 pub fn new_storage_manager<
@@ -153,9 +160,11 @@ pub fn new_storage_manager<
         float_free_regs: bumpalo::vec![in env.arena],
         float_used_regs: bumpalo::vec![in env.arena],
         float_used_callee_saved_regs: MutSet::default(),
+        live_across_calls: MutSet::default(),
         free_stack_chunks: bumpalo::vec![in env.arena],
         stack_size: 0,
         fn_call_stack_size: 0,
+        spill_count: 0,
     }
 }
 
@@ -182,15 +191,21 @@ impl<
         self.float_used_regs.clear();
         self.float_free_regs
             .extend_from_slice(CC::FLOAT_DEFAULT_FREE_REGS);
+        self.live_across_calls.clear();
         self.free_stack_chunks.clear();
         self.stack_size = 0;
         self.fn_call_stack_size = 0;
+        self.spill_count = 0;
     }
 
     pub fn stack_size(&self) -> u32 {
         self.stack_size
     }
 
+    pub fn spill_count(&self) -> u32 {
+        self.spill_count
+    }
+
     pub fn fn_call_stack_size(&self) -> u32 {
         self.fn_call_stack_size
     }
@@ -207,6 +222,10 @@ impl<
         used_regs
     }
 
+    pub fn live_across_calls_mut(&mut self) -> &mut MutSet<Symbol> {
+        &mut self.live_across_calls
+    }
+
     /// Returns true if the symbol is storing a primitive value.
     pub fn is_stored_primitive(&self, sym: &Symbol) -> bool {
         matches!(
@@ -232,6 +251,23 @@ impl<
         }
     }
 
+    /// Get a callee saved general register from the free list.
+    /// Falls back to any general register if none are free.
+    fn get_callee_saved_general_reg(&mut self, buf: &mut Vec<'a, u8>) -> GeneralReg {
+        match self
+            .general_free_regs
+            .iter()
+            .rposition(|reg| CC::general_callee_saved(reg))
+        {
+            Some(pos) => {
+                let reg = self.general_free_regs.remove(pos);
+                self.general_used_callee_saved_regs.insert(reg);
+                reg
+            }
+            None => self.get_general_reg(buf),
+        }
+    }
+
     /// Get a float register from the free list.
     /// Will free data to the stack if necessary to get the register.
     fn get_float_reg(&mut self, buf: &mut Vec<'a, u8>) -> FloatReg {
@@ -249,11 +285,32 @@ impl<
         }
     }
 
+    /// Get a callee saved float register from the free list.
+    /// Falls back to any float register if none are free.
+    fn get_callee_saved_float_reg(&mut self, buf: &mut Vec<'a, u8>) -> FloatReg {
+        match self
+            .float_free_regs
+            .iter()
+            .rposition(|reg| CC::float_callee_saved(reg))
+        {
+            Some(pos) => {
+                let reg = self.float_free_regs.remove(pos);
+                self.float_used_callee_saved_regs.insert(reg);
+                reg
+            }
+            None => self.get_float_reg(buf),
+        }
+    }
+
     /// Claims a general reg for a specific symbol.
     /// They symbol should not already have storage.
     pub fn claim_general_reg(&mut self, buf: &mut Vec<'a, u8>, sym: &Symbol) -> GeneralReg {
         debug_assert_eq!(self.symbol_storage_map.get(sym), None);
-        let reg = self.get_general_reg(buf);
+        let reg = if self.live_across_calls.contains(sym) {
+            self.get_callee_saved_general_reg(buf)
+        } else {
+            self.get_general_reg(buf)
+        };
         self.general_used_regs.push((reg, *sym));
         self.symbol_storage_map.insert(*sym, Reg(General(reg)));
         reg
@@ -263,7 +320,11 @@ impl<
     /// They symbol should not already have storage.
     pub fn claim_float_reg(&mut self, buf: &mut Vec<'a, u8>, sym: &Symbol) -> FloatReg {
         debug_assert_eq!(self.symbol_storage_map.get(sym), None);
-        let reg = self.get_float_reg(buf);
+        let reg = if self.live_across_calls.contains(sym) {
+            self.get_callee_saved_float_reg(buf)
+        } else {
+            self.get_float_reg(buf)
+        };
         self.float_used_regs.push((reg, *sym));
         self.symbol_storage_map.insert(*sym, Reg(Float(reg)));
         reg
@@ -410,6 +471,18 @@ impl<
                 self.free_reference(sym);
                 reg
             }
+            Stack(ReferencedPrimitive {
+                base_offset, size, ..
+            }) if base_offset % 4 == 0 && size == 4 => {
+                // An F32, e.g. a record field. Only its 4 bytes can be read, the rest belongs to
+                // whatever comes after it.
+                let reg = self.get_float_reg(buf);
+                ASM::mov_freg32_base32(buf, reg, base_offset);
+                self.float_used_regs.push((reg, *sym));
+                self.symbol_storage_map.insert(*sym, Reg(Float(reg)));
+                self.free_reference(sym);
+                reg
+            }
             Stack(ReferencedPrimitive { .. }) => {
                 todo!("loading referenced primitives")
             }
@@ -519,6 +592,11 @@ impl<
                 // The primitive is aligned and the data is exactly 8 bytes, treat it like regular stack.
                 ASM::mov_freg64_base32(buf, reg, *base_offset);
             }
+            Stack(ReferencedPrimitive {
+                base_offset, size, ..
+            }) if base_offset % 4 == 0 && *size == 4 => {
+                ASM::mov_freg32_base32(buf, reg, *base_offset);
+            }
             Stack(ReferencedPrimitive { .. }) => {
                 todo!("loading referenced primitives")
             }
@@ -749,12 +827,17 @@ impl<
         let (base_offset, size) = self.stack_offset_and_size(sym);
         debug_assert!(base_offset % 8 == 0);
         debug_assert!(size % 8 == 0);
-        self.with_tmp_general_reg(buf, |_storage_manager, buf, tmp_reg| {
-            for i in (0..size as i32).step_by(8) {
-                ASM::mov_reg64_base32(buf, tmp_reg, base_offset + i);
-                ASM::mov_mem64_offset32_reg64(buf, ret_reg, i, tmp_reg);
-            }
-        });
+
+        if size > UNROLLED_COPY_LIMIT {
+            ASM::copy_base32_to_mem_offset32(buf, ret_reg, 0, base_offset, size);
+        } else {
+            self.with_tmp_general_reg(buf, |_storage_manager, buf, tmp_reg| {
+                for i in (0..size as i32).step_by(8) {
+                    ASM::mov_reg64_base32(buf, tmp_reg, base_offset + i);
+                    ASM::mov_mem64_offset32_reg64(buf, ret_reg, i, tmp_reg);
+                }
+            });
+        }
     }
 
     /// Copies a symbol to the specified stack offset. This is used for things like filling structs.
@@ -806,7 +889,11 @@ impl<
                         let reg = self.load_to_float_reg(buf, sym);
                         ASM::mov_base32_freg64(buf, to_offset, reg);
                     }
-                    FloatWidth::F32 => todo!(),
+                    FloatWidth::F32 => {
+                        debug_assert_eq!(to_offset % 4, 0);
+                        let reg = self.load_to_float_reg(buf, sym);
+                        ASM::mov_base32_freg32(buf, to_offset, reg);
+                    }
                 },
                 Builtin::Bool => {
                     // same as 8-bit integer, but we special-case true/false because these symbols
@@ -826,8 +913,7 @@ impl<
                         }
                     }
                 }
-                Builtin::Decimal => todo!(),
-                Builtin::Str | Builtin::List(_) => {
+                Builtin::Decimal | Builtin::Str | Builtin::List(_) => {
                     let (from_offset, size) = self.stack_offset_and_size(sym);
                     debug_assert_eq!(from_offset % 8, 0);
                     debug_assert_eq!(size % 8, 0);
@@ -835,7 +921,7 @@ impl<
                     self.copy_to_stack_offset(buf, size, from_offset, to_offset)
                 }
             },
-            Layout::Boxed(_) => {
+            pointer_layouts!() => {
                 // like a 64-bit integer
                 debug_assert_eq!(to_offset % 8, 0);
                 let reg = self.load_to_general_reg(buf, sym);
@@ -852,6 +938,13 @@ impl<
                 )
             }
             _ if layout_interner.stack_size(*layout) == 0 => {}
+            Layout::Struct { .. } | Layout::Union(UnionLayout::NonRecursive(_)) => {
+                // Records and tag unions are always on the stack, even when they're 8 bytes or
+                // less, e.g. a record with a single box in it.
+                let (from_offset, size) = self.stack_offset_and_size(sym);
+                debug_assert_eq!(size, layout_interner.stack_size(*layout));
+                self.copy_to_stack_offset(buf, size, from_offset, to_offset)
+            }
             // TODO: Verify this is always true.
             // The dev backend does not deal with refcounting and does not care about if data is safe to memcpy.
             // It is just temporarily storing the value due to needing to free registers.
@@ -877,6 +970,18 @@ impl<
         let mut copied = 0;
         let size = size as i32;
 
+        if size as u32 > UNROLLED_COPY_LIMIT {
+            // Copy all the whole 8 byte chunks at once, leaving the rest to the movs below.
+            copied = size - size % 8;
+            ASM::copy_base32_to_mem_offset32(
+                buf,
+                CC::BASE_PTR_REG,
+                to_offset,
+                from_offset,
+                copied as u32,
+            );
+        }
+
         self.with_tmp_general_reg(buf, |_storage_manager, buf, reg| {
             if size - copied >= 8 {
                 for _ in (0..(size - copied)).step_by(8) {
@@ -1028,6 +1133,7 @@ impl<
         match self.remove_storage_for_sym(sym) {
             Reg(reg_storage) => {
                 debug_assert_eq!(reg_storage, wanted_reg);
+                self.spill_count += 1;
                 let base_offset = self.claim_stack_size(8);
                 match reg_storage {
                     General(reg) => ASM::mov_base32_reg64(buf, base_offset, reg),
@@ -1116,8 +1222,8 @@ impl<
             .insert(*sym, Rc::new((base_offset, size)));
     }
 
-    /// Specifies a no data exists.
-    pub fn no_data_arg(&mut self, sym: &Symbol) {
+    /// Specifies that a symbol has no data, because its layout is zero sized.
+    pub fn no_data(&mut self, sym: &Symbol) {
         self.symbol_storage_map.insert(*sym, NoData);
     }
 
@@ -1139,6 +1245,15 @@ impl<
         self.fn_call_stack_size = max(self.fn_call_stack_size, tmp_size);
     }
 
+    /// Keeps track of the callee saved regs used by a branch that was built from a clone of this storage.
+    /// They still need to be saved and restored by the function even though the branch's storage is dropped.
+    pub fn update_used_callee_saved_regs(&mut self, branch: &Self) {
+        self.general_used_callee_saved_regs
+            .extend(&branch.general_used_callee_saved_regs);
+        self.float_used_callee_saved_regs
+            .extend(&branch.float_used_callee_saved_regs);
+    }
+
     /// Setups a join point.
     /// To do this, each of the join pionts params are given a storage location.
     /// Then those locations are stored.
@@ -1275,6 +1390,36 @@ impl<
         base_offset
     }
 
+    /// claim_stack_area_with_alignment is claim_stack_area for data that must be aligned to
+    /// more than 8 bytes, like a struct with an I128 that a zig builtin writes through a pointer.
+    /// The base pointer is 16 byte aligned, so this only has to pick an offset that is too.
+    pub fn claim_stack_area_with_alignment(
+        &mut self,
+        sym: &Symbol,
+        size: u32,
+        alignment: u32,
+    ) -> i32 {
+        if alignment <= 8 {
+            return self.claim_stack_area(sym, size);
+        }
+        debug_assert_eq!(alignment, 16);
+
+        // Claim 8 extra bytes, so that the chunk has a 16 byte aligned offset with room after it.
+        let chunk_size = size + 8;
+        let chunk_offset = self.claim_stack_size(chunk_size);
+        let base_offset = if chunk_offset % 16 == 0 {
+            chunk_offset
+        } else {
+            chunk_offset + 8
+        };
+
+        self.symbol_storage_map
+            .insert(*sym, Stack(Complex { base_offset, size }));
+        self.allocation_map
+            .insert(*sym, Rc::new((chunk_offset, chunk_size)));
+        base_offset
+    }
+
     /// claim_stack_size claims `amount` bytes from the stack alignind to 8.
     /// This may be free space in the stack or result in increasing the stack size.
     /// It returns base pointer relative offset of the new data.
@@ -1409,27 +1554,99 @@ impl<
         }
     }
 
-    pub fn push_used_caller_saved_regs_to_stack(&mut self, buf: &mut Vec<'a, u8>) {
+    /// Saves the values in caller saved regs before a call, preferring free callee saved regs
+    /// over the stack. `dying` are the symbols that the call uses for the last time.
+    pub fn push_used_caller_saved_regs_to_stack(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        dying: &[Symbol],
+    ) {
         let old_general_used_regs = std::mem::replace(
             &mut self.general_used_regs,
             bumpalo::vec![in self.env.arena],
         );
         for (reg, saved_sym) in old_general_used_regs.into_iter() {
-            if CC::general_caller_saved(&reg) {
+            if !CC::general_caller_saved(&reg) {
+                self.general_used_regs.push((reg, saved_sym));
+            } else if dying.contains(&saved_sym)
+                && !CC::GENERAL_PARAM_REGS.contains(&reg)
+                && !CC::GENERAL_RETURN_REGS.contains(&reg)
+            {
+                // The call is the last use of this value, and setting up the args won't overwrite it.
+                // It can stay where it is until the args are loaded.
+                self.general_used_regs.push((reg, saved_sym));
+            } else if let (Some(Reg(_)), Some(pos)) = (
+                self.symbol_storage_map.get(&saved_sym),
+                self.general_free_regs
+                    .iter()
+                    .rposition(|r| CC::general_callee_saved(r)),
+            ) {
+                // Moving to a free callee saved reg is cheaper than a round trip through the stack.
+                let new_reg = self.general_free_regs.remove(pos);
+                self.general_used_callee_saved_regs.insert(new_reg);
+                ASM::mov_reg64_reg64(buf, new_reg, reg);
                 self.general_free_regs.push(reg);
-                self.free_to_stack(buf, &saved_sym, General(reg));
+                self.general_used_regs.push((new_reg, saved_sym));
+                self.symbol_storage_map
+                    .insert(saved_sym, Reg(General(new_reg)));
             } else {
-                self.general_used_regs.push((reg, saved_sym));
+                self.general_free_regs.push(reg);
+                self.free_to_stack(buf, &saved_sym, General(reg));
             }
         }
         let old_float_used_regs =
             std::mem::replace(&mut self.float_used_regs, bumpalo::vec![in self.env.arena]);
         for (reg, saved_sym) in old_float_used_regs.into_iter() {
-            if CC::float_caller_saved(&reg) {
+            if !CC::float_caller_saved(&reg) {
+                self.float_used_regs.push((reg, saved_sym));
+            } else if dying.contains(&saved_sym)
+                && !CC::FLOAT_PARAM_REGS.contains(&reg)
+                && !CC::FLOAT_RETURN_REGS.contains(&reg)
+            {
+                self.float_used_regs.push((reg, saved_sym));
+            } else if let (Some(Reg(_)), Some(pos)) = (
+                self.symbol_storage_map.get(&saved_sym),
+                self.float_free_regs
+                    .iter()
+                    .rposition(|r| CC::float_callee_saved(r)),
+            ) {
+                let new_reg = self.float_free_regs.remove(pos);
+                self.float_used_callee_saved_regs.insert(new_reg);
+                ASM::mov_freg64_freg64(buf, new_reg, reg);
                 self.float_free_regs.push(reg);
-                self.free_to_stack(buf, &saved_sym, Float(reg));
+                self.float_used_regs.push((new_reg, saved_sym));
+                self.symbol_storage_map
+                    .insert(saved_sym, Reg(Float(new_reg)));
             } else {
-                self.float_used_regs.push((reg, saved_sym));
+                self.float_free_regs.push(reg);
+                self.free_to_stack(buf, &saved_sym, Float(reg));
+            }
+        }
+    }
+
+    /// Frees the values that were left in caller saved regs because a call was their last use.
+    /// The call has clobbered them, so nothing may read them anymore.
+    pub fn free_clobbered_symbols(&mut self, dying: &[Symbol]) {
+        for sym in dying {
+            let in_caller_saved_reg = match self.symbol_storage_map.get(sym) {
+                Some(
+                    Reg(General(reg))
+                    | Stack(Primitive {
+                        reg: Some(General(reg)),
+                        ..
+                    }),
+                ) => CC::general_caller_saved(reg),
+                Some(
+                    Reg(Float(reg))
+                    | Stack(Primitive {
+                        reg: Some(Float(reg)),
+                        ..
+                    }),
+                ) => CC::float_caller_saved(reg),
+                _ => false,
+            };
+            if in_caller_saved_reg {
+                self.free_symbol(sym);
             }
         }
     }
@@ -1476,7 +1693,7 @@ fn is_primitive(layout_interner: &mut STLayoutInterner<'_>, layout: InLayout<'_>
     match layout {
         single_register_layouts!() => true,
         _ => match layout_interner.get(layout) {
-            Layout::Boxed(_) => true,
+            pointer_layouts!() => true,
             Layout::LambdaSet(lambda_set) => {
                 is_primitive(layout_interner, lambda_set.runtime_representation())
             }
@@ -1,13 +1,13 @@
 use crate::generic64::{storage::StorageManager, Assembler, CallConv, RegTrait};
 use crate::{
-    single_register_floats, single_register_int_builtins, single_register_integers,
-    single_register_layouts, Relocation,
+    pointer_layouts, single_register_floats, single_register_int_builtins,
+    single_register_integers, single_register_layouts, unwind::UnwindStep, DataAccess, Relocation,
 };
 use bumpalo::collections::Vec;
-use roc_builtins::bitcode::FloatWidth;
+use roc_builtins::bitcode::{FloatWidth, IntWidth};
 use roc_error_macros::internal_error;
 use roc_module::symbol::Symbol;
-use roc_mono::layout::{InLayout, Layout, LayoutInterner, STLayoutInterner, UnionLayout};
+use roc_mono::layout::{Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, UnionLayout};
 
 use super::{CompareOperation, RegisterWidth};
 
@@ -36,6 +36,21 @@ impl RegTrait for X86_64GeneralReg {
     fn value(&self) -> u8 {
         *self as u8
     }
+
+    fn dwarf_number(&self) -> u16 {
+        // DWARF numbers the first 8 registers in a different order from the instruction encoding
+        match self {
+            X86_64GeneralReg::RAX => 0,
+            X86_64GeneralReg::RDX => 1,
+            X86_64GeneralReg::RCX => 2,
+            X86_64GeneralReg::RBX => 3,
+            X86_64GeneralReg::RSI => 4,
+            X86_64GeneralReg::RDI => 5,
+            X86_64GeneralReg::RBP => 6,
+            X86_64GeneralReg::RSP => 7,
+            _ => *self as u16,
+        }
+    }
 }
 impl std::fmt::Display for X86_64GeneralReg {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -87,6 +102,10 @@ impl RegTrait for X86_64FloatReg {
     fn value(&self) -> u8 {
         *self as u8
     }
+
+    fn dwarf_number(&self) -> u16 {
+        17 + *self as u16
+    }
 }
 impl std::fmt::Display for X86_64FloatReg {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -124,6 +143,9 @@ pub struct X86_64SystemV {}
 
 const STACK_ALIGNMENT: u8 = 16;
 
+/// Stack frames larger than this must be probed one page at a time, or we may skip the guard page.
+const STACK_PROBE_SIZE: i32 = 4096;
+
 impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64SystemV {
     const BASE_PTR_REG: X86_64GeneralReg = X86_64GeneralReg::RBP;
     const STACK_PTR_REG: X86_64GeneralReg = X86_64GeneralReg::RSP;
@@ -218,6 +240,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
     #[inline(always)]
     fn setup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         saved_general_regs: &[X86_64GeneralReg],
         saved_float_regs: &[X86_64FloatReg],
         requested_stack_size: i32,
@@ -225,6 +248,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
     ) -> i32 {
         x86_64_generic_setup_stack(
             buf,
+            unwind,
             saved_general_regs,
             saved_float_regs,
             requested_stack_size,
@@ -235,6 +259,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
     #[inline(always)]
     fn cleanup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         saved_general_regs: &[X86_64GeneralReg],
         saved_float_regs: &[X86_64FloatReg],
         aligned_stack_size: i32,
@@ -242,6 +267,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
     ) {
         x86_64_generic_cleanup_stack(
             buf,
+            unwind,
             saved_general_regs,
             saved_float_regs,
             aligned_stack_size,
@@ -251,7 +277,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
 
     #[inline(always)]
     fn load_args<'a>(
-        _buf: &mut Vec<'a, u8>,
+        buf: &mut Vec<'a, u8>,
         storage_manager: &mut StorageManager<
             'a,
             '_,
@@ -279,7 +305,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         }
 
         for (in_layout, sym) in args.iter() {
-            state.load_arg(storage_manager, layout_interner, *sym, *in_layout);
+            state.load_arg(buf, storage_manager, layout_interner, *sym, *in_layout);
         }
     }
 
@@ -304,8 +330,9 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
 
         if Self::returns_via_arg_pointer(layout_interner, ret_layout) {
             // Save space on the stack for the result we will be return.
-            let base_offset =
-                storage_manager.claim_stack_area(dst, layout_interner.stack_size(*ret_layout));
+            // The callee may rely on its alignment, e.g. zig's parse results with an I128 in them.
+            let (size, alignment) = layout_interner.stack_size_and_alignment(*ret_layout);
+            let base_offset = storage_manager.claim_stack_area_with_alignment(dst, size, alignment);
             // Set the first reg to the address base + offset.
             let ret_reg = Self::GENERAL_PARAM_REGS[general_i];
             general_i += 1;
@@ -350,30 +377,16 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
             }
             x if layout_interner.stack_size(x) == 0 => {}
             x if !Self::returns_via_arg_pointer(layout_interner, &x) => {
-                let (base_offset, size) = storage_manager.stack_offset_and_size(sym);
+                let (base_offset, _) = storage_manager.stack_offset_and_size(sym);
                 debug_assert_eq!(base_offset % 8, 0);
-                if size <= 8 {
-                    X86_64Assembler::mov_reg64_base32(
-                        buf,
-                        Self::GENERAL_RETURN_REGS[0],
-                        base_offset,
-                    );
-                } else if size <= 16 {
-                    X86_64Assembler::mov_reg64_base32(
-                        buf,
-                        Self::GENERAL_RETURN_REGS[0],
-                        base_offset,
-                    );
-                    X86_64Assembler::mov_reg64_base32(
-                        buf,
-                        Self::GENERAL_RETURN_REGS[1],
-                        base_offset + 8,
-                    );
-                } else {
-                    internal_error!(
-                        "types that don't return via arg pointer must be less than 16 bytes"
-                    );
-                }
+                let classes = Self::eightbyte_classes(layout_interner, x);
+                Self::load_eightbytes(
+                    buf,
+                    &classes,
+                    base_offset,
+                    Self::GENERAL_RETURN_REGS,
+                    Self::FLOAT_RETURN_REGS,
+                );
             }
             _ => {
                 // This is a large type returned via the arg pointer.
@@ -410,20 +423,14 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
             x if !Self::returns_via_arg_pointer(layout_interner, &x) => {
                 let size = layout_interner.stack_size(*layout);
                 let offset = storage_manager.claim_stack_area(sym, size);
-                if size <= 8 {
-                    X86_64Assembler::mov_base32_reg64(buf, offset, Self::GENERAL_RETURN_REGS[0]);
-                } else if size <= 16 {
-                    X86_64Assembler::mov_base32_reg64(buf, offset, Self::GENERAL_RETURN_REGS[0]);
-                    X86_64Assembler::mov_base32_reg64(
-                        buf,
-                        offset + 8,
-                        Self::GENERAL_RETURN_REGS[1],
-                    );
-                } else {
-                    internal_error!(
-                        "types that don't return via arg pointer must be less than 16 bytes"
-                    );
-                }
+                let classes = Self::eightbyte_classes(layout_interner, x);
+                Self::store_eightbytes(
+                    buf,
+                    &classes,
+                    offset,
+                    Self::GENERAL_RETURN_REGS,
+                    Self::FLOAT_RETURN_REGS,
+                );
             }
             _ => {
                 // This should have been recieved via an arg pointer.
@@ -462,28 +469,14 @@ impl X64_64SystemVStoreArgs {
             x if layout_interner.stack_size(x) > 16 => {
                 // TODO: Double check this.
                 // Just copy onto the stack.
-                // Use return reg as buffer because it will be empty right now.
-                let (base_offset, size) = storage_manager.stack_offset_and_size(&sym);
-                debug_assert_eq!(base_offset % 8, 0);
-                for i in (0..size as i32).step_by(8) {
-                    X86_64Assembler::mov_reg64_base32(
-                        buf,
-                        Self::GENERAL_RETURN_REGS[0],
-                        base_offset + i,
-                    );
-                    X86_64Assembler::mov_stack32_reg64(
-                        buf,
-                        self.tmp_stack_offset + i,
-                        Self::GENERAL_RETURN_REGS[0],
-                    );
-                }
-                self.tmp_stack_offset += size as i32;
+                let alignment = layout_interner.alignment_bytes(x);
+                self.store_arg_on_stack(buf, storage_manager, sym, alignment);
             }
             other => {
                 // look at the layout in more detail
                 match layout_interner.get(other) {
-                    Layout::Boxed(_) => {
-                        // treat boxed like a 64-bit integer
+                    pointer_layouts!() => {
+                        // treat pointers like a 64-bit integer
                         self.store_arg_general(buf, storage_manager, sym)
                     }
                     Layout::LambdaSet(lambda_set) => self.store_arg(
@@ -493,41 +486,12 @@ impl X64_64SystemVStoreArgs {
                         sym,
                         lambda_set.runtime_representation(),
                     ),
-                    Layout::Struct { .. } => {
-                        // for now, just also store this on the stack
-                        let (base_offset, size) = storage_manager.stack_offset_and_size(&sym);
-                        debug_assert_eq!(base_offset % 8, 0);
-                        for i in (0..size as i32).step_by(8) {
-                            X86_64Assembler::mov_reg64_base32(
-                                buf,
-                                Self::GENERAL_RETURN_REGS[0],
-                                base_offset + i,
-                            );
-                            X86_64Assembler::mov_stack32_reg64(
-                                buf,
-                                self.tmp_stack_offset + i,
-                                Self::GENERAL_RETURN_REGS[0],
-                            );
-                        }
-                        self.tmp_stack_offset += size as i32;
-                    }
-                    Layout::Union(UnionLayout::NonRecursive(_)) => {
-                        // for now, just also store this on the stack
-                        let (base_offset, size) = storage_manager.stack_offset_and_size(&sym);
-                        debug_assert_eq!(base_offset % 8, 0);
-                        for i in (0..size as i32).step_by(8) {
-                            X86_64Assembler::mov_reg64_base32(
-                                buf,
-                                Self::GENERAL_RETURN_REGS[0],
-                                base_offset + i,
-                            );
-                            X86_64Assembler::mov_stack32_reg64(
-                                buf,
-                                self.tmp_stack_offset + i,
-                                Self::GENERAL_RETURN_REGS[0],
-                            );
-                        }
-                        self.tmp_stack_offset += size as i32;
+                    // 128-bit integers are passed like a struct of two 64-bit integers
+                    Layout::Builtin(Builtin::Int(IntWidth::I128 | IntWidth::U128))
+                    | Layout::Builtin(Builtin::Decimal)
+                    | Layout::Struct { .. }
+                    | Layout::Union(UnionLayout::NonRecursive(_)) => {
+                        self.store_arg_eightbytes(buf, storage_manager, layout_interner, sym, other)
                     }
                     _ => {
                         todo!("calling with arg type, {:?}", layout_interner.dbg(other));
@@ -537,6 +501,63 @@ impl X64_64SystemVStoreArgs {
         }
     }
 
+    /// Passes a 128-bit integer, or a struct or union of at most 16 bytes, in registers if there
+    /// are enough of them left.
+    fn store_arg_eightbytes<'a>(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
+        layout_interner: &mut STLayoutInterner<'a>,
+        sym: Symbol,
+        in_layout: InLayout<'a>,
+    ) {
+        let classes = X86_64SystemV::eightbyte_classes(layout_interner, in_layout);
+        let (general_count, float_count) = count_eightbyte_classes(&classes);
+
+        if self.general_i + general_count <= Self::GENERAL_PARAM_REGS.len()
+            && self.float_i + float_count <= Self::FLOAT_PARAM_REGS.len()
+        {
+            let (base_offset, _) = storage_manager.stack_offset_and_size(&sym);
+            debug_assert_eq!(base_offset % 8, 0);
+            X86_64SystemV::load_eightbytes(
+                buf,
+                &classes,
+                base_offset,
+                &Self::GENERAL_PARAM_REGS[self.general_i..],
+                &Self::FLOAT_PARAM_REGS[self.float_i..],
+            );
+            self.general_i += general_count;
+            self.float_i += float_count;
+        } else {
+            // If any part of it doesn't fit in registers, all of it goes on the stack.
+            let alignment = layout_interner.alignment_bytes(in_layout);
+            self.store_arg_on_stack(buf, storage_manager, sym, alignment);
+        }
+    }
+
+    fn store_arg_on_stack<'a>(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
+        sym: Symbol,
+        alignment: u32,
+    ) {
+        self.tmp_stack_offset = align_stack_arg_offset(self.tmp_stack_offset, alignment);
+
+        // Use return reg as buffer because it will be empty right now.
+        let (base_offset, size) = storage_manager.stack_offset_and_size(&sym);
+        debug_assert_eq!(base_offset % 8, 0);
+        for i in (0..size as i32).step_by(8) {
+            X86_64Assembler::mov_reg64_base32(buf, Self::GENERAL_RETURN_REGS[0], base_offset + i);
+            X86_64Assembler::mov_stack32_reg64(
+                buf,
+                self.tmp_stack_offset + i,
+                Self::GENERAL_RETURN_REGS[0],
+            );
+        }
+        self.tmp_stack_offset += size as i32;
+    }
+
     fn store_arg_general<'a>(
         &mut self,
         buf: &mut Vec<'a, u8>,
@@ -600,6 +621,7 @@ type X86_64StorageManager<'a, 'r, CallConv> =
 impl X64_64SystemVLoadArgs {
     fn load_arg<'a>(
         &mut self,
+        buf: &mut Vec<'a, u8>,
         storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
         layout_interner: &mut STLayoutInterner<'a>,
         sym: Symbol,
@@ -610,33 +632,30 @@ impl X64_64SystemVLoadArgs {
             single_register_integers!() => self.load_arg_general(storage_manager, sym),
             single_register_floats!() => self.load_arg_float(storage_manager, sym),
             _ if stack_size == 0 => {
-                storage_manager.no_data_arg(&sym);
+                storage_manager.no_data(&sym);
             }
             _ if stack_size > 16 => {
                 // TODO: Double check this.
-                storage_manager.complex_stack_arg(&sym, self.argument_offset, stack_size);
-                self.argument_offset += stack_size as i32;
+                let alignment = layout_interner.alignment_bytes(in_layout);
+                self.load_arg_on_stack(storage_manager, sym, stack_size, alignment);
             }
             other => match layout_interner.get(other) {
-                Layout::Boxed(_) => {
-                    // boxed layouts are pointers, which we treat as 64-bit integers
+                pointer_layouts!() => {
+                    // boxes and recursive unions are pointers, which we treat as 64-bit integers
                     self.load_arg_general(storage_manager, sym)
                 }
                 Layout::LambdaSet(lambda_set) => self.load_arg(
+                    buf,
                     storage_manager,
                     layout_interner,
                     sym,
                     lambda_set.runtime_representation(),
                 ),
-                Layout::Struct { .. } => {
-                    // for now, just also store this on the stack
-                    storage_manager.complex_stack_arg(&sym, self.argument_offset, stack_size);
-                    self.argument_offset += stack_size as i32;
-                }
-                Layout::Union(UnionLayout::NonRecursive(_)) => {
-                    // for now, just also store this on the stack
-                    storage_manager.complex_stack_arg(&sym, self.argument_offset, stack_size);
-                    self.argument_offset += stack_size as i32;
+                Layout::Builtin(Builtin::Int(IntWidth::I128 | IntWidth::U128))
+                | Layout::Builtin(Builtin::Decimal)
+                | Layout::Struct { .. }
+                | Layout::Union(UnionLayout::NonRecursive(_)) => {
+                    self.load_arg_eightbytes(buf, storage_manager, layout_interner, sym, other)
                 }
                 _ => {
                     todo!("Loading args with layout {:?}", layout_interner.dbg(other));
@@ -645,6 +664,52 @@ impl X64_64SystemVLoadArgs {
         }
     }
 
+    /// Loads a 128-bit integer, or a struct or union of at most 16 bytes, which was passed in
+    /// registers if there were enough of them left.
+    fn load_arg_eightbytes<'a>(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut X86_64StorageManager<'a, '_, X86_64SystemV>,
+        layout_interner: &mut STLayoutInterner<'a>,
+        sym: Symbol,
+        in_layout: InLayout<'a>,
+    ) {
+        let stack_size = layout_interner.stack_size(in_layout);
+        let classes = X86_64SystemV::eightbyte_classes(layout_interner, in_layout);
+        let (general_count, float_count) = count_eightbyte_classes(&classes);
+
+        if self.general_i + general_count <= X86_64SystemV::GENERAL_PARAM_REGS.len()
+            && self.float_i + float_count <= X86_64SystemV::FLOAT_PARAM_REGS.len()
+        {
+            // Complex values live on the stack, so spill the registers to it.
+            let base_offset = storage_manager.claim_stack_area(&sym, stack_size);
+            X86_64SystemV::store_eightbytes(
+                buf,
+                &classes,
+                base_offset,
+                &X86_64SystemV::GENERAL_PARAM_REGS[self.general_i..],
+                &X86_64SystemV::FLOAT_PARAM_REGS[self.float_i..],
+            );
+            self.general_i += general_count;
+            self.float_i += float_count;
+        } else {
+            let alignment = layout_interner.alignment_bytes(in_layout);
+            self.load_arg_on_stack(storage_manager, sym, stack_size, alignment);
+        }
+    }
+
+    fn load_arg_on_stack(
+        &mut self,
+        storage_manager: &mut X86_64StorageManager<'_, '_, X86_64SystemV>,
+        sym: Symbol,
+        stack_size: u32,
+        alignment: u32,
+    ) {
+        self.argument_offset = align_stack_arg_offset(self.argument_offset, alignment);
+        storage_manager.complex_stack_arg(&sym, self.argument_offset, stack_size);
+        self.argument_offset += stack_size as i32;
+    }
+
     fn load_arg_general(
         &mut self,
         storage_manager: &mut X86_64StorageManager<'_, '_, X86_64SystemV>,
@@ -665,8 +730,8 @@ impl X64_64SystemVLoadArgs {
         storage_manager: &mut X86_64StorageManager<'_, '_, X86_64SystemV>,
         sym: Symbol,
     ) {
-        if self.general_i < X86_64SystemV::GENERAL_PARAM_REGS.len() {
-            let reg = X86_64SystemV::FLOAT_PARAM_REGS[self.general_i];
+        if self.float_i < X86_64SystemV::FLOAT_PARAM_REGS.len() {
+            let reg = X86_64SystemV::FLOAT_PARAM_REGS[self.float_i];
             storage_manager.float_reg_arg(&sym, reg);
             self.float_i += 1;
         } else {
@@ -676,15 +741,205 @@ impl X64_64SystemVLoadArgs {
     }
 }
 
+/// Stack arguments take up whole eightbytes, and ones with 16-byte alignment, like `__int128`,
+/// start on a 16-byte boundary. The stack is 16-byte aligned at the call, so offsets from it are
+/// too.
+fn align_stack_arg_offset(offset: i32, alignment: u32) -> i32 {
+    if alignment > 8 {
+        (offset + 15) & !15
+    } else {
+        offset
+    }
+}
+
+/// The class of an eightbyte of a struct or union, which decides the kind of register it's
+/// passed in. See section 3.2.3 of https://github.com/hjl-tools/x86-psABI/wiki/x86-64-psABI-1.0.pdf
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SystemVClass {
+    Integer,
+    Sse,
+}
+
+impl SystemVClass {
+    fn merge(self, other: SystemVClass) -> SystemVClass {
+        if self == SystemVClass::Integer || other == SystemVClass::Integer {
+            SystemVClass::Integer
+        } else {
+            SystemVClass::Sse
+        }
+    }
+}
+
+/// How many general and float registers the eightbytes of a value need.
+fn count_eightbyte_classes(classes: &[Option<SystemVClass>; 2]) -> (usize, usize) {
+    let general_count = classes
+        .iter()
+        .filter(|class| **class == Some(SystemVClass::Integer))
+        .count();
+    let float_count = classes
+        .iter()
+        .filter(|class| **class == Some(SystemVClass::Sse))
+        .count();
+
+    (general_count, float_count)
+}
+
+/// Merges the class of every scalar in the layout into the eightbytes it overlaps.
+fn classify_eightbytes<'a>(
+    interner: &STLayoutInterner<'a>,
+    in_layout: InLayout<'a>,
+    offset: u32,
+    classes: &mut [Option<SystemVClass>; 2],
+) {
+    match interner.get(in_layout) {
+        Layout::Builtin(Builtin::Float(_)) => {
+            let size = interner.stack_size(in_layout);
+            merge_eightbyte_classes(classes, offset, size, SystemVClass::Sse);
+        }
+        Layout::Struct { field_layouts, .. } => {
+            let mut field_offset = offset;
+            for field_layout in field_layouts {
+                classify_eightbytes(interner, *field_layout, field_offset, classes);
+                field_offset += interner.stack_size(*field_layout);
+            }
+        }
+        Layout::Union(union_layout @ UnionLayout::NonRecursive(tags)) => {
+            // Every tag's payload starts at the beginning of the union.
+            for field_layouts in tags {
+                let mut field_offset = offset;
+                for field_layout in field_layouts.iter() {
+                    classify_eightbytes(interner, *field_layout, field_offset, classes);
+                    field_offset += interner.stack_size(*field_layout);
+                }
+            }
+
+            let (data_size, data_alignment) =
+                union_layout.data_size_and_alignment(interner, interner.target_info());
+            let id_size = union_layout.discriminant().stack_size();
+            merge_eightbyte_classes(
+                classes,
+                offset + data_size - data_alignment,
+                id_size,
+                SystemVClass::Integer,
+            );
+        }
+        Layout::LambdaSet(lambda_set) => {
+            classify_eightbytes(
+                interner,
+                lambda_set.runtime_representation(),
+                offset,
+                classes,
+            );
+        }
+        _ => {
+            // Integers, pointers, and everything else that isn't a float.
+            let size = interner.stack_size(in_layout);
+            merge_eightbyte_classes(classes, offset, size, SystemVClass::Integer);
+        }
+    }
+}
+
+fn merge_eightbyte_classes(
+    classes: &mut [Option<SystemVClass>; 2],
+    offset: u32,
+    size: u32,
+    class: SystemVClass,
+) {
+    if size == 0 {
+        return;
+    }
+
+    for index in (offset / 8)..=((offset + size - 1) / 8) {
+        if let Some(existing) = classes.get_mut(index as usize) {
+            *existing = Some(existing.map_or(class, |previous| previous.merge(class)));
+        }
+    }
+}
+
 impl X86_64SystemV {
     fn returns_via_arg_pointer<'a>(
         interner: &STLayoutInterner<'a>,
         ret_layout: &InLayout<'a>,
     ) -> bool {
-        // TODO: This will need to be more complex/extended to fully support the calling convention.
-        // details here: https://github.com/hjl-tools/x86-psABI/wiki/x86-64-psABI-1.0.pdf
+        // Roc never packs fields unaligned, so anything that fits in two eightbytes can be
+        // returned in registers. See `eightbyte_classes` for which registers.
         interner.stack_size(*ret_layout) > 16
     }
+
+    /// The classes of the eightbytes of a struct or union of at most 16 bytes, in order.
+    /// Trailing eightbytes past the end of the value are None.
+    fn eightbyte_classes<'a>(
+        interner: &STLayoutInterner<'a>,
+        in_layout: InLayout<'a>,
+    ) -> [Option<SystemVClass>; 2] {
+        let size = interner.stack_size(in_layout);
+        debug_assert!(size <= 16);
+
+        let mut classes = [None; 2];
+        classify_eightbytes(interner, in_layout, 0, &mut classes);
+
+        // An eightbyte that is only padding can go in either kind of register.
+        let eightbyte_count = (size as usize + 7) / 8;
+        for class in classes.iter_mut().take(eightbyte_count) {
+            class.get_or_insert(SystemVClass::Integer);
+        }
+
+        classes
+    }
+
+    /// Loads each eightbyte of the value at `base_offset` into the next register of its class.
+    fn load_eightbytes(
+        buf: &mut Vec<'_, u8>,
+        classes: &[Option<SystemVClass>; 2],
+        base_offset: i32,
+        general_regs: &[X86_64GeneralReg],
+        float_regs: &[X86_64FloatReg],
+    ) {
+        let mut general_regs = general_regs.iter();
+        let mut float_regs = float_regs.iter();
+
+        for (index, class) in classes.iter().enumerate() {
+            let offset = base_offset + 8 * index as i32;
+            match class {
+                Some(SystemVClass::Integer) => {
+                    let reg = *general_regs.next().unwrap();
+                    X86_64Assembler::mov_reg64_base32(buf, reg, offset);
+                }
+                Some(SystemVClass::Sse) => {
+                    let reg = *float_regs.next().unwrap();
+                    X86_64Assembler::mov_freg64_base32(buf, reg, offset);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Stores the next register of each eightbyte's class into the value at `base_offset`.
+    fn store_eightbytes(
+        buf: &mut Vec<'_, u8>,
+        classes: &[Option<SystemVClass>; 2],
+        base_offset: i32,
+        general_regs: &[X86_64GeneralReg],
+        float_regs: &[X86_64FloatReg],
+    ) {
+        let mut general_regs = general_regs.iter();
+        let mut float_regs = float_regs.iter();
+
+        for (index, class) in classes.iter().enumerate() {
+            let offset = base_offset + 8 * index as i32;
+            match class {
+                Some(SystemVClass::Integer) => {
+                    let reg = *general_regs.next().unwrap();
+                    X86_64Assembler::mov_base32_reg64(buf, offset, reg);
+                }
+                Some(SystemVClass::Sse) => {
+                    let reg = *float_regs.next().unwrap();
+                    X86_64Assembler::mov_base32_freg64(buf, offset, reg);
+                }
+                None => {}
+            }
+        }
+    }
 }
 
 impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64WindowsFastcall {
@@ -785,6 +1040,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
     #[inline(always)]
     fn setup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         saved_general_regs: &[X86_64GeneralReg],
         saved_float_regs: &[X86_64FloatReg],
         requested_stack_size: i32,
@@ -792,6 +1048,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
     ) -> i32 {
         x86_64_generic_setup_stack(
             buf,
+            unwind,
             saved_general_regs,
             saved_float_regs,
             requested_stack_size,
@@ -802,6 +1059,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
     #[inline(always)]
     fn cleanup_stack(
         buf: &mut Vec<'_, u8>,
+        unwind: &mut Vec<'_, UnwindStep>,
         saved_general_regs: &[X86_64GeneralReg],
         saved_float_regs: &[X86_64FloatReg],
         aligned_stack_size: i32,
@@ -809,6 +1067,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
     ) {
         x86_64_generic_cleanup_stack(
             buf,
+            unwind,
             saved_general_regs,
             saved_float_regs,
             aligned_stack_size,
@@ -831,6 +1090,12 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
             i += 1;
         }
         for (layout, sym) in args.iter() {
+            // Zero sized args aren't passed at all, so they don't take up a position.
+            if layout_interner.stack_size(*layout) == 0 {
+                storage_manager.no_data(sym);
+                continue;
+            }
+
             if i < Self::GENERAL_PARAM_REGS.len() {
                 match *layout {
                     single_register_integers!() => {
@@ -841,7 +1106,6 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
                         storage_manager.float_reg_arg(sym, Self::FLOAT_PARAM_REGS[i]);
                         i += 1;
                     }
-                    x if layout_interner.stack_size(x) == 0 => {}
                     x => {
                         todo!("Loading args with layout {:?}", x);
                     }
@@ -883,7 +1147,13 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
             storage_manager.claim_stack_area(dst, layout_interner.stack_size(*ret_layout));
             todo!("claim first parama reg for the address");
         }
-        for (i, (sym, layout)) in args.iter().zip(arg_layouts.iter()).enumerate() {
+        // The position of the arg, which decides its register. Zero sized args don't have one.
+        let mut i = 0;
+        for (sym, layout) in args.iter().zip(arg_layouts.iter()) {
+            if layout_interner.stack_size(*layout) == 0 {
+                continue;
+            }
+
             match *layout {
                 single_register_integers!() => {
                     if i < Self::GENERAL_PARAM_REGS.len() {
@@ -929,11 +1199,11 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
                         tmp_stack_offset += 8;
                     }
                 }
-                x if layout_interner.stack_size(x) == 0 => {}
                 x => {
                     todo!("calling with arg type, {:?}", x);
                 }
             }
+            i += 1;
         }
         storage_manager.update_fn_call_stack_size(tmp_stack_offset as u32);
     }
@@ -987,13 +1257,30 @@ impl X86_64WindowsFastcall {
 #[inline(always)]
 fn x86_64_generic_setup_stack(
     buf: &mut Vec<'_, u8>,
+    unwind: &mut Vec<'_, UnwindStep>,
     saved_general_regs: &[X86_64GeneralReg],
     saved_float_regs: &[X86_64FloatReg],
     requested_stack_size: i32,
     fn_call_stack_size: i32,
 ) -> i32 {
     X86_64Assembler::push_reg64(buf, X86_64GeneralReg::RBP);
+    unwind.push(UnwindStep::Cfa {
+        code_offset: buf.len() as u32,
+        register: X86_64GeneralReg::RSP.dwarf_number(),
+        offset: 16,
+    });
+    unwind.push(UnwindStep::Saved {
+        code_offset: buf.len() as u32,
+        register: X86_64GeneralReg::RBP.dwarf_number(),
+        cfa_offset: -16,
+    });
     X86_64Assembler::mov_reg64_reg64(buf, X86_64GeneralReg::RBP, X86_64GeneralReg::RSP);
+    // From here on, the return address and the saved RBP are just above RBP.
+    unwind.push(UnwindStep::Cfa {
+        code_offset: buf.len() as u32,
+        register: X86_64GeneralReg::RBP.dwarf_number(),
+        offset: 16,
+    });
 
     let full_stack_size = match requested_stack_size
         .checked_add(8 * (saved_general_regs.len() + saved_float_regs.len()) as i32)
@@ -1014,21 +1301,35 @@ fn x86_64_generic_setup_stack(
     };
     if let Some(aligned_stack_size) = full_stack_size.checked_add(offset as i32) {
         if aligned_stack_size > 0 {
-            X86_64Assembler::sub_reg64_reg64_imm32(
-                buf,
-                X86_64GeneralReg::RSP,
-                X86_64GeneralReg::RSP,
-                aligned_stack_size,
-            );
+            if aligned_stack_size > STACK_PROBE_SIZE {
+                x86_64_probe_stack(buf, aligned_stack_size);
+            } else {
+                X86_64Assembler::sub_reg64_reg64_imm32(
+                    buf,
+                    X86_64GeneralReg::RSP,
+                    X86_64GeneralReg::RSP,
+                    aligned_stack_size,
+                );
+            }
 
             // Put values at the top of the stack to avoid conflicts with previously saved variables.
             let mut offset = aligned_stack_size - fn_call_stack_size;
             for reg in saved_general_regs {
                 X86_64Assembler::mov_base32_reg64(buf, -offset, *reg);
+                unwind.push(UnwindStep::Saved {
+                    code_offset: buf.len() as u32,
+                    register: reg.dwarf_number(),
+                    cfa_offset: -16 - offset,
+                });
                 offset -= 8;
             }
             for reg in saved_float_regs {
                 X86_64Assembler::mov_base32_freg64(buf, -offset, *reg);
+                unwind.push(UnwindStep::Saved {
+                    code_offset: buf.len() as u32,
+                    register: reg.dwarf_number(),
+                    cfa_offset: -16 - offset,
+                });
                 offset -= 8;
             }
             aligned_stack_size
@@ -1040,10 +1341,48 @@ fn x86_64_generic_setup_stack(
     }
 }
 
+/// Allocate a large stack frame, writing to each page as we go.
+/// Linux and Windows grow the stack by detecting accesses to a guard page just past the end of it.
+/// If we moved RSP down by more than a page in one go, the first access could land beyond the guard page.
+fn x86_64_probe_stack(buf: &mut Vec<'_, u8>, stack_size: i32) {
+    // R11 is a scratch register in both calling conventions, and never holds an argument.
+    let counter = X86_64GeneralReg::R11;
+    let pages = stack_size / STACK_PROBE_SIZE;
+    let remainder = stack_size % STACK_PROBE_SIZE;
+
+    X86_64Assembler::mov_reg64_imm64(buf, counter, pages as i64);
+
+    let loop_start = buf.len();
+    X86_64Assembler::sub_reg64_reg64_imm32(
+        buf,
+        X86_64GeneralReg::RSP,
+        X86_64GeneralReg::RSP,
+        STACK_PROBE_SIZE,
+    );
+    X86_64Assembler::mov_stack32_reg64(buf, 0, counter);
+    X86_64Assembler::sub_reg64_reg64_imm32(buf, counter, counter, 1);
+
+    // Emit the jump once to find its length, then again with the real (backward) offset.
+    let jne_start = buf.len();
+    let jne_end = X86_64Assembler::jne_reg64_imm64_imm32(buf, counter, 0, 0);
+    buf.truncate(jne_start);
+    X86_64Assembler::jne_reg64_imm64_imm32(buf, counter, 0, loop_start as i32 - jne_end as i32);
+
+    if remainder > 0 {
+        X86_64Assembler::sub_reg64_reg64_imm32(
+            buf,
+            X86_64GeneralReg::RSP,
+            X86_64GeneralReg::RSP,
+            remainder,
+        );
+    }
+}
+
 #[inline(always)]
 #[allow(clippy::unnecessary_wraps)]
 fn x86_64_generic_cleanup_stack(
     buf: &mut Vec<'_, u8>,
+    unwind: &mut Vec<'_, UnwindStep>,
     saved_general_regs: &[X86_64GeneralReg],
     saved_float_regs: &[X86_64FloatReg],
     aligned_stack_size: i32,
@@ -1068,6 +1407,16 @@ fn x86_64_generic_cleanup_stack(
     }
     //X86_64Assembler::mov_reg64_reg64(buf, X86_64GeneralReg::RSP, X86_64GeneralReg::RBP);
     X86_64Assembler::pop_reg64(buf, X86_64GeneralReg::RBP);
+    // Only the return address is left on the stack
+    unwind.push(UnwindStep::Cfa {
+        code_offset: buf.len() as u32,
+        register: X86_64GeneralReg::RSP.dwarf_number(),
+        offset: 8,
+    });
+    unwind.push(UnwindStep::Restored {
+        code_offset: buf.len() as u32,
+        register: X86_64GeneralReg::RBP.dwarf_number(),
+    });
 }
 
 type Reg64 = X86_64GeneralReg;
@@ -1105,10 +1454,11 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     ) {
         movsd_freg64_rip_offset32(buf, dst, 0);
 
-        // TODO: make sure this constant only loads once instead of every call to abs
+        // The mask is pooled with the module's other constants, so it is only stored once
         relocs.push(Relocation::LocalData {
             offset: buf.len() as u64 - 4,
             data: 0x7fffffffffffffffu64.to_le_bytes().to_vec(),
+            access: DataAccess::RipRelative,
         });
 
         andpd_freg64_freg64(buf, dst, src);
@@ -1172,6 +1522,36 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         });
     }
 
+    fn copy_base32_to_mem_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        dst_offset: i32,
+        src_offset: i32,
+        size: u32,
+    ) {
+        use X86_64GeneralReg::*;
+
+        debug_assert_eq!(size % 8, 0);
+        debug_assert_ne!(dst, RSP);
+
+        // rep movsb copies rcx bytes from [rsi] to [rdi], so those need to be restored afterwards.
+        push_reg64(buf, RSI);
+        push_reg64(buf, RDI);
+        push_reg64(buf, RCX);
+
+        // dst may be one of those registers, so read it before any of them are overwritten.
+        mov_reg64_reg64(buf, RDI, dst);
+        add_reg64_imm32(buf, RDI, dst_offset);
+        mov_reg64_reg64(buf, RSI, RBP);
+        add_reg64_imm32(buf, RSI, src_offset);
+        mov_reg64_imm64(buf, RCX, size as i64);
+        rep_movsb(buf);
+
+        pop_reg64(buf, RCX);
+        pop_reg64(buf, RDI);
+        pop_reg64(buf, RSI);
+    }
+
     #[inline(always)]
     fn function_pointer(
         buf: &mut Vec<'_, u8>,
@@ -1350,6 +1730,22 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         buf.len()
     }
 
+    #[inline(always)]
+    fn je_reg64_imm64_imm32(
+        buf: &mut Vec<'_, u8>,
+        reg: X86_64GeneralReg,
+        imm: u64,
+        offset: i32,
+    ) -> usize {
+        buf.reserve(13);
+        if imm > i32::MAX as u64 {
+            todo!("comparison with values greater than i32::max");
+        }
+        cmp_reg64_imm32(buf, reg, imm as i32);
+        je_imm32(buf, offset);
+        buf.len()
+    }
+
     #[inline(always)]
     fn mov_freg32_imm32(
         buf: &mut Vec<'_, u8>,
@@ -1361,6 +1757,7 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         relocs.push(Relocation::LocalData {
             offset: buf.len() as u64 - 4,
             data: imm.to_le_bytes().to_vec(),
+            access: DataAccess::RipRelative,
         });
     }
     #[inline(always)]
@@ -1374,6 +1771,7 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         relocs.push(Relocation::LocalData {
             offset: buf.len() as u64 - 4,
             data: imm.to_le_bytes().to_vec(),
+            access: DataAccess::RipRelative,
         });
     }
     #[inline(always)]
@@ -1388,11 +1786,23 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     fn mov_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
         mov_reg64_reg64(buf, dst, src);
     }
+    #[inline(always)]
+    fn mov_reg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64FloatReg) {
+        movq_reg64_freg64(buf, dst, src);
+    }
+    #[inline(always)]
+    fn mov_reg32_freg32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64FloatReg) {
+        movd_reg32_freg32(buf, dst, src);
+    }
 
     #[inline(always)]
     fn mov_freg64_base32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, offset: i32) {
         movsd_freg64_base64_offset32(buf, dst, X86_64GeneralReg::RBP, offset)
     }
+    #[inline(always)]
+    fn mov_freg32_base32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, offset: i32) {
+        movss_freg32_base32_offset32(buf, dst, X86_64GeneralReg::RBP, offset)
+    }
 
     #[inline(always)]
     fn mov_reg64_base32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, offset: i32) {
@@ -1415,6 +1825,10 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     fn mov_base32_freg64(buf: &mut Vec<'_, u8>, offset: i32, src: X86_64FloatReg) {
         movsd_base64_offset32_freg64(buf, X86_64GeneralReg::RBP, offset, src)
     }
+    #[inline(always)]
+    fn mov_base32_freg32(buf: &mut Vec<'_, u8>, offset: i32, src: X86_64FloatReg) {
+        movss_base32_offset32_freg32(buf, X86_64GeneralReg::RBP, offset, src)
+    }
 
     #[inline(always)]
     fn movesd_mem64_offset32_freg64(
@@ -1425,6 +1839,33 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     ) {
         movsd_base64_offset32_freg64(buf, ptr, offset, src)
     }
+    #[inline(always)]
+    fn mov_mem32_offset32_freg32(
+        buf: &mut Vec<'_, u8>,
+        ptr: X86_64GeneralReg,
+        offset: i32,
+        src: X86_64FloatReg,
+    ) {
+        movss_base32_offset32_freg32(buf, ptr, offset, src)
+    }
+    #[inline(always)]
+    fn mov_freg64_mem64_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64FloatReg,
+        ptr: X86_64GeneralReg,
+        offset: i32,
+    ) {
+        movsd_freg64_base64_offset32(buf, dst, ptr, offset)
+    }
+    #[inline(always)]
+    fn mov_freg32_mem32_offset32(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64FloatReg,
+        ptr: X86_64GeneralReg,
+        offset: i32,
+    ) {
+        movss_freg32_base32_offset32(buf, dst, ptr, offset)
+    }
 
     #[inline(always)]
     fn mov_base32_reg64(buf: &mut Vec<'_, u8>, offset: i32, src: X86_64GeneralReg) {
@@ -1537,7 +1978,8 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
             4 => {
                 // The Intel documentation (3.4.1.1 General-Purpose Registers in 64-Bit Mode in manual Basic Architecture))
                 // 32-bit operands generate a 32-bit result, zero-extended to a 64-bit result in the destination general-purpose register.
-                Self::mov_reg64_base32(buf, dst, offset)
+                // A 64-bit load would pick up whatever follows the value, e.g. the error code of a parse result.
+                Self::mov_reg32_base32(buf, dst, offset)
             }
             2 => movzx_reg64_base16_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
             1 => movzx_reg64_base8_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
@@ -1545,6 +1987,36 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         }
     }
 
+    #[inline(always)]
+    fn movsx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        register_width: RegisterWidth,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+    ) {
+        match register_width {
+            RegisterWidth::W64 => mov_reg64_reg64(buf, dst, src),
+            RegisterWidth::W32 => movsxd_reg64_reg32(buf, dst, src),
+            RegisterWidth::W16 => movsx_reg64_reg16(buf, dst, src),
+            RegisterWidth::W8 => movsx_reg64_reg8(buf, dst, src),
+        }
+    }
+    #[inline(always)]
+    fn movzx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        register_width: RegisterWidth,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+    ) {
+        match register_width {
+            RegisterWidth::W64 => mov_reg64_reg64(buf, dst, src),
+            // Writing a 32-bit register zeroes the upper half of the 64-bit register
+            RegisterWidth::W32 => mov_reg32_reg32(buf, dst, src),
+            RegisterWidth::W16 => movzx_reg64_reg16(buf, dst, src),
+            RegisterWidth::W8 => movzx_reg64_reg8(buf, dst, src),
+        }
+    }
+
     #[inline(always)]
     fn mov_freg64_stack32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, offset: i32) {
         movsd_freg64_base64_offset32(buf, dst, X86_64GeneralReg::RSP, offset)
@@ -1568,6 +2040,29 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         neg_reg64(buf, dst);
     }
 
+    #[inline(always)]
+    fn lzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+        lzcnt_reg64_reg64(buf, dst, src);
+    }
+
+    #[inline(always)]
+    fn tzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+        tzcnt_reg64_reg64(buf, dst, src);
+    }
+
+    #[inline(always)]
+    fn popcnt_reg64_reg64<'a, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        _storage_manager: &mut StorageManager<'a, '_, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+    ) where
+        ASM: Assembler<X86_64GeneralReg, X86_64FloatReg>,
+        CC: CallConv<X86_64GeneralReg, X86_64FloatReg, ASM>,
+    {
+        popcnt_reg64_reg64(buf, dst, src);
+    }
+
     #[inline(always)]
     fn sub_reg64_reg64_imm32(
         buf: &mut Vec<'_, u8>,
@@ -1697,12 +2192,87 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         cvtsi2sd_freg64_reg64(buf, dst, src);
     }
 
-    #[inline(always)]
-    fn ret(buf: &mut Vec<'_, u8>) {
-        ret(buf);
-    }
+    fn unsigned_to_float_freg_reg64<'a, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<'a, '_, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        width: FloatWidth,
+        dst: X86_64FloatReg,
+        src: X86_64GeneralReg,
+    ) where
+        ASM: Assembler<X86_64GeneralReg, X86_64FloatReg>,
+        CC: CallConv<X86_64GeneralReg, X86_64FloatReg, ASM>,
+    {
+        type Convert = fn(&mut Vec<'_, u8>, X86_64FloatReg, X86_64GeneralReg);
+        type Add = fn(&mut Vec<'_, u8>, X86_64FloatReg, X86_64FloatReg);
 
-    fn set_if_overflow(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
+        let (convert, add): (Convert, Add) = match width {
+            FloatWidth::F32 => (cvtsi2ss_freg64_reg64, addss_freg32_freg32),
+            FloatWidth::F64 => (cvtsi2sd_freg64_reg64, addsd_freg64_freg64),
+        };
+
+        // The temporary registers are claimed up front, since claiming one can spill a symbol,
+        // and that must happen on both paths.
+        storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, half| {
+            storage_manager.with_tmp_general_reg(buf, |_, buf, low_bit| {
+                // CVTSI2SD is signed, so it only works if the top bit is clear.
+                test_reg64_reg64(buf, src, src);
+
+                let js_location = buf.len();
+                js_imm32(buf, 0);
+
+                convert(buf, dst, src);
+
+                let jmp_location = buf.len();
+                jmp_imm32(buf, 0);
+
+                // Otherwise, convert half the value and double it. The lowest bit is kept so that
+                // the halved value still rounds the same way.
+                let big_start = buf.len();
+                mov_reg64_reg64(buf, half, src);
+                shr_reg64_imm8(buf, half, 1);
+                mov_reg64_reg64(buf, low_bit, src);
+                and_reg64_imm8(buf, low_bit, 1);
+                or_reg64_reg64(buf, half, low_bit);
+                convert(buf, dst, half);
+                add(buf, dst, dst);
+
+                let end = buf.len();
+
+                patch_rel32(buf, js_location + 2, big_start);
+                patch_rel32(buf, jmp_location + 1, end);
+            });
+        });
+    }
+
+    #[inline(always)]
+    fn ret(buf: &mut Vec<'_, u8>) {
+        ret(buf);
+    }
+
+    fn nop(buf: &mut Vec<'_, u8>) {
+        nop(buf);
+    }
+
+    fn trap(buf: &mut Vec<'_, u8>) {
+        ud2(buf);
+    }
+
+    const PATCHABLE_ENTRY_SIZE: usize = 16;
+
+    fn patchable_entry(buf: &mut Vec<'_, u8>) {
+        jmp_imm8(buf, 14);
+        // The address is right after this jump
+        jmp_rip_offset32_indirect(buf, 0);
+        buf.extend(0u64.to_le_bytes());
+    }
+
+    fn redirect_patchable_entry(code: &mut [u8], target: u64) {
+        code[8..16].copy_from_slice(&target.to_le_bytes());
+        // Jump to the indirect jump instead of over it, a single byte that's written atomically
+        code[1] = 0;
+    }
+
+    fn set_if_overflow(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
         seto_reg64(buf, dst);
     }
 
@@ -1757,6 +2327,33 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         shift_reg64_reg64_reg64(buf, storage_manager, sar_reg64_reg64, dst, src1, src2)
     }
 
+    fn shl_reg64_reg64_imm8(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+        imm8: u8,
+    ) {
+        shift_reg64_reg64_imm8(buf, shl_reg64_imm8, dst, src, imm8)
+    }
+
+    fn shr_reg64_reg64_imm8(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+        imm8: u8,
+    ) {
+        shift_reg64_reg64_imm8(buf, shr_reg64_imm8, dst, src, imm8)
+    }
+
+    fn sar_reg64_reg64_imm8(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+        imm8: u8,
+    ) {
+        shift_reg64_reg64_imm8(buf, sar_reg64_imm8, dst, src, imm8)
+    }
+
     fn sqrt_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
         sqrtsd_freg64_freg64(buf, dst, src)
     }
@@ -1804,6 +2401,24 @@ fn shift_reg64_reg64_reg64<'a, ASM, CC>(
     }
 }
 
+/// Unlike shifting by a register, shifting by an immediate doesn't need RCX
+#[inline(always)]
+fn shift_reg64_reg64_imm8(
+    buf: &mut Vec<'_, u8>,
+    shift_function: fn(buf: &mut Vec<'_, u8>, X86_64GeneralReg, u8),
+    dst: X86_64GeneralReg,
+    src: X86_64GeneralReg,
+    imm8: u8,
+) {
+    if dst != src {
+        mov_reg64_reg64(buf, dst, src);
+    }
+
+    if imm8 != 0 {
+        shift_function(buf, dst, imm8);
+    }
+}
+
 impl X86_64Assembler {
     #[inline(always)]
     fn pop_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
@@ -1984,6 +2599,15 @@ fn shl_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
     buf.extend([rex, 0xD3, 0xC0 | (4 << 3) | dst_mod]);
 }
 
+/// `SHL r/m64, imm8` -> Multiply r/m64 by 2, imm8 times.
+#[inline(always)]
+fn shl_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: u8) {
+    let rex = add_rm_extension(dst, REX_W);
+
+    let dst_mod = dst as u8 % 8;
+    buf.extend([rex, 0xC1, 0xC0 | (4 << 3) | dst_mod, imm]);
+}
+
 /// `SHR r/m64, CL` -> Unsigned divide r/m64 by 2, CL times.
 #[inline(always)]
 fn shr_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
@@ -1994,6 +2618,15 @@ fn shr_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
     buf.extend([rex, 0xD3, 0xC0 | (5 << 3) | dst_mod]);
 }
 
+/// `SHR r/m64, imm8` -> Unsigned divide r/m64 by 2, imm8 times.
+#[inline(always)]
+fn shr_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: u8) {
+    let rex = add_rm_extension(dst, REX_W);
+
+    let dst_mod = dst as u8 % 8;
+    buf.extend([rex, 0xC1, 0xC0 | (5 << 3) | dst_mod, imm]);
+}
+
 /// `SAR r/m64, CL` -> Signed divide r/m64 by 2, CL times.
 #[inline(always)]
 fn sar_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
@@ -2004,6 +2637,15 @@ fn sar_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
     buf.extend([rex, 0xD3, 0xC0 | (7 << 3) | dst_mod]);
 }
 
+/// `SAR r/m64, imm8` -> Signed divide r/m64 by 2, imm8 times.
+#[inline(always)]
+fn sar_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: u8) {
+    let rex = add_rm_extension(dst, REX_W);
+
+    let dst_mod = dst as u8 % 8;
+    buf.extend([rex, 0xC1, 0xC0 | (7 << 3) | dst_mod, imm]);
+}
+
 /// `ADDSD xmm1,xmm2/m64` -> Add the low double-precision floating-point value from xmm2/mem to xmm1 and store the result in xmm1.
 #[inline(always)]
 fn addsd_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
@@ -2273,7 +2915,6 @@ fn sqrtss_freg32_freg32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64F
 }
 
 /// `TEST r/m64,r64` -> AND r64 with r/m64; set SF, ZF, PF according to result.
-#[allow(dead_code)]
 #[inline(always)]
 fn test_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
     binop_reg64_reg64(0x85, buf, dst, src);
@@ -2349,6 +2990,20 @@ fn jmp_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
     buf.extend(imm.to_le_bytes());
 }
 
+/// `JMP rel8` -> Jump short, RIP = RIP + 8-bit displacement sign extended to 64-bits.
+#[inline(always)]
+fn jmp_imm8(buf: &mut Vec<'_, u8>, imm: i8) {
+    buf.extend([0xEB, imm as u8]);
+}
+
+/// `JMP r/m64` -> Jump absolute indirect, to the address stored at RIP + 32-bit displacement.
+#[inline(always)]
+fn jmp_rip_offset32_indirect(buf: &mut Vec<'_, u8>, offset: i32) {
+    buf.reserve(6);
+    buf.extend([0xFF, 0x25]);
+    buf.extend(offset.to_le_bytes());
+}
+
 /// Jump near if not equal (ZF=0).
 #[inline(always)]
 fn jne_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
@@ -2358,6 +3013,32 @@ fn jne_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
     buf.extend(imm.to_le_bytes());
 }
 
+/// Jump near if equal (ZF=1).
+#[inline(always)]
+fn je_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
+    buf.reserve(6);
+    buf.push(0x0F);
+    buf.push(0x84);
+    buf.extend(imm.to_le_bytes());
+}
+
+/// Jump near if sign (SF=1).
+#[inline(always)]
+fn js_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
+    buf.reserve(6);
+    buf.push(0x0F);
+    buf.push(0x88);
+    buf.extend(imm.to_le_bytes());
+}
+
+/// Points the rel32 of a jump, which is at `offset` and is the end of the instruction, at `target`.
+#[inline(always)]
+fn patch_rel32(buf: &mut Vec<'_, u8>, offset: usize, target: usize) {
+    let rel32 = target as i32 - (offset + 4) as i32;
+
+    buf[offset..offset + 4].copy_from_slice(&rel32.to_le_bytes());
+}
+
 /// `MOV r/m64, imm32` -> Move imm32 sign extended to 64-bits to r/m64.
 #[inline(always)]
 fn mov_reg64_imm32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i32) {
@@ -2690,6 +3371,43 @@ fn movzx_reg64_base16_offset32(
     movzx_reg64_base_offset32(buf, dst, base, offset, 0xB7)
 }
 
+/// `MOVSX r64,r/m8` -> Move r/m8 with sign extention to r64.
+#[inline(always)]
+fn movsx_reg64_reg8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extended_binop_reg64_reg64(0x0F, 0xBE, buf, src, dst);
+}
+
+/// `MOVSX r64,r/m16` -> Move r/m16 with sign extention to r64.
+#[inline(always)]
+fn movsx_reg64_reg16(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extended_binop_reg64_reg64(0x0F, 0xBF, buf, src, dst);
+}
+
+/// `MOVSXD r64,r/m32` -> Move r/m32 with sign extention to r64.
+#[inline(always)]
+fn movsxd_reg64_reg32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    binop_reg64_reg64(0x63, buf, src, dst);
+}
+
+/// `MOVZX r64,r/m8` -> Move r/m8 with zero extention to r64.
+#[inline(always)]
+fn movzx_reg64_reg8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extended_binop_reg64_reg64(0x0F, 0xB6, buf, src, dst);
+}
+
+/// `MOVZX r64,r/m16` -> Move r/m16 with zero extention to r64.
+#[inline(always)]
+fn movzx_reg64_reg16(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extended_binop_reg64_reg64(0x0F, 0xB7, buf, src, dst);
+}
+
+/// `MOV r/m32,r32` -> Move r32 to r/m32, zeroing the upper half of the 64-bit register.
+/// Unlike `mov_reg64_reg64`, this is generated even if dst and src are the same.
+#[inline(always)]
+fn mov_reg32_reg32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    binop_reg32_reg32(0x89, buf, dst, src);
+}
+
 /// `MOVSD xmm1,xmm2` -> Move scalar double-precision floating-point value from xmm2 to xmm1 register.
 /// This will not generate anything if dst and src are the same.
 #[inline(always)]
@@ -2828,6 +3546,56 @@ fn movsd_freg64_base64_offset32(
     buf.extend(offset.to_le_bytes());
 }
 
+/// `MOVSS r/m32,xmm1` -> Move xmm1 to r/m32. where m32 references the base pointer.
+#[inline(always)]
+fn movss_base32_offset32_freg32(
+    buf: &mut Vec<'_, u8>,
+    base: X86_64GeneralReg,
+    offset: i32,
+    src: X86_64FloatReg,
+) {
+    let rex = add_rm_extension(base, REX);
+    let rex = add_reg_extension(src, rex);
+    let src_mod = (src as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(10);
+    buf.push(0xF3);
+    if src as u8 > 7 || base as u8 > 7 {
+        buf.push(rex);
+    }
+    buf.extend([0x0F, 0x11, 0x80 | src_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
+/// `MOVSS xmm1,r/m32` -> Move r/m32 to xmm1. where m32 references the base pointer.
+#[inline(always)]
+fn movss_freg32_base32_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64FloatReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    let rex = add_rm_extension(base, REX);
+    let rex = add_reg_extension(dst, rex);
+    let dst_mod = (dst as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(10);
+    buf.push(0xF3);
+    if dst as u8 > 7 || base as u8 > 7 {
+        buf.push(rex);
+    }
+    buf.extend([0x0F, 0x10, 0x80 | dst_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
 /// `NEG r/m64` -> Two's complement negate r/m64.
 #[inline(always)]
 fn neg_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
@@ -2836,6 +3604,33 @@ fn neg_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
     buf.extend([rex, 0xF7, 0xD8 | reg_mod]);
 }
 
+/// `LZCNT r64,r/m64` -> Count the leading zero bits of r/m64 into r64.
+/// CPUs without LZCNT (before Haswell) run this as BSR, which gives the wrong count.
+#[inline(always)]
+fn lzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    buf.reserve(5);
+    buf.push(0xF3);
+    // Like IMUL, the register operand is the destination.
+    extended_binop_reg64_reg64(0x0F, 0xBD, buf, src, dst);
+}
+
+/// `TZCNT r64,r/m64` -> Count the trailing zero bits of r/m64 into r64.
+/// CPUs without BMI1 (before Haswell) run this as BSF, which leaves r64 as is for a zero r/m64.
+#[inline(always)]
+fn tzcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    buf.reserve(5);
+    buf.push(0xF3);
+    extended_binop_reg64_reg64(0x0F, 0xBC, buf, src, dst);
+}
+
+/// `POPCNT r64,r/m64` -> Count the one bits of r/m64 into r64.
+#[inline(always)]
+fn popcnt_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    buf.reserve(5);
+    buf.push(0xF3);
+    extended_binop_reg64_reg64(0x0F, 0xB8, buf, src, dst);
+}
+
 // helper function for `set*` instructions
 #[inline(always)]
 fn set_reg64_help(op_code: u8, buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
@@ -2888,6 +3683,33 @@ fn cvtsx2_help<T: RegTrait, V: RegTrait>(
     buf.extend([op_code1, 0x0F, op_code2, 0xC0 | mod1 | mod2])
 }
 
+/// `MOVQ r/m64,xmm` -> Move quadword from xmm register to r/m64, without converting it.
+#[inline(always)]
+fn movq_reg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64FloatReg) {
+    let rex = add_rm_extension(dst, REX_W);
+    let rex = add_reg_extension(src, rex);
+    let mod1 = (src.value() % 8) << 3;
+    let mod2 = dst.value() % 8;
+
+    buf.extend([0x66, rex, 0x0F, 0x7E, 0xC0 | mod1 | mod2])
+}
+
+/// `MOVD r/m32,xmm` -> Move doubleword from xmm register to r/m32, without converting it.
+/// The upper half of the 64-bit register is zeroed.
+#[inline(always)]
+fn movd_reg32_freg32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64FloatReg) {
+    let rex = add_rm_extension(dst, REX);
+    let rex = add_reg_extension(src, rex);
+    let mod1 = (src.value() % 8) << 3;
+    let mod2 = dst.value() % 8;
+
+    if rex == REX {
+        buf.extend([0x66, 0x0F, 0x7E, 0xC0 | mod1 | mod2])
+    } else {
+        buf.extend([0x66, rex, 0x0F, 0x7E, 0xC0 | mod1 | mod2])
+    }
+}
+
 /// `SETE r/m64` -> Set Byte on Condition - zero/equal (ZF=1)
 #[inline(always)]
 fn sete_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
@@ -2913,7 +3735,6 @@ fn cvtsi2sd_freg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64
 }
 
 /// `CVTSI2SS r/m64` -> Convert one signed quadword integer from r/m64 to one single-precision floating-point value in xmm.
-#[allow(dead_code)]
 #[inline(always)]
 fn cvtsi2ss_freg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64GeneralReg) {
     cvtsi2_help(buf, 0xF3, 0x2A, dst, src)
@@ -2992,6 +3813,18 @@ fn ret(buf: &mut Vec<'_, u8>) {
     buf.push(0xC3);
 }
 
+/// `NOP` -> No operation.
+#[inline(always)]
+fn nop(buf: &mut Vec<'_, u8>) {
+    buf.push(0x90);
+}
+
+/// `UD2` -> Raise an invalid opcode exception.
+#[inline(always)]
+fn ud2(buf: &mut Vec<'_, u8>) {
+    buf.extend([0x0F, 0x0B]);
+}
+
 /// `SUB r/m64, imm32` -> Subtract imm32 sign-extended to 64-bits from r/m64.
 #[inline(always)]
 fn sub_reg64_imm32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i32) {
@@ -3033,6 +3866,12 @@ fn push_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
     }
 }
 
+/// `REP MOVSB` -> Move RCX bytes from [RSI] to [RDI], incrementing both.
+#[inline(always)]
+fn rep_movsb(buf: &mut Vec<'_, u8>) {
+    buf.extend([0xF3, 0xA4]);
+}
+
 // When writing tests, it is a good idea to test both a number and unnumbered register.
 // This is because R8-R15 often have special instruction prefixes.
 #[cfg(test)]
@@ -3261,6 +4100,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_shl_reg64_imm8() {
+        disassembler_test!(
+            shl_reg64_imm8,
+            |reg, imm| format!("shl {reg}, {imm}"),
+            ALL_GENERAL_REGS,
+            [1, 7]
+        );
+    }
+
     #[test]
     fn test_shr_reg64_reg64() {
         disassembler_test!(
@@ -3270,6 +4119,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_shr_reg64_imm8() {
+        disassembler_test!(
+            shr_reg64_imm8,
+            |reg, imm| format!("shr {reg}, {imm}"),
+            ALL_GENERAL_REGS,
+            [1, 7]
+        );
+    }
+
     #[test]
     fn test_sar_reg64_reg64() {
         disassembler_test!(
@@ -3279,6 +4138,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sar_reg64_imm8() {
+        disassembler_test!(
+            sar_reg64_imm8,
+            |reg, imm| format!("sar {reg}, {imm}"),
+            ALL_GENERAL_REGS,
+            [1, 7]
+        );
+    }
+
     #[test]
     fn test_cmovl_reg64_reg64() {
         disassembler_test!(
@@ -3309,6 +4178,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lzcnt_reg64_reg64() {
+        disassembler_test!(
+            lzcnt_reg64_reg64,
+            |reg1, reg2| format!("lzcnt {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_tzcnt_reg64_reg64() {
+        disassembler_test!(
+            tzcnt_reg64_reg64,
+            |reg1, reg2| format!("tzcnt {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_popcnt_reg64_reg64() {
+        disassembler_test!(
+            popcnt_reg64_reg64,
+            |reg1, reg2| format!("popcnt {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
     #[test]
     fn test_mul_reg64_reg64() {
         disassembler_test!(
@@ -3376,6 +4275,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_jmp_imm8() {
+        const INST_SIZE: i8 = 2;
+        disassembler_test!(
+            jmp_imm8,
+            |imm| format!("jmp 0x{:x}", imm + INST_SIZE),
+            [0, 14, 0x70]
+        );
+    }
+
+    #[test]
+    fn test_jmp_rip_offset32_indirect() {
+        disassembler_test!(
+            jmp_rip_offset32_indirect,
+            |offset| format!("jmp qword ptr [rip + 0x{:x}]", offset),
+            [0x10, TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_redirect_patchable_entry() {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+
+        X86_64Assembler::patchable_entry(&mut buf);
+        assert_eq!(buf.len(), X86_64Assembler::PATCHABLE_ENTRY_SIZE);
+
+        X86_64Assembler::redirect_patchable_entry(&mut buf, 0x1122_3344_5566_7788);
+        assert_eq!(
+            &buf[..],
+            &[
+                0xEB, 0x00, // jmp to the next instruction
+                0xFF, 0x25, 0x00, 0x00, 0x00, 0x00, // jmp qword ptr [rip]
+                0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11,
+            ]
+        );
+    }
+
     #[test]
     fn test_jmp_imm32() {
         const INST_SIZE: i32 = 5;
@@ -3396,6 +4333,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_je_imm32() {
+        const INST_SIZE: i32 = 6;
+        disassembler_test!(
+            je_imm32,
+            |imm| format!("je 0x{:x}", imm + INST_SIZE),
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_js_imm32() {
+        const INST_SIZE: i32 = 6;
+        disassembler_test!(
+            js_imm32,
+            |imm| format!("js 0x{:x}", imm + INST_SIZE),
+            [TEST_I32]
+        );
+    }
+
     #[test]
     fn test_mov_reg64_imm32() {
         disassembler_test!(
@@ -3463,6 +4420,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_movss_freg32_base32_offset32() {
+        disassembler_test!(
+            movss_freg32_base32_offset32,
+            |reg1, reg2, imm| format!("movss {}, dword ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_FLOAT_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_movss_base32_offset32_freg32() {
+        disassembler_test!(
+            movss_base32_offset32_freg32,
+            |reg1, imm, reg2| format!("movss dword ptr [{} + 0x{:x}], {}", reg1, imm, reg2),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            ALL_FLOAT_REGS
+        );
+    }
+
     #[test]
     fn test_mov_reg64_base64_offset32() {
         disassembler_test!(
@@ -3581,6 +4560,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_movsx_reg64_reg() {
+        disassembler_test!(
+            movsxd_reg64_reg32,
+            |reg1, reg2| format!(
+                "movsxd {}, {}",
+                reg1,
+                X86_64GeneralReg::low_32bits_string(&reg2)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            movsx_reg64_reg16,
+            |reg1, reg2| format!(
+                "movsx {}, {}",
+                reg1,
+                X86_64GeneralReg::low_16bits_string(&reg2)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            movsx_reg64_reg8,
+            |reg1, reg2| format!(
+                "movsx {}, {}",
+                reg1,
+                X86_64GeneralReg::low_8bits_string(&reg2)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_movzx_reg64_reg() {
+        disassembler_test!(
+            mov_reg32_reg32,
+            |reg1, reg2| format!(
+                "mov {}, {}",
+                X86_64GeneralReg::low_32bits_string(&reg1),
+                X86_64GeneralReg::low_32bits_string(&reg2)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            movzx_reg64_reg16,
+            |reg1, reg2| format!(
+                "movzx {}, {}",
+                reg1,
+                X86_64GeneralReg::low_16bits_string(&reg2)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            movzx_reg64_reg8,
+            |reg1, reg2| format!(
+                "movzx {}, {}",
+                reg1,
+                X86_64GeneralReg::low_8bits_string(&reg2)
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
     #[test]
     fn test_movsx_reg64_base32_offset32() {
         disassembler_test!(
@@ -3646,6 +4693,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_movq_reg64_freg64() {
+        disassembler_test!(
+            movq_reg64_freg64,
+            |reg1, reg2| format!("movq {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_FLOAT_REGS
+        );
+    }
+
+    #[test]
+    fn test_movd_reg32_freg32() {
+        disassembler_test!(
+            movd_reg32_freg32,
+            |reg1, reg2| format!(
+                "movd {}, {}",
+                X86_64GeneralReg::low_32bits_string(&reg1),
+                reg2
+            ),
+            ALL_GENERAL_REGS,
+            ALL_FLOAT_REGS
+        );
+    }
+
     #[test]
     fn test_movss_freg32_freg32() {
         disassembler_test!(
@@ -3724,6 +4795,16 @@ mod tests {
         disassembler_test!(ret, || "ret");
     }
 
+    #[test]
+    fn test_nop() {
+        disassembler_test!(nop, || "nop");
+    }
+
+    #[test]
+    fn test_ud2() {
+        disassembler_test!(ud2, || "ud2");
+    }
+
     #[test]
     fn test_sub_reg64_imm32() {
         disassembler_test!(
@@ -3744,6 +4825,11 @@ mod tests {
         disassembler_test!(push_reg64, |reg| format!("push {}", reg), ALL_GENERAL_REGS);
     }
 
+    #[test]
+    fn test_rep_movsb() {
+        disassembler_test!(rep_movsb, || "rep movsb byte ptr [rdi], byte ptr [rsi]");
+    }
+
     #[test]
     fn test_sqrt_freg64_freg64() {
         disassembler_test!(
@@ -3810,4 +4896,94 @@ mod tests {
             ALL_GENERAL_REGS
         );
     }
+
+    #[test]
+    fn test_stack_unwind_steps() {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        let mut unwind = bumpalo::vec![in &arena];
+        let stack_size =
+            X86_64SystemV::setup_stack(&mut buf, &mut unwind, &[X86_64GeneralReg::RBX], &[], 8, 0);
+        let setup_len = buf.len() as u32;
+        X86_64SystemV::cleanup_stack(
+            &mut buf,
+            &mut unwind,
+            &[X86_64GeneralReg::RBX],
+            &[],
+            stack_size,
+            0,
+        );
+        let cleanup_len = buf.len() as u32;
+
+        use UnwindStep::*;
+        assert_eq!(
+            unwind.as_slice(),
+            &[
+                // push rbp
+                Cfa {
+                    code_offset: 1,
+                    register: 7,
+                    offset: 16
+                },
+                Saved {
+                    code_offset: 1,
+                    register: 6,
+                    cfa_offset: -16
+                },
+                // mov rbp, rsp
+                Cfa {
+                    code_offset: 4,
+                    register: 6,
+                    offset: 16
+                },
+                // mov [rbp - 16], rbx
+                Saved {
+                    code_offset: setup_len,
+                    register: 3,
+                    cfa_offset: -32
+                },
+                // pop rbp
+                Cfa {
+                    code_offset: cleanup_len,
+                    register: 7,
+                    offset: 8
+                },
+                Restored {
+                    code_offset: cleanup_len,
+                    register: 6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_systemv_eightbyte_classes() {
+        use roc_target::TargetInfo;
+
+        let mut interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut classes_of = |field_layouts: &'static [InLayout<'static>]| {
+            let layout = interner.insert(Layout::struct_no_name_order(field_layouts));
+            X86_64SystemV::eightbyte_classes(&interner, layout)
+        };
+
+        use SystemVClass::*;
+        assert_eq!(classes_of(&[Layout::I64]), [Some(Integer), None]);
+        assert_eq!(
+            classes_of(&[Layout::F64, Layout::F64]),
+            [Some(Sse), Some(Sse)]
+        );
+        assert_eq!(
+            classes_of(&[Layout::F64, Layout::I64]),
+            [Some(Sse), Some(Integer)]
+        );
+        // Two f32s share an eightbyte, and an int anywhere in one makes it Integer.
+        assert_eq!(
+            classes_of(&[Layout::F64, Layout::F32, Layout::F32]),
+            [Some(Sse), Some(Sse)]
+        );
+        assert_eq!(
+            classes_of(&[Layout::F64, Layout::F32, Layout::U8]),
+            [Some(Sse), Some(Integer)]
+        );
+    }
 }
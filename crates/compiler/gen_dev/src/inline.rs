@@ -0,0 +1,350 @@
+//! Inlines calls to tiny procs into their callers, before code generation.
+//!
+//! Roc generates lots of procs that do little more than call another one, like the adapters
+//! between lambda sets and curried functions. In the dev backend each of those costs a call, a
+//! prologue and an epilogue. This pass replaces calls to procs whose whole body is a few `let`s
+//! followed by a `ret` with those `let`s, renamed so that every symbol in the caller stays unique.
+//!
+//! Only bodies without any refcounting are inlined. Such a body uses each argument exactly the way
+//! its signature says, so the refcounting the caller does around the call is still correct.
+//! The inlined procs are still built, because the host or a higher-order builtin may call them,
+//! but each one is in its own section, so the linker drops the ones nothing calls anymore.
+use bumpalo::{collections::Vec, Bump};
+use roc_collections::all::MutMap;
+use roc_module::symbol::{IdentIds, ModuleId, Symbol};
+use roc_mono::ir::{Call, CallType, Expr, Proc, ProcLayout, Stmt};
+use roc_mono::layout::InLayout;
+use std::fmt;
+
+/// Procs with at most this many `let`s in their body are inlined
+const MAX_INLINED_LETS: usize = 4;
+
+/// How many levels of calls to inline, when an inlined body calls another tiny proc
+const MAX_INLINE_DEPTH: usize = 8;
+
+/// Counts of the inlining done, for debugging
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InlineStats {
+    pub inlinable_procs: usize,
+    pub inlined_calls: usize,
+}
+
+impl fmt::Display for InlineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "inlined {} calls to {} small procs",
+            self.inlined_calls, self.inlinable_procs
+        )
+    }
+}
+
+/// The body of a proc that can be inlined: its `let`s, the last of which binds the return value
+struct InlineBody<'a> {
+    args: &'a [(InLayout<'a>, Symbol)],
+    lets: std::vec::Vec<(Symbol, Expr<'a>, InLayout<'a>)>,
+}
+
+pub fn inline_small_procs<'a>(
+    arena: &'a Bump,
+    module_id: ModuleId,
+    ident_ids: &mut IdentIds,
+    procedures: &mut MutMap<(Symbol, ProcLayout<'a>), Proc<'a>>,
+) -> InlineStats {
+    let bodies: MutMap<(Symbol, ProcLayout<'a>), InlineBody<'a>> = procedures
+        .iter()
+        .filter_map(|(key, proc)| Some((*key, inline_body(proc)?)))
+        .collect();
+
+    let mut env = Env {
+        arena,
+        module_id,
+        ident_ids,
+        bodies: &bodies,
+        stats: InlineStats {
+            inlinable_procs: bodies.len(),
+            inlined_calls: 0,
+        },
+    };
+
+    for proc in procedures.values_mut() {
+        proc.body = env.inline_stmt(&proc.body);
+    }
+
+    env.stats
+}
+
+/// The proc's body, if it's small and simple enough to inline
+fn inline_body<'a>(proc: &Proc<'a>) -> Option<InlineBody<'a>> {
+    let mut lets = std::vec::Vec::new();
+    let mut stmt = &proc.body;
+
+    loop {
+        match stmt {
+            Stmt::Let(symbol, expr, layout, continuation)
+                if lets.len() < MAX_INLINED_LETS && is_inlinable(expr, proc.name.name()) =>
+            {
+                lets.push((*symbol, expr.clone(), *layout));
+                stmt = continuation;
+            }
+            Stmt::Ret(symbol) => {
+                return match lets.last() {
+                    Some((last, _, _)) if last == symbol => Some(InlineBody {
+                        args: proc.args,
+                        lets,
+                    }),
+                    // Returning an argument would need an alias, which the IR can't express
+                    _ => None,
+                };
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Whether this is an expression that can be copied into another proc. Anything that refers to
+/// refcounting or memory reuse stays where it is.
+fn is_inlinable(expr: &Expr, proc_symbol: Symbol) -> bool {
+    match expr {
+        Expr::Call(Call {
+            call_type: CallType::ByName { name, .. },
+            ..
+        }) => name.name() != proc_symbol,
+        Expr::Call(Call {
+            call_type: CallType::LowLevel { .. } | CallType::Foreign { .. },
+            ..
+        }) => true,
+        Expr::Literal(_) | Expr::Struct(_) | Expr::StructAtIndex { .. } => true,
+        _ => false,
+    }
+}
+
+struct Env<'a, 'r> {
+    arena: &'a Bump,
+    module_id: ModuleId,
+    ident_ids: &'r mut IdentIds,
+    bodies: &'r MutMap<(Symbol, ProcLayout<'a>), InlineBody<'a>>,
+    stats: InlineStats,
+}
+
+impl<'a, 'r> Env<'a, 'r> {
+    fn inline_stmt(&mut self, stmt: &Stmt<'a>) -> Stmt<'a> {
+        match stmt {
+            Stmt::Let(symbol, expr, layout, continuation) => {
+                let mut lets = std::vec::Vec::new();
+                self.expand_let(*symbol, expr, *layout, 0, &mut lets);
+
+                let mut stmt = self.inline_stmt(continuation);
+                for (symbol, expr, layout) in lets.into_iter().rev() {
+                    stmt = Stmt::Let(symbol, expr, layout, self.arena.alloc(stmt));
+                }
+                stmt
+            }
+            Stmt::Switch {
+                cond_symbol,
+                cond_layout,
+                branches,
+                default_branch,
+                ret_layout,
+            } => {
+                let mut new_branches = Vec::with_capacity_in(branches.len(), self.arena);
+                for (value, info, branch) in branches.iter() {
+                    new_branches.push((*value, info.clone(), self.inline_stmt(branch)));
+                }
+                let default_stmt = self.inline_stmt(default_branch.1);
+
+                Stmt::Switch {
+                    cond_symbol: *cond_symbol,
+                    cond_layout: *cond_layout,
+                    branches: new_branches.into_bump_slice(),
+                    default_branch: (default_branch.0.clone(), self.arena.alloc(default_stmt)),
+                    ret_layout: *ret_layout,
+                }
+            }
+            Stmt::Refcounting(modify, continuation) => {
+                let continuation = self.inline_stmt(continuation);
+                Stmt::Refcounting(*modify, self.arena.alloc(continuation))
+            }
+            Stmt::Expect {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => {
+                let remainder = self.inline_stmt(remainder);
+                Stmt::Expect {
+                    condition: *condition,
+                    region: *region,
+                    lookups: *lookups,
+                    variables: *variables,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::ExpectFx {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => {
+                let remainder = self.inline_stmt(remainder);
+                Stmt::ExpectFx {
+                    condition: *condition,
+                    region: *region,
+                    lookups: *lookups,
+                    variables: *variables,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Dbg {
+                symbol,
+                variable,
+                remainder,
+            } => {
+                let remainder = self.inline_stmt(remainder);
+                Stmt::Dbg {
+                    symbol: *symbol,
+                    variable: *variable,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Join {
+                id,
+                parameters,
+                body,
+                remainder,
+            } => {
+                let body = self.inline_stmt(body);
+                let remainder = self.inline_stmt(remainder);
+                Stmt::Join {
+                    id: *id,
+                    parameters: *parameters,
+                    body: self.arena.alloc(body),
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Ret(_) | Stmt::Jump(_, _) | Stmt::Crash(_, _) => stmt.clone(),
+        }
+    }
+
+    /// Push the `let`s that `symbol = expr` becomes, which is the inlined body if it's a call to
+    /// a proc that can be inlined, or else just the `let` itself.
+    fn expand_let(
+        &mut self,
+        symbol: Symbol,
+        expr: &Expr<'a>,
+        layout: InLayout<'a>,
+        depth: usize,
+        lets: &mut std::vec::Vec<(Symbol, Expr<'a>, InLayout<'a>)>,
+    ) {
+        let body = match expr {
+            Expr::Call(Call {
+                call_type:
+                    CallType::ByName {
+                        name,
+                        ret_layout,
+                        arg_layouts,
+                        ..
+                    },
+                arguments,
+            }) if depth < MAX_INLINE_DEPTH => {
+                let bodies = self.bodies;
+                let key = (
+                    name.name(),
+                    ProcLayout {
+                        arguments: *arg_layouts,
+                        result: *ret_layout,
+                        niche: name.niche(),
+                    },
+                );
+
+                bodies
+                    .get(&key)
+                    .and_then(|body| Some((body, self.rename(body, symbol, arguments)?)))
+            }
+            _ => None,
+        };
+
+        match body {
+            Some((body, renamed)) => {
+                self.stats.inlined_calls += 1;
+
+                for ((new_symbol, new_expr), (_, _, layout)) in renamed.into_iter().zip(&body.lets)
+                {
+                    self.expand_let(new_symbol, &new_expr, *layout, depth + 1, lets);
+                }
+            }
+            None => lets.push((symbol, expr.clone(), layout)),
+        }
+    }
+
+    /// The `let`s of `body` with its arguments replaced by the caller's, and its own symbols by
+    /// new ones. The last `let` binds `result`, which is where the caller wants the return value.
+    fn rename(
+        &mut self,
+        body: &InlineBody<'a>,
+        result: Symbol,
+        arguments: &[Symbol],
+    ) -> Option<std::vec::Vec<(Symbol, Expr<'a>)>> {
+        if body.args.len() != arguments.len() {
+            return None;
+        }
+
+        let mut renames: MutMap<Symbol, Symbol> = body
+            .args
+            .iter()
+            .map(|(_, arg)| *arg)
+            .zip(arguments.iter().copied())
+            .collect();
+        let mut renamed = std::vec::Vec::with_capacity(body.lets.len());
+
+        for (index, (symbol, expr, _)) in body.lets.iter().enumerate() {
+            let new_expr = self.rename_expr(expr, &renames)?;
+            let new_symbol = if index + 1 == body.lets.len() {
+                result
+            } else {
+                Symbol::new(self.module_id, self.ident_ids.gen_unique())
+            };
+
+            renames.insert(*symbol, new_symbol);
+            renamed.push((new_symbol, new_expr));
+        }
+
+        Some(renamed)
+    }
+
+    /// The expression with its symbols renamed, or `None` if it uses a symbol from outside the
+    /// inlined body, which it shouldn't.
+    fn rename_expr(&self, expr: &Expr<'a>, renames: &MutMap<Symbol, Symbol>) -> Option<Expr<'a>> {
+        let rename_all = |symbols: &[Symbol]| -> Option<&'a [Symbol]> {
+            let mut renamed = Vec::with_capacity_in(symbols.len(), self.arena);
+            for symbol in symbols {
+                renamed.push(*renames.get(symbol)?);
+            }
+            Some(renamed.into_bump_slice())
+        };
+
+        match expr {
+            Expr::Literal(literal) => Some(Expr::Literal(*literal)),
+            Expr::Call(Call {
+                call_type,
+                arguments,
+            }) => Some(Expr::Call(Call {
+                call_type: call_type.clone(),
+                arguments: rename_all(arguments)?,
+            })),
+            Expr::Struct(fields) => Some(Expr::Struct(rename_all(fields)?)),
+            Expr::StructAtIndex {
+                index,
+                field_layouts,
+                structure,
+            } => Some(Expr::StructAtIndex {
+                index: *index,
+                field_layouts: *field_layouts,
+                structure: *renames.get(structure)?,
+            }),
+            _ => None,
+        }
+    }
+}
@@ -14,19 +14,32 @@ use roc_module::low_level::{LowLevel, LowLevelWrapperType};
 use roc_module::symbol::{Interns, ModuleId, Symbol};
 use roc_mono::code_gen_help::{CallerProc, CodeGenHelp};
 use roc_mono::ir::{
-    BranchInfo, CallType, Expr, HigherOrderLowLevel, JoinPointId, ListLiteralElement, Literal,
-    Param, Proc, ProcLayout, SelfRecursive, Stmt,
+    BranchInfo, CallType, CrashTag, Expr, HigherOrderLowLevel, JoinPointId, ListLiteralElement,
+    Literal, Param, Proc, ProcLayout, SelfRecursive, Stmt,
 };
 use roc_mono::layout::{
     Builtin, InLayout, Layout, LayoutIds, LayoutInterner, STLayoutInterner, TagIdIntType,
     UnionLayout,
 };
 use roc_mono::list_element_layout;
+use std::cell::RefCell;
 
+mod assembly;
 mod generic64;
+use generic64::CodeLayoutStats;
 mod object_builder;
 pub use object_builder::build_module;
+mod patchable;
+pub use patchable::{redirect_patchable_entry, PATCHABLE_ENTRIES_SECTION};
+mod inline;
+mod proc_stats;
 mod run_roc;
+pub use inline::{inline_small_procs, InlineStats};
+pub use proc_stats::{report_proc_stats, ProcStats, ProcStatsFormat};
+mod unsupported;
+pub use unsupported::{report_unsupported, Unsupported};
+mod unwind;
+use unwind::UnwindStep;
 
 pub struct Env<'a> {
     pub arena: &'a Bump,
@@ -34,8 +47,31 @@ pub struct Env<'a> {
     pub exposed_to_host: MutSet<Symbol>,
     pub lazy_literals: bool,
     pub generate_allocators: bool,
+    /// Call [ROC_LOG_REFCOUNT] before every refcount increment and decrement, with the pointer to
+    /// the refcount and the change, so that the host can check they're balanced.
+    pub log_refcounts: bool,
+    /// Everything that [build_module] found it couldn't generate code for. The procs they're in
+    /// crash when they're called, with a message saying what wasn't supported.
+    pub unsupported: RefCell<std::vec::Vec<Unsupported>>,
+    /// Make an assembly listing of every proc as it's built, in [Env::assembly].
+    pub emit_assembly: bool,
+    /// The listing of each proc that [build_module] built, when [Env::emit_assembly] is set: its
+    /// Roc name, its symbol, and its instructions, with the linker's relocations marked.
+    pub assembly: RefCell<String>,
+    /// Measure every proc as it's built, in [Env::proc_stats].
+    pub collect_proc_stats: bool,
+    /// The size, spills, relocations, helpers, and build time of each proc that [build_module]
+    /// built, when [Env::collect_proc_stats] is set. See [report_proc_stats].
+    pub proc_stats: RefCell<std::vec::Vec<ProcStats>>,
+    /// Start every proc with a patchable entry, so that an incremental dev loop can redirect
+    /// calls to it to a recompiled version without relinking. See [redirect_patchable_entry].
+    pub patchable_prologues: bool,
 }
 
+/// The host function that is called to log refcount changes, when [Env::log_refcounts] is set.
+/// Its arguments are the pointer to the refcount and the change, e.g. -1 for a decrement.
+pub const ROC_LOG_REFCOUNT: &str = "roc_log_refcount";
+
 // These relocations likely will need a length.
 // They may even need more definition, but this should be at least good enough for how we will use elf.
 #[derive(Debug, Clone)]
@@ -46,11 +82,15 @@ pub enum Relocation {
         // This should probably technically be a bumpalo::Vec.
         // The problem is that it currently is built in a place that can't access the arena.
         data: std::vec::Vec<u8>,
+        access: DataAccess,
     },
     LinkedFunction {
         offset: u64,
         name: String,
     },
+    /// Loads the address of the data from the GOT.
+    /// On x86_64 `offset` is a 32 bit RIP-relative field, and on aarch64 it is an ADRP
+    /// followed by a 64 bit LDR.
     LinkedData {
         offset: u64,
         name: String,
@@ -62,6 +102,30 @@ pub enum Relocation {
     },
 }
 
+/// The instructions at the offset of a [Relocation::LocalData], which decide how it is patched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataAccess {
+    /// x86_64: a 32 bit displacement from the end of the instruction
+    RipRelative,
+    /// aarch64: an ADRP, then an ADD that completes the data's address
+    PageAdd,
+    /// aarch64: an ADRP, then an LDR of 4 bytes from the data
+    PageLoad32,
+    /// aarch64: an ADRP, then an LDR of 8 bytes from the data
+    PageLoad64,
+}
+
+/// How floats are ordered by a comparison that returns an `Order`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FloatOrdering {
+    /// The IEEE 754 comparisons: `-0.0` and `0.0` are equal, and a comparison with NaN is
+    /// unordered, which comes out as `GT` like in the other backends
+    Ieee,
+    /// IEEE 754 totalOrder, for sorting: NaN is above Infinity (or below -Infinity, when its sign
+    /// bit is set), and `-0.0` is below `0.0`
+    Total,
+}
+
 trait Backend<'a> {
     fn env(&self) -> &Env<'a>;
     fn interns(&self) -> &Interns;
@@ -149,7 +213,8 @@ trait Backend<'a> {
     /// finalize does setup because things like stack size and jump locations are not know until the function is written.
     /// For example, this can store the frame pointer and setup stack space.
     /// finalize is run at the end of build_proc when all internal code is finalized.
-    fn finalize(&mut self) -> (Vec<u8>, Vec<Relocation>);
+    /// Returns the procedure bytes, its relocations, and how to unwind its frame.
+    fn finalize(&mut self) -> (Vec<u8>, Vec<Relocation>, Vec<UnwindStep>);
 
     // load_args is used to let the backend know what the args are.
     // The backend should track these args so it can use them as needed.
@@ -159,12 +224,18 @@ trait Backend<'a> {
     fn build_wrapped_jmp(&mut self) -> (&'a [u8], u64);
 
     /// build_proc creates a procedure and outputs it to the wrapped object writer.
-    /// Returns the procedure bytes, its relocations, and the names of the refcounting functions it references.
+    /// Returns the procedure bytes, its relocations, how to unwind its frame,
+    /// and the names of the refcounting functions it references.
     fn build_proc(
         &mut self,
         proc: Proc<'a>,
         layout_ids: &mut LayoutIds<'a>,
-    ) -> (Vec<u8>, Vec<Relocation>, Vec<'a, (Symbol, String)>) {
+    ) -> (
+        Vec<u8>,
+        Vec<Relocation>,
+        Vec<UnwindStep>,
+        Vec<'a, (Symbol, String)>,
+    ) {
         let proc_name = self.function_symbol_to_string(
             proc.name.name(),
             proc.args.iter().map(|t| t.0),
@@ -173,13 +244,27 @@ trait Backend<'a> {
         );
 
         self.reset(proc_name, proc.is_self_recursive);
-        self.load_args(proc.args, &proc.ret_layout);
-        for (layout, sym) in proc.args {
-            self.set_layout_map(*sym, layout);
+
+        // Check before generating anything, since the backend can't stop part way through a proc
+        match unsupported::find_unsupported(self.interner(), &proc) {
+            None => {
+                self.load_args(proc.args, &proc.ret_layout);
+                for (layout, sym) in proc.args {
+                    self.set_layout_map(*sym, layout);
+                }
+                self.scan_ast(&proc.body);
+                self.create_free_map();
+                self.scan_calls(&proc.body, &mut MutSet::default());
+                self.build_stmt(&proc.body, &proc.ret_layout);
+            }
+            Some(unsupported) => {
+                let message = unsupported.message(self.interns());
+                let message = self.env().arena.alloc_str(&message);
+                self.load_literal(&Symbol::DEV_TMP4, &Layout::STR, &Literal::Str(message));
+                self.build_crash(&Symbol::DEV_TMP4, CrashTag::Roc);
+                self.env().unsupported.borrow_mut().push(unsupported);
+            }
         }
-        self.scan_ast(&proc.body);
-        self.create_free_map();
-        self.build_stmt(&proc.body, &proc.ret_layout);
         let mut helper_proc_names = bumpalo::vec![in self.env().arena];
         helper_proc_names.reserve(self.helper_proc_symbols().len());
         for (rc_proc_sym, rc_proc_layout) in self.helper_proc_symbols() {
@@ -189,14 +274,17 @@ trait Backend<'a> {
 
             helper_proc_names.push((*rc_proc_sym, name));
         }
-        let (bytes, relocs) = self.finalize();
-        (bytes, relocs, helper_proc_names)
+        let (bytes, relocs, unwind) = self.finalize();
+        (bytes, relocs, unwind, helper_proc_names)
     }
 
     /// build_stmt builds a statement and outputs at the end of the buffer.
     fn build_stmt(&mut self, stmt: &Stmt<'a>, ret_layout: &InLayout<'a>) {
+        self.trace_stmt(stmt);
+
         match stmt {
             Stmt::Let(sym, expr, layout, following) => {
+                self.mark_dying_symbols(stmt);
                 self.build_expr(sym, expr, layout);
                 self.set_layout_map(*sym, layout);
                 self.free_symbols(stmt);
@@ -279,7 +367,12 @@ trait Backend<'a> {
                 self.build_jump(id, args, arg_layouts.into_bump_slice(), ret_layout);
                 self.free_symbols(stmt);
             }
-            x => todo!("the statement, {:?}", x),
+            Stmt::Crash(msg, tag) => {
+                self.load_literal_symbols(&[*msg]);
+                self.build_crash(msg, *tag);
+                self.free_symbols(stmt);
+            }
+            x => internal_error!("the statement, {:?}, should have been found unsupported", x),
         }
     }
     // build_switch generates a instructions for a switch statement.
@@ -311,6 +404,9 @@ trait Backend<'a> {
         ret_layout: &InLayout<'a>,
     );
 
+    /// build_crash calls roc_panic with the Str in msg and the tag. It does not return.
+    fn build_crash(&mut self, msg: &Symbol, tag: CrashTag);
+
     /// build_expr builds the expressions for the specified symbol.
     /// The builder must keep track of the symbol because it may be referred to later.
     fn build_expr(&mut self, sym: &Symbol, expr: &Expr<'a>, layout: &InLayout<'a>) {
@@ -364,7 +460,7 @@ trait Backend<'a> {
 
                         // Now that the arguments are needed, load them if they are literals.
                         self.load_literal_symbols(arguments);
-                        self.build_fn_call(sym, fn_name, arguments, arg_layouts, ret_layout)
+                        self.build_proc_call(sym, fn_name, arguments, arg_layouts, ret_layout)
                     }
 
                     CallType::LowLevel { op: lowlevel, .. } => {
@@ -390,7 +486,10 @@ trait Backend<'a> {
                     CallType::HigherOrder(higher_order) => {
                         self.build_higher_order_lowlevel(sym, higher_order, *layout)
                     }
-                    x => todo!("the call type, {:?}", x),
+                    x => internal_error!(
+                        "the call type, {:?}, should have been found unsupported",
+                        x
+                    ),
                 }
             }
             Expr::EmptyArray => {
@@ -458,7 +557,10 @@ trait Backend<'a> {
                 self.load_literal_symbols([*ptr].as_slice());
                 self.expr_unbox(*sym, *ptr, element_layout)
             }
-            x => todo!("the expression, {:?}", x),
+            x => internal_error!(
+                "the expression, {:?}, should have been found unsupported",
+                x
+            ),
         }
     }
 
@@ -472,6 +574,10 @@ trait Backend<'a> {
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
     ) {
+        if self.build_low_level_with_immediate(sym, lowlevel, args, ret_layout) {
+            return;
+        }
+
         // Now that the arguments are needed, load them if they are literals.
         self.load_literal_symbols(args);
         match lowlevel {
@@ -501,7 +607,8 @@ trait Backend<'a> {
                     arg_layouts[0], *ret_layout,
                     "NumAdd: expected to have the same argument and return layout"
                 );
-                self.build_num_add(sym, &args[0], &args[1], ret_layout)
+                self.build_num_add(sym, &args[0], &args[1], ret_layout);
+                self.build_panic_on_overflow(*lowlevel, sym, &args[0], &args[1], ret_layout)
             }
             LowLevel::NumAddWrap => {
                 debug_assert_eq!(
@@ -590,13 +697,89 @@ trait Backend<'a> {
                 );
                 self.build_num_neg(sym, &args[0], ret_layout)
             }
-            LowLevel::NumPowInt => self.build_fn_call(
-                sym,
-                bitcode::NUM_POW_INT[IntWidth::I64].to_string(),
-                args,
-                arg_layouts,
-                ret_layout,
-            ),
+            LowLevel::NumCountLeadingZeroBits
+            | LowLevel::NumCountTrailingZeroBits
+            | LowLevel::NumCountOneBits => {
+                let int_width = match self.interner().get(arg_layouts[0]) {
+                    Layout::Builtin(Builtin::Int(int_width)) => int_width,
+                    _ => internal_error!("{:?} on a non-integer", lowlevel),
+                };
+
+                match int_width {
+                    // These don't fit in a register, so use the same zig builtins as the other
+                    // backends
+                    IntWidth::I128 | IntWidth::U128 => {
+                        let intrinsic = match lowlevel {
+                            LowLevel::NumCountLeadingZeroBits => {
+                                &bitcode::NUM_COUNT_LEADING_ZERO_BITS[int_width]
+                            }
+                            LowLevel::NumCountTrailingZeroBits => {
+                                &bitcode::NUM_COUNT_TRAILING_ZERO_BITS[int_width]
+                            }
+                            _ => &bitcode::NUM_COUNT_ONE_BITS[int_width],
+                        };
+
+                        self.build_fn_call(
+                            sym,
+                            intrinsic.to_string(),
+                            args,
+                            arg_layouts,
+                            ret_layout,
+                        )
+                    }
+                    _ => match lowlevel {
+                        LowLevel::NumCountLeadingZeroBits => {
+                            self.build_int_count_leading_zero_bits(sym, &args[0], int_width)
+                        }
+                        LowLevel::NumCountTrailingZeroBits => {
+                            self.build_int_count_trailing_zero_bits(sym, &args[0], int_width)
+                        }
+                        _ => self.build_int_count_one_bits(sym, &args[0], int_width),
+                    },
+                }
+            }
+            LowLevel::NumPowInt => {
+                let int_width = match self.interner().get(*ret_layout) {
+                    Layout::Builtin(Builtin::Int(int_width)) => int_width,
+                    _ => internal_error!("NumPowInt with a non-integer return layout"),
+                };
+
+                self.build_fn_call(
+                    sym,
+                    bitcode::NUM_POW_INT[int_width].to_string(),
+                    args,
+                    arg_layouts,
+                    ret_layout,
+                )
+            }
+            LowLevel::NumPow => {
+                let float_width = match self.interner().get(*ret_layout) {
+                    Layout::Builtin(Builtin::Float(float_width)) => float_width,
+                    _ => internal_error!("NumPow with a non-float return layout"),
+                };
+
+                self.build_fn_call(
+                    sym,
+                    bitcode::NUM_POW[float_width].to_string(),
+                    args,
+                    arg_layouts,
+                    ret_layout,
+                )
+            }
+            LowLevel::NumDivCeilUnchecked => {
+                let int_width = match self.interner().get(*ret_layout) {
+                    Layout::Builtin(Builtin::Int(int_width)) => int_width,
+                    _ => internal_error!("NumDivCeilUnchecked with a non-integer return layout"),
+                };
+
+                self.build_fn_call(
+                    sym,
+                    bitcode::NUM_DIV_CEIL[int_width].to_string(),
+                    args,
+                    arg_layouts,
+                    ret_layout,
+                )
+            }
             LowLevel::NumSub => {
                 debug_assert_eq!(
                     2,
@@ -611,7 +794,8 @@ trait Backend<'a> {
                     arg_layouts[0], *ret_layout,
                     "NumSub: expected to have the same argument and return layout"
                 );
-                self.build_num_sub(sym, &args[0], &args[1], ret_layout)
+                self.build_num_sub(sym, &args[0], &args[1], ret_layout);
+                self.build_panic_on_overflow(*lowlevel, sym, &args[0], &args[1], ret_layout)
             }
             LowLevel::NumSubWrap => {
                 debug_assert_eq!(
@@ -644,10 +828,6 @@ trait Backend<'a> {
                     // saturated sub is just normal sub
                     self.build_num_sub(sym, &args[0], &args[1], ret_layout)
                 }
-                Layout::Builtin(Builtin::Decimal) => {
-                    // self.load_args_and_call_zig(backend, bitcode::DEC_SUB_SATURATED)
-                    todo!()
-                }
                 _ => internal_error!("invalid return type"),
             },
             LowLevel::NumBitwiseAnd => {
@@ -672,6 +852,8 @@ trait Backend<'a> {
                 }
             }
             LowLevel::And => {
+                // Roc's `&&` and `||` evaluate both sides (see `Bool.and`), so there is no control
+                // flow to lower here. Both arguments are 0 or 1, so a bitwise operation is exact.
                 if let Layout::Builtin(Builtin::Bool) = self.interner().get(*ret_layout) {
                     self.build_int_bitwise_and(sym, &args[0], &args[1], IntWidth::U8)
                 } else {
@@ -779,6 +961,31 @@ trait Backend<'a> {
                 );
                 self.build_num_gt(sym, &args[0], &args[1], &arg_layouts[0])
             }
+            LowLevel::NumCompare | LowLevel::NumCompareTotal => {
+                debug_assert_eq!(
+                    2,
+                    args.len(),
+                    "{:?}: expected to have exactly two arguments",
+                    lowlevel
+                );
+                debug_assert_eq!(
+                    arg_layouts[0], arg_layouts[1],
+                    "{:?}: expected all arguments of to have the same layout",
+                    lowlevel
+                );
+                debug_assert_eq!(
+                    Layout::U8,
+                    *ret_layout,
+                    "{:?}: expected to have return layout of type Order",
+                    lowlevel
+                );
+
+                let float_ordering = match lowlevel {
+                    LowLevel::NumCompareTotal => FloatOrdering::Total,
+                    _ => FloatOrdering::Ieee,
+                };
+                self.build_num_compare(sym, &args[0], &args[1], &arg_layouts[0], float_ordering)
+            }
             LowLevel::NumToFrac => {
                 debug_assert_eq!(
                     1,
@@ -786,11 +993,46 @@ trait Backend<'a> {
                     "NumToFrac: expected to have exactly one argument"
                 );
 
-                debug_assert!(
-                    matches!(*ret_layout, Layout::F32 | Layout::F64),
-                    "NumToFrac: expected to have return layout of type Float"
-                );
-                self.build_num_to_frac(sym, &args[0], &arg_layouts[0], ret_layout)
+                let float_width = match *ret_layout {
+                    Layout::F32 => FloatWidth::F32,
+                    Layout::F64 => FloatWidth::F64,
+                    _ => internal_error!("NumToFrac: expected to have return layout of type Float"),
+                };
+
+                // 128-bit integers and decimals don't fit in a register, so zig converts those
+                match self.interner().get(arg_layouts[0]) {
+                    Layout::Builtin(Builtin::Int(
+                        int_width @ (IntWidth::I128 | IntWidth::U128),
+                    )) => {
+                        let intrinsic = match float_width {
+                            FloatWidth::F32 => &bitcode::NUM_INT_TO_F32[int_width],
+                            FloatWidth::F64 => &bitcode::NUM_INT_TO_F64[int_width],
+                        };
+
+                        self.build_fn_call(
+                            sym,
+                            intrinsic.to_string(),
+                            args,
+                            arg_layouts,
+                            ret_layout,
+                        )
+                    }
+                    Layout::Builtin(Builtin::Decimal) => {
+                        let intrinsic = match float_width {
+                            FloatWidth::F32 => bitcode::DEC_TO_F32,
+                            FloatWidth::F64 => bitcode::DEC_TO_F64,
+                        };
+
+                        self.build_fn_call(
+                            sym,
+                            intrinsic.to_string(),
+                            args,
+                            arg_layouts,
+                            ret_layout,
+                        )
+                    }
+                    _ => self.build_num_to_frac(sym, &args[0], &arg_layouts[0], ret_layout),
+                }
             }
             LowLevel::NumLte => {
                 debug_assert_eq!(
@@ -850,13 +1092,27 @@ trait Backend<'a> {
 
                 self.build_num_sqrt(*sym, args[0], float_width);
             }
-            LowLevel::NumRound => self.build_fn_call(
-                sym,
-                bitcode::NUM_ROUND_F64[IntWidth::I64].to_string(),
-                args,
-                arg_layouts,
-                ret_layout,
-            ),
+            LowLevel::NumRound | LowLevel::NumFloor | LowLevel::NumCeiling => {
+                let int_width = match self.interner().get(*ret_layout) {
+                    Layout::Builtin(Builtin::Int(int_width)) => int_width,
+                    _ => internal_error!("{:?} with a non-integer return layout", lowlevel),
+                };
+
+                let intrinsic = match (lowlevel, arg_layouts[0]) {
+                    (LowLevel::NumRound, Layout::F32) => &bitcode::NUM_ROUND_F32[int_width],
+                    (LowLevel::NumRound, Layout::F64) => &bitcode::NUM_ROUND_F64[int_width],
+                    (LowLevel::NumFloor, Layout::F32) => &bitcode::NUM_FLOOR_F32[int_width],
+                    (LowLevel::NumFloor, Layout::F64) => &bitcode::NUM_FLOOR_F64[int_width],
+                    (LowLevel::NumCeiling, Layout::F32) => &bitcode::NUM_CEILING_F32[int_width],
+                    (LowLevel::NumCeiling, Layout::F64) => &bitcode::NUM_CEILING_F64[int_width],
+                    (LowLevel::NumRound, Layout::DEC) => &bitcode::DEC_ROUND[int_width],
+                    (LowLevel::NumFloor, Layout::DEC) => &bitcode::DEC_FLOOR[int_width],
+                    (LowLevel::NumCeiling, Layout::DEC) => &bitcode::DEC_CEILING[int_width],
+                    _ => internal_error!("{:?} on a non-fractional number", lowlevel),
+                };
+
+                self.build_fn_call(sym, intrinsic.to_string(), args, arg_layouts, ret_layout)
+            }
             LowLevel::ListLen => {
                 debug_assert_eq!(
                     1,
@@ -865,6 +1121,14 @@ trait Backend<'a> {
                 );
                 self.build_list_len(sym, &args[0])
             }
+            LowLevel::ListGetCapacity => {
+                debug_assert_eq!(
+                    1,
+                    args.len(),
+                    "ListGetCapacity: expected to have exactly one argument"
+                );
+                self.build_list_get_capacity(sym, &args[0])
+            }
             LowLevel::ListWithCapacity => {
                 debug_assert_eq!(
                     1,
@@ -906,6 +1170,15 @@ trait Backend<'a> {
                 );
                 self.build_list_replace_unsafe(sym, args, arg_layouts, ret_layout)
             }
+            LowLevel::ListSwap => {
+                debug_assert_eq!(
+                    3,
+                    args.len(),
+                    "ListSwap: expected to have exactly three arguments"
+                );
+                let elem_layout = list_element_layout!(self.interner(), *ret_layout);
+                self.build_list_swap(sym, args, arg_layouts, elem_layout, ret_layout)
+            }
             LowLevel::ListConcat => {
                 debug_assert_eq!(
                     2,
@@ -1078,8 +1351,11 @@ trait Backend<'a> {
                 ret_layout,
             ),
             LowLevel::StrToNum => {
+                // The result is the record `{ aresult : Num a, berrorcode : U8 }`, which has the
+                // same layout as zig's `NumParseResult`. The calling convention decides whether
+                // it comes back in registers or through a pointer to the stack.
                 let number_layout = match self.interner().get(*ret_layout) {
-                    Layout::Struct { field_layouts, .. } => field_layouts[0], // TODO: why is it sometimes a struct?
+                    Layout::Struct { field_layouts, .. } => field_layouts[0],
                     _ => unreachable!(),
                 };
 
@@ -1099,7 +1375,7 @@ trait Backend<'a> {
                 debug_assert_eq!(
                     1,
                     args.len(),
-                    "RefCountGetPtr: expected to have exactly one argument"
+                    "PtrCast: expected to have exactly one argument"
                 );
                 self.build_ptr_cast(sym, &args[0])
             }
@@ -1111,20 +1387,38 @@ trait Backend<'a> {
 
                 self.build_ptr_write(*sym, args[0], args[1], element_layout);
             }
-            LowLevel::RefCountDec => self.build_fn_call(
-                sym,
-                bitcode::UTILS_DECREF.to_string(),
-                args,
-                arg_layouts,
-                ret_layout,
-            ),
-            LowLevel::RefCountInc => self.build_fn_call(
-                sym,
-                bitcode::UTILS_INCREF.to_string(),
-                args,
-                arg_layouts,
-                ret_layout,
-            ),
+            LowLevel::RefCountDec => {
+                if self.env().log_refcounts {
+                    self.load_literal(
+                        &Symbol::DEV_TMP,
+                        &Layout::I64,
+                        &Literal::Int((-1i128).to_ne_bytes()),
+                    );
+                    self.build_log_refcount(&args[0], &Symbol::DEV_TMP, &arg_layouts[0]);
+                    self.free_symbol(&Symbol::DEV_TMP);
+                }
+
+                self.build_fn_call(
+                    sym,
+                    bitcode::UTILS_DECREF.to_string(),
+                    args,
+                    arg_layouts,
+                    ret_layout,
+                )
+            }
+            LowLevel::RefCountInc => {
+                if self.env().log_refcounts {
+                    self.build_log_refcount(&args[0], &args[1], &arg_layouts[0]);
+                }
+
+                self.build_fn_call(
+                    sym,
+                    bitcode::UTILS_INCREF.to_string(),
+                    args,
+                    arg_layouts,
+                    ret_layout,
+                )
+            }
             LowLevel::NumToStr => {
                 let arg_layout = arg_layouts[0];
                 let intrinsic = match self.interner().get(arg_layout) {
@@ -1137,9 +1431,20 @@ trait Backend<'a> {
                 self.build_fn_call(sym, intrinsic.to_string(), args, arg_layouts, ret_layout)
             }
             LowLevel::StrIsEmpty => {
-                let intrinsic = bitcode::STR_IS_EMPTY.to_string();
-                self.build_fn_call(sym, intrinsic, args, arg_layouts, ret_layout);
+                debug_assert_eq!(
+                    1,
+                    args.len(),
+                    "StrIsEmpty: expected to have exactly one argument"
+                );
+                self.build_str_is_empty(sym, &args[0])
             }
+            LowLevel::StrGetCapacity => self.build_fn_call(
+                sym,
+                bitcode::STR_CAPACITY.to_string(),
+                args,
+                arg_layouts,
+                ret_layout,
+            ),
             LowLevel::NumIntCast => {
                 let source_width = match self.interner().get(arg_layouts[0]) {
                     Layout::Builtin(Builtin::Int(width)) => width,
@@ -1153,7 +1458,7 @@ trait Backend<'a> {
 
                 self.build_num_int_cast(sym, &args[0], source_width, target_width)
             }
-            x => todo!("low level, {:?}", x),
+            x => internal_error!("low level, {:?}, should have been found unsupported", x),
         }
     }
 
@@ -1261,6 +1566,17 @@ trait Backend<'a> {
         ret_layout: &InLayout<'a>,
     );
 
+    /// build_proc_call is build_fn_call for a proc called directly by a let statement.
+    /// Values that the statement uses for the last time do not need to survive the call.
+    fn build_proc_call(
+        &mut self,
+        dst: &Symbol,
+        fn_name: String,
+        args: &[Symbol],
+        arg_layouts: &[InLayout<'a>],
+        ret_layout: &InLayout<'a>,
+    );
+
     fn build_fn_pointer(&mut self, dst: &Symbol, fn_name: String);
 
     /// Move a returned value into `dst`
@@ -1281,6 +1597,20 @@ trait Backend<'a> {
     /// build_num_add stores the sum of src1 and src2 into dst.
     fn build_num_add(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>);
 
+    /// build_panic_on_overflow panics if dst, the result of the lowlevel NumAdd or NumSub
+    /// on src1 and src2, overflowed. It does nothing for layouts that can't overflow.
+    fn build_panic_on_overflow(
+        &mut self,
+        lowlevel: LowLevel,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    );
+
+    /// build_int_add_imm32 stores the sum of the integer src and imm32 into dst.
+    fn build_int_add_imm32(&mut self, dst: &Symbol, src: &Symbol, imm32: i32);
+
     /// build_num_add_checked stores the sum of src1 and src2 into dst.
     fn build_num_add_checked(
         &mut self,
@@ -1376,6 +1706,52 @@ trait Backend<'a> {
         int_width: IntWidth,
     );
 
+    /// stores the `Num.countLeadingZeroBits src` into dst.
+    fn build_int_count_leading_zero_bits(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        int_width: IntWidth,
+    );
+
+    /// stores the `Num.countTrailingZeroBits src` into dst.
+    fn build_int_count_trailing_zero_bits(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        int_width: IntWidth,
+    );
+
+    /// stores the `Num.countOneBits src` into dst.
+    fn build_int_count_one_bits(&mut self, dst: &Symbol, src: &Symbol, int_width: IntWidth);
+
+    /// stores the `Num.shiftLeftBy src amount` into dst, for an amount known at compile time.
+    fn build_int_shift_left_imm(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        amount: u8,
+        int_width: IntWidth,
+    );
+
+    /// stores the `Num.shiftRightBy src amount` into dst, for an amount known at compile time.
+    fn build_int_shift_right_imm(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        amount: u8,
+        int_width: IntWidth,
+    );
+
+    /// stores the `Num.shiftRightZfBy src amount` into dst, for an amount known at compile time.
+    fn build_int_shift_right_zero_fill_imm(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        amount: u8,
+        int_width: IntWidth,
+    );
+
     /// build_eq stores the result of `src1 == src2` into dst.
     fn build_eq(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, arg_layout: &InLayout<'a>);
 
@@ -1403,6 +1779,17 @@ trait Backend<'a> {
         arg_layout: &InLayout<'a>,
     );
 
+    /// build_num_compare stores the `Order` of `src1` relative to `src2` into dst:
+    /// 0 for `EQ`, 1 for `GT` and 2 for `LT`.
+    fn build_num_compare(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        arg_layout: &InLayout<'a>,
+        float_ordering: FloatOrdering,
+    );
+
     /// build_num_to_frac convert Number to Frac
     fn build_num_to_frac(
         &mut self,
@@ -1436,6 +1823,12 @@ trait Backend<'a> {
     /// build_list_len returns the length of a list.
     fn build_list_len(&mut self, dst: &Symbol, list: &Symbol);
 
+    /// build_list_get_capacity returns the capacity of a list, taking seamless slices into account.
+    fn build_list_get_capacity(&mut self, dst: &Symbol, list: &Symbol);
+
+    /// build_str_is_empty checks whether a string has zero length, whether it is small or not.
+    fn build_str_is_empty(&mut self, dst: &Symbol, string: &Symbol);
+
     /// generate a call to a higher-order lowlevel
     fn build_higher_order_lowlevel(
         &mut self,
@@ -1482,6 +1875,7 @@ trait Backend<'a> {
     );
 
     /// build_list_replace_unsafe returns the old element and new list with the list having the new element inserted.
+    /// Unique lists are updated in place, shared lists are copied first.
     fn build_list_replace_unsafe(
         &mut self,
         dst: &Symbol,
@@ -1490,6 +1884,17 @@ trait Backend<'a> {
         ret_layout: &InLayout<'a>,
     );
 
+    /// build_list_swap returns the list with the elements at the two indices swapped.
+    /// Unique lists are updated in place, shared lists are copied first.
+    fn build_list_swap(
+        &mut self,
+        dst: &Symbol,
+        args: &'a [Symbol],
+        arg_layouts: &[InLayout<'a>],
+        elem_layout: InLayout<'a>,
+        ret_layout: &InLayout<'a>,
+    );
+
     /// build_list_concat returns a new list containing the two argument lists concatenated.
     fn build_list_concat(
         &mut self,
@@ -1509,7 +1914,22 @@ trait Backend<'a> {
         ret_layout: &InLayout<'a>,
     );
 
-    /// build_refcount_getptr loads the pointer to the reference count of src into dst.
+    /// build_log_refcount calls the host's [ROC_LOG_REFCOUNT] with the pointer to a refcount and
+    /// how much it is about to change by.
+    fn build_log_refcount(&mut self, ptr: &Symbol, delta: &Symbol, ptr_layout: &InLayout<'a>) {
+        self.build_fn_call(
+            &Symbol::DEV_TMP2,
+            ROC_LOG_REFCOUNT.to_string(),
+            &[*ptr, *delta],
+            &[*ptr_layout, Layout::I64],
+            &Layout::UNIT,
+        );
+        self.free_symbol(&Symbol::DEV_TMP2);
+    }
+
+    /// build_ptr_cast reinterprets the pointer-sized value in src as dst, e.g. an integer as a
+    /// box or the other way around. Only the layout changes, not the bits.
+    /// A value too big for a register, like a struct, is cast to its address instead.
     fn build_ptr_cast(&mut self, dst: &Symbol, src: &Symbol);
 
     fn build_ptr_write(
@@ -1520,6 +1940,74 @@ trait Backend<'a> {
         element_layout: InLayout<'a>,
     );
 
+    /// Builds shifts and integer additions whose second argument is a literal with immediate
+    /// instructions, so the literal never needs to be loaded into a register.
+    /// Returns false if the low level can't be built this way.
+    fn build_low_level_with_immediate(
+        &mut self,
+        sym: &Symbol,
+        lowlevel: &LowLevel,
+        args: &'a [Symbol],
+        ret_layout: &InLayout<'a>,
+    ) -> bool {
+        let int_width = match self.interner().get(*ret_layout) {
+            Layout::Builtin(Builtin::Int(int_width)) => int_width,
+            _ => return false,
+        };
+
+        if args.len() != 2 || matches!(int_width, IntWidth::U128 | IntWidth::I128) {
+            return false;
+        }
+
+        let value = match self.int_literal_value(&args[1]) {
+            Some(value) => value,
+            None => return false,
+        };
+
+        match lowlevel {
+            // Larger shifts behave differently on each architecture, so leave them to the general case
+            LowLevel::NumShiftLeftBy | LowLevel::NumShiftRightBy | LowLevel::NumShiftRightZfBy
+                if (0..64).contains(&value) =>
+            {
+                self.load_literal_symbols(&args[..1]);
+
+                let amount = value as u8;
+                match lowlevel {
+                    LowLevel::NumShiftLeftBy => {
+                        self.build_int_shift_left_imm(sym, &args[0], amount, int_width)
+                    }
+                    LowLevel::NumShiftRightBy => {
+                        self.build_int_shift_right_imm(sym, &args[0], amount, int_width)
+                    }
+                    _ => self.build_int_shift_right_zero_fill_imm(sym, &args[0], amount, int_width),
+                }
+            }
+            // Every architecture can add these without needing a temporary register. NumAdd is
+            // left out, it needs both operands in registers to check for overflow.
+            LowLevel::NumAddWrap if (0..(1 << 24)).contains(&value) => {
+                self.load_literal_symbols(&args[..1]);
+                self.build_int_add_imm32(sym, &args[0], value as i32);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// The value of an integer literal that hasn't been loaded yet, if the symbol is one
+    fn int_literal_value(&mut self, sym: &Symbol) -> Option<i128> {
+        if !self.env().lazy_literals {
+            return None;
+        }
+
+        let (lit, _) = self.literal_map().get(sym)?;
+        // Safe for the same reason as in load_literal_symbols
+        match unsafe { **lit } {
+            Literal::Int(bytes) => Some(i128::from_ne_bytes(bytes)),
+            _ => None,
+        }
+    }
+
     /// literal_map gets the map from symbol to literal and layout, used for lazy loading and literal folding.
     fn literal_map(&mut self) -> &mut MutMap<Symbol, (*const Literal<'a>, *const InLayout<'a>)>;
 
@@ -1652,6 +2140,83 @@ trait Backend<'a> {
     /// set_free_map sets the free map to the given map.
     fn set_free_map(&mut self, map: MutMap<*const Stmt<'a>, Vec<'a, Symbol>>);
 
+    /// mark_dying_symbols records the symbols that the given statement uses for the last time.
+    /// It is called right before the statement is built.
+    fn mark_dying_symbols(&mut self, stmt: &Stmt<'a>);
+
+    /// live_across_calls gets the set of symbols that are still needed after a call is made.
+    fn live_across_calls(&mut self) -> &mut MutSet<Symbol>;
+
+    /// trace_stmt records where the code for a statement starts, when ROC_LOG_DEV_EMIT is set.
+    fn trace_stmt(&mut self, stmt: &Stmt<'a>);
+
+    /// code_layout_stats gets how much code was moved out of the hot paths, for ROC_PRINT_DEV_CODE_LAYOUT.
+    fn code_layout_stats(&self) -> &CodeLayoutStats;
+
+    /// spill_count gets how many values the proc being built moved from registers to the stack
+    /// to free the register, for [ProcStats].
+    fn spill_count(&self) -> u32;
+
+    /// scan_calls fills the live across calls set, using the last seen map.
+    /// `live` holds the symbols that are defined and not yet dead at the current statement.
+    /// Like scan_ast, this must iterate through the ast in the same way that build_stmt does.
+    fn scan_calls(&mut self, stmt: &Stmt<'a>, live: &mut MutSet<Symbol>) {
+        let stmt_ptr = stmt as *const Stmt<'a>;
+        let makes_call = match stmt {
+            Stmt::Let(_, Expr::Call(call), _, _) => {
+                !matches!(call.call_type, CallType::LowLevel { .. })
+            }
+            Stmt::Refcounting(..) => true,
+            _ => false,
+        };
+
+        // Drop the symbols this statement uses for the last time.
+        // If it makes a call, everything else that is live has to survive it.
+        let mut still_live = MutSet::default();
+        for sym in live.iter() {
+            if self.last_seen_map().get(sym) != Some(&stmt_ptr) {
+                still_live.insert(*sym);
+            }
+        }
+        if makes_call {
+            self.live_across_calls().extend(still_live.iter().copied());
+        }
+        *live = still_live;
+
+        match stmt {
+            Stmt::Let(sym, _, _, following) => {
+                if self.last_seen_map().get(sym) != Some(&stmt_ptr) {
+                    live.insert(*sym);
+                }
+                self.scan_calls(following, live);
+            }
+            Stmt::Refcounting(_, following) => self.scan_calls(following, live),
+            Stmt::Switch {
+                branches,
+                default_branch,
+                ..
+            } => {
+                for (_, _, branch) in *branches {
+                    self.scan_calls(branch, &mut live.clone());
+                }
+                self.scan_calls(default_branch.1, live);
+            }
+            Stmt::Join {
+                parameters,
+                body: continuation,
+                remainder,
+                ..
+            } => {
+                let mut continuation_live = live.clone();
+                continuation_live.extend(parameters.iter().map(|param| param.symbol));
+                self.scan_calls(remainder, live);
+                self.scan_calls(continuation, &mut continuation_live);
+            }
+            Stmt::Ret(_) | Stmt::Jump(..) => {}
+            Stmt::Dbg { .. } | Stmt::Expect { .. } | Stmt::ExpectFx { .. } | Stmt::Crash(..) => {}
+        }
+    }
+
     /// scan_ast runs through the ast and fill the last seen map.
     /// This must iterate through the ast in the same way that build_stmt does. i.e. then before else.
     fn scan_ast(&mut self, stmt: &Stmt<'a>) {
@@ -1763,11 +2328,13 @@ trait Backend<'a> {
                 }
             }
 
-            Stmt::Dbg { .. } => todo!("dbg not implemented in the dev backend"),
-            Stmt::Expect { .. } => todo!("expect is not implemented in the dev backend"),
-            Stmt::ExpectFx { .. } => todo!("expect-fx is not implemented in the dev backend"),
+            Stmt::Dbg { .. } | Stmt::Expect { .. } | Stmt::ExpectFx { .. } => {
+                internal_error!("{:?} should have been found unsupported", stmt)
+            }
 
-            Stmt::Crash(..) => todo!("crash is not implemented in the dev backend"),
+            Stmt::Crash(msg, _) => {
+                self.set_last_seen(*msg, stmt);
+            }
         }
     }
 
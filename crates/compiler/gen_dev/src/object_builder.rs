@@ -1,19 +1,27 @@
+use crate::assembly;
 use crate::generic64::{aarch64, new_backend_64bit, x86_64};
-use crate::{Backend, Env, Relocation};
+use crate::inline::inline_small_procs;
+use crate::patchable::add_patchable_entries_section;
+use crate::unwind::EhFrame;
+use crate::{Backend, DataAccess, Env, ProcStats, Relocation, ROC_LOG_REFCOUNT};
 use bumpalo::collections::Vec;
 use object::write::{self, SectionId, SymbolId};
 use object::write::{Object, StandardSection, StandardSegment, Symbol, SymbolSection};
+use object::{elf, macho};
 use object::{
     Architecture, BinaryFormat, Endianness, RelocationEncoding, RelocationKind, SectionKind,
     SymbolFlags, SymbolKind, SymbolScope,
 };
-use roc_collections::all::MutMap;
+use roc_builtins::bitcode;
+use roc_collections::all::{MutMap, MutSet};
 use roc_error_macros::internal_error;
 use roc_module::symbol;
 use roc_module::symbol::Interns;
 use roc_mono::ir::{Proc, ProcLayout};
 use roc_mono::layout::{LayoutIds, STLayoutInterner};
+use roc_mono::simplify::{simplify_proc, SimplifyStats};
 use roc_target::TargetInfo;
+use std::time::Instant;
 use target_lexicon::{Architecture as TargetArch, BinaryFormat as TargetBF, Triple};
 
 // This is used by some code below which is currently commented out.
@@ -27,8 +35,17 @@ pub fn build_module<'a, 'r>(
     interns: &'r mut Interns,
     layout_interner: &'r mut STLayoutInterner<'a>,
     target: &Triple,
-    procedures: MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>>,
+    mut procedures: MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>>,
 ) -> Object<'a> {
+    // Inline before anything is built, so that every backend sees the same procs
+    let ident_ids = interns.all_ident_ids.get_mut(&env.module_id).unwrap();
+    #[allow(unused_variables)] // only printed in debug builds
+    let inline_stats = inline_small_procs(env.arena, env.module_id, ident_ids, &mut procedures);
+
+    roc_debug_flags::dbg_do!(roc_debug_flags::ROC_PRINT_DEV_INLINE_STATS, {
+        eprintln!("{:?}: {}", env.module_id, inline_stats);
+    });
+
     match target {
         Triple {
             architecture: TargetArch::X86_64,
@@ -68,6 +85,25 @@ pub fn build_module<'a, 'r>(
                 ),
             )
         }
+        Triple {
+            architecture: TargetArch::X86_64,
+            binary_format: TargetBF::Coff,
+            ..
+        } if cfg!(feature = "target-x86_64") => {
+            let backend = new_backend_64bit::<
+                x86_64::X86_64GeneralReg,
+                x86_64::X86_64FloatReg,
+                x86_64::X86_64Assembler,
+                x86_64::X86_64WindowsFastcall,
+            >(env, TargetInfo::default_x86_64(), interns, layout_interner);
+            // Symbols are not decorated on x86_64 Windows (unlike 32-bit x86, which prepends `_`),
+            // and that's what the object crate's default COFF mangling does for us.
+            build_object(
+                procedures,
+                backend,
+                Object::new(BinaryFormat::Coff, Architecture::X86_64, Endianness::Little),
+            )
+        }
         Triple {
             architecture: TargetArch::Aarch64(_),
             binary_format: TargetBF::Elf,
@@ -112,6 +148,174 @@ pub fn build_module<'a, 'r>(
     }
 }
 
+/// Each procedure gets its own section, so the linker can drop the ones that are never called.
+/// ELF linkers merge `.text.foo` into `.text`, and COFF linkers do the same with `.text$foo`.
+fn add_proc_section(output: &mut Object, sym: symbol::Symbol) -> SectionId {
+    let name = match output.format() {
+        BinaryFormat::Coff => format!(".text${:x}", sym.as_u64()),
+        _ => format!(".text.{:x}", sym.as_u64()),
+    };
+
+    output.add_section(
+        output.segment_name(StandardSegment::Text).to_vec(),
+        name.into_bytes(),
+        SectionKind::Text,
+    )
+}
+
+/// The relocation for a call to a function that may be defined in another object file.
+/// On x86_64 `offset` is the call's 32 bit displacement, and on aarch64 it's the BL instruction.
+fn call_relocation(output: &Object, offset: u64, symbol: SymbolId) -> write::Relocation {
+    let (size, kind, encoding, addend) = match (output.architecture(), output.format()) {
+        (Architecture::Aarch64, _) => (
+            26,
+            RelocationKind::Relative,
+            RelocationEncoding::AArch64Call,
+            0,
+        ),
+        // COFF has no PLT: calls are plain rel32, and the linker adds thunks for DLL imports.
+        (_, BinaryFormat::Coff) => (
+            32,
+            RelocationKind::Relative,
+            RelocationEncoding::Generic,
+            -4,
+        ),
+        _ => (
+            32,
+            RelocationKind::PltRelative,
+            RelocationEncoding::X86Branch,
+            -4,
+        ),
+    };
+
+    write::Relocation {
+        offset,
+        size,
+        kind,
+        encoding,
+        symbol,
+        addend,
+    }
+}
+
+/// The relocations for a reference to data in this object file.
+/// x86_64 reaches it with a single RIP-relative field. On aarch64, an ADRP gets the data's 4KiB
+/// page and the instruction after it adds the offset in the page, so each needs a relocation.
+fn local_data_relocations(
+    output: &Object,
+    offset: u64,
+    symbol: SymbolId,
+    access: DataAccess,
+) -> std::vec::Vec<write::Relocation> {
+    match (output.architecture(), access) {
+        (Architecture::Aarch64, DataAccess::RipRelative) => {
+            internal_error!("RIP-relative data access on aarch64")
+        }
+        (Architecture::Aarch64, _) => {
+            let (page, page_offset) = match output.format() {
+                BinaryFormat::MachO => (
+                    RelocationKind::MachO {
+                        value: macho::ARM64_RELOC_PAGE21,
+                        relative: true,
+                    },
+                    // ld64 looks at the instruction to see whether it's an ADD or a load
+                    RelocationKind::MachO {
+                        value: macho::ARM64_RELOC_PAGEOFF12,
+                        relative: false,
+                    },
+                ),
+                _ => (
+                    RelocationKind::Elf(elf::R_AARCH64_ADR_PREL_PG_HI21),
+                    RelocationKind::Elf(match access {
+                        DataAccess::PageLoad32 => elf::R_AARCH64_LDST32_ABS_LO12_NC,
+                        DataAccess::PageLoad64 => elf::R_AARCH64_LDST64_ABS_LO12_NC,
+                        _ => elf::R_AARCH64_ADD_ABS_LO12_NC,
+                    }),
+                ),
+            };
+
+            page_relocations(offset, symbol, page, page_offset)
+        }
+        (_, DataAccess::RipRelative) => vec![write::Relocation {
+            offset,
+            size: 32,
+            kind: RelocationKind::Relative,
+            encoding: RelocationEncoding::Generic,
+            symbol,
+            addend: -4,
+        }],
+        (_, _) => internal_error!("{:?} data access on x86_64", access),
+    }
+}
+
+/// The relocations for loading the address of data that may be defined in another object file
+fn linked_data_relocations(
+    output: &Object,
+    offset: u64,
+    symbol: SymbolId,
+) -> std::vec::Vec<write::Relocation> {
+    match (output.architecture(), output.format()) {
+        (Architecture::Aarch64, BinaryFormat::MachO) => page_relocations(
+            offset,
+            symbol,
+            RelocationKind::MachO {
+                value: macho::ARM64_RELOC_GOT_LOAD_PAGE21,
+                relative: true,
+            },
+            RelocationKind::MachO {
+                value: macho::ARM64_RELOC_GOT_LOAD_PAGEOFF12,
+                relative: false,
+            },
+        ),
+        (Architecture::Aarch64, _) => page_relocations(
+            offset,
+            symbol,
+            RelocationKind::Elf(elf::R_AARCH64_ADR_GOT_PAGE),
+            RelocationKind::Elf(elf::R_AARCH64_LD64_GOT_LO12_NC),
+        ),
+        (_, format) => vec![write::Relocation {
+            offset,
+            size: 32,
+            // COFF has no GOT, so we refer to the data directly.
+            kind: if format == BinaryFormat::Coff {
+                RelocationKind::Relative
+            } else {
+                RelocationKind::GotRelative
+            },
+            encoding: RelocationEncoding::Generic,
+            symbol,
+            addend: -4,
+        }],
+    }
+}
+
+/// The pair of relocations for an aarch64 ADRP at `offset`, and the instruction after it
+fn page_relocations(
+    offset: u64,
+    symbol: SymbolId,
+    page: RelocationKind,
+    page_offset: RelocationKind,
+) -> std::vec::Vec<write::Relocation> {
+    vec![
+        write::Relocation {
+            offset,
+            size: 32,
+            kind: page,
+            encoding: RelocationEncoding::Generic,
+            symbol,
+            addend: 0,
+        },
+        write::Relocation {
+            offset: offset + 4,
+            size: 32,
+            kind: page_offset,
+            encoding: RelocationEncoding::Generic,
+            symbol,
+            addend: 0,
+        },
+    ]
+}
+
 fn generate_wrapper<'a, B: Backend<'a>>(
     backend: &mut B,
     output: &mut Object,
@@ -141,20 +345,14 @@ fn generate_wrapper<'a, B: Backend<'a>>(
         size: 0,
         kind: SymbolKind::Text,
         scope: SymbolScope::Dynamic,
-        weak: true,
+        // COFF weak externals need a default definition to fall back on, which we don't have.
+        weak: output.format() != BinaryFormat::Coff,
         section: SymbolSection::Undefined,
         flags: SymbolFlags::None,
     };
     output.add_symbol(symbol);
     if let Some(sym_id) = output.symbol_id(name) {
-        let reloc = write::Relocation {
-            offset: offset + proc_offset,
-            size: 32,
-            kind: RelocationKind::PltRelative,
-            encoding: RelocationEncoding::X86Branch,
-            symbol: sym_id,
-            addend: -4,
-        };
+        let reloc = call_relocation(output, offset + proc_offset, sym_id);
 
         match output.add_relocation(text_section, reloc) {
             Ok(obj) => obj,
@@ -170,7 +368,7 @@ fn build_object<'a, B: Backend<'a>>(
     mut backend: B,
     mut output: Object<'a>,
 ) -> Object<'a> {
-    let data_section = output.section_id(StandardSection::Data);
+    let mut constants = ConstantPool::new(output.section_id(StandardSection::Data));
 
     let arena = backend.env().arena;
 
@@ -185,24 +383,42 @@ fn build_object<'a, B: Backend<'a>>(
     */
 
     if backend.env().generate_allocators {
-        generate_wrapper(
-            &mut backend,
-            &mut output,
-            "roc_alloc".into(),
-            "malloc".into(),
-        );
+        // When logging refcounts, the allocations are logged too, so that tests can check that
+        // everything gets freed exactly once.
+        let log_refcounts = backend.env().log_refcounts;
+        let (alloc, realloc, dealloc) = if log_refcounts {
+            (
+                bitcode::UTILS_TEST_ALLOC,
+                bitcode::UTILS_TEST_REALLOC,
+                bitcode::UTILS_TEST_DEALLOC,
+            )
+        } else {
+            ("malloc", "realloc", "free")
+        };
+
+        generate_wrapper(&mut backend, &mut output, "roc_alloc".into(), alloc.into());
         generate_wrapper(
             &mut backend,
             &mut output,
             "roc_realloc".into(),
-            "realloc".into(),
+            realloc.into(),
         );
         generate_wrapper(
             &mut backend,
             &mut output,
             "roc_dealloc".into(),
-            "free".into(),
+            dealloc.into(),
         );
+
+        if log_refcounts {
+            generate_wrapper(
+                &mut backend,
+                &mut output,
+                ROC_LOG_REFCOUNT.into(),
+                bitcode::UTILS_TEST_LOG_REFCOUNT.into(),
+            );
+        }
+
         generate_wrapper(
             &mut backend,
             &mut output,
@@ -228,22 +444,52 @@ fn build_object<'a, B: Backend<'a>>(
         )
     }
 
+    // Unwind tables, so that debuggers, profilers, and panics can walk through Roc frames.
+    // Only ELF for now: Mach-O wants compact unwind info or subtractor relocations in __eh_frame,
+    // and COFF has its own .pdata/.xdata format.
+    let mut eh_frame = match output.format() {
+        BinaryFormat::Elf => Some(EhFrame::new(output.architecture())),
+        _ => None,
+    };
+
+    // The procs whose calls can be redirected, see Env::patchable_prologues
+    let mut patchable_procs = std::vec::Vec::new();
+
     // Build procedures from user code
     let mut relocations = bumpalo::vec![in arena];
-    for (fn_name, section_id, proc_id, proc) in procs {
+    let mut simplify_stats = SimplifyStats::default();
+    for (_, section_id, proc_id, proc) in procs {
+        if backend.env().patchable_prologues {
+            patchable_procs.push(proc_id);
+        }
+
+        let proc = simplify_proc(arena, backend.interner(), proc, &mut simplify_stats);
+
         build_proc(
             &mut output,
             &mut backend,
             &mut relocations,
+            &mut eh_frame,
             &mut layout_ids,
-            data_section,
-            fn_name,
+            &mut constants,
             section_id,
             proc_id,
             proc,
         )
     }
 
+    roc_debug_flags::dbg_do!(roc_debug_flags::ROC_PRINT_SIMPLIFY_STATS, {
+        eprintln!("{:?}: {}", backend.env().module_id, simplify_stats);
+    });
+
+    roc_debug_flags::dbg_do!(roc_debug_flags::ROC_PRINT_DEV_CODE_LAYOUT, {
+        eprintln!(
+            "{:?}: {}",
+            backend.env().module_id,
+            backend.code_layout_stats()
+        );
+    });
+
     // Generate IR for specialized helper procs (refcounting & equality)
     let helper_procs = {
         let (module_id, _interner, interns, helper_proc_gen, caller_procs) =
@@ -263,10 +509,23 @@ fn build_object<'a, B: Backend<'a>>(
 
     let empty = bumpalo::collections::Vec::new_in(arena);
     let helper_symbols_and_layouts = std::mem::replace(backend.helper_proc_symbols_mut(), empty);
+    let helper_layouts: MutMap<_, _> = helper_symbols_and_layouts.into_iter().collect();
     let mut helper_names_symbols_procs = Vec::with_capacity_in(helper_procs.len(), arena);
 
-    // Names and linker data for helpers
-    for ((sym, layout), proc) in helper_symbols_and_layouts.into_iter().zip(helper_procs) {
+    // Names and linker data for helpers. Every user proc that needs a helper for the same
+    // (layout, op) refers to the same symbol, so each helper is emitted once per module.
+    let mut emitted_helpers = MutSet::default();
+    for proc in helper_procs {
+        let sym = proc.name.name();
+        if !emitted_helpers.insert(sym) {
+            continue;
+        }
+
+        let layout = match helper_layouts.get(&sym) {
+            Some(layout) => *layout,
+            None => internal_error!("no linker data for helper proc {:?}", sym),
+        };
+
         let fn_name = backend.function_symbol_to_string(
             sym,
             layout.arguments.iter().copied(),
@@ -281,11 +540,7 @@ fn build_object<'a, B: Backend<'a>>(
             }
         } else {
             // The symbol isn't defined yet and will just be used by other rc procs.
-            let section_id = output.add_section(
-                output.segment_name(StandardSegment::Text).to_vec(),
-                format!(".text.{:x}", sym.as_u64()).as_bytes().to_vec(),
-                SectionKind::Text,
-            );
+            let section_id = add_proc_section(&mut output, sym);
 
             let rc_symbol = Symbol {
                 name: fn_name.as_bytes().to_vec(),
@@ -305,14 +560,18 @@ fn build_object<'a, B: Backend<'a>>(
     }
 
     // Build helpers
-    for (fn_name, section_id, proc_id, proc) in helper_names_symbols_procs {
+    for (_, section_id, proc_id, proc) in helper_names_symbols_procs {
+        if backend.env().patchable_prologues {
+            patchable_procs.push(proc_id);
+        }
+
         build_proc(
             &mut output,
             &mut backend,
             &mut relocations,
+            &mut eh_frame,
             &mut layout_ids,
-            data_section,
-            fn_name,
+            &mut constants,
             section_id,
             proc_id,
             proc,
@@ -326,9 +585,81 @@ fn build_object<'a, B: Backend<'a>>(
             Err(e) => internal_error!("{:?}", e),
         }
     }
+
+    if let Some(eh_frame) = eh_frame {
+        add_eh_frame_section(&mut output, eh_frame);
+    }
+
+    if !patchable_procs.is_empty() {
+        add_patchable_entries_section(&mut output, &patchable_procs);
+    }
+
     output
 }
 
+fn add_eh_frame_section(output: &mut Object, eh_frame: EhFrame) {
+    let (bytes, function_addresses) = eh_frame.finish();
+    let section_id = output.add_section(vec![], b".eh_frame".to_vec(), SectionKind::ReadOnlyData);
+    output.append_section_data(section_id, &bytes, 8);
+
+    for (offset, symbol) in function_addresses {
+        let reloc = write::Relocation {
+            offset,
+            size: 32,
+            kind: RelocationKind::Relative,
+            encoding: RelocationEncoding::Generic,
+            symbol,
+            addend: 0,
+        };
+        if let Err(e) = output.add_relocation(section_id, reloc) {
+            internal_error!("{:?}", e);
+        }
+    }
+}
+
+/// The data that procs load through [Relocation::LocalData], in the module's data section.
+/// Procs keep loading the same float constants, so each distinct constant is only written once,
+/// and every relocation to it uses the same symbol.
+struct ConstantPool {
+    data_section: SectionId,
+    symbols: MutMap<std::vec::Vec<u8>, SymbolId>,
+}
+
+impl ConstantPool {
+    fn new(data_section: SectionId) -> Self {
+        ConstantPool {
+            data_section,
+            symbols: MutMap::default(),
+        }
+    }
+
+    /// The symbol of the constant with these bytes, adding it to the data section if it's new
+    fn symbol(&mut self, output: &mut Object, data: &[u8]) -> SymbolId {
+        if let Some(data_id) = self.symbols.get(data) {
+            return *data_id;
+        }
+
+        let data_symbol = write::Symbol {
+            name: format!("roc_const.{}", self.symbols.len())
+                .as_bytes()
+                .to_vec(),
+            value: 0,
+            size: 0,
+            kind: SymbolKind::Data,
+            scope: SymbolScope::Compilation,
+            weak: false,
+            section: SymbolSection::Section(self.data_section),
+            flags: SymbolFlags::None,
+        };
+        let data_id = output.add_symbol(data_symbol);
+        // 8 byte alignment, so that aarch64 can load 8 bytes with a scaled page offset
+        output.add_symbol_data(data_id, self.data_section, data, 8);
+
+        self.symbols.insert(data.to_vec(), data_id);
+        data_id
+    }
+}
+
 fn build_proc_symbol<'a, B: Backend<'a>>(
     output: &mut Object<'a>,
     layout_ids: &mut LayoutIds<'a>,
@@ -353,11 +684,7 @@ fn build_proc_symbol<'a, B: Backend<'a>>(
         base_name
     };
 
-    let section_id = output.add_section(
-        output.segment_name(StandardSegment::Text).to_vec(),
-        format!(".text.{:x}", sym.as_u64()).as_bytes().to_vec(),
-        SectionKind::Text,
-    );
+    let section_id = add_proc_section(output, sym);
 
     let proc_symbol = Symbol {
         name: fn_name.as_bytes().to_vec(),
@@ -384,53 +711,48 @@ fn build_proc<'a, B: Backend<'a>>(
     output: &mut Object,
     backend: &mut B,
     relocations: &mut Vec<'a, (SectionId, object::write::Relocation)>,
+    eh_frame: &mut Option<EhFrame>,
     layout_ids: &mut LayoutIds<'a>,
-    data_section: SectionId,
-    fn_name: String,
+    constants: &mut ConstantPool,
     section_id: SectionId,
     proc_id: SymbolId,
     proc: Proc<'a>,
 ) {
-    let mut local_data_index = 0;
-    let (proc_data, relocs, rc_proc_names) = backend.build_proc(proc, layout_ids);
+    let roc_symbol = proc.name.name();
+    let helpers_before = backend.helper_proc_symbols().len();
+    let build_start = Instant::now();
+    let (proc_data, relocs, unwind, rc_proc_names) = backend.build_proc(proc, layout_ids);
+    if backend.env().collect_proc_stats {
+        backend.env().proc_stats.borrow_mut().push(ProcStats {
+            name: roc_name(backend, roc_symbol),
+            symbol: String::from_utf8_lossy(&output.symbol(proc_id).name).into_owned(),
+            bytes: proc_data.len(),
+            spills: backend.spill_count(),
+            relocations: relocs.len(),
+            helpers: backend.helper_proc_symbols().len() - helpers_before,
+            build_time: build_start.elapsed(),
+        });
+    }
+    if backend.env().emit_assembly {
+        write_assembly(output, backend, roc_symbol, proc_id, &proc_data, &relocs);
+    }
     let proc_offset = output.add_symbol_data(proc_id, section_id, &proc_data, 16);
+    if let Some(eh_frame) = eh_frame {
+        eh_frame.add_function(proc_id, proc_data.len() as u32, &unwind);
+    }
     for reloc in relocs.iter() {
-        let elfreloc = match reloc {
-            Relocation::LocalData { offset, data } => {
-                let data_symbol = write::Symbol {
-                    name: format!("{}.data{}", fn_name, local_data_index)
-                        .as_bytes()
-                        .to_vec(),
-                    value: 0,
-                    size: 0,
-                    kind: SymbolKind::Data,
-                    scope: SymbolScope::Compilation,
-                    weak: false,
-                    section: SymbolSection::Section(data_section),
-                    flags: SymbolFlags::None,
-                };
-                local_data_index += 1;
-                let data_id = output.add_symbol(data_symbol);
-                output.add_symbol_data(data_id, data_section, data, 4);
-                write::Relocation {
-                    offset: offset + proc_offset,
-                    size: 32,
-                    kind: RelocationKind::Relative,
-                    encoding: RelocationEncoding::Generic,
-                    symbol: data_id,
-                    addend: -4,
-                }
+        let elfrelocs = match reloc {
+            Relocation::LocalData {
+                offset,
+                data,
+                access,
+            } => {
+                let data_id = constants.symbol(output, data);
+                local_data_relocations(output, offset + proc_offset, data_id, *access)
             }
             Relocation::LinkedData { offset, name } => {
                 if let Some(sym_id) = output.symbol_id(name.as_bytes()) {
-                    write::Relocation {
-                        offset: offset + proc_offset,
-                        size: 32,
-                        kind: RelocationKind::GotRelative,
-                        encoding: RelocationEncoding::Generic,
-                        symbol: sym_id,
-                        addend: -4,
-                    }
+                    linked_data_relocations(output, offset + proc_offset, sym_id)
                 } else {
                     internal_error!("failed to find data symbol for {:?}", name);
                 }
@@ -454,11 +776,7 @@ fn build_proc<'a, B: Backend<'a>>(
                 if output.symbol_id(name.as_bytes()).is_none() {
                     for (sym, rc_name) in rc_proc_names.iter() {
                         if name == rc_name {
-                            let section_id = output.add_section(
-                                output.segment_name(StandardSegment::Text).to_vec(),
-                                format!(".text.{:x}", sym.as_u64()).as_bytes().to_vec(),
-                                SectionKind::Text,
-                            );
+                            let section_id = add_proc_section(output, *sym);
 
                             let rc_symbol = Symbol {
                                 name: name.as_bytes().to_vec(),
@@ -476,20 +794,144 @@ fn build_proc<'a, B: Backend<'a>>(
                 }
 
                 if let Some(sym_id) = output.symbol_id(name.as_bytes()) {
-                    write::Relocation {
-                        offset: offset + proc_offset,
-                        size: 32,
-                        kind: RelocationKind::PltRelative,
-                        encoding: RelocationEncoding::X86Branch,
-                        symbol: sym_id,
-                        addend: -4,
-                    }
+                    vec![call_relocation(output, offset + proc_offset, sym_id)]
                 } else {
                     internal_error!("failed to find fn symbol for {:?}", name);
                 }
             }
             Relocation::JmpToReturn { .. } => unreachable!(),
         };
-        relocations.push((section_id, elfreloc));
+        for elfreloc in elfrelocs {
+            relocations.push((section_id, elfreloc));
+        }
+    }
+}
+
+/// Add a proc's listing to [Env::assembly]
+fn write_assembly<'a, B: Backend<'a>>(
+    output: &Object,
+    backend: &B,
+    roc_symbol: symbol::Symbol,
+    proc_id: SymbolId,
+    code: &[u8],
+    relocs: &[Relocation],
+) {
+    let architecture = match output.architecture() {
+        Architecture::X86_64 => roc_target::Architecture::X86_64,
+        Architecture::Aarch64 => roc_target::Architecture::Aarch64,
+        other => internal_error!("the dev backend doesn't build for {:?}", other),
+    };
+    let roc_name = roc_name(backend, roc_symbol);
+    let symbol_name = String::from_utf8_lossy(&output.symbol(proc_id).name);
+
+    assembly::write_proc_listing(
+        &mut backend.env().assembly.borrow_mut(),
+        architecture,
+        &roc_name,
+        &symbol_name,
+        code,
+        relocs,
+    );
+}
+
+/// The name of the proc in Roc, like `UserApp.main`
+fn roc_name<'a, B: Backend<'a>>(backend: &B, roc_symbol: symbol::Symbol) -> String {
+    let interns = backend.interns();
+
+    format!(
+        "{}.{}",
+        roc_symbol.module_string(interns),
+        roc_symbol.as_str(interns)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{add_proc_section, build_module};
+    use crate::Env;
+    use bumpalo::Bump;
+    use object::read::{Object as _, ObjectSection as _, ObjectSymbol as _, RelocationTarget};
+    use object::write::Object;
+    use object::{Architecture, BinaryFormat, Endianness, RelocationKind};
+    use roc_collections::all::{MutMap, MutSet};
+    use roc_module::symbol::{Interns, Symbol};
+    use roc_mono::layout::STLayoutInterner;
+    use roc_target::TargetInfo;
+    use target_lexicon::Triple;
+
+    #[test]
+    fn coff_proc_sections() {
+        let mut output = Object::new(BinaryFormat::Coff, Architecture::X86_64, Endianness::Little);
+        let section_id = add_proc_section(&mut output, Symbol::LIST_MAP);
+
+        assert_eq!(
+            output.section(section_id).name(),
+            Some(format!(".text${:x}", Symbol::LIST_MAP.as_u64()).as_str())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "target-x86_64")]
+    fn coff_object_for_x86_64_windows() {
+        let arena = Bump::new();
+        let mut interns = Interns::default();
+        let module_id = interns.module_id(&"Test".into());
+        interns.all_ident_ids.get_or_insert(module_id);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let env = Env {
+            arena: &arena,
+            module_id,
+            exposed_to_host: MutSet::default(),
+            lazy_literals: false,
+            generate_allocators: true,
+            log_refcounts: false,
+            unsupported: Default::default(),
+            emit_assembly: false,
+            assembly: Default::default(),
+            collect_proc_stats: false,
+            proc_stats: Default::default(),
+            patchable_prologues: false,
+        };
+        let target: Triple = "x86_64-pc-windows-msvc".parse().unwrap();
+
+        let bytes = build_module(
+            &env,
+            &mut interns,
+            &mut layout_interner,
+            &target,
+            MutMap::default(),
+        )
+        .write()
+        .unwrap();
+        let file = object::File::parse(&*bytes).unwrap();
+
+        assert_eq!(file.format(), BinaryFormat::Coff);
+        assert_eq!(file.architecture(), Architecture::X86_64);
+        assert!(file.section_by_name(".eh_frame").is_none());
+
+        // The allocator wrappers are undecorated, and so are the functions they call
+        let wrapper = file
+            .symbols()
+            .find(|symbol| symbol.name() == Ok("roc_alloc"))
+            .unwrap();
+        assert!(wrapper.is_definition());
+
+        let malloc = file
+            .symbols()
+            .find(|symbol| symbol.name() == Ok("malloc"))
+            .unwrap();
+        assert!(malloc.is_undefined());
+        assert!(!malloc.is_weak());
+
+        // Calls are plain rel32, without a PLT
+        let text = file.section_by_name(".text").unwrap();
+        let (_, call) = text
+            .relocations()
+            .find(|(_, reloc)| reloc.target() == RelocationTarget::Symbol(malloc.index()))
+            .unwrap();
+        assert_eq!(call.kind(), RelocationKind::Relative);
+        assert_eq!(call.size(), 32);
+        assert_eq!(call.addend(), -4);
     }
 }
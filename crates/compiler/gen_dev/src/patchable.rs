@@ -0,0 +1,58 @@
+//! Patchable entries, for redirecting calls to a proc to a recompiled version of it without
+//! relinking, like `-fpatchable-function-entry` in C compilers. With
+//! [crate::Env::patchable_prologues], every proc starts with a jump over an indirect jump, before
+//! its prologue. Redirecting the proc fills in the indirect jump's target and removes the jump
+//! over it.
+//!
+//! The object file lists the address of every patchable entry in a section of its own, so that
+//! whatever does the redirecting can find them after linking.
+use crate::generic64::{aarch64, x86_64, Assembler};
+use object::write::{self, Object, StandardSegment, SymbolId};
+use object::{BinaryFormat, RelocationEncoding, RelocationKind, SectionKind};
+use roc_error_macros::internal_error;
+use roc_target::Architecture;
+
+/// The section with the addresses of the patchable entries, 8 bytes each. It's a valid C
+/// identifier, so ELF linkers define `__start_roc_patchable_entries` and
+/// `__stop_roc_patchable_entries` around it.
+pub const PATCHABLE_ENTRIES_SECTION: &str = "roc_patchable_entries";
+
+/// Redirect calls to a proc built with [crate::Env::patchable_prologues] to `target`. `code` is
+/// the proc from its start, and it has to be writable.
+pub fn redirect_patchable_entry(architecture: Architecture, code: &mut [u8], target: u64) {
+    match architecture {
+        Architecture::X86_64 => x86_64::X86_64Assembler::redirect_patchable_entry(code, target),
+        Architecture::Aarch64 => aarch64::AArch64Assembler::redirect_patchable_entry(code, target),
+        other => internal_error!("the dev backend doesn't build for {:?}", other),
+    }
+}
+
+/// Add the section listing the procs with patchable entries
+pub fn add_patchable_entries_section(output: &mut Object, procs: &[SymbolId]) {
+    // Mach-O section names are limited to 16 bytes
+    let name = match output.format() {
+        BinaryFormat::MachO => "__roc_patchable",
+        _ => PATCHABLE_ENTRIES_SECTION,
+    };
+    let section_id = output.add_section(
+        output.segment_name(StandardSegment::Data).to_vec(),
+        name.as_bytes().to_vec(),
+        SectionKind::Data,
+    );
+
+    output.append_section_data(section_id, &vec![0; procs.len() * 8], 8);
+
+    for (index, proc_id) in procs.iter().enumerate() {
+        let reloc = write::Relocation {
+            offset: index as u64 * 8,
+            size: 64,
+            kind: RelocationKind::Absolute,
+            encoding: RelocationEncoding::Generic,
+            symbol: *proc_id,
+            addend: 0,
+        };
+        if let Err(e) = output.add_relocation(section_id, reloc) {
+            internal_error!("{:?}", e);
+        }
+    }
+}
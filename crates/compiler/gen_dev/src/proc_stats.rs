@@ -0,0 +1,267 @@
+//! Statistics about the code generated for each proc, for keeping track of the dev backend's
+//! code quality over time. They're collected when [Env::collect_proc_stats](crate::Env) is set,
+//! and [report_proc_stats] summarizes them as a table, or as JSON for other tools to compare.
+use std::fmt::Write;
+use std::time::Duration;
+
+/// What it took to build one proc
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcStats {
+    /// The proc's Roc name, like `UserApp.main`
+    pub name: String,
+    /// The proc's symbol in the object file
+    pub symbol: String,
+    /// Size of the machine code
+    pub bytes: usize,
+    /// How many times a value was moved from a register to the stack, to free the register
+    pub spills: u32,
+    /// Relocations the linker has to apply to the proc
+    pub relocations: usize,
+    /// Helper procs (like refcounting and equality) that the proc asked for.
+    /// A helper that several procs ask for is only generated once.
+    pub helpers: usize,
+    /// Time spent generating the machine code
+    pub build_time: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcStatsFormat {
+    Text,
+    Json,
+}
+
+impl ProcStatsFormat {
+    /// JSON for paths ending in `.json`, text for anything else
+    pub fn for_path(path: &std::path::Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension == "json" => ProcStatsFormat::Json,
+            _ => ProcStatsFormat::Text,
+        }
+    }
+}
+
+/// A report of the stats of every proc, and their totals. The text report lists the biggest
+/// procs first. The JSON report keeps the procs in the order they were built.
+pub fn report_proc_stats(stats: &[ProcStats], format: ProcStatsFormat) -> String {
+    match format {
+        ProcStatsFormat::Text => text_report(stats),
+        ProcStatsFormat::Json => json_report(stats),
+    }
+}
+
+fn text_report(stats: &[ProcStats]) -> String {
+    let mut sorted: std::vec::Vec<&ProcStats> = stats.iter().collect();
+    sorted.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{:>8} {:>6} {:>6} {:>7} {:>10}  proc",
+        "bytes", "spills", "relocs", "helpers", "build µs"
+    );
+
+    for proc in sorted {
+        let _ = writeln!(
+            report,
+            "{:>8} {:>6} {:>6} {:>7} {:>10}  {} ({})",
+            proc.bytes,
+            proc.spills,
+            proc.relocations,
+            proc.helpers,
+            proc.build_time.as_micros(),
+            proc.name,
+            proc.symbol
+        );
+    }
+
+    let totals = Totals::of(stats);
+    let _ = writeln!(
+        report,
+        "{:>8} {:>6} {:>6} {:>7} {:>10}  total of {} procs",
+        totals.bytes,
+        totals.spills,
+        totals.relocations,
+        totals.helpers,
+        totals.build_time.as_micros(),
+        stats.len()
+    );
+
+    report
+}
+
+fn json_report(stats: &[ProcStats]) -> String {
+    let mut report = String::from("{\n  \"procs\": [");
+
+    for (index, proc) in stats.iter().enumerate() {
+        if index > 0 {
+            report.push(',');
+        }
+        let _ = write!(
+            report,
+            "\n    {{\"name\": {}, \"symbol\": {}, \"bytes\": {}, \"spills\": {}, \"relocations\": {}, \"helpers\": {}, \"build_micros\": {}}}",
+            json_string(&proc.name),
+            json_string(&proc.symbol),
+            proc.bytes,
+            proc.spills,
+            proc.relocations,
+            proc.helpers,
+            proc.build_time.as_micros()
+        );
+    }
+
+    let totals = Totals::of(stats);
+    let _ = write!(
+        report,
+        "\n  ],\n  \"total\": {{\"procs\": {}, \"bytes\": {}, \"spills\": {}, \"relocations\": {}, \"helpers\": {}, \"build_micros\": {}}}\n}}\n",
+        stats.len(),
+        totals.bytes,
+        totals.spills,
+        totals.relocations,
+        totals.helpers,
+        totals.build_time.as_micros()
+    );
+
+    report
+}
+
+#[derive(Default)]
+struct Totals {
+    bytes: usize,
+    spills: u64,
+    relocations: usize,
+    helpers: usize,
+    build_time: Duration,
+}
+
+impl Totals {
+    fn of(stats: &[ProcStats]) -> Self {
+        stats.iter().fold(Totals::default(), |totals, proc| Totals {
+            bytes: totals.bytes + proc.bytes,
+            spills: totals.spills + proc.spills as u64,
+            relocations: totals.relocations + proc.relocations,
+            helpers: totals.helpers + proc.helpers,
+            build_time: totals.build_time + proc.build_time,
+        })
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{json_string, report_proc_stats, ProcStats, ProcStatsFormat};
+    use std::path::Path;
+    use std::time::Duration;
+
+    fn stats() -> std::vec::Vec<ProcStats> {
+        vec![
+            ProcStats {
+                name: "UserApp.main".to_string(),
+                symbol: "roc__main_1_exposed".to_string(),
+                bytes: 40,
+                spills: 1,
+                relocations: 2,
+                helpers: 0,
+                build_time: Duration::from_micros(15),
+            },
+            ProcStats {
+                name: "List.map".to_string(),
+                symbol: "List_map_3".to_string(),
+                bytes: 120,
+                spills: 3,
+                relocations: 4,
+                helpers: 2,
+                build_time: Duration::from_micros(30),
+            },
+        ]
+    }
+
+    #[test]
+    fn text_report_lists_the_biggest_procs_first() {
+        let report = report_proc_stats(&stats(), ProcStatsFormat::Text);
+
+        assert_eq!(
+            report.lines().collect::<std::vec::Vec<_>>(),
+            [
+                "   bytes spills relocs helpers   build µs  proc",
+                "     120      3      4       2         30  List.map (List_map_3)",
+                "      40      1      2       0         15  UserApp.main (roc__main_1_exposed)",
+                "     160      4      6       2         45  total of 2 procs",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_report_keeps_the_build_order() {
+        let report = report_proc_stats(&stats(), ProcStatsFormat::Json);
+
+        assert_eq!(
+            report,
+            concat!(
+                "{\n  \"procs\": [",
+                "\n    {\"name\": \"UserApp.main\", \"symbol\": \"roc__main_1_exposed\", ",
+                "\"bytes\": 40, \"spills\": 1, \"relocations\": 2, \"helpers\": 0, ",
+                "\"build_micros\": 15},",
+                "\n    {\"name\": \"List.map\", \"symbol\": \"List_map_3\", ",
+                "\"bytes\": 120, \"spills\": 3, \"relocations\": 4, \"helpers\": 2, ",
+                "\"build_micros\": 30}",
+                "\n  ],\n  \"total\": {\"procs\": 2, \"bytes\": 160, \"spills\": 4, ",
+                "\"relocations\": 6, \"helpers\": 2, \"build_micros\": 45}\n}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn empty_reports() {
+        assert_eq!(
+            report_proc_stats(&[], ProcStatsFormat::Json),
+            concat!(
+                "{\n  \"procs\": [\n  ],\n  \"total\": {\"procs\": 0, \"bytes\": 0, ",
+                "\"spills\": 0, \"relocations\": 0, \"helpers\": 0, \"build_micros\": 0}\n}\n",
+            )
+        );
+        assert!(report_proc_stats(&[], ProcStatsFormat::Text).ends_with("total of 0 procs\n"));
+    }
+
+    #[test]
+    fn json_string_escaping() {
+        assert_eq!(json_string("List.map"), r#""List.map""#);
+        assert_eq!(json_string(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(json_string(r"a\b"), r#""a\\b""#);
+        assert_eq!(json_string("tab\tnew\nline"), r#""tab\u0009new\u000aline""#);
+        assert_eq!(json_string("café µs"), r#""café µs""#);
+    }
+
+    #[test]
+    fn format_for_path() {
+        assert_eq!(
+            ProcStatsFormat::for_path(Path::new("main.proc-stats.json")),
+            ProcStatsFormat::Json
+        );
+        assert_eq!(
+            ProcStatsFormat::for_path(Path::new("stats.txt")),
+            ProcStatsFormat::Text
+        );
+        assert_eq!(
+            ProcStatsFormat::for_path(Path::new("stats")),
+            ProcStatsFormat::Text
+        );
+    }
+}
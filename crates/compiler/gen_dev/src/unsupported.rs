@@ -0,0 +1,358 @@
+//! Finding what the dev backend can't generate code for yet, before it starts on a proc.
+//!
+//! A proc that uses something unsupported is replaced by one that crashes with a message saying
+//! what it was, and the problem is recorded in [Env::unsupported](crate::Env::unsupported), so
+//! that the rest of the module can still be built and the problems reported all together.
+use roc_collections::all::{MutMap, MutSet};
+use roc_module::low_level::{LowLevel, LowLevelWrapperType};
+use roc_module::symbol::{Interns, Symbol};
+use roc_mono::ir::{CallType, Expr, Proc, Stmt};
+use roc_mono::layout::{Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, UnionLayout};
+use roc_mono::low_level::HigherOrder;
+
+/// Something in a proc that the dev backend doesn't support yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    /// What isn't supported, e.g. "the low level StrGraphemes" or "the low level NumMul on Dec"
+    pub feature: String,
+    /// The symbol defined by the statement that uses it, or the proc's name
+    pub symbol: Symbol,
+}
+
+impl Unsupported {
+    /// The message that the proc crashes with when it's called
+    pub fn message(&self, interns: &Interns) -> String {
+        format!(
+            "unsupported in dev backend: {} at {}",
+            self.feature,
+            symbol_name(self.symbol, interns)
+        )
+    }
+}
+
+/// A report of everything the dev backend couldn't build, for the end of a build
+pub fn report_unsupported(unsupported: &[Unsupported], interns: &Interns) -> String {
+    let mut report = format!(
+        "The dev backend doesn't support everything this program uses yet, so {} will crash if they're called:\n\n",
+        if unsupported.len() == 1 {
+            "1 function".to_string()
+        } else {
+            format!("{} functions", unsupported.len())
+        }
+    );
+
+    for problem in unsupported {
+        report.push_str("    ");
+        report.push_str(&problem.message(interns));
+        report.push('\n');
+    }
+
+    report.push_str(
+        "\nTo build it with the LLVM backend instead, leave out the `--dev` flag, e.g. use `--optimize`.\n",
+    );
+
+    report
+}
+
+fn symbol_name(symbol: Symbol, interns: &Interns) -> String {
+    format!(
+        "{}.{}",
+        symbol.module_string(interns),
+        symbol.as_str(interns)
+    )
+}
+
+/// The first thing in the proc that the dev backend can't generate code for, if there is one
+pub(crate) fn find_unsupported<'a>(
+    interner: &STLayoutInterner<'a>,
+    proc: &Proc<'a>,
+) -> Option<Unsupported> {
+    let mut finder = Finder {
+        interner,
+        checked_layouts: MutSet::default(),
+        symbol_layouts: MutMap::default(),
+    };
+
+    let proc_symbol = proc.name.name();
+    proc.args
+        .iter()
+        .find_map(|(layout, arg)| finder.define(*arg, *layout))
+        .or_else(|| finder.layout(proc.ret_layout, proc_symbol))
+        .or_else(|| finder.stmt(&proc.body, proc_symbol))
+}
+
+struct Finder<'a, 'r> {
+    interner: &'r STLayoutInterner<'a>,
+    /// Layouts that are already known to be supported
+    checked_layouts: MutSet<InLayout<'a>>,
+    /// The layouts of the symbols defined so far, for the arguments of low levels
+    symbol_layouts: MutMap<Symbol, InLayout<'a>>,
+}
+
+impl<'a, 'r> Finder<'a, 'r> {
+    fn stmt(&mut self, stmt: &Stmt<'a>, proc_symbol: Symbol) -> Option<Unsupported> {
+        let unsupported = |feature: &str| {
+            Some(Unsupported {
+                feature: feature.to_string(),
+                symbol: proc_symbol,
+            })
+        };
+
+        match stmt {
+            Stmt::Let(sym, expr, layout, following) => self
+                .define(*sym, *layout)
+                .or_else(|| self.expr(expr, *sym, *layout))
+                .or_else(|| self.stmt(following, proc_symbol)),
+            Stmt::Switch {
+                cond_symbol,
+                cond_layout,
+                branches,
+                default_branch,
+                ..
+            } => self
+                .layout(*cond_layout, *cond_symbol)
+                .or_else(|| {
+                    branches
+                        .iter()
+                        .find_map(|(_, _, branch)| self.stmt(branch, proc_symbol))
+                })
+                .or_else(|| self.stmt(default_branch.1, proc_symbol)),
+            Stmt::Join {
+                parameters,
+                body,
+                remainder,
+                ..
+            } => parameters
+                .iter()
+                .find_map(|param| self.define(param.symbol, param.layout))
+                .or_else(|| self.stmt(body, proc_symbol))
+                .or_else(|| self.stmt(remainder, proc_symbol)),
+            Stmt::Refcounting(_, following) => self.stmt(following, proc_symbol),
+            Stmt::Ret(_) | Stmt::Jump(..) | Stmt::Crash(..) => None,
+            Stmt::Dbg { .. } => unsupported("dbg"),
+            Stmt::Expect { .. } => unsupported("expect"),
+            Stmt::ExpectFx { .. } => unsupported("expect-fx"),
+        }
+    }
+
+    /// Check a symbol's layout, and remember it for the low levels that use the symbol
+    fn define(&mut self, sym: Symbol, layout: InLayout<'a>) -> Option<Unsupported> {
+        self.symbol_layouts.insert(sym, layout);
+
+        self.layout(layout, sym)
+    }
+
+    fn expr(&mut self, expr: &Expr<'a>, sym: Symbol, layout: InLayout<'a>) -> Option<Unsupported> {
+        let check_low_level = |lowlevel: LowLevel, arguments: &[Symbol]| {
+            low_level(lowlevel).or_else(|| {
+                // Any number can be stored and moved, but only some operations work on Dec
+                let uses_dec = layout == Layout::DEC
+                    || arguments
+                        .iter()
+                        .any(|arg| self.symbol_layouts.get(arg) == Some(&Layout::DEC));
+
+                (uses_dec && !dec_low_level(lowlevel, layout))
+                    .then(|| format!("the low level {:?} on Dec", lowlevel))
+            })
+        };
+
+        let feature = match expr {
+            Expr::Call(call) => match &call.call_type {
+                CallType::ByName { name, .. } => {
+                    match LowLevelWrapperType::from_symbol(name.name()) {
+                        LowLevelWrapperType::CanBeReplacedBy(lowlevel) => {
+                            check_low_level(lowlevel, call.arguments)
+                        }
+                        LowLevelWrapperType::NotALowLevelWrapper => None,
+                    }
+                }
+                CallType::LowLevel { op, .. } => check_low_level(*op, call.arguments),
+                CallType::HigherOrder(higher_order) => match higher_order.op {
+                    HigherOrder::ListMap { .. } => None,
+                    HigherOrder::ListMap2 { .. } => Some("List.map2".to_string()),
+                    HigherOrder::ListMap3 { .. } => Some("List.map3".to_string()),
+                    HigherOrder::ListMap4 { .. } => Some("List.map4".to_string()),
+                    HigherOrder::ListSortWith { .. } => Some("List.sortWith".to_string()),
+                },
+                CallType::Foreign { .. } => Some("foreign calls".to_string()),
+            },
+            Expr::Literal(_)
+            | Expr::Struct(_)
+            | Expr::StructAtIndex { .. }
+            | Expr::GetTagId { .. }
+            | Expr::UnionAtIndex { .. }
+            | Expr::Tag { .. }
+            | Expr::Array { .. }
+            | Expr::EmptyArray
+            | Expr::ExprBox { .. }
+            | Expr::ExprUnbox { .. } => None,
+            Expr::NullPointer => Some("null pointers".to_string()),
+            Expr::Reuse { .. } | Expr::Reset { .. } | Expr::ResetRef { .. } => {
+                Some("reusing allocations".to_string())
+            }
+            Expr::RuntimeErrorFunction(_) => Some("runtime error functions".to_string()),
+        };
+
+        feature.map(|feature| Unsupported {
+            feature,
+            symbol: sym,
+        })
+    }
+
+    fn layout(&mut self, layout: InLayout<'a>, sym: Symbol) -> Option<Unsupported> {
+        if !self.checked_layouts.insert(layout) {
+            return None;
+        }
+
+        let unsupported = match self.interner.get(layout) {
+            Layout::Builtin(Builtin::List(elem)) => self.layout(elem, sym),
+            Layout::Builtin(_) => None,
+            Layout::Struct { field_layouts, .. } => self.layouts(field_layouts, sym),
+            Layout::Boxed(inner) => self.layout(inner, sym),
+            Layout::LambdaSet(lambda_set) => self.layout(lambda_set.runtime_representation(), sym),
+            // The union that it points to is checked where it's defined
+            Layout::RecursivePointer(_) => None,
+            Layout::Union(union_layout) => match union_layout {
+                UnionLayout::NonRecursive(tags) | UnionLayout::Recursive(tags) => {
+                    tags.iter().find_map(|fields| self.layouts(fields, sym))
+                }
+                UnionLayout::NullableWrapped { other_tags, .. } => other_tags
+                    .iter()
+                    .find_map(|fields| self.layouts(fields, sym)),
+                UnionLayout::NonNullableUnwrapped(fields)
+                | UnionLayout::NullableUnwrapped {
+                    other_fields: fields,
+                    ..
+                } => self.layouts(fields, sym),
+            },
+        };
+
+        if unsupported.is_some() {
+            // Only remember it as supported if all of its parts are
+            self.checked_layouts.remove(&layout);
+        }
+
+        unsupported
+    }
+
+    fn layouts(&mut self, layouts: &[InLayout<'a>], sym: Symbol) -> Option<Unsupported> {
+        layouts.iter().find_map(|layout| self.layout(*layout, sym))
+    }
+}
+
+/// The name of the low level if [build_run_low_level](crate::Backend::build_run_low_level)
+/// can't build it
+fn low_level(lowlevel: LowLevel) -> Option<String> {
+    use LowLevel::*;
+
+    let supported = matches!(
+        lowlevel,
+        NumAbs
+            | NumAdd
+            | NumAddWrap
+            | NumAddChecked
+            | NumSubChecked
+            | NumAcos
+            | NumAsin
+            | NumAtan
+            | NumMul
+            | NumDivTruncUnchecked
+            | NumDivFrac
+            | NumNeg
+            | NumCountLeadingZeroBits
+            | NumCountTrailingZeroBits
+            | NumCountOneBits
+            | NumPowInt
+            | NumPow
+            | NumDivCeilUnchecked
+            | NumSub
+            | NumSubWrap
+            | NumSubSaturated
+            | NumBitwiseAnd
+            | NumBitwiseOr
+            | NumBitwiseXor
+            | And
+            | Or
+            | NumShiftLeftBy
+            | NumShiftRightBy
+            | NumShiftRightZfBy
+            | Eq
+            | NotEq
+            | Not
+            | NumLt
+            | NumCompare
+            | NumCompareTotal
+            | NumGt
+            | NumToFrac
+            | NumLte
+            | NumGte
+            | NumLogUnchecked
+            | NumSqrtUnchecked
+            | NumRound
+            | NumFloor
+            | NumCeiling
+            | ListLen
+            | ListGetCapacity
+            | ListWithCapacity
+            | ListReserve
+            | ListAppendUnsafe
+            | ListGetUnsafe
+            | ListReplaceUnsafe
+            | ListSwap
+            | ListConcat
+            | ListPrepend
+            | StrConcat
+            | StrJoinWith
+            | StrSplit
+            | StrStartsWith
+            | StrStartsWithScalar
+            | StrAppendScalar
+            | StrEndsWith
+            | StrCountGraphemes
+            | StrSubstringUnsafe
+            | StrToUtf8
+            | StrCountUtf8Bytes
+            | StrFromUtf8Range
+            | StrRepeat
+            | StrTrim
+            | StrTrimLeft
+            | StrTrimRight
+            | StrReserve
+            | StrWithCapacity
+            | StrToScalars
+            | StrGetUnsafe
+            | StrGetScalarUnsafe
+            | StrToNum
+            | PtrCast
+            | PtrWrite
+            | RefCountDec
+            | RefCountInc
+            | NumToStr
+            | StrIsEmpty
+            | StrGetCapacity
+            | NumIntCast
+    );
+
+    if supported {
+        None
+    } else {
+        Some(format!("the low level {:?}", lowlevel))
+    }
+}
+
+/// Whether [build_run_low_level](crate::Backend::build_run_low_level) can build the low level
+/// when Dec is one of its arguments, or what it returns
+fn dec_low_level(lowlevel: LowLevel, ret_layout: InLayout<'_>) -> bool {
+    use LowLevel::*;
+
+    match lowlevel {
+        // These only move the number around, without looking at it
+        ListGetUnsafe | ListReplaceUnsafe | ListAppendUnsafe | ListPrepend | PtrCast | PtrWrite
+        | RefCountInc | RefCountDec => true,
+        // These call zig builtins that take or return a Dec
+        NumSubChecked | StrToNum | NumToStr | NumRound | NumFloor | NumCeiling => true,
+        // Converting a Dec to a float is supported, but not converting a number to Dec
+        NumToFrac => ret_layout != Layout::DEC,
+        _ => false,
+    }
+}
@@ -0,0 +1,317 @@
+//! Unwind tables, which let debuggers, profilers, and panics unwinding through Roc code find the
+//! caller of each function. They are DWARF call frame information in an `.eh_frame` section,
+//! built from the [UnwindStep]s that the calling conventions record in their prologues and epilogues.
+use object::write::SymbolId;
+use object::Architecture;
+use roc_error_macros::internal_error;
+
+/// From `code_offset` in a function onwards, how to find the caller's frame changes like this.
+/// The CFA (canonical frame address) is the value the stack pointer had just before the call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnwindStep {
+    /// The CFA is now `offset` bytes above the address in `register`
+    Cfa {
+        code_offset: u32,
+        register: u16,
+        offset: u32,
+    },
+    /// The caller's value of `register` is saved at the CFA plus `cfa_offset`
+    Saved {
+        code_offset: u32,
+        register: u16,
+        cfa_offset: i32,
+    },
+    /// `register` has the caller's value again
+    Restored { code_offset: u32, register: u16 },
+}
+
+impl UnwindStep {
+    fn code_offset(&self) -> u32 {
+        match self {
+            UnwindStep::Cfa { code_offset, .. }
+            | UnwindStep::Saved { code_offset, .. }
+            | UnwindStep::Restored { code_offset, .. } => *code_offset,
+        }
+    }
+
+    /// The same step, taking effect from `code_offset` instead
+    pub fn at_code_offset(self, code_offset: u32) -> Self {
+        match self {
+            UnwindStep::Cfa {
+                register, offset, ..
+            } => UnwindStep::Cfa {
+                code_offset,
+                register,
+                offset,
+            },
+            UnwindStep::Saved {
+                register,
+                cfa_offset,
+                ..
+            } => UnwindStep::Saved {
+                code_offset,
+                register,
+                cfa_offset,
+            },
+            UnwindStep::Restored { register, .. } => UnwindStep::Restored {
+                code_offset,
+                register,
+            },
+        }
+    }
+}
+
+// DWARF call frame instructions
+const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+const DW_CFA_OFFSET: u8 = 0x80;
+const DW_CFA_RESTORE: u8 = 0xc0;
+const DW_CFA_NOP: u8 = 0x00;
+const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+const DW_CFA_OFFSET_EXTENDED: u8 = 0x05;
+const DW_CFA_RESTORE_EXTENDED: u8 = 0x06;
+const DW_CFA_DEF_CFA: u8 = 0x0c;
+
+/// FDEs refer to their function with a 4 byte offset from the field itself
+const DW_EH_PE_PCREL_SDATA4: u8 = 0x1b;
+
+/// Saved registers are always 8 bytes apart, so offsets from the CFA are encoded in units of 8
+const DATA_ALIGNMENT: i32 = -8;
+
+/// An `.eh_frame` section: one CIE with what all the functions have in common, then an FDE for each
+pub struct EhFrame {
+    bytes: std::vec::Vec<u8>,
+    /// Where each FDE needs the address of its function filled in
+    relocations: std::vec::Vec<(u64, SymbolId)>,
+}
+
+impl EhFrame {
+    pub fn new(architecture: Architecture) -> Self {
+        // The return address column, and what's true on entry to every function
+        let (return_address, initial_steps): (u8, &[UnwindStep]) = match architecture {
+            Architecture::X86_64 => (
+                16, // RIP
+                &[
+                    // The call pushed the return address, so the CFA is 8 bytes above RSP
+                    UnwindStep::Cfa {
+                        code_offset: 0,
+                        register: 7,
+                        offset: 8,
+                    },
+                    UnwindStep::Saved {
+                        code_offset: 0,
+                        register: 16,
+                        cfa_offset: -8,
+                    },
+                ],
+            ),
+            Architecture::Aarch64 => (
+                30, // LR holds the return address until the prologue saves it
+                &[UnwindStep::Cfa {
+                    code_offset: 0,
+                    register: 31,
+                    offset: 0,
+                }],
+            ),
+            _ => internal_error!("unwind tables for {:?}", architecture),
+        };
+
+        let mut bytes = vec![];
+        let length_index = bytes.len();
+        bytes.extend(0u32.to_le_bytes()); // length, filled in below
+        bytes.extend(0u32.to_le_bytes()); // CIE id
+        bytes.push(1); // version
+        bytes.extend(b"zR\0"); // augmentation: the FDE pointer encoding follows
+        encode_uleb(&mut bytes, 1); // code alignment factor
+        encode_sleb(&mut bytes, DATA_ALIGNMENT as i64);
+        bytes.push(return_address);
+        encode_uleb(&mut bytes, 1); // augmentation data length
+        bytes.push(DW_EH_PE_PCREL_SDATA4);
+        encode_steps(&mut bytes, initial_steps);
+        finish_entry(&mut bytes, length_index);
+
+        EhFrame {
+            bytes,
+            relocations: vec![],
+        }
+    }
+
+    /// Describe the frame of a function, `code_size` bytes long, that starts at `symbol`
+    pub fn add_function(&mut self, symbol: SymbolId, code_size: u32, steps: &[UnwindStep]) {
+        let bytes = &mut self.bytes;
+        let length_index = bytes.len();
+        bytes.extend(0u32.to_le_bytes()); // length, filled in below
+
+        // Distance back to the CIE, which is at the start of the section
+        let cie_pointer = bytes.len() as u32;
+        bytes.extend(cie_pointer.to_le_bytes());
+
+        self.relocations.push((bytes.len() as u64, symbol));
+        bytes.extend(0u32.to_le_bytes()); // function address
+        bytes.extend(code_size.to_le_bytes());
+        encode_uleb(bytes, 0); // augmentation data length
+
+        encode_steps(bytes, steps);
+        finish_entry(bytes, length_index);
+    }
+
+    /// The section's contents, and the offsets in it that need a 32 bit PC-relative relocation to
+    /// a function symbol.
+    pub fn finish(self) -> (std::vec::Vec<u8>, std::vec::Vec<(u64, SymbolId)>) {
+        (self.bytes, self.relocations)
+    }
+}
+
+fn encode_steps(bytes: &mut std::vec::Vec<u8>, steps: &[UnwindStep]) {
+    let mut code_offset = 0;
+
+    for step in steps {
+        debug_assert!(
+            step.code_offset() >= code_offset,
+            "unwind steps out of order"
+        );
+        let advance = step.code_offset() - code_offset;
+        if advance > 0 {
+            if advance < 0x40 {
+                bytes.push(DW_CFA_ADVANCE_LOC | advance as u8);
+            } else if advance <= u8::MAX as u32 {
+                bytes.push(DW_CFA_ADVANCE_LOC1);
+                bytes.push(advance as u8);
+            } else if advance <= u16::MAX as u32 {
+                bytes.push(DW_CFA_ADVANCE_LOC2);
+                bytes.extend((advance as u16).to_le_bytes());
+            } else {
+                bytes.push(DW_CFA_ADVANCE_LOC4);
+                bytes.extend(advance.to_le_bytes());
+            }
+            code_offset = step.code_offset();
+        }
+
+        match *step {
+            UnwindStep::Cfa {
+                register, offset, ..
+            } => {
+                bytes.push(DW_CFA_DEF_CFA);
+                encode_uleb(bytes, register as u64);
+                encode_uleb(bytes, offset as u64);
+            }
+            UnwindStep::Saved {
+                register,
+                cfa_offset,
+                ..
+            } => {
+                debug_assert!(cfa_offset < 0 && cfa_offset % DATA_ALIGNMENT == 0);
+                let factored_offset = (cfa_offset / DATA_ALIGNMENT) as u64;
+                if register < 0x40 {
+                    bytes.push(DW_CFA_OFFSET | register as u8);
+                } else {
+                    bytes.push(DW_CFA_OFFSET_EXTENDED);
+                    encode_uleb(bytes, register as u64);
+                }
+                encode_uleb(bytes, factored_offset);
+            }
+            UnwindStep::Restored { register, .. } => {
+                if register < 0x40 {
+                    bytes.push(DW_CFA_RESTORE | register as u8);
+                } else {
+                    bytes.push(DW_CFA_RESTORE_EXTENDED);
+                    encode_uleb(bytes, register as u64);
+                }
+            }
+        }
+    }
+}
+
+/// Pad a CIE or FDE to a multiple of 8 bytes, and fill in its length
+fn finish_entry(bytes: &mut std::vec::Vec<u8>, length_index: usize) {
+    while (bytes.len() - length_index) % 8 != 0 {
+        bytes.push(DW_CFA_NOP);
+    }
+    let length = (bytes.len() - length_index - 4) as u32;
+    bytes[length_index..][..4].copy_from_slice(&length.to_le_bytes());
+}
+
+fn encode_uleb(bytes: &mut std::vec::Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn encode_sleb(bytes: &mut std::vec::Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let sign_bit_clear = byte & 0x40 == 0;
+        if (value == 0 && sign_bit_clear) || (value == -1 && !sign_bit_clear) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leb128() {
+        let mut bytes = vec![];
+        encode_uleb(&mut bytes, 624485);
+        assert_eq!(bytes, [0xe5, 0x8e, 0x26]);
+
+        bytes.clear();
+        encode_sleb(&mut bytes, -8);
+        assert_eq!(bytes, [0x78]);
+
+        bytes.clear();
+        encode_sleb(&mut bytes, -123456);
+        assert_eq!(bytes, [0xc0, 0xbb, 0x78]);
+    }
+
+    #[test]
+    fn test_encode_steps() {
+        let mut bytes = vec![];
+        encode_steps(
+            &mut bytes,
+            &[
+                UnwindStep::Cfa {
+                    code_offset: 1,
+                    register: 7,
+                    offset: 16,
+                },
+                UnwindStep::Saved {
+                    code_offset: 1,
+                    register: 6,
+                    cfa_offset: -16,
+                },
+                UnwindStep::Cfa {
+                    code_offset: 4,
+                    register: 6,
+                    offset: 16,
+                },
+                UnwindStep::Restored {
+                    code_offset: 300,
+                    register: 6,
+                },
+            ],
+        );
+        assert_eq!(
+            bytes,
+            [
+                0x41, 0x0c, 7, 16, // advance 1, def_cfa rsp+16
+                0x86, 2, // rbp at cfa-16
+                0x43, 0x0c, 6, 16, // advance 3, def_cfa rbp+16
+                0x03, 0x28, 0x01, // advance 296
+                0xc6, // restore rbp
+            ]
+        );
+    }
+}
@@ -1251,6 +1251,44 @@ pub(crate) fn run_low_level<'a, 'ctx>(
             unreachable!("Not used in LLVM backend: {:?}", op);
         }
 
+        NumCompareTotal => {
+            arguments_with_layouts!((lhs_arg, lhs_layout), (rhs_arg, rhs_layout));
+
+            debug_assert_eq!(lhs_layout, rhs_layout);
+
+            let (lhs_key, rhs_key) = match layout_interner.get(lhs_layout) {
+                Layout::Builtin(Builtin::Float(float_width)) => (
+                    float_total_order_key(env, lhs_arg.into_float_value(), float_width),
+                    float_total_order_key(env, rhs_arg.into_float_value(), float_width),
+                ),
+                // Dec has no NaN or negative zero, so its total order is the usual one
+                Layout::Builtin(Builtin::Decimal) => {
+                    (lhs_arg.into_int_value(), rhs_arg.into_int_value())
+                }
+                _ => {
+                    unreachable!("Compiler bug: tried to run numeric operation {:?} on invalid layout: ({:?})", op, lhs_layout);
+                }
+            };
+
+            let tag_eq = env.context.i8_type().const_int(0_u64, false);
+            let tag_gt = env.context.i8_type().const_int(1_u64, false);
+            let tag_lt = env.context.i8_type().const_int(2_u64, false);
+
+            let are_equal =
+                env.builder
+                    .build_int_compare(IntPredicate::EQ, lhs_key, rhs_key, "total_eq");
+            let is_less_than =
+                env.builder
+                    .build_int_compare(IntPredicate::SLT, lhs_key, rhs_key, "total_compare");
+
+            let step1 = env
+                .builder
+                .build_select(is_less_than, tag_lt, tag_gt, "lt_or_gt");
+
+            env.builder
+                .build_select(are_equal, tag_eq, step1.into_int_value(), "lt_or_gt")
+        }
+
         Unreachable => match RocReturn::from_layout(env, layout_interner, layout) {
             RocReturn::Return => {
                 let basic_type = basic_type_from_layout(env, layout_interner, layout);
@@ -1267,6 +1305,32 @@ pub(crate) fn run_low_level<'a, 'ctx>(
     }
 }
 
+/// The bits of a float, in a form where comparing them as signed integers orders them like
+/// IEEE 754 totalOrder: all the bits but the sign are flipped for a negative float, so that the
+/// more negative it is, the lower it is.
+fn float_total_order_key<'ctx>(
+    env: &Env<'_, 'ctx, '_>,
+    arg: FloatValue<'ctx>,
+    float_width: FloatWidth,
+) -> IntValue<'ctx> {
+    let bd = env.builder;
+    let int_type = match float_width {
+        FloatWidth::F32 => env.context.i32_type(),
+        FloatWidth::F64 => env.context.i64_type(),
+    };
+
+    let bits = bd
+        .build_bitcast(arg, int_type, "float_bits")
+        .into_int_value();
+
+    // bits ^ ((bits >> sign arithmetic) >> 1 logical), which is all ones but the sign if negative
+    let sign_shift = int_type.const_int(int_type.get_bit_width() as u64 - 1, false);
+    let sign = bd.build_right_shift(bits, sign_shift, true, "float_sign");
+    let mask = bd.build_right_shift(sign, int_type.const_int(1, false), false, "key_mask");
+
+    bd.build_xor(bits, mask, "total_order_key")
+}
+
 fn intwidth_from_layout(layout: InLayout) -> IntWidth {
     layout.to_int_width()
 }
@@ -18,6 +18,7 @@ use roc_module::symbol::{Interns, ModuleId, Symbol};
 use roc_mono::code_gen_help::CodeGenHelp;
 use roc_mono::ir::{Proc, ProcLayout};
 use roc_mono::layout::{LayoutIds, STLayoutInterner};
+use roc_mono::simplify::{simplify_proc, SimplifyStats};
 use roc_target::TargetInfo;
 use roc_wasm_module::parse::ParseError;
 use roc_wasm_module::{Align, LocalId, ValueType, WasmModule};
@@ -97,6 +98,7 @@ pub fn build_app_module<'a, 'r>(
     let mut proc_lookup = Vec::with_capacity_in(procedures.len() * 2, env.arena);
     let mut host_to_app_map = Vec::with_capacity_in(env.exposed_to_host.len(), env.arena);
     let mut maybe_main_fn_index = None;
+    let mut simplify_stats = SimplifyStats::default();
 
     // Adjust Wasm function indices to account for functions from the object file
     let fn_index_offset: u32 =
@@ -106,7 +108,12 @@ pub fn build_app_module<'a, 'r>(
     // Create a lookup to tell us the final index of each proc in the output file
     for (i, ((sym, proc_layout), proc)) in procedures.into_iter().enumerate() {
         let fn_index = fn_index_offset + i as u32;
-        procs.push(proc);
+        procs.push(simplify_proc(
+            env.arena,
+            layout_interner,
+            proc,
+            &mut simplify_stats,
+        ));
         if env.exposed_to_host.contains(&sym) {
             maybe_main_fn_index = Some(fn_index);
 
@@ -126,6 +133,10 @@ pub fn build_app_module<'a, 'r>(
         });
     }
 
+    roc_debug_flags::dbg_do!(roc_debug_flags::ROC_PRINT_SIMPLIFY_STATS, {
+        eprintln!("{:?}: {}", env.module_id, simplify_stats);
+    });
+
     let mut backend = WasmBackend::new(
         env,
         layout_interner,
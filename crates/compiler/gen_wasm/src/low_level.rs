@@ -10,6 +10,7 @@ use roc_mono::layout::{Builtin, FieldOrderHash, InLayout, Layout, LayoutInterner
 use roc_mono::low_level::HigherOrder;
 
 use crate::backend::{ProcLookupData, ProcSource, WasmBackend};
+use crate::code_builder::CodeBuilder;
 use crate::layout::{CallConv, StackMemoryFormat, WasmLayout};
 use crate::storage::{AddressValue, StackMemoryLocation, StoredValue};
 use crate::{PTR_TYPE, TARGET_INFO};
@@ -74,6 +75,7 @@ impl From<ValueType> for CodeGenNumType {
             ValueType::I64 => CodeGenNumType::I64,
             ValueType::F32 => CodeGenNumType::F32,
             ValueType::F64 => CodeGenNumType::F64,
+            ValueType::V128 => internal_error!("Roc numbers are never stored as a v128"),
         }
     }
 }
@@ -1216,7 +1218,8 @@ impl<'a> LowLevelCall<'a> {
                         backend.code_builder.f64_lt();
                         backend.code_builder.i32_add();
                     }
-                    x => todo!("{:?} for {:?}", self.lowlevel, x),
+                    I128 | Decimal => self.compare_128(backend, layout != Layout::U128),
+                    x => internal_error!("{:?} for {:?}", self.lowlevel, x),
                 }
             }
             NumDivFrac => {
@@ -1957,6 +1960,38 @@ impl<'a> LowLevelCall<'a> {
 
             PtrWrite => todo!("{:?}", self.lowlevel),
 
+            NumCompareTotal => {
+                // Like NumCompare, but on keys that order the floats like integers
+                let layout = backend.storage.symbol_layouts[&self.arguments[0]];
+                match CodeGenNumType::from(layout) {
+                    F32 => {
+                        let [key0, key1] = self.total_order_keys(backend, ValueType::I32);
+                        let code_builder = &mut backend.code_builder;
+                        code_builder.get_local(key0);
+                        code_builder.get_local(key1);
+                        code_builder.i32_ne();
+                        code_builder.get_local(key0);
+                        code_builder.get_local(key1);
+                        code_builder.i32_lt_s();
+                        code_builder.i32_add();
+                    }
+                    F64 => {
+                        let [key0, key1] = self.total_order_keys(backend, ValueType::I64);
+                        let code_builder = &mut backend.code_builder;
+                        code_builder.get_local(key0);
+                        code_builder.get_local(key1);
+                        code_builder.i64_ne();
+                        code_builder.get_local(key0);
+                        code_builder.get_local(key1);
+                        code_builder.i64_lt_s();
+                        code_builder.i32_add();
+                    }
+                    // Dec has no NaN or negative zero, so its total order is the usual one
+                    Decimal => self.compare_128(backend, true),
+                    x => internal_error!("{:?} for {:?}", self.lowlevel, x),
+                }
+            }
+
             Hash => todo!("{:?}", self.lowlevel),
 
             Eq | NotEq => self.eq_or_neq(backend),
@@ -1972,6 +2007,7 @@ impl<'a> LowLevelCall<'a> {
                     ValueType::I64 => backend.code_builder.i64_const(0),
                     ValueType::F32 => backend.code_builder.f32_const(0.0),
                     ValueType::F64 => backend.code_builder.f64_const(0.0),
+                    ValueType::V128 => internal_error!("Roc values are never stored as a v128"),
                 },
                 StoredValue::StackMemory { .. } => { /* do nothing */ }
             },
@@ -2064,12 +2100,14 @@ impl<'a> LowLevelCall<'a> {
                         ValueType::I64 => backend.code_builder.i64_eq(),
                         ValueType::F32 => backend.code_builder.f32_eq(),
                         ValueType::F64 => backend.code_builder.f64_eq(),
+                        ValueType::V128 => internal_error!("Roc values are never stored as a v128"),
                     },
                     LowLevel::NotEq => match value_type {
                         ValueType::I32 => backend.code_builder.i32_ne(),
                         ValueType::I64 => backend.code_builder.i64_ne(),
                         ValueType::F32 => backend.code_builder.f32_ne(),
                         ValueType::F64 => backend.code_builder.f64_ne(),
+                        ValueType::V128 => internal_error!("Roc values are never stored as a v128"),
                     },
                     _ => internal_error!("{:?} ended up in Equality code", self.lowlevel),
                 }
@@ -2137,6 +2175,98 @@ impl<'a> LowLevelCall<'a> {
         backend.code_builder.i32_and();
     }
 
+    /// Compares two 128-bit numbers in stack memory, like NumCompare does for smaller ones.
+    /// The high halves are compared with their sign, and only decide the order when they differ.
+    /// Otherwise the low halves, compared without a sign, decide it.
+    fn compare_128(&self, backend: &mut WasmBackend<'a, '_>, is_signed: bool) {
+        let [(ptr0, offset0), (ptr1, offset1)] =
+            [self.arguments[0], self.arguments[1]].map(|arg| match backend.storage.get(&arg) {
+                StoredValue::StackMemory { location, .. } => {
+                    location.local_and_offset(backend.storage.stack_frame_pointer)
+                }
+                _ => internal_error!("128-bit numbers should be in stack memory"),
+            });
+
+        let load_halves = |code_builder: &mut CodeBuilder<'a>, half: u32| {
+            code_builder.get_local(ptr0);
+            code_builder.i64_load(Align::Bytes8, offset0 + half);
+            code_builder.get_local(ptr1);
+            code_builder.i64_load(Align::Bytes8, offset1 + half);
+        };
+        const LOW: u32 = 0;
+        const HIGH: u32 = 8;
+        let code_builder = &mut backend.code_builder;
+
+        // (x != y) as u8
+        load_halves(code_builder, LOW);
+        code_builder.i64_ne();
+        load_halves(code_builder, HIGH);
+        code_builder.i64_ne();
+        code_builder.i32_or();
+
+        // + (x < y) as u8
+        load_halves(code_builder, LOW);
+        code_builder.i64_lt_u();
+        load_halves(code_builder, HIGH);
+        if is_signed {
+            code_builder.i64_lt_s();
+        } else {
+            code_builder.i64_lt_u();
+        }
+        load_halves(code_builder, HIGH);
+        code_builder.i64_eq();
+        code_builder.select();
+
+        code_builder.i32_add();
+    }
+
+    /// Stores the bits of each float argument in a new local, in a form where comparing them as
+    /// signed integers orders the floats like IEEE 754 totalOrder: all the bits but the sign are
+    /// flipped for a negative float, so that the more negative it is, the lower it is.
+    fn total_order_keys(
+        &self,
+        backend: &mut WasmBackend<'a, '_>,
+        key_type: ValueType,
+    ) -> [LocalId; 2] {
+        let keys = [
+            backend.storage.create_anonymous_local(key_type),
+            backend.storage.create_anonymous_local(key_type),
+        ];
+
+        for (arg, key) in self.arguments.iter().zip(keys) {
+            let code_builder = &mut backend.code_builder;
+            backend.storage.load_symbols(code_builder, &[*arg]);
+
+            // key = bits ^ ((bits >> sign arithmetic) >> 1 logical)
+            match key_type {
+                ValueType::I32 => {
+                    code_builder.i32_reinterpret_f32();
+                    code_builder.tee_local(key);
+                    code_builder.get_local(key);
+                    code_builder.i32_const(31);
+                    code_builder.i32_shr_s();
+                    code_builder.i32_const(1);
+                    code_builder.i32_shr_u();
+                    code_builder.i32_xor();
+                }
+                _ => {
+                    code_builder.i64_reinterpret_f64();
+                    code_builder.tee_local(key);
+                    code_builder.get_local(key);
+                    code_builder.i64_const(63);
+                    code_builder.i64_shr_s();
+                    code_builder.i64_const(1);
+                    code_builder.i64_shr_u();
+                    code_builder.i64_xor();
+                }
+            }
+
+            code_builder.set_local(key);
+        }
+
+        keys
+    }
+
     fn num_to_str(&self, backend: &mut WasmBackend<'a, '_>) {
         let arg_layout = backend.storage.symbol_layouts[&self.arguments[0]];
         match backend.layout_interner.get(arg_layout) {
@@ -2187,6 +2317,7 @@ fn num_is_finite(backend: &mut WasmBackend<'_, '_>, argument: Symbol) {
                     backend.code_builder.i64_const(0x7ff0_0000_0000_0000);
                     backend.code_builder.i64_ne();
                 }
+                ValueType::V128 => internal_error!("Roc values are never stored as a v128"),
             }
         }
         StackMemory { format, .. } => {
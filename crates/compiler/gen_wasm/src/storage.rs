@@ -69,6 +69,7 @@ impl StoredValue {
                     I64 => &[I64],
                     F32 => &[F32],
                     F64 => &[F64],
+                    V128 => internal_error!("Roc values are never stored as a v128"),
                 }
             }
             // Stack memory values: 1 Roc argument => 0-2 Wasm arguments
@@ -31,6 +31,30 @@ pub struct DocDef {
     pub type_vars: Vec<String>,
     pub type_annotation: TypeAnnotation,
     pub docs: Option<String>,
+    /// Names of the abilities this type has implementations for, as written in its `has` clause.
+    /// e.g. `["Eq", "Hash.Hash"]`. Always empty for anything other than opaque types.
+    pub implements: Vec<String>,
+    /// Names of the types used in the type annotation, as written, e.g. `["Task", "Str.Str"]`.
+    /// Each name is only listed once.
+    pub referenced_types: Vec<String>,
+    /// The arguments of a function, in order. Empty unless they're named, either by the
+    /// definition's argument patterns or by `* name - description` lines in its doc comment.
+    pub args: Vec<DocArg>,
+    /// The definition as it's written in the module, without its doc comment
+    pub source: String,
+    /// The qualified name of what this definition re-exports, e.g. `"Internal.decode"` for
+    /// `decode = Internal.decode`, or `"Internal.Json"` for `Json : Internal.Json`.
+    pub reexported_from: Option<String>,
+}
+
+/// An argument of a documented function
+#[derive(Debug, Clone)]
+pub struct DocArg {
+    /// The name of the argument, or `_` if it has none
+    pub name: String,
+    pub type_annotation: TypeAnnotation,
+    /// From the `* name - description` line about this argument, if there is one
+    pub docs: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -100,6 +124,7 @@ pub fn generate_module_docs(
     exposed_module_ids: &[ModuleId],
     exposed_symbols: VecSet<Symbol>,
     header_comments: &[CommentOrNewline<'_>],
+    src: &str,
 ) -> ModuleDocumentation {
     let entries = generate_entry_docs(
         home,
@@ -108,6 +133,7 @@ pub fn generate_module_docs(
         parsed_defs,
         exposed_module_ids,
         header_comments,
+        src,
     );
 
     ModuleDocumentation {
@@ -152,6 +178,7 @@ fn generate_entry_docs(
     defs: &roc_parse::ast::Defs<'_>,
     exposed_module_ids: &[ModuleId],
     header_comments: &[CommentOrNewline<'_>],
+    src: &str,
 ) -> Vec<DocEntry> {
     use roc_parse::ast::Pattern;
 
@@ -178,6 +205,12 @@ fn generate_entry_docs(
 
         let docs = comments_or_new_lines_to_docs(&scratchpad);
 
+        let region = defs.regions[index];
+        let source = src
+            .get(region.start().offset as usize..region.end().offset as usize)
+            .unwrap_or_default()
+            .to_string();
+
         match either_index.split() {
             Err(value_index) => match &defs.value_defs[value_index.index()] {
                 ValueDef::Annotation(loc_pattern, loc_ann) => {
@@ -185,12 +218,19 @@ fn generate_entry_docs(
                         // Check if this module exposes the def
                         if let Some(ident_id) = ident_ids.get_id(identifier) {
                             let name = identifier.to_string();
+                            let type_annotation = type_to_docs(false, loc_ann.value);
+                            let (docs, args) = doc_args(&type_annotation, &[], docs);
                             let doc_def = DocDef {
                                 name,
                                 symbol: Symbol::new(home, ident_id),
-                                type_annotation: type_to_docs(false, loc_ann.value),
+                                referenced_types: referenced_type_names(&type_annotation),
+                                args,
+                                type_annotation,
                                 type_vars: Vec::new(),
                                 docs,
+                                implements: Vec::new(),
+                                source,
+                                reexported_from: None,
                             };
                             acc.push(DocEntry::DocDef(doc_def));
                         }
@@ -200,25 +240,53 @@ fn generate_entry_docs(
                 ValueDef::AnnotatedBody {
                     ann_pattern,
                     ann_type,
+                    body_expr,
                     ..
                 } => {
                     if let Pattern::Identifier(identifier) = ann_pattern.value {
                         // Check if this module exposes the def
                         if let Some(ident_id) = ident_ids.get_id(identifier) {
+                            let type_annotation = type_to_docs(false, ann_type.value);
+                            let arg_names = closure_arg_names(&body_expr.value);
+                            let (docs, args) = doc_args(&type_annotation, &arg_names, docs);
                             let doc_def = DocDef {
                                 name: identifier.to_string(),
-                                type_annotation: type_to_docs(false, ann_type.value),
+                                referenced_types: referenced_type_names(&type_annotation),
+                                args,
+                                type_annotation,
                                 type_vars: Vec::new(),
                                 symbol: Symbol::new(home, ident_id),
                                 docs,
+                                implements: Vec::new(),
+                                source,
+                                reexported_from: reexported_value(&body_expr.value),
                             };
                             acc.push(DocEntry::DocDef(doc_def));
                         }
                     }
                 }
 
-                ValueDef::Body(_, _) => {
-                    // TODO generate docs for un-annotated bodies
+                ValueDef::Body(loc_pattern, loc_expr) => {
+                    // TODO generate docs for other un-annotated bodies
+                    if let (Pattern::Identifier(identifier), Some(reexported_from)) =
+                        (loc_pattern.value, reexported_value(&loc_expr.value))
+                    {
+                        if let Some(ident_id) = ident_ids.get_id(identifier) {
+                            let doc_def = DocDef {
+                                name: identifier.to_string(),
+                                symbol: Symbol::new(home, ident_id),
+                                type_vars: Vec::new(),
+                                type_annotation: TypeAnnotation::NoTypeAnn,
+                                docs,
+                                implements: Vec::new(),
+                                referenced_types: Vec::new(),
+                                args: Vec::new(),
+                                source,
+                                reexported_from: Some(reexported_from),
+                            };
+                            acc.push(DocEntry::DocDef(doc_def));
+                        }
+                    }
                 }
 
                 ValueDef::Dbg { .. } => {
@@ -257,19 +325,26 @@ fn generate_entry_docs(
                             type_to_docs(false, ann.value)
                         };
 
+                    let reexported_from = reexported_type(name.value, &type_vars, &ann.value);
                     let ident_id = ident_ids.get_id(name.value).unwrap();
                     let doc_def = DocDef {
                         name: name.value.to_string(),
+                        referenced_types: referenced_type_names(&type_annotation),
                         type_annotation,
                         type_vars,
                         docs,
                         symbol: Symbol::new(home, ident_id),
+                        implements: Vec::new(),
+                        args: Vec::new(),
+                        source,
+                        reexported_from,
                     };
                     acc.push(DocEntry::DocDef(doc_def));
                 }
 
                 TypeDef::Opaque {
                     header: TypeHeader { name, vars },
+                    derived,
                     ..
                 } => {
                     let mut type_vars = Vec::new();
@@ -280,6 +355,11 @@ fn generate_entry_docs(
                         }
                     }
 
+                    let implements = match derived {
+                        Some(loc_abilities) => implemented_ability_names(&loc_abilities.value),
+                        None => Vec::new(),
+                    };
+
                     let ident_id = ident_ids.get_id(name.value).unwrap();
                     let doc_def = DocDef {
                         name: name.value.to_string(),
@@ -287,6 +367,11 @@ fn generate_entry_docs(
                         type_vars,
                         docs,
                         symbol: Symbol::new(home, ident_id),
+                        implements,
+                        referenced_types: Vec::new(),
+                        args: Vec::new(),
+                        source,
+                        reexported_from: None,
                     };
                     acc.push(DocEntry::DocDef(doc_def));
                 }
@@ -320,13 +405,19 @@ fn generate_entry_docs(
                         })
                         .collect();
 
+                    let type_annotation = TypeAnnotation::Ability { members };
                     let ident_id = ident_ids.get_id(name.value).unwrap();
                     let doc_def = DocDef {
                         name: name.value.to_string(),
-                        type_annotation: TypeAnnotation::Ability { members },
+                        referenced_types: referenced_type_names(&type_annotation),
+                        type_annotation,
                         symbol: Symbol::new(home, ident_id),
                         type_vars,
                         docs,
+                        implements: Vec::new(),
+                        args: Vec::new(),
+                        source,
+                        reexported_from: None,
                     };
                     acc.push(DocEntry::DocDef(doc_def));
                 }
@@ -346,6 +437,180 @@ fn generate_entry_docs(
     acc
 }
 
+/// Pairs up a function's argument types with their names and docs, and takes the docs out of
+/// the function's doc comment. Each argument can be described by a line like this, anywhere in
+/// the comment:
+///
+/// ```text
+/// ## * msg - what to send
+/// ```
+///
+/// The names come from the definition if it has them, e.g. `\msg, target -> ...`, or else
+/// from the order of those lines. Returns no arguments if neither of them names every argument.
+fn doc_args(
+    type_annotation: &TypeAnnotation,
+    arg_names: &[String],
+    docs: Option<String>,
+) -> (Option<String>, Vec<DocArg>) {
+    let (arg_types, docs) = match (type_annotation, docs) {
+        (Function { args, .. }, Some(docs)) => (args, docs),
+        (Function { args, .. }, None) => (args, String::new()),
+        (_, docs) => return (docs, Vec::new()),
+    };
+
+    let described: Vec<(&str, &str)> = docs.lines().filter_map(arg_description).collect();
+
+    let names: Vec<String> =
+        if arg_names.len() == arg_types.len() && arg_names.iter().any(|name| name != "_") {
+            arg_names.to_vec()
+        } else if described.len() == arg_types.len() {
+            described.iter().map(|(name, _)| name.to_string()).collect()
+        } else {
+            let docs = if docs.is_empty() { None } else { Some(docs) };
+            return (docs, Vec::new());
+        };
+
+    let args = names
+        .into_iter()
+        .zip(arg_types)
+        .map(|(name, arg_type)| {
+            let docs = described
+                .iter()
+                .find(|(described_name, _)| *described_name == name)
+                .map(|(_, description)| description.to_string());
+
+            DocArg {
+                name,
+                type_annotation: arg_type.clone(),
+                docs,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Leave any lines that don't describe one of the arguments, like other list items
+    let mut other_lines = String::new();
+
+    for line in docs.lines() {
+        let is_arg_line = matches!(
+            arg_description(line),
+            Some((name, _)) if args.iter().any(|arg| arg.name == name)
+        );
+
+        if !is_arg_line {
+            other_lines.push_str(line);
+            other_lines.push('\n');
+        }
+    }
+
+    let docs = if other_lines.trim().is_empty() {
+        None
+    } else {
+        Some(other_lines)
+    };
+
+    (docs, args)
+}
+
+/// The name and description in a line like `* msg - what to send`
+fn arg_description(line: &str) -> Option<(&str, &str)> {
+    let (name, description) = line.trim_start().strip_prefix("* ")?.split_once(" - ")?;
+    let name = name.trim();
+    let is_name = name.starts_with(|c: char| c.is_lowercase())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+
+    if is_name {
+        Some((name, description.trim()))
+    } else {
+        None
+    }
+}
+
+/// The names of a function's arguments, from its definition, e.g. `["msg", "target"]` for
+/// `\msg, target -> ...`. Arguments that aren't a plain identifier are named `_`.
+fn closure_arg_names(expr: &ast::Expr) -> Vec<String> {
+    use roc_parse::ast::Pattern;
+
+    fn arg_name(pattern: &Pattern) -> String {
+        match pattern {
+            Pattern::Identifier(name) => name.to_string(),
+            Pattern::SpaceBefore(sub_pattern, _) | Pattern::SpaceAfter(sub_pattern, _) => {
+                arg_name(sub_pattern)
+            }
+            _ => "_".to_string(),
+        }
+    }
+
+    match expr {
+        ast::Expr::Closure(args, _) => args.iter().map(|arg| arg_name(&arg.value)).collect(),
+        ast::Expr::SpaceBefore(sub_expr, _) | ast::Expr::SpaceAfter(sub_expr, _) => {
+            closure_arg_names(sub_expr)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The qualified name a value re-exports, if its body is just a lookup in another module,
+/// e.g. `Internal.decode`
+fn reexported_value(expr: &ast::Expr) -> Option<String> {
+    match expr {
+        ast::Expr::Var { module_name, ident } if !module_name.is_empty() => {
+            Some(format!("{}.{}", module_name, ident))
+        }
+        ast::Expr::SpaceBefore(sub_expr, _) | ast::Expr::SpaceAfter(sub_expr, _) => {
+            reexported_value(sub_expr)
+        }
+        _ => None,
+    }
+}
+
+/// The qualified name a type alias re-exports, if it's the same type from another module,
+/// e.g. `Json a : Internal.Json a`
+fn reexported_type(name: &str, type_vars: &[String], ann: &ast::TypeAnnotation) -> Option<String> {
+    match ann {
+        ast::TypeAnnotation::Apply(module_name, type_name, args)
+            if !module_name.is_empty()
+                && *type_name == name
+                && args.len() == type_vars.len()
+                && args
+                    .iter()
+                    .zip(type_vars)
+                    .all(|(arg, var)| is_type_var(&arg.value, var)) =>
+        {
+            Some(format!("{}.{}", module_name, type_name))
+        }
+        ast::TypeAnnotation::SpaceBefore(sub_ann, _)
+        | ast::TypeAnnotation::SpaceAfter(sub_ann, _) => reexported_type(name, type_vars, sub_ann),
+        _ => None,
+    }
+}
+
+fn is_type_var(ann: &ast::TypeAnnotation, name: &str) -> bool {
+    matches!(ann.extract_spaces().item, ast::TypeAnnotation::BoundVariable(var) if var == name)
+}
+
+/// The names of the abilities in a `has [...]` clause, e.g. `["Eq", "Hash.Hash"]`
+fn implemented_ability_names(has_abilities: &ast::HasAbilities) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for loc_has_ability in has_abilities.collection().iter() {
+        if let ast::HasAbility::HasAbility { ability, .. } =
+            loc_has_ability.value.extract_spaces().item
+        {
+            if let ast::TypeAnnotation::Apply(module_name, ability_name, _) =
+                ability.value.extract_spaces().item
+            {
+                if module_name.is_empty() {
+                    names.push(ability_name.to_string());
+                } else {
+                    names.push(format!("{}.{}", module_name, ability_name));
+                }
+            }
+        }
+    }
+
+    names
+}
+
 /// Does this type contain any types which are not exposed outside the package?
 /// (If so, we shouldn't try to render a type annotation for it.)
 fn contains_unexposed_type(
@@ -463,6 +728,64 @@ fn contains_unexposed_type(
     }
 }
 
+/// The names of all the types applied anywhere in a type annotation, in the order they appear
+fn referenced_type_names(type_annotation: &TypeAnnotation) -> Vec<String> {
+    fn help(type_annotation: &TypeAnnotation, names: &mut Vec<String>) {
+        match type_annotation {
+            TagUnion { tags, extension } => {
+                for tag in tags {
+                    for value in tag.values.iter() {
+                        help(value, names);
+                    }
+                }
+                help(extension, names);
+            }
+            Function { args, output } => {
+                for arg in args {
+                    help(arg, names);
+                }
+                help(output, names);
+            }
+            Apply { name, parts } => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+                for part in parts {
+                    help(part, names);
+                }
+            }
+            Record { fields, extension } => {
+                for field in fields {
+                    match field {
+                        RecordField::RecordField {
+                            type_annotation, ..
+                        }
+                        | RecordField::OptionalField {
+                            type_annotation, ..
+                        } => help(type_annotation, names),
+                        RecordField::LabelOnly { .. } => {}
+                    }
+                }
+                help(extension, names);
+            }
+            TypeAnnotation::Ability { members } => {
+                for member in members {
+                    help(&member.type_annotation, names);
+                }
+            }
+            TypeAnnotation::ObscuredTagUnion
+            | TypeAnnotation::ObscuredRecord
+            | TypeAnnotation::Wildcard
+            | BoundVariable(_)
+            | NoTypeAnn => {}
+        }
+    }
+
+    let mut names = Vec::new();
+    help(type_annotation, &mut names);
+    names
+}
+
 fn type_to_docs(in_func_type_ann: bool, type_annotation: ast::TypeAnnotation) -> TypeAnnotation {
     match type_annotation {
         ast::TypeAnnotation::TagUnion { tags, ext } => {
@@ -636,3 +959,152 @@ fn comments_or_new_lines_to_docs<'a>(
         Some(docs)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{arg_description, closure_arg_names, doc_args, TypeAnnotation};
+    use bumpalo::Bump;
+    use pretty_assertions::assert_eq;
+    use roc_parse::test_helpers::parse_expr_with;
+
+    fn apply(name: &str) -> TypeAnnotation {
+        TypeAnnotation::Apply {
+            name: name.to_string(),
+            parts: Vec::new(),
+        }
+    }
+
+    /// The type of `send : Str, Target -> Bool`
+    fn send_type() -> TypeAnnotation {
+        TypeAnnotation::Function {
+            args: vec![apply("Str"), apply("Target")],
+            output: Box::new(apply("Bool")),
+        }
+    }
+
+    fn names(arg_names: &[&str]) -> Vec<String> {
+        arg_names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// The docs left in the doc comment, and each argument's name and docs
+    fn args(
+        type_annotation: &TypeAnnotation,
+        arg_names: &[&str],
+        docs: Option<&str>,
+    ) -> (Option<String>, Vec<(String, Option<String>)>) {
+        let (docs, args) = doc_args(type_annotation, &names(arg_names), docs.map(String::from));
+        let args = args.into_iter().map(|arg| (arg.name, arg.docs)).collect();
+
+        (docs, args)
+    }
+
+    #[test]
+    fn argument_description_lines() {
+        assert_eq!(
+            arg_description("* msg - what to send"),
+            Some(("msg", "what to send"))
+        );
+        assert_eq!(
+            arg_description("  *  target_2 -  where  "),
+            Some(("target_2", "where"))
+        );
+        assert_eq!(arg_description("* Msg - a type"), None);
+        assert_eq!(arg_description("* two words - no"), None);
+        assert_eq!(arg_description("* msg: what to send"), None);
+        assert_eq!(arg_description("- msg - a dash list"), None);
+    }
+
+    #[test]
+    fn names_from_the_definition() {
+        let docs = "Sends it.\n* target - where to\n\n* msg - what to send\n";
+
+        assert_eq!(
+            args(&send_type(), &["msg", "target"], Some(docs)),
+            (
+                Some("Sends it.\n\n".to_string()),
+                vec![
+                    ("msg".to_string(), Some("what to send".to_string())),
+                    ("target".to_string(), Some("where to".to_string())),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn names_from_the_doc_comment() {
+        // Pattern arguments, like `\{ text }, _ ->`, don't have names in the definition
+        let docs = "* msg - what to send\n* target - where to\n";
+
+        assert_eq!(
+            args(&send_type(), &["_", "_"], Some(docs)),
+            (
+                None,
+                vec![
+                    ("msg".to_string(), Some("what to send".to_string())),
+                    ("target".to_string(), Some("where to".to_string())),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn some_arguments_undescribed() {
+        let docs = "Sends it.\n* target - where to\n* other - not an argument\n";
+
+        assert_eq!(
+            args(&send_type(), &["msg", "target"], Some(docs)),
+            (
+                Some("Sends it.\n* other - not an argument\n".to_string()),
+                vec![
+                    ("msg".to_string(), None),
+                    ("target".to_string(), Some("where to".to_string())),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn no_arguments_unless_every_one_is_named() {
+        let docs = "Sends it.\n* msg - what to send\n";
+
+        assert_eq!(
+            args(&send_type(), &[], Some(docs)),
+            (Some(docs.to_string()), Vec::new())
+        );
+        assert_eq!(
+            args(&send_type(), &["_", "_"], Some(docs)),
+            (Some(docs.to_string()), Vec::new())
+        );
+    }
+
+    #[test]
+    fn values_have_no_arguments() {
+        let docs = "* msg - what to send\n";
+
+        assert_eq!(
+            args(&apply("Str"), &[], Some(docs)),
+            (Some(docs.to_string()), Vec::new())
+        );
+    }
+
+    #[test]
+    fn named_arguments_without_a_doc_comment() {
+        assert_eq!(
+            args(&send_type(), &["msg", "target"], None),
+            (
+                None,
+                vec![("msg".to_string(), None), ("target".to_string(), None)]
+            )
+        );
+    }
+
+    #[test]
+    fn argument_names_of_a_definition() {
+        let arena = Bump::new();
+        let names = |src: &str| closure_arg_names(&parse_expr_with(&arena, src).unwrap());
+
+        assert_eq!(names("\\msg, target -> msg"), vec!["msg", "target"]);
+        assert_eq!(names("\\{ text }, _, n -> n"), vec!["_", "_", "n"]);
+        assert_eq!(names("List.map"), Vec::<String>::new());
+    }
+}
@@ -5471,6 +5471,7 @@ fn canonicalize_and_constrain<'a>(
                 exposed_module_ids,
                 module_output.exposed_symbols.clone(),
                 parsed.header_comments,
+                parsed.src,
             );
 
             Some(docs)
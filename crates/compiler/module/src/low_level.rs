@@ -69,6 +69,9 @@ pub enum LowLevel {
     NumLt,
     NumLte,
     NumCompare,
+    /// Like NumCompare, but floats are compared by IEEE 754 totalOrder, so that they can be
+    /// sorted: -NaN < -Infinity < ... < -0.0 < 0.0 < ... < Infinity < NaN
+    NumCompareTotal,
     NumDivFrac,
     NumDivTruncUnchecked,
     NumDivCeilUnchecked,
@@ -294,6 +297,7 @@ map_symbol_to_lowlevel! {
     NumLt <= NUM_LT,
     NumLte <= NUM_LTE,
     NumCompare <= NUM_COMPARE,
+    NumCompareTotal <= NUM_COMPARE_TOTAL,
     NumDivFrac <= NUM_DIV_FRAC,
     NumDivCeilUnchecked <= NUM_DIV_CEIL,
     NumDivTruncUnchecked <= NUM_DIV_TRUNC,
@@ -1254,6 +1254,7 @@ define_builtins! {
         153 NUM_COUNT_TRAILING_ZERO_BITS: "countTrailingZeroBits"
         154 NUM_COUNT_ONE_BITS: "countOneBits"
         155 NUM_ABS_DIFF: "absDiff"
+        156 NUM_COMPARE_TOTAL: "compareTotal"
     }
     4 BOOL: "Bool" => {
         0 BOOL_BOOL: "Bool" exposed_type=true // the Bool.Bool type alias
@@ -985,10 +985,12 @@ pub fn lowlevel_borrow_signature(arena: &Bump, op: LowLevel) -> &[Ownership] {
 
         And | Or | NumAdd | NumAddWrap | NumAddChecked | NumAddSaturated | NumSub | NumSubWrap
         | NumSubChecked | NumSubSaturated | NumMul | NumMulWrap | NumMulSaturated
-        | NumMulChecked | NumGt | NumGte | NumLt | NumLte | NumCompare | NumDivFrac
-        | NumDivTruncUnchecked | NumDivCeilUnchecked | NumRemUnchecked | NumIsMultipleOf
-        | NumPow | NumPowInt | NumBitwiseAnd | NumBitwiseXor | NumBitwiseOr | NumShiftLeftBy
-        | NumShiftRightBy | NumShiftRightZfBy => arena.alloc_slice_copy(&[irrelevant, irrelevant]),
+        | NumMulChecked | NumGt | NumGte | NumLt | NumLte | NumCompare | NumCompareTotal
+        | NumDivFrac | NumDivTruncUnchecked | NumDivCeilUnchecked | NumRemUnchecked
+        | NumIsMultipleOf | NumPow | NumPowInt | NumBitwiseAnd | NumBitwiseXor | NumBitwiseOr
+        | NumShiftLeftBy | NumShiftRightBy | NumShiftRightZfBy => {
+            arena.alloc_slice_copy(&[irrelevant, irrelevant])
+        }
 
         NumToStr
         | NumAbs
@@ -17,6 +17,7 @@ pub mod layout;
 pub mod layout_soa;
 pub mod low_level;
 pub mod reset_reuse;
+pub mod simplify;
 pub mod tail_recursion;
 
 pub mod debug;
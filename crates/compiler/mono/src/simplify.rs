@@ -0,0 +1,774 @@
+//! A cheap simplification pass over the mono IR, which the dev and wasm backends run on each
+//! procedure before code generation. It does three things:
+//! - folds arithmetic, comparisons and boolean logic on constants of known layout
+//! - replaces a `switch` on a constant with the branch that would be taken
+//! - removes `let` bindings that are never used and have no side effects
+//!
+//! Anything that could crash at runtime (overflow, division by zero) is left alone,
+//! so that the program still crashes in the same way.
+use crate::ir::{BranchInfo, Call, CallType, Expr, ListLiteralElement, Literal, Proc, Stmt};
+use crate::layout::{Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner};
+use bumpalo::{collections::Vec, Bump};
+use roc_builtins::bitcode::{FloatWidth, IntWidth};
+use roc_collections::all::{MutMap, MutSet};
+use roc_module::low_level::LowLevel;
+use roc_module::symbol::Symbol;
+use std::fmt;
+
+/// Counts of the simplifications made, for debugging
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SimplifyStats {
+    pub folded_constants: usize,
+    pub simplified_switches: usize,
+    pub removed_lets: usize,
+}
+
+impl fmt::Display for SimplifyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "folded {} constants, simplified {} switches, removed {} unused lets",
+            self.folded_constants, self.simplified_switches, self.removed_lets
+        )
+    }
+}
+
+pub fn simplify_proc<'a>(
+    arena: &'a Bump,
+    layout_interner: &STLayoutInterner<'a>,
+    proc: Proc<'a>,
+    stats: &mut SimplifyStats,
+) -> Proc<'a> {
+    let mut env = Env {
+        arena,
+        layout_interner,
+        constants: MutMap::default(),
+        used: MutSet::default(),
+        stats,
+    };
+
+    let folded = env.fold_stmt(&proc.body);
+    let body = env.remove_dead_lets(&folded);
+
+    Proc { body, ..proc }
+}
+
+struct Env<'a, 'r> {
+    arena: &'a Bump,
+    layout_interner: &'r STLayoutInterner<'a>,
+    /// Symbols that are known to be constant. Symbols are unique within a proc, so one map will do.
+    constants: MutMap<Symbol, (Literal<'a>, InLayout<'a>)>,
+    /// Symbols used in the statements visited so far, when walking backwards
+    used: MutSet<Symbol>,
+    stats: &'r mut SimplifyStats,
+}
+
+impl<'a, 'r> Env<'a, 'r> {
+    fn fold_stmt(&mut self, stmt: &Stmt<'a>) -> Stmt<'a> {
+        match stmt {
+            Stmt::Let(symbol, expr, layout, continuation) => {
+                let expr = match expr {
+                    Expr::Call(Call {
+                        call_type: CallType::LowLevel { op, .. },
+                        arguments,
+                    }) => match self.fold_lowlevel(*op, arguments) {
+                        Some(literal) => {
+                            self.stats.folded_constants += 1;
+                            Expr::Literal(literal)
+                        }
+                        None => expr.clone(),
+                    },
+                    _ => expr.clone(),
+                };
+
+                if let Expr::Literal(literal) = expr {
+                    self.constants.insert(*symbol, (literal, *layout));
+                }
+
+                let continuation = self.fold_stmt(continuation);
+                Stmt::Let(*symbol, expr, *layout, self.arena.alloc(continuation))
+            }
+            Stmt::Switch {
+                cond_symbol,
+                cond_layout,
+                branches,
+                default_branch,
+                ret_layout,
+            } => {
+                if let Some(value) = self.switch_value(*cond_symbol) {
+                    self.stats.simplified_switches += 1;
+
+                    let taken = branches
+                        .iter()
+                        .find(|(branch_value, _, _)| *branch_value == value)
+                        .map(|(_, _, branch)| branch)
+                        .unwrap_or(default_branch.1);
+
+                    return self.fold_stmt(taken);
+                }
+
+                let mut new_branches = Vec::with_capacity_in(branches.len(), self.arena);
+                for (value, info, branch) in branches.iter() {
+                    new_branches.push((*value, info.clone(), self.fold_stmt(branch)));
+                }
+                let default_stmt = self.fold_stmt(default_branch.1);
+
+                Stmt::Switch {
+                    cond_symbol: *cond_symbol,
+                    cond_layout: *cond_layout,
+                    branches: new_branches.into_bump_slice(),
+                    default_branch: (default_branch.0.clone(), self.arena.alloc(default_stmt)),
+                    ret_layout: *ret_layout,
+                }
+            }
+            Stmt::Refcounting(modify, continuation) => {
+                let continuation = self.fold_stmt(continuation);
+                Stmt::Refcounting(*modify, self.arena.alloc(continuation))
+            }
+            Stmt::Expect {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => {
+                let remainder = self.fold_stmt(remainder);
+                Stmt::Expect {
+                    condition: *condition,
+                    region: *region,
+                    lookups: *lookups,
+                    variables: *variables,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::ExpectFx {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => {
+                let remainder = self.fold_stmt(remainder);
+                Stmt::ExpectFx {
+                    condition: *condition,
+                    region: *region,
+                    lookups: *lookups,
+                    variables: *variables,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Dbg {
+                symbol,
+                variable,
+                remainder,
+            } => {
+                let remainder = self.fold_stmt(remainder);
+                Stmt::Dbg {
+                    symbol: *symbol,
+                    variable: *variable,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Join {
+                id,
+                parameters,
+                body,
+                remainder,
+            } => {
+                let body = self.fold_stmt(body);
+                let remainder = self.fold_stmt(remainder);
+                Stmt::Join {
+                    id: *id,
+                    parameters: *parameters,
+                    body: self.arena.alloc(body),
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Ret(_) | Stmt::Jump(_, _) | Stmt::Crash(_, _) => stmt.clone(),
+        }
+    }
+
+    /// The value a `switch` would compare against its branches, if the condition is constant
+    fn switch_value(&self, symbol: Symbol) -> Option<u64> {
+        match self.constants.get(&symbol)?.0 {
+            Literal::Bool(b) => Some(b as u64),
+            Literal::Byte(b) => Some(b as u64),
+            Literal::Int(bytes) => Some(i128::from_ne_bytes(bytes) as u64),
+            _ => None,
+        }
+    }
+
+    fn fold_lowlevel(&self, op: LowLevel, arguments: &[Symbol]) -> Option<Literal<'a>> {
+        use LowLevel::*;
+
+        let mut args = std::vec::Vec::with_capacity(arguments.len());
+        for symbol in arguments {
+            args.push(*self.constants.get(symbol)?);
+        }
+
+        match (op, args.as_slice()) {
+            (Not, [(Literal::Bool(a), _)]) => Some(Literal::Bool(!a)),
+            (And, [(Literal::Bool(a), _), (Literal::Bool(b), _)]) => Some(Literal::Bool(*a && *b)),
+            (Or, [(Literal::Bool(a), _), (Literal::Bool(b), _)]) => Some(Literal::Bool(*a || *b)),
+            (Eq, [(Literal::Bool(a), _), (Literal::Bool(b), _)]) => Some(Literal::Bool(a == b)),
+            (NotEq, [(Literal::Bool(a), _), (Literal::Bool(b), _)]) => Some(Literal::Bool(a != b)),
+
+            (_, [(Literal::Int(a), layout), (Literal::Int(b), _)]) => {
+                let width = self.int_width(*layout)?;
+                let a = i128::from_ne_bytes(*a);
+                let b = i128::from_ne_bytes(*b);
+                let checked = |result: Option<i128>| {
+                    result.filter(|&n| wrap_int(n, width) == n).map(int_literal)
+                };
+
+                match op {
+                    NumAdd => checked(a.checked_add(b)),
+                    NumSub => checked(a.checked_sub(b)),
+                    NumMul => checked(a.checked_mul(b)),
+                    NumAddWrap => Some(int_literal(wrap_int(a.wrapping_add(b), width))),
+                    NumSubWrap => Some(int_literal(wrap_int(a.wrapping_sub(b), width))),
+                    NumMulWrap => Some(int_literal(wrap_int(a.wrapping_mul(b), width))),
+                    NumBitwiseAnd => Some(int_literal(wrap_int(a & b, width))),
+                    NumBitwiseOr => Some(int_literal(wrap_int(a | b, width))),
+                    NumBitwiseXor => Some(int_literal(wrap_int(a ^ b, width))),
+                    Eq => Some(Literal::Bool(a == b)),
+                    NotEq => Some(Literal::Bool(a != b)),
+                    NumLt => Some(Literal::Bool(a < b)),
+                    NumLte => Some(Literal::Bool(a <= b)),
+                    NumGt => Some(Literal::Bool(a > b)),
+                    NumGte => Some(Literal::Bool(a >= b)),
+                    _ => None,
+                }
+            }
+            (NumNeg, [(Literal::Int(a), layout)]) => {
+                let width = self.int_width(*layout)?;
+                let negated = i128::from_ne_bytes(*a).checked_neg()?;
+
+                if width.is_signed() && wrap_int(negated, width) == negated {
+                    Some(int_literal(negated))
+                } else {
+                    None
+                }
+            }
+
+            (_, [(Literal::Float(a), layout), (Literal::Float(b), _)]) => {
+                let width = self.float_width(*layout)?;
+
+                match op {
+                    NumAdd => Some(float_literal(a + b, width)),
+                    NumSub => Some(float_literal(a - b, width)),
+                    NumMul => Some(float_literal(a * b, width)),
+                    NumDivFrac => Some(float_literal(a / b, width)),
+                    NumLt => Some(Literal::Bool(a < b)),
+                    NumLte => Some(Literal::Bool(a <= b)),
+                    NumGt => Some(Literal::Bool(a > b)),
+                    NumGte => Some(Literal::Bool(a >= b)),
+                    _ => None,
+                }
+            }
+            (NumNeg, [(Literal::Float(a), _)]) => Some(Literal::Float(-a)),
+
+            _ => None,
+        }
+    }
+
+    /// Integer widths we can fold. 128-bit integers are left alone since they don't fit in an i128 in general.
+    fn int_width(&self, layout: InLayout<'a>) -> Option<IntWidth> {
+        match self.layout_interner.get(layout) {
+            Layout::Builtin(Builtin::Int(width)) if width.stack_size() <= 8 => Some(width),
+            _ => None,
+        }
+    }
+
+    fn float_width(&self, layout: InLayout<'a>) -> Option<FloatWidth> {
+        match self.layout_interner.get(layout) {
+            Layout::Builtin(Builtin::Float(width)) => Some(width),
+            _ => None,
+        }
+    }
+
+    /// Walk backwards through the statements, removing `let`s whose symbols are never used
+    fn remove_dead_lets(&mut self, stmt: &Stmt<'a>) -> Stmt<'a> {
+        match stmt {
+            Stmt::Let(symbol, expr, layout, continuation) => {
+                let continuation = self.remove_dead_lets(continuation);
+
+                if !self.used.contains(symbol) && is_pure(expr) {
+                    self.stats.removed_lets += 1;
+                    return continuation;
+                }
+
+                self.use_expr(expr);
+                Stmt::Let(
+                    *symbol,
+                    expr.clone(),
+                    *layout,
+                    self.arena.alloc(continuation),
+                )
+            }
+            Stmt::Switch {
+                cond_symbol,
+                cond_layout,
+                branches,
+                default_branch,
+                ret_layout,
+            } => {
+                let mut new_branches = Vec::with_capacity_in(branches.len(), self.arena);
+                for (value, info, branch) in branches.iter() {
+                    self.use_branch_info(info);
+                    new_branches.push((*value, info.clone(), self.remove_dead_lets(branch)));
+                }
+                self.use_branch_info(&default_branch.0);
+                let default_stmt = self.remove_dead_lets(default_branch.1);
+                self.used.insert(*cond_symbol);
+
+                Stmt::Switch {
+                    cond_symbol: *cond_symbol,
+                    cond_layout: *cond_layout,
+                    branches: new_branches.into_bump_slice(),
+                    default_branch: (default_branch.0.clone(), self.arena.alloc(default_stmt)),
+                    ret_layout: *ret_layout,
+                }
+            }
+            Stmt::Refcounting(modify, continuation) => {
+                let continuation = self.remove_dead_lets(continuation);
+                self.used.insert(modify.get_symbol());
+                Stmt::Refcounting(*modify, self.arena.alloc(continuation))
+            }
+            Stmt::Expect {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => {
+                let remainder = self.remove_dead_lets(remainder);
+                self.used.insert(*condition);
+                self.used.extend(lookups.iter().copied());
+                Stmt::Expect {
+                    condition: *condition,
+                    region: *region,
+                    lookups: *lookups,
+                    variables: *variables,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::ExpectFx {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => {
+                let remainder = self.remove_dead_lets(remainder);
+                self.used.insert(*condition);
+                self.used.extend(lookups.iter().copied());
+                Stmt::ExpectFx {
+                    condition: *condition,
+                    region: *region,
+                    lookups: *lookups,
+                    variables: *variables,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Dbg {
+                symbol,
+                variable,
+                remainder,
+            } => {
+                let remainder = self.remove_dead_lets(remainder);
+                self.used.insert(*symbol);
+                Stmt::Dbg {
+                    symbol: *symbol,
+                    variable: *variable,
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Join {
+                id,
+                parameters,
+                body,
+                remainder,
+            } => {
+                let body = self.remove_dead_lets(body);
+                let remainder = self.remove_dead_lets(remainder);
+                Stmt::Join {
+                    id: *id,
+                    parameters: *parameters,
+                    body: self.arena.alloc(body),
+                    remainder: self.arena.alloc(remainder),
+                }
+            }
+            Stmt::Ret(symbol) | Stmt::Crash(symbol, _) => {
+                self.used.insert(*symbol);
+                stmt.clone()
+            }
+            Stmt::Jump(_, arguments) => {
+                self.used.extend(arguments.iter().copied());
+                stmt.clone()
+            }
+        }
+    }
+
+    fn use_branch_info(&mut self, info: &BranchInfo<'a>) {
+        if let BranchInfo::Constructor { scrutinee, .. } = info {
+            self.used.insert(*scrutinee);
+        }
+    }
+
+    fn use_expr(&mut self, expr: &Expr<'a>) {
+        match expr {
+            Expr::Literal(_)
+            | Expr::NullPointer
+            | Expr::EmptyArray
+            | Expr::RuntimeErrorFunction(_) => {}
+            Expr::Call(Call {
+                call_type,
+                arguments,
+            }) => {
+                self.used.extend(arguments.iter().copied());
+                if let CallType::HigherOrder(higher_order) = call_type {
+                    self.used
+                        .insert(higher_order.passed_function.captured_environment);
+                }
+            }
+            Expr::Tag { arguments, .. } | Expr::Struct(arguments) => {
+                self.used.extend(arguments.iter().copied());
+            }
+            Expr::Reuse {
+                symbol, arguments, ..
+            } => {
+                self.used.insert(*symbol);
+                self.used.extend(arguments.iter().copied());
+            }
+            Expr::StructAtIndex { structure, .. }
+            | Expr::GetTagId { structure, .. }
+            | Expr::UnionAtIndex { structure, .. } => {
+                self.used.insert(*structure);
+            }
+            Expr::Array { elems, .. } => {
+                for elem in elems.iter() {
+                    if let ListLiteralElement::Symbol(symbol) = elem {
+                        self.used.insert(*symbol);
+                    }
+                }
+            }
+            Expr::ExprBox { symbol }
+            | Expr::ExprUnbox { symbol }
+            | Expr::Reset { symbol, .. }
+            | Expr::ResetRef { symbol, .. } => {
+                self.used.insert(*symbol);
+            }
+        }
+    }
+}
+
+/// Expressions that can be removed if their result is unused.
+/// Calls are never removed, since they could crash or have effects,
+/// and neither is anything that allocates or touches refcounts.
+fn is_pure(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Literal(_)
+            | Expr::Struct(_)
+            | Expr::StructAtIndex { .. }
+            | Expr::GetTagId { .. }
+            | Expr::NullPointer
+            | Expr::EmptyArray
+    )
+}
+
+fn int_literal<'a>(value: i128) -> Literal<'a> {
+    Literal::Int(value.to_ne_bytes())
+}
+
+fn float_literal<'a>(value: f64, width: FloatWidth) -> Literal<'a> {
+    match width {
+        FloatWidth::F32 => Literal::Float(value as f32 as f64),
+        FloatWidth::F64 => Literal::Float(value),
+    }
+}
+
+/// Truncate an integer to the given width, as the machine would
+fn wrap_int(value: i128, width: IntWidth) -> i128 {
+    let unused_bits = 128 - 8 * width.stack_size();
+
+    if width.is_signed() {
+        (value << unused_bits) >> unused_bits
+    } else {
+        ((value as u128) << unused_bits >> unused_bits) as i128
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::{HostExposedLayouts, SelfRecursive, UpdateModeId};
+    use crate::layout::LambdaName;
+    use roc_target::TargetInfo;
+
+    const ARG: Symbol = Symbol::ARG_1;
+    const A: Symbol = Symbol::ARG_2;
+    const B: Symbol = Symbol::ARG_3;
+    const C: Symbol = Symbol::ARG_4;
+    const D: Symbol = Symbol::ARG_5;
+
+    /// Simplifies a proc with one I64 argument, `ARG`, and the given body
+    fn simplify<'a>(arena: &'a Bump, body: Stmt<'a>) -> (Stmt<'a>, SimplifyStats) {
+        let interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let proc = Proc {
+            name: LambdaName::no_niche(Symbol::ATTR_ATTR),
+            args: arena.alloc([(Layout::I64, ARG)]),
+            body,
+            closure_data_layout: None,
+            ret_layout: Layout::I64,
+            is_self_recursive: SelfRecursive::NotSelfRecursive,
+            host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+        };
+        let mut stats = SimplifyStats::default();
+        let proc = simplify_proc(arena, &interner, proc, &mut stats);
+
+        (proc.body, stats)
+    }
+
+    /// A chain of `let`s, ending in `end`
+    fn lets<'a>(
+        arena: &'a Bump,
+        bindings: &[(Symbol, Expr<'a>, InLayout<'a>)],
+        end: Stmt<'a>,
+    ) -> Stmt<'a> {
+        bindings
+            .iter()
+            .rev()
+            .fold(end, |continuation, (symbol, expr, layout)| {
+                Stmt::Let(*symbol, expr.clone(), *layout, arena.alloc(continuation))
+            })
+    }
+
+    fn int<'a>(value: i128) -> Expr<'a> {
+        Expr::Literal(int_literal(value))
+    }
+
+    fn low_level<'a>(arena: &'a Bump, op: LowLevel, arguments: &[Symbol]) -> Expr<'a> {
+        Expr::Call(Call {
+            call_type: CallType::LowLevel {
+                op,
+                update_mode: UpdateModeId::BACKEND_DUMMY,
+            },
+            arguments: arena.alloc_slice_copy(arguments),
+        })
+    }
+
+    /// The expression `symbol` is bound to, in a chain of `let`s
+    fn bound_expr<'s, 'a>(stmt: &'s Stmt<'a>, symbol: Symbol) -> Option<&'s Expr<'a>> {
+        match stmt {
+            Stmt::Let(s, expr, _, _) if *s == symbol => Some(expr),
+            Stmt::Let(_, _, _, continuation) => bound_expr(continuation, symbol),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn wrap_int_truncates_like_the_machine() {
+        assert_eq!(wrap_int(256, IntWidth::U8), 0);
+        assert_eq!(wrap_int(255, IntWidth::I8), -1);
+        assert_eq!(wrap_int(-1, IntWidth::U16), 0xFFFF);
+        assert_eq!(
+            wrap_int(i64::MAX as i128 + 1, IntWidth::I64),
+            i64::MIN as i128
+        );
+        assert_eq!(wrap_int(-5, IntWidth::I32), -5);
+    }
+
+    #[test]
+    fn fold_in_range() {
+        let arena = Bump::new();
+        let body = lets(
+            &arena,
+            &[
+                (A, int(100), Layout::I8),
+                (B, int(27), Layout::I8),
+                (C, low_level(&arena, LowLevel::NumAdd, &[A, B]), Layout::I8),
+            ],
+            Stmt::Ret(C),
+        );
+
+        let (body, stats) = simplify(&arena, body);
+
+        assert_eq!(stats.folded_constants, 1);
+        assert_eq!(
+            body,
+            lets(&arena, &[(C, int(127), Layout::I8)], Stmt::Ret(C))
+        );
+    }
+
+    #[test]
+    fn overflow_is_not_folded() {
+        let arena = Bump::new();
+
+        for (op, a, b, layout) in [
+            (LowLevel::NumAdd, 127, 1, Layout::I8),
+            (LowLevel::NumSub, 0, 1, Layout::U8),
+            (LowLevel::NumMul, i64::MAX as i128, 2, Layout::I64),
+        ] {
+            let body = lets(
+                &arena,
+                &[
+                    (A, int(a), layout),
+                    (B, int(b), layout),
+                    (C, low_level(&arena, op, &[A, B]), layout),
+                ],
+                Stmt::Ret(C),
+            );
+
+            let (body, stats) = simplify(&arena, body);
+
+            // The op is left to crash at runtime, and its arguments are kept for it
+            assert_eq!(stats.folded_constants, 0, "{:?}", op);
+            assert_eq!(bound_expr(&body, C), Some(&low_level(&arena, op, &[A, B])));
+            assert_eq!(bound_expr(&body, A), Some(&int(a)));
+        }
+    }
+
+    #[test]
+    fn negating_the_minimum_is_not_folded() {
+        let arena = Bump::new();
+        let body = lets(
+            &arena,
+            &[
+                (A, int(-128), Layout::I8),
+                (B, low_level(&arena, LowLevel::NumNeg, &[A]), Layout::I8),
+            ],
+            Stmt::Ret(B),
+        );
+
+        let (_, stats) = simplify(&arena, body);
+
+        assert_eq!(stats.folded_constants, 0);
+    }
+
+    #[test]
+    fn wrapping_ops_are_folded() {
+        let arena = Bump::new();
+        let body = lets(
+            &arena,
+            &[
+                (A, int(200), Layout::U8),
+                (B, int(100), Layout::U8),
+                (
+                    C,
+                    low_level(&arena, LowLevel::NumAddWrap, &[A, B]),
+                    Layout::U8,
+                ),
+            ],
+            Stmt::Ret(C),
+        );
+
+        let (body, stats) = simplify(&arena, body);
+
+        assert_eq!(stats.folded_constants, 1);
+        assert_eq!(bound_expr(&body, C), Some(&int(44)));
+    }
+
+    fn switch_on<'a>(arena: &'a Bump, cond: i128) -> Stmt<'a> {
+        lets(
+            arena,
+            &[
+                (A, int(10), Layout::I64),
+                (B, int(20), Layout::I64),
+                (C, int(cond), Layout::I64),
+            ],
+            Stmt::Switch {
+                cond_symbol: C,
+                cond_layout: Layout::I64,
+                branches: arena.alloc([(1, BranchInfo::None, Stmt::Ret(A))]),
+                default_branch: (BranchInfo::None, arena.alloc(Stmt::Ret(B))),
+                ret_layout: Layout::I64,
+            },
+        )
+    }
+
+    #[test]
+    fn constant_switch_takes_its_branch() {
+        let arena = Bump::new();
+
+        let (body, stats) = simplify(&arena, switch_on(&arena, 1));
+
+        assert_eq!(stats.simplified_switches, 1);
+        assert_eq!(
+            body,
+            lets(&arena, &[(A, int(10), Layout::I64)], Stmt::Ret(A))
+        );
+    }
+
+    #[test]
+    fn constant_switch_takes_the_default_branch() {
+        let arena = Bump::new();
+
+        let (body, stats) = simplify(&arena, switch_on(&arena, 5));
+
+        assert_eq!(stats.simplified_switches, 1);
+        assert_eq!(
+            body,
+            lets(&arena, &[(B, int(20), Layout::I64)], Stmt::Ret(B))
+        );
+    }
+
+    #[test]
+    fn switch_on_an_argument_is_kept() {
+        let arena = Bump::new();
+        let body = Stmt::Switch {
+            cond_symbol: ARG,
+            cond_layout: Layout::I64,
+            branches: arena.alloc([(1, BranchInfo::None, Stmt::Ret(ARG))]),
+            default_branch: (BranchInfo::None, arena.alloc(Stmt::Ret(ARG))),
+            ret_layout: Layout::I64,
+        };
+
+        let (simplified, stats) = simplify(&arena, body.clone());
+
+        assert_eq!(stats.simplified_switches, 0);
+        assert_eq!(simplified, body);
+    }
+
+    #[test]
+    fn dead_lets_are_removed() {
+        let arena = Bump::new();
+        let body = lets(
+            &arena,
+            &[
+                // Unused, and only used by another unused let
+                (A, int(1), Layout::I64),
+                (B, Expr::Struct(arena.alloc([A])), Layout::I64),
+                // Unused, but a call could crash, so it stays
+                (
+                    C,
+                    low_level(&arena, LowLevel::NumAdd, &[ARG, ARG]),
+                    Layout::I64,
+                ),
+                // Used
+                (D, int(2), Layout::I64),
+            ],
+            Stmt::Ret(D),
+        );
+
+        let (body, stats) = simplify(&arena, body);
+
+        assert_eq!(stats.removed_lets, 2);
+        assert_eq!(
+            body,
+            lets(
+                &arena,
+                &[
+                    (
+                        C,
+                        low_level(&arena, LowLevel::NumAdd, &[ARG, ARG]),
+                        Layout::I64
+                    ),
+                    (D, int(2), Layout::I64),
+                ],
+                Stmt::Ret(D),
+            )
+        );
+    }
+}
@@ -147,6 +147,38 @@ fn or_bool() {
     assert_evals_to!("Bool.false || Bool.false", false, bool);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn logic_on_comparisons() {
+    assert_evals_to!("(1 < 2) && !(3 == 4)", true, bool);
+    assert_evals_to!("(1 > 2) || (3 != 3)", false, bool);
+    assert_evals_to!("!(1u8 == 1u8) || (2i64 >= 2i64)", true, bool);
+    assert_evals_to!(r#"("foo" == "bar") || ("foo" != "foo")"#, false, bool);
+    assert_evals_to!(r#"("foo" == "foo") && !("foo" != "foo")"#, true, bool);
+    assert_evals_to!(
+        r#"!("a long string, that is not small" == "bar")"#,
+        true,
+        bool
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn not_keeps_its_argument() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                x = "foo" == "foo"
+                y = !x
+
+                x && !y
+                "#
+        ),
+        true,
+        bool
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn not_bool() {
@@ -190,6 +222,44 @@ fn record() {
     );
 }
 
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_record_eq() {
+    // The equality helpers start by comparing the two records' addresses
+    assert_evals_to!("{ x: 123, y: 456 } == { x: 123, y: 456 }", true, bool);
+    assert_evals_to!("{ x: 123, y: 456 } == { x: 123, y: 789 }", false, bool);
+    assert_evals_to!(
+        indoc!(
+            r#"
+                r = { x: 123, y: 456 }
+
+                r == r
+            "#
+        ),
+        true,
+        bool
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_eq() {
+    assert_evals_to!("[1, 2, 3] == [1, 2, 3]", true, bool);
+    assert_evals_to!("[1, 2, 3] == [1, 2, 4]", false, bool);
+    assert_evals_to!("[1, 2, 3] != [1, 2]", true, bool);
+    assert_evals_to!(
+        indoc!(
+            r#"
+                list = [{ x: 1, y: 2 }, { x: 3, y: 4 }]
+
+                list == list
+            "#
+        ),
+        true,
+        bool
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn unit() {
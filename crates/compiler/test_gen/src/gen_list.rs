@@ -2,7 +2,7 @@
 use crate::helpers::llvm::assert_evals_to;
 
 #[cfg(feature = "gen-dev")]
-use crate::helpers::dev::assert_evals_to;
+use crate::helpers::dev::{assert_evals_to, assert_refcounts_balanced};
 
 #[cfg(feature = "gen-wasm")]
 use crate::helpers::wasm::assert_evals_to;
@@ -704,7 +704,7 @@ fn list_drop_first() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn list_swap() {
     assert_evals_to!(
         "List.swap [] 0 1",
@@ -739,6 +739,75 @@ fn list_swap() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn list_swap_shared() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            wrapper = \shared ->
+                # This should not mutate the original
+                swapped = List.swap shared 0 2
+
+                { swapped, shared }
+
+            wrapper [1, 2, 3]
+            "#
+        ),
+        (
+            RocList::from_slice(&[3, 2, 1]),
+            RocList::from_slice(&[1, 2, 3])
+        ),
+        (RocList<i64>, RocList<i64>)
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_swap_unique_balanced() {
+    // List.repeat puts the list on the heap, so there's a refcount to check
+    assert_refcounts_balanced!(
+        indoc!(
+            r#"
+            list = List.append (List.repeat 1u64 3) 7
+
+            when List.first (List.swap list 0 3) is
+                Ok first -> first
+                Err _ -> 0
+            "#
+        ),
+        7,
+        u64
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_swap_shared_balanced() {
+    assert_refcounts_balanced!(
+        indoc!(
+            r#"
+            list = List.append (List.repeat 1u64 3) 7
+            swapped = List.swap list 0 3
+
+            swappedFirst =
+                when List.first swapped is
+                    Ok first -> first
+                    Err _ -> 0
+
+            originalFirst =
+                when List.first list is
+                    Ok first -> first
+                    Err _ -> 0
+
+            swappedFirst * 10 + originalFirst
+            "#
+        ),
+        71,
+        u64
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn list_append_to_empty_list() {
@@ -1314,6 +1383,54 @@ fn list_map_using_defined_function() {
     );
 }
 
+#[test]
+#[cfg(feature = "gen-dev")]
+fn list_map_helpers_emitted_once_per_module() {
+    use object::{Object, ObjectSymbol};
+
+    let arena = bumpalo::Bump::new();
+    let (_, _, object_bytes) = crate::helpers::dev::build_app_object(
+        &arena,
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            increment : I64 -> I64
+            increment = \i -> i + 1
+
+            main =
+                a = List.map [1, 2, 3] increment
+                b = List.map [4, 5] increment
+
+                List.len a + List.len b
+            "#
+        ),
+        false,
+        false,
+        false,
+    );
+
+    let file = object::File::parse(&*object_bytes).expect("failed to parse the app object");
+    let mut names: Vec<&str> = file
+        .symbols()
+        .filter(|symbol| symbol.is_definition())
+        .filter_map(|symbol| symbol.name().ok())
+        .collect();
+
+    // Both maps pass the same function, so they share one caller proc
+    let caller_procs = names
+        .iter()
+        .filter(|name| name.contains("#help_caller"))
+        .count();
+    assert_eq!(caller_procs, 1, "{:?}", names);
+
+    // ...and the same refcounting helpers
+    let count = names.len();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), count, "{:?}", names);
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn list_map_all_inline() {
@@ -1863,13 +1980,13 @@ fn fn_int_list_len() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn int_list_is_empty() {
     assert_evals_to!("List.isEmpty [12, 9, 6, 3]", false, bool);
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn empty_list_is_empty() {
     assert_evals_to!("List.isEmpty []", true, bool);
 }
@@ -2118,7 +2235,7 @@ fn replace_unique_get_large_value() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn replace_shared_int_list() {
     assert_evals_to!(
         indoc!(
@@ -2147,7 +2264,7 @@ fn replace_shared_int_list() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn get_set_unique_int_list_i64() {
     assert_evals_to!(
         indoc!(
@@ -2161,7 +2278,7 @@ fn get_set_unique_int_list_i64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn get_set_unique_int_list_i8() {
     assert_evals_to!(
         indoc!(
@@ -2175,7 +2292,7 @@ fn get_set_unique_int_list_i8() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn set_unique_int_list() {
     assert_evals_to!(
         "List.set [12, 9, 7, 1, 5] 2 33",
@@ -2184,6 +2301,21 @@ fn set_unique_int_list() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn set_unique_int_list_repeatedly() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            List.walk [0, 1, 2, 3] [10, 20, 30, 40] \list, index ->
+                List.set list index (index * 2)
+            "#
+        ),
+        RocList::from_slice(&[0, 2, 4, 6]),
+        RocList<i64>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn set_unique_list_oob() {
@@ -2195,7 +2327,7 @@ fn set_unique_list_oob() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn set_shared_int_list() {
     assert_evals_to!(
         indoc!(
@@ -2223,7 +2355,7 @@ fn set_shared_int_list() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn set_shared_list_oob() {
     assert_evals_to!(
         indoc!(
@@ -2249,6 +2381,35 @@ fn set_shared_list_oob() {
     );
 }
 
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_set_unique_balanced() {
+    assert_refcounts_balanced!("List.sum (List.set (List.repeat 1u64 5) 2 10)", 14, u64);
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_set_shared_balanced() {
+    assert_refcounts_balanced!(
+        indoc!(
+            r#"
+            list = List.repeat 1u64 5
+            updated = List.set list 2 10
+
+            List.sum list * 100 + List.sum updated
+            "#
+        ),
+        514,
+        u64
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_set_out_of_bounds_balanced() {
+    assert_refcounts_balanced!("List.sum (List.set (List.repeat 1u64 5) 99 10)", 5, u64);
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn get_unique_int_list() {
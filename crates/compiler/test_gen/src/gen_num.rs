@@ -1714,7 +1714,127 @@ fn float_to_float() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(feature = "gen-dev")]
+fn num_to_frac_u64_above_i64_max() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : U64
+            n = 18446744073709551615
+
+            f : F64
+            f = Num.toFrac n
+            f
+            "#
+        ),
+        18446744073709551615.0,
+        f64
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : U64
+            n = 9223372036854775809
+
+            f : F32
+            f = Num.toFrac n
+            f
+            "#
+        ),
+        9223372036854775809.0,
+        f32
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn num_to_frac_64_bit_int_to_f32_rounds_once() {
+    // Going through F64 first would round 2^63 + 2^39 + 1 down to 2^63 + 2^39, which is exactly
+    // halfway between two F32s, and then round that to the even one, 2^63.
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : U64
+            n = 0x8000_0080_0000_0001
+
+            f : F32
+            f = Num.toFrac n
+            f
+            "#
+        ),
+        0x8000_0080_0000_0001u64 as f32,
+        f32
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : I64
+            n = -4611686293305294849
+
+            f : F32
+            f = Num.toFrac n
+            f
+            "#
+        ),
+        -0x4000_0040_0000_0001i64 as f32,
+        f32
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn num_to_frac_u128() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : U128
+            n = 340282366920938463463374607431768211455
+
+            f : F64
+            f = Num.toFrac n
+            f
+            "#
+        ),
+        340282366920938463463374607431768211455.0,
+        f64
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn num_to_frac_dec() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : Dec
+            n = -12.5
+
+            f : F64
+            f = Num.toFrac n
+            f
+            "#
+        ),
+        -12.5,
+        f64
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn int_cast_of_u64() {
+    assert_evals_to!("Num.toU32 0xFFFF_FFFF_0000_0001u64", 1, u32);
+    assert_evals_to!("Num.toI8 0xFFu64", -1, i8);
+    assert_evals_to!(
+        "Num.toU128 0xFFFF_FFFF_FFFF_FFFFu64",
+        0xFFFF_FFFF_FFFF_FFFF,
+        u128
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn int_compare() {
     assert_evals_to!("Num.compare 0 1", RocOrder::Lt, RocOrder);
     assert_evals_to!("Num.compare 1 1", RocOrder::Eq, RocOrder);
@@ -1730,27 +1850,201 @@ fn float_compare() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn unsigned_int_compare() {
+    assert_evals_to!("Num.compare 1u8 255u8", RocOrder::Lt, RocOrder);
+    assert_evals_to!("Num.compare 0xFFFF_FFFFu32 1u32", RocOrder::Gt, RocOrder);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn f64_compare() {
+    assert_evals_to!("Num.compare 0.01f64 3.14f64", RocOrder::Lt, RocOrder);
+    assert_evals_to!("Num.compare 3.14f64 3.14f64", RocOrder::Eq, RocOrder);
+    assert_evals_to!("Num.compare 3.14f64 0.01f64", RocOrder::Gt, RocOrder);
+    assert_evals_to!("Num.compare 1.0f32 2.0f32", RocOrder::Lt, RocOrder);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn float_compare_signed_zero() {
+    // IEEE comparisons don't tell the zeros apart
+    assert_evals_to!("Num.compare -0.0f64 0.0f64", RocOrder::Eq, RocOrder);
+    assert_evals_to!("Num.compare 0.0f32 -0.0f32", RocOrder::Eq, RocOrder);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn float_compare_nan() {
+    // Comparisons with NaN are unordered, which comes out as GT on either side
+    assert_evals_to!(
+        indoc!(
+            r#"
+            nan = 0.0f64 / 0.0f64
+
+            Num.compare nan 1.0f64
+            "#
+        ),
+        RocOrder::Gt,
+        RocOrder
+    );
+    assert_evals_to!(
+        indoc!(
+            r#"
+            nan = 0.0f64 / 0.0f64
+
+            Num.compare 1.0f64 nan
+            "#
+        ),
+        RocOrder::Gt,
+        RocOrder
+    );
+    assert_evals_to!(
+        indoc!(
+            r#"
+            nan = 0.0f64 / 0.0f64
+
+            Num.compare nan nan
+            "#
+        ),
+        RocOrder::Gt,
+        RocOrder
+    );
+}
+
+/// Checks that `Num.compareTotal` puts -inf < -0.0 < 0.0 < inf, for floats with the given literal
+/// suffix. Dividing zero by zero gives a NaN whose sign depends on the platform, so NaN is only
+/// checked to be equal to itself, and beyond both infinities on the same side.
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn assert_compare_total_in_order(suffix: &str) {
+    let values = ["ninf", "-0.0", "0.0", "inf"];
+    let compare = |a: &str, b: &str| {
+        format!(
+            indoc!(
+                r#"
+                inf = 1.0{suffix} / 0.0{suffix}
+                ninf = -1.0{suffix} / 0.0{suffix}
+                nan = 0.0{suffix} / 0.0{suffix}
+                x : F{bits}
+                x = {a}
+                y : F{bits}
+                y = {b}
+
+                Num.compareTotal x y
+                "#
+            ),
+            suffix = suffix,
+            bits = &suffix[1..],
+            a = a,
+            b = b,
+        )
+    };
+
+    for pair in values.windows(2) {
+        assert_evals_to!(&compare(pair[0], pair[1]), RocOrder::Lt, RocOrder);
+        assert_evals_to!(&compare(pair[1], pair[0]), RocOrder::Gt, RocOrder);
+    }
+
+    for value in values.iter().chain(&["nan"]) {
+        assert_evals_to!(&compare(value, value), RocOrder::Eq, RocOrder);
+    }
+
+    assert_evals_to!(
+        &format!(
+            indoc!(
+                r#"
+                inf = 1.0{suffix} / 0.0{suffix}
+                ninf = -1.0{suffix} / 0.0{suffix}
+                nan = 0.0{suffix} / 0.0{suffix}
+
+                Num.compareTotal nan inf == Num.compareTotal nan ninf
+                "#
+            ),
+            suffix = suffix,
+        ),
+        true,
+        bool
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn compare_total_f64() {
+    assert_compare_total_in_order("f64");
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn compare_total_f32() {
+    assert_compare_total_in_order("f32");
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn compare_total_dec() {
+    assert_evals_to!("Num.compareTotal -1.5dec 0.25dec", RocOrder::Lt, RocOrder);
+    assert_evals_to!("Num.compareTotal 0.25dec 0.25dec", RocOrder::Eq, RocOrder);
+    assert_evals_to!("Num.compareTotal 0.25dec -1.5dec", RocOrder::Gt, RocOrder);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn int_128_compare() {
+    // The high halves are equal, so the low halves decide
+    assert_evals_to!("Num.compare -2i128 -1i128", RocOrder::Lt, RocOrder);
+    assert_evals_to!(
+        "Num.compare 0x1_0000_0000_0000_0000i128 1i128",
+        RocOrder::Gt,
+        RocOrder
+    );
+    assert_evals_to!("Num.compare -1i128 1i128", RocOrder::Lt, RocOrder);
+    assert_evals_to!("Num.compare 1i128 1i128", RocOrder::Eq, RocOrder);
+    assert_evals_to!(
+        "Num.compare 0xFFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFFu128 1u128",
+        RocOrder::Gt,
+        RocOrder
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn pow() {
     assert_evals_to!("Num.pow 2.0 2.0", 4.0, f64);
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn pow_f32() {
+    assert_evals_to!("Num.pow 2.0f32 3.0", 8.0, f32);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn ceiling() {
-    assert_evals_to!("Num.ceiling 1.1", 2, i64);
+    assert_evals_to!("Num.ceiling 1.1f64", 2, i64);
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn floor() {
-    assert_evals_to!("Num.floor 1.9", 1, i64);
+    assert_evals_to!("Num.floor 1.9f64", 1, i64);
 }
 
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn pow_int() {
     assert_evals_to!("Num.powInt 2 3", 8, i64);
+    assert_evals_to!("Num.powInt 3u8 4", 81, u8);
+    assert_evals_to!("Num.powInt -2i32 3", -8, i32);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn div_ceil() {
+    assert_evals_to!("Num.divCeil 7 2", 4, i64);
+    assert_evals_to!("Num.divCeil 6 3", 2, i64);
+    assert_evals_to!("Num.divCeil -7 2", -3, i64);
+    assert_evals_to!("Num.divCeil 7u8 2", 4, u8);
 }
 
 #[test]
@@ -2056,6 +2350,72 @@ fn shift_left_by() {
     assert_evals_to!("Num.shiftLeftBy 2u16 2", 8, u16);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn shift_by_variable_amount() {
+    // The shift amounts aren't literals here, so they have to be in registers
+    assert_evals_to!(
+        indoc!(
+            r#"
+            shift = \n -> Num.shiftLeftBy 3 n
+
+            shift 4
+            "#
+        ),
+        48,
+        i64
+    );
+    assert_evals_to!(
+        indoc!(
+            r#"
+            shift = \n -> Num.shiftRightBy -64i32 n
+
+            shift 2
+            "#
+        ),
+        -16,
+        i32
+    );
+    assert_evals_to!(
+        indoc!(
+            r#"
+            shift = \n -> Num.shiftRightZfBy 64u8 n
+
+            shift 3
+            "#
+        ),
+        8,
+        u8
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn add_literal() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            addTen = \n -> n + 10
+
+            addTen 32
+            "#
+        ),
+        42,
+        i64
+    );
+    assert_evals_to!(
+        indoc!(
+            r#"
+            addTen = \n -> Num.addWrap n 10
+
+            addTen 127i8
+            "#
+        ),
+        -119,
+        i8
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn shift_right_by() {
@@ -3345,7 +3705,7 @@ fn num_to_str_f64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn num_to_str_dec() {
     use roc_std::RocStr;
 
@@ -3672,7 +4032,7 @@ fn monomorphized_ints_aliased() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn to_float_f32() {
     assert_evals_to!(
         indoc!(
@@ -3691,7 +4051,7 @@ fn to_float_f32() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn to_float_f64() {
     assert_evals_to!(
         indoc!(
@@ -3710,7 +4070,7 @@ fn to_float_f64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 // https://github.com/roc-lang/roc/issues/2696
 fn upcast_of_int_is_zext() {
     assert_evals_to!(
@@ -3770,7 +4130,7 @@ fn div_of_unsigned() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn dec_float_suffix() {
     assert_evals_to!(
         indoc!(
@@ -3784,7 +4144,7 @@ fn dec_float_suffix() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn dec_no_decimal() {
     assert_evals_to!(
         indoc!(
@@ -3798,13 +4158,13 @@ fn dec_no_decimal() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn ceiling_to_u32() {
     assert_evals_to!(
         indoc!(
             r#"
             n : U32
-            n = Num.ceiling 124.5
+            n = Num.ceiling 124.5f64
             n
             "#
         ),
@@ -3814,13 +4174,13 @@ fn ceiling_to_u32() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn floor_to_u32() {
     assert_evals_to!(
         indoc!(
             r#"
             n : U32
-            n = Num.floor 124.5
+            n = Num.floor 124.5f64
             n
             "#
         ),
@@ -3830,13 +4190,13 @@ fn floor_to_u32() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn round_to_u32() {
     assert_evals_to!(
         indoc!(
             r#"
             n : U32
-            n = Num.round 124.49
+            n = Num.round 124.49f64
             n
             "#
         ),
@@ -3845,6 +4205,68 @@ fn round_to_u32() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn round_f32_to_i8() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            x : F32
+            x = -2.5
+
+            n : I8
+            n = Num.round x
+            n
+            "#
+        ),
+        -3,
+        i8
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn round_dec() {
+    assert_evals_to!("Num.round 2.5dec", 3, i64);
+    assert_evals_to!("Num.round -2.5dec", -3, i64);
+    assert_evals_to!("Num.round 2.49dec", 2, i64);
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : U8
+            n = Num.round 254.7dec
+            n
+            "#
+        ),
+        255,
+        u8
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn floor_and_ceiling_dec() {
+    assert_evals_to!("Num.floor 1.5dec", 1, i64);
+    assert_evals_to!("Num.floor -1.5dec", -2, i64);
+    assert_evals_to!("Num.floor -3dec", -3, i64);
+    assert_evals_to!("Num.ceiling 1.5dec", 2, i64);
+    assert_evals_to!("Num.ceiling -1.5dec", -1, i64);
+    assert_evals_to!("Num.ceiling 3dec", 3, i64);
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n : I128
+            n = Num.ceiling 170141183460469.000000000000000001dec
+            n
+            "#
+        ),
+        170141183460470,
+        i128
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn promote_u64_number_layout() {
@@ -3973,7 +4395,7 @@ fn condition_polymorphic_num_becomes_float() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn num_count_leading_zero_bits() {
     assert_evals_to!(r#"Num.countLeadingZeroBits 0b0010_1000u8"#, 2, usize);
     assert_evals_to!(r#"Num.countLeadingZeroBits 0b0010_1000u16"#, 10, usize);
@@ -3982,7 +4404,7 @@ fn num_count_leading_zero_bits() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn num_count_trailing_zero_bits() {
     assert_evals_to!(r#"Num.countTrailingZeroBits 0b0010_1000u8"#, 3, usize);
     assert_evals_to!(r#"Num.countTrailingZeroBits 0b0010_0000u16"#, 5, usize);
@@ -3991,7 +4413,7 @@ fn num_count_trailing_zero_bits() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn num_count_one_bits() {
     assert_evals_to!(r#"Num.countOneBits 0b0010_1000u8"#, 2, usize);
     assert_evals_to!(r#"Num.countOneBits 0b0010_0000u16"#, 1, usize);
@@ -3999,6 +4421,15 @@ fn num_count_one_bits() {
     assert_evals_to!(r#"Num.countOneBits 0b0010_1111u64"#, 5, usize);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn num_count_bits_of_negative_ints() {
+    assert_evals_to!(r#"Num.countLeadingZeroBits -1i8"#, 0, usize);
+    assert_evals_to!(r#"Num.countTrailingZeroBits -128i8"#, 7, usize);
+    assert_evals_to!(r#"Num.countOneBits -1i16"#, 16, usize);
+    assert_evals_to!(r#"Num.countOneBits -1i64"#, 64, usize);
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn num_abs_diff_int() {
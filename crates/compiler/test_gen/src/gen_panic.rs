@@ -1,4 +1,5 @@
 use indoc::indoc;
+#[allow(unused_imports)]
 use roc_std::RocList;
 
 #[cfg(feature = "gen-llvm")]
@@ -7,6 +8,9 @@ use crate::helpers::llvm::assert_evals_to;
 #[cfg(feature = "gen-wasm")]
 use crate::helpers::wasm::assert_evals_to;
 
+#[cfg(all(feature = "gen-dev", unix))]
+use crate::helpers::dev::{assert_crashes, assert_evals_to};
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 #[should_panic = r#"User crash with message: "hello crash""#]
@@ -83,3 +87,137 @@ fn crash_in_passed_closure() {
         RocList<u8>
     );
 }
+
+// The dev backend puts crashing code after the return of its proc, so these check that the jumps
+// to it are taken exactly when they should be. The input comes from a list's length so that the
+// switches and checks can't be worked out while compiling.
+
+#[test]
+#[cfg(all(feature = "gen-dev", unix))]
+fn dev_crash_in_when_branch() {
+    let source = |n: u64| {
+        format!(
+            indoc!(
+                r#"
+                app "test" provides [main] to "./platform"
+
+                main =
+                    when List.len (List.repeat 0u8 {}) is
+                        1 -> 10u8
+                        2 -> crash "two"
+                        _ -> 30u8
+                "#
+            ),
+            n
+        )
+    };
+
+    assert_evals_to!(&source(1), 10u8, u8);
+    assert_evals_to!(&source(3), 30u8, u8);
+    assert_crashes!(&source(2), u8);
+}
+
+#[test]
+#[cfg(all(feature = "gen-dev", unix))]
+fn dev_crash_in_default_branch() {
+    // The last branch is built inline, so its jump over the others goes to the crash instead
+    let source = |n: u64| {
+        format!(
+            indoc!(
+                r#"
+                app "test" provides [main] to "./platform"
+
+                main =
+                    when List.len (List.repeat 0u8 {}) is
+                        1 -> 10u8
+                        2 -> 20u8
+                        _ -> crash "neither"
+                "#
+            ),
+            n
+        )
+    };
+
+    assert_evals_to!(&source(1), 10u8, u8);
+    assert_evals_to!(&source(2), 20u8, u8);
+    assert_crashes!(&source(3), u8);
+}
+
+#[test]
+#[cfg(all(feature = "gen-dev", unix))]
+fn dev_crash_in_default_branch_after_crashing_branch() {
+    let source = |n: u64| {
+        format!(
+            indoc!(
+                r#"
+                app "test" provides [main] to "./platform"
+
+                main =
+                    when List.len (List.repeat 0u8 {}) is
+                        1 -> 10u8
+                        2 -> crash "two"
+                        _ -> crash "neither"
+                "#
+            ),
+            n
+        )
+    };
+
+    assert_evals_to!(&source(1), 10u8, u8);
+    assert_crashes!(&source(2), u8);
+    assert_crashes!(&source(3), u8);
+}
+
+#[test]
+#[cfg(all(feature = "gen-dev", unix))]
+fn dev_crash_on_overflow() {
+    let source = |n: u64, expr: &str, ty: &str| {
+        format!(
+            indoc!(
+                r#"
+                app "test" provides [main] to "./platform"
+
+                main : {}
+                main =
+                    n = Num.to{} (List.len (List.repeat 0u8 {}))
+                    {}
+                "#
+            ),
+            ty, ty, n, expr
+        )
+    };
+
+    assert_evals_to!(&source(200, "n + 55", "U8"), 255u8, u8);
+    assert_crashes!(&source(200, "n + 56", "U8"), u8);
+    assert_evals_to!(&source(28, "-100 - n", "I8"), -128i8, i8);
+    assert_crashes!(&source(29, "-100 - n", "I8"), i8);
+    assert_evals_to!(&source(3, "n - 3", "U64"), 0u64, u64);
+    assert_crashes!(&source(2, "n - 3", "U64"), u64);
+    assert_evals_to!(&source(2, "Num.maxI64 - 2 + n", "I64"), i64::MAX, i64);
+    assert_crashes!(&source(3, "Num.maxI64 - 2 + n", "I64"), i64);
+    assert_evals_to!(&source(2, "Num.minI64 + 2 - n", "I64"), i64::MIN, i64);
+    assert_crashes!(&source(3, "Num.minI64 + 2 - n", "I64"), i64);
+}
+
+#[test]
+#[cfg(all(feature = "gen-dev", unix))]
+fn dev_crash_on_division_by_zero() {
+    let source = |n: u64, ty: &str| {
+        format!(
+            indoc!(
+                r#"
+                app "test" provides [main] to "./platform"
+
+                main : {}
+                main = 100 // Num.to{} (List.len (List.repeat 0u8 {}))
+                "#
+            ),
+            ty, ty, n
+        )
+    };
+
+    assert_evals_to!(&source(4, "U32"), 25u32, u32);
+    assert_crashes!(&source(0, "U32"), u32);
+    assert_evals_to!(&source(4, "I64"), 25i64, i64);
+    assert_crashes!(&source(0, "I64"), i64);
+}
@@ -9,7 +9,7 @@ use crate::helpers::wasm::assert_evals_to;
 
 use indoc::indoc;
 #[allow(unused_imports)]
-use roc_std::{RocBox, RocList, RocStr};
+use roc_std::{RocBox, RocDec, RocList, RocStr};
 
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
@@ -597,7 +597,7 @@ fn top_level_destructure() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn linked_list_len_0() {
     assert_evals_to!(
         indoc!(
@@ -653,7 +653,7 @@ fn linked_list_len_twice_0() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn linked_list_len_1() {
     assert_evals_to!(
         indoc!(
@@ -709,7 +709,7 @@ fn linked_list_len_twice_1() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn linked_list_len_3() {
     assert_evals_to!(
         indoc!(
@@ -738,7 +738,7 @@ fn linked_list_len_3() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn linked_list_sum_num_a() {
     assert_evals_to!(
         indoc!(
@@ -767,7 +767,7 @@ fn linked_list_sum_num_a() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn linked_list_sum_int() {
     assert_evals_to!(
         indoc!(
@@ -1279,7 +1279,7 @@ fn linked_list_is_singleton() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn linked_list_is_empty_1() {
     assert_evals_to!(
         indoc!(
@@ -3348,7 +3348,69 @@ fn box_and_unbox_f32() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_and_unbox_signed_ints() {
+    assert_evals_to!("Box.unbox (Box.box (-123i64))", -123, i64);
+    assert_evals_to!("Box.unbox (Box.box (-123i32))", -123, i32);
+    assert_evals_to!("Box.unbox (Box.box (-123i16))", -123, i16);
+    assert_evals_to!("Box.unbox (Box.box (-123i8))", -123, i8);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_and_unbox_128_bit_ints() {
+    assert_evals_to!("Box.unbox (Box.box Num.maxI128)", i128::MAX, i128);
+    assert_evals_to!("Box.unbox (Box.box Num.maxU128)", u128::MAX, u128);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_and_unbox_dec() {
+    assert_evals_to!(
+        "Box.unbox (Box.box -10.75dec)",
+        RocDec::from_str_to_i128_unsafe("-10.75"),
+        i128
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_and_unbox_f32_in_record() {
+    assert_evals_to!(
+        "Box.unbox (Box.box { a: 1.5f32, b: 42u32 })",
+        (1.5, 42),
+        (f32, u32)
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_and_unbox_odd_sized_record() {
+    assert_evals_to!(
+        "Box.unbox (Box.box { a: 1u8, b: 2u8, c: 3u8 })",
+        (1, 2, 3),
+        (u8, u8, u8)
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_and_unbox_box() {
+    assert_evals_to!("Box.unbox (Box.unbox (Box.box (Box.box 123u64)))", 123, u64)
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_and_unbox_list() {
+    assert_evals_to!(
+        "Box.unbox (Box.box [1u8, 2u8, 3u8])",
+        RocList::from_slice(&[1, 2, 3]),
+        RocList<u8>
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn box_and_unbox_record() {
     assert_evals_to!(
         indoc!(
@@ -3362,7 +3424,7 @@ fn box_and_unbox_record() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn box_and_unbox_tag_union() {
     assert_evals_to!(
         indoc!(
@@ -3378,6 +3440,123 @@ fn box_and_unbox_tag_union() {
     )
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_in_tag_union() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            v : [A (Box U64), B]
+            v = A (Box.box 42u64)
+
+            when v is
+                A b -> Box.unbox b
+                B -> 0
+            "#
+        ),
+        42,
+        u64
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_next_to_other_fields_in_tag_union() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            v : [A U8 (Box U64) U16, B]
+            v = A 1u8 (Box.box 40u64) 2u16
+
+            when v is
+                A x b y -> Num.toU64 x + Box.unbox b + Num.toU64 y
+                B -> 0
+            "#
+        ),
+        43,
+        u64
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn box_in_record() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            r = { a: Box.box 15u64, b: 27u8 }
+
+            Box.unbox r.a + Num.toU64 r.b
+            "#
+        ),
+        42,
+        u64
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn record_of_only_a_box_in_tag_union() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            v : [Wrapped { inner : Box U32 }, Empty]
+            v = Wrapped { inner: Box.box 42u32 }
+
+            when v is
+                Wrapped { inner } -> Box.unbox inner
+                Empty -> 0
+            "#
+        ),
+        42,
+        u32
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn boxed_tag_union_in_tag_union() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            inner : [X U8, Y U8]
+            inner = Y 27u8
+
+            v : [A (Box [X U8, Y U8]), B]
+            v = A (Box.box inner)
+
+            when v is
+                A b ->
+                    when Box.unbox b is
+                        X x -> x
+                        Y y -> y + 1
+                B -> 0
+            "#
+        ),
+        28,
+        u8
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn nested_box_in_tag_union() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            v : [A (Box (Box U64)), B]
+            v = A (Box.box (Box.box 42u64))
+
+            when v is
+                A b -> Box.unbox (Box.unbox b)
+                B -> 0
+            "#
+        ),
+        42,
+        u64
+    )
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn closure_called_in_its_defining_scope() {
@@ -4480,3 +4659,90 @@ fn pass_lambda_set_to_function() {
         i64
     );
 }
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn chain_of_small_wrappers() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            add : I64, I64 -> I64
+            add = \a, b -> a + b
+
+            addTwice = \a, b -> add (add a b) b
+
+            double = \x -> addTwice x 0 + x
+
+            pair = \x -> { first: double x, second: x }
+
+            main = (pair 5).first + (pair 1).second
+            "#
+        ),
+        11,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn small_wrapper_of_refcounted_value() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            greet = \name -> Str.concat "Hello, " name
+
+            twice = \name -> Str.concat (greet name) "!"
+
+            main = twice "World"
+            "#
+        ),
+        RocStr::from("Hello, World!"),
+        RocStr
+    );
+}
+
+/// What calls to the Roc proc go to after dev_redirect_patchable_entry redirects it
+#[cfg(all(feature = "gen-dev", unix, target_arch = "x86_64"))]
+extern "C" fn redirected_main() -> i64 {
+    42
+}
+
+#[test]
+#[cfg(all(feature = "gen-dev", unix, target_arch = "x86_64"))]
+fn dev_redirect_patchable_entry() {
+    use crate::helpers::dev::{helper, redirect_proc};
+
+    let arena = bumpalo::Bump::new();
+    let (main_fn_name, errors, lib) = helper(
+        &arena,
+        indoc!(
+            r#"
+            double = \x -> x * 2
+
+            double 5 + 1
+            "#
+        ),
+        true,
+        true,
+        false,
+        true,
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let call_main = || unsafe {
+        let main: libloading::Symbol<unsafe extern "C" fn() -> i64> =
+            lib.get(main_fn_name.as_bytes()).unwrap();
+        main()
+    };
+
+    // Until it's redirected, the entry jumps straight to the proc's own code
+    assert_eq!(call_main(), 11);
+
+    redirect_proc(&lib, &main_fn_name, redirected_main as usize as u64);
+
+    assert_eq!(call_main(), 42);
+}
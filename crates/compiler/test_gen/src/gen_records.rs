@@ -87,6 +87,35 @@ fn f64_record() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn f32_record() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                   rec = { y: 17.25f32, x: 15.5f32, z: 19i64 }
+
+                   rec.x + rec.y
+                "#
+        ),
+        32.75,
+        f32
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+                   x = 1.5f32
+                   rec = { x, y: 2.25f32 }
+
+                   rec.x + rec.y
+                "#
+        ),
+        3.75,
+        f32
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn fn_record() {
@@ -712,7 +741,48 @@ fn return_record_7() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn pass_and_return_record_larger_than_64_bytes() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            swap = \r -> { r & a: r.i, i: r.a }
+
+            main =
+                swap { a: 1, b: 2, c: 3, d: 4, e: 5, f: 6, g: 7, h: 8, i: 9 }
+            "#
+        ),
+        [9, 2, 3, 4, 5, 6, 7, 8, 1],
+        [i64; 9]
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn stack_frame_larger_than_a_page() {
+    // 4^5 I64s make an 8KiB record, so main's stack frame has to be probed page by page
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            quad = \x -> { a: x, b: x, c: x, d: x }
+
+            main =
+                big = quad (quad (quad (quad (quad 21i64))))
+
+                big.a.b.c.d.a + big.d.c.b.a.d
+            "#
+        ),
+        42,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn return_record_float_int() {
     assert_evals_to!(
         indoc!(
@@ -726,7 +796,7 @@ fn return_record_float_int() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn return_record_int_float() {
     assert_evals_to!(
         indoc!(
@@ -740,7 +810,7 @@ fn return_record_int_float() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn return_record_float_float() {
     assert_evals_to!(
         indoc!(
@@ -753,6 +823,106 @@ fn return_record_float_float() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn pass_small_records_with_floats() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            sum : { x : F64, y : F64 }, { a : I64, b : F64 } -> F64
+            sum = \p, q -> p.x + p.y + Num.toFrac q.a + q.b
+
+            main = sum { x: 1.5, y: 2.25 } { a: 3, b: 0.25 }
+            "#
+        ),
+        7.0,
+        f64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn pass_small_records_past_float_registers() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            Point : { x : F64, y : F64 }
+
+            sum : Point, Point, Point, Point, Point -> F64
+            sum = \p, q, r, s, t -> p.x + q.x + r.x + s.x + t.x + t.y
+
+            main = sum { x: 1, y: 0 } { x: 2, y: 0 } { x: 3, y: 0 } { x: 4, y: 0 } { x: 5, y: 6 }
+            "#
+        ),
+        21.0,
+        f64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn pass_unit_args_between_ints_and_floats() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : {}, I64, {}, F64, {}, I64 -> F64
+            f = \_, a, _, b, _, c -> Num.toFrac a + b + Num.toFrac c
+
+            main = f {} 3 {} 0.5 {} 4
+            "#
+        ),
+        7.5,
+        f64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn pass_unit_args_past_general_registers() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : I64, {}, I64, I64, {}, I64, I64, I64, {}, I64, I64 -> I64
+            f = \a, _, b, c, _, d, e, g, _, h, i -> a + b + c + d + e + g + h + i
+
+            main = f 1 {} 2 3 {} 4 5 6 {} 7 8
+            "#
+        ),
+        36,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn pass_returned_unit_as_arg() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            ignore : I64 -> {}
+            ignore = \_ -> {}
+
+            f : {}, I64 -> I64
+            f = \_, a -> a + 1
+
+            main = f (ignore 1) 41
+            "#
+        ),
+        42,
+        i64
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn return_record_float_float_float() {
@@ -1,6 +1,9 @@
 #[cfg(feature = "gen-wasm")]
 use crate::helpers::{wasm::assert_refcounts, RefCount::*};
 
+#[cfg(feature = "gen-dev")]
+use crate::helpers::dev::assert_refcounts_balanced;
+
 #[allow(unused_imports)]
 use indoc::indoc;
 
@@ -571,3 +574,70 @@ fn reset_reuse_alignment_8() {
         ]
     );
 }
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_str_balanced() {
+    assert_refcounts_balanced!(
+        indoc!(
+            r#"
+                s = Str.concat "A long enough string " "to be heap-allocated"
+
+                Str.isEmpty s
+            "#
+        ),
+        false,
+        bool
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_of_lists_balanced() {
+    assert_refcounts_balanced!(
+        indoc!(
+            r#"
+                list = [0x111, 0x222, 0x333]
+
+                List.len [list, list, list]
+            "#
+        ),
+        3,
+        usize
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_list_of_str_balanced() {
+    // Freeing the list decrements each element, through a pointer cast from the list's elements
+    assert_refcounts_balanced!(
+        indoc!(
+            r#"
+                s = Str.concat "A long enough string " "to be heap-allocated"
+                list = [s, s, s]
+
+                List.len list
+            "#
+        ),
+        3,
+        usize
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn dev_str_in_record_balanced() {
+    assert_refcounts_balanced!(
+        indoc!(
+            r#"
+                s = Str.concat "A long enough string " "to be heap-allocated"
+                rec = { a: s, b: s, c: 42 }
+
+                rec.c
+            "#
+        ),
+        42,
+        i64
+    );
+}
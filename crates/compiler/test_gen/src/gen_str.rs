@@ -518,6 +518,13 @@ fn empty_str_is_empty() {
     assert_evals_to!(r#"Str.isEmpty """#, true, bool);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn big_empty_str_is_empty() {
+    // a big string with no contents, since its capacity is more than a small string can hold
+    assert_evals_to!(r#"Str.isEmpty (Str.withCapacity 50)"#, true, bool);
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_starts_with() {
@@ -1417,7 +1424,7 @@ fn str_to_nat() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_i128() {
     assert_evals_to!(
         indoc!(
@@ -1431,7 +1438,7 @@ fn str_to_i128() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_u128() {
     assert_evals_to!(
         indoc!(
@@ -1473,7 +1480,7 @@ fn str_to_u64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_i32() {
     assert_evals_to!(
         indoc!(
@@ -1487,7 +1494,7 @@ fn str_to_i32() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_u32() {
     assert_evals_to!(
         indoc!(
@@ -1501,7 +1508,21 @@ fn str_to_u32() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn str_to_u32_invalid() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            Str.toU32 "-1"
+            "#
+        ),
+        RocResult::err(()),
+        RocResult<u32, ()>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_i16() {
     assert_evals_to!(
         indoc!(
@@ -1515,7 +1536,7 @@ fn str_to_i16() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_u16() {
     assert_evals_to!(
         indoc!(
@@ -1529,7 +1550,7 @@ fn str_to_u16() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_i8() {
     assert_evals_to!(
         indoc!(
@@ -1543,7 +1564,7 @@ fn str_to_i8() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_u8() {
     assert_evals_to!(
         indoc!(
@@ -1557,7 +1578,7 @@ fn str_to_u8() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_f64() {
     assert_evals_to!(
         indoc!(
@@ -1574,7 +1595,7 @@ fn str_to_f64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_f32() {
     assert_evals_to!(
         indoc!(
@@ -1591,7 +1612,7 @@ fn str_to_f32() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn str_to_dec() {
     use roc_std::RocDec;
 
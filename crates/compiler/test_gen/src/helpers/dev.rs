@@ -27,19 +27,20 @@ fn promote_expr_to_module(src: &str) -> String {
     buffer
 }
 
+/// Compiles `src` with the dev backend, returning the name of main, the problems whose
+/// reporting is delayed until main runs, and the object file's bytes.
 #[allow(dead_code)]
-pub fn helper(
+pub fn build_app_object(
     arena: &bumpalo::Bump,
     src: &str,
-    _leak: bool,
     lazy_literals: bool,
-) -> (String, Vec<roc_problem::can::Problem>, Library) {
+    log_refcounts: bool,
+    patchable_prologues: bool,
+) -> (String, Vec<roc_problem::can::Problem>, Vec<u8>) {
     use std::path::PathBuf;
 
-    let dir = tempdir().unwrap();
     let filename = PathBuf::from("Test.roc");
     let src_dir = PathBuf::from("fake/test/path");
-    let app_o_file = dir.path().join("app.o");
 
     let module_src;
     let temp;
@@ -191,6 +192,13 @@ pub fn helper(
         exposed_to_host: exposed_to_host.top_level_values.keys().copied().collect(),
         lazy_literals,
         generate_allocators: true, // Needed for testing, since we don't have a platform
+        log_refcounts,
+        unsupported: Default::default(),
+        emit_assembly: false,
+        assembly: Default::default(),
+        collect_proc_stats: false,
+        proc_stats: Default::default(),
+        patchable_prologues,
     };
 
     let target = target_lexicon::Triple::host();
@@ -202,13 +210,51 @@ pub fn helper(
         procedures,
     );
 
+    let unsupported = env.unsupported.into_inner();
+    if !unsupported.is_empty() {
+        panic!(
+            "{}",
+            roc_gen_dev::report_unsupported(&unsupported, &interns)
+        );
+    }
+
     let module_out = module_object
         .write()
         .expect("failed to build output object");
+
+    (main_fn_name, delayed_errors, module_out)
+}
+
+#[allow(dead_code)]
+pub fn helper(
+    arena: &bumpalo::Bump,
+    src: &str,
+    _leak: bool,
+    lazy_literals: bool,
+    log_refcounts: bool,
+    patchable_prologues: bool,
+) -> (String, Vec<roc_problem::can::Problem>, Library) {
+    let dir = tempdir().unwrap();
+    let app_o_file = dir.path().join("app.o");
+
+    let (main_fn_name, delayed_errors, module_out) = build_app_object(
+        arena,
+        src,
+        lazy_literals,
+        log_refcounts,
+        patchable_prologues,
+    );
     std::fs::write(&app_o_file, module_out).expect("failed to write object to file");
 
-    let builtins_host_tempfile =
-        roc_bitcode::host_tempfile().expect("failed to write host builtins object to tempfile");
+    let target = target_lexicon::Triple::host();
+
+    // only the test build of the builtins has the allocators that log refcounts
+    let builtins_host_tempfile = if log_refcounts {
+        roc_bitcode::host_test_tempfile()
+    } else {
+        roc_bitcode::host_tempfile()
+    }
+    .expect("failed to write host builtins object to tempfile");
 
     if false {
         std::fs::copy(&app_o_file, "/tmp/app.o").unwrap();
@@ -269,7 +315,7 @@ macro_rules! assert_evals_to {
 
         let arena = Bump::new();
         let (main_fn_name, errors, lib) =
-            $crate::helpers::dev::helper(&arena, $src, $leak, $lazy_literals);
+            $crate::helpers::dev::helper(&arena, $src, $leak, $lazy_literals, false, false);
 
         let transform = |success| {
             let expected = $expected;
@@ -283,3 +329,237 @@ macro_rules! assert_evals_to {
 
 #[allow(unused_imports)]
 pub(crate) use assert_evals_to;
+
+/// Runs `f` in a forked child process, and tells whether the child was killed by a signal.
+/// roc_panic doesn't stop the program in these tests, so a crash shows up as the trap after it.
+#[cfg(unix)]
+#[allow(dead_code)]
+pub fn crashes_in_child(f: impl FnOnce()) -> bool {
+    unsafe {
+        match libc::fork() {
+            -1 => panic!("fork failed: {}", std::io::Error::last_os_error()),
+            0 => {
+                // Don't run the parent's exit handlers or unwind into the test harness
+                f();
+                libc::_exit(0)
+            }
+            child => {
+                let mut status = 0;
+                if libc::waitpid(child, &mut status, 0) == -1 {
+                    panic!("waitpid failed: {}", std::io::Error::last_os_error());
+                }
+                libc::WIFSIGNALED(status)
+            }
+        }
+    }
+}
+
+/// Redirects calls to the proc `fn_name` in `lib`, which was built with patchable prologues,
+/// to `target`. The code is made writable in place, like an incremental dev loop would do.
+/// Only on x86_64, since aarch64 would also need its instruction cache flushed after the patch.
+#[cfg(all(unix, target_arch = "x86_64"))]
+#[allow(dead_code)]
+pub fn redirect_proc(lib: &Library, fn_name: &str, target: u64) {
+    // The size of the patchable entry on x86_64
+    const ENTRY_SIZE: usize = 16;
+
+    unsafe {
+        let proc: libloading::Symbol<unsafe extern "C" fn()> = lib
+            .get(fn_name.as_bytes())
+            .unwrap_or_else(|e| panic!("Unable to find `{}`: {}", fn_name, e));
+        let start = *proc as usize;
+
+        let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+        let page = start & !(page_size - 1);
+        let protection = libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC;
+        if libc::mprotect(page as *mut _, start + ENTRY_SIZE - page, protection) != 0 {
+            panic!("mprotect failed: {}", std::io::Error::last_os_error());
+        }
+
+        let code = std::slice::from_raw_parts_mut(start as *mut u8, ENTRY_SIZE);
+        roc_gen_dev::redirect_patchable_entry(roc_target::Architecture::X86_64, code, target);
+    }
+}
+
+/// Checks that the program crashes, which assert_evals_to can't do because the crash stops the
+/// whole test process. It only runs on unix, see crashes_in_child.
+#[allow(unused_macros)]
+macro_rules! assert_crashes {
+    ($src:expr, $ty:ty) => {{
+        use bumpalo::Bump;
+
+        let arena = Bump::new();
+        let (main_fn_name, errors, lib) =
+            $crate::helpers::dev::helper(&arena, $src, true, false, false, false);
+        assert!(errors.is_empty(), "Encountered errors: {:?}", errors);
+
+        let main: libloading::Symbol<unsafe extern "C" fn() -> $ty> =
+            unsafe { lib.get(main_fn_name.as_bytes()) }.expect("errored");
+
+        let crashed = $crate::helpers::dev::crashes_in_child(|| {
+            unsafe { main() };
+        });
+        assert!(crashed, "expected the program to crash, but it returned");
+    }};
+}
+
+#[allow(unused_imports)]
+pub(crate) use assert_crashes;
+
+/// One entry in the log that the builtins keep when the program is built with `log_refcounts`.
+/// Matches `Event` in refcount_log.zig.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct RefcountEvent {
+    kind: u64,
+    /// The start of the allocation, or the refcount's address for a refcount change
+    address: usize,
+    /// The size of an allocation, or how much a refcount changed by
+    value: isize,
+}
+
+const EVENT_ALLOC: u64 = 0;
+const EVENT_DEALLOC: u64 = 1;
+const EVENT_REFCOUNT: u64 = 2;
+
+/// How many events fit in the log, see refcount_log.zig
+const REFCOUNT_LOG_CAPACITY: usize = 4096;
+
+/// Check the log of a program built with `log_refcounts`: everything it allocated was freed
+/// exactly once, and no refcount changed after its allocation was freed.
+/// Anything owned by the program's result counts as leaked, so only use this for results that
+/// don't point to the heap.
+#[allow(dead_code)]
+pub fn check_refcount_log(lib: &Library) -> Result<(), String> {
+    let events = unsafe {
+        let get_log: libloading::Symbol<unsafe extern "C" fn(*mut usize) -> *const RefcountEvent> =
+            lib.get(bitcode::UTILS_TEST_REFCOUNT_LOG.as_bytes())
+                .map_err(|err| err.to_string())?;
+        let mut len = 0;
+        let events = get_log(&mut len);
+
+        if len > REFCOUNT_LOG_CAPACITY {
+            return Err(format!(
+                "The program made {} allocations and refcount changes, but only {} fit in the log",
+                len, REFCOUNT_LOG_CAPACITY
+            ));
+        }
+
+        std::slice::from_raw_parts(events, len)
+    };
+
+    // The start and size of each allocation
+    let mut live: Vec<(usize, usize)> = Vec::new();
+    let mut freed: Vec<(usize, usize)> = Vec::new();
+    let contains = |(start, size): &(usize, usize), address: usize| {
+        *start <= address && address < start + size
+    };
+
+    for (index, event) in events.iter().enumerate() {
+        match event.kind {
+            EVENT_ALLOC => {
+                let allocation = (event.address, event.value as usize);
+
+                // The allocator can hand out memory again once it has been freed
+                freed.retain(|(start, size)| {
+                    start + size <= allocation.0 || allocation.0 + allocation.1 <= *start
+                });
+                live.push(allocation);
+            }
+            EVENT_DEALLOC => match live.iter().position(|(start, _)| *start == event.address) {
+                Some(position) => freed.push(live.swap_remove(position)),
+                None => {
+                    return Err(format!(
+                        "Event {}: {:#x} was freed, but it wasn't allocated\n{}",
+                        index,
+                        event.address,
+                        refcount_history(events, event.address, 1)
+                    ));
+                }
+            },
+            EVENT_REFCOUNT => {
+                if live.iter().any(|alloc| contains(alloc, event.address)) {
+                    continue;
+                }
+
+                // Refcounts that were never on the heap belong to constants, like string literals
+                if let Some((start, size)) =
+                    freed.iter().find(|alloc| contains(alloc, event.address))
+                {
+                    return Err(format!(
+                        "Event {}: the refcount at {:#x} changed by {} after it was freed\n{}",
+                        index,
+                        event.address,
+                        event.value,
+                        refcount_history(events, *start, *size)
+                    ));
+                }
+            }
+            other => unreachable!("unknown refcount log event {}", other),
+        }
+    }
+
+    if live.is_empty() {
+        Ok(())
+    } else {
+        let leaks: Vec<String> = live
+            .iter()
+            .map(|(start, size)| refcount_history(events, *start, *size))
+            .collect();
+
+        Err(format!(
+            "{} allocations were never freed\n{}",
+            leaks.len(),
+            leaks.join("\n")
+        ))
+    }
+}
+
+/// Every logged event for the memory from `start` to `start + size`
+fn refcount_history(events: &[RefcountEvent], start: usize, size: usize) -> String {
+    let mut buf = String::new();
+
+    for (index, event) in events.iter().enumerate() {
+        if event.address < start || start + size <= event.address {
+            continue;
+        }
+
+        let description = match event.kind {
+            EVENT_ALLOC => format!("allocated {} bytes at {:#x}", event.value, event.address),
+            EVENT_DEALLOC => format!("freed {:#x}", event.address),
+            _ => format!("refcount at {:#x} {:+}", event.address, event.value),
+        };
+
+        buf.push_str(&format!("    {}: {}\n", index, description));
+    }
+
+    buf
+}
+
+/// Like assert_evals_to, but also logs every allocation and refcount change while the program
+/// runs, and checks that everything it allocated was freed exactly once.
+/// The result must not point to the heap, or it will count as leaked.
+#[allow(unused_macros)]
+macro_rules! assert_refcounts_balanced {
+    ($src:expr, $expected:expr, $ty:ty) => {{
+        use bumpalo::Bump;
+        use roc_gen_dev::run_jit_function_raw;
+
+        let arena = Bump::new();
+        let (main_fn_name, errors, lib) =
+            $crate::helpers::dev::helper(&arena, $src, true, true, true, false);
+
+        let transform = |success| {
+            let expected = $expected;
+            assert_eq!(&success, &expected);
+        };
+        run_jit_function_raw!(lib, main_fn_name, $ty, transform, errors);
+
+        if let Err(problem) = $crate::helpers::dev::check_refcount_log(&lib) {
+            panic!("Unbalanced refcounts: {}", problem);
+        }
+    }};
+}
+
+#[allow(unused_imports)]
+pub(crate) use assert_refcounts_balanced;
@@ -7,14 +7,15 @@ use roc_gen_wasm::DEBUG_SETTINGS;
 use roc_load::{ExecutionMode, LoadConfig, Threading};
 use roc_packaging::cache::RocCacheDir;
 use roc_reporting::report::DEFAULT_PALETTE_HTML;
-use roc_std::RocStr;
-use roc_wasm_interp::{wasi, ImportDispatcher, Instance, WasiDispatcher};
+use roc_wasm_interp::{wasi, ImportDispatcher, Instance, Memory, MemoryErrorMode, WasiDispatcher};
 use roc_wasm_module::{Export, ExportType, Value, WasmModule};
 use std::marker::PhantomData;
 use std::path::PathBuf;
 
 const TEST_WRAPPER_NAME: &str = "test_wrapper";
 const INIT_REFCOUNT_NAME: &str = "init_refcount_test";
+/// The test platform's `roc_panic` passes its arguments on to this import
+const PANIC_IMPORT: (&str, &str) = ("env", "send_panic_msg_to_rust");
 
 macro_rules! host_bytes_path {
     () => {
@@ -47,31 +48,33 @@ fn write_final_wasm() -> bool {
 }
 
 #[allow(dead_code)]
-pub fn compile_to_wasm_bytes<'a, T: Wasm32Result>(
+pub fn compile_to_wasm_module<'a, T: Wasm32Result>(
     arena: &'a bumpalo::Bump,
     src: &str,
     test_wrapper_type_info: PhantomData<T>,
-) -> Vec<u8> {
+) -> WasmModule<'a> {
     let platform_bytes = include_bytes!(host_bytes_path!());
     println!("Loading test host {}", host_bytes_path!());
 
-    let compiled_bytes =
-        compile_roc_to_wasm_bytes(arena, platform_bytes, src, test_wrapper_type_info);
+    let module = compile_roc_to_wasm_module(arena, platform_bytes, src, test_wrapper_type_info);
 
     if write_final_wasm() {
+        let mut compiled_bytes = Vec::with_capacity(module.size());
+        module.serialize(&mut compiled_bytes);
+
         let build_dir_hash = crate::helpers::src_hash(src);
         crate::helpers::save_wasm_file(&compiled_bytes, build_dir_hash)
     };
 
-    compiled_bytes
+    module
 }
 
-fn compile_roc_to_wasm_bytes<'a, T: Wasm32Result>(
+fn compile_roc_to_wasm_module<'a, T: Wasm32Result>(
     arena: &'a bumpalo::Bump,
     host_bytes: &[u8],
     src: &str,
     _test_wrapper_type_info: PhantomData<T>,
-) -> Vec<u8> {
+) -> WasmModule<'a> {
     let filename = PathBuf::from("Test.roc");
     let src_dir = PathBuf::from("fake/test/path");
 
@@ -166,10 +169,7 @@ fn compile_roc_to_wasm_bytes<'a, T: Wasm32Result>(
 
     module.eliminate_dead_code(env.arena, called_fns);
 
-    let mut app_module_bytes = std::vec::Vec::with_capacity(module.size());
-    module.serialize(&mut app_module_bytes);
-
-    app_module_bytes
+    module
 }
 
 #[allow(dead_code)]
@@ -179,9 +179,14 @@ where
 {
     let arena = bumpalo::Bump::new();
 
-    let wasm_bytes = crate::helpers::wasm::compile_to_wasm_bytes(&arena, src, phantom);
+    let module = crate::helpers::wasm::compile_to_wasm_module(&arena, src, phantom);
 
-    run_wasm_test_bytes::<T>(TEST_WRAPPER_NAME, wasm_bytes)
+    let dispatcher = TestDispatcher {
+        wasi: wasi::WasiDispatcher::default(),
+    };
+    let is_debug_mode = roc_debug_flags::dbg_set!(roc_debug_flags::ROC_LOG_WASM_INTERP);
+    let inst = Instance::from_module(&arena, module, dispatcher, is_debug_mode)?;
+    run_test_wrapper(inst, TEST_WRAPPER_NAME)
 }
 
 struct TestDispatcher<'a> {
@@ -194,20 +199,10 @@ impl<'a> ImportDispatcher for TestDispatcher<'a> {
         module_name: &str,
         function_name: &str,
         arguments: &[Value],
-        memory: &mut [u8],
+        memory: &mut Memory<'_>,
     ) -> Option<Value> {
         if module_name == wasi::MODULE_NAME {
             self.wasi.dispatch(function_name, arguments, memory)
-        } else if module_name == "env" && function_name == "send_panic_msg_to_rust" {
-            let msg_ptr = arguments[0].expect_i32().unwrap();
-            let tag = arguments[1].expect_i32().unwrap();
-            let roc_msg = RocStr::decode(memory, msg_ptr as _);
-            let msg = match tag {
-                0 => format!(r#"Roc failed with message: "{}""#, roc_msg),
-                1 => format!(r#"User crash with message: "{}""#, roc_msg),
-                tag => format!(r#"Got an invald panic tag: "{}""#, tag),
-            };
-            panic!("{}", msg)
         } else {
             panic!(
                 "TestDispatcher does not implement {}.{}",
@@ -215,8 +210,13 @@ impl<'a> ImportDispatcher for TestDispatcher<'a> {
             );
         }
     }
+
+    fn exit_status(&self) -> Option<i32> {
+        self.wasi.exit_status
+    }
 }
 
+#[allow(dead_code)]
 pub(crate) fn run_wasm_test_bytes<T>(
     test_wrapper_name: &str,
     wasm_bytes: Vec<u8>,
@@ -228,22 +228,24 @@ where
     let require_relocatable = false;
     let module = WasmModule::preload(&arena, &wasm_bytes, require_relocatable)
         .map_err(|e| format!("{:?}", e))?;
-    run_wasm_test_module(&arena, test_wrapper_name, &module)
+
+    let dispatcher = TestDispatcher {
+        wasi: wasi::WasiDispatcher::default(),
+    };
+    let is_debug_mode = roc_debug_flags::dbg_set!(roc_debug_flags::ROC_LOG_WASM_INTERP);
+    let inst = Instance::for_module(&arena, &module, dispatcher, is_debug_mode)?;
+    run_test_wrapper(inst, test_wrapper_name)
 }
 
-pub(crate) fn run_wasm_test_module<'a, T>(
-    arena: &'a Bump,
+fn run_test_wrapper<T>(
+    mut inst: Instance<'_, TestDispatcher<'_>>,
     test_wrapper_name: &str,
-    module: &WasmModule<'a>,
 ) -> Result<T, String>
 where
     T: FromWasm32Memory + Wasm32Result,
 {
-    let dispatcher = TestDispatcher {
-        wasi: wasi::WasiDispatcher::default(),
-    };
-    let is_debug_mode = roc_debug_flags::dbg_set!(roc_debug_flags::ROC_LOG_WASM_INTERP);
-    let mut inst = Instance::for_module(&arena, &module, dispatcher, is_debug_mode)?;
+    inst.set_memory_error_mode(MemoryErrorMode::Explain);
+    inst.set_panic_import(Some(PANIC_IMPORT));
     let opt_value = inst.call_export(test_wrapper_name, [])?;
     let addr_value = opt_value.ok_or("No return address from Wasm test")?;
     let addr = addr_value.expect_i32().map_err(|e| format!("{:?}", e))?;
@@ -262,17 +264,15 @@ where
 {
     let arena = bumpalo::Bump::new();
 
-    let wasm_bytes = crate::helpers::wasm::compile_to_wasm_bytes(&arena, src, phantom);
-
-    let require_relocatable = false;
-    let module = WasmModule::preload(&arena, &wasm_bytes, require_relocatable)
-        .map_err(|e| format!("{:?}", e))?;
+    let module = crate::helpers::wasm::compile_to_wasm_module(&arena, src, phantom);
 
     let dispatcher = TestDispatcher {
         wasi: wasi::WasiDispatcher::default(),
     };
     let is_debug_mode = roc_debug_flags::dbg_set!(roc_debug_flags::ROC_LOG_WASM_INTERP);
-    let mut inst = Instance::for_module(&arena, &module, dispatcher, is_debug_mode)?;
+    let mut inst = Instance::from_module(&arena, module, dispatcher, is_debug_mode)?;
+    inst.set_memory_error_mode(MemoryErrorMode::Explain);
+    inst.set_panic_import(Some(PANIC_IMPORT));
 
     // Allocate a vector in the test host that refcounts will be copied into
     let mut refcount_vector_addr: i32 = inst
@@ -18,7 +18,7 @@ use roc_mono::ir::{
     UpdateModeId,
 };
 use roc_mono::layout::{LambdaName, Layout, Niche, STLayoutInterner};
-use roc_wasm_interp::{wasi, ImportDispatcher, Instance, WasiDispatcher};
+use roc_wasm_interp::{wasi, ImportDispatcher, Instance, Memory, WasiDispatcher};
 use roc_wasm_module::{Value, WasmModule};
 
 const LINKING_TEST_HOST_WASM: &str = "build/wasm_linking_test_host.wasm";
@@ -190,7 +190,7 @@ impl ImportDispatcher for TestDispatcher<'_> {
         module_name: &str,
         function_name: &str,
         arguments: &[Value],
-        memory: &mut [u8],
+        memory: &mut Memory<'_>,
     ) -> Option<Value> {
         if module_name == wasi::MODULE_NAME {
             self.wasi.dispatch(function_name, arguments, memory)
@@ -210,22 +210,18 @@ impl ImportDispatcher for TestDispatcher<'_> {
             );
         }
     }
-}
 
-fn execute_wasm_module<'a>(arena: &'a Bump, orig_module: WasmModule<'a>) -> Result<i32, String> {
-    // FIXME: see if we can skip serializing and re-parsing!
-    // Some metadata seems to be left over from the host file. e.g. CodeSection::section_start
-    let module = {
-        let mut buffer = Vec::with_capacity(orig_module.size());
-        orig_module.serialize(&mut buffer);
-        WasmModule::preload(arena, &buffer, false).map_err(|e| format!("{:?}", e))?
-    };
+    fn exit_status(&self) -> Option<i32> {
+        self.wasi.exit_status
+    }
+}
 
+fn execute_wasm_module<'a>(arena: &'a Bump, module: WasmModule<'a>) -> Result<i32, String> {
     let dispatcher = TestDispatcher {
         wasi: wasi::WasiDispatcher::default(),
     };
     let is_debug_mode = false;
-    let mut inst = Instance::for_module(&arena, &module, dispatcher, is_debug_mode)?;
+    let mut inst = Instance::from_module(arena, module, dispatcher, is_debug_mode)?;
 
     // In Zig, main can only return u8 or void, but our result is too wide for that.
     // But I want to use main so that I can test that _start is created for it!
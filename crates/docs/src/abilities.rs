@@ -0,0 +1,153 @@
+//! Links between abilities and the types that implement them, so that a type's
+//! entry lists its abilities and an ability's entry lists its implementers.
+use crate::metadata::escape_attr;
+use crate::{is_hidden, push_html, sidebar_link_url};
+use roc_can::scope::Scope;
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::{DocDef, DocEntry, ModuleDocumentation};
+use roc_module::symbol::{Interns, Symbol};
+use roc_region::all::Region;
+
+/// An exposed type which implements some ability
+pub struct Implementer {
+    /// Qualified name, e.g. "Dict.Dict"
+    pub name: String,
+    pub url: String,
+}
+
+/// For every ability, find the exposed types in the documented package that implement it.
+pub fn find_implementers<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    base_url: &str,
+    modules: I,
+    interns: &Interns,
+    all_exposed_symbols: &VecSet<Symbol>,
+) -> VecMap<Symbol, Vec<Implementer>> {
+    let mut implementers: VecMap<Symbol, Vec<Implementer>> = VecMap::default();
+
+    for module in modules {
+        for entry in module.entries.iter() {
+            let doc_def = match entry {
+                DocEntry::DocDef(doc_def)
+                    if all_exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) =>
+                {
+                    doc_def
+                }
+                _ => continue,
+            };
+
+            for ability_name in doc_def.implements.iter() {
+                if let Some(ability) = resolve_name(&module.scope, interns, ability_name) {
+                    let implementer = Implementer {
+                        name: format!("{}.{}", module.name, doc_def.name),
+                        url: format!(
+                            "{}#{}",
+                            sidebar_link_url(base_url, &module.name),
+                            doc_def.name
+                        ),
+                    };
+
+                    match implementers.get_mut(&ability) {
+                        Some(list) => list.push(implementer),
+                        None => {
+                            implementers.insert(ability, vec![implementer]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    implementers
+}
+
+/// Look up a type or ability by the name it was written with, e.g. "Eq" or "Hash.Hash".
+/// Returns None if it isn't in scope, rather than failing, since this is only used for links.
+pub(crate) fn resolve_name(scope: &Scope, interns: &Interns, name: &str) -> Option<Symbol> {
+    match name.rsplit_once('.') {
+        None => scope.lookup_str(name, Region::zero()).ok(),
+        Some((module_name, ident)) => {
+            let module_id = interns.module_ids.get_id(&module_name.into())?;
+            let ident_id = interns.all_ident_ids.get(&module_id)?.get_id(ident)?;
+
+            Some(Symbol::new(module_id, ident_id))
+        }
+    }
+}
+
+/// Renders the abilities a type implements, linking to the docs of each one that's documented
+/// here. Builtin abilities like `Eq` and `Hash` have no page in a package's docs, so they're
+/// rendered as plain code, like abilities that aren't in scope.
+pub fn render_implements(
+    buf: &mut String,
+    base_url: &str,
+    doc_def: &DocDef,
+    scope: &Scope,
+    interns: &Interns,
+    all_exposed_symbols: &VecSet<Symbol>,
+    label: &str,
+) {
+    if doc_def.implements.is_empty() {
+        return;
+    }
+
+    let mut links = Vec::with_capacity(doc_def.implements.len());
+
+    for ability_name in doc_def.implements.iter() {
+        let mut link = String::new();
+
+        match resolve_name(scope, interns, ability_name) {
+            Some(ability) if all_exposed_symbols.contains(&ability) => {
+                let url = format!(
+                    "{}{}#{}",
+                    base_url,
+                    ability.module_string(interns).as_str(),
+                    ability.as_str(interns)
+                );
+
+                push_html(&mut link, "a", vec![("href", url.as_str())], ability_name);
+            }
+            _ => {
+                push_html(&mut link, "code", vec![], ability_name);
+            }
+        }
+
+        links.push(link);
+    }
+
+    push_html(
+        buf,
+        "p",
+        vec![("class", "implements")],
+        format!("{} {}", escape_attr(label), links.join(", ")),
+    );
+}
+
+/// Renders the types in this package which implement an ability.
+pub fn render_implementers(buf: &mut String, implementers: &[Implementer], label: &str) {
+    if implementers.is_empty() {
+        return;
+    }
+
+    let links: Vec<String> = implementers
+        .iter()
+        .map(|implementer| {
+            let mut link = String::new();
+
+            push_html(
+                &mut link,
+                "a",
+                vec![("href", implementer.url.as_str())],
+                implementer.name.as_str(),
+            );
+
+            link
+        })
+        .collect();
+
+    push_html(
+        buf,
+        "p",
+        vec![("class", "implementers")],
+        format!("{} {}", escape_attr(label), links.join(", ")),
+    );
+}
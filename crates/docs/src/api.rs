@@ -0,0 +1,410 @@
+//! A machine-readable snapshot of a package's API, written next to the generated docs as
+//! api.json, and a diff between two snapshots. Package authors can use the diff to write
+//! changelogs and to decide whether a release needs a new major version.
+use crate::{is_hidden, type_annotation_to_html};
+use roc_collections::VecSet;
+use roc_load::docs::{DocEntry, ModuleDocumentation, TypeAnnotation};
+use roc_module::symbol::Symbol;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The file the snapshot is written to, next to the modules' directories.
+pub const API_JSON: &str = "api.json";
+
+/// Every documented entry of every module, in the order they appear in the docs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiSnapshot {
+    pub modules: Vec<ApiModule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiModule {
+    pub name: String,
+    pub entries: Vec<ApiEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiEntry {
+    pub name: String,
+    /// What follows the name in the entry's heading in the docs, e.g. `: List a -> Nat` for a
+    /// function or `k v : [@..]` for an opaque type. Empty if the entry has neither.
+    pub signature: String,
+}
+
+impl ApiSnapshot {
+    /// The exposed entries of each module, leaving out the hidden ones just like the docs do.
+    pub fn from_modules<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+        modules: I,
+        all_exposed_symbols: &VecSet<Symbol>,
+    ) -> Self {
+        let mut api_modules = Vec::new();
+
+        for module in modules {
+            let mut entries = Vec::new();
+
+            for entry in module.entries.iter() {
+                match entry {
+                    DocEntry::DocDef(doc_def)
+                        if all_exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) =>
+                    {
+                        entries.push(ApiEntry {
+                            name: doc_def.name.clone(),
+                            signature: signature(&doc_def.type_vars, &doc_def.type_annotation),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+
+            api_modules.push(ApiModule {
+                name: module.name.clone(),
+                entries,
+            });
+        }
+
+        ApiSnapshot {
+            modules: api_modules,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("an API snapshot is always valid JSON")
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|err| format!("I couldn't read {}: {}", path.display(), err))?;
+
+        serde_json::from_str(&json).map_err(|err| {
+            format!(
+                "{} isn't an API snapshot from `roc docs`: {}",
+                path.display(),
+                err
+            )
+        })
+    }
+}
+
+/// The annotation on a single line, so that only changes to the type itself show up in a diff
+pub(crate) fn signature(type_vars: &[String], type_ann: &TypeAnnotation) -> String {
+    let mut buf = type_vars.join(" ");
+
+    if !matches!(type_ann, TypeAnnotation::NoTypeAnn) {
+        if !buf.is_empty() {
+            buf.push(' ');
+        }
+
+        buf.push_str(": ");
+        type_annotation_to_html(0, &mut buf, type_ann, false);
+    }
+
+    buf.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// One difference between two snapshots of a package's API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiChange {
+    ModuleAdded {
+        module: String,
+    },
+    ModuleRemoved {
+        module: String,
+    },
+    EntryAdded {
+        module: String,
+        entry: ApiEntry,
+    },
+    EntryRemoved {
+        module: String,
+        entry: ApiEntry,
+    },
+    SignatureChanged {
+        module: String,
+        name: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl ApiChange {
+    /// Whether code using the old API might no longer compile against the new one.
+    /// Any change to a signature counts, since we don't know how the entry is used.
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            ApiChange::ModuleAdded { .. } | ApiChange::EntryAdded { .. } => false,
+            ApiChange::ModuleRemoved { .. }
+            | ApiChange::EntryRemoved { .. }
+            | ApiChange::SignatureChanged { .. } => true,
+        }
+    }
+}
+
+impl fmt::Display for ApiChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiChange::ModuleAdded { module } => write!(f, "module {module} was added"),
+            ApiChange::ModuleRemoved { module } => write!(f, "module {module} was removed"),
+            ApiChange::EntryAdded { module, entry } => {
+                write!(f, "{module}.{} {} was added", entry.name, entry.signature)
+            }
+            ApiChange::EntryRemoved { module, entry } => {
+                write!(f, "{module}.{} {} was removed", entry.name, entry.signature)
+            }
+            ApiChange::SignatureChanged {
+                module,
+                name,
+                old,
+                new,
+            } => write!(
+                f,
+                "{module}.{name} changed from `{name} {old}` to `{name} {new}`"
+            ),
+        }
+    }
+}
+
+/// Everything that was added, removed, or changed between `old` and `new`, in the order of
+/// the modules and entries in `new`, followed by what was removed.
+pub fn diff_api(old: &ApiSnapshot, new: &ApiSnapshot) -> Vec<ApiChange> {
+    let mut changes = Vec::new();
+
+    for new_module in new.modules.iter() {
+        let module = &new_module.name;
+        let old_module = match old.modules.iter().find(|old| old.name == *module) {
+            Some(old_module) => old_module,
+            None => {
+                changes.push(ApiChange::ModuleAdded {
+                    module: module.clone(),
+                });
+
+                continue;
+            }
+        };
+
+        for new_entry in new_module.entries.iter() {
+            match old_module
+                .entries
+                .iter()
+                .find(|old| old.name == new_entry.name)
+            {
+                Some(old_entry) if old_entry.signature != new_entry.signature => {
+                    changes.push(ApiChange::SignatureChanged {
+                        module: module.clone(),
+                        name: new_entry.name.clone(),
+                        old: old_entry.signature.clone(),
+                        new: new_entry.signature.clone(),
+                    });
+                }
+                Some(_) => {}
+                None => changes.push(ApiChange::EntryAdded {
+                    module: module.clone(),
+                    entry: new_entry.clone(),
+                }),
+            }
+        }
+
+        for old_entry in old_module.entries.iter() {
+            if !new_module
+                .entries
+                .iter()
+                .any(|new| new.name == old_entry.name)
+            {
+                changes.push(ApiChange::EntryRemoved {
+                    module: module.clone(),
+                    entry: old_entry.clone(),
+                });
+            }
+        }
+    }
+
+    for old_module in old.modules.iter() {
+        if !new.modules.iter().any(|new| new.name == old_module.name) {
+            changes.push(ApiChange::ModuleRemoved {
+                module: old_module.name.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// A report of the changes, with the breaking ones first
+pub fn render_api_diff(changes: &[ApiChange]) -> String {
+    if changes.is_empty() {
+        return "No API changes.\n".to_string();
+    }
+
+    let mut buf = String::new();
+    let (breaking, additive): (Vec<_>, Vec<_>) =
+        changes.iter().partition(|change| change.is_breaking());
+
+    for (heading, changes) in [
+        ("Breaking changes", breaking),
+        ("Additive changes", additive),
+    ] {
+        if changes.is_empty() {
+            continue;
+        }
+
+        if !buf.is_empty() {
+            buf.push('\n');
+        }
+
+        buf.push_str(&format!("{heading}:\n"));
+
+        for change in changes {
+            buf.push_str(&format!("    {change}\n"));
+        }
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_api, render_api_diff, ApiChange, ApiEntry, ApiModule, ApiSnapshot};
+    use pretty_assertions::assert_eq;
+
+    fn entry(name: &str, signature: &str) -> ApiEntry {
+        ApiEntry {
+            name: name.to_string(),
+            signature: signature.to_string(),
+        }
+    }
+
+    fn module(name: &str, entries: Vec<ApiEntry>) -> ApiModule {
+        ApiModule {
+            name: name.to_string(),
+            entries,
+        }
+    }
+
+    #[test]
+    fn same_api_has_no_changes() {
+        let api = ApiSnapshot {
+            modules: vec![module("Str", vec![entry("len", ": Str -> Nat")])],
+        };
+
+        assert_eq!(diff_api(&api, &api.clone()), vec![]);
+        assert_eq!(render_api_diff(&[]), "No API changes.\n");
+    }
+
+    #[test]
+    fn finds_every_kind_of_change() {
+        let old = ApiSnapshot {
+            modules: vec![
+                module(
+                    "Str",
+                    vec![
+                        entry("len", ": Str -> Nat"),
+                        entry("trim", ": Str -> Str"),
+                        entry("isEmpty", ": Str -> Bool"),
+                    ],
+                ),
+                module("Internal", vec![entry("helper", ": {} -> {}")]),
+            ],
+        };
+        let new = ApiSnapshot {
+            modules: vec![
+                module(
+                    "Str",
+                    vec![
+                        entry("len", ": Str -> U64"),
+                        entry("isEmpty", ": Str -> Bool"),
+                        entry("reverse", ": Str -> Str"),
+                    ],
+                ),
+                module("Json", vec![entry("decode", ": List U8 -> Str")]),
+            ],
+        };
+
+        assert_eq!(
+            diff_api(&old, &new),
+            vec![
+                ApiChange::SignatureChanged {
+                    module: "Str".to_string(),
+                    name: "len".to_string(),
+                    old: ": Str -> Nat".to_string(),
+                    new: ": Str -> U64".to_string(),
+                },
+                ApiChange::EntryAdded {
+                    module: "Str".to_string(),
+                    entry: entry("reverse", ": Str -> Str"),
+                },
+                ApiChange::EntryRemoved {
+                    module: "Str".to_string(),
+                    entry: entry("trim", ": Str -> Str"),
+                },
+                ApiChange::ModuleAdded {
+                    module: "Json".to_string(),
+                },
+                ApiChange::ModuleRemoved {
+                    module: "Internal".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_additions_are_not_breaking() {
+        let added = ApiChange::EntryAdded {
+            module: "Str".to_string(),
+            entry: entry("reverse", ": Str -> Str"),
+        };
+        let removed = ApiChange::EntryRemoved {
+            module: "Str".to_string(),
+            entry: entry("trim", ": Str -> Str"),
+        };
+
+        assert!(!added.is_breaking());
+        assert!(!ApiChange::ModuleAdded {
+            module: "Json".to_string()
+        }
+        .is_breaking());
+        assert!(removed.is_breaking());
+        assert!(ApiChange::ModuleRemoved {
+            module: "Internal".to_string()
+        }
+        .is_breaking());
+    }
+
+    #[test]
+    fn report_lists_breaking_changes_first() {
+        let changes = [
+            ApiChange::EntryAdded {
+                module: "Str".to_string(),
+                entry: entry("reverse", ": Str -> Str"),
+            },
+            ApiChange::SignatureChanged {
+                module: "Str".to_string(),
+                name: "len".to_string(),
+                old: ": Str -> Nat".to_string(),
+                new: ": Str -> U64".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            render_api_diff(&changes),
+            concat!(
+                "Breaking changes:\n",
+                "    Str.len changed from `len : Str -> Nat` to `len : Str -> U64`\n",
+                "\n",
+                "Additive changes:\n",
+                "    Str.reverse : Str -> Str was added\n",
+            )
+        );
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_json() {
+        let api = ApiSnapshot {
+            modules: vec![module("Dict", vec![entry("Dict", "k v : [@..]")])],
+        };
+
+        let parsed: ApiSnapshot = serde_json::from_str(&api.to_json()).unwrap();
+
+        assert_eq!(parsed, api);
+    }
+}
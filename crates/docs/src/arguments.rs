@@ -0,0 +1,41 @@
+//! A table of a function's arguments under its signature, for functions whose doc comments
+//! describe their arguments with lines like `* msg - what to send`.
+use crate::metadata::escape_attr;
+use crate::{push_html, type_annotation_to_html};
+use roc_load::docs::DocDef;
+
+/// Renders the name, type, and description of each argument. `render_docs` turns a description
+/// into HTML, so that it can use the same markdown and links as the rest of the doc comment.
+pub fn render_arguments(
+    buf: &mut String,
+    doc_def: &DocDef,
+    caption: &str,
+    mut render_docs: impl FnMut(&mut String, &str),
+) {
+    if doc_def.args.iter().all(|arg| arg.docs.is_none()) {
+        return;
+    }
+
+    let mut table = String::new();
+
+    push_html(&mut table, "caption", vec![], escape_attr(caption));
+
+    for arg in &doc_def.args {
+        let mut row = String::new();
+        let mut type_html = String::new();
+        let mut docs_html = String::new();
+
+        type_annotation_to_html(0, &mut type_html, &arg.type_annotation, false);
+
+        if let Some(docs) = &arg.docs {
+            render_docs(&mut docs_html, docs);
+        }
+
+        push_html(&mut row, "th", vec![("scope", "row")], arg.name.as_str());
+        push_html(&mut row, "td", vec![("class", "argument-type")], type_html);
+        push_html(&mut row, "td", vec![], docs_html);
+        push_html(&mut table, "tr", vec![], row);
+    }
+
+    push_html(buf, "table", vec![("class", "arguments")], table);
+}
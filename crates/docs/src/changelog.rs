@@ -0,0 +1,197 @@
+//! A package's `CHANGELOG.md`, next to its main .roc file, becomes a page of its docs at
+//! `changelog/`. Each heading that starts with a version, like `## 1.2.0`, `## v1.2.0` or
+//! `## [1.2.0] - 2024-05-01`, gets an anchor such as `#v1.2.0`, so the version in the header can
+//! link to what changed in it.
+//!
+//! It's rendered like the package's other markdown, so its code blocks are highlighted and
+//! shortcut links like `[Str.concat]` go to the docs. That also means a shortcut link which isn't
+//! a name, like `[Unreleased]`, needs a definition at the end of the file.
+use crate::guides::read_markdown;
+use std::path::Path;
+
+/// The changelog's directory in the package's docs
+pub const CHANGELOG_DIR: &str = "changelog";
+const CHANGELOG_FILE: &str = "CHANGELOG.md";
+
+pub fn read_changelog(root_file: &Path) -> Option<String> {
+    let package_dir = root_file.parent().unwrap_or_else(|| Path::new("."));
+
+    read_markdown(&package_dir.join(CHANGELOG_FILE))
+}
+
+/// The rendered changelog, and the anchors of the versions in it
+pub struct ChangelogPage {
+    pub html: String,
+    anchors: Vec<String>,
+}
+
+impl ChangelogPage {
+    /// Gives each heading that starts with a version an `id`, in the changelog's rendered HTML
+    pub fn from_html(html: &str) -> Self {
+        let mut buf = String::with_capacity(html.len());
+        let mut anchors: Vec<String> = Vec::new();
+        let mut rest = html;
+
+        while let Some((start, level)) = find_heading(rest) {
+            // Everything up to and including the `<h2` of `<h2>`
+            let (before, after) = rest.split_at(start + 3);
+            let close_tag = format!("</h{level}>");
+
+            buf.push_str(before);
+            rest = after;
+
+            let content = match rest[1..].find(&close_tag) {
+                Some(end) => &rest[1..1 + end],
+                None => continue,
+            };
+
+            if let Some(version) = heading_version(&strip_tags(content)) {
+                let anchor = version_anchor(version);
+
+                // If a version is in there twice, its first heading gets the link
+                if !anchors.contains(&anchor) {
+                    buf.push_str(&format!(r#" id="{anchor}""#));
+                    anchors.push(anchor);
+                }
+            }
+        }
+
+        buf.push_str(rest);
+
+        Self { html: buf, anchors }
+    }
+
+    /// The changelog's URL, going to the version's section if it has one
+    pub fn url(&self, base_url: &str, version: &str) -> String {
+        let anchor = version_anchor(version);
+
+        if self.anchors.contains(&anchor) {
+            format!("{base_url}{CHANGELOG_DIR}/#{anchor}")
+        } else {
+            format!("{base_url}{CHANGELOG_DIR}/")
+        }
+    }
+}
+
+/// e.g. "v1.2.0" for "1.2.0" or "v1.2.0". The "v" keeps the id from starting with a digit, which
+/// CSS selectors would need to escape.
+fn version_anchor(version: &str) -> String {
+    format!("v{}", version.trim_start_matches('v'))
+}
+
+/// The start and level of the next heading. The headings come from markdown, so they don't have
+/// any attributes.
+fn find_heading(html: &str) -> Option<(usize, char)> {
+    html.match_indices("<h").find_map(|(index, _)| {
+        let mut chars = html[index + 2..].chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(level @ '1'..='6'), Some('>')) => Some((index, level)),
+            _ => None,
+        }
+    })
+}
+
+/// The version at the start of a heading's text, e.g. "1.2.0" in "[1.2.0] - 2024-05-01"
+fn heading_version(text: &str) -> Option<&str> {
+    let first_word = text.split_whitespace().next()?;
+    let version = first_word
+        .trim_start_matches('[')
+        .trim_end_matches(|c: char| c == ']' || c == ':');
+    let is_version = version
+        .trim_start_matches('v')
+        .starts_with(|c: char| c.is_ascii_digit())
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+
+    is_version.then_some(version)
+}
+
+/// The text of some HTML, without its tags. Entities are left as they are, since they can't be
+/// part of a version.
+fn strip_tags(html: &str) -> String {
+    let mut buf = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => buf.push(c),
+            _ => {}
+        }
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::{heading_version, ChangelogPage};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn versions_at_the_start_of_headings() {
+        assert_eq!(heading_version("1.2.0"), Some("1.2.0"));
+        assert_eq!(heading_version("v1.2.0"), Some("v1.2.0"));
+        assert_eq!(heading_version("[1.2.0] - 2024-05-01"), Some("1.2.0"));
+        assert_eq!(
+            heading_version("2.0.0-beta.1+build: the big one"),
+            Some("2.0.0-beta.1+build")
+        );
+        assert_eq!(heading_version("Unreleased"), None);
+        assert_eq!(heading_version("[Unreleased]"), None);
+        assert_eq!(heading_version("version 1.2.0"), None);
+        assert_eq!(heading_version("v"), None);
+        assert_eq!(heading_version("1.2.0/docs"), None);
+        assert_eq!(heading_version(""), None);
+    }
+
+    #[test]
+    fn anchors_for_version_headings() {
+        let page = ChangelogPage::from_html(concat!(
+            "<h1>Changelog</h1>",
+            "<h2>Unreleased</h2>",
+            "<h2><a href=\"https://example.com\">1.2.0</a> - 2024-05-01</h2>",
+            "<h3>Added</h3>",
+            "<h2>v1.1.0</h2>",
+            "<h2>1.1.0</h2>",
+        ));
+
+        assert_eq!(
+            page.html,
+            concat!(
+                "<h1>Changelog</h1>",
+                "<h2>Unreleased</h2>",
+                "<h2 id=\"v1.2.0\"><a href=\"https://example.com\">1.2.0</a> - 2024-05-01</h2>",
+                "<h3>Added</h3>",
+                "<h2 id=\"v1.1.0\">v1.1.0</h2>",
+                // The first heading for a version gets its anchor
+                "<h2>1.1.0</h2>",
+            )
+        );
+    }
+
+    #[test]
+    fn malformed_headings_are_left_alone() {
+        let html = concat!(
+            "<h2>1.0.0",
+            "<h7>1.0.0</h7>",
+            "<hr>",
+            "<header>1.0.0</header>",
+            "<h2",
+        );
+
+        assert_eq!(ChangelogPage::from_html(html).html, html);
+    }
+
+    #[test]
+    fn url_goes_to_the_version_if_it_has_a_heading() {
+        let page = ChangelogPage::from_html("<h2>[1.2.0]</h2>");
+
+        assert_eq!(page.url("/", "1.2.0"), "/changelog/#v1.2.0");
+        assert_eq!(page.url("/pkg/", "v1.2.0"), "/pkg/changelog/#v1.2.0");
+        assert_eq!(page.url("/", "1.3.0"), "/changelog/");
+    }
+}
@@ -0,0 +1,226 @@
+//! Extracts the code blocks under `# Examples` headings in doc comments into standalone
+//! .roc files, so that a package's CI can check that its documented examples still compile.
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+
+/// A .roc file containing one documented example
+pub struct ExampleFile {
+    pub file_name: String,
+    pub contents: String,
+}
+
+/// Makes one file for every example in the module's docs, numbered in the order they appear.
+pub fn example_files(module: &ModuleDocumentation, is_builtin: bool) -> Vec<ExampleFile> {
+    let mut files = Vec::new();
+
+    for entry in module.entries.iter() {
+        let docs = match entry {
+            DocEntry::DetachedDoc(docs) => docs.as_str(),
+            DocEntry::DocDef(doc_def) => match &doc_def.docs {
+                Some(docs) => docs.as_str(),
+                None => continue,
+            },
+        };
+
+        for code in examples_in_markdown(docs) {
+            let header_name = format!("{}Example{}", module.name.replace('.', ""), files.len() + 1);
+            let contents = example_module(&header_name, &module.name, is_builtin, &code);
+
+            files.push(ExampleFile {
+                file_name: format!("{header_name}.roc"),
+                contents,
+            });
+        }
+    }
+
+    files
+}
+
+/// The contents of the Roc code blocks that come after an "Examples" heading,
+/// up until the next heading of the same or a higher level.
+fn examples_in_markdown(markdown: &str) -> Vec<String> {
+    use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+
+    let mut examples = Vec::new();
+    let mut examples_level = None;
+    let mut heading_text = String::new();
+    let mut code: Option<String> = None;
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Heading(..)) => heading_text.clear(),
+            Event::End(Tag::Heading(level, ..)) => {
+                let level = level as usize;
+
+                if heading_text.trim().eq_ignore_ascii_case("examples") {
+                    examples_level = Some(level);
+                } else if matches!(examples_level, Some(examples) if level <= examples) {
+                    examples_level = None;
+                }
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if examples_level.is_some() =>
+            {
+                let lang = info.split_whitespace().next().unwrap_or("");
+
+                if lang.is_empty() || lang == "roc" {
+                    code = Some(String::new());
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some(code) = code.take() {
+                    if !code.trim().is_empty() {
+                        examples.push(code);
+                    }
+                }
+            }
+            Event::Text(text) => match code.as_mut() {
+                Some(code) => code.push_str(&text),
+                None => heading_text.push_str(&text),
+            },
+            Event::Code(text) => heading_text.push_str(&text),
+            _ => {}
+        }
+    }
+
+    examples
+}
+
+/// Turns an example into a module of its own. Examples which already have a module header are
+/// left alone. Otherwise we add an interface header that imports the documented module, and if
+/// the example is an expression rather than some definitions, we give it a name.
+fn example_module(header_name: &str, module_name: &str, is_builtin: bool, code: &str) -> String {
+    let has_header = code.split_whitespace().next().map_or(false, |keyword| {
+        matches!(
+            keyword,
+            "app" | "interface" | "platform" | "hosted" | "package"
+        )
+    });
+
+    if has_header {
+        return code.to_string();
+    }
+
+    let mut buf = String::new();
+
+    buf.push_str(&format!("interface {header_name}\n"));
+    buf.push_str("    exposes []\n");
+
+    if is_builtin {
+        buf.push_str("    imports []\n\n");
+    } else {
+        buf.push_str(&format!("    imports [{module_name}]\n\n"));
+    }
+
+    if is_definitions(code) {
+        buf.push_str(code);
+    } else {
+        buf.push_str("example =\n");
+
+        for line in code.lines() {
+            if !line.is_empty() {
+                buf.push_str("    ");
+                buf.push_str(line);
+            }
+            buf.push('\n');
+        }
+    }
+
+    buf
+}
+
+/// Whether the example is only definitions, so it can go in a module as it is. Its last item
+/// (the last unindented line) has to start a definition or type annotation, e.g. `foo = ...`.
+/// Definitions followed by an expression have to be wrapped in a definition of their own.
+fn is_definitions(code: &str) -> bool {
+    let last_item = code
+        .lines()
+        .rev()
+        .find(|line| line.starts_with(|c: char| !c.is_whitespace()) && !line.starts_with('#'));
+
+    match last_item {
+        Some(line) => {
+            let name_len = line
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(line.len());
+            let starts_lowercase = line.starts_with(|c: char| c.is_ascii_lowercase());
+            let rest = line[name_len..].trim_start();
+
+            starts_lowercase
+                && ((rest.starts_with('=') && !rest.starts_with("==")) || rest.starts_with(':'))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{example_module, examples_in_markdown, is_definitions};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn definitions() {
+        assert!(is_definitions("x = 1\n"));
+        assert!(is_definitions(
+            "add : I64, I64 -> I64\nadd = \\a, b ->\n    a + b\n"
+        ));
+        assert!(is_definitions("x = 1\n\n# The answer\n"));
+    }
+
+    #[test]
+    fn expressions() {
+        assert!(!is_definitions("1 + 1\n"));
+        assert!(!is_definitions("x == 1\n"));
+        assert!(!is_definitions("List.map [1, 2] \\x ->\n    x = 1\n"));
+        assert!(!is_definitions(""));
+    }
+
+    #[test]
+    fn definitions_then_an_expression() {
+        assert!(!is_definitions("x = 1\ny = 2\n\nx + y\n"));
+        assert!(!is_definitions(
+            "f : Str -> Str\nf = \\s -> s\n\nf \"hi\"\n"
+        ));
+    }
+
+    #[test]
+    fn trailing_expression_gets_wrapped() {
+        assert_eq!(
+            example_module("ListExample1", "List", true, "x = [1]\n\nList.len x\n"),
+            concat!(
+                "interface ListExample1\n",
+                "    exposes []\n",
+                "    imports []\n\n",
+                "example =\n",
+                "    x = [1]\n",
+                "\n",
+                "    List.len x\n",
+            )
+        );
+    }
+
+    #[test]
+    fn definitions_stay_as_they_are() {
+        assert_eq!(
+            example_module("JsonExample2", "Json", false, "x = Json.null\n"),
+            "interface JsonExample2\n    exposes []\n    imports [Json]\n\nx = Json.null\n"
+        );
+    }
+
+    #[test]
+    fn only_roc_code_under_examples() {
+        let markdown = concat!(
+            "```\nbefore = 1\n```\n\n",
+            "## Examples\n\n",
+            "```\na = 1\n```\n\n",
+            "```json\n{}\n```\n\n",
+            "```roc\nb\n```\n\n",
+            "## Next\n\n",
+            "```\nafter = 1\n```\n",
+        );
+
+        assert_eq!(
+            examples_in_markdown(markdown),
+            vec!["a = 1\n".to_string(), "b\n".to_string()]
+        );
+    }
+}
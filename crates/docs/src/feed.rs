@@ -0,0 +1,268 @@
+//! An Atom feed of the API changes since the previous release, for people who want to follow how
+//! a package's API evolves in their feed reader. It's written as changes.xml at the root of the
+//! docs, so its URL stays the same from one version to the next, and each change links to the
+//! entry in the docs of the version it was made in.
+use crate::api::ApiChange;
+use crate::metadata::{canonical_url, escape_attr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The file the feed is written to, at the root of the docs
+pub const CHANGES_XML: &str = "changes.xml";
+
+/// Feed readers need absolute URLs, so the feed needs both of them.
+pub struct FeedUrls<'a> {
+    /// The docs' canonical URL, without the version
+    pub root: &'a str,
+    /// The canonical URL of the version being documented
+    pub pages: &'a str,
+}
+
+/// The feed of `changes`, with the newest version's changes in it. `title` is
+/// [DocsStrings::changes_feed_title](crate::DocsStrings::changes_feed_title) with the package
+/// name filled in.
+pub fn render_changes_feed(
+    urls: &FeedUrls,
+    title: &str,
+    version: Option<&str>,
+    changes: &[ApiChange],
+) -> String {
+    let feed_url = format!("{}/{}", urls.root.trim_end_matches('/'), CHANGES_XML);
+    let updated = rfc3339(build_time());
+    let mut buf = String::new();
+
+    buf.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    buf.push('\n');
+    buf.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    buf.push('\n');
+    push_element(&mut buf, 1, "id", &feed_url);
+    push_element(&mut buf, 1, "title", title);
+    push_element(&mut buf, 1, "updated", &updated);
+    buf.push_str(&format!(
+        "  <link rel=\"self\" href=\"{}\"/>\n",
+        escape_attr(&feed_url)
+    ));
+    buf.push_str(&format!(
+        "  <link href=\"{}/\"/>\n",
+        escape_attr(urls.pages.trim_end_matches('/'))
+    ));
+
+    for change in changes {
+        let title = match version {
+            Some(version) => format!("{version}: {change}"),
+            None => change.to_string(),
+        };
+
+        buf.push_str("  <entry>\n");
+        push_element(
+            &mut buf,
+            2,
+            "id",
+            &format!("{}#{}", feed_url, change_id(version, change)),
+        );
+        push_element(&mut buf, 2, "title", &title);
+        push_element(&mut buf, 2, "updated", &updated);
+        buf.push_str("    <author><name>roc docs</name></author>\n");
+        if let Some(link) = change_link(urls.pages, change) {
+            buf.push_str(&format!("    <link href=\"{}\"/>\n", escape_attr(&link)));
+        }
+        buf.push_str(&format!(
+            "    <category term=\"{}\"/>\n",
+            if change.is_breaking() {
+                "breaking"
+            } else {
+                "additive"
+            }
+        ));
+        if let ApiChange::SignatureChanged { name, old, new, .. } = change {
+            push_element(
+                &mut buf,
+                2,
+                "content",
+                &format!("Before: {name} {old}\nAfter: {name} {new}"),
+            );
+        }
+        buf.push_str("  </entry>\n");
+    }
+
+    buf.push_str("</feed>\n");
+
+    buf
+}
+
+fn push_element(buf: &mut String, depth: usize, tag: &str, text: &str) {
+    buf.push_str(&"  ".repeat(depth));
+    buf.push_str(&format!("<{tag}>{}</{tag}>\n", escape_attr(text)));
+}
+
+/// What makes a change different from every other change in the feed, for its entry's id
+fn change_id(version: Option<&str>, change: &ApiChange) -> String {
+    let id = match change {
+        ApiChange::ModuleAdded { module } => format!("module-added/{module}"),
+        ApiChange::ModuleRemoved { module } => format!("module-removed/{module}"),
+        ApiChange::EntryAdded { module, entry } => format!("added/{module}.{}", entry.name),
+        ApiChange::EntryRemoved { module, entry } => format!("removed/{module}.{}", entry.name),
+        ApiChange::SignatureChanged { module, name, .. } => format!("changed/{module}.{name}"),
+    };
+
+    match version {
+        Some(version) => format!("{version}/{id}"),
+        None => id,
+    }
+}
+
+/// Where the change can be seen in the docs. What was removed isn't there anymore.
+fn change_link(pages_url: &str, change: &ApiChange) -> Option<String> {
+    match change {
+        ApiChange::ModuleAdded { module } => Some(canonical_url(pages_url, module)),
+        ApiChange::EntryAdded { module, entry } => Some(format!(
+            "{}#{}",
+            canonical_url(pages_url, module),
+            entry.name
+        )),
+        ApiChange::SignatureChanged { module, name, .. } => {
+            Some(format!("{}#{}", canonical_url(pages_url, module), name))
+        }
+        ApiChange::EntryRemoved { module, .. } => Some(canonical_url(pages_url, module)),
+        ApiChange::ModuleRemoved { .. } => None,
+    }
+}
+
+/// When these docs were built, in seconds since the Unix epoch. Like other reproducible build
+/// tools, this uses `SOURCE_DATE_EPOCH` if it's set, so that rebuilding gives the same feed.
+fn build_time() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|seconds| seconds.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs())
+        })
+}
+
+/// A time like 2023-04-05T06:07:08Z, which is what Atom expects
+fn rfc3339(seconds_since_epoch: u64) -> String {
+    let days = (seconds_since_epoch / 86_400) as i64;
+    let seconds_of_day = seconds_since_epoch % 86_400;
+
+    // Howard Hinnant's civil_from_days, for the proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{change_id, change_link, render_changes_feed, rfc3339, FeedUrls};
+    use crate::api::{ApiChange, ApiEntry};
+    use pretty_assertions::assert_eq;
+
+    const URLS: FeedUrls = FeedUrls {
+        root: "https://example.com/docs/",
+        pages: "https://example.com/docs/1.2.0",
+    };
+
+    fn entry(name: &str, signature: &str) -> ApiEntry {
+        ApiEntry {
+            name: name.to_string(),
+            signature: signature.to_string(),
+        }
+    }
+
+    #[test]
+    fn rfc3339_times() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(1_680_674_828), "2023-04-05T06:07:08Z");
+        // Leap days, and the end of a leap year
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_609_459_199), "2020-12-31T23:59:59Z");
+        assert_eq!(rfc3339(4_107_542_400), "2100-03-01T00:00:00Z");
+    }
+
+    #[test]
+    fn feed_text_is_escaped() {
+        let changes = vec![ApiChange::SignatureChanged {
+            module: "Dict".to_string(),
+            name: "get".to_string(),
+            old: ": Dict k v, k -> Result v [KeyNotFound]".to_string(),
+            new: ": Dict k v, k -> Result v [KeyNotFound, Other] where k has Eq & Hash".to_string(),
+        }];
+
+        let feed = render_changes_feed(&URLS, "API changes in <Dict & co>", None, &changes);
+
+        assert!(feed.contains("  <title>API changes in &lt;Dict &amp; co&gt;</title>\n"));
+        assert!(feed.contains("where k has Eq &amp; Hash"));
+        assert!(!feed.contains("Eq & Hash"));
+        assert!(feed.contains("    <category term=\"breaking\"/>\n"));
+        assert!(feed.contains(
+            "    <content>Before: get : Dict k v, k -&gt; Result v [KeyNotFound]\nAfter: "
+        ));
+    }
+
+    #[test]
+    fn feed_links() {
+        let changes = vec![ApiChange::ModuleAdded {
+            module: "Json.Decode".to_string(),
+        }];
+
+        let feed = render_changes_feed(&URLS, "Changes", Some("1.2.0"), &changes);
+
+        assert!(feed.starts_with(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+            "  <id>https://example.com/docs/changes.xml</id>\n",
+            "  <title>Changes</title>\n",
+        )));
+        assert!(
+            feed.contains("  <link rel=\"self\" href=\"https://example.com/docs/changes.xml\"/>\n")
+        );
+        assert!(feed.contains("  <link href=\"https://example.com/docs/1.2.0/\"/>\n"));
+        assert!(feed.contains(
+            "    <id>https://example.com/docs/changes.xml#1.2.0/module-added/Json.Decode</id>\n"
+        ));
+        assert!(feed.contains("    <title>1.2.0: module Json.Decode was added</title>\n"));
+        assert!(feed.contains(r#"<link href="https://example.com/docs/1.2.0/Json/Decode/"/>"#));
+        assert!(feed.contains("    <category term=\"additive\"/>\n"));
+        assert!(feed.ends_with("  </entry>\n</feed>\n"));
+    }
+
+    #[test]
+    fn ids_and_links_of_changes() {
+        let added = ApiChange::EntryAdded {
+            module: "Str".to_string(),
+            entry: entry("trim", ": Str -> Str"),
+        };
+        let removed = ApiChange::ModuleRemoved {
+            module: "Old".to_string(),
+        };
+
+        assert_eq!(change_id(Some("1.2.0"), &added), "1.2.0/added/Str.trim");
+        assert_eq!(change_id(None, &removed), "module-removed/Old");
+        assert_eq!(
+            change_link(URLS.pages, &added).as_deref(),
+            Some("https://example.com/docs/1.2.0/Str/#trim")
+        );
+        assert_eq!(change_link(URLS.pages, &removed), None);
+    }
+}
@@ -0,0 +1,258 @@
+//! Highlighting for fenced code blocks in doc comments. Roc code goes through roc_highlight.
+//! Other languages are highlighted by syntect when the `syntect` feature is enabled, and shown
+//! as plain text otherwise, rather than being misread as Roc.
+use crate::metadata::escape_attr;
+
+/// The language named by a fence's info string, e.g. "json" for ```json
+fn fence_language(info: &str) -> &str {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+}
+
+/// Fences with no language are Roc, as are the ones tagged with Roc-specific attributes
+fn is_roc(language: &str) -> bool {
+    matches!(language, "" | "roc") || is_roc_attribute(language)
+}
+
+/// Attributes that can go in a Roc fence's info string, e.g. ```roc,repl
+fn is_roc_attribute(tag: &str) -> bool {
+    matches!(tag, "repl" | "unchecked")
+}
+
+pub fn highlight_fenced_code(info: &str, code: &str) -> String {
+    let language = fence_language(info);
+
+    if is_roc(language) {
+        roc_highlight::highlight_roc_code(code)
+    } else {
+        highlight_other_language(language, code)
+    }
+}
+
+#[cfg(feature = "syntect")]
+thread_local! {
+    static SYNTAXES: syntect::parsing::SyntaxSet =
+        syntect::parsing::SyntaxSet::load_defaults_newlines();
+}
+
+#[cfg(feature = "syntect")]
+fn highlight_other_language(language: &str, code: &str) -> String {
+    use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+    use syntect::util::LinesWithEndings;
+
+    SYNTAXES.with(|syntaxes| {
+        let syntax = match syntaxes.find_syntax_by_token(language) {
+            Some(syntax) => syntax,
+            None => return plain_code(code),
+        };
+
+        // The spaced class names are TextMate scopes like "string quoted",
+        // which styles.css already has colors for.
+        let mut generator =
+            ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, ClassStyle::Spaced);
+
+        for line in LinesWithEndings::from(code) {
+            if generator
+                .parse_html_for_line_which_includes_newline(line)
+                .is_err()
+            {
+                return plain_code(code);
+            }
+        }
+
+        format!("<pre><samp>{}</samp></pre>", generator.finalize())
+    })
+}
+
+#[cfg(not(feature = "syntect"))]
+fn highlight_other_language(_language: &str, code: &str) -> String {
+    plain_code(code)
+}
+
+fn plain_code(code: &str) -> String {
+    format!("<pre><samp>{}</samp></pre>", escape_attr(code))
+}
+
+/// Whether a fence's language is one that gets highlighted. Without the `syntect` feature
+/// every language is shown as plain text, so there's no telling which ones are typos.
+#[cfg(feature = "syntect")]
+fn is_known_language(language: &str) -> bool {
+    SYNTAXES.with(|syntaxes| syntaxes.find_syntax_by_token(language).is_some())
+}
+
+#[cfg(not(feature = "syntect"))]
+fn is_known_language(_language: &str) -> bool {
+    true
+}
+
+/// Something wrong with a fenced code block, which would make the docs look wrong rather than
+/// stopping them from being generated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenceProblem {
+    /// A fence that's never closed, so the rest of the doc comment is code. `line` is where it
+    /// opens, counting from 1 at the start of the doc comment.
+    Unclosed { line: usize },
+    /// A tag in a fence's info string that's neither a known language nor a Roc attribute
+    UnknownTag { line: usize, tag: String },
+}
+
+/// Finds the fences in a doc comment the same way CommonMark does, and reports their problems
+pub fn fence_problems(markdown: &str) -> Vec<FenceProblem> {
+    let mut problems = Vec::new();
+    // The fence character, length, and line of the fence that's currently open
+    let mut open_fence: Option<(char, usize, usize)> = None;
+
+    for (index, line) in markdown.lines().enumerate() {
+        let (fence_char, fence_len, info) = match parse_fence(line) {
+            Some(fence) => fence,
+            None => continue,
+        };
+
+        match open_fence {
+            Some((open_char, open_len, _)) => {
+                if fence_char == open_char && fence_len >= open_len && info.trim().is_empty() {
+                    open_fence = None;
+                }
+            }
+            // Backtick fences can't have backticks in their info string, so this is inline code
+            None if fence_char == '`' && info.contains('`') => {}
+            None => {
+                let line = index + 1;
+
+                problems.extend(unknown_tags(info).map(|tag| FenceProblem::UnknownTag {
+                    line,
+                    tag: tag.to_string(),
+                }));
+
+                open_fence = Some((fence_char, fence_len, line));
+            }
+        }
+    }
+
+    if let Some((_, _, line)) = open_fence {
+        problems.push(FenceProblem::Unclosed { line });
+    }
+
+    problems
+}
+
+/// The fence character, its length, and the info string after it, if the line is a code fence
+fn parse_fence(line: &str) -> Option<(char, usize, &str)> {
+    let rest = line.trim_start_matches(' ');
+
+    if line.len() - rest.len() > 3 {
+        // This is an indented code block
+        return None;
+    }
+
+    let fence_char = rest.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let fence_len = rest.len() - rest.trim_start_matches(fence_char).len();
+
+    (fence_len >= 3).then(|| (fence_char, fence_len, &rest[fence_len..]))
+}
+
+/// The tags in a fence's info string which don't mean anything to us. Only Roc fences are
+/// checked past the language, since other languages' fences can have their own attributes.
+fn unknown_tags(info: &str) -> impl Iterator<Item = &str> {
+    let info = info.trim();
+    let roc = is_roc(fence_language(info));
+
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|tag| !tag.is_empty())
+        .enumerate()
+        .filter(move |(index, tag)| {
+            if *index == 0 {
+                !roc && !is_known_language(tag)
+            } else {
+                roc && !(*tag == "roc" || is_roc_attribute(tag))
+            }
+        })
+        .map(|(_, tag)| tag)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fence_problems, highlight_fenced_code, FenceProblem};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn closed_fences() {
+        let markdown = "Intro\n\n```\nx = 1\n```\n\n```roc,repl\n1 + 1\n```\n\n~~~\ny\n~~~";
+
+        assert_eq!(fence_problems(markdown), vec![]);
+    }
+
+    #[test]
+    fn unclosed_fence() {
+        let markdown = "Intro\n\n```roc\nx = 1\n\nMore prose";
+
+        assert_eq!(
+            fence_problems(markdown),
+            vec![FenceProblem::Unclosed { line: 3 }]
+        );
+    }
+
+    #[test]
+    fn closed_by_a_longer_fence_only() {
+        // A shorter fence or one with the other character is part of the code
+        let markdown = "````\n```\n~~~~\n`````\n```\nopen";
+
+        assert_eq!(
+            fence_problems(markdown),
+            vec![FenceProblem::Unclosed { line: 5 }]
+        );
+    }
+
+    #[test]
+    fn closing_fence_has_no_info() {
+        let markdown = "```\nx\n``` roc\n";
+
+        assert_eq!(
+            fence_problems(markdown),
+            vec![FenceProblem::Unclosed { line: 1 }]
+        );
+    }
+
+    #[test]
+    fn unknown_roc_tag() {
+        let markdown = "```roc,rpel\n1 + 1\n```\n\n```roc unchecked\nx\n```";
+
+        assert_eq!(
+            fence_problems(markdown),
+            vec![FenceProblem::UnknownTag {
+                line: 1,
+                tag: "rpel".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn other_languages_keep_their_attributes() {
+        let markdown = "```json,ignore\n{}\n```";
+
+        assert_eq!(fence_problems(markdown), vec![]);
+    }
+
+    #[test]
+    fn inline_code_and_indented_code_are_not_fences() {
+        let markdown = "```a`b``` is inline code.\n\n    ```\n    indented\n";
+
+        assert_eq!(fence_problems(markdown), vec![]);
+    }
+
+    #[test]
+    fn other_language_is_escaped() {
+        #[cfg(not(feature = "syntect"))]
+        assert_eq!(
+            highlight_fenced_code("html", "<a href=\"x\">&</a>"),
+            "<pre><samp>&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;</samp></pre>"
+        );
+
+        // syntect escapes too, whichever way it highlights the code
+        let html = highlight_fenced_code("nosuchlanguage", "<script>");
+
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+}
@@ -0,0 +1,45 @@
+//! Finds the hand-written markdown that a package keeps next to its source, in a `docs` directory
+//! beside its main .roc file:
+//!
+//! - `docs/index.md` becomes the landing page at the root of the site.
+//! - `docs/Json.Decode.md` is appended to the `Json.Decode` module's page, after its entries.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const GUIDES_DIR: &str = "docs";
+const LANDING_PAGE: &str = "index.md";
+
+/// The markdown files of a package
+pub struct Guides {
+    dir: PathBuf,
+}
+
+impl Guides {
+    pub fn for_root_file(root_file: &Path) -> Self {
+        let package_dir = root_file.parent().unwrap_or_else(|| Path::new("."));
+
+        Self {
+            dir: package_dir.join(GUIDES_DIR),
+        }
+    }
+
+    pub fn landing_page(&self) -> Option<String> {
+        read_markdown(&self.dir.join(LANDING_PAGE))
+    }
+
+    pub fn module_supplement(&self, module_name: &str) -> Option<String> {
+        read_markdown(&self.dir.join(format!("{module_name}.md")))
+    }
+}
+
+pub(crate) fn read_markdown(path: &Path) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(markdown) => Some(markdown),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => panic!(
+            "TODO gracefully handle failing to read {}: {}",
+            path.display(),
+            err
+        ),
+    }
+}
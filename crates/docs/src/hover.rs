@@ -0,0 +1,212 @@
+//! Hover cards for editors, written next to the generated docs as hover.jsonl, so that a language
+//! server can show the docs of a symbol without the whole HTML site.
+//!
+//! The file is JSON Lines. The first line is a header, `{"version":1}`, and each line after it is
+//! the card of one exposed entry:
+//!
+//! - `symbol`: the fully qualified name, e.g. `Str.concat`
+//! - `signature`: same as in api.json, e.g. `: Str, Str -> Str`
+//! - `doc`: the first paragraph of the doc comment, as markdown, or null if there's none
+//! - `url`: relative to the file, e.g. `Str#concat`
+//! - `deprecated`: null, or the text after the doc comment's `@deprecated` line, which may be empty
+//!
+//! Any change to the format must bump `HOVER_VERSION`, so that editors can ignore a file they
+//! don't understand rather than show wrong cards.
+use crate::api::signature;
+use crate::is_hidden;
+use roc_collections::VecSet;
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+use roc_module::symbol::Symbol;
+use serde::Serialize;
+
+/// The file the cards are written to, next to the modules' directories.
+pub const HOVER_JSONL: &str = "hover.jsonl";
+
+pub const HOVER_VERSION: u32 = 1;
+
+/// A line in a doc comment starting with this marks the entry as deprecated. The rest of the line
+/// says why, or what to use instead.
+const DEPRECATED_MARKER: &str = "@deprecated";
+
+#[derive(Debug, Serialize)]
+struct HoverHeader {
+    version: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct HoverCard {
+    symbol: String,
+    signature: String,
+    doc: Option<String>,
+    url: String,
+    deprecated: Option<String>,
+}
+
+/// The cards of the exposed entries of every module, leaving out the hidden ones just like the
+/// docs do.
+pub fn render_hover_jsonl<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    modules: I,
+    all_exposed_symbols: &VecSet<Symbol>,
+) -> String {
+    let header = HoverHeader {
+        version: HOVER_VERSION,
+    };
+    let mut buf = to_json_line(&header);
+
+    for module in modules {
+        for entry in module.entries.iter() {
+            match entry {
+                DocEntry::DocDef(doc_def)
+                    if all_exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) =>
+                {
+                    let docs = doc_def.docs.as_deref().unwrap_or("");
+
+                    let card = HoverCard {
+                        symbol: format!("{}.{}", module.name, doc_def.name),
+                        signature: signature(&doc_def.type_vars, &doc_def.type_annotation),
+                        doc: first_paragraph(docs),
+                        url: format!("{}#{}", module.name, doc_def.name),
+                        deprecated: deprecation(docs),
+                    };
+
+                    buf.push_str(&to_json_line(&card));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    buf
+}
+
+fn to_json_line<T: Serialize>(value: &T) -> String {
+    let mut line = serde_json::to_string(value).expect("a hover card is always valid JSON");
+    line.push('\n');
+    line
+}
+
+/// The markdown of the first paragraph, skipping any headings or code blocks before it
+fn first_paragraph(markdown: &str) -> Option<String> {
+    use pulldown_cmark::{Event, Parser, Tag};
+
+    Parser::new(markdown)
+        .into_offset_iter()
+        .find_map(|(event, range)| match event {
+            Event::Start(Tag::Paragraph) => {
+                let paragraph = markdown[range].trim();
+                let is_deprecation = paragraph.starts_with(DEPRECATED_MARKER);
+
+                (!is_deprecation).then(|| paragraph.to_string())
+            }
+            _ => None,
+        })
+}
+
+/// The text after the `@deprecated` marker, if the doc comment has one
+fn deprecation(markdown: &str) -> Option<String> {
+    markdown.lines().find_map(|line| {
+        let reason = line.trim_start().strip_prefix(DEPRECATED_MARKER)?;
+
+        if reason.is_empty() || reason.starts_with(char::is_whitespace) {
+            Some(reason.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{deprecation, first_paragraph, render_hover_jsonl};
+    use pretty_assertions::assert_eq;
+    use roc_can::scope::Scope;
+    use roc_collections::VecSet;
+    use roc_load::docs::{DocDef, DocEntry, ModuleDocumentation, TypeAnnotation};
+    use roc_module::symbol::{Interns, Symbol};
+
+    #[test]
+    fn first_paragraph_of_the_docs() {
+        assert_eq!(
+            first_paragraph("Joins two strings.\nIn order.\n\nMore."),
+            Some("Joins two strings.\nIn order.".to_string())
+        );
+        assert_eq!(
+            first_paragraph("# Heading\n\n```\ncode\n```\n\nThe `first` *one*.\n"),
+            Some("The `first` *one*.".to_string())
+        );
+        assert_eq!(first_paragraph("@deprecated Use other\n"), None);
+        assert_eq!(first_paragraph(""), None);
+    }
+
+    #[test]
+    fn deprecation_reasons() {
+        assert_eq!(
+            deprecation("Joins.\n\n@deprecated Use `Str.joinWith`\n"),
+            Some("Use `Str.joinWith`".to_string())
+        );
+        assert_eq!(deprecation("  @deprecated\n"), Some(String::new()));
+        assert_eq!(deprecation("@deprecatedly\n"), None);
+        assert_eq!(deprecation("Not deprecated.\n"), None);
+    }
+
+    #[test]
+    fn cards_of_exposed_entries() {
+        let mut interns = Interns::default();
+        let home = interns.module_id(&"Str".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(home);
+        let mut exposed = VecSet::default();
+        let mut entries = vec![DocEntry::DetachedDoc("Strings.".to_string())];
+
+        for (name, docs, is_exposed) in [
+            (
+                "concat",
+                Some("Joins \"two\" strings.\n\n@deprecated Use `joinWith`"),
+                true,
+            ),
+            ("hidden", Some("@hidden"), true),
+            ("internal", None, false),
+            ("empty", None, true),
+        ] {
+            let symbol = Symbol::new(home, ident_ids.add_str(name));
+
+            if is_exposed {
+                exposed.insert(symbol);
+            }
+
+            entries.push(DocEntry::DocDef(DocDef {
+                name: name.to_string(),
+                symbol,
+                type_vars: Vec::new(),
+                type_annotation: TypeAnnotation::Apply {
+                    name: "Str".to_string(),
+                    parts: Vec::new(),
+                },
+                docs: docs.map(String::from),
+                implements: Vec::new(),
+                referenced_types: Vec::new(),
+                args: Vec::new(),
+                source: String::new(),
+                reexported_from: None,
+            }));
+        }
+
+        let module = ModuleDocumentation {
+            name: "Str".to_string(),
+            entries,
+            scope: Scope::new(home, Default::default(), Default::default()),
+            exposed_symbols: exposed.clone(),
+        };
+
+        assert_eq!(
+            render_hover_jsonl([module].iter(), &exposed),
+            concat!(
+                "{\"version\":1}\n",
+                "{\"symbol\":\"Str.concat\",\"signature\":\": Str\",",
+                "\"doc\":\"Joins \\\"two\\\" strings.\",\"url\":\"Str#concat\",",
+                "\"deprecated\":\"Use `joinWith`\"}\n",
+                "{\"symbol\":\"Str.empty\",\"signature\":\": Str\",",
+                "\"doc\":null,\"url\":\"Str#empty\",\"deprecated\":null}\n",
+            )
+        );
+    }
+}
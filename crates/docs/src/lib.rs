@@ -4,30 +4,334 @@ extern crate pulldown_cmark;
 extern crate roc_load;
 use bumpalo::Bump;
 use roc_can::scope::Scope;
-use roc_collections::VecSet;
-use roc_load::docs::{DocEntry, TypeAnnotation};
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::{DocDef, DocEntry, TypeAnnotation};
 use roc_load::docs::{ModuleDocumentation, RecordField};
 use roc_load::{ExecutionMode, LoadConfig, LoadedModule, LoadingProblem, Threading};
-use roc_module::symbol::{Interns, Symbol};
+use roc_module::symbol::{IdentIds, Interns, ModuleId, Symbol};
 use roc_packaging::cache::{self, RocCacheDir};
 use roc_parse::ident::{parse_ident, Accessor, Ident};
 use roc_parse::state::State;
 use roc_region::all::Region;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+mod abilities;
+mod api;
+mod arguments;
+mod changelog;
+mod examples;
+mod feed;
+mod fences;
+mod guides;
+mod hover;
+mod links;
+mod lint;
+mod math;
+mod metadata;
+mod module_filter;
+mod normalize;
+mod opensearch;
+mod ordering;
+mod partials;
+mod plain_text;
+mod platform;
+mod redirects;
+mod reexports;
+mod references;
+mod search_index;
+mod see_also;
+mod single_file;
+mod strings;
+mod tags;
+mod versions;
+mod workspace;
+
+pub use api::{diff_api, render_api_diff, ApiChange, ApiSnapshot};
+pub use links::BrokenLink;
+pub use lint::{lint_docs, DocProblem, DocProblemKind};
+use metadata::escape_attr;
+pub use ordering::EntryOrder;
+pub use partials::{read_html_partial, HtmlPartials};
+pub use redirects::read_redirects;
+use strings::with_name;
+pub use strings::DocsStrings;
+pub use versions::check_version;
+
 const BUILD_DIR: &str = "./generated-docs";
 
 const LINK_SVG: &str = include_str!("./static/link.svg");
 
-pub fn generate_docs_html(root_file: PathBuf) {
+/// Doc comments starting with this are left out of the docs, e.g. for things that must be
+/// exposed for a platform's glue code but aren't meant to be used directly.
+const HIDDEN_MARKER: &str = "@hidden";
+
+/// Options for how the docs get generated.
+#[derive(Debug, Clone, Default)]
+pub struct DocsConfig {
+    /// The absolute URL the docs will be published at, e.g. "https://roc-lang.org/builtins".
+    /// When set, every page gets a canonical URL and a sitemap.xml is generated.
+    pub canonical_base_url: Option<String>,
+    /// The text around the documentation itself, e.g. for translating it into another language.
+    pub strings: DocsStrings,
+    /// The version being documented, e.g. "1.2.0". When set, the pages go in a directory named
+    /// after it, e.g. "/1.2.0/Str", and a versions.json listing every version is generated.
+    pub version: Option<String>,
+    /// The versions which have already been published, newest first. Each page links to its
+    /// module in these versions.
+    pub published_versions: Vec<String>,
+    /// The order of the entries in each module, on its page and in the sidebar. A module can
+    /// choose a different order with an `@order` line in its doc comment.
+    pub entry_order: EntryOrder,
+    /// Also write the whole package's docs to one self-contained docs.html, with everything it
+    /// needs inlined, e.g. for reading offline or attaching to a release.
+    pub single_file: bool,
+    /// Put each entry's highlighted source code under it, in a collapsed section. This makes the
+    /// pages quite a bit bigger, so it's off by default.
+    pub embed_source: bool,
+    /// Glob patterns for the modules to document, e.g. "Json.*". When empty, every module is
+    /// documented.
+    pub include_modules: Vec<String>,
+    /// Glob patterns for modules to leave out, even if they match `include_modules`, e.g.
+    /// "Internal*". Doc links to their entries render as plain code.
+    pub exclude_modules: Vec<String>,
+    /// Render TeX math in doc comments, written as `$x^2$` inline, `$$x^2$$` on its own, or in a
+    /// ```math fence. Pages with math load KaTeX from a CDN to render it. The single-file docs
+    /// don't, so they show the TeX as it was written.
+    pub math: bool,
+    /// The api.json of an earlier release. Entries that have moved to another module since then
+    /// are listed in a redirects.json, and links to where they used to be lead to where they are.
+    /// With a `canonical_base_url`, the changes since then also go in an Atom feed, changes.xml.
+    pub previous_api: Option<ApiSnapshot>,
+    /// Moves to redirect that can't be found from `previous_api`, e.g. renames, from old qualified
+    /// names to new ones like "Json.decode" to "Decode.fromJson". See [read_redirects].
+    pub redirects: BTreeMap<String, String>,
+    /// The site's own HTML to add to every page, e.g. its navigation or an analytics snippet.
+    /// See [read_html_partial].
+    pub html_partials: HtmlPartials,
+}
+
+/// Generates the docs into ./generated-docs, then checks every link within them, returning the
+/// ones which don't go anywhere.
+pub fn generate_docs_html(root_file: PathBuf, config: DocsConfig) -> Vec<BrokenLink> {
     let build_dir = Path::new(BUILD_DIR);
-    let loaded_module = load_module_for_docs(root_file);
 
-    // TODO get these from the platform's source file rather than hardcoding them!
-    let package_name = "Documentation".to_string();
-    let version = String::new();
+    exit_on_invalid_versions(&config);
+
+    let mut loaded_module = load_module_for_docs(root_file.clone());
+
+    let left_out = prepare_modules(&mut loaded_module, &config);
+    create_build_dir(build_dir, &config);
+
+    let root_url = base_url(None);
+    let package = PackageDir {
+        // TODO get this from the platform's source file rather than hardcoding it!
+        name: "Documentation".to_string(),
+        dir: build_dir.to_path_buf(),
+        root_url: root_url.clone(),
+        canonical_root_url: config.canonical_base_url.clone(),
+    };
+    let generated = generate_package_docs(
+        &root_file,
+        &loaded_module,
+        &left_out,
+        &package,
+        &config,
+        VecMap::default(),
+    );
+
+    println!("🎉 Docs generated in {}", build_dir.display());
+
+    check_generated_links(build_dir, &root_url, &generated.other_versions)
+}
+
+/// Generates the docs of every package in `workspace_dir` into ./generated-docs, each in a
+/// directory named after where it is in the workspace, with an index page listing them. Then
+/// checks every link within them, returning the ones which don't go anywhere.
+pub fn generate_workspace_docs_html(workspace_dir: &Path, config: DocsConfig) -> Vec<BrokenLink> {
+    let build_dir = Path::new(BUILD_DIR);
+
+    exit_on_invalid_versions(&config);
+
+    let packages = workspace::find_packages(workspace_dir);
+
+    if packages.is_empty() {
+        eprintln!(
+            "There are no packages in {}, so there's nothing to document.",
+            workspace_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    let root_url = base_url(None);
+    let package_dirs: Vec<PackageDir> = packages
+        .iter()
+        .map(|package| PackageDir {
+            name: package.name.clone(),
+            dir: build_dir.join(&package.name),
+            root_url: format!("{}{}/", root_url, package.name),
+            canonical_root_url: config
+                .canonical_base_url
+                .as_deref()
+                .map(|url| format!("{}/{}", url.trim_end_matches('/'), package.name)),
+        })
+        .collect();
+
+    // Every package is loaded before any docs are generated, so that links to the packages it
+    // depends on can be checked against what they expose
+    let (loaded_modules, left_out_modules): (Vec<LoadedModule>, Vec<VecSet<ModuleId>>) = packages
+        .iter()
+        .map(|package| {
+            let mut loaded_module = load_module_for_docs(package.root_file.clone());
+            let left_out = prepare_modules(&mut loaded_module, &config);
+
+            (loaded_module, left_out)
+        })
+        .unzip();
+
+    let documented: Vec<workspace::DocumentedPackage> = packages
+        .iter()
+        .zip(package_dirs.iter())
+        .zip(loaded_modules.iter())
+        .map(|((package, package_dir), loaded_module)| {
+            workspace::DocumentedPackage::new(
+                package,
+                package_dir.base_url(config.version.as_deref()),
+                loaded_module,
+            )
+        })
+        .collect();
+
+    create_build_dir(build_dir, &config);
+
+    let mut other_versions = Vec::new();
+    let mut index_entries = Vec::new();
+    let mut search_index =
+        search_index::SearchIndex::from_modules(&root_url, std::iter::empty(), &VecSet::default());
+
+    for (index, package) in packages.iter().enumerate() {
+        let loaded_module = &loaded_modules[index];
+        let package_dir = &package_dirs[index];
+        let links = workspace::links_to_other_packages(loaded_module, &documented, index);
+        let generated = generate_package_docs(
+            &package.root_file,
+            loaded_module,
+            &left_out_modules[index],
+            package_dir,
+            &config,
+            links,
+        );
+
+        let package_url = package_dir.base_url(config.version.as_deref());
+        let mut package_search_index = search_index::SearchIndex::from_modules(
+            &root_url,
+            loaded_module.docs_by_module.values(),
+            &documented[index].exposed_symbols,
+        );
+        package_search_index.map_urls(|url| format!("{package_url}{url}"));
+        search_index.append(package_search_index);
+
+        other_versions.extend(generated.other_versions);
+        index_entries.push(workspace::IndexEntry {
+            name: package.name.clone(),
+            url: generated.home_url,
+            description: package.description.clone(),
+        });
+    }
+
+    let localized_template = localized_template(&config.strings, &config.html_partials);
+
+    fs::write(
+        build_dir.join(search_index::SEARCH_INDEX_JSON),
+        search_index.to_json(),
+    )
+    .expect("TODO gracefully handle failing to write search-index.json");
+
+    fs::write(
+        build_dir.join("index.html"),
+        workspace::render_index(
+            &localized_template,
+            &root_url,
+            config.canonical_base_url.as_deref(),
+            &index_entries,
+            &config.strings,
+        ),
+    )
+    .expect("TODO gracefully handle failing to write the workspace's index page");
+
+    println!("🎉 Docs generated in {}", build_dir.display());
+
+    check_generated_links(build_dir, &root_url, &other_versions)
+}
+
+/// Where one package's docs go. That's the whole build dir, unless the package is one of several
+/// in a workspace.
+struct PackageDir {
+    name: String,
+    /// e.g. "./generated-docs/json"
+    dir: PathBuf,
+    /// The URL of `dir`, e.g. "/json/"
+    root_url: String,
+    /// The absolute URL of `dir`, e.g. "https://example.com/docs/json"
+    canonical_root_url: Option<String>,
+}
+
+impl PackageDir {
+    /// The path every page's URL starts with, e.g. "/json/1.2.0/"
+    fn base_url(&self, version: Option<&str>) -> String {
+        match version {
+            Some(version) => format!("{}{}/", self.root_url, version),
+            None => self.root_url.clone(),
+        }
+    }
+}
+
+/// What the rest of the site needs to know about a package's docs once they're generated
+struct GeneratedPackage {
+    /// The package's landing page, or its first module's page if it doesn't have one
+    home_url: String,
+    /// The URLs of the other published versions, which aren't in the build dir
+    other_versions: Vec<String>,
+}
 
+/// Leaves out the modules that shouldn't be documented, and puts the rest in order. Returns the
+/// ones which were left out.
+fn prepare_modules(loaded_module: &mut LoadedModule, config: &DocsConfig) -> VecSet<ModuleId> {
+    // These don't stop the docs from being generated, so they're warnings, like in `roc check`.
+    // Modules that are left out are checked too, so that links to them resolve.
+    for problem in lint::lint_docs(loaded_module) {
+        eprintln!("Doc comment problem in {}", problem);
+    }
+
+    let left_out = module_filter::retain_documented(
+        &mut loaded_module.docs_by_module,
+        &config.include_modules,
+        &config.exclude_modules,
+    );
+
+    normalize::sort_modules(&mut loaded_module.docs_by_module);
+
+    for (_, module_docs) in loaded_module.docs_by_module.iter_mut() {
+        ordering::order_entries(module_docs, config.entry_order);
+        normalize::rename_type_vars(module_docs);
+    }
+
+    left_out
+}
+
+/// Makes a fresh, empty build dir, with the assets every page uses
+/// The versions name directories, so they're checked before anything is written
+fn exit_on_invalid_versions(config: &DocsConfig) {
+    if let Err(message) =
+        versions::check_versions(config.version.as_deref(), &config.published_versions)
+    {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
+
+fn create_build_dir(build_dir: &Path, config: &DocsConfig) {
     // Clear out the generated-docs dir (we'll create a fresh one at the end)
     if build_dir.exists() {
         fs::remove_dir_all(build_dir)
@@ -42,6 +346,12 @@ pub fn generate_docs_html(root_file: PathBuf) {
     )
     .expect("TODO gracefully handle failing to make the search javascript");
 
+    fs::write(
+        build_dir.join(search_index::SEARCH_INDEX_SCHEMA_JSON),
+        include_str!("./static/search-index.schema.json"),
+    )
+    .expect("TODO gracefully handle failing to make the search index schema");
+
     fs::write(
         build_dir.join("styles.css"),
         include_str!("./static/styles.css"),
@@ -54,29 +364,176 @@ pub fn generate_docs_html(root_file: PathBuf) {
     )
     .expect("TODO gracefully handle failing to make the favicon");
 
-    let template_html = include_str!("./static/index.html")
+    if config.math {
+        fs::write(
+            build_dir.join(math::MATH_JS),
+            include_str!("./static/math.js"),
+        )
+        .expect("TODO gracefully handle failing to make the math javascript");
+    }
+}
+
+/// Checks every link in the build dir, and prints the broken ones
+fn check_generated_links(
+    build_dir: &Path,
+    root_url: &str,
+    other_versions: &[String],
+) -> Vec<BrokenLink> {
+    let broken_links = links::check_links(build_dir, root_url, other_versions);
+
+    if !broken_links.is_empty() {
+        eprintln!("\n{} broken links:\n", broken_links.len());
+
+        for broken_link in broken_links.iter() {
+            eprintln!("    {broken_link}");
+        }
+    }
+
+    broken_links
+}
+
+/// The page template, with the text around the docs and the site's own partials filled in
+fn localized_template(strings: &DocsStrings, partials: &HtmlPartials) -> String {
+    partials
+        .fill(include_str!("./static/index.html"))
+        .replace("<!-- lang -->", &escape_attr(&strings.lang))
+        .replace(
+            "<!-- Skip to content -->",
+            &escape_attr(&strings.skip_to_content),
+        )
+        .replace(
+            "<!-- Modules nav label -->",
+            &escape_attr(&strings.modules_nav_label),
+        )
+        .replace(
+            "<!-- Search placeholder -->",
+            &escape_attr(&strings.search_placeholder),
+        )
+        .replace("<!-- Search label -->", &escape_attr(&strings.search_label))
+        .replace(
+            "<!-- Search shortcut hint -->",
+            &with_name(
+                &escape_attr(&strings.search_shortcut_hint),
+                r#"<span id="search-shortcut-key">s</span>"#,
+            ),
+        )
+        .replace(
+            "<!-- Search palette placeholder -->",
+            &escape_attr(&strings.search_palette_placeholder),
+        )
+        .replace(
+            "<!-- Search palette no results -->",
+            &escape_attr(&strings.search_palette_no_results),
+        )
+        .replace("<!-- Logo title -->", &escape_attr(&strings.logo_title))
+        .replace("<!-- Footer -->", &render_footer(&strings.footer))
+}
+
+/// Generates one package's docs into its dir. `links` are the entries of other packages in the
+/// workspace which its doc links can go to.
+fn generate_package_docs(
+    root_file: &Path,
+    loaded_module: &LoadedModule,
+    left_out: &VecSet<ModuleId>,
+    package: &PackageDir,
+    config: &DocsConfig,
+    links: VecMap<Symbol, reexports::Reexport>,
+) -> GeneratedPackage {
+    let guides = guides::Guides::for_root_file(root_file);
+    let platform = platform::PlatformApi::read(root_file);
+
+    let package_name = package.name.clone();
+    let version = config.version.clone().unwrap_or_default();
+    let versions = match config.version.as_deref() {
+        Some(version) => versions::all_versions(version, &config.published_versions),
+        None => Vec::new(),
+    };
+    // The assets are at the root of the site, even when the package's pages aren't
+    let site_root_url = base_url(None);
+    let root_url = package.root_url.clone();
+    let base_url = package.base_url(config.version.as_deref());
+    let pages_dir = match config.version.as_deref() {
+        Some(version) => package.dir.join(version),
+        None => package.dir.clone(),
+    };
+    let canonical_base_url =
+        package
+            .canonical_root_url
+            .as_deref()
+            .map(|url| match config.version.as_deref() {
+                Some(version) => format!("{}/{}", url.trim_end_matches('/'), version),
+                None => url.to_string(),
+            });
+
+    fs::create_dir_all(&pages_dir)
+        .expect("TODO gracefully handle being unable to create the package's dir");
+
+    let landing_page = guides.landing_page();
+    // A platform always gets a landing page, to explain how to write an app for it
+    let has_landing_page = landing_page.is_some() || platform.is_some();
+
+    // Browsers only search with absolute URLs, so use the canonical ones when we know them.
+    // Searches go to the landing page, or to the first module's page if there isn't one.
+    let pages_url = match canonical_base_url.as_deref() {
+        Some(url) => format!("{}/", url.trim_end_matches('/')),
+        None => base_url.clone(),
+    };
+    let home_page = match (
+        has_landing_page,
+        loaded_module.docs_by_module.values().next(),
+    ) {
+        (false, Some(module)) => format!("{}/", module.name.as_str()),
+        _ => String::new(),
+    };
+    let search_page_url = format!("{pages_url}{home_page}");
+    let favicon_url = match config.canonical_base_url.as_deref() {
+        Some(url) => format!("{}/favicon.svg", url.trim_end_matches('/')),
+        None => "/favicon.svg".to_string(),
+    };
+    let opensearch_title = with_name(&config.strings.opensearch_title, &package_name);
+
+    let strings = &config.strings;
+    let localized_template = localized_template(strings, &config.html_partials);
+    // The single-file docs inline the assets rather than linking to them
+    let template_html = localized_template
+        .replace(
+            "<!-- search-index.json -->",
+            &format!("{base_url}{}", search_index::SEARCH_INDEX_JSON),
+        )
         .replace("<!-- search.js -->", "/search.js")
         .replace("<!-- styles.css -->", "/styles.css")
         .replace("<!-- favicon.svg -->", "/favicon.svg")
+        .replace(
+            "<!-- OpenSearch -->",
+            &opensearch::render_opensearch_link(
+                &opensearch_title,
+                &format!("{base_url}{}", opensearch::OPENSEARCH_XML),
+            ),
+        )
         .replace(
             "<!-- Prefetch links -->",
             loaded_module
                 .docs_by_module
                 .iter()
                 .map(|(_, module)| {
-                    let href = sidebar_link_url(module.name.as_str());
+                    let href = sidebar_link_url(&base_url, module.name.as_str());
 
                     format!(r#"<link rel="prefetch" href="{href}"/>"#)
                 })
                 .collect::<Vec<String>>()
                 .join("\n    ")
                 .as_str(),
-        )
-        .replace(
-            "<!-- Module links -->",
-            render_sidebar(loaded_module.docs_by_module.values()).as_str(),
         );
 
+    // Only pages with math need KaTeX, and pages can only have math when it's turned on
+    let math_head = |html: &str| {
+        if config.math && math::has_math(html) {
+            math::render_head()
+        } else {
+            String::new()
+        }
+    };
+
     let all_exposed_symbols = {
         let mut set = VecSet::default();
 
@@ -87,48 +544,545 @@ pub fn generate_docs_html(root_file: PathBuf) {
         set
     };
 
+    let implementers = abilities::find_implementers(
+        &base_url,
+        loaded_module.docs_by_module.values(),
+        &loaded_module.interns,
+        &all_exposed_symbols,
+    );
+
+    let references = references::find_references(
+        &base_url,
+        loaded_module.docs_by_module.values(),
+        &loaded_module.interns,
+        &all_exposed_symbols,
+    );
+
+    let see_also = see_also::find_see_also(
+        &base_url,
+        loaded_module.docs_by_module.values(),
+        &loaded_module.interns,
+        &all_exposed_symbols,
+    );
+
+    let mut reexports = reexports::find_reexports(
+        loaded_module.docs_by_module.values(),
+        &loaded_module.interns,
+        &all_exposed_symbols,
+    );
+
+    for (symbol, link) in links.into_iter() {
+        if !reexports.contains_key(&symbol) {
+            reexports.insert(symbol, link);
+        }
+    }
+
+    let api_snapshot =
+        ApiSnapshot::from_modules(loaded_module.docs_by_module.values(), &all_exposed_symbols);
+
+    let redirects = redirects::Redirects::find(
+        config.previous_api.as_ref(),
+        &config.redirects,
+        &api_snapshot,
+    );
+
+    // The headers of the other pages link to the current version in the changelog
+    let changelog_page = changelog::read_changelog(root_file).map(|markdown| {
+        // Like the landing page, the changelog isn't in any module
+        let scope = Scope::new(
+            loaded_module.module_id,
+            IdentIds::default(),
+            Default::default(),
+        );
+        let mut html = String::new();
+
+        markdown_to_html(
+            &mut html,
+            &base_url,
+            &all_exposed_symbols,
+            &reexports,
+            &scope,
+            &markdown,
+            &loaded_module,
+            left_out,
+            config.math,
+        );
+
+        changelog::ChangelogPage::from_html(&html)
+    });
+    let changelog_url = changelog_page
+        .as_ref()
+        .map(|page| page.url(&base_url, &version));
+
+    let mut single_file_modules = Vec::new();
+
     // Write each package's module docs html file
     for module_docs in loaded_module.docs_by_module.values() {
         let module_name = module_docs.name.as_str();
-        let module_dir = build_dir.join(module_name.replace('.', "/").as_str());
+        let module_dir = pages_dir.join(module_name.replace('.', "/").as_str());
 
         fs::create_dir_all(&module_dir)
             .expect("TODO gracefully handle not being able to create the module dir");
 
+        let canonical_url = canonical_base_url
+            .as_deref()
+            .map(|base_url| metadata::canonical_url(base_url, module_name));
+
+        let version_switcher = if versions.is_empty() {
+            String::new()
+        } else {
+            versions::render_version_switcher(
+                &root_url,
+                &version,
+                &versions,
+                module_name,
+                &strings.versions_label,
+            )
+        };
+
+        let supplement = guides.module_supplement(module_name);
+        let mut module_docs_html = render_module_documentation(
+            &base_url,
+            module_docs,
+            &loaded_module,
+            left_out,
+            &all_exposed_symbols,
+            &reexports,
+            &implementers,
+            &references,
+            &see_also,
+            supplement.as_deref(),
+            strings,
+            config.embed_source,
+            config.math,
+        );
+
+        redirects.render_moved_out(
+            &mut module_docs_html,
+            &base_url,
+            module_name,
+            &strings.moved_to,
+        );
+
         let rendered_module = template_html
+            .replace(
+                "<!-- Module links -->",
+                render_sidebar(
+                    &base_url,
+                    loaded_module.docs_by_module.values(),
+                    module_name,
+                )
+                .as_str(),
+            )
             .replace(
                 "<!-- Page title -->",
                 page_title(package_name.as_str(), module_name).as_str(),
             )
             .replace(
-                "<!-- Package Name and Version -->",
-                render_name_and_version(package_name.as_str(), version.as_str()).as_str(),
+                "<!-- Page metadata -->",
+                metadata::render_page_metadata(
+                    format!("{module_name} - {package_name}").as_str(),
+                    metadata::module_description(module_docs).as_deref(),
+                    canonical_url.as_deref(),
+                )
+                .as_str(),
             )
             .replace(
-                "<!-- Module Docs -->",
-                render_module_documentation(module_docs, &loaded_module, &all_exposed_symbols)
-                    .as_str(),
-            );
+                "<!-- Package Name and Version -->",
+                render_name_and_version(
+                    &base_url,
+                    package_name.as_str(),
+                    version.as_str(),
+                    &version_switcher,
+                    changelog_url.as_deref(),
+                    &strings.changelog,
+                )
+                .as_str(),
+            )
+            .replace("<!-- Module Docs -->", module_docs_html.as_str())
+            .replace("<!-- Math -->", &math_head(&module_docs_html));
 
         fs::write(module_dir.join("index.html"), rendered_module)
             .expect("TODO gracefully handle failing to write index.html inside module's dir");
+
+        fs::write(
+            pages_dir.join(plain_text::module_file_name(module_name)),
+            plain_text::render_module_markdown(
+                module_docs,
+                &all_exposed_symbols,
+                supplement.as_deref(),
+            ),
+        )
+        .expect("TODO gracefully handle failing to write the module's markdown");
+
+        if config.single_file {
+            single_file_modules.push((module_name.to_string(), module_docs_html));
+        }
     }
 
-    println!("🎉 Docs generated in {}", build_dir.display());
+    let mut single_file_landing_page = None;
+
+    if has_landing_page {
+        let canonical_url = canonical_base_url
+            .as_deref()
+            .map(|base_url| format!("{}/", base_url.trim_end_matches('/')));
+
+        let version_switcher = if versions.is_empty() {
+            String::new()
+        } else {
+            versions::render_version_switcher(
+                &root_url,
+                &version,
+                &versions,
+                "",
+                &strings.versions_label,
+            )
+        };
+
+        // The landing page isn't in any module, so only builtins and qualified names are in scope.
+        let scope = Scope::new(
+            loaded_module.module_id,
+            IdentIds::default(),
+            Default::default(),
+        );
+        let mut landing_html = String::new();
+
+        if let Some(markdown) = landing_page.as_deref() {
+            markdown_to_html(
+                &mut landing_html,
+                &base_url,
+                &all_exposed_symbols,
+                &reexports,
+                &scope,
+                markdown,
+                &loaded_module,
+                left_out,
+                config.math,
+            );
+        }
+
+        if let Some(platform) = platform.as_ref() {
+            platform::render_platform_section(
+                &mut landing_html,
+                platform,
+                &base_url,
+                loaded_module.docs_by_module.values(),
+                loaded_module.module_id,
+                &loaded_module.interns,
+                &all_exposed_symbols,
+                &reexports,
+                strings,
+            );
+        }
+
+        let rendered_landing_page = template_html
+            .replace(
+                "<!-- Module links -->",
+                render_sidebar(&base_url, loaded_module.docs_by_module.values(), "").as_str(),
+            )
+            .replace(
+                "<!-- Page title -->",
+                format!("<title>{package_name}</title>").as_str(),
+            )
+            .replace(
+                "<!-- Page metadata -->",
+                metadata::render_page_metadata(
+                    package_name.as_str(),
+                    landing_page
+                        .as_deref()
+                        .and_then(metadata::first_sentence)
+                        .as_deref(),
+                    canonical_url.as_deref(),
+                )
+                .as_str(),
+            )
+            .replace(
+                "<!-- Package Name and Version -->",
+                render_name_and_version(
+                    &base_url,
+                    package_name.as_str(),
+                    version.as_str(),
+                    &version_switcher,
+                    changelog_url.as_deref(),
+                    &strings.changelog,
+                )
+                .as_str(),
+            )
+            .replace("<!-- Module Docs -->", landing_html.as_str())
+            .replace("<!-- Math -->", &math_head(&landing_html));
+
+        fs::write(pages_dir.join("index.html"), rendered_landing_page)
+            .expect("TODO gracefully handle failing to write the landing page");
+
+        if config.single_file {
+            single_file_landing_page = Some(landing_html);
+        }
+    }
+
+    if let Some(changelog_page) = changelog_page.as_ref() {
+        let changelog_dir = pages_dir.join(changelog::CHANGELOG_DIR);
+        let title = format!("{} - {package_name}", strings.changelog);
+        let canonical_url = canonical_base_url
+            .as_deref()
+            .map(|base_url| metadata::canonical_url(base_url, changelog::CHANGELOG_DIR));
+
+        let version_switcher = if versions.is_empty() {
+            String::new()
+        } else {
+            versions::render_version_switcher(
+                &root_url,
+                &version,
+                &versions,
+                &format!("{}/", changelog::CHANGELOG_DIR),
+                &strings.versions_label,
+            )
+        };
+
+        let rendered_changelog = template_html
+            .replace(
+                "<!-- Module links -->",
+                render_sidebar(&base_url, loaded_module.docs_by_module.values(), "").as_str(),
+            )
+            .replace(
+                "<!-- Page title -->",
+                format!("<title>{}</title>", escape_attr(&title)).as_str(),
+            )
+            .replace(
+                "<!-- Page metadata -->",
+                metadata::render_page_metadata(&title, None, canonical_url.as_deref()).as_str(),
+            )
+            .replace(
+                "<!-- Package Name and Version -->",
+                render_name_and_version(
+                    &base_url,
+                    package_name.as_str(),
+                    version.as_str(),
+                    &version_switcher,
+                    changelog_url.as_deref(),
+                    &strings.changelog,
+                )
+                .as_str(),
+            )
+            .replace("<!-- Module Docs -->", changelog_page.html.as_str())
+            .replace("<!-- Math -->", &math_head(&changelog_page.html));
+
+        fs::create_dir_all(&changelog_dir)
+            .expect("TODO gracefully handle not being able to create the changelog dir");
+        fs::write(changelog_dir.join("index.html"), rendered_changelog)
+            .expect("TODO gracefully handle failing to write the changelog page");
+    }
+
+    // Search engines need absolute URLs, so we can only make a sitemap if we know the base URL.
+    if let Some(base_url) = canonical_base_url.as_deref() {
+        let landing_url = has_landing_page.then(|| format!("{}/", base_url.trim_end_matches('/')));
+        let changelog_url = changelog_page
+            .as_ref()
+            .map(|_| metadata::canonical_url(base_url, changelog::CHANGELOG_DIR));
+        let urls = landing_url.into_iter().chain(changelog_url).chain(
+            loaded_module
+                .docs_by_module
+                .values()
+                .map(|module| metadata::canonical_url(base_url, module.name.as_str())),
+        );
+
+        fs::write(
+            pages_dir.join("sitemap.xml"),
+            metadata::render_sitemap(urls),
+        )
+        .expect("TODO gracefully handle failing to write sitemap.xml");
+    }
+
+    fs::write(
+        pages_dir.join(plain_text::LLMS_TXT),
+        plain_text::render_llms_txt(
+            &base_url,
+            package_name.as_str(),
+            landing_page.as_deref(),
+            loaded_module.docs_by_module.values(),
+        ),
+    )
+    .expect("TODO gracefully handle failing to write llms.txt");
+
+    fs::write(pages_dir.join(api::API_JSON), api_snapshot.to_json())
+        .expect("TODO gracefully handle failing to write api.json");
+
+    // Like the sitemap, the feed needs absolute URLs.
+    if let (Some(previous_api), Some(root), Some(pages)) = (
+        config.previous_api.as_ref(),
+        package.canonical_root_url.as_deref(),
+        canonical_base_url.as_deref(),
+    ) {
+        let changes = diff_api(previous_api, &api_snapshot);
+        let title = with_name(&config.strings.changes_feed_title, &package_name);
+
+        fs::write(
+            package.dir.join(feed::CHANGES_XML),
+            feed::render_changes_feed(
+                &feed::FeedUrls { root, pages },
+                &title,
+                config.version.as_deref(),
+                &changes,
+            ),
+        )
+        .expect("TODO gracefully handle failing to write changes.xml");
+    }
+
+    if config.previous_api.is_some() || !config.redirects.is_empty() {
+        fs::write(
+            pages_dir.join(redirects::REDIRECTS_JSON),
+            redirects.to_json(&base_url),
+        )
+        .expect("TODO gracefully handle failing to write redirects.json");
+
+        for (module_name, page) in redirects.render_gone_modules(&base_url, &api_snapshot) {
+            let module_dir = pages_dir.join(module_name.replace('.', "/").as_str());
+
+            fs::create_dir_all(&module_dir)
+                .expect("TODO gracefully handle not being able to create the module dir");
+            fs::write(module_dir.join("index.html"), page)
+                .expect("TODO gracefully handle failing to write a moved module's redirect page");
+        }
+    }
+
+    let search_index = search_index::SearchIndex::from_modules(
+        &site_root_url,
+        loaded_module.docs_by_module.values(),
+        &all_exposed_symbols,
+    );
+
+    fs::write(
+        pages_dir.join(search_index::SEARCH_INDEX_JSON),
+        search_index.to_json(),
+    )
+    .expect("TODO gracefully handle failing to write search-index.json");
+
+    fs::write(
+        pages_dir.join(opensearch::OPENSEARCH_XML),
+        opensearch::render_opensearch_descriptor(&opensearch_title, &search_page_url, &favicon_url),
+    )
+    .expect("TODO gracefully handle failing to write opensearch.xml");
+
+    fs::write(
+        pages_dir.join(hover::HOVER_JSONL),
+        hover::render_hover_jsonl(loaded_module.docs_by_module.values(), &all_exposed_symbols),
+    )
+    .expect("TODO gracefully handle failing to write hover.jsonl");
+
+    if config.single_file {
+        let single_file = single_file::SingleFile {
+            base_url: &base_url,
+            head: format!("<title>{package_name}</title>")
+                + &metadata::render_page_metadata(
+                    package_name.as_str(),
+                    landing_page
+                        .as_deref()
+                        .and_then(metadata::first_sentence)
+                        .as_deref(),
+                    None,
+                ),
+            sidebar: render_sidebar(&base_url, loaded_module.docs_by_module.values(), ""),
+            // The changelog isn't in the single file, so there's nothing for the version to link to
+            name_and_version: render_name_and_version(
+                &base_url,
+                package_name.as_str(),
+                version.as_str(),
+                "",
+                None,
+                &strings.changelog,
+            ),
+            landing_page: single_file_landing_page,
+            modules: single_file_modules,
+        };
+
+        fs::write(
+            pages_dir.join(single_file::SINGLE_FILE_HTML),
+            single_file.render(&localized_template, search_index),
+        )
+        .expect("TODO gracefully handle failing to write the single-file docs");
+    }
+
+    if !versions.is_empty() {
+        fs::write(
+            package.dir.join(versions::VERSIONS_JSON),
+            versions::render_versions_json(&versions),
+        )
+        .expect("TODO gracefully handle failing to write versions.json");
+    }
+
+    // Other versions are published separately, so they aren't in the build dir to check against.
+    let other_versions: Vec<String> = versions
+        .iter()
+        .filter(|other| **other != version)
+        .map(|other| format!("{root_url}{other}/"))
+        .collect();
+
+    GeneratedPackage {
+        home_url: format!("{base_url}{home_page}"),
+        other_versions,
+    }
+}
+
+/// Writes every example in the package's docs to its own .roc file in `out_dir`.
+pub fn extract_examples(root_file: PathBuf, out_dir: &Path) {
+    let loaded_module = load_module_for_docs(root_file);
+
+    fs::create_dir_all(out_dir)
+        .expect("TODO gracefully handle being unable to create the examples dir");
+
+    let mut count = 0;
+
+    for (module_id, module_docs) in loaded_module.docs_by_module.iter() {
+        for example in examples::example_files(module_docs, module_id.is_builtin()) {
+            fs::write(out_dir.join(&example.file_name), example.contents)
+                .expect("TODO gracefully handle failing to write an example file");
+
+            count += 1;
+        }
+    }
+
+    println!("🎉 Extracted {} examples into {}", count, out_dir.display());
 }
 
-fn sidebar_link_url(module_name: &str) -> String {
-    format!("{}{}", base_url(), module_name)
+fn sidebar_link_url(base_url: &str, module_name: &str) -> String {
+    format!("{}{}", base_url, module_name)
 }
 
 fn page_title(package_name: &str, module_name: &str) -> String {
     format!("<title>{module_name} - {package_name}</title>")
 }
 
+fn is_hidden(doc_def: &DocDef) -> bool {
+    match &doc_def.docs {
+        Some(docs) => docs.trim_start().starts_with(HIDDEN_MARKER),
+        None => false,
+    }
+}
+
+fn render_footer(lines: &[String]) -> String {
+    let mut buf = String::new();
+
+    for line in lines {
+        push_html(&mut buf, "p", vec![], escape_attr(line));
+    }
+
+    buf
+}
+
+#[allow(clippy::too_many_arguments)]
 fn render_module_documentation(
+    base_url: &str,
     module: &ModuleDocumentation,
     root_module: &LoadedModule,
+    left_out: &VecSet<ModuleId>,
     all_exposed_symbols: &VecSet<Symbol>,
+    reexports: &VecMap<Symbol, reexports::Reexport>,
+    implementers: &VecMap<Symbol, Vec<abilities::Implementer>>,
+    references: &VecMap<Symbol, Vec<references::Reference>>,
+    see_also: &VecMap<Symbol, Vec<see_also::SeeAlso>>,
+    supplement: Option<&str>,
+    strings: &DocsStrings,
+    embed_source: bool,
+    math: bool,
 ) -> String {
     let mut buf = String::new();
 
@@ -145,8 +1099,16 @@ fn render_module_documentation(
         link_buf
     });
 
-    for entry in &module.entries {
+    // Entries re-exported from other modules stay where they're declared, like in the sidebar,
+    // with a note saying where they come from
+    for entry in module.entries.iter() {
         match entry {
+            DocEntry::DocDef(doc_def) if is_hidden(doc_def) => {
+                // Keep the anchor, so that existing links to this entry still go to this module
+                if all_exposed_symbols.contains(&doc_def.symbol) {
+                    push_html(&mut buf, "span", vec![("id", doc_def.name.as_str())], "");
+                }
+            }
             DocEntry::DocDef(doc_def) => {
                 // Only render entries that are exposed
                 if all_exposed_symbols.contains(&doc_def.symbol) {
@@ -154,9 +1116,15 @@ fn render_module_documentation(
 
                     let name = doc_def.name.as_str();
                     let href = format!("#{name}");
+                    let link_label = escape_attr(&with_name(&strings.link_to_entry, name));
                     let mut content = String::new();
 
-                    push_html(&mut content, "a", vec![("href", href.as_str())], LINK_SVG);
+                    push_html(
+                        &mut content,
+                        "a",
+                        vec![("href", href.as_str()), ("aria-label", link_label.as_str())],
+                        LINK_SVG,
+                    );
                     push_html(&mut content, "strong", vec![], name);
 
                     for type_var in &doc_def.type_vars {
@@ -178,34 +1146,133 @@ fn render_module_documentation(
                         content.as_str(),
                     );
 
+                    if let Some(original) = &doc_def.reexported_from {
+                        reexports::render_note(
+                            &mut buf,
+                            base_url,
+                            original,
+                            all_exposed_symbols,
+                            &root_module.interns,
+                            &strings.reexported_from,
+                        );
+                    }
+
+                    arguments::render_arguments(
+                        &mut buf,
+                        doc_def,
+                        &strings.arguments,
+                        |docs_buf, docs| {
+                            markdown_to_html(
+                                docs_buf,
+                                base_url,
+                                all_exposed_symbols,
+                                reexports,
+                                &module.scope,
+                                docs,
+                                root_module,
+                                left_out,
+                                math,
+                            )
+                        },
+                    );
+
                     if let Some(docs) = &doc_def.docs {
                         markdown_to_html(
                             &mut buf,
+                            base_url,
                             all_exposed_symbols,
+                            reexports,
                             &module.scope,
                             docs,
                             root_module,
+                            left_out,
+                            math,
+                        );
+                    }
+
+                    tags::render_tag_constructors(&mut buf, doc_def, &strings.link_to_entry);
+
+                    abilities::render_implements(
+                        &mut buf,
+                        base_url,
+                        doc_def,
+                        &module.scope,
+                        &root_module.interns,
+                        all_exposed_symbols,
+                        &strings.implements,
+                    );
+
+                    if let Some(implementers) = implementers.get(&doc_def.symbol) {
+                        abilities::render_implementers(
+                            &mut buf,
+                            implementers,
+                            &strings.implemented_by,
+                        );
+                    }
+
+                    if let Some(references) = references.get(&doc_def.symbol) {
+                        references::render_referenced_by(
+                            &mut buf,
+                            references,
+                            &strings.referenced_by,
                         );
                     }
 
+                    if embed_source && !doc_def.source.is_empty() {
+                        render_source(&mut buf, &doc_def.source, &strings.source);
+                    }
+
+                    if let Some(see_also) = see_also.get(&doc_def.symbol) {
+                        see_also::render_see_also(&mut buf, see_also, &strings.see_also);
+                    }
+
                     buf.push_str("</section>");
                 }
             }
             DocEntry::DetachedDoc(docs) => {
                 markdown_to_html(
                     &mut buf,
+                    base_url,
                     all_exposed_symbols,
+                    reexports,
                     &module.scope,
                     docs,
                     root_module,
+                    left_out,
+                    math,
                 );
             }
         };
     }
 
+    if let Some(markdown) = supplement {
+        buf.push_str(r#"<div class="module-supplement">"#);
+        markdown_to_html(
+            &mut buf,
+            base_url,
+            all_exposed_symbols,
+            reexports,
+            &module.scope,
+            markdown,
+            root_module,
+            left_out,
+            math,
+        );
+        buf.push_str("</div>");
+    }
+
     buf
 }
 
+fn render_source(buf: &mut String, source: &str, label: &str) {
+    let mut content = String::new();
+
+    push_html(&mut content, "summary", vec![], escape_attr(label));
+    content.push_str(&roc_highlight::highlight_roc_code(source));
+
+    push_html(buf, "details", vec![("class", "entry-source")], content);
+}
+
 fn push_html(buf: &mut String, tag_name: &str, attrs: Vec<(&str, &str)>, content: impl AsRef<str>) {
     buf.push('<');
     buf.push_str(tag_name);
@@ -231,11 +1298,13 @@ fn push_html(buf: &mut String, tag_name: &str, attrs: Vec<(&str, &str)>, content
     buf.push('>');
 }
 
-fn base_url() -> String {
+/// The path every page's URL starts with, e.g. "/builtins/1.2.0/" in
+/// "https://roc-lang.org/builtins/1.2.0/Str". Without a version, it's the root of the docs.
+fn base_url(version: Option<&str>) -> String {
     // e.g. "builtins/" in "https://roc-lang.org/builtins/Str"
     //
     // TODO make this a CLI flag to the `docs` subcommand instead of an env var
-    match std::env::var("ROC_DOCS_URL_ROOT") {
+    let mut url_str = match std::env::var("ROC_DOCS_URL_ROOT") {
         Ok(root_builtins_path) => {
             let mut url_str = String::with_capacity(root_builtins_path.len() + 64);
 
@@ -258,12 +1327,28 @@ fn base_url() -> String {
 
             url_str
         }
+    };
+
+    if let Some(version) = version {
+        url_str.push_str(version);
+        url_str.push('/');
     }
+
+    url_str
 }
 
-fn render_name_and_version(name: &str, version: &str) -> String {
+/// Renders the package name, followed by the version switcher if there is one, or else the version.
+/// If the package has a changelog, the version links to it, or a link to it follows the switcher.
+fn render_name_and_version(
+    base_url: &str,
+    name: &str,
+    version: &str,
+    version_switcher: &str,
+    changelog_url: Option<&str>,
+    changelog_label: &str,
+) -> String {
     let mut buf = String::new();
-    let mut url_str = base_url();
+    let mut url_str = base_url.to_string();
 
     url_str.push_str(name);
 
@@ -275,34 +1360,59 @@ fn render_name_and_version(name: &str, version: &str) -> String {
         link_buf
     });
 
-    let mut versions_url_str = base_url();
+    if !version_switcher.is_empty() {
+        buf.push_str(version_switcher);
 
-    versions_url_str.push('/');
-    versions_url_str.push_str(name);
-    versions_url_str.push('/');
-    versions_url_str.push_str(version);
+        if let Some(url) = changelog_url {
+            push_html(
+                &mut buf,
+                "a",
+                vec![("class", "changelog-link"), ("href", url)],
+                escape_attr(changelog_label),
+            );
+        }
+
+        return buf;
+    }
 
     push_html(
         &mut buf,
         "a",
-        vec![("class", "version"), ("href", versions_url_str.as_str())],
+        vec![
+            ("class", "version"),
+            ("href", changelog_url.unwrap_or(base_url)),
+        ],
         version,
     );
 
     buf
 }
 
-fn render_sidebar<'a, I: Iterator<Item = &'a ModuleDocumentation>>(modules: I) -> String {
+/// Renders the sidebar for the page of `current_module`. The current module is
+/// marked active here (rather than in search.js) so the sidebar is complete
+/// even when JavaScript is disabled.
+fn render_sidebar<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    base_url: &str,
+    modules: I,
+    current_module: &str,
+) -> String {
     let mut buf = String::new();
 
     for module in modules {
-        let href = sidebar_link_url(module.name.as_str());
+        let href = sidebar_link_url(base_url, module.name.as_str());
         let mut sidebar_entry_content = String::new();
 
+        let mut link_attrs = vec![("class", "sidebar-module-link"), ("href", href.as_str())];
+
+        if module.name.as_str() == current_module {
+            link_attrs[0] = ("class", "sidebar-module-link active");
+            link_attrs.push(("aria-current", "page"));
+        }
+
         push_html(
             &mut sidebar_entry_content,
             "a",
-            vec![("class", "sidebar-module-link"), ("href", &href)],
+            link_attrs,
             module.name.as_str(),
         );
 
@@ -311,7 +1421,7 @@ fn render_sidebar<'a, I: Iterator<Item = &'a ModuleDocumentation>>(modules: I) -
 
             for entry in &module.entries {
                 if let DocEntry::DocDef(doc_def) = entry {
-                    if module.exposed_symbols.contains(&doc_def.symbol) {
+                    if module.exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) {
                         let mut entry_href = String::new();
 
                         entry_href.push_str(href.as_str());
@@ -324,6 +1434,18 @@ fn render_sidebar<'a, I: Iterator<Item = &'a ModuleDocumentation>>(modules: I) -
                             vec![("href", entry_href.as_str())],
                             doc_def.name.as_str(),
                         );
+
+                        for tag in tags::tag_constructors(doc_def) {
+                            let tag_href =
+                                format!("{}#{}", href, tags::tag_anchor(&doc_def.name, &tag.name));
+
+                            push_html(
+                                &mut entries_buf,
+                                "a",
+                                vec![("class", "sidebar-tag-link"), ("href", tag_href.as_str())],
+                                tag.name.as_str(),
+                            );
+                        }
                     }
                 }
             }
@@ -671,14 +1793,18 @@ struct DocUrl {
     title: String,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn doc_url<'a>(
+    base_url: &str,
     all_exposed_symbols: &VecSet<Symbol>,
+    reexports: &'a VecMap<Symbol, reexports::Reexport>,
     scope: &Scope,
     interns: &'a Interns,
     mut module_name: &'a str,
-    ident: &str,
-) -> DocUrl {
-    if module_name.is_empty() {
+    mut ident: &'a str,
+    tag: Option<&str>,
+) -> Result<DocUrl, String> {
+    let symbol = if module_name.is_empty() {
         // This is an unqualified lookup, so look for the ident
         // in scope!
         match scope.lookup_str(ident, Region::zero()) {
@@ -688,19 +1814,32 @@ fn doc_url<'a>(
                 // module - for example, if this is in scope from an
                 // unqualified import.
                 module_name = symbol.module_string(interns);
+
+                symbol
             }
             Err(_) => {
-                // TODO return Err here
-                panic!(
+                return Err(format!(
                     "Tried to generate an automatic link in docs for symbol `{}`, but that symbol was not in scope in this module.",
                     ident
-                );
+                ));
             }
         }
     } else {
         match interns.module_ids.get_id(&module_name.into()) {
             Some(module_id) => {
-                let symbol = interns.symbol(module_id, ident.into());
+                let ident_id = interns
+                    .all_ident_ids
+                    .get(&module_id)
+                    .and_then(|ident_ids| ident_ids.get_id(ident));
+                let symbol = match ident_id {
+                    Some(ident_id) => Symbol::new(module_id, ident_id),
+                    None => {
+                        return Err(format!(
+                            "Tried to generate an automatic link in docs for `{}.{}`, but `{}` has nothing named `{}`.",
+                            module_name, ident, module_name, ident
+                        ));
+                    }
+                };
 
                 if symbol.is_builtin() {
                     // We can always generate links for builtin modules.
@@ -712,11 +1851,12 @@ fn doc_url<'a>(
                 }
                 // Note: You can do qualified lookups on your own module, e.g.
                 // if I'm in the Foo module, I can do a `Foo.bar` lookup.
-                else if !all_exposed_symbols.contains(&symbol) {
-                    // TODO return Err here
-                    panic!(
-                            "Tried to generate an automatic link in docs for `{}.{}`, but `{}` does not expose `{}`.",
-                            module_name, ident, module_name, ident);
+                else if !all_exposed_symbols.contains(&symbol) && !reexports.contains_key(&symbol)
+                {
+                    return Err(format!(
+                        "Tried to generate an automatic link in docs for `{}.{}`, but `{}` does not expose `{}`.",
+                        module_name, ident, module_name, ident
+                    ));
                 }
 
                 // This is a valid symbol for this dependency,
@@ -724,92 +1864,176 @@ fn doc_url<'a>(
                 //
                 // TODO: In the future, this is where we'll
                 // incorporate the package name into the link.
+
+                symbol
             }
             None => {
-                // TODO return Err here
-                panic!("Tried to generate a doc link for `{}.{}` but the `{}` module was not imported!", module_name, ident, module_name);
+                return Err(format!(
+                    "Tried to generate a doc link for `{}.{}` but the `{}` module was not imported!",
+                    module_name, ident, module_name
+                ));
             }
         }
-    }
+    };
 
-    let mut url = base_url();
+    let mut url = base_url.to_string();
 
-    // Example:
+    // Symbols that are only exposed through a re-export are documented where they're re-exported,
+    // and those of other packages in the workspace are documented in that package's docs
+    if let Some(reexport) = reexports.get(&symbol) {
+        module_name = reexport.module_name.as_str();
+        ident = reexport.name.as_str();
+
+        if let Some(package_url) = &reexport.package_url {
+            url = package_url.clone();
+        }
+    }
+
+    // Examples:
     //
     // module_name: "Str", ident: "join" => "/Str#join"
+    // module_name: "Graphics", ident: "Color", tag: "Custom" => "/Graphics#Color.Custom"
+    let anchor = match tag {
+        Some(tag_name) => tags::tag_anchor(ident, tag_name),
+        None => ident.to_string(),
+    };
+
     url.push_str(module_name);
     url.push('#');
-    url.push_str(ident);
+    url.push_str(&anchor);
 
-    DocUrl {
+    Ok(DocUrl {
         url,
-        title: format!("Docs for {}.{}", module_name, ident),
+        title: format!("Docs for {}.{}", module_name, anchor),
+    })
+}
+
+/// Resolves a shortcut link - see https://spec.commonmark.org/0.30/#shortcut-reference-link -
+/// like `[Str.join]` or `[myFunction]` as an identifier, based on what's currently in scope.
+/// Returns `None` for references that aren't identifiers, like `[foo.bar]`, which are left as
+/// they are, and an error for identifiers that the docs can't link to.
+#[allow(clippy::too_many_arguments)]
+fn resolve_shortcut_link(
+    arena: &Bump,
+    reference: &str,
+    base_url: &str,
+    all_exposed_symbols: &VecSet<Symbol>,
+    reexports: &VecMap<Symbol, reexports::Reexport>,
+    scope: &Scope,
+    loaded_module: &LoadedModule,
+) -> Option<Result<DocUrl, String>> {
+    let interns = &loaded_module.interns;
+
+    // Tags like [Color.Custom] aren't valid identifiers, so check for them first
+    if let Some((symbol, tag_name)) =
+        tags::resolve_tag_reference(reference, scope, all_exposed_symbols, loaded_module)
+    {
+        return Some(doc_url(
+            base_url,
+            all_exposed_symbols,
+            reexports,
+            scope,
+            interns,
+            symbol.module_string(interns).as_str(),
+            symbol.as_str(interns),
+            Some(tag_name),
+        ));
+    }
+
+    let state = State::new(reference.as_bytes());
+
+    match parse_ident(arena, state, 0) {
+        Ok((_, Ident::Access { module_name, parts }, _)) => {
+            let mut iter = parts.iter();
+
+            match iter.next() {
+                Some(Accessor::RecordField(symbol_name)) if iter.next().is_none() => Some(doc_url(
+                    base_url,
+                    all_exposed_symbols,
+                    reexports,
+                    scope,
+                    interns,
+                    module_name,
+                    symbol_name,
+                    None,
+                )),
+                _ => {
+                    // This had record field access,
+                    // e.g. [foo.bar] - which we
+                    // can't create a doc link to!
+                    None
+                }
+            }
+        }
+        Ok((_, Ident::Tag(type_name), _)) => {
+            // This looks like a tag name, but it could
+            // be a type alias that's in scope, e.g. [I64]
+            Some(doc_url(
+                base_url,
+                all_exposed_symbols,
+                reexports,
+                scope,
+                interns,
+                "",
+                type_name,
+                None,
+            ))
+        }
+        _ => None,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn markdown_to_html(
     buf: &mut String,
+    base_url: &str,
     all_exposed_symbols: &VecSet<Symbol>,
+    reexports: &VecMap<Symbol, reexports::Reexport>,
     scope: &Scope,
     markdown: &str,
     loaded_module: &LoadedModule,
+    left_out: &VecSet<ModuleId>,
+    math: bool,
 ) {
     use pulldown_cmark::{BrokenLink, CodeBlockKind, CowStr, Event, LinkType, Tag::*};
 
     let mut arena = Bump::new();
     let mut broken_link_callback = |link: BrokenLink| {
-        // A shortcut link - see https://spec.commonmark.org/0.30/#shortcut-reference-link -
-        // is something like `[foo]` in markdown. If you have a shortcut link
-        // without a corresponding `[foo]: https://foo.com` entry
-        // at the end of the document, we resolve it as an identifier based on
-        // what's currently in scope, so you write things like [Str.join] or
+        // A shortcut link is something like `[foo]` in markdown. If you have a shortcut link
+        // without a corresponding `[foo]: https://foo.com` entry at the end of the document,
+        // we resolve it as an identifier, so you can write things like [Str.join] or
         // [myFunction] and have them resolve to the docs for what you wrote.
         match link.link_type {
             LinkType::Shortcut => {
-                let state = State::new(link.reference.as_bytes());
+                // There's no page to link to, so it's rendered as code without a link
+                if module_filter::is_left_out(
+                    &link.reference,
+                    &loaded_module.interns,
+                    left_out,
+                    reexports,
+                ) {
+                    return Some(("".into(), "".into()));
+                }
 
                 // Reset the bump arena so we aren't constantly reallocating
                 // more memory as we iterate through these.
                 arena.reset();
 
-                match parse_ident(&arena, state, 0) {
-                    Ok((_, Ident::Access { module_name, parts }, _)) => {
-                        let mut iter = parts.iter();
-
-                        match iter.next() {
-                            Some(Accessor::RecordField(symbol_name)) if iter.next().is_none() => {
-                                let DocUrl { url, title } = doc_url(
-                                    all_exposed_symbols,
-                                    scope,
-                                    &loaded_module.interns,
-                                    module_name,
-                                    symbol_name,
-                                );
-
-                                Some((url.into(), title.into()))
-                            }
-                            _ => {
-                                // This had record field access,
-                                // e.g. [foo.bar] - which we
-                                // can't create a doc link to!
-                                None
-                            }
-                        }
-                    }
-                    Ok((_, Ident::Tag(type_name), _)) => {
-                        // This looks like a tag name, but it could
-                        // be a type alias that's in scope, e.g. [I64]
-                        let DocUrl { url, title } = doc_url(
-                            all_exposed_symbols,
-                            scope,
-                            &loaded_module.interns,
-                            "",
-                            type_name,
-                        );
-
-                        Some((url.into(), title.into()))
-                    }
-                    _ => None,
+                let result = resolve_shortcut_link(
+                    &arena,
+                    &link.reference,
+                    base_url,
+                    all_exposed_symbols,
+                    reexports,
+                    scope,
+                    loaded_module,
+                )?;
+
+                match result {
+                    Ok(DocUrl { url, title }) => Some((url.into(), title.into())),
+                    // prepare_modules already reported it, see lint::lint_docs. Like a link to
+                    // a module that's left out, it's rendered as code without a link.
+                    Err(_) => Some(("".into(), "".into())),
                 }
             }
             _ => None,
@@ -818,6 +2042,13 @@ fn markdown_to_html(
 
     let markdown_options = pulldown_cmark::Options::ENABLE_TABLES;
 
+    // The math goes back in after the rest is rendered, so markdown doesn't mangle the TeX
+    let extracted_math = math.then(|| math::extract_math(markdown, markdown_options));
+    let markdown = match &extracted_math {
+        Some(extracted) => extracted.markdown.as_str(),
+        None => markdown,
+    };
+
     let mut in_code_block: Option<CowStr> = None;
     let mut to_highlight = String::new();
 
@@ -835,6 +2066,27 @@ fn markdown_to_html(
                     roc_highlight::highlight_roc_code_inline(cow_str.to_string().as_str());
                 docs_parser.push(Event::Html(CowStr::from(highlighted_html)));
             }
+            Event::End(Link(LinkType::ShortcutUnknown, ref url, _)) if url.is_empty() => {
+                // Drop the link to a module left out of the docs, or one that doesn't resolve,
+                // keeping its text as code
+                let start = docs_parser
+                    .iter()
+                    .rposition(|event| matches!(event, Event::Start(Link(..))));
+
+                if let Some(start) = start {
+                    docs_parser.remove(start);
+                }
+
+                match docs_parser.pop() {
+                    Some(Event::Text(string)) => {
+                        docs_parser.push(Event::Code(string));
+                    }
+                    Some(first) => {
+                        docs_parser.push(first);
+                    }
+                    None => {}
+                }
+            }
             Event::End(Link(LinkType::ShortcutUnknown, ref _url, ref _title)) => {
                 // Replace the preceding Text node with a Code node, so it
                 // renders as the equivalent of [`List.len`] instead of [List.len]
@@ -853,6 +2105,15 @@ fn markdown_to_html(
             Event::Start(CodeBlock(CodeBlockKind::Fenced(cow_str))) => {
                 in_code_block = Some(cow_str);
             }
+            Event::End(CodeBlock(_))
+                if math && in_code_block.as_deref().map_or(false, math::is_math_fence) =>
+            {
+                let math_html = math::render_math_fence(&to_highlight);
+                docs_parser.push(Event::Html(CowStr::from(math_html)));
+
+                to_highlight = String::new();
+                in_code_block = None;
+            }
             Event::End(CodeBlock(_)) => {
                 match in_code_block {
                     Some(cow_str) => {
@@ -865,7 +2126,8 @@ fn markdown_to_html(
                         }
 
                         // TODO HANDLE CHECKING BY DEFAULT
-                        let highlighted_html = roc_highlight::highlight_roc_code(&to_highlight);
+                        let highlighted_html =
+                            fences::highlight_fenced_code(&cow_str, &to_highlight);
                         docs_parser.push(Event::Html(CowStr::from(highlighted_html)));
                     }
                     None => {
@@ -903,5 +2165,12 @@ fn markdown_to_html(
         }
     }
 
-    pulldown_cmark::html::push_html(buf, docs_parser.into_iter());
+    match &extracted_math {
+        Some(extracted) => {
+            let mut html = String::new();
+            pulldown_cmark::html::push_html(&mut html, docs_parser.into_iter());
+            buf.push_str(&extracted.restore(&html));
+        }
+        None => pulldown_cmark::html::push_html(buf, docs_parser.into_iter()),
+    }
 }
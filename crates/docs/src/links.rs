@@ -0,0 +1,333 @@
+//! Checks that every link within the generated site goes somewhere, e.g. so that renaming an
+//! entry can't silently break the prose links to it in other modules' docs.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A link to a page, asset, or anchor that isn't in the generated site
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The page the link is on, relative to the build dir, e.g. "Str/index.html"
+    pub page: String,
+    /// The entry whose docs the link is in, if it's in one
+    pub entry: Option<String>,
+    pub href: String,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.entry {
+            Some(entry) => write!(f, "{} (in {}): {}", self.page, entry, self.href),
+            None => write!(f, "{}: {}", self.page, self.href),
+        }
+    }
+}
+
+/// Check the links on every page in `build_dir`. Site-absolute links are resolved with
+/// `root_url` standing for `build_dir`. Links starting with one of `ignored_prefixes` are
+/// skipped, e.g. links to other versions of the docs, which are published separately.
+pub fn check_links(
+    build_dir: &Path,
+    root_url: &str,
+    ignored_prefixes: &[String],
+) -> Vec<BrokenLink> {
+    let mut pages = Vec::new();
+    find_html_files(build_dir, &mut pages);
+    pages.sort();
+
+    let mut anchors_by_page: HashMap<PathBuf, Option<HashSet<String>>> = HashMap::new();
+    let mut broken = Vec::new();
+
+    for page in pages.iter() {
+        let html = match fs::read_to_string(page) {
+            Ok(html) => html,
+            Err(_) => continue,
+        };
+        let page_name = page
+            .strip_prefix(build_dir)
+            .unwrap_or(page)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        for (position, href) in attribute_values(&html, "href")
+            .into_iter()
+            .chain(attribute_values(&html, "src"))
+        {
+            if is_external(&href)
+                || href == "/"
+                || href == root_url
+                || ignored_prefixes
+                    .iter()
+                    .any(|prefix| href.starts_with(prefix))
+            {
+                continue;
+            }
+
+            let (path, fragment) = match href.split_once('#') {
+                Some((path, fragment)) => (path, fragment),
+                None => (href.as_str(), ""),
+            };
+
+            let target = if path.is_empty() {
+                Some(page.clone())
+            } else {
+                resolve(build_dir, root_url, page, path)
+            };
+
+            let exists = match &target {
+                Some(_) if fragment.is_empty() => true,
+                Some(target) => anchors_by_page
+                    .entry(target.clone())
+                    .or_insert_with(|| {
+                        fs::read_to_string(target).ok().map(|html| {
+                            attribute_values(&html, "id")
+                                .into_iter()
+                                .map(|(_, id)| id)
+                                .collect()
+                        })
+                    })
+                    .as_ref()
+                    .map_or(true, |ids| ids.contains(fragment)),
+                None => false,
+            };
+
+            if !exists {
+                broken.push(BrokenLink {
+                    page: page_name.clone(),
+                    entry: entry_at(&html, position),
+                    href,
+                });
+            }
+        }
+    }
+
+    broken
+}
+
+fn find_html_files(dir: &Path, pages: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            find_html_files(&path, pages);
+        } else if path.extension().map_or(false, |ext| ext == "html") {
+            pages.push(path);
+        }
+    }
+}
+
+fn is_external(href: &str) -> bool {
+    href.starts_with("//") || href.contains(':')
+}
+
+/// The file a link's path refers to, if it exists. Pages are directories with an index.html.
+fn resolve(build_dir: &Path, root_url: &str, page: &Path, path: &str) -> Option<PathBuf> {
+    let file = if let Some(rest) = path.strip_prefix(root_url) {
+        build_dir.join(rest)
+    } else if let Some(rest) = path.strip_prefix('/') {
+        // Assets like /styles.css are at the root of the site, whatever the root_url is
+        build_dir.join(rest)
+    } else {
+        // Pages are served without a trailing slash, e.g. /Str, so relative links
+        // start from the directory that the page's directory is in. The root page is
+        // served as /, so its relative links start from the root.
+        let page_dir = page.strip_prefix(build_dir).ok()?.parent()?;
+        let mut file = page_dir.parent().unwrap_or(page_dir).to_path_buf();
+
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                // Links can't leave the site
+                ".." => {
+                    if !file.pop() {
+                        return None;
+                    }
+                }
+                _ => file.push(segment),
+            }
+        }
+
+        build_dir.join(file)
+    };
+
+    if file.is_dir() {
+        let index = file.join("index.html");
+        index.is_file().then_some(index)
+    } else {
+        file.is_file().then_some(file)
+    }
+}
+
+/// Every value of the given attribute in the html, with where the attribute is
+fn attribute_values(html: &str, attribute: &str) -> Vec<(usize, String)> {
+    let needle = format!(" {}=\"", attribute);
+    let mut values = Vec::new();
+    let mut rest_start = 0;
+
+    while let Some(found) = html[rest_start..].find(&needle) {
+        let value_start = rest_start + found + needle.len();
+        let value_end = match html[value_start..].find('"') {
+            Some(len) => value_start + len,
+            None => break,
+        };
+
+        values.push((rest_start + found, unescape(&html[value_start..value_end])));
+        rest_start = value_end;
+    }
+
+    values
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// The entry whose heading comes last before `position`, if any
+fn entry_at(html: &str, position: usize) -> Option<String> {
+    attribute_values(&html[..position], "id")
+        .into_iter()
+        .rev()
+        .find(|(id_position, _)| html[..*id_position].ends_with("<h3"))
+        .map(|(_, id)| id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_links, BrokenLink};
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn finds_missing_pages_and_anchors() {
+        let dir = tempdir().unwrap();
+        let build_dir = dir.path();
+
+        fs::create_dir(build_dir.join("Str")).unwrap();
+        fs::write(build_dir.join("styles.css"), "").unwrap();
+        fs::write(
+            build_dir.join("index.html"),
+            concat!(
+                r#"<link rel="stylesheet" href="/styles.css">"#,
+                r#"<a href="/">Home</a>"#,
+                r#"<a href="/Str">Str</a>"#,
+                r#"<a href="/Str#concat">concat</a>"#,
+                r#"<a href="/Str#missing">missing</a>"#,
+                r#"<a href="/Nope">Nope</a>"#,
+                r#"<a href="https://example.com">Example</a>"#,
+                r#"<a href="/old/1.0/Str">Old Str</a>"#,
+            ),
+        )
+        .unwrap();
+        fs::write(
+            build_dir.join("Str").join("index.html"),
+            concat!(
+                r#"<h3 id="concat" class="entry-name">concat</h3>"#,
+                r##"<p>See <a href="#concat">concat</a> and <a href="Num#toStr">toStr</a></p>"##,
+            ),
+        )
+        .unwrap();
+
+        let broken = check_links(build_dir, "/", &["/old/".to_string()]);
+
+        assert_eq!(
+            broken,
+            vec![
+                BrokenLink {
+                    page: "Str/index.html".to_string(),
+                    entry: Some("concat".to_string()),
+                    href: "Num#toStr".to_string(),
+                },
+                BrokenLink {
+                    page: "index.html".to_string(),
+                    entry: None,
+                    href: "/Str#missing".to_string(),
+                },
+                BrokenLink {
+                    page: "index.html".to_string(),
+                    entry: None,
+                    href: "/Nope".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_links_from_the_root_page() {
+        let dir = tempdir().unwrap();
+        let build_dir = dir.path().join("build");
+
+        // Outside the site, where relative links from the root page used to be resolved
+        fs::create_dir_all(dir.path().join("Num")).unwrap();
+        fs::write(dir.path().join("Num").join("index.html"), "").unwrap();
+
+        fs::create_dir_all(build_dir.join("Str")).unwrap();
+        fs::write(
+            build_dir.join("Str").join("index.html"),
+            r#"<h3 id="concat" class="entry-name">concat</h3><a href="../Str">Str</a>"#,
+        )
+        .unwrap();
+        fs::write(
+            build_dir.join("index.html"),
+            concat!(
+                r#"<a href="Str">Str</a>"#,
+                r#"<a href="./Str#concat">concat</a>"#,
+                r#"<a href="Num">Num</a>"#,
+                r#"<a href="../Num">Num</a>"#,
+            ),
+        )
+        .unwrap();
+
+        let broken = check_links(&build_dir, "/", &[]);
+
+        assert_eq!(
+            broken,
+            vec![
+                BrokenLink {
+                    page: "Str/index.html".to_string(),
+                    entry: Some("concat".to_string()),
+                    href: "../Str".to_string(),
+                },
+                BrokenLink {
+                    page: "index.html".to_string(),
+                    entry: None,
+                    href: "Num".to_string(),
+                },
+                BrokenLink {
+                    page: "index.html".to_string(),
+                    entry: None,
+                    href: "../Num".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_link_mentions_its_entry() {
+        let in_entry = BrokenLink {
+            page: "Str/index.html".to_string(),
+            entry: Some("concat".to_string()),
+            href: "Num#toStr".to_string(),
+        };
+        let on_page = BrokenLink {
+            entry: None,
+            ..in_entry.clone()
+        };
+
+        assert_eq!(
+            in_entry.to_string(),
+            "Str/index.html (in concat): Num#toStr"
+        );
+        assert_eq!(on_page.to_string(), "Str/index.html: Num#toStr");
+    }
+}
@@ -0,0 +1,227 @@
+//! Problems in doc comments that would break the generated docs or make them look wrong, found
+//! without generating anything, so that `roc check` and editors can report them as warnings.
+//! Links are resolved and fences are parsed the same way as when generating the HTML.
+use crate::fences::{self, FenceProblem};
+use crate::{base_url, is_hidden, resolve_shortcut_link, DocUrl};
+use bumpalo::Bump;
+use pulldown_cmark::{BrokenLink, LinkType, Options, Parser};
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+use roc_load::LoadedModule;
+use roc_module::symbol::Symbol;
+use std::fmt;
+
+/// A problem in a doc comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocProblem {
+    pub module_name: String,
+    /// The entry whose doc comment has the problem, or `None` for a doc comment that isn't
+    /// attached to an entry
+    pub entry: Option<String>,
+    pub kind: DocProblemKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocProblemKind {
+    /// A shortcut link like `[Str.join]` to something that can't be linked to, e.g. because it's
+    /// not in scope or not exposed
+    UnknownLink { reference: String, message: String },
+    /// A fenced code block that's never closed, so the rest of the doc comment is code.
+    /// Lines are counted from 1 at the start of the doc comment.
+    UnclosedFence { line: usize },
+    /// A tag in a fence's info string that's neither a known language nor a Roc attribute,
+    /// e.g. ```roc,rpel
+    UnknownFenceTag { line: usize, tag: String },
+}
+
+impl fmt::Display for DocProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.entry {
+            Some(entry) => write!(f, "{}.{}: ", self.module_name, entry)?,
+            None => write!(f, "{}: ", self.module_name)?,
+        }
+
+        match &self.kind {
+            DocProblemKind::UnknownLink { message, .. } => write!(f, "{}", message),
+            DocProblemKind::UnclosedFence { line } => write!(
+                f,
+                "the code block on line {} of the doc comment is never closed",
+                line
+            ),
+            DocProblemKind::UnknownFenceTag { line, tag } => write!(
+                f,
+                "the code block on line {} of the doc comment has an unknown tag `{}`",
+                line, tag
+            ),
+        }
+    }
+}
+
+/// Check the doc comments of every module that docs would be generated for
+pub fn lint_docs(loaded_module: &LoadedModule) -> Vec<DocProblem> {
+    let mut all_exposed_symbols = VecSet::default();
+
+    for docs in loaded_module.docs_by_module.values() {
+        all_exposed_symbols.insert_all(docs.exposed_symbols.iter().copied());
+    }
+
+    let reexports = crate::reexports::find_reexports(
+        loaded_module.docs_by_module.values(),
+        &loaded_module.interns,
+        &all_exposed_symbols,
+    );
+
+    let mut problems = Vec::new();
+
+    for module in loaded_module.docs_by_module.values() {
+        let mut lint = |entry: Option<&str>, markdown: &str| {
+            let kinds = lint_markdown(
+                loaded_module,
+                module,
+                &all_exposed_symbols,
+                &reexports,
+                markdown,
+            );
+
+            problems.extend(kinds.into_iter().map(|kind| DocProblem {
+                module_name: module.name.clone(),
+                entry: entry.map(str::to_string),
+                kind,
+            }));
+        };
+
+        for entry in module.entries.iter() {
+            match entry {
+                DocEntry::DocDef(doc_def) => {
+                    // Only the entries that get rendered
+                    if is_hidden(doc_def) || !all_exposed_symbols.contains(&doc_def.symbol) {
+                        continue;
+                    }
+
+                    for arg_docs in doc_def.args.iter().filter_map(|arg| arg.docs.as_ref()) {
+                        lint(Some(&doc_def.name), arg_docs);
+                    }
+
+                    if let Some(docs) = &doc_def.docs {
+                        lint(Some(&doc_def.name), docs);
+                    }
+                }
+                DocEntry::DetachedDoc(docs) => lint(None, docs),
+            }
+        }
+    }
+
+    problems
+}
+
+fn lint_markdown(
+    loaded_module: &LoadedModule,
+    module: &ModuleDocumentation,
+    all_exposed_symbols: &VecSet<Symbol>,
+    reexports: &VecMap<Symbol, crate::reexports::Reexport>,
+    markdown: &str,
+) -> Vec<DocProblemKind> {
+    let base_url = base_url(None);
+    let mut arena = Bump::new();
+    let mut problems = Vec::new();
+
+    {
+        let mut broken_link_callback = |link: BrokenLink| {
+            if link.link_type == LinkType::Shortcut {
+                arena.reset();
+
+                let result = resolve_shortcut_link(
+                    &arena,
+                    &link.reference,
+                    &base_url,
+                    all_exposed_symbols,
+                    reexports,
+                    &module.scope,
+                    loaded_module,
+                );
+
+                match result {
+                    Some(Ok(DocUrl { url, title })) => return Some((url.into(), title.into())),
+                    Some(Err(message)) => problems.push(DocProblemKind::UnknownLink {
+                        reference: link.reference.to_string(),
+                        message,
+                    }),
+                    None => {}
+                }
+            }
+
+            None
+        };
+
+        let parser = Parser::new_with_broken_link_callback(
+            markdown,
+            Options::ENABLE_TABLES,
+            Some(&mut broken_link_callback),
+        );
+
+        // Links are only resolved as the parser gets to them
+        parser.for_each(drop);
+    }
+
+    problems.extend(
+        fences::fence_problems(markdown)
+            .into_iter()
+            .map(|problem| match problem {
+                FenceProblem::Unclosed { line } => DocProblemKind::UnclosedFence { line },
+                FenceProblem::UnknownTag { line, tag } => {
+                    DocProblemKind::UnknownFenceTag { line, tag }
+                }
+            }),
+    );
+
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DocProblem, DocProblemKind};
+    use pretty_assertions::assert_eq;
+
+    fn problem(entry: Option<&str>, kind: DocProblemKind) -> String {
+        DocProblem {
+            module_name: "Dict".to_string(),
+            entry: entry.map(str::to_string),
+            kind,
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn unknown_link() {
+        let kind = DocProblemKind::UnknownLink {
+            reference: "Str.jion".to_string(),
+            message: "Str.jion is not exposed".to_string(),
+        };
+
+        assert_eq!(
+            problem(Some("insert"), kind),
+            "Dict.insert: Str.jion is not exposed"
+        );
+    }
+
+    #[test]
+    fn unclosed_fence_in_module_docs() {
+        assert_eq!(
+            problem(None, DocProblemKind::UnclosedFence { line: 4 }),
+            "Dict: the code block on line 4 of the doc comment is never closed"
+        );
+    }
+
+    #[test]
+    fn unknown_fence_tag() {
+        let kind = DocProblemKind::UnknownFenceTag {
+            line: 2,
+            tag: "rpel".to_string(),
+        };
+
+        assert_eq!(
+            problem(Some("empty"), kind),
+            "Dict.empty: the code block on line 2 of the doc comment has an unknown tag `rpel`"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Math in doc comments, when [DocsConfig::math](crate::DocsConfig::math) is on: `$x^2$` inline,
+//! `$$\sum_i x_i$$` on its own line, and ```math fences. The TeX is escaped into elements with the
+//! `math` class, which KaTeX renders in the browser. Pages without math don't load KaTeX at all.
+//!
+//! A `$` only starts math when it's followed by something other than whitespace, and only ends it
+//! when it comes after something other than whitespace and isn't followed by a digit, so prices
+//! like "$5 or $10" stay as they are. `\$` is always a plain dollar sign.
+use crate::metadata::escape_attr;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use std::ops::Range;
+
+/// The script that renders the math on a page, next to search.js
+pub const MATH_JS: &str = "math.js";
+
+const KATEX_URL: &str = "https://cdn.jsdelivr.net/npm/katex@0.16.9/dist";
+
+// Stand-ins for the math while the rest of the markdown is rendered, so that e.g. the `_` in
+// `$x_1$` isn't read as emphasis. They're in the private use area, so they can't clash with text.
+const PLACEHOLDER_START: char = '\u{E000}';
+const PLACEHOLDER_END: char = '\u{E001}';
+
+/// The markdown with its math swapped for placeholders, and the html each placeholder stands for
+pub struct Extracted {
+    pub markdown: String,
+    rendered: Vec<String>,
+}
+
+impl Extracted {
+    /// Put the math back into the html rendered from [Extracted::markdown]
+    pub fn restore(&self, html: &str) -> String {
+        if self.rendered.is_empty() {
+            return html.to_string();
+        }
+
+        let mut buf = String::with_capacity(html.len());
+        let mut rest = html;
+
+        while let Some(start) = rest.find(PLACEHOLDER_START) {
+            buf.push_str(&rest[..start]);
+            rest = &rest[start + PLACEHOLDER_START.len_utf8()..];
+
+            let end = rest.find(PLACEHOLDER_END).unwrap_or(rest.len());
+            match rest[..end].parse::<usize>() {
+                Ok(index) if index < self.rendered.len() => buf.push_str(&self.rendered[index]),
+                _ => buf.push_str(&rest[..end]),
+            }
+            rest = &rest[(end + PLACEHOLDER_END.len_utf8()).min(rest.len())..];
+        }
+
+        buf.push_str(rest);
+        buf
+    }
+}
+
+/// Swap the math in the markdown for placeholders. Code spans and code blocks are left alone.
+pub fn extract_math(markdown: &str, options: Options) -> Extracted {
+    let mut extracted = Extracted {
+        markdown: String::with_capacity(markdown.len()),
+        rendered: Vec::new(),
+    };
+    let mut copied = 0;
+
+    for code in code_ranges(markdown, options) {
+        if code.start >= copied {
+            extract_from_text(&mut extracted, &markdown[copied..code.start]);
+            extracted.markdown.push_str(&markdown[code.clone()]);
+            copied = code.end;
+        }
+    }
+
+    extract_from_text(&mut extracted, &markdown[copied..]);
+    extracted
+}
+
+/// Where the code spans and code blocks are, in order. Their `$`s are never math.
+fn code_ranges(markdown: &str, options: Options) -> Vec<Range<usize>> {
+    Parser::new_ext(markdown, options)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Html(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+fn extract_from_text(extracted: &mut Extracted, text: &str) {
+    let bytes = text.as_bytes();
+    let mut copied = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] != b'$' || is_escaped(bytes, index) {
+            index += 1;
+            continue;
+        }
+
+        let display = bytes.get(index + 1) == Some(&b'$');
+        let found = if display {
+            find_display_end(bytes, index + 2).map(|end| (index + 2, end, end + 2))
+        } else {
+            find_inline_end(bytes, index + 1).map(|end| (index + 1, end, end + 1))
+        };
+
+        match found {
+            Some((tex_start, tex_end, after)) => {
+                extracted.markdown.push_str(&text[copied..index]);
+                extracted.markdown.push(PLACEHOLDER_START);
+                extracted
+                    .markdown
+                    .push_str(&extracted.rendered.len().to_string());
+                extracted.markdown.push(PLACEHOLDER_END);
+                extracted
+                    .rendered
+                    .push(render_math(&text[tex_start..tex_end], display));
+
+                index = after;
+                copied = after;
+            }
+            None => {
+                // Skip both dollars of a `$$` that doesn't close, so the second isn't read as `$`
+                index += if display { 2 } else { 1 };
+            }
+        }
+    }
+
+    extracted.markdown.push_str(&text[copied..]);
+}
+
+/// Whether the byte at `index` comes after an odd number of backslashes
+fn is_escaped(bytes: &[u8], index: usize) -> bool {
+    bytes[..index]
+        .iter()
+        .rev()
+        .take_while(|byte| **byte == b'\\')
+        .count()
+        % 2
+        == 1
+}
+
+/// Where the TeX of `$...$` ends, i.e. the index of the closing `$`
+fn find_inline_end(bytes: &[u8], start: usize) -> Option<usize> {
+    match bytes.get(start) {
+        Some(byte) if !byte.is_ascii_whitespace() => {}
+        _ => return None,
+    }
+
+    let mut index = start + 1;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'$' if !is_escaped(bytes, index) => {
+                let closes = !bytes[index - 1].is_ascii_whitespace()
+                    && !bytes.get(index + 1).map_or(false, u8::is_ascii_digit);
+
+                return if closes { Some(index) } else { None };
+            }
+            b'\n' if bytes.get(index + 1) == Some(&b'\n') => return None,
+            _ => index += 1,
+        }
+    }
+
+    None
+}
+
+/// Where the TeX of `$$...$$` ends, i.e. the index of the first closing `$`
+fn find_display_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut index = start;
+
+    while index + 1 < bytes.len() {
+        match bytes[index] {
+            b'$' if bytes[index + 1] == b'$' && !is_escaped(bytes, index) => {
+                let is_empty = bytes[start..index].iter().all(u8::is_ascii_whitespace);
+
+                return if is_empty { None } else { Some(index) };
+            }
+            b'\n' if bytes[index + 1] == b'\n' => return None,
+            _ => index += 1,
+        }
+    }
+
+    None
+}
+
+/// The element KaTeX renders the TeX into. Until it does, or if it can't, the TeX shows as is.
+pub fn render_math(tex: &str, display: bool) -> String {
+    let class = if display {
+        "math math-display"
+    } else {
+        "math math-inline"
+    };
+
+    format!(
+        r#"<span class="{class}">{}</span>"#,
+        escape_attr(tex.trim())
+    )
+}
+
+/// A ```math fence, which is always displayed on its own
+pub fn render_math_fence(tex: &str) -> String {
+    format!(
+        r#"<div class="math math-display">{}</div>"#,
+        escape_attr(tex.trim())
+    )
+}
+
+/// Whether a rendered page has any math in it, and so needs [render_head]
+pub fn has_math(html: &str) -> bool {
+    // Text from the markdown has its quotes escaped, so only our own elements can match.
+    html.contains(r#"class="math "#)
+}
+
+/// The stylesheet and scripts for rendering math, for a page's `<head>`
+pub fn render_head() -> String {
+    format!(
+        r#"<link rel="stylesheet" href="{KATEX_URL}/katex.min.css" crossorigin="anonymous">
+    <script type="text/javascript" src="{KATEX_URL}/katex.min.js" crossorigin="anonymous" defer></script>
+    <script type="text/javascript" src="/{MATH_JS}" defer></script>"#
+    )
+}
+
+/// Whether a fence's info string is for math, e.g. ```math
+pub fn is_math_fence(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace()).next() == Some("math")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract_math, has_math, is_math_fence, render_math, render_math_fence};
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Options;
+
+    fn placeholder(index: usize) -> String {
+        format!("\u{E000}{index}\u{E001}")
+    }
+
+    #[test]
+    fn inline_math_but_not_prices() {
+        let extracted = extract_math("Area $\\pi r^2$ costs $5 or $10.", Options::empty());
+
+        assert_eq!(
+            extracted.markdown,
+            format!("Area {} costs $5 or $10.", placeholder(0))
+        );
+        assert_eq!(
+            extracted.restore(&format!("<p>{}</p>", extracted.markdown)),
+            r#"<p>Area <span class="math math-inline">\pi r^2</span> costs $5 or $10.</p>"#
+        );
+    }
+
+    #[test]
+    fn tex_is_escaped() {
+        let extracted = extract_math("$a < b$ and $$x > \"y\"$$", Options::empty());
+
+        assert_eq!(
+            extracted.restore(&extracted.markdown),
+            concat!(
+                r#"<span class="math math-inline">a &lt; b</span> and "#,
+                r#"<span class="math math-display">x &gt; &quot;y&quot;</span>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn code_escaped_dollars_and_paragraph_breaks_are_not_math() {
+        let extracted = extract_math("`$x$` and $y$", Options::empty());
+        assert_eq!(extracted.markdown, format!("`$x$` and {}", placeholder(0)));
+
+        for markdown in ["\\$x$ costs", "$x\n\ny$", "```\n$x$\n```\n"] {
+            let extracted = extract_math(markdown, Options::empty());
+
+            assert_eq!(extracted.markdown, markdown);
+            assert_eq!(extracted.restore(markdown), markdown);
+        }
+    }
+
+    #[test]
+    fn math_fences() {
+        assert!(is_math_fence("math"));
+        assert!(is_math_fence("math,ignore"));
+        assert!(!is_math_fence("mathematica"));
+        assert!(!is_math_fence("roc"));
+
+        assert_eq!(
+            render_math_fence("\n  x^2 < 1\n"),
+            r#"<div class="math math-display">x^2 &lt; 1</div>"#
+        );
+    }
+
+    #[test]
+    fn pages_with_math() {
+        assert!(has_math(&render_math("x", false)));
+        assert!(has_math(&render_math_fence("x")));
+        assert!(!has_math(r#"<p class="mathematics">x</p>"#));
+        assert!(!has_math("<p>class=&quot;math &quot;</p>"));
+    }
+}
@@ -0,0 +1,181 @@
+//! Metadata for search engines and link previews: canonical URLs,
+//! OpenGraph/Twitter meta tags, and sitemap.xml.
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+
+/// The absolute URL of a module's page, e.g. "https://roc-lang.org/builtins/Str/"
+pub fn canonical_url(canonical_base_url: &str, module_name: &str) -> String {
+    format!(
+        "{}/{}/",
+        canonical_base_url.trim_end_matches('/'),
+        module_name.replace('.', "/")
+    )
+}
+
+/// The `<meta>` and `<link>` tags that go in the `<head>` of a page.
+pub fn render_page_metadata(
+    title: &str,
+    description: Option<&str>,
+    canonical_url: Option<&str>,
+) -> String {
+    let mut buf = String::new();
+    let title = escape_attr(title);
+
+    if let Some(url) = canonical_url {
+        let url = escape_attr(url);
+
+        buf.push_str(&format!(r#"<link rel="canonical" href="{url}">"#));
+        buf.push_str(&format!(r#"<meta property="og:url" content="{url}">"#));
+    }
+
+    buf.push_str(r#"<meta property="og:type" content="website">"#);
+    buf.push_str(&format!(r#"<meta property="og:title" content="{title}">"#));
+    buf.push_str(r#"<meta name="twitter:card" content="summary">"#);
+    buf.push_str(&format!(r#"<meta name="twitter:title" content="{title}">"#));
+
+    if let Some(description) = description {
+        let description = escape_attr(description);
+
+        buf.push_str(&format!(
+            r#"<meta name="description" content="{description}">"#
+        ));
+        buf.push_str(&format!(
+            r#"<meta property="og:description" content="{description}">"#
+        ));
+        buf.push_str(&format!(
+            r#"<meta name="twitter:description" content="{description}">"#
+        ));
+    }
+
+    buf
+}
+
+/// The first sentence of the first doc comment in the module, as plain text.
+pub fn module_description(module: &ModuleDocumentation) -> Option<String> {
+    module.entries.iter().find_map(|entry| {
+        let docs = match entry {
+            DocEntry::DetachedDoc(docs) => docs.as_str(),
+            DocEntry::DocDef(doc_def) if crate::is_hidden(doc_def) => return None,
+            DocEntry::DocDef(doc_def) => doc_def.docs.as_deref()?,
+        };
+
+        first_sentence(docs)
+    })
+}
+
+/// Extracts the first sentence of the first paragraph of some markdown, without any formatting.
+pub fn first_sentence(markdown: &str) -> Option<String> {
+    use pulldown_cmark::{Event, Parser, Tag};
+
+    let mut text = String::new();
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(str) | Event::Code(str) => text.push_str(&str),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(Tag::Paragraph) if !text.trim().is_empty() => break,
+            Event::End(Tag::CodeBlock(_) | Tag::Heading(..)) => text.clear(),
+            _ => {}
+        }
+    }
+
+    let text = text.trim();
+    let end = text
+        .match_indices(|c| c == '.' || c == '!' || c == '?')
+        .map(|(index, punctuation)| index + punctuation.len())
+        .find(|&end| text[end..].is_empty() || text[end..].starts_with(char::is_whitespace))
+        .unwrap_or(text.len());
+    let sentence = &text[..end];
+
+    if sentence.is_empty() {
+        None
+    } else {
+        Some(sentence.to_string())
+    }
+}
+
+pub fn render_sitemap<I: Iterator<Item = String>>(urls: I) -> String {
+    let mut buf = String::new();
+
+    buf.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    buf.push('\n');
+    buf.push_str(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#);
+    buf.push('\n');
+
+    for url in urls {
+        buf.push_str("  <url><loc>");
+        buf.push_str(&escape_attr(&url));
+        buf.push_str("</loc></url>\n");
+    }
+
+    buf.push_str("</urlset>\n");
+
+    buf
+}
+
+/// Escapes text for use in an HTML/XML attribute or text node.
+pub fn escape_attr(text: &str) -> String {
+    let mut buf = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\'' => buf.push_str("&#39;"),
+            _ => buf.push(c),
+        }
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::first_sentence;
+
+    #[test]
+    fn stops_at_the_end_of_the_first_sentence() {
+        assert_eq!(
+            first_sentence("Adds two numbers. Overflow crashes!"),
+            Some("Adds two numbers.".to_string())
+        );
+        assert_eq!(
+            first_sentence("Is it empty? Check the length."),
+            Some("Is it empty?".to_string())
+        );
+    }
+
+    #[test]
+    fn punctuation_inside_a_word_does_not_end_the_sentence() {
+        assert_eq!(
+            first_sentence("Works like `List.map`, but version 1.2 only.\nMore text."),
+            Some("Works like List.map, but version 1.2 only.".to_string())
+        );
+    }
+
+    #[test]
+    fn skips_headings_and_code_blocks() {
+        let markdown = "# Overview\n\n```\nx = 1. y = 2.\n```\n\nThe actual summary. The rest.";
+
+        assert_eq!(
+            first_sentence(markdown),
+            Some("The actual summary.".to_string())
+        );
+    }
+
+    #[test]
+    fn a_paragraph_without_punctuation_is_one_sentence() {
+        assert_eq!(
+            first_sentence("Decodes JSON\ninto Roc values\n\nSecond paragraph."),
+            Some("Decodes JSON into Roc values".to_string())
+        );
+    }
+
+    #[test]
+    fn nothing_to_summarize() {
+        assert_eq!(first_sentence(""), None);
+        assert_eq!(first_sentence("# Just a heading"), None);
+        assert_eq!(first_sentence("```\ncode only\n```"), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Choosing which modules get documented, with glob patterns on their names, e.g. `Json.*` or
+//! `Internal*`. In a pattern, `*` matches any run of characters, dots included, and `?` matches
+//! exactly one character.
+//!
+//! The modules which are left out are removed before anything gets generated, so they have no
+//! pages and aren't in the sidebar, the search index or any of the other generated files. Doc
+//! links to their entries render as plain code, since there's nothing to link to, unless they're
+//! documented somewhere else.
+use crate::reexports::Reexport;
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::ModuleDocumentation;
+use roc_module::symbol::{Interns, ModuleId, Symbol};
+
+/// Remove the modules which don't match any of the `include` patterns (unless there are none),
+/// or which match one of the `exclude` patterns. Returns the ones which were removed.
+pub fn retain_documented(
+    docs_by_module: &mut VecMap<ModuleId, ModuleDocumentation>,
+    include: &[String],
+    exclude: &[String],
+) -> VecSet<ModuleId> {
+    for pattern in include.iter().chain(exclude) {
+        let matches_any = docs_by_module
+            .values()
+            .any(|module| glob_matches(pattern, &module.name));
+
+        if !matches_any {
+            eprintln!("Warning: no module matches the pattern `{pattern}`");
+        }
+    }
+
+    let mut left_out = VecSet::default();
+
+    for (module_id, _) in
+        docs_by_module.drain_filter(|_, module| !is_documented(&module.name, include, exclude))
+    {
+        left_out.insert(module_id);
+    }
+
+    left_out
+}
+
+/// Whether a doc link like `[Internal.decode]` is to one of the `left_out` modules, which
+/// [retain_documented] removed. Modules which were never documented, like the builtins, aren't
+/// left out, and neither are entries which are documented somewhere else, like a re-export or
+/// another package in the workspace.
+pub fn is_left_out(
+    reference: &str,
+    interns: &Interns,
+    left_out: &VecSet<ModuleId>,
+    reexports: &VecMap<Symbol, Reexport>,
+) -> bool {
+    let (module_name, ident) = match reference.rsplit_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    match interns.module_ids.get_id(&module_name.into()) {
+        Some(module_id) if left_out.contains(&module_id) => {
+            let documented_elsewhere = interns
+                .all_ident_ids
+                .get(&module_id)
+                .and_then(|ident_ids| ident_ids.get_id(ident))
+                .map_or(false, |ident_id| {
+                    reexports.contains_key(&Symbol::new(module_id, ident_id))
+                });
+
+            !documented_elsewhere
+        }
+        _ => false,
+    }
+}
+
+fn is_documented(module_name: &str, include: &[String], exclude: &[String]) -> bool {
+    let included = include.is_empty()
+        || include
+            .iter()
+            .any(|pattern| glob_matches(pattern, module_name));
+
+    included
+        && !exclude
+            .iter()
+            .any(|pattern| glob_matches(pattern, module_name))
+}
+
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let name = name.as_bytes();
+
+    // Where to resume if the rest doesn't match: just after the last `*`, with it having
+    // matched one more byte of the name
+    let mut backtrack: Option<(usize, usize)> = None;
+    let mut p = 0;
+    let mut n = 0;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&byte) if byte == b'?' || byte == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&byte| byte == b'*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{glob_matches, is_documented, is_left_out};
+    use crate::reexports::Reexport;
+    use roc_collections::{VecMap, VecSet};
+    use roc_module::symbol::{Interns, Symbol};
+
+    #[test]
+    fn star_matches_any_run_of_characters() {
+        assert!(glob_matches("Json.*", "Json.Decode"));
+        assert!(glob_matches("Json.*", "Json.Decode.Internal"));
+        assert!(!glob_matches("Json.*", "Json"));
+        assert!(glob_matches("Internal*", "Internal"));
+        assert!(glob_matches("Internal*", "InternalHelpers"));
+        assert!(glob_matches("*.Internal", "Json.Decode.Internal"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!glob_matches("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn question_mark_matches_exactly_one_character() {
+        assert!(glob_matches("V?", "V2"));
+        assert!(!glob_matches("V?", "V"));
+        assert!(!glob_matches("V?", "V10"));
+    }
+
+    #[test]
+    fn without_wildcards_the_whole_name_has_to_match() {
+        assert!(glob_matches("Dict", "Dict"));
+        assert!(!glob_matches("Dict", "DictExtra"));
+        assert!(!glob_matches("Dict", "Dic"));
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let none: &[String] = &[];
+        let json = &["Json.*".to_string()];
+        let internal = &["*Internal*".to_string()];
+
+        assert!(is_documented("Dict", none, none));
+        assert!(is_documented("Json.Decode", json, none));
+        assert!(!is_documented("Dict", json, none));
+        assert!(!is_documented("InternalDict", none, internal));
+        assert!(!is_documented("Json.Internal", json, internal));
+        assert!(is_documented("Json.Encode", json, internal));
+    }
+
+    #[test]
+    fn only_links_to_removed_modules_are_left_out() {
+        let mut interns = Interns::default();
+        let internal = interns.module_id(&"Internal".into());
+        let decode = interns
+            .all_ident_ids
+            .get_or_insert(internal)
+            .add_str("decode");
+        let mut left_out = VecSet::default();
+        left_out.insert(internal);
+        let none = VecMap::default();
+
+        assert!(is_left_out("Internal.decode", &interns, &left_out, &none));
+        // The builtins aren't documented, but they weren't removed either
+        assert!(!is_left_out("Str.concat", &interns, &left_out, &none));
+        assert!(!is_left_out("Unknown.decode", &interns, &left_out, &none));
+        assert!(!is_left_out("decode", &interns, &left_out, &none));
+
+        let mut reexports = VecMap::default();
+        reexports.insert(
+            Symbol::new(internal, decode),
+            Reexport {
+                module_name: "Json".to_string(),
+                name: "decode".to_string(),
+                package_url: None,
+            },
+        );
+
+        assert!(!is_left_out(
+            "Internal.decode",
+            &interns,
+            &left_out,
+            &reexports
+        ));
+    }
+}
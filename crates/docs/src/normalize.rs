@@ -0,0 +1,411 @@
+//! Makes the generated docs come out the same on every run, so that they can be diffed.
+//!
+//! Modules finish loading in whatever order their threads happen to finish, so they're sorted by
+//! name. Type variables are renamed to `a`, `b`, `c`… in the order they first appear in each
+//! signature, so that a signature is always rendered the same way, however it was written. The
+//! docs get the same names in their inline code, so that they still match the signature.
+use roc_collections::VecMap;
+use roc_load::docs::{DocDef, DocEntry, ModuleDocumentation, RecordField, TypeAnnotation};
+use roc_module::symbol::ModuleId;
+
+/// Sort the modules by name, for the sidebar and everything else that lists them
+pub fn sort_modules(docs_by_module: &mut VecMap<ModuleId, ModuleDocumentation>) {
+    let mut modules: Vec<(ModuleId, ModuleDocumentation)> =
+        std::mem::take(docs_by_module).into_iter().collect();
+
+    modules.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+
+    *docs_by_module = modules.into_iter().collect();
+}
+
+/// Rename the type variables of every entry in the module
+pub fn rename_type_vars(module: &mut ModuleDocumentation) {
+    for entry in module.entries.iter_mut() {
+        if let DocEntry::DocDef(doc_def) = entry {
+            rename_in_doc_def(doc_def);
+        }
+    }
+}
+
+/// The type variables in a type's header come first, so `Dict k v` becomes `Dict a b`. An
+/// argument's type is part of the function's signature, so it uses the same names, and so does
+/// the prose.
+fn rename_in_doc_def(doc_def: &mut DocDef) {
+    let mut renamer = Renamer::default();
+
+    for type_var in doc_def.type_vars.iter_mut() {
+        *type_var = renamer.rename(type_var);
+    }
+
+    renamer.annotation(&mut doc_def.type_annotation);
+
+    for arg in doc_def.args.iter_mut() {
+        renamer.annotation(&mut arg.type_annotation);
+    }
+
+    renamer.docs(&mut doc_def.docs);
+
+    for arg in doc_def.args.iter_mut() {
+        renamer.docs(&mut arg.docs);
+    }
+}
+
+/// The new names of the type variables seen so far in one signature
+#[derive(Default)]
+struct Renamer {
+    names: VecMap<String, String>,
+}
+
+impl Renamer {
+    fn rename(&mut self, name: &str) -> String {
+        let next = self.names.len();
+
+        self.names
+            .get_or_insert(name.to_string(), || var_name(next))
+            .clone()
+    }
+
+    fn annotation(&mut self, type_ann: &mut TypeAnnotation) {
+        match type_ann {
+            TypeAnnotation::BoundVariable(name) => *name = self.rename(name),
+            TypeAnnotation::TagUnion { tags, extension } => {
+                for value in tags.iter_mut().flat_map(|tag| tag.values.iter_mut()) {
+                    self.annotation(value);
+                }
+                self.annotation(extension);
+            }
+            TypeAnnotation::Function { args, output } => {
+                for arg in args.iter_mut() {
+                    self.annotation(arg);
+                }
+                self.annotation(output);
+            }
+            TypeAnnotation::Apply { parts, .. } => {
+                for part in parts.iter_mut() {
+                    self.annotation(part);
+                }
+            }
+            TypeAnnotation::Record { fields, extension } => {
+                for field in fields.iter_mut() {
+                    match field {
+                        RecordField::RecordField {
+                            type_annotation, ..
+                        }
+                        | RecordField::OptionalField {
+                            type_annotation, ..
+                        } => self.annotation(type_annotation),
+                        RecordField::LabelOnly { .. } => {}
+                    }
+                }
+                self.annotation(extension);
+            }
+            TypeAnnotation::Ability { members } => {
+                // Each member has a signature of its own
+                for member in members.iter_mut() {
+                    let mut renamer = Renamer::default();
+
+                    renamer.annotation(&mut member.type_annotation);
+
+                    for (name, abilities) in member.able_variables.iter_mut() {
+                        *name = renamer.rename(name);
+                        for ability in abilities.iter_mut() {
+                            renamer.annotation(ability);
+                        }
+                    }
+
+                    renamer.docs(&mut member.docs);
+                }
+            }
+            TypeAnnotation::ObscuredTagUnion
+            | TypeAnnotation::ObscuredRecord
+            | TypeAnnotation::Wildcard
+            | TypeAnnotation::NoTypeAnn => {}
+        }
+    }
+
+    /// Use the new names in the docs' inline code, like `List elem`. Plain words are left alone,
+    /// since "state" or "a" in a sentence probably isn't the type variable, and so are code blocks,
+    /// which are examples rather than signatures.
+    fn docs(&self, docs: &mut Option<String>) {
+        if let Some(text) = docs {
+            *text = self.code_spans(text);
+        }
+    }
+
+    fn code_spans(&self, text: &str) -> String {
+        let mut buf = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('`') {
+            buf.push_str(&rest[..start]);
+
+            let span = &rest[start..];
+            let ticks = span.len() - span.trim_start_matches('`').len();
+            let (fence, code) = span.split_at(ticks);
+
+            match code.find(fence) {
+                Some(end) => {
+                    buf.push_str(fence);
+                    if ticks < 3 {
+                        buf.push_str(&self.code(&code[..end]));
+                    } else {
+                        buf.push_str(&code[..end]);
+                    }
+                    buf.push_str(fence);
+                    rest = &code[end + ticks..];
+                }
+                None => {
+                    // An unclosed backtick is just a backtick
+                    buf.push_str(span);
+                    rest = "";
+                }
+            }
+        }
+
+        buf.push_str(rest);
+
+        buf
+    }
+
+    /// Rename the lowercase identifiers in some code that are type variables. An identifier after
+    /// a `.` is a field or a module's value, so it's never one.
+    fn code(&self, code: &str) -> String {
+        let mut buf = String::with_capacity(code.len());
+        let mut word = String::new();
+        let mut after_dot = false;
+
+        for c in code.chars().chain(std::iter::once(' ')) {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+                continue;
+            }
+
+            if !word.is_empty() {
+                match self.names.get(&word) {
+                    Some(new_name) if !after_dot => buf.push_str(new_name),
+                    _ => buf.push_str(&word),
+                }
+                word.clear();
+            }
+
+            after_dot = c == '.';
+            buf.push(c);
+        }
+
+        // Drop the space that ended the last word
+        buf.pop();
+
+        buf
+    }
+}
+
+/// `a` to `z`, then `a1` to `z1`, and so on
+fn var_name(index: usize) -> String {
+    let letter = (b'a' + (index % 26) as u8) as char;
+
+    match index / 26 {
+        0 => letter.to_string(),
+        round => format!("{letter}{round}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rename_in_doc_def, var_name};
+    use roc_load::docs::{AbilityMember, DocArg, DocDef, TypeAnnotation};
+    use roc_module::symbol::Symbol;
+
+    fn var(name: &str) -> TypeAnnotation {
+        TypeAnnotation::BoundVariable(name.to_string())
+    }
+
+    fn apply(name: &str, parts: Vec<TypeAnnotation>) -> TypeAnnotation {
+        TypeAnnotation::Apply {
+            name: name.to_string(),
+            parts,
+        }
+    }
+
+    fn doc_def(type_vars: &[&str], type_annotation: TypeAnnotation) -> DocDef {
+        DocDef {
+            name: "insert".to_string(),
+            symbol: Symbol::LIST_MAP,
+            type_vars: type_vars.iter().map(|var| var.to_string()).collect(),
+            type_annotation,
+            docs: None,
+            implements: Vec::new(),
+            referenced_types: Vec::new(),
+            args: Vec::new(),
+            source: String::new(),
+            reexported_from: None,
+        }
+    }
+
+    /// The names of the type variables in the annotation, in the order they appear
+    fn var_names(type_ann: &TypeAnnotation, names: &mut Vec<String>) {
+        match type_ann {
+            TypeAnnotation::BoundVariable(name) => names.push(name.clone()),
+            TypeAnnotation::Function { args, output } => {
+                for arg in args.iter() {
+                    var_names(arg, names);
+                }
+                var_names(output, names);
+            }
+            TypeAnnotation::Apply { parts, .. } => {
+                for part in parts.iter() {
+                    var_names(part, names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn names_in(type_ann: &TypeAnnotation) -> Vec<String> {
+        let mut names = Vec::new();
+
+        var_names(type_ann, &mut names);
+
+        names
+    }
+
+    #[test]
+    fn var_names_go_round_the_alphabet() {
+        assert_eq!(var_name(0), "a");
+        assert_eq!(var_name(25), "z");
+        assert_eq!(var_name(26), "a1");
+        assert_eq!(var_name(27), "b1");
+        assert_eq!(var_name(52), "a2");
+    }
+
+    #[test]
+    fn renames_in_order_of_first_appearance() {
+        // insert : Dict k v, k, elem -> Dict k v
+        let mut def = doc_def(
+            &[],
+            TypeAnnotation::Function {
+                args: vec![
+                    apply("Dict", vec![var("key"), var("value")]),
+                    var("key"),
+                    var("elem"),
+                ],
+                output: Box::new(apply("Dict", vec![var("key"), var("value")])),
+            },
+        );
+
+        rename_in_doc_def(&mut def);
+
+        assert_eq!(
+            names_in(&def.type_annotation),
+            ["a", "b", "a", "c", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn header_and_args_share_names_with_the_annotation() {
+        // Dict k v : List v k extra, with an argument of type v
+        let mut def = doc_def(
+            &["k", "v"],
+            apply("List", vec![var("v"), var("k"), var("extra")]),
+        );
+        def.args.push(DocArg {
+            name: "value".to_string(),
+            type_annotation: var("v"),
+            docs: None,
+        });
+
+        rename_in_doc_def(&mut def);
+
+        assert_eq!(def.type_vars, ["a", "b"]);
+        assert_eq!(names_in(&def.type_annotation), ["b", "a", "c"]);
+        assert_eq!(names_in(&def.args[0].type_annotation), ["b"]);
+    }
+
+    #[test]
+    fn docs_use_the_new_names_in_inline_code() {
+        // walk : List elem, state, (state, elem -> state) -> state
+        let mut def = doc_def(
+            &[],
+            TypeAnnotation::Function {
+                args: vec![
+                    apply("List", vec![var("elem")]),
+                    var("state"),
+                    TypeAnnotation::Function {
+                        args: vec![var("state"), var("elem")],
+                        output: Box::new(var("state")),
+                    },
+                ],
+                output: Box::new(var("state")),
+            },
+        );
+        def.docs = Some(
+            "Walks a `List elem`, starting from the given `state`. The new state is \
+             `step state elem`, or `model.state` for a record.\n\n\
+             ```\nList.walk [1] 0 \\state, elem -> state + elem\n```\n\n\
+             An unclosed ` is left as it is"
+                .to_string(),
+        );
+        def.args.push(DocArg {
+            name: "state".to_string(),
+            type_annotation: var("state"),
+            docs: Some("The initial `state`".to_string()),
+        });
+
+        rename_in_doc_def(&mut def);
+
+        assert_eq!(
+            def.docs.as_deref(),
+            Some(
+                "Walks a `List a`, starting from the given `b`. The new state is \
+                 `step b a`, or `model.state` for a record.\n\n\
+                 ```\nList.walk [1] 0 \\state, elem -> state + elem\n```\n\n\
+                 An unclosed ` is left as it is"
+            )
+        );
+        assert_eq!(def.args[0].docs.as_deref(), Some("The initial `b`"));
+    }
+
+    #[test]
+    fn ability_members_are_renamed_separately() {
+        let member = |name: &str, type_annotation| AbilityMember {
+            name: name.to_string(),
+            type_annotation,
+            able_variables: vec![("self".to_string(), vec![apply("Hash", vec![])])],
+            docs: Some(format!("`{name} self`")),
+        };
+        let mut def = doc_def(
+            &[],
+            TypeAnnotation::Ability {
+                members: vec![
+                    member(
+                        "hash",
+                        TypeAnnotation::Function {
+                            args: vec![var("hasher"), var("self")],
+                            output: Box::new(var("hasher")),
+                        },
+                    ),
+                    member(
+                        "isEq",
+                        TypeAnnotation::Function {
+                            args: vec![var("self"), var("self")],
+                            output: Box::new(apply("Bool", vec![])),
+                        },
+                    ),
+                ],
+            },
+        );
+
+        rename_in_doc_def(&mut def);
+
+        match &def.type_annotation {
+            TypeAnnotation::Ability { members } => {
+                assert_eq!(names_in(&members[0].type_annotation), ["a", "b", "a"]);
+                assert_eq!(members[0].able_variables[0].0, "b");
+                assert_eq!(names_in(&members[1].type_annotation), ["a", "a"]);
+                assert_eq!(members[1].able_variables[0].0, "a");
+                assert_eq!(members[0].docs.as_deref(), Some("`hash b`"));
+                assert_eq!(members[1].docs.as_deref(), Some("`isEq a`"));
+            }
+            other => panic!("expected an ability, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! An OpenSearch descriptor, so browsers can offer to search the docs from the address bar.
+//! It points at a docs page with `?search=` and the query, which search.js opens the search
+//! palette for. Other sites can link to searches the same way.
+use crate::metadata::escape_attr;
+
+/// The file the descriptor is written to, next to the pages it searches
+pub const OPENSEARCH_XML: &str = "opensearch.xml";
+
+/// Browsers only show this many characters of a search engine's name
+const MAX_SHORT_NAME_CHARS: usize = 16;
+
+/// The descriptor for searching the docs at `search_page_url`. `title` is
+/// [DocsStrings::opensearch_title](crate::DocsStrings::opensearch_title) with the package name
+/// filled in.
+pub fn render_opensearch_descriptor(
+    title: &str,
+    search_page_url: &str,
+    favicon_url: &str,
+) -> String {
+    let short_name: String = title.chars().take(MAX_SHORT_NAME_CHARS).collect();
+    let template = format!("{}?search={{searchTerms}}", search_page_url);
+    let mut buf = String::new();
+
+    buf.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    buf.push('\n');
+    buf.push_str(r#"<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">"#);
+    buf.push('\n');
+    buf.push_str(&format!(
+        "  <ShortName>{}</ShortName>\n",
+        escape_attr(short_name.trim_end())
+    ));
+    buf.push_str(&format!(
+        "  <Description>{}</Description>\n",
+        escape_attr(title)
+    ));
+    buf.push_str("  <InputEncoding>UTF-8</InputEncoding>\n");
+    buf.push_str(&format!(
+        "  <Image type=\"image/svg+xml\">{}</Image>\n",
+        escape_attr(favicon_url)
+    ));
+    buf.push_str(&format!(
+        "  <Url type=\"text/html\" method=\"get\" template=\"{}\"/>\n",
+        escape_attr(&template)
+    ));
+    buf.push_str("</OpenSearchDescription>\n");
+
+    buf
+}
+
+/// The `<link>` that tells browsers about the descriptor, for the head of every page
+pub fn render_opensearch_link(title: &str, descriptor_url: &str) -> String {
+    format!(
+        r#"<link rel="search" type="application/opensearchdescription+xml" title="{}" href="{}">"#,
+        escape_attr(title),
+        escape_attr(descriptor_url)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_opensearch_descriptor, render_opensearch_link};
+
+    #[test]
+    fn descriptor_searches_the_page_with_the_query() {
+        assert_eq!(
+            render_opensearch_descriptor(
+                "json docs",
+                "https://example.com/json/",
+                "https://example.com/json/favicon.svg"
+            ),
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<OpenSearchDescription xmlns=\"http://a9.com/-/spec/opensearch/1.1/\">\n",
+                "  <ShortName>json docs</ShortName>\n",
+                "  <Description>json docs</Description>\n",
+                "  <InputEncoding>UTF-8</InputEncoding>\n",
+                "  <Image type=\"image/svg+xml\">https://example.com/json/favicon.svg</Image>\n",
+                "  <Url type=\"text/html\" method=\"get\" ",
+                "template=\"https://example.com/json/?search={searchTerms}\"/>\n",
+                "</OpenSearchDescription>\n",
+            )
+        );
+    }
+
+    #[test]
+    fn short_name_is_cut_to_16_characters() {
+        let descriptor = render_opensearch_descriptor(
+            "Search the <roc-lang/basic-cli> docs",
+            "/",
+            "/favicon.svg",
+        );
+
+        // Cut before escaping
+        assert!(
+            descriptor.contains("<ShortName>Search the &lt;roc-</ShortName>"),
+            "{}",
+            descriptor
+        );
+        assert!(
+            descriptor
+                .contains("<Description>Search the &lt;roc-lang/basic-cli&gt; docs</Description>"),
+            "{}",
+            descriptor
+        );
+
+        // ...and without a trailing space
+        let descriptor =
+            render_opensearch_descriptor("Search the docs for json", "/", "/favicon.svg");
+        assert!(
+            descriptor.contains("<ShortName>Search the docs</ShortName>"),
+            "{}",
+            descriptor
+        );
+    }
+
+    #[test]
+    fn urls_are_escaped() {
+        let descriptor =
+            render_opensearch_descriptor("docs", "/docs?v=1&lang=en", "/favicon.svg?v=1&x=2");
+
+        assert!(
+            descriptor.contains(r#"template="/docs?v=1&amp;lang=en?search={searchTerms}""#),
+            "{}",
+            descriptor
+        );
+        assert!(
+            descriptor.contains(">/favicon.svg?v=1&amp;x=2</Image>"),
+            "{}",
+            descriptor
+        );
+    }
+
+    #[test]
+    fn link_points_at_the_descriptor() {
+        assert_eq!(
+            render_opensearch_link("\"json\" docs", "/json/opensearch.xml"),
+            r#"<link rel="search" type="application/opensearchdescription+xml" title="&quot;json&quot; docs" href="/json/opensearch.xml">"#
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! The order entries appear in, both on a module's page and in the sidebar.
+//!
+//! A module can override the order chosen for the whole package with a line in its doc comment:
+//!
+//! - `@order source` keeps the order they're declared in.
+//! - `@order alphabetical` sorts them by name.
+//! - `@order map, walk, keepIf` puts those entries first, in that order, and the rest after them
+//!   in the order they're declared in.
+//!
+//! Only the entries move. Standalone doc comments, like the one at the top of the module,
+//! stay where they are.
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+use std::str::FromStr;
+
+const ORDER_MARKER: &str = "@order";
+
+/// How to order the entries of each module, unless its doc comment says otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EntryOrder {
+    /// The order they're declared in
+    #[default]
+    Source,
+    /// Sorted by name, ignoring case
+    Alphabetical,
+}
+
+impl FromStr for EntryOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "source" => Ok(EntryOrder::Source),
+            "alphabetical" => Ok(EntryOrder::Alphabetical),
+            _ => Err(format!(
+                "unknown entry order `{s}`, expected `source` or `alphabetical`"
+            )),
+        }
+    }
+}
+
+/// What a module's `@order` line asks for
+#[derive(Debug, PartialEq, Eq)]
+enum ModuleOrder {
+    Package(EntryOrder),
+    Explicit(Vec<String>),
+}
+
+/// Rearranges the module's entries, and removes its `@order` line so that it isn't rendered.
+pub fn order_entries(module: &mut ModuleDocumentation, package_order: EntryOrder) {
+    let order = match take_order_line(&mut module.entries) {
+        Some(line) => parse_order_line(&line),
+        None => ModuleOrder::Package(package_order),
+    };
+
+    if order == ModuleOrder::Package(EntryOrder::Source) {
+        return;
+    }
+
+    let mut doc_defs: Vec<DocEntry> = Vec::new();
+    let mut slots = Vec::new();
+
+    for (index, entry) in module.entries.iter().enumerate() {
+        if let DocEntry::DocDef(_) = entry {
+            slots.push(index);
+            doc_defs.push(entry.clone());
+        }
+    }
+
+    let name = |entry: &DocEntry| match entry {
+        DocEntry::DocDef(doc_def) => doc_def.name.clone(),
+        DocEntry::DetachedDoc(_) => unreachable!(),
+    };
+
+    match order {
+        ModuleOrder::Package(EntryOrder::Source) => {}
+        ModuleOrder::Package(EntryOrder::Alphabetical) => {
+            // The sort is stable, so names differing only in case stay in source order
+            doc_defs.sort_by_cached_key(|entry| name(entry).to_lowercase());
+        }
+        ModuleOrder::Explicit(names) => {
+            for listed in names.iter() {
+                if !doc_defs.iter().any(|entry| name(entry) == *listed) {
+                    eprintln!(
+                        "Warning: the @order line in {} lists `{listed}`, which isn't one of its entries",
+                        module.name
+                    );
+                }
+            }
+
+            doc_defs.sort_by_key(|entry| {
+                let entry_name = name(entry);
+
+                names
+                    .iter()
+                    .position(|listed| *listed == entry_name)
+                    .unwrap_or(names.len())
+            });
+        }
+    }
+
+    for (slot, doc_def) in slots.into_iter().zip(doc_defs) {
+        module.entries[slot] = doc_def;
+    }
+}
+
+/// Finds the `@order` line in the module's doc comment, if it has one, and removes it.
+fn take_order_line(entries: &mut [DocEntry]) -> Option<String> {
+    let docs = match entries.first_mut() {
+        Some(DocEntry::DetachedDoc(docs)) => docs,
+        _ => return None,
+    };
+
+    let mut order_line = None;
+    let mut remaining = String::with_capacity(docs.len());
+
+    for line in docs.split_inclusive('\n') {
+        match line.trim().strip_prefix(ORDER_MARKER) {
+            Some(rest) if order_line.is_none() && (rest.is_empty() || rest.starts_with(' ')) => {
+                order_line = Some(rest.trim().to_string());
+            }
+            _ => remaining.push_str(line),
+        }
+    }
+
+    if order_line.is_some() {
+        *docs = remaining;
+    }
+
+    order_line
+}
+
+fn parse_order_line(line: &str) -> ModuleOrder {
+    match line.parse() {
+        Ok(order) => ModuleOrder::Package(order),
+        Err(_) => ModuleOrder::Explicit(
+            line.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{order_entries, parse_order_line, take_order_line, EntryOrder, ModuleOrder};
+    use pretty_assertions::assert_eq;
+    use roc_can::scope::Scope;
+    use roc_collections::VecSet;
+    use roc_load::docs::{DocDef, DocEntry, ModuleDocumentation, TypeAnnotation};
+    use roc_module::symbol::{Interns, Symbol};
+
+    fn module(docs: &str, names: &[&str]) -> ModuleDocumentation {
+        let mut interns = Interns::default();
+        let home = interns.module_id(&"Dict".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(home);
+        let mut entries = vec![DocEntry::DetachedDoc(docs.to_string())];
+
+        for name in names {
+            entries.push(DocEntry::DocDef(DocDef {
+                name: name.to_string(),
+                symbol: Symbol::new(home, ident_ids.add_str(name)),
+                type_vars: Vec::new(),
+                type_annotation: TypeAnnotation::NoTypeAnn,
+                docs: None,
+                implements: Vec::new(),
+                referenced_types: Vec::new(),
+                args: Vec::new(),
+                source: String::new(),
+                reexported_from: None,
+            }));
+        }
+
+        // A standalone doc comment between the entries, which has to stay where it is
+        entries.insert(2, DocEntry::DetachedDoc("## More".to_string()));
+
+        ModuleDocumentation {
+            name: "Dict".to_string(),
+            entries,
+            scope: Scope::new(home, Default::default(), Default::default()),
+            exposed_symbols: VecSet::default(),
+        }
+    }
+
+    /// The module's doc comment, and the names of its entries in order, with `#` for the
+    /// standalone doc comment between them
+    fn layout(module: &ModuleDocumentation) -> (String, Vec<String>) {
+        let docs = match &module.entries[0] {
+            DocEntry::DetachedDoc(docs) => docs.clone(),
+            DocEntry::DocDef(_) => unreachable!(),
+        };
+        let names = module.entries[1..]
+            .iter()
+            .map(|entry| match entry {
+                DocEntry::DocDef(doc_def) => doc_def.name.clone(),
+                DocEntry::DetachedDoc(_) => "#".to_string(),
+            })
+            .collect();
+
+        (docs, names)
+    }
+
+    #[test]
+    fn entry_orders() {
+        assert_eq!("source".parse::<EntryOrder>(), Ok(EntryOrder::Source));
+        assert_eq!(
+            "alphabetical".parse::<EntryOrder>(),
+            Ok(EntryOrder::Alphabetical)
+        );
+        assert!("random".parse::<EntryOrder>().is_err());
+    }
+
+    #[test]
+    fn order_lines() {
+        assert_eq!(
+            parse_order_line("alphabetical"),
+            ModuleOrder::Package(EntryOrder::Alphabetical)
+        );
+        assert_eq!(
+            parse_order_line("map, walk,, keepIf "),
+            ModuleOrder::Explicit(vec![
+                "map".to_string(),
+                "walk".to_string(),
+                "keepIf".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn only_the_first_order_line_is_taken() {
+        let mut entries = vec![DocEntry::DetachedDoc(
+            "Dicts.\n  @order alphabetical\n@ordered\n@order source\n".to_string(),
+        )];
+
+        assert_eq!(
+            take_order_line(&mut entries),
+            Some("alphabetical".to_string())
+        );
+        assert!(matches!(
+            &entries[0],
+            DocEntry::DetachedDoc(docs) if docs == "Dicts.\n@ordered\n@order source\n"
+        ));
+        assert_eq!(take_order_line(&mut []), None);
+    }
+
+    #[test]
+    fn package_order() {
+        let names = ["walk", "Dict", "insert", "empty"];
+        let mut source = module("Dicts.", &names);
+        let mut alphabetical = module("Dicts.", &names);
+
+        order_entries(&mut source, EntryOrder::Source);
+        order_entries(&mut alphabetical, EntryOrder::Alphabetical);
+
+        assert_eq!(
+            layout(&source).1,
+            vec!["walk", "#", "Dict", "insert", "empty"]
+        );
+        assert_eq!(
+            layout(&alphabetical).1,
+            vec!["Dict", "#", "empty", "insert", "walk"]
+        );
+    }
+
+    #[test]
+    fn module_order_overrides_the_package_order() {
+        let mut alphabetical = module("Dicts.\n@order alphabetical\n", &["walk", "Dict", "empty"]);
+        let mut source = module("Dicts.\n@order source\n", &["walk", "Dict", "empty"]);
+
+        order_entries(&mut alphabetical, EntryOrder::Source);
+        order_entries(&mut source, EntryOrder::Alphabetical);
+
+        assert_eq!(
+            layout(&alphabetical),
+            (
+                "Dicts.\n".to_string(),
+                vec!["Dict", "#", "empty", "walk"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            )
+        );
+        assert_eq!(layout(&source).1, vec!["walk", "#", "Dict", "empty"]);
+    }
+
+    #[test]
+    fn explicit_order_then_the_rest_in_source_order() {
+        let mut module = module(
+            "@order empty, insert, missing\nDicts.",
+            &["walk", "Dict", "insert", "keys", "empty"],
+        );
+
+        order_entries(&mut module, EntryOrder::Alphabetical);
+
+        assert_eq!(
+            layout(&module),
+            (
+                "Dicts.".to_string(),
+                vec!["empty", "#", "insert", "walk", "Dict", "keys"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            )
+        );
+    }
+}
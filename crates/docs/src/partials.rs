@@ -0,0 +1,193 @@
+//! HTML that a site adds to every page of its docs, like its own navigation or an analytics
+//! snippet, without forking the page template.
+//!
+//! The template's placeholders are HTML comments which are filled in one after another, so
+//! comments are stripped from the partials before they go in. Otherwise a partial which happened
+//! to contain e.g. `<!-- Module Docs -->` would get a module's docs put in it.
+use std::fs;
+use std::path::Path;
+
+/// Tags which would end or restart the page around a partial
+const DOCUMENT_TAGS: [&str; 4] = ["!doctype", "html", "head", "body"];
+
+/// HTML to put in every page, in addition to the docs. Each one is empty by default.
+#[derive(Debug, Clone, Default)]
+pub struct HtmlPartials {
+    /// Goes at the end of each page's `<head>`, e.g. an analytics `<script>` or a stylesheet
+    pub head_extra: String,
+    /// Goes in the top header, after the package's name, e.g. links to the rest of the site
+    pub header: String,
+    /// Goes in the footer, after the lines of [DocsStrings::footer](crate::DocsStrings)
+    pub footer: String,
+}
+
+impl HtmlPartials {
+    /// Fill in the template's placeholders for the partials
+    pub(crate) fn fill(&self, template: &str) -> String {
+        template
+            .replace("<!-- Head extra -->", &strip_comments(&self.head_extra))
+            .replace("<!-- Header extra -->", &strip_comments(&self.header))
+            .replace("<!-- Footer extra -->", &strip_comments(&self.footer))
+    }
+}
+
+/// Read a partial for [HtmlPartials]. It's a fragment of a page rather than a page of its own, so
+/// it can't have a doctype or its own `<html>`, `<head>` or `<body>` tags.
+pub fn read_html_partial(path: &Path) -> Result<String, String> {
+    let html = fs::read_to_string(path)
+        .map_err(|err| format!("I couldn't read {}: {}", path.display(), err))?;
+
+    match find_document_tag(&html) {
+        Some(tag) => Err(format!(
+            "{} has a <{}> tag, but it should only have the HTML to add to each page, since the page around it is already there",
+            path.display(),
+            tag
+        )),
+        None => Ok(html),
+    }
+}
+
+fn find_document_tag(html: &str) -> Option<&'static str> {
+    let lowercase = html.to_ascii_lowercase();
+
+    for (index, _) in lowercase.match_indices('<') {
+        let rest = lowercase[index + 1..].trim_start_matches('/');
+
+        for tag in DOCUMENT_TAGS {
+            // e.g. <head> or <head lang="en">, but not <header>
+            let is_tag = rest.strip_prefix(tag).map_or(false, |after| {
+                after.is_empty() || after.starts_with(|c: char| c == '>' || c.is_whitespace())
+            });
+
+            if is_tag {
+                return Some(tag);
+            }
+        }
+    }
+
+    None
+}
+
+/// Removes every HTML comment. An unclosed one would comment out the rest of the page, so it's
+/// removed up to the end.
+fn strip_comments(html: &str) -> String {
+    let mut buf = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<!--") {
+        buf.push_str(&rest[..start]);
+
+        rest = match rest[start + 4..].find("-->") {
+            Some(end) => &rest[start + 4 + end + 3..],
+            None => "",
+        };
+    }
+
+    buf.push_str(rest);
+
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_html_partial, strip_comments, HtmlPartials};
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn fills_each_placeholder() {
+        let partials = HtmlPartials {
+            head_extra: r#"<script src="/analytics.js"></script>"#.to_string(),
+            header: r#"<a href="/blog">Blog</a>"#.to_string(),
+            footer: String::new(),
+        };
+        let template = concat!(
+            "<head><!-- Head extra --></head>",
+            "<header><!-- Header extra --></header>",
+            "<footer><!-- Footer extra --></footer>",
+        );
+
+        assert_eq!(
+            partials.fill(template),
+            concat!(
+                r#"<head><script src="/analytics.js"></script></head>"#,
+                r#"<header><a href="/blog">Blog</a></header>"#,
+                "<footer></footer>",
+            )
+        );
+    }
+
+    #[test]
+    fn partials_cant_fill_other_placeholders() {
+        let partials = HtmlPartials {
+            header: "<nav><!-- Module Docs --></nav>".to_string(),
+            ..HtmlPartials::default()
+        };
+
+        assert_eq!(
+            partials.fill("<!-- Header extra --><!-- Module Docs -->"),
+            "<nav></nav><!-- Module Docs -->"
+        );
+    }
+
+    #[test]
+    fn strips_comments() {
+        assert_eq!(strip_comments("a<!-- b -->c<!---->d"), "acd");
+        assert_eq!(strip_comments("a <!-- never closed"), "a ");
+        assert_eq!(strip_comments("no comments"), "no comments");
+    }
+
+    #[test]
+    fn reads_a_partial() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("header.html");
+
+        fs::write(
+            &path,
+            "<header-links><a href=\"/\">Home</a></header-links>\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_html_partial(&path),
+            Ok("<header-links><a href=\"/\">Home</a></header-links>\n".to_string())
+        );
+    }
+
+    #[test]
+    fn partials_cant_be_pages() {
+        let dir = tempdir().unwrap();
+
+        for (html, tag) in [
+            ("<!DOCTYPE html><p>Hi</p>", "!doctype"),
+            ("<HTML lang=\"en\"></HTML>", "html"),
+            ("<script></script></head>", "head"),
+            ("<body>\n<p>Hi</p>", "body"),
+        ] {
+            let path = dir.path().join("partial.html");
+
+            fs::write(&path, html).unwrap();
+
+            let message = read_html_partial(&path).unwrap_err();
+
+            assert!(
+                message.contains(&format!("has a <{}> tag", tag)),
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn missing_partial() {
+        let path = tempdir().unwrap().path().join("missing.html");
+        let message = read_html_partial(&path).unwrap_err();
+
+        assert!(
+            message.starts_with(&format!("I couldn't read {}: ", path.display())),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! The docs as markdown rather than HTML, for searching with grep and for LLMs, which are both
+//! better off without the markup. Each module gets its own file, e.g. `Str.md`, and llms.txt
+//! lists them all. Headings are the entries' anchors on the HTML pages, e.g. `## concat` for
+//! `Str#concat` and `### Color.Custom` for a tag, so they stay the same from one version to the
+//! next.
+use crate::metadata::{first_sentence, module_description};
+use crate::tags::{tag_anchor, tag_constructors};
+use crate::{is_hidden, type_annotation_to_html};
+use roc_collections::VecSet;
+use roc_load::docs::{DocDef, DocEntry, ModuleDocumentation, TypeAnnotation};
+use roc_module::symbol::Symbol;
+
+/// The index of the markdown files, next to the modules' directories.
+pub const LLMS_TXT: &str = "llms.txt";
+
+/// The file a module's markdown is written to, e.g. "Str.md" for the page at "Str"
+pub fn module_file_name(module_name: &str) -> String {
+    format!("{module_name}.md")
+}
+
+/// The index of every module, following the llms.txt convention: a title, a summary, and a
+/// list of links to the markdown files.
+pub fn render_llms_txt<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    base_url: &str,
+    package_name: &str,
+    landing_page: Option<&str>,
+    modules: I,
+) -> String {
+    let mut buf = format!("# {package_name}\n");
+
+    if let Some(summary) = landing_page.and_then(first_sentence) {
+        buf.push_str(&format!("\n> {summary}\n"));
+    }
+
+    buf.push_str("\n## Modules\n\n");
+
+    for module in modules {
+        let name = module.name.as_str();
+        let url = format!("{base_url}{}", module_file_name(name));
+
+        match module_description(module) {
+            Some(description) => buf.push_str(&format!("- [{name}]({url}): {description}\n")),
+            None => buf.push_str(&format!("- [{name}]({url})\n")),
+        }
+    }
+
+    buf
+}
+
+/// A module's docs as markdown: each exposed entry's signature, doc comment, and tags, in the
+/// same order as on its page.
+pub fn render_module_markdown(
+    module: &ModuleDocumentation,
+    all_exposed_symbols: &VecSet<Symbol>,
+    supplement: Option<&str>,
+) -> String {
+    let mut buf = format!("# {}\n", module.name);
+
+    for entry in &module.entries {
+        match entry {
+            DocEntry::DocDef(doc_def)
+                if all_exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) =>
+            {
+                let name = doc_def.name.as_str();
+                let mut signature = String::from(name);
+
+                for type_var in &doc_def.type_vars {
+                    signature.push(' ');
+                    signature.push_str(type_var);
+                }
+
+                if !matches!(doc_def.type_annotation, TypeAnnotation::NoTypeAnn) {
+                    signature.push_str(" : ");
+                    type_annotation_to_html(0, &mut signature, &doc_def.type_annotation, false);
+                }
+
+                buf.push_str(&format!(
+                    "\n## {name}\n\n```roc\n{}\n```\n",
+                    signature.trim_end()
+                ));
+
+                if let Some(docs) = &doc_def.docs {
+                    push_markdown(&mut buf, docs);
+                }
+
+                push_arguments(&mut buf, doc_def);
+
+                for tag in tag_constructors(doc_def) {
+                    let mut constructor = tag.name.clone();
+
+                    for value in &tag.values {
+                        constructor.push(' ');
+                        type_annotation_to_html(0, &mut constructor, value, true);
+                    }
+
+                    buf.push_str(&format!(
+                        "\n### {}\n\n```roc\n{}\n```\n",
+                        tag_anchor(name, &tag.name),
+                        constructor.trim_end()
+                    ));
+                }
+            }
+            DocEntry::DocDef(_) => {}
+            DocEntry::DetachedDoc(docs) => push_markdown(&mut buf, docs),
+        }
+    }
+
+    if let Some(markdown) = supplement {
+        push_markdown(&mut buf, markdown);
+    }
+
+    buf
+}
+
+/// Appends a list of the function's arguments, if its doc comment describes them
+fn push_arguments(buf: &mut String, doc_def: &DocDef) {
+    if doc_def.args.iter().all(|arg| arg.docs.is_none()) {
+        return;
+    }
+
+    buf.push('\n');
+
+    for arg in &doc_def.args {
+        let mut arg_type = String::new();
+        type_annotation_to_html(0, &mut arg_type, &arg.type_annotation, false);
+
+        match &arg.docs {
+            Some(docs) => buf.push_str(&format!(
+                "* `{}` : `{}` - {docs}\n",
+                arg.name,
+                arg_type.trim()
+            )),
+            None => buf.push_str(&format!("* `{}` : `{}`\n", arg.name, arg_type.trim())),
+        }
+    }
+}
+
+/// Appends a doc comment as its own paragraphs
+fn push_markdown(buf: &mut String, markdown: &str) {
+    let markdown = markdown.trim();
+
+    if !markdown.is_empty() {
+        buf.push('\n');
+        buf.push_str(markdown);
+        buf.push('\n');
+    }
+}
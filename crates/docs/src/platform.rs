@@ -0,0 +1,380 @@
+//! What a platform asks of the apps built on it. None of the platform's modules say this, it's
+//! all in the platform's header: the value an app has to provide and its type (`requires`), the
+//! modules apps can import (`exposes`), and what the platform gives the host (`provides`).
+//! For a platform, this goes on the landing page, so it's the first thing app authors see.
+use crate::{doc_url, escape_attr, push_html, reexports, sidebar_link_url, with_name, DocsStrings};
+use bumpalo::Bump;
+use roc_can::scope::Scope;
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+use roc_module::symbol::{Interns, ModuleId, Symbol};
+use roc_parse::ast::{ExtractSpaces, Header};
+use roc_parse::state::State;
+use std::fs;
+use std::path::Path;
+
+/// The parts of a platform header that app authors need to know about
+pub struct PlatformApi {
+    /// The type variables that the app chooses, like `Model` in
+    /// `requires { Model } { main : App Model }`
+    pub rigids: Vec<String>,
+    /// The name of the value the app provides, e.g. "main"
+    pub required_name: String,
+    /// Its type, as it's written in the header
+    pub required_type: String,
+    /// The modules apps can import
+    pub exposes: Vec<String>,
+    /// What the platform provides to the host, e.g. "mainForHost"
+    pub provides: Vec<String>,
+}
+
+impl PlatformApi {
+    /// The API of the platform whose main .roc file this is, or `None` if it isn't a platform.
+    /// Headers that don't parse are left for the loader to report.
+    pub fn read(root_file: &Path) -> Option<Self> {
+        let src = fs::read(root_file).ok()?;
+        let arena = Bump::new();
+        let (module, _) = roc_parse::module::parse_header(&arena, State::new(&src)).ok()?;
+
+        let header = match module.header {
+            Header::Platform(header) => header,
+            _ => return None,
+        };
+
+        let requires = header.requires.item;
+        let signature = requires.signature.value.extract_spaces().item;
+        let type_region = signature.ann.region;
+        let required_type =
+            &src[type_region.start().offset as usize..type_region.end().offset as usize];
+
+        Some(Self {
+            rigids: requires
+                .rigids
+                .items
+                .iter()
+                .map(|rigid| <&str>::from(rigid.value.extract_spaces().item).to_string())
+                .collect(),
+            required_name: signature.ident.value.to_string(),
+            required_type: String::from_utf8_lossy(required_type).into_owned(),
+            exposes: header
+                .exposes
+                .item
+                .items
+                .iter()
+                .map(|name| name.value.extract_spaces().item.as_str().to_string())
+                .collect(),
+            provides: header
+                .provides
+                .item
+                .items
+                .iter()
+                .map(|name| name.value.extract_spaces().item.as_str().to_string())
+                .collect(),
+        })
+    }
+}
+
+/// The section of the landing page explaining how to write an app for the platform
+#[allow(clippy::too_many_arguments)]
+pub fn render_platform_section<'a, I: Iterator<Item = &'a ModuleDocumentation> + Clone>(
+    buf: &mut String,
+    platform: &PlatformApi,
+    base_url: &str,
+    modules: I,
+    home: ModuleId,
+    interns: &Interns,
+    all_exposed_symbols: &VecSet<Symbol>,
+    reexports: &VecMap<Symbol, reexports::Reexport>,
+    strings: &DocsStrings,
+) {
+    let mut content = String::new();
+
+    push_html(
+        &mut content,
+        "h2",
+        vec![("id", "platform")],
+        escape_attr(&strings.platform_heading),
+    );
+
+    push_html(
+        &mut content,
+        "p",
+        vec![],
+        escape_attr(&with_name(
+            &strings.platform_requires,
+            &platform.required_name,
+        )),
+    );
+
+    let mut signature = String::new();
+
+    signature.push_str(&escape_attr(&platform.required_name));
+    signature.push_str(" : ");
+    signature.push_str(&link_type_names(
+        &platform.required_type,
+        &platform.rigids,
+        base_url,
+        modules.clone(),
+        home,
+        interns,
+        all_exposed_symbols,
+        reexports,
+    ));
+
+    push_html(&mut content, "pre", vec![], {
+        let mut code = String::new();
+
+        push_html(&mut code, "code", vec![], signature);
+
+        code
+    });
+
+    if !platform.rigids.is_empty() {
+        push_html(
+            &mut content,
+            "p",
+            vec![],
+            escape_attr(&with_name(
+                &strings.platform_rigids,
+                &platform.rigids.join(", "),
+            )),
+        );
+    }
+
+    if !platform.exposes.is_empty() {
+        push_html(
+            &mut content,
+            "p",
+            vec![],
+            escape_attr(&strings.platform_exposes),
+        );
+
+        let documented: Vec<&str> = modules.map(|module| module.name.as_str()).collect();
+        let mut list = String::new();
+
+        for module_name in platform.exposes.iter() {
+            let mut item = String::new();
+
+            // Modules left out of the docs are still exposed, they just have nothing to link to
+            if documented.contains(&module_name.as_str()) {
+                let href = sidebar_link_url(base_url, module_name);
+
+                push_html(&mut item, "a", vec![("href", href.as_str())], module_name);
+            } else {
+                push_html(&mut item, "code", vec![], module_name);
+            }
+
+            push_html(&mut list, "li", vec![], item);
+        }
+
+        push_html(&mut content, "ul", vec![], list);
+    }
+
+    if !platform.provides.is_empty() {
+        let provides: Vec<String> = platform
+            .provides
+            .iter()
+            .map(|name| {
+                let mut code = String::new();
+
+                push_html(&mut code, "code", vec![], escape_attr(name));
+
+                code
+            })
+            .collect();
+
+        push_html(&mut content, "p", vec![], {
+            let mut paragraph = escape_attr(&strings.platform_provides);
+
+            paragraph.push(' ');
+            paragraph.push_str(&provides.join(", "));
+
+            paragraph
+        });
+    }
+
+    push_html(buf, "section", vec![("class", "platform-api")], content);
+}
+
+/// The type as HTML, with each type name that's documented linking to its docs. Names are
+/// qualified like `Task.Task`, or unqualified if exactly one documented module exposes them.
+#[allow(clippy::too_many_arguments)]
+fn link_type_names<'a, I: Iterator<Item = &'a ModuleDocumentation> + Clone>(
+    type_str: &str,
+    rigids: &[String],
+    base_url: &str,
+    modules: I,
+    home: ModuleId,
+    interns: &Interns,
+    all_exposed_symbols: &VecSet<Symbol>,
+    reexports: &VecMap<Symbol, reexports::Reexport>,
+) -> String {
+    // Only qualified names are looked up in scope, so it doesn't need anything in it
+    let scope = Scope::new(home, Default::default(), Default::default());
+    let mut buf = String::new();
+    let mut rest = type_str;
+
+    while let Some(start) = rest.find(|c: char| c.is_ascii_uppercase()) {
+        // Don't split identifiers like `myModel`, that only have an uppercase letter in them
+        let is_word_start = rest[..start]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !c.is_alphanumeric() && c != '_' && c != '.');
+        let len = rest[start..]
+            .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
+            .unwrap_or(rest.len() - start);
+        let name = rest[start..start + len].trim_end_matches('.');
+        let end = start + name.len();
+
+        buf.push_str(&escape_attr(&rest[..start]));
+
+        let url = if !is_word_start || rigids.iter().any(|rigid| rigid == name) {
+            None
+        } else {
+            match name.rsplit_once('.') {
+                Some((module_name, ident)) => doc_url(
+                    base_url,
+                    all_exposed_symbols,
+                    reexports,
+                    &scope,
+                    interns,
+                    module_name,
+                    ident,
+                    None,
+                )
+                .ok()
+                .map(|found| found.url),
+                None => unqualified_url(base_url, modules.clone(), all_exposed_symbols, name),
+            }
+        };
+
+        match url {
+            Some(url) => push_html(&mut buf, "a", vec![("href", url.as_str())], name),
+            None => buf.push_str(&escape_attr(name)),
+        }
+
+        rest = &rest[end..];
+    }
+
+    buf.push_str(&escape_attr(rest));
+
+    buf
+}
+
+fn unqualified_url<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    base_url: &str,
+    modules: I,
+    all_exposed_symbols: &VecSet<Symbol>,
+    name: &str,
+) -> Option<String> {
+    let mut found = modules.filter(|module| {
+        module.entries.iter().any(|entry| match entry {
+            DocEntry::DocDef(doc_def) => {
+                doc_def.name == name && all_exposed_symbols.contains(&doc_def.symbol)
+            }
+            DocEntry::DetachedDoc(_) => false,
+        })
+    });
+
+    match (found.next(), found.next()) {
+        (Some(module), None) => Some(format!(
+            "{}#{}",
+            sidebar_link_url(base_url, module.name.as_str()),
+            name
+        )),
+        // Nothing to link to, or no way to tell which one it is
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_platform_section, PlatformApi};
+    use crate::DocsStrings;
+    use pretty_assertions::assert_eq;
+    use roc_collections::{VecMap, VecSet};
+    use roc_load::docs::ModuleDocumentation;
+    use roc_module::symbol::Interns;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn read(src: &str) -> Option<PlatformApi> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("main.roc");
+
+        fs::write(&path, src).unwrap();
+
+        PlatformApi::read(&path)
+    }
+
+    #[test]
+    fn reads_the_platform_header() {
+        let platform = read(concat!(
+            "platform \"gui\"\n",
+            "    requires { Model, Msg } { program : Program Model Msg }\n",
+            "    exposes [Game, Elem]\n",
+            "    packages {}\n",
+            "    imports [Game.{ Program }]\n",
+            "    provides [programForHost]\n",
+        ))
+        .unwrap();
+
+        assert_eq!(platform.rigids, vec!["Model", "Msg"]);
+        assert_eq!(platform.required_name, "program");
+        assert_eq!(platform.required_type, "Program Model Msg");
+        assert_eq!(platform.exposes, vec!["Game", "Elem"]);
+        assert_eq!(platform.provides, vec!["programForHost"]);
+    }
+
+    #[test]
+    fn only_platforms_have_an_api() {
+        let interface = "interface Game\n    exposes []\n    imports []\n\nx = 1\n";
+
+        assert!(read(interface).is_none());
+        assert!(read("platform \"broken\"\n    requires").is_none());
+        assert!(PlatformApi::read(&tempdir().unwrap().path().join("missing.roc")).is_none());
+    }
+
+    #[test]
+    fn renders_the_platform_section() {
+        let platform = PlatformApi {
+            rigids: vec!["Model".to_string()],
+            required_name: "main".to_string(),
+            required_type: "Model -> Str".to_string(),
+            exposes: vec!["Game".to_string()],
+            provides: vec!["mainForHost".to_string()],
+        };
+        let mut interns = Interns::default();
+        let home = interns.module_id(&"Game".into());
+        let no_modules: &[ModuleDocumentation] = &[];
+        let mut buf = String::new();
+
+        render_platform_section(
+            &mut buf,
+            &platform,
+            "/",
+            no_modules.iter(),
+            home,
+            &interns,
+            &VecSet::default(),
+            &VecMap::default(),
+            &DocsStrings::default(),
+        );
+
+        assert_eq!(
+            buf,
+            concat!(
+                r#"<section class="platform-api" >"#,
+                r#"<h2 id="platform" >Writing an app for this platform</h2>"#,
+                "<p>An app for this platform provides main, with this type:</p>",
+                "<pre><code>main : Model -&gt; Str</code></pre>",
+                "<p>The app chooses what these type variables are: Model</p>",
+                "<p>The app can import these modules:</p>",
+                // Game isn't documented, so there's nothing to link to
+                "<ul><li><code>Game</code></li></ul>",
+                "<p>The platform provides this to the host: <code>mainForHost</code></p>",
+                "</section>",
+            )
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Redirects for entries that moved to another module, so that links to where they used to be
+//! keep working after a package is reorganized.
+//!
+//! Moves are found by comparing the API with a previous api.json: an entry that's gone from its
+//! module moved if exactly one module gained an entry with its name. Renames can't be found that
+//! way, so they can also be listed explicitly. Every move is written to redirects.json, mapping
+//! the old qualified name to the entry's current URL. A module that's gone entirely gets a small
+//! page which sends each of its old anchors on to the entry's new home, and a module that's still
+//! there keeps an anchor for each entry that moved out of it, with a link to where it went.
+use crate::api::ApiSnapshot;
+use crate::metadata::escape_attr;
+use crate::{push_html, sidebar_link_url};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// The file the redirects are written to, next to the modules' directories.
+pub const REDIRECTS_JSON: &str = "redirects.json";
+
+/// Where each moved entry went, by qualified name, e.g. "Json.decode" to "Decode.fromJson"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Redirects {
+    moved: BTreeMap<String, String>,
+}
+
+impl Redirects {
+    /// The entries of `previous` that moved since, and the `explicit` moves of entries that are
+    /// no longer documented where they were. Explicit moves take precedence.
+    pub fn find(
+        previous: Option<&ApiSnapshot>,
+        explicit: &BTreeMap<String, String>,
+        current: &ApiSnapshot,
+    ) -> Self {
+        let current_names = qualified_names(current);
+        let mut moved = BTreeMap::new();
+
+        if let Some(previous) = previous {
+            let previous_names = qualified_names(previous);
+            let mut gained: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+            for (module, name) in current_names.difference(&previous_names) {
+                gained.entry(*name).or_default().push(*module);
+            }
+
+            for (module, name) in previous_names.difference(&current_names) {
+                if let Some([new_module]) = gained.get(name).map(Vec::as_slice) {
+                    moved.insert(format!("{module}.{name}"), format!("{new_module}.{name}"));
+                }
+            }
+        }
+
+        for (old, new) in explicit {
+            match split_qualified(old) {
+                Some(old_entry) if !current_names.contains(&old_entry) => {
+                    moved.insert(old.clone(), new.clone());
+                }
+                Some(_) => {
+                    eprintln!("Ignoring the redirect from {old} to {new}, because {old} is still documented.");
+                }
+                None => {
+                    eprintln!("Ignoring the redirect from {old} to {new}, because {old} isn't a qualified name like Module.entry.");
+                }
+            }
+        }
+
+        Redirects { moved }
+    }
+
+    /// redirects.json: the current URL of every moved entry, by its old qualified name
+    pub fn to_json(&self, base_url: &str) -> String {
+        let urls: BTreeMap<&str, String> = self
+            .moved
+            .iter()
+            .map(|(old, new)| (old.as_str(), entry_url(base_url, new)))
+            .collect();
+
+        serde_json::to_string_pretty(&urls).expect("redirects are always valid JSON")
+    }
+
+    /// The entries which moved out of a module, with the qualified names they have now
+    fn moved_out_of<'r>(&'r self, module_name: &'r str) -> impl Iterator<Item = (&str, &str)> {
+        self.moved.iter().filter_map(move |(old, new)| {
+            let (module, name) = split_qualified(old)?;
+
+            (module == module_name).then_some((name, new.as_str()))
+        })
+    }
+
+    /// An anchor for each entry that moved out of a module which is still documented, saying
+    /// where it went. `template` is [DocsStrings::moved_to](crate::DocsStrings::moved_to).
+    pub fn render_moved_out(
+        &self,
+        buf: &mut String,
+        base_url: &str,
+        module_name: &str,
+        template: &str,
+    ) {
+        let mut notes = String::new();
+
+        for (name, new) in self.moved_out_of(module_name) {
+            let url = entry_url(base_url, new);
+            let mut link = String::new();
+            push_html(
+                &mut link,
+                "a",
+                vec![("href", url.as_str())],
+                escape_attr(new),
+            );
+
+            push_html(
+                &mut notes,
+                "p",
+                vec![("id", name), ("class", "moved-entry")],
+                escape_attr(template).replace("{name}", &link),
+            );
+        }
+
+        if !notes.is_empty() {
+            push_html(buf, "section", vec![("class", "moved-entries")], notes);
+        }
+    }
+
+    /// The modules that aren't documented anymore but had entries move out of them, each with
+    /// the page that goes in its place
+    pub fn render_gone_modules(
+        &self,
+        base_url: &str,
+        current: &ApiSnapshot,
+    ) -> Vec<(String, String)> {
+        let current_modules: BTreeSet<&str> = current
+            .modules
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect();
+        let mut gone_modules: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+
+        for (old, new) in self.moved.iter() {
+            if let Some((module, name)) = split_qualified(old) {
+                if !current_modules.contains(module) {
+                    gone_modules
+                        .entry(module)
+                        .or_default()
+                        .push((name, new.as_str()));
+                }
+            }
+        }
+
+        gone_modules
+            .into_iter()
+            .map(|(module, moved)| {
+                let page = render_redirect_page(base_url, module, &moved);
+
+                (module.to_string(), page)
+            })
+            .collect()
+    }
+}
+
+/// Read a JSON object of explicit moves, from old qualified names to new ones, e.g.
+/// `{"Json.decode": "Decode.fromJson"}`
+pub fn read_redirects(path: &Path) -> Result<BTreeMap<String, String>, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|err| format!("I couldn't read {}: {}", path.display(), err))?;
+
+    serde_json::from_str(&json).map_err(|err| {
+        format!(
+            "{} should be a JSON object from old qualified names to new ones, like {{\"Json.decode\": \"Decode.fromJson\"}}: {}",
+            path.display(),
+            err
+        )
+    })
+}
+
+/// A page that sends each of a gone module's anchors to where its entry went. Anything else goes
+/// to the module that most of its entries went to.
+fn render_redirect_page(base_url: &str, module: &str, moved: &[(&str, &str)]) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (_, new) in moved {
+        if let Some((new_module, _)) = split_qualified(new) {
+            *counts.entry(new_module).or_default() += 1;
+        }
+    }
+    let fallback = match counts
+        .iter()
+        .max_by_key(|(name, count)| (**count, std::cmp::Reverse(*name)))
+    {
+        Some((new_module, _)) => sidebar_link_url(base_url, new_module),
+        None => base_url.to_string(),
+    };
+
+    let anchors: BTreeMap<&str, String> = moved
+        .iter()
+        .map(|(name, new)| (*name, entry_url(base_url, new)))
+        .collect();
+    let anchors_json = serde_json::to_string(&anchors)
+        .expect("redirects are always valid JSON")
+        .replace("</", "<\\/");
+
+    let mut links = String::new();
+    for (name, new) in moved {
+        let url = entry_url(base_url, new);
+        let mut link = String::new();
+        push_html(
+            &mut link,
+            "a",
+            vec![("href", url.as_str())],
+            escape_attr(new),
+        );
+        push_html(
+            &mut links,
+            "li",
+            vec![],
+            format!("{}.{} → {}", escape_attr(module), escape_attr(name), link),
+        );
+    }
+
+    let fallback = escape_attr(&fallback);
+
+    format!(
+        r#"<!doctype html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <title>{module}</title>
+    <meta name="robots" content="noindex">
+    <script>
+        var target = {anchors_json}[location.hash.slice(1)];
+        if (target) location.replace(target);
+    </script>
+    <meta http-equiv="refresh" content="0; url={fallback}">
+</head>
+<body>
+<p>The entries of {module} have moved:</p>
+<ul>{links}</ul>
+</body>
+</html>
+"#,
+        module = escape_attr(module),
+    )
+}
+
+/// Every entry in the snapshot, as its module's name and its own name
+fn qualified_names(snapshot: &ApiSnapshot) -> BTreeSet<(&str, &str)> {
+    snapshot
+        .modules
+        .iter()
+        .flat_map(|module| {
+            module
+                .entries
+                .iter()
+                .map(move |entry| (module.name.as_str(), entry.name.as_str()))
+        })
+        .collect()
+}
+
+/// "Json.Decode.string" to ("Json.Decode", "string")
+fn split_qualified(qualified: &str) -> Option<(&str, &str)> {
+    qualified
+        .rsplit_once('.')
+        .filter(|(module, name)| !module.is_empty() && !name.is_empty())
+}
+
+fn entry_url(base_url: &str, qualified: &str) -> String {
+    match split_qualified(qualified) {
+        Some((module, name)) => format!("{}#{}", sidebar_link_url(base_url, module), name),
+        None => sidebar_link_url(base_url, qualified),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Redirects;
+    use crate::api::{ApiEntry, ApiModule, ApiSnapshot};
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    fn snapshot(modules: &[(&str, &[&str])]) -> ApiSnapshot {
+        ApiSnapshot {
+            modules: modules
+                .iter()
+                .map(|(name, entries)| ApiModule {
+                    name: name.to_string(),
+                    entries: entries
+                        .iter()
+                        .map(|entry| ApiEntry {
+                            name: entry.to_string(),
+                            signature: String::new(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn moves(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect()
+    }
+
+    /// Json.decode moved to Decode, and Util is gone, with helper in both A and B
+    fn redirects() -> (Redirects, ApiSnapshot) {
+        let previous = snapshot(&[("Json", &["decode", "encode"]), ("Util", &["helper"])]);
+        let current = snapshot(&[
+            ("Json", &["encode"]),
+            ("Decode", &["decode"]),
+            ("A", &["helper"]),
+            ("B", &["helper"]),
+        ]);
+        let explicit = moves(&[
+            ("Util.helper", "A.helper"),
+            ("Json.encode", "X.y"),
+            ("nodot", "A.b"),
+        ]);
+
+        (
+            Redirects::find(Some(&previous), &explicit, &current),
+            current,
+        )
+    }
+
+    #[test]
+    fn finds_unambiguous_and_explicit_moves() {
+        let (redirects, _) = redirects();
+
+        assert_eq!(
+            redirects.moved,
+            moves(&[
+                ("Json.decode", "Decode.decode"),
+                ("Util.helper", "A.helper"),
+            ])
+        );
+    }
+
+    #[test]
+    fn only_explicit_moves_without_a_previous_api() {
+        let current = snapshot(&[("Decode", &["decode"])]);
+        let explicit = moves(&[("Json.decode", "Decode.decode")]);
+
+        assert_eq!(Redirects::find(None, &explicit, &current).moved, explicit);
+    }
+
+    #[test]
+    fn json_maps_old_names_to_urls() {
+        let (redirects, _) = redirects();
+        let urls: BTreeMap<String, String> = serde_json::from_str(&redirects.to_json("/")).unwrap();
+
+        assert_eq!(
+            urls,
+            moves(&[
+                ("Json.decode", "/Decode#decode"),
+                ("Util.helper", "/A#helper"),
+            ])
+        );
+    }
+
+    #[test]
+    fn gone_modules_get_a_redirect_page() {
+        let (redirects, current) = redirects();
+        let pages = redirects.render_gone_modules("/", &current);
+
+        assert_eq!(pages.len(), 1);
+
+        let (module, page) = &pages[0];
+        assert_eq!(module, "Util");
+        assert!(page.contains(r#"var target = {"helper":"/A#helper"}"#));
+        assert!(page.contains(r#"content="0; url=/A""#));
+    }
+
+    #[test]
+    fn remaining_modules_keep_anchors_for_moved_entries() {
+        let (redirects, _) = redirects();
+        let mut buf = String::new();
+
+        redirects.render_moved_out(&mut buf, "/", "Json", "Moved to {name}");
+
+        assert_eq!(
+            buf,
+            concat!(
+                r#"<section class="moved-entries" ><p id="decode" class="moved-entry" >"#,
+                r#"Moved to <a href="/Decode#decode" >Decode.decode</a></p></section>"#,
+            )
+        );
+
+        let mut buf = String::new();
+        redirects.render_moved_out(&mut buf, "/", "Decode", "Moved to {name}");
+        assert_eq!(buf, "");
+    }
+}
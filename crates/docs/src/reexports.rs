@@ -0,0 +1,217 @@
+//! Entries a module re-exports from another module, e.g. `decode = Internal.decode`. These are
+//! documented on the page of the module that exposes them, since the module they come from is
+//! often internal to the package and has no page of its own.
+use crate::metadata::escape_attr;
+use crate::{is_hidden, push_html, sidebar_link_url};
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+use roc_module::symbol::{Interns, Symbol};
+
+/// Where a re-exported symbol is documented
+#[derive(Debug, Clone)]
+pub struct Reexport {
+    /// The module which exposes it, e.g. "Json"
+    pub module_name: String,
+    /// Its name in that module, e.g. "decode"
+    pub name: String,
+    /// The base URL of the docs it's in, if they're another package's in the same workspace
+    pub package_url: Option<String>,
+}
+
+/// For every symbol that's only exposed through a re-export, find where the re-export is
+/// documented, so that links to the original symbol can go there instead.
+pub fn find_reexports<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    modules: I,
+    interns: &Interns,
+    all_exposed_symbols: &VecSet<Symbol>,
+) -> VecMap<Symbol, Reexport> {
+    let mut reexports = VecMap::default();
+
+    for module in modules {
+        for entry in module.entries.iter() {
+            let (doc_def, original) = match entry {
+                DocEntry::DocDef(doc_def)
+                    if all_exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) =>
+                {
+                    match &doc_def.reexported_from {
+                        Some(original) => (doc_def, original),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            let symbol = match resolve_qualified(interns, original) {
+                Some(symbol) if !all_exposed_symbols.contains(&symbol) => symbol,
+                _ => continue,
+            };
+
+            // If several modules re-export the same symbol, the first one is the canonical one
+            if !reexports.contains_key(&symbol) {
+                let reexport = Reexport {
+                    module_name: module.name.clone(),
+                    name: doc_def.name.clone(),
+                    package_url: None,
+                };
+
+                reexports.insert(symbol, reexport);
+            }
+        }
+    }
+
+    reexports
+}
+
+/// Split a name like "Internal.decode" or "json.Internal.decode" into its module name and ident,
+/// without the package shorthand, since modules are known by their names in their own package
+fn split_qualified(qualified_name: &str) -> Option<(&str, &str)> {
+    let (module_name, ident) = qualified_name.rsplit_once('.')?;
+
+    let module_name = match module_name.split_once('.') {
+        Some((shorthand, rest)) if shorthand.starts_with(|c: char| c.is_ascii_lowercase()) => rest,
+        _ => module_name,
+    };
+
+    Some((module_name, ident))
+}
+
+/// Look up a name like "Internal.decode" or "json.Internal.decode"
+fn resolve_qualified(interns: &Interns, qualified_name: &str) -> Option<Symbol> {
+    let (module_name, ident) = split_qualified(qualified_name)?;
+    let module_id = interns.module_ids.get_id(&module_name.into())?;
+    let ident_id = interns.all_ident_ids.get(&module_id)?.get_id(ident)?;
+
+    Some(Symbol::new(module_id, ident_id))
+}
+
+/// Renders a note saying where an entry comes from, e.g. "Re-exported from Internal.decode".
+/// It links to the original if that's documented too.
+pub fn render_note(
+    buf: &mut String,
+    base_url: &str,
+    original: &str,
+    all_exposed_symbols: &VecSet<Symbol>,
+    interns: &Interns,
+    template: &str,
+) {
+    let name = match resolve_qualified(interns, original) {
+        Some(symbol) if all_exposed_symbols.contains(&symbol) => {
+            let (module_name, ident) = split_qualified(original).unwrap_or(("", original));
+            let url = format!("{}#{}", sidebar_link_url(base_url, module_name), ident);
+            let mut link = String::new();
+
+            push_html(&mut link, "a", vec![("href", url.as_str())], original);
+
+            link
+        }
+        _ => {
+            let mut code = String::new();
+
+            push_html(&mut code, "code", vec![], original);
+
+            code
+        }
+    };
+
+    let note = escape_attr(template).replace("{name}", &name);
+
+    push_html(buf, "p", vec![("class", "reexport-note")], note);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_note, resolve_qualified, split_qualified};
+    use pretty_assertions::assert_eq;
+    use roc_collections::VecSet;
+    use roc_module::symbol::{Interns, Symbol};
+
+    fn interns_with_decode() -> (Interns, Symbol) {
+        let mut interns = Interns::default();
+        let internal = interns.module_id(&"Internal".into());
+        let decode = interns
+            .all_ident_ids
+            .get_or_insert(internal)
+            .add_str("decode");
+
+        (interns, Symbol::new(internal, decode))
+    }
+
+    #[test]
+    fn split_without_the_package_shorthand() {
+        assert_eq!(
+            split_qualified("Internal.decode"),
+            Some(("Internal", "decode"))
+        );
+        assert_eq!(
+            split_qualified("json.Internal.decode"),
+            Some(("Internal", "decode"))
+        );
+        assert_eq!(
+            split_qualified("Json.Decode.decode"),
+            Some(("Json.Decode", "decode"))
+        );
+        assert_eq!(
+            split_qualified("json.Json.Decode.decode"),
+            Some(("Json.Decode", "decode"))
+        );
+        assert_eq!(split_qualified("decode"), None);
+    }
+
+    #[test]
+    fn resolve_with_or_without_the_package_shorthand() {
+        let (interns, decode) = interns_with_decode();
+
+        assert_eq!(resolve_qualified(&interns, "Internal.decode"), Some(decode));
+        assert_eq!(
+            resolve_qualified(&interns, "json.Internal.decode"),
+            Some(decode)
+        );
+        assert_eq!(resolve_qualified(&interns, "Internal.encode"), None);
+        assert_eq!(resolve_qualified(&interns, "Other.decode"), None);
+    }
+
+    #[test]
+    fn note_links_to_a_documented_original() {
+        let (interns, decode) = interns_with_decode();
+        let mut exposed = VecSet::default();
+        exposed.insert(decode);
+        let mut buf = String::new();
+
+        render_note(
+            &mut buf,
+            "/",
+            "json.Internal.decode",
+            &exposed,
+            &interns,
+            "Re-exported from {name}",
+        );
+
+        assert_eq!(
+            buf,
+            concat!(
+                r#"<p class="reexport-note" >Re-exported from "#,
+                r##"<a href="/Internal#decode" >json.Internal.decode</a></p>"##,
+            )
+        );
+    }
+
+    #[test]
+    fn note_names_an_undocumented_original() {
+        let (interns, _) = interns_with_decode();
+        let mut buf = String::new();
+
+        render_note(
+            &mut buf,
+            "/",
+            "Internal.decode",
+            &VecSet::default(),
+            &interns,
+            "Re-exported from {name}",
+        );
+
+        assert_eq!(
+            buf,
+            r#"<p class="reexport-note" >Re-exported from <code>Internal.decode</code></p>"#
+        );
+    }
+}
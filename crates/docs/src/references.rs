@@ -0,0 +1,99 @@
+//! "Referenced by" lists, so that from a type's entry you can find every documented entry that
+//! uses it in its type annotation, e.g. everything that returns a `Task`.
+use crate::abilities::resolve_name;
+use crate::metadata::escape_attr;
+use crate::{is_hidden, push_html, sidebar_link_url};
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::{DocEntry, ModuleDocumentation};
+use roc_module::symbol::{Interns, Symbol};
+
+/// An exposed entry whose type annotation mentions some other entry
+pub struct Reference {
+    /// Qualified name, e.g. "Task.await"
+    pub name: String,
+    pub url: String,
+}
+
+/// For every exposed symbol, find the exposed entries which mention it in their type annotation.
+pub fn find_references<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    base_url: &str,
+    modules: I,
+    interns: &Interns,
+    all_exposed_symbols: &VecSet<Symbol>,
+) -> VecMap<Symbol, Vec<Reference>> {
+    let mut references: VecMap<Symbol, Vec<Reference>> = VecMap::default();
+
+    for module in modules {
+        for entry in module.entries.iter() {
+            let doc_def = match entry {
+                DocEntry::DocDef(doc_def)
+                    if all_exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) =>
+                {
+                    doc_def
+                }
+                _ => continue,
+            };
+
+            for type_name in doc_def.referenced_types.iter() {
+                let referenced = match resolve_name(&module.scope, interns, type_name) {
+                    Some(symbol) if symbol != doc_def.symbol => symbol,
+                    _ => continue,
+                };
+
+                if !all_exposed_symbols.contains(&referenced) {
+                    continue;
+                }
+
+                let reference = Reference {
+                    name: format!("{}.{}", module.name, doc_def.name),
+                    url: format!(
+                        "{}#{}",
+                        sidebar_link_url(base_url, &module.name),
+                        doc_def.name
+                    ),
+                };
+
+                match references.get_mut(&referenced) {
+                    Some(list) => list.push(reference),
+                    None => {
+                        references.insert(referenced, vec![reference]);
+                    }
+                }
+            }
+        }
+    }
+
+    references
+}
+
+/// Renders a collapsed list of the entries which reference this one.
+pub fn render_referenced_by(buf: &mut String, references: &[Reference], label: &str) {
+    if references.is_empty() {
+        return;
+    }
+
+    let mut list = String::new();
+
+    for reference in references {
+        push_html(&mut list, "li", vec![], {
+            let mut link = String::new();
+
+            push_html(
+                &mut link,
+                "a",
+                vec![("href", reference.url.as_str())],
+                reference.name.as_str(),
+            );
+
+            link
+        });
+    }
+
+    let summary = format!("{} ({})", escape_attr(label), references.len());
+    let mut content = String::new();
+
+    push_html(&mut content, "summary", vec![], summary);
+    push_html(&mut content, "ul", vec![], list);
+
+    push_html(buf, "details", vec![("class", "referenced-by")], content);
+}
@@ -0,0 +1,113 @@
+//! The index behind the search palette, written next to the generated docs as
+//! search-index.json. Its format is described by static/search-index.schema.json. Any change to
+//! the format must bump `SEARCH_INDEX_VERSION` and the schema together, so that search.js can
+//! ignore an index it doesn't understand rather than show wrong results.
+use crate::api::signature;
+use crate::is_hidden;
+use roc_collections::VecSet;
+use roc_load::docs::{DocEntry, ModuleDocumentation, TypeAnnotation};
+use roc_module::symbol::Symbol;
+use serde::Serialize;
+
+/// The file the index is written to, next to the modules' directories.
+pub const SEARCH_INDEX_JSON: &str = "search-index.json";
+
+/// The schema of every version of the index, at the root of the build dir.
+pub const SEARCH_INDEX_SCHEMA_JSON: &str = "search-index.schema.json";
+
+pub const SEARCH_INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct SearchIndex {
+    #[serde(rename = "$schema")]
+    schema: String,
+    version: u32,
+    entries: Vec<SearchEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchEntry {
+    module: String,
+    name: String,
+    /// Same as in api.json, e.g. `: Str, Str -> Str`
+    signature: String,
+    /// Relative to the index, e.g. `Str#concat`
+    url: String,
+    /// The names of the argument types of a function, for `takes:` searches
+    takes: Vec<String>,
+    /// The name of the type a function returns, or of a value's own type, for `returns:` searches
+    returns: Option<String>,
+}
+
+impl SearchIndex {
+    /// The exposed entries of every module, leaving out the hidden ones just like the docs do.
+    pub fn from_modules<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+        root_url: &str,
+        modules: I,
+        all_exposed_symbols: &VecSet<Symbol>,
+    ) -> Self {
+        let mut entries = Vec::new();
+
+        for module in modules {
+            for entry in module.entries.iter() {
+                match entry {
+                    DocEntry::DocDef(doc_def)
+                        if all_exposed_symbols.contains(&doc_def.symbol) && !is_hidden(doc_def) =>
+                    {
+                        let is_value = doc_def.name.starts_with(char::is_lowercase);
+                        let (takes, returns) = match &doc_def.type_annotation {
+                            TypeAnnotation::Function { args, output } => (
+                                args.iter().filter_map(type_name).collect(),
+                                type_name(output),
+                            ),
+                            type_ann if is_value => (Vec::new(), type_name(type_ann)),
+                            _ => (Vec::new(), None),
+                        };
+
+                        entries.push(SearchEntry {
+                            module: module.name.clone(),
+                            name: doc_def.name.clone(),
+                            signature: signature(&doc_def.type_vars, &doc_def.type_annotation),
+                            url: format!("{}#{}", module.name, doc_def.name),
+                            takes,
+                            returns,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        SearchIndex {
+            schema: format!("{root_url}{SEARCH_INDEX_SCHEMA_JSON}"),
+            version: SEARCH_INDEX_VERSION,
+            entries,
+        }
+    }
+
+    /// Change the url of every entry, e.g. to point at anchors in the single-file docs
+    pub fn map_urls(&mut self, f: impl Fn(&str) -> String) {
+        for entry in self.entries.iter_mut() {
+            entry.url = f(&entry.url);
+        }
+    }
+
+    /// Add the entries of another index, e.g. of another package in the same workspace
+    pub fn append(&mut self, other: SearchIndex) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a search index is always valid JSON")
+    }
+}
+
+/// The unqualified name of a type, e.g. `Result` for `Result Str [NotFound]`
+fn type_name(type_ann: &TypeAnnotation) -> Option<String> {
+    match type_ann {
+        TypeAnnotation::Apply { name, .. } => {
+            Some(name.rsplit('.').next().unwrap_or(name).to_string())
+        }
+        _ => None,
+    }
+}
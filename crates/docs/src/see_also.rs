@@ -0,0 +1,398 @@
+//! "See also" lists, suggesting other entries whose types have a lot in common with an entry's,
+//! e.g. `Str.toUtf8` for `Str.fromUtf8`, or other functions taking and returning the same types.
+//! It only looks at the type annotations, and always suggests the same entries for the same
+//! types, however the modules were loaded.
+use crate::abilities::resolve_name;
+use crate::metadata::escape_attr;
+use crate::{is_hidden, push_html, sidebar_link_url};
+use roc_can::scope::Scope;
+use roc_collections::{VecMap, VecSet};
+use roc_load::docs::{DocEntry, ModuleDocumentation, RecordField, TypeAnnotation};
+use roc_module::symbol::{Interns, Symbol};
+
+/// At most this many suggestions are shown for an entry
+const MAX_SEE_ALSO: usize = 5;
+
+/// Entries have to score at least this much to be suggested. Sharing a result type is worth 1,
+/// as is each argument type they have in common, and being each other's inverse is worth 3.
+const MIN_SCORE: u32 = 2;
+
+const INVERSE_SCORE: u32 = 3;
+
+/// An exposed entry with a similar type to another one
+pub struct SeeAlso {
+    /// Qualified name, e.g. "Str.toUtf8"
+    pub name: String,
+    pub url: String,
+}
+
+/// The parts of an entry's type that are compared. A value is treated like a function with no
+/// arguments. Types are written out with their names fully qualified and their type variables
+/// left out, so that `List a` and `List elem` are the same.
+struct Shape {
+    symbol: Symbol,
+    name: String,
+    url: String,
+    args: Vec<String>,
+    output: String,
+    /// The output and the types in it, for finding inverses whose output is wrapped in another type
+    output_parts: VecSet<String>,
+}
+
+/// For every exposed function or value, find the other exposed entries whose types are most like
+/// its own, best first. Entries with nothing in common with any other don't get a list.
+pub fn find_see_also<'a, I: Iterator<Item = &'a ModuleDocumentation>>(
+    base_url: &str,
+    modules: I,
+    interns: &Interns,
+    all_exposed_symbols: &VecSet<Symbol>,
+) -> VecMap<Symbol, Vec<SeeAlso>> {
+    let mut shapes = Vec::new();
+
+    for module in modules {
+        for entry in module.entries.iter() {
+            match entry {
+                DocEntry::DocDef(doc_def)
+                    if all_exposed_symbols.contains(&doc_def.symbol)
+                        && !is_hidden(doc_def)
+                        && doc_def.name.starts_with(char::is_lowercase) =>
+                {
+                    let (args, output) = match &doc_def.type_annotation {
+                        TypeAnnotation::NoTypeAnn => continue,
+                        TypeAnnotation::Function { args, output } => (args.as_slice(), &**output),
+                        type_ann => (&[][..], type_ann),
+                    };
+
+                    let key =
+                        |type_ann: &TypeAnnotation| type_key(&module.scope, interns, type_ann);
+                    let mut output_parts = VecSet::default();
+                    collect_parts(&module.scope, interns, output, &mut output_parts);
+
+                    shapes.push(Shape {
+                        symbol: doc_def.symbol,
+                        name: format!("{}.{}", module.name, doc_def.name),
+                        url: format!(
+                            "{}#{}",
+                            sidebar_link_url(base_url, &module.name),
+                            doc_def.name
+                        ),
+                        args: args.iter().map(key).collect(),
+                        output: key(output),
+                        output_parts,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The order the modules were loaded in mustn't change which entries win a tie
+    shapes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut see_also = VecMap::default();
+
+    for shape in shapes.iter() {
+        let mut scored: Vec<(u32, &Shape)> = shapes
+            .iter()
+            .filter(|other| other.symbol != shape.symbol)
+            .map(|other| (score(shape, other), other))
+            .filter(|(score, _)| *score >= MIN_SCORE)
+            .collect();
+
+        if scored.is_empty() {
+            continue;
+        }
+
+        // Stable, so entries with the same score stay in alphabetical order
+        scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        let list = scored
+            .into_iter()
+            .take(MAX_SEE_ALSO)
+            .map(|(_, other)| SeeAlso {
+                name: other.name.clone(),
+                url: other.url.clone(),
+            })
+            .collect();
+
+        see_also.insert(shape.symbol, list);
+    }
+
+    see_also
+}
+
+fn score(shape: &Shape, other: &Shape) -> u32 {
+    if is_inverse(shape, other) {
+        return INVERSE_SCORE;
+    }
+
+    let mut score = 0;
+
+    if !shape.output.is_empty() && shape.output == other.output {
+        score += 1;
+    }
+
+    // Each of the other entry's arguments can only match one of this entry's
+    let mut other_args: Vec<&String> = other.args.iter().filter(|arg| !arg.is_empty()).collect();
+    for arg in shape.args.iter().filter(|arg| !arg.is_empty()) {
+        if let Some(index) = other_args.iter().position(|other_arg| *other_arg == arg) {
+            other_args.swap_remove(index);
+            score += 1;
+        }
+    }
+
+    score
+}
+
+/// Whether each function takes what the other one returns, like `toUtf8` and `fromUtf8`. The
+/// result of one may be wrapped, e.g. in a `Result`, so any type in it counts. Like in `score`,
+/// type variables and wildcards don't count, or every generic function would match.
+fn is_inverse(shape: &Shape, other: &Shape) -> bool {
+    let takes_output_of =
+        |a: &Shape, b: &Shape| !b.output.is_empty() && a.args.iter().any(|arg| *arg == b.output);
+    let takes_part_of_output_of = |a: &Shape, b: &Shape| {
+        a.args
+            .iter()
+            .any(|arg| !arg.is_empty() && b.output_parts.contains(arg))
+    };
+
+    shape.output != other.output
+        && ((takes_output_of(shape, other) && takes_part_of_output_of(other, shape))
+            || (takes_output_of(other, shape) && takes_part_of_output_of(shape, other)))
+}
+
+/// The type written out for comparing, or an empty string for a type variable or wildcard, which
+/// don't say anything about what an entry is for.
+fn type_key(scope: &Scope, interns: &Interns, type_ann: &TypeAnnotation) -> String {
+    let mut buf = String::new();
+    write_type_key(scope, interns, type_ann, &mut buf);
+    buf
+}
+
+fn write_type_key(scope: &Scope, interns: &Interns, type_ann: &TypeAnnotation, buf: &mut String) {
+    match type_ann {
+        TypeAnnotation::Apply { name, parts } => {
+            buf.push_str(&qualified_name(scope, interns, name));
+            for part in parts {
+                buf.push_str(" (");
+                write_type_key(scope, interns, part, buf);
+                buf.push(')');
+            }
+        }
+        TypeAnnotation::Function { args, output } => {
+            for arg in args {
+                buf.push('(');
+                write_type_key(scope, interns, arg, buf);
+                buf.push_str("), ");
+            }
+            buf.push_str("-> (");
+            write_type_key(scope, interns, output, buf);
+            buf.push(')');
+        }
+        TypeAnnotation::Record { fields, .. } => {
+            let mut fields: Vec<(&str, &TypeAnnotation)> = fields
+                .iter()
+                .filter_map(|field| match field {
+                    RecordField::RecordField {
+                        name,
+                        type_annotation,
+                    }
+                    | RecordField::OptionalField {
+                        name,
+                        type_annotation,
+                    } => Some((name.as_str(), type_annotation)),
+                    RecordField::LabelOnly { .. } => None,
+                })
+                .collect();
+            fields.sort_by_key(|(name, _)| *name);
+
+            buf.push('{');
+            for (name, field_type) in fields {
+                buf.push_str(name);
+                buf.push_str(": (");
+                write_type_key(scope, interns, field_type, buf);
+                buf.push_str("), ");
+            }
+            buf.push('}');
+        }
+        TypeAnnotation::TagUnion { tags, .. } => {
+            let mut names: Vec<&str> = tags.iter().map(|tag| tag.name.as_str()).collect();
+            names.sort_unstable();
+
+            buf.push('[');
+            buf.push_str(&names.join(", "));
+            buf.push(']');
+        }
+        TypeAnnotation::ObscuredRecord => buf.push_str("{..}"),
+        TypeAnnotation::ObscuredTagUnion => buf.push_str("[..]"),
+        TypeAnnotation::BoundVariable(_)
+        | TypeAnnotation::Wildcard
+        | TypeAnnotation::Ability { .. }
+        | TypeAnnotation::NoTypeAnn => {}
+    }
+}
+
+/// The key of every named type in the annotation and in its arguments, e.g. `Result.Result
+/// (Str.Str) ()` and `Str.Str` for `Result Str *`. Type variables and wildcards are left out.
+fn collect_parts(
+    scope: &Scope,
+    interns: &Interns,
+    type_ann: &TypeAnnotation,
+    parts: &mut VecSet<String>,
+) {
+    match type_ann {
+        TypeAnnotation::Apply { parts: args, .. } => {
+            parts.insert(type_key(scope, interns, type_ann));
+            for arg in args {
+                collect_parts(scope, interns, arg, parts);
+            }
+        }
+        TypeAnnotation::Record { fields, .. } => {
+            for field in fields {
+                if let RecordField::RecordField {
+                    type_annotation, ..
+                }
+                | RecordField::OptionalField {
+                    type_annotation, ..
+                } = field
+                {
+                    collect_parts(scope, interns, type_annotation, parts);
+                }
+            }
+        }
+        TypeAnnotation::TagUnion { tags, .. } => {
+            for value in tags.iter().flat_map(|tag| tag.values.iter()) {
+                collect_parts(scope, interns, value, parts);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// e.g. `Str.Str` for `Str`, so that the same type has the same name in every module. Names that
+/// can't be resolved are left as they're written.
+fn qualified_name(scope: &Scope, interns: &Interns, name: &str) -> String {
+    match resolve_name(scope, interns, name) {
+        Some(symbol) => format!(
+            "{}.{}",
+            symbol.module_string(interns),
+            symbol.as_str(interns)
+        ),
+        None => name.to_string(),
+    }
+}
+
+/// Renders the suggestions as a line of links, at the end of the entry.
+pub fn render_see_also(buf: &mut String, see_also: &[SeeAlso], label: &str) {
+    if see_also.is_empty() {
+        return;
+    }
+
+    let mut content = String::new();
+
+    push_html(&mut content, "span", vec![], escape_attr(label));
+
+    for (index, entry) in see_also.iter().enumerate() {
+        content.push_str(if index == 0 { " " } else { ", " });
+        push_html(
+            &mut content,
+            "a",
+            vec![("href", entry.url.as_str())],
+            entry.name.as_str(),
+        );
+    }
+
+    push_html(buf, "p", vec![("class", "see-also")], content);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_inverse, score, Shape, INVERSE_SCORE};
+    use roc_module::symbol::Symbol;
+
+    fn shape(symbol: Symbol, args: &[&str], output: &str, output_parts: &[&str]) -> Shape {
+        Shape {
+            symbol,
+            name: String::new(),
+            url: String::new(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            output: output.to_string(),
+            output_parts: output_parts.iter().map(|part| part.to_string()).collect(),
+        }
+    }
+
+    const STR: &str = "Str.Str";
+    const BYTES: &str = "List.List (Num.U8)";
+    const FROM_UTF8_RESULT: &str = "Result.Result (Str.Str) ([BadUtf8])";
+
+    #[test]
+    fn inverse_through_a_wrapped_output() {
+        let to_utf8 = shape(Symbol::STR_TO_UTF8, &[STR], BYTES, &[BYTES, "Num.U8"]);
+        let from_utf8 = shape(
+            Symbol::STR_FROM_UTF8,
+            &[BYTES],
+            FROM_UTF8_RESULT,
+            &[FROM_UTF8_RESULT, STR],
+        );
+
+        assert!(is_inverse(&to_utf8, &from_utf8));
+        assert!(is_inverse(&from_utf8, &to_utf8));
+        assert_eq!(score(&to_utf8, &from_utf8), INVERSE_SCORE);
+    }
+
+    #[test]
+    fn type_variables_dont_make_an_inverse() {
+        // encode : a -> List U8 and decode : List U8 -> Result a err
+        let encode = shape(Symbol::STR_TO_UTF8, &[""], BYTES, &[BYTES, "Num.U8"]);
+        let decode = shape(
+            Symbol::STR_FROM_UTF8,
+            &[BYTES],
+            "Result.Result () ()",
+            &["Result.Result () ()", ""],
+        );
+
+        assert!(!is_inverse(&encode, &decode));
+        assert!(!is_inverse(&decode, &encode));
+    }
+
+    #[test]
+    fn same_output_is_not_an_inverse() {
+        let a = shape(Symbol::STR_CONCAT, &[STR, STR], STR, &[STR]);
+        let b = shape(Symbol::STR_JOIN_WITH, &[STR], STR, &[STR]);
+
+        assert!(!is_inverse(&a, &b));
+    }
+
+    #[test]
+    fn score_counts_output_and_each_argument_once() {
+        let concat = shape(Symbol::STR_CONCAT, &[STR, STR], STR, &[STR]);
+        let trim = shape(Symbol::STR_TRIM, &[STR], STR, &[STR]);
+        let repeat = shape(Symbol::STR_REPEAT, &[STR, "Num.Nat"], STR, &[STR]);
+
+        // the output, and trim's one Str argument can only match one of concat's
+        assert_eq!(score(&concat, &trim), 2);
+        assert_eq!(score(&trim, &concat), 2);
+        assert_eq!(score(&concat, &repeat), 2);
+        assert_eq!(score(&concat, &concat), 3);
+    }
+
+    #[test]
+    fn score_ignores_type_variables() {
+        let map = shape(Symbol::LIST_MAP, &["List.List ()", ""], "List.List ()", &[]);
+        let first = shape(Symbol::LIST_FIRST, &[""], "", &[]);
+        let walk = shape(Symbol::LIST_WALK, &["List.List ()", "", ""], "", &[]);
+
+        assert_eq!(score(&map, &first), 0);
+        assert_eq!(score(&first, &walk), 0);
+        assert_eq!(score(&map, &walk), 1);
+    }
+
+    #[test]
+    fn nothing_in_common() {
+        let to_utf8 = shape(Symbol::STR_TO_UTF8, &[STR], BYTES, &[BYTES]);
+        let len = shape(Symbol::LIST_LEN, &["List.List ()"], "Num.Nat", &["Num.Nat"]);
+
+        assert_eq!(score(&to_utf8, &len), 0);
+        assert!(!is_inverse(&to_utf8, &len));
+    }
+}
@@ -0,0 +1,240 @@
+//! The whole package's docs in one self-contained HTML file, for reading offline or attaching to
+//! a release. The stylesheet, scripts, favicon, and search index are inlined, and every module
+//! goes on the one page. Links between pages become anchors within it, and each module's ids get
+//! the module's name as a prefix, so that e.g. `List.map` and `Result.map` stay distinct.
+use crate::search_index::SearchIndex;
+
+/// The file the single-file docs are written to, next to the modules' directories.
+pub const SINGLE_FILE_HTML: &str = "docs.html";
+
+// The parts of index.html that refer to separate files
+const SCRIPT_TAG: &str =
+    r#"<script type="text/javascript" src="<!-- search.js -->" defer></script>"#;
+const STYLESHEET_TAG: &str = r#"<link rel="stylesheet" href="<!-- styles.css -->">"#;
+const FAVICON_URL: &str = "<!-- favicon.svg -->";
+const SEARCH_INDEX_URL: &str = "<!-- search-index.json -->";
+const LOGO_LINK: &str = r#"<a class="logo" href="/""#;
+
+/// The rendered docs to put in the single file
+pub struct SingleFile<'a> {
+    pub base_url: &'a str,
+    /// The `<title>` and metadata tags for the `<head>`
+    pub head: String,
+    pub sidebar: String,
+    pub name_and_version: String,
+    /// The landing page, if the package has one
+    pub landing_page: Option<String>,
+    /// Each module's name and the docs from its page
+    pub modules: Vec<(String, String)>,
+}
+
+impl<'a> SingleFile<'a> {
+    /// `template` is index.html with the text around the docs filled in, but not the assets.
+    pub fn render(&self, template: &str, mut search_index: SearchIndex) -> String {
+        search_index.map_urls(|url| match url.split_once('#') {
+            Some((module, name)) => anchor(module, name),
+            None => anchor(url, ""),
+        });
+
+        // Inline scripts can't be deferred, so the scripts go after everything they look for.
+        let scripts = format!(
+            "<script type=\"application/json\" id=\"search-index\">{}</script>\n<script type=\"text/javascript\">{}</script>\n</body>",
+            search_index.to_json().replace("</", "<\\/"),
+            include_str!("./static/search.js"),
+        );
+
+        let mut main = String::new();
+
+        if let Some(landing_page) = &self.landing_page {
+            main.push_str(&self.rewrite_links(landing_page, ""));
+        }
+
+        for (module_name, html) in self.modules.iter() {
+            main.push_str(&format!(
+                r#"<section class="single-file-module" id="{}">"#,
+                module_name
+            ));
+            main.push_str(&self.rewrite_links(html, module_name));
+            main.push_str("</section>");
+        }
+
+        let html = replace_part(template, SCRIPT_TAG, "");
+        let html = replace_part(&html, "</body>", &scripts);
+        let html = replace_part(
+            &html,
+            STYLESHEET_TAG,
+            &format!("<style>\n{}</style>", include_str!("./static/styles.css")),
+        );
+        let html = replace_part(
+            &html,
+            FAVICON_URL,
+            &svg_data_url(include_str!("./static/favicon.svg")),
+        );
+        let html = replace_part(&html, SEARCH_INDEX_URL, "");
+        let html = replace_part(&html, LOGO_LINK, r##"<a class="logo" href="#""##);
+
+        html.replace("<!-- Prefetch links -->", "")
+            .replace("<!-- OpenSearch -->", "")
+            .replace("<!-- Page title -->", &self.head)
+            .replace("<!-- Page metadata -->", "")
+            .replace("<!-- Math -->", "")
+            .replace(
+                "<!-- Module links -->",
+                &self.rewrite_links(&self.sidebar, ""),
+            )
+            .replace(
+                "<!-- Package Name and Version -->",
+                &self.rewrite_links(&self.name_and_version, ""),
+            )
+            .replace("<!-- Module Docs -->", &main)
+    }
+
+    /// Point the links in the html of a page at anchors in the single file, and prefix its ids
+    /// with the page's module name. The landing page's module name is empty.
+    fn rewrite_links(&self, html: &str, module_name: &str) -> String {
+        let html = rewrite_attribute(html, "href", |href| {
+            if let Some(fragment) = href.strip_prefix('#') {
+                Some(anchor(module_name, fragment))
+            } else {
+                let rest = href.strip_prefix(self.base_url)?;
+                let (path, fragment) = rest.split_once('#').unwrap_or((rest, ""));
+
+                (path.is_empty() || self.modules.iter().any(|(name, _)| name == path))
+                    .then(|| anchor(path, fragment))
+            }
+        });
+
+        if module_name.is_empty() {
+            html
+        } else {
+            rewrite_attribute(&html, "id", |id| Some(format!("{module_name}/{id}")))
+        }
+    }
+}
+
+/// The link to an id on a module's part of the page, e.g. `#Str/concat`, or to the module itself
+/// if `id` is empty. The landing page's module name is empty.
+fn anchor(module_name: &str, id: &str) -> String {
+    match (module_name, id) {
+        ("", id) => format!("#{id}"),
+        (module_name, "") => format!("#{module_name}"),
+        (module_name, id) => format!("#{module_name}/{id}"),
+    }
+}
+
+/// Replace every value of the attribute for which `rewrite` returns something
+fn rewrite_attribute(
+    html: &str,
+    attribute: &str,
+    rewrite: impl Fn(&str) -> Option<String>,
+) -> String {
+    let needle = format!(" {attribute}=\"");
+    let mut buf = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(found) = rest.find(&needle) {
+        let value_start = found + needle.len();
+        let value_end = match rest[value_start..].find('"') {
+            Some(len) => value_start + len,
+            None => break,
+        };
+        let value = &rest[value_start..value_end];
+
+        buf.push_str(&rest[..value_start]);
+        buf.push_str(rewrite(value).as_deref().unwrap_or(value));
+        rest = &rest[value_end..];
+    }
+
+    buf.push_str(rest);
+
+    buf
+}
+
+/// `html` with `part` replaced. It's a bug for the part to be missing, since the two come from
+/// the same template.
+fn replace_part(html: &str, part: &str, replacement: &str) -> String {
+    debug_assert!(html.contains(part), "index.html has no {part}");
+
+    html.replace(part, replacement)
+}
+
+/// A `data:` URL with the SVG in it, percent-encoding anything that isn't allowed in a URL or
+/// in an attribute value.
+fn svg_data_url(svg: &str) -> String {
+    let mut url = String::from("data:image/svg+xml,");
+
+    for byte in svg.trim().bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.!~*'()/:;=,?@".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    url
+}
+
+#[cfg(test)]
+mod test {
+    use super::SingleFile;
+    use pretty_assertions::assert_eq;
+
+    fn single_file() -> SingleFile<'static> {
+        SingleFile {
+            base_url: "/pkg/",
+            head: String::new(),
+            sidebar: String::new(),
+            name_and_version: String::new(),
+            landing_page: None,
+            modules: vec![
+                ("Str".to_string(), String::new()),
+                ("Json.Decode".to_string(), String::new()),
+            ],
+        }
+    }
+
+    #[test]
+    fn anchors_get_the_module_name() {
+        let html = r##"<h3 id="concat"><a href="#concat">concat</a></h3>"##;
+
+        assert_eq!(
+            single_file().rewrite_links(html, "Str"),
+            r##"<h3 id="Str/concat"><a href="#Str/concat">concat</a></h3>"##
+        );
+        // The landing page's ids stay as they are
+        assert_eq!(single_file().rewrite_links(html, ""), html);
+    }
+
+    #[test]
+    fn absolute_links_to_modules_become_anchors() {
+        let html = concat!(
+            r#"<a href="/pkg/Str">Str</a>"#,
+            r#"<a href="/pkg/Json.Decode#decode">decode</a>"#,
+            r#"<a href="/pkg/">Home</a>"#,
+        );
+
+        assert_eq!(
+            single_file().rewrite_links(html, ""),
+            concat!(
+                r##"<a href="#Str">Str</a>"##,
+                r##"<a href="#Json.Decode/decode">decode</a>"##,
+                r##"<a href="#">Home</a>"##,
+            )
+        );
+    }
+
+    #[test]
+    fn other_links_stay_as_they_are() {
+        let html = concat!(
+            // Not a module of this package
+            r#"<a href="/pkg/Nope#x">Nope</a>"#,
+            r#"<a href="/other/Str">Str</a>"#,
+            r#"<a href="https://example.com/pkg/Str">Example</a>"#,
+            // Relative links don't come from the docs generator, so they're left to the author
+            r#"<a href="Str#concat">concat</a>"#,
+            r#"<a href="../Str">Str</a>"#,
+        );
+
+        assert_eq!(single_file().rewrite_links(html, "Json.Decode"), html);
+    }
+}
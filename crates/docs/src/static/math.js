@@ -0,0 +1,10 @@
+(() => {
+  // The docs generator only loads this (and KaTeX) on pages with math in their doc comments.
+  // Each math element holds its TeX as text, which stays readable if KaTeX can't load.
+  document.querySelectorAll(".math").forEach((element) => {
+    katex.render(element.textContent, element, {
+      displayMode: element.classList.contains("math-display"),
+      throwOnError: false,
+    });
+  });
+})();
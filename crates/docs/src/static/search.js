@@ -1,4 +1,7 @@
 (() => {
+  // Everything on the page works without JS; this only enables the extras.
+  document.documentElement.classList.remove("no-js");
+
   let sidebar = document.getElementById("sidebar-nav");
   let searchBox = document.getElementById("module-search");
 
@@ -9,13 +12,10 @@
       // Un-hide everything
       sidebar.querySelectorAll(".sidebar-entry a").forEach((entry) => entry.classList.remove("hidden"));
 
-      // Re-hide all the sub-entries except for those of the current module
-      let currentModuleName = document.querySelector('.module-name').textContent;
-
+      // Re-hide all the sub-entries except for those of the current module,
+      // which the docs generator already marked as active.
       sidebar.querySelectorAll(".sidebar-entry").forEach((entry) => {
-        let entryName = entry.querySelector('.sidebar-module-link').textContent;
-        if (currentModuleName === entryName) {
-          entry.firstChild.classList.add("active");
+        if (entry.querySelector(".sidebar-module-link.active") !== null) {
           return;
         };
         entry.querySelectorAll(".sidebar-sub-entries a").forEach((subEntry) => subEntry.classList.add("hidden"));
@@ -41,13 +41,17 @@
     }
   }
 
+  function isEditable(element) {
+    return element === searchBox || element.tagName === "INPUT" || element.tagName === "TEXTAREA" || element.isContentEditable;
+  }
+
   searchBox.addEventListener("input", search);
 
   search();
 
   // Capture '/' keypress for quick search
   window.addEventListener("keyup", (e) => {
-    if (e.key === "s" && document.activeElement !== searchBox) {
+    if (e.key === "s" && !isEditable(document.activeElement)) {
       e.preventDefault;
       searchBox.focus();
       searchBox.value = "";
@@ -65,6 +69,278 @@
     }
   });
 
+  // The search palette searches every entry of every module, using search-index.json.
+  // Open it with "/" or Ctrl+K, move through the results with the arrow keys, and press Enter
+  // to go to one. Besides words to match against entry names, a query can have operators:
+  //   module:Str      only entries in modules whose name starts with "Str"
+  //   returns:Result  only functions returning a Result, or values which are one
+  //   takes:Str       only functions with a Str argument
+  const SEARCH_INDEX_VERSION = 1;
+  const MAX_RESULTS = 50;
+  const SEARCH_OPERATORS = ["module", "returns", "takes"];
+
+  const palette = document.getElementById("search-palette");
+  const paletteInput = document.getElementById("search-palette-input");
+  const paletteResults = document.getElementById("search-palette-results");
+  let searchIndex = null;
+  let selectedIndex = 0;
+
+  function loadSearchIndex() {
+    if (searchIndex !== null) {
+      return Promise.resolve(searchIndex);
+    }
+
+    const indexUrl = new URL(palette.dataset.indexUrl, window.location.href);
+    // The single-file docs have the index inlined, since they can't fetch anything.
+    const inlineIndex = document.getElementById("search-index");
+    const loadIndex = inlineIndex === null
+      ? fetch(indexUrl).then((response) => response.json())
+      : Promise.resolve().then(() => JSON.parse(inlineIndex.textContent));
+
+    return loadIndex
+      .then((index) => {
+        // An index in a format we don't know would give wrong results, so don't use it at all.
+        const entries = index.version === SEARCH_INDEX_VERSION ? index.entries : [];
+
+        searchIndex = entries.map((entry) => ({ ...entry, href: new URL(entry.url, indexUrl).href }));
+
+        return searchIndex;
+      })
+      .catch(() => {
+        searchIndex = [];
+
+        return searchIndex;
+      });
+  }
+
+  function parseQuery(query) {
+    const parsed = { words: [], module: null, returns: null, takes: null };
+
+    query.toLowerCase().split(/\s+/).filter((token) => token !== "").forEach((token) => {
+      const colon = token.indexOf(":");
+      const operator = colon > 0 ? token.slice(0, colon) : null;
+      const operand = colon > 0 ? token.slice(colon + 1) : token;
+
+      if (SEARCH_OPERATORS.includes(operator) && operand !== "") {
+        parsed[operator] = operand;
+      } else {
+        parsed.words.push(token);
+      }
+    });
+
+    return parsed;
+  }
+
+  // How well `word` matches `name`, or 0 if it doesn't. Exact matches beat prefixes, which beat
+  // substrings, which beat fuzzy matches of the word's letters in order, e.g. "wsp" for "walkUntilSpace".
+  function wordScore(word, name) {
+    if (name === word) {
+      return 1000;
+    }
+
+    if (name.startsWith(word)) {
+      return 800 - name.length;
+    }
+
+    if (name.includes(word)) {
+      return 600 - name.length;
+    }
+
+    let position = 0;
+    let gaps = 0;
+
+    for (const char of word) {
+      const found = name.indexOf(char, position);
+
+      if (found === -1) {
+        return 0;
+      }
+
+      gaps += found - position;
+      position = found + 1;
+    }
+
+    return Math.max(1, 400 - gaps * 10 - name.length);
+  }
+
+  function entryScore(query, entry) {
+    if (query.module !== null && !entry.module.toLowerCase().startsWith(query.module)) {
+      return 0;
+    }
+
+    if (query.returns !== null && (entry.returns === null || entry.returns.toLowerCase() !== query.returns)) {
+      return 0;
+    }
+
+    if (query.takes !== null && !entry.takes.some((arg) => arg.toLowerCase() === query.takes)) {
+      return 0;
+    }
+
+    const name = entry.name.toLowerCase();
+    const qualifiedName = `${entry.module}.${entry.name}`.toLowerCase();
+    let score = 1;
+
+    for (const word of query.words) {
+      const wordMatch = Math.max(wordScore(word, name), wordScore(word, qualifiedName));
+
+      if (wordMatch === 0) {
+        return 0;
+      }
+
+      score += wordMatch;
+    }
+
+    return score;
+  }
+
+  function renderResults(results) {
+    paletteResults.replaceChildren();
+
+    if (results.length === 0) {
+      const item = document.createElement("li");
+      item.classList.add("search-palette-empty");
+      item.textContent = palette.dataset.noResults;
+      paletteResults.appendChild(item);
+
+      return;
+    }
+
+    results.forEach((entry, index) => {
+      const link = document.createElement("a");
+      link.href = entry.href;
+      link.tabIndex = -1;
+
+      const name = document.createElement("span");
+      name.classList.add("search-palette-name");
+      name.textContent = `${entry.module}.${entry.name}`;
+
+      const signature = document.createElement("code");
+      signature.classList.add("search-palette-signature");
+      signature.textContent = entry.signature;
+
+      link.append(name, " ", signature);
+
+      const item = document.createElement("li");
+      item.id = `search-palette-result-${index}`;
+      item.setAttribute("role", "option");
+      item.appendChild(link);
+      paletteResults.appendChild(item);
+    });
+
+    selectResult(0);
+  }
+
+  function selectResult(index) {
+    const items = paletteResults.querySelectorAll("li[role=option]");
+
+    if (items.length === 0) {
+      return;
+    }
+
+    selectedIndex = (index + items.length) % items.length;
+
+    items.forEach((item, itemIndex) => {
+      const isSelected = itemIndex === selectedIndex;
+
+      item.setAttribute("aria-selected", isSelected);
+
+      if (isSelected) {
+        paletteInput.setAttribute("aria-activedescendant", item.id);
+        item.scrollIntoView({ block: "nearest" });
+      }
+    });
+  }
+
+  function searchPalette() {
+    const query = parseQuery(paletteInput.value);
+
+    if (query.words.length === 0 && query.module === null && query.returns === null && query.takes === null) {
+      paletteResults.replaceChildren();
+      paletteInput.removeAttribute("aria-activedescendant");
+
+      return;
+    }
+
+    loadSearchIndex().then((entries) => {
+      const results = entries
+        .map((entry) => ({ entry, score: entryScore(query, entry) }))
+        .filter((result) => result.score > 0)
+        .sort((a, b) => b.score - a.score)
+        .slice(0, MAX_RESULTS)
+        .map((result) => result.entry);
+
+      renderResults(results);
+    });
+  }
+
+  function openPalette() {
+    palette.classList.remove("hidden");
+    paletteInput.value = "";
+    paletteResults.replaceChildren();
+    paletteInput.focus();
+
+    // Start downloading the index while the user types.
+    loadSearchIndex();
+  }
+
+  function closePalette() {
+    palette.classList.add("hidden");
+    paletteInput.blur();
+  }
+
+  paletteInput.addEventListener("input", searchPalette);
+
+  paletteInput.addEventListener("keydown", (e) => {
+    if (e.key === "ArrowDown") {
+      e.preventDefault();
+      selectResult(selectedIndex + 1);
+    } else if (e.key === "ArrowUp") {
+      e.preventDefault();
+      selectResult(selectedIndex - 1);
+    } else if (e.key === "Enter") {
+      const selected = paletteResults.querySelector("li[aria-selected=true] a");
+
+      if (selected !== null) {
+        e.preventDefault();
+        closePalette();
+        window.location.href = selected.href;
+      }
+    } else if (e.key === "Escape") {
+      e.preventDefault();
+      closePalette();
+    }
+  });
+
+  paletteInput.addEventListener("blur", closePalette);
+
+  paletteResults.addEventListener("mousedown", (e) => {
+    // Keep the focus in the input, so the palette stays open until the clicked link is followed.
+    e.preventDefault();
+  });
+
+  // Links within the same page (like in the single-file docs) don't unload it, so close the palette.
+  paletteResults.addEventListener("click", closePalette);
+
+  // Searches can be linked to with ?search=, which is also how browsers search the docs,
+  // using opensearch.xml.
+  const linkedQuery = new URLSearchParams(window.location.search).get("search");
+
+  if (linkedQuery !== null && linkedQuery.trim() !== "") {
+    openPalette();
+    paletteInput.value = linkedQuery;
+    searchPalette();
+  }
+
+  window.addEventListener("keydown", (e) => {
+    const isShortcut = (e.key === "/" && !isEditable(document.activeElement))
+      || (e.key === "k" && (e.ctrlKey || e.metaKey));
+
+    if (isShortcut) {
+      e.preventDefault();
+      openPalette();
+    }
+  });
+
   const isTouchSupported = () => {
     try{ document.createEvent("TouchEvent"); return true; }
     catch(e){ return false; }
@@ -121,4 +397,38 @@
       });  
     }
   });
+
+  // Pages of older versions were generated before the newer versions existed,
+  // so get the current list of versions and add any that are missing.
+  const versionSwitcher = document.querySelector(".version-switcher");
+
+  if (versionSwitcher !== null) {
+    const versionsUrl = versionSwitcher.dataset.versionsUrl;
+    const rootUrl = versionsUrl.slice(0, versionsUrl.lastIndexOf("/") + 1);
+    const moduleName = versionSwitcher.dataset.module;
+    const list = versionSwitcher.querySelector("ul");
+
+    fetch(versionsUrl)
+      .then((response) => response.json())
+      .then((versions) => {
+        const known = new Set(Array.from(list.querySelectorAll("a"), (link) => link.textContent));
+
+        versions.reverse().forEach((version) => {
+          if (known.has(version)) {
+            return;
+          }
+
+          const link = document.createElement("a");
+          link.href = `${rootUrl}${version}/${moduleName}`;
+          link.textContent = version;
+
+          const item = document.createElement("li");
+          item.appendChild(link);
+          list.prepend(item);
+        });
+      })
+      .catch(() => {
+        // The links in the page still work; they just might not include the newest versions.
+      });
+  }
 })();
@@ -0,0 +1,111 @@
+//! The text that appears on every generated page regardless of what's being documented:
+//! navigation, search, headings, and so on. Overriding these allows docs sites in languages
+//! other than English.
+
+/// All the user-facing text the docs generator adds around a package's own documentation.
+///
+/// Strings containing `{name}` have it replaced by the name of the thing being linked to.
+/// The strings are escaped before being inserted into the HTML, so they should be plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocsStrings {
+    /// The `lang` attribute of the page, e.g. "en" or "pt-BR"
+    pub lang: String,
+    pub skip_to_content: String,
+    /// Accessible label for the sidebar listing the modules
+    pub modules_nav_label: String,
+    pub search_placeholder: String,
+    pub search_label: String,
+    /// Shown after the search label, telling the user which key focuses the search box
+    pub search_shortcut_hint: String,
+    /// Placeholder of the search palette, which searches every entry of every module
+    pub search_palette_placeholder: String,
+    /// Shown in the search palette when nothing matches the query
+    pub search_palette_no_results: String,
+    /// Tooltip for the logo, which links to the root of the site
+    pub logo_title: String,
+    /// Accessible label for the link icon next to each entry
+    pub link_to_entry: String,
+    /// Caption of the table describing a function's arguments
+    pub arguments: String,
+    /// Precedes the list of abilities a type implements
+    pub implements: String,
+    /// Precedes the list of types which implement an ability
+    pub implemented_by: String,
+    /// Summary of the collapsible list of entries whose types mention this one
+    pub referenced_by: String,
+    /// Precedes the links to other entries with similar types
+    pub see_also: String,
+    /// Summary of the collapsible source code of each entry, when it's included
+    pub source: String,
+    /// Note under a re-exported entry, where `{name}` is the qualified name of the original
+    pub reexported_from: String,
+    /// In place of an entry that moved to another module, where `{name}` is its new qualified name
+    pub moved_to: String,
+    /// Title of the Atom feed of API changes, where `{name}` is the package's name
+    pub changes_feed_title: String,
+    /// Name browsers show for searching these docs, where `{name}` is the package's name.
+    /// Only its first 16 characters fit in some browsers' lists of search engines.
+    pub opensearch_title: String,
+    /// Heading of the landing page section explaining how to write an app for a platform
+    pub platform_heading: String,
+    /// Introduces the type of the value an app must provide, where `{name}` is its name
+    pub platform_requires: String,
+    /// Explains the type variables in that type, where `{name}` is the list of them
+    pub platform_rigids: String,
+    /// Precedes the list of modules an app for the platform can import
+    pub platform_exposes: String,
+    /// Precedes what the platform provides to the host, e.g. `mainForHost`
+    pub platform_provides: String,
+    /// Accessible label for the dropdown listing the other published versions
+    pub versions_label: String,
+    /// Title of the page made from the package's CHANGELOG.md, and of the link to it in the header
+    pub changelog: String,
+    /// Title and heading of a workspace's index page, which lists its packages
+    pub packages: String,
+    pub footer: Vec<String>,
+}
+
+impl Default for DocsStrings {
+    fn default() -> Self {
+        Self {
+            lang: "en".to_string(),
+            skip_to_content: "Skip to content".to_string(),
+            modules_nav_label: "Modules".to_string(),
+            search_placeholder: "Search".to_string(),
+            search_label: "Search".to_string(),
+            search_shortcut_hint: "(press {name})".to_string(),
+            search_palette_placeholder: "Search, e.g. map module:List returns:List".to_string(),
+            search_palette_no_results: "No results".to_string(),
+            logo_title: "Return to Roc packages".to_string(),
+            link_to_entry: "Link to {name}".to_string(),
+            arguments: "Arguments".to_string(),
+            implements: "Implements".to_string(),
+            implemented_by: "Implemented by".to_string(),
+            referenced_by: "Referenced by".to_string(),
+            see_also: "See also:".to_string(),
+            source: "Source".to_string(),
+            reexported_from: "Re-exported from {name}".to_string(),
+            moved_to: "Moved to {name}".to_string(),
+            changes_feed_title: "API changes in {name}".to_string(),
+            opensearch_title: "{name} docs".to_string(),
+            platform_heading: "Writing an app for this platform".to_string(),
+            platform_requires: "An app for this platform provides {name}, with this type:"
+                .to_string(),
+            platform_rigids: "The app chooses what these type variables are: {name}".to_string(),
+            platform_exposes: "The app can import these modules:".to_string(),
+            platform_provides: "The platform provides this to the host:".to_string(),
+            versions_label: "Versions".to_string(),
+            changelog: "Changelog".to_string(),
+            packages: "Packages".to_string(),
+            footer: vec![
+                "Made by people who like to make nice things.".to_string(),
+                "© 2021".to_string(),
+            ],
+        }
+    }
+}
+
+/// Substitutes `name` into a string like `"Link to {name}"`
+pub fn with_name(template: &str, name: &str) -> String {
+    template.replace("{name}", name)
+}
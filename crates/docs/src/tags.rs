@@ -0,0 +1,201 @@
+//! The constructors of an exposed tag union type, each with its own anchor, e.g. `#Color.Custom`
+//! for `Custom` in `Color : [Red, Green, Custom U8 U8 U8]`, so that they can be linked to from
+//! the sidebar and from doc comments.
+use crate::abilities::resolve_name;
+use crate::metadata::escape_attr;
+use crate::strings::with_name;
+use crate::{push_html, type_annotation_to_html, LINK_SVG};
+use roc_can::scope::Scope;
+use roc_collections::VecSet;
+use roc_load::docs::{DocDef, DocEntry, Tag, TypeAnnotation};
+use roc_load::LoadedModule;
+use roc_module::symbol::Symbol;
+
+/// The tags of a type whose definition is a tag union. Values and opaque types have none.
+pub fn tag_constructors(doc_def: &DocDef) -> &[Tag] {
+    let is_type = doc_def.name.starts_with(|c: char| c.is_uppercase());
+
+    match &doc_def.type_annotation {
+        TypeAnnotation::TagUnion { tags, .. } if is_type => tags.as_slice(),
+        _ => &[],
+    }
+}
+
+/// The id of a tag's anchor on its type's module page, e.g. "Color.Custom"
+pub fn tag_anchor(type_name: &str, tag_name: &str) -> String {
+    format!("{type_name}.{tag_name}")
+}
+
+/// Renders each of the type's tags with its arguments, under the type's heading.
+pub fn render_tag_constructors(buf: &mut String, doc_def: &DocDef, link_label: &str) {
+    let tags = tag_constructors(doc_def);
+
+    if tags.is_empty() {
+        return;
+    }
+
+    let mut list = String::new();
+
+    for tag in tags {
+        let anchor = tag_anchor(&doc_def.name, &tag.name);
+        let href = format!("#{anchor}");
+        let label = escape_attr(&with_name(link_label, &anchor));
+        let mut content = String::new();
+
+        push_html(
+            &mut content,
+            "a",
+            vec![("href", href.as_str()), ("aria-label", label.as_str())],
+            LINK_SVG,
+        );
+        push_html(&mut content, "strong", vec![], tag.name.as_str());
+
+        for value in &tag.values {
+            content.push(' ');
+            type_annotation_to_html(0, &mut content, value, true);
+        }
+
+        push_html(&mut list, "li", vec![("id", anchor.as_str())], content);
+    }
+
+    push_html(buf, "ul", vec![("class", "tag-constructors")], list);
+}
+
+/// Resolves a reference like `Color.Custom` or `Shape.Color.Custom` in a doc comment to the
+/// exposed type and the name of one of its tags. Returns None for anything else, including
+/// `Module.Type` references, which read the same way but name a type rather than a tag.
+pub fn resolve_tag_reference<'a>(
+    reference: &'a str,
+    scope: &Scope,
+    all_exposed_symbols: &VecSet<Symbol>,
+    loaded_module: &LoadedModule,
+) -> Option<(Symbol, &'a str)> {
+    let (type_name, tag_name) = reference.rsplit_once('.')?;
+    let type_ident = type_name.rsplit('.').next()?;
+
+    if ![type_ident, tag_name]
+        .iter()
+        .all(|name| name.starts_with(|c: char| c.is_uppercase()))
+    {
+        return None;
+    }
+
+    let symbol = resolve_name(scope, &loaded_module.interns, type_name)?;
+
+    if !all_exposed_symbols.contains(&symbol) {
+        return None;
+    }
+
+    let module = loaded_module.docs_by_module.get(&symbol.module_id())?;
+    let has_tag = module.entries.iter().any(|entry| match entry {
+        DocEntry::DocDef(doc_def) if doc_def.symbol == symbol => tag_constructors(doc_def)
+            .iter()
+            .any(|tag| tag.name == tag_name),
+        _ => false,
+    });
+
+    has_tag.then_some((symbol, tag_name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_tag_constructors, tag_anchor, tag_constructors};
+    use crate::LINK_SVG;
+    use pretty_assertions::assert_eq;
+    use roc_load::docs::{DocDef, Tag, TypeAnnotation};
+    use roc_module::symbol::Symbol;
+
+    fn doc_def(name: &str, type_annotation: TypeAnnotation) -> DocDef {
+        DocDef {
+            name: name.to_string(),
+            symbol: Symbol::LIST_MAP,
+            type_vars: Vec::new(),
+            type_annotation,
+            docs: None,
+            implements: Vec::new(),
+            referenced_types: Vec::new(),
+            args: Vec::new(),
+            source: String::new(),
+            reexported_from: None,
+        }
+    }
+
+    fn apply(name: &str, parts: Vec<TypeAnnotation>) -> TypeAnnotation {
+        TypeAnnotation::Apply {
+            name: name.to_string(),
+            parts,
+        }
+    }
+
+    fn color() -> TypeAnnotation {
+        TypeAnnotation::TagUnion {
+            tags: vec![
+                Tag {
+                    name: "Red".to_string(),
+                    values: Vec::new(),
+                },
+                Tag {
+                    name: "Custom".to_string(),
+                    values: vec![
+                        apply("U8", Vec::new()),
+                        apply("List", vec![TypeAnnotation::BoundVariable("a".to_string())]),
+                    ],
+                },
+            ],
+            extension: Box::new(TypeAnnotation::NoTypeAnn),
+        }
+    }
+
+    #[test]
+    fn only_tag_union_types_have_constructors() {
+        let names = |doc_def: &DocDef| -> Vec<String> {
+            tag_constructors(doc_def)
+                .iter()
+                .map(|tag| tag.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names(&doc_def("Color", color())), vec!["Red", "Custom"]);
+        // A value whose type is a tag union
+        assert!(names(&doc_def("red", color())).is_empty());
+        assert!(names(&doc_def("Id", apply("U64", Vec::new()))).is_empty());
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(tag_anchor("Color", "Custom"), "Color.Custom");
+    }
+
+    #[test]
+    fn renders_each_constructor() {
+        let mut buf = String::new();
+
+        render_tag_constructors(&mut buf, &doc_def("Color", color()), "Link to {name}");
+
+        assert_eq!(
+            buf,
+            format!(
+                concat!(
+                    r#"<ul class="tag-constructors" >"#,
+                    r##"<li id="Color.Red" ><a href="#Color.Red" "##,
+                    r#"aria-label="Link to Color.Red" >"#,
+                    "{svg}</a><strong>Red</strong></li>",
+                    r##"<li id="Color.Custom" ><a href="#Color.Custom" "##,
+                    r#"aria-label="Link to Color.Custom" >"#,
+                    "{svg}</a><strong>Custom</strong> U8 (List a)</li>",
+                    "</ul>",
+                ),
+                svg = LINK_SVG
+            )
+        );
+    }
+
+    #[test]
+    fn no_list_without_constructors() {
+        let mut buf = String::new();
+
+        render_tag_constructors(&mut buf, &doc_def("red", color()), "Link to {name}");
+
+        assert_eq!(buf, "");
+    }
+}
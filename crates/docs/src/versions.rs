@@ -0,0 +1,239 @@
+//! Publishing several versions of a package's docs side by side, e.g. "/1.2.0/Str" and
+//! "/1.1.0/Str", with a switcher in the header to go between them.
+use crate::metadata::escape_attr;
+use crate::push_html;
+
+/// The file listing every published version, next to the versions' directories.
+pub const VERSIONS_JSON: &str = "versions.json";
+
+/// Versions name directories of the site, so each has to be a semver version like "1.2.0" or
+/// "2.0.0-beta.1+build.5", or a single simple path segment like "latest". That rules out
+/// versions like "../elsewhere" or "/tmp/docs", which would put the pages outside the site.
+pub fn check_version(version: &str) -> Result<(), String> {
+    if is_semver(version) || is_simple_segment(version) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{:?} isn't a valid docs version. It has to be a semver version like 1.2.0, \
+            or a name made of letters, digits, `.`, `-` and `_` like latest.",
+            version
+        ))
+    }
+}
+
+/// Checks the version being documented and the published ones, see [check_version]
+pub fn check_versions(current: Option<&str>, published: &[String]) -> Result<(), String> {
+    current
+        .into_iter()
+        .chain(published.iter().map(String::as_str))
+        .try_for_each(check_version)
+}
+
+fn is_semver(version: &str) -> bool {
+    let (version, build) = match version.split_once('+') {
+        Some((version, build)) => (version, Some(build)),
+        None => (version, None),
+    };
+    let (core, pre_release) = match version.split_once('-') {
+        Some((core, pre_release)) => (core, Some(pre_release)),
+        None => (version, None),
+    };
+    let is_identifiers = |text: &str| {
+        text.split('.').all(|identifier| {
+            !identifier.is_empty()
+                && identifier
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    };
+
+    core.split('.').count() == 3
+        && core
+            .split('.')
+            .all(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+        && pre_release.map_or(true, is_identifiers)
+        && build.map_or(true, is_identifiers)
+}
+
+fn is_simple_segment(version: &str) -> bool {
+    !version.is_empty()
+        && !version.starts_with('.')
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// The published versions, plus the one being generated if it isn't among them yet.
+/// A new version goes first, since the published ones are listed newest first.
+pub fn all_versions(current: &str, published: &[String]) -> Vec<String> {
+    let mut versions = Vec::with_capacity(published.len() + 1);
+
+    if !published.iter().any(|version| version == current) {
+        versions.push(current.to_string());
+    }
+
+    versions.extend(published.iter().cloned());
+
+    versions
+}
+
+/// A JSON array of the versions, e.g. `["1.2.0", "1.1.0"]`
+pub fn render_versions_json(versions: &[String]) -> String {
+    let mut buf = String::new();
+
+    buf.push('[');
+
+    for (index, version) in versions.iter().enumerate() {
+        if index > 0 {
+            buf.push_str(", ");
+        }
+
+        buf.push('"');
+
+        for c in version.chars() {
+            match c {
+                '"' => buf.push_str("\\\""),
+                '\\' => buf.push_str("\\\\"),
+                _ => buf.push(c),
+            }
+        }
+
+        buf.push('"');
+    }
+
+    buf.push_str("]\n");
+
+    buf
+}
+
+/// A dropdown with a link to the current module in each version. It's a `<details>` element so
+/// it works without JavaScript. search.js adds any versions published after this page was made,
+/// by reading versions.json.
+pub fn render_version_switcher(
+    root_url: &str,
+    current: &str,
+    versions: &[String],
+    module_name: &str,
+    label: &str,
+) -> String {
+    let mut list = String::new();
+
+    for version in versions {
+        let href = escape_attr(&format!("{root_url}{version}/{module_name}"));
+        let mut link_attrs = vec![("href", href.as_str())];
+
+        if version == current {
+            link_attrs.push(("aria-current", "page"));
+        }
+
+        push_html(&mut list, "li", vec![], {
+            let mut link = String::new();
+
+            push_html(&mut link, "a", link_attrs, escape_attr(version));
+
+            link
+        });
+    }
+
+    let versions_url = escape_attr(&format!("{root_url}{VERSIONS_JSON}"));
+    let mut buf = String::new();
+
+    push_html(
+        &mut buf,
+        "details",
+        vec![
+            ("class", "version-switcher"),
+            ("data-versions-url", versions_url.as_str()),
+            ("data-module", module_name),
+        ],
+        {
+            let mut content = String::new();
+            let label = escape_attr(label);
+
+            push_html(
+                &mut content,
+                "summary",
+                vec![("class", "version"), ("aria-label", label.as_str())],
+                escape_attr(current),
+            );
+            push_html(&mut content, "ul", vec![], list);
+
+            content
+        },
+    );
+
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::{all_versions, check_version, check_versions, render_versions_json};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn semver_versions() {
+        for version in [
+            "1.2.0",
+            "0.0.1",
+            "2.0.0-beta.1",
+            "1.0.0+build.5",
+            "1.0.0-rc-1+20230101",
+        ] {
+            assert_eq!(check_version(version), Ok(()), "{}", version);
+        }
+    }
+
+    #[test]
+    fn simple_segments() {
+        for version in ["latest", "main", "v2", "nightly_2023-01-01", "1.2"] {
+            assert_eq!(check_version(version), Ok(()), "{}", version);
+        }
+    }
+
+    #[test]
+    fn paths_are_not_versions() {
+        for version in [
+            "",
+            ".",
+            "..",
+            "../elsewhere",
+            "1.0.0/../..",
+            "/tmp/docs",
+            "C:\\docs",
+            "a/b",
+            ".hidden",
+            "1.0.0+",
+        ] {
+            assert!(check_version(version).is_err(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn every_version_is_checked() {
+        let published = vec!["1.1.0".to_string(), "../1.0.0".to_string()];
+
+        assert_eq!(check_versions(None, &[]), Ok(()));
+        assert_eq!(check_versions(Some("1.2.0"), &published[..1]), Ok(()));
+        assert!(check_versions(Some("1.2.0"), &published).is_err());
+        assert!(check_versions(Some("/1.2.0"), &[]).is_err());
+    }
+
+    #[test]
+    fn new_version_goes_first() {
+        let published = vec!["1.1.0".to_string(), "1.0.0".to_string()];
+
+        assert_eq!(
+            all_versions("1.2.0", &published),
+            vec!["1.2.0", "1.1.0", "1.0.0"]
+        );
+        assert_eq!(all_versions("1.1.0", &published), vec!["1.1.0", "1.0.0"]);
+    }
+
+    #[test]
+    fn versions_json() {
+        assert_eq!(
+            render_versions_json(&["1.2.0".to_string(), "a\"b".to_string()]),
+            "[\"1.2.0\", \"a\\\"b\"]\n"
+        );
+    }
+}
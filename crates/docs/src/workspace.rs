@@ -0,0 +1,415 @@
+//! Docs for a workspace: a directory with several packages in it, e.g. `json/main.roc` and
+//! `json/extra/main.roc`. Each package's docs go in a directory named after where the package is
+//! in the workspace, e.g. `/json/extra/`, and an index page at the root lists them all.
+//!
+//! When a package depends on another one in the workspace, its doc links to the other package's
+//! entries go to that package's docs, rather than rendering as plain code.
+use crate::api::ApiSnapshot;
+use crate::guides::Guides;
+use crate::metadata::{escape_attr, first_sentence, render_page_metadata};
+use crate::reexports::Reexport;
+use crate::{push_html, search_index, DocsStrings, BUILD_DIR};
+use roc_collections::{VecMap, VecSet};
+use roc_load::LoadedModule;
+use roc_module::symbol::Symbol;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ROOT_FILE: &str = "main.roc";
+
+/// A package somewhere in the workspace
+pub struct WorkspacePackage {
+    /// Where it is in the workspace, e.g. "json/extra"
+    pub name: String,
+    pub root_file: PathBuf,
+    /// The first sentence of its landing page, if it has one
+    pub description: Option<String>,
+}
+
+/// Finds every package or platform in the subdirectories of `workspace_dir`, however deeply
+/// they're nested, sorted by name.
+pub fn find_packages(workspace_dir: &Path) -> Vec<WorkspacePackage> {
+    let mut packages = Vec::new();
+
+    find_packages_help(workspace_dir, workspace_dir, &mut packages);
+
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    packages
+}
+
+fn find_packages_help(workspace_dir: &Path, dir: &Path, packages: &mut Vec<WorkspacePackage>) {
+    let entries = fs::read_dir(dir).unwrap_or_else(|err| {
+        panic!(
+            "TODO gracefully handle failing to read {}: {}",
+            dir.display(),
+            err
+        )
+    });
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        // Hidden directories are things like .git, and the generated docs aren't source
+        if !path.is_dir()
+            || file_name.starts_with('.')
+            || file_name == BUILD_DIR.trim_start_matches("./")
+        {
+            continue;
+        }
+
+        let root_file = path.join(ROOT_FILE);
+
+        if is_package(&root_file) {
+            let name = path
+                .strip_prefix(workspace_dir)
+                .unwrap_or(&path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let description = Guides::for_root_file(&root_file)
+                .landing_page()
+                .as_deref()
+                .and_then(first_sentence);
+
+            packages.push(WorkspacePackage {
+                name,
+                root_file,
+                description,
+            });
+        }
+
+        // Packages can be nested inside other packages' directories
+        find_packages_help(workspace_dir, &path, packages);
+    }
+}
+
+/// Whether the file's header says it's a package or a platform, rather than an app
+fn is_package(root_file: &Path) -> bool {
+    let source = match fs::read_to_string(root_file) {
+        Ok(source) => source,
+        Err(_) => return false,
+    };
+
+    let header = source
+        .lines()
+        .map(str::trim_start)
+        .find(|line| !line.is_empty() && !line.starts_with('#'));
+
+    match header {
+        Some(line) => line.starts_with("package") || line.starts_with("platform"),
+        None => false,
+    }
+}
+
+/// What the other packages in the workspace need to know to link to a package's docs
+pub struct DocumentedPackage {
+    /// The package's directory, canonicalized so it can be compared with the paths its modules
+    /// were loaded from
+    dir: PathBuf,
+    /// The path every page's URL starts with, e.g. "/json/"
+    base_url: String,
+    /// The names of the entries documented in each module
+    entries_by_module: VecMap<String, Vec<String>>,
+    pub exposed_symbols: VecSet<Symbol>,
+}
+
+impl DocumentedPackage {
+    pub fn new(package: &WorkspacePackage, base_url: String, loaded_module: &LoadedModule) -> Self {
+        let dir = package.root_file.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut exposed_symbols = VecSet::default();
+
+        for docs in loaded_module.docs_by_module.values() {
+            exposed_symbols.insert_all(docs.exposed_symbols.iter().copied());
+        }
+
+        let snapshot =
+            ApiSnapshot::from_modules(loaded_module.docs_by_module.values(), &exposed_symbols);
+        let entries_by_module = snapshot
+            .modules
+            .into_iter()
+            .map(|module| {
+                let names = module.entries.into_iter().map(|entry| entry.name).collect();
+
+                (module.name, names)
+            })
+            .collect();
+
+        Self {
+            dir: canonicalize(dir),
+            base_url,
+            entries_by_module,
+            exposed_symbols,
+        }
+    }
+}
+
+/// Where the doc links of `packages[current]` can go in the other packages' docs: the entries
+/// of every module it loaded from another package in the workspace, which that package
+/// documents.
+pub fn links_to_other_packages(
+    loaded_module: &LoadedModule,
+    packages: &[DocumentedPackage],
+    current: usize,
+) -> VecMap<Symbol, Reexport> {
+    let interns = &loaded_module.interns;
+    let mut links = VecMap::default();
+
+    for (module_id, (path, _)) in loaded_module.sources.iter() {
+        if module_id.is_builtin() || loaded_module.docs_by_module.contains_key(module_id) {
+            continue;
+        }
+
+        // The innermost package directory the module is in is the package it belongs to
+        let path = canonicalize(path);
+        let owner = packages
+            .iter()
+            .enumerate()
+            .filter(|(_, package)| path.starts_with(&package.dir))
+            .max_by_key(|(_, package)| package.dir.components().count());
+
+        let package = match owner {
+            Some((index, package)) if index != current => package,
+            _ => continue,
+        };
+
+        let module_name = match interns.module_ids.get_name(*module_id) {
+            Some(module_name) => module_name.as_str(),
+            None => continue,
+        };
+        let (entries, ident_ids) = match (
+            package.entries_by_module.get(&module_name.to_string()),
+            interns.all_ident_ids.get(module_id),
+        ) {
+            (Some(entries), Some(ident_ids)) => (entries, ident_ids),
+            _ => continue,
+        };
+
+        for name in entries.iter() {
+            if let Some(ident_id) = ident_ids.get_id(name) {
+                let link = Reexport {
+                    module_name: module_name.to_string(),
+                    name: name.clone(),
+                    package_url: Some(package.base_url.clone()),
+                };
+
+                links.insert(Symbol::new(*module_id, ident_id), link);
+            }
+        }
+    }
+
+    links
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A package in the list on the workspace's index page
+pub struct IndexEntry {
+    pub name: String,
+    /// The package's landing page, or its first module's page
+    pub url: String,
+    pub description: Option<String>,
+}
+
+/// The index page, which lists the packages in the sidebar and in its main content, along with
+/// their descriptions.
+pub fn render_index(
+    localized_template: &str,
+    root_url: &str,
+    canonical_base_url: Option<&str>,
+    entries: &[IndexEntry],
+    strings: &DocsStrings,
+) -> String {
+    let mut sidebar = String::new();
+    let mut list = String::new();
+
+    for entry in entries {
+        let mut link = String::new();
+
+        push_html(
+            &mut link,
+            "a",
+            vec![
+                ("class", "sidebar-module-link"),
+                ("href", entry.url.as_str()),
+            ],
+            escape_attr(&entry.name),
+        );
+        push_html(&mut sidebar, "div", vec![("class", "sidebar-entry")], link);
+
+        let mut item = String::new();
+
+        push_html(
+            &mut item,
+            "a",
+            vec![("href", entry.url.as_str())],
+            escape_attr(&entry.name),
+        );
+
+        if let Some(description) = &entry.description {
+            push_html(&mut item, "p", vec![], escape_attr(description));
+        }
+
+        push_html(&mut list, "li", vec![], item);
+    }
+
+    let mut content = String::new();
+
+    push_html(
+        &mut content,
+        "h2",
+        vec![("class", "workspace-heading")],
+        escape_attr(&strings.packages),
+    );
+    push_html(
+        &mut content,
+        "ul",
+        vec![("class", "workspace-packages")],
+        list,
+    );
+
+    let canonical_url = canonical_base_url.map(|url| format!("{}/", url.trim_end_matches('/')));
+    let title = escape_attr(&strings.packages);
+
+    localized_template
+        .replace(
+            "<!-- search-index.json -->",
+            &format!("{root_url}{}", search_index::SEARCH_INDEX_JSON),
+        )
+        .replace("<!-- search.js -->", "/search.js")
+        .replace("<!-- styles.css -->", "/styles.css")
+        .replace("<!-- favicon.svg -->", "/favicon.svg")
+        .replace("<!-- Module links -->", &sidebar)
+        .replace(
+            "<!-- Page title -->",
+            format!("<title>{title}</title>").as_str(),
+        )
+        .replace(
+            "<!-- Page metadata -->",
+            render_page_metadata(&strings.packages, None, canonical_url.as_deref()).as_str(),
+        )
+        .replace("<!-- Module Docs -->", &content)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_packages, is_package, render_index, IndexEntry};
+    use crate::DocsStrings;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    const PACKAGE: &str = "package \"json\"\n    exposes []\n    packages {}\n";
+    const PLATFORM: &str = "# The platform\n\nplatform \"cli\"\n    requires {} { main : _ }\n";
+    const APP: &str = "app \"hello\"\n    packages {}\n";
+
+    fn write(dir: &Path, path: &str, contents: &str) {
+        let path = dir.join(path);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn packages_and_platforms_are_packages() {
+        let dir = tempdir().unwrap();
+
+        write(dir.path(), "package.roc", PACKAGE);
+        write(dir.path(), "platform.roc", PLATFORM);
+        write(dir.path(), "app.roc", APP);
+        write(dir.path(), "empty.roc", "\n# Nothing here\n");
+
+        assert!(is_package(&dir.path().join("package.roc")));
+        assert!(is_package(&dir.path().join("platform.roc")));
+        assert!(!is_package(&dir.path().join("app.roc")));
+        assert!(!is_package(&dir.path().join("empty.roc")));
+        assert!(!is_package(&dir.path().join("missing.roc")));
+    }
+
+    #[test]
+    fn finds_nested_packages_sorted_by_name() {
+        let dir = tempdir().unwrap();
+
+        write(dir.path(), "json/main.roc", PACKAGE);
+        write(dir.path(), "json/extra/main.roc", PACKAGE);
+        write(
+            dir.path(),
+            "json/extra/docs/index.md",
+            "# Extra\n\nMore *JSON* things. And then some.",
+        );
+        write(dir.path(), "cli/main.roc", PLATFORM);
+        write(dir.path(), "a/b/c/main.roc", PACKAGE);
+        write(dir.path(), "hello/main.roc", APP);
+        write(dir.path(), "nothing/Other.roc", PACKAGE);
+        write(dir.path(), ".hidden/main.roc", PACKAGE);
+        write(dir.path(), "generated-docs/old/main.roc", PACKAGE);
+
+        let packages = find_packages(dir.path());
+        let found: Vec<(&str, Option<&str>)> = packages
+            .iter()
+            .map(|package| (package.name.as_str(), package.description.as_deref()))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("a/b/c", None),
+                ("cli", None),
+                ("json", None),
+                ("json/extra", Some("More JSON things.")),
+            ]
+        );
+        assert_eq!(
+            packages[2].root_file,
+            dir.path().join("json").join("main.roc")
+        );
+    }
+
+    #[test]
+    fn index_lists_every_package() {
+        let entries = vec![
+            IndexEntry {
+                name: "json".to_string(),
+                url: "/json/".to_string(),
+                description: Some("Encode & decode JSON.".to_string()),
+            },
+            IndexEntry {
+                name: "cli".to_string(),
+                url: "/cli/".to_string(),
+                description: None,
+            },
+        ];
+
+        let html = render_index(
+            "<nav><!-- Module links --></nav><main><!-- Module Docs --></main>",
+            "/",
+            None,
+            &entries,
+            &DocsStrings::default(),
+        );
+
+        assert_eq!(
+            html,
+            concat!(
+                "<nav>",
+                r#"<div class="sidebar-entry" >"#,
+                r#"<a class="sidebar-module-link" href="/json/" >json</a></div>"#,
+                r#"<div class="sidebar-entry" >"#,
+                r#"<a class="sidebar-module-link" href="/cli/" >cli</a></div>"#,
+                "</nav><main>",
+                r#"<h2 class="workspace-heading" >Packages</h2>"#,
+                r#"<ul class="workspace-packages" >"#,
+                r#"<li><a href="/json/" >json</a><p>Encode &amp; decode JSON.</p></li>"#,
+                r#"<li><a href="/cli/" >cli</a></li>"#,
+                "</ul></main>",
+            )
+        );
+    }
+}
@@ -1,10 +1,30 @@
 //! Provides a binary that is only used for static build servers.
-use clap::{Arg, Command};
-use roc_docs::generate_docs_html;
+use clap::{Arg, ArgMatches, Command};
+use roc_docs::{
+    diff_api, generate_docs_html, generate_workspace_docs_html, read_html_partial, read_redirects,
+    render_api_diff, ApiSnapshot, DocsConfig, HtmlPartials,
+};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub const ROC_FILE: &str = "ROC_FILE";
+pub const FLAG_CANONICAL_URL: &str = "canonical-url";
+pub const FLAG_DOCS_VERSION: &str = "docs-version";
+pub const FLAG_PUBLISHED_VERSIONS: &str = "published-versions";
+pub const FLAG_STRICT: &str = "strict";
+pub const FLAG_ENTRY_ORDER: &str = "entry-order";
+pub const FLAG_DIFF: &str = "diff";
+pub const FLAG_SINGLE_FILE: &str = "single-file";
+pub const FLAG_EMBED_SOURCE: &str = "embed-source";
+pub const FLAG_INCLUDE_MODULES: &str = "include-modules";
+pub const FLAG_EXCLUDE_MODULES: &str = "exclude-modules";
+pub const FLAG_MATH: &str = "math";
+pub const FLAG_PREVIOUS_API: &str = "previous-api";
+pub const FLAG_REDIRECTS: &str = "redirects";
+pub const FLAG_WORKSPACE: &str = "workspace";
+pub const FLAG_HTML_HEAD_EXTRA: &str = "html-head-extra";
+pub const FLAG_HTML_HEADER: &str = "html-header";
+pub const FLAG_HTML_FOOTER: &str = "html-footer";
 const DEFAULT_ROC_FILENAME: &str = "main.roc";
 
 fn main() -> io::Result<()> {
@@ -18,14 +38,225 @@ fn main() -> io::Result<()> {
                 .required(false)
                 .default_value(DEFAULT_ROC_FILENAME),
         )
+        .arg(
+            Arg::new(FLAG_CANONICAL_URL)
+                .long(FLAG_CANONICAL_URL)
+                .help("The URL the docs will be published at, e.g. https://example.com/my-package")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_DOCS_VERSION)
+                .long(FLAG_DOCS_VERSION)
+                .help("The version being documented, e.g. 1.2.0")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_PUBLISHED_VERSIONS)
+                .long(FLAG_PUBLISHED_VERSIONS)
+                .help("The versions that are already published, newest first, e.g. 1.1.0,1.0.0")
+                .use_value_delimiter(true)
+                .multiple_values(true)
+                .requires(FLAG_DOCS_VERSION)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_ENTRY_ORDER)
+                .long(FLAG_ENTRY_ORDER)
+                .help("The order of the entries in each module, unless its doc comment has an @order line")
+                .possible_values(["source", "alphabetical"])
+                .default_value("source")
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_STRICT)
+                .long(FLAG_STRICT)
+                .help("Exit with a nonzero code if the generated docs have any broken links")
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_SINGLE_FILE)
+                .long(FLAG_SINGLE_FILE)
+                .help("Also write the docs to one self-contained docs.html, e.g. to read offline")
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_EMBED_SOURCE)
+                .long(FLAG_EMBED_SOURCE)
+                .help("Show each entry's source code in a collapsed section under it")
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_INCLUDE_MODULES)
+                .long(FLAG_INCLUDE_MODULES)
+                .help("Only document the modules matching one of these glob patterns, e.g. Json.*,Csv")
+                .use_value_delimiter(true)
+                .multiple_values(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_EXCLUDE_MODULES)
+                .long(FLAG_EXCLUDE_MODULES)
+                .help("Leave out the modules matching one of these glob patterns, e.g. Internal*")
+                .use_value_delimiter(true)
+                .multiple_values(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_MATH)
+                .long(FLAG_MATH)
+                .help("Render TeX math in doc comments, written between dollar signs or in ```math fences")
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_PREVIOUS_API)
+                .long(FLAG_PREVIOUS_API)
+                .help("The api.json of an earlier release, to redirect links to entries that have moved to another module since, and with --canonical-url, to write an Atom feed of the API changes")
+                .value_name("API_JSON")
+                .allow_invalid_utf8(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_REDIRECTS)
+                .long(FLAG_REDIRECTS)
+                .help("A JSON file of other moves to redirect, e.g. renames, like {\"Json.decode\": \"Decode.fromJson\"}")
+                .value_name("JSON_FILE")
+                .allow_invalid_utf8(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_WORKSPACE)
+                .long(FLAG_WORKSPACE)
+                .help("Document every package in this directory, with an index page listing them, instead of one package")
+                .value_name("DIR")
+                .allow_invalid_utf8(true)
+                .conflicts_with(ROC_FILE)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_HTML_HEAD_EXTRA)
+                .long(FLAG_HTML_HEAD_EXTRA)
+                .help("An HTML file to add to the end of every page's <head>, e.g. an analytics snippet")
+                .value_name("HTML_FILE")
+                .allow_invalid_utf8(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_HTML_HEADER)
+                .long(FLAG_HTML_HEADER)
+                .help("An HTML file to add to the top header of every page, e.g. links to the rest of your site")
+                .value_name("HTML_FILE")
+                .allow_invalid_utf8(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_HTML_FOOTER)
+                .long(FLAG_HTML_FOOTER)
+                .help("An HTML file to add to the footer of every page, after the default footer")
+                .value_name("HTML_FILE")
+                .allow_invalid_utf8(true)
+                .required(false),
+        )
+        .arg(
+            Arg::new(FLAG_DIFF)
+                .long(FLAG_DIFF)
+                .help("Instead of generating docs, list the API changes between two api.json files")
+                .value_names(&["OLD", "NEW"])
+                .number_of_values(2)
+                .allow_invalid_utf8(true)
+                .required(false),
+        )
         .get_matches();
 
-    // Populate roc_files
-    generate_docs_html(PathBuf::from(matches.value_of_os(ROC_FILE).unwrap()));
+    if let Some(mut paths) = matches.values_of_os(FLAG_DIFF) {
+        let old = ApiSnapshot::read(Path::new(paths.next().unwrap()));
+        let new = ApiSnapshot::read(Path::new(paths.next().unwrap()));
+
+        match (old, new) {
+            (Ok(old), Ok(new)) => print!("{}", render_api_diff(&diff_api(&old, &new))),
+            (Err(problem), _) | (_, Err(problem)) => {
+                eprintln!("{problem}");
+                std::process::exit(1);
+            }
+        }
+
+        return Ok(());
+    }
+
+    let previous_api = matches
+        .value_of_os(FLAG_PREVIOUS_API)
+        .map(|path| ApiSnapshot::read(Path::new(path)))
+        .transpose();
+    let redirects = matches
+        .value_of_os(FLAG_REDIRECTS)
+        .map(|path| read_redirects(Path::new(path)))
+        .transpose();
+    let html_partials = read_html_partials(&matches);
+    let (previous_api, redirects, html_partials) = match (previous_api, redirects, html_partials) {
+        (Ok(previous_api), Ok(redirects), Ok(html_partials)) => {
+            (previous_api, redirects.unwrap_or_default(), html_partials)
+        }
+        (Err(problem), _, _) | (_, Err(problem), _) | (_, _, Err(problem)) => {
+            eprintln!("{problem}");
+            std::process::exit(1);
+        }
+    };
+
+    let config = DocsConfig {
+        canonical_base_url: matches.value_of(FLAG_CANONICAL_URL).map(String::from),
+        version: matches.value_of(FLAG_DOCS_VERSION).map(String::from),
+        published_versions: matches
+            .values_of(FLAG_PUBLISHED_VERSIONS)
+            .map(|versions| versions.map(String::from).collect())
+            .unwrap_or_default(),
+        entry_order: matches.value_of_t(FLAG_ENTRY_ORDER).unwrap_or_default(),
+        single_file: matches.is_present(FLAG_SINGLE_FILE),
+        embed_source: matches.is_present(FLAG_EMBED_SOURCE),
+        include_modules: matches
+            .values_of(FLAG_INCLUDE_MODULES)
+            .map(|patterns| patterns.map(String::from).collect())
+            .unwrap_or_default(),
+        exclude_modules: matches
+            .values_of(FLAG_EXCLUDE_MODULES)
+            .map(|patterns| patterns.map(String::from).collect())
+            .unwrap_or_default(),
+        math: matches.is_present(FLAG_MATH),
+        previous_api,
+        redirects,
+        html_partials,
+        ..DocsConfig::default()
+    };
+
+    let broken_links = match matches.value_of_os(FLAG_WORKSPACE) {
+        Some(workspace_dir) => generate_workspace_docs_html(Path::new(workspace_dir), config),
+        None => generate_docs_html(
+            PathBuf::from(matches.value_of_os(ROC_FILE).unwrap()),
+            config,
+        ),
+    };
+
+    if matches.is_present(FLAG_STRICT) && !broken_links.is_empty() {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
 
+/// The partials given by the --html-* flags, which are empty unless they're given
+fn read_html_partials(matches: &ArgMatches) -> Result<HtmlPartials, String> {
+    let read_partial = |flag: &str| match matches.value_of_os(flag) {
+        Some(path) => read_html_partial(Path::new(path)),
+        None => Ok(String::new()),
+    };
+
+    Ok(HtmlPartials {
+        head_extra: read_partial(FLAG_HTML_HEAD_EXTRA)?,
+        header: read_partial(FLAG_HTML_HEADER)?,
+        footer: read_partial(FLAG_HTML_FOOTER)?,
+    })
+}
+
 // These functions don't end up in the final Roc binary but Windows linker needs a definition inside the crate.
 // On Windows, there seems to be less dead-code-elimination than on Linux or MacOS, or maybe it's done later.
 #[cfg(windows)]
@@ -51,6 +51,8 @@ pub fn generate(input_path: &Path, output_path: &Path, spec_path: &Path) -> io::
                 backend: CodeGenBackend::Llvm(LlvmBackendMode::BinaryGlue),
                 opt_level: OptLevel::Development,
                 emit_debug_info: false,
+                patchable_entries: false,
+                emit_proc_stats: false,
             };
 
             let load_config = standard_load_config(
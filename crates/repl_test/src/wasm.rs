@@ -1,6 +1,6 @@
 use bumpalo::Bump;
 use roc_wasm_interp::{
-    wasi, DefaultImportDispatcher, ImportDispatcher, Instance, Value, WasiDispatcher,
+    wasi, DefaultImportDispatcher, ImportDispatcher, Instance, Memory, Value, WasiDispatcher,
 };
 
 const COMPILER_BYTES: &[u8] =
@@ -21,7 +21,7 @@ impl<'a> ImportDispatcher for CompilerDispatcher<'a> {
         module_name: &str,
         function_name: &str,
         arguments: &[Value],
-        compiler_memory: &mut [u8],
+        compiler_memory: &mut Memory<'_>,
     ) -> Option<Value> {
         let unknown = || {
             panic!(
@@ -39,22 +39,33 @@ impl<'a> ImportDispatcher for CompilerDispatcher<'a> {
                     // Get some bytes from the compiler Wasm instance and create the app Wasm instance
                     // fn test_create_app(app_bytes_ptr: *const u8, app_bytes_len: usize) -> u32;
                     assert_eq!(arguments.len(), 2);
-                    let app_bytes_ptr = arguments[0].expect_i32().unwrap() as usize;
-                    let app_bytes_len = arguments[1].expect_i32().unwrap() as usize;
-                    let app_bytes = &compiler_memory[app_bytes_ptr..][..app_bytes_len];
+                    let app_bytes_ptr = arguments[0].expect_i32().unwrap() as u32;
+                    let app_bytes_len = arguments[1].expect_i32().unwrap() as u32;
+                    let app_bytes = compiler_memory
+                        .read_bytes(app_bytes_ptr, app_bytes_len)
+                        .unwrap();
 
                     let is_debug_mode = false;
-                    let instance = Instance::from_bytes(
+                    let result = Instance::from_bytes(
                         self.arena,
                         app_bytes,
                         DefaultImportDispatcher::default(),
                         is_debug_mode,
-                    )
-                    .unwrap();
+                    );
 
-                    self.app = Some(instance);
-                    let ok = Value::I32(true as i32);
-                    Some(ok)
+                    // A bad app module is reported to the REPL rather than crashing the test
+                    let ok = match result {
+                        Ok(instance) => {
+                            self.app = Some(instance);
+                            true
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to load the app module: {}", e);
+                            self.app = None;
+                            false
+                        }
+                    };
+                    Some(Value::I32(ok as i32))
                 }
                 "test_run_app" => {
                     // fn test_run_app() -> usize;
@@ -79,12 +90,12 @@ impl<'a> ImportDispatcher for CompilerDispatcher<'a> {
                     // and return the location in that buffer where we can find the app result.
                     // fn test_get_result_and_memory(buffer_alloc_addr: *mut u8) -> usize;
                     assert_eq!(arguments.len(), 1);
-                    let buffer_alloc_addr = arguments[0].expect_i32().unwrap() as usize;
+                    let buffer_alloc_addr = arguments[0].expect_i32().unwrap() as u32;
                     match &self.app {
                         Some(instance) => {
-                            let len = instance.memory.len();
-                            compiler_memory[buffer_alloc_addr..][..len]
-                                .copy_from_slice(&instance.memory);
+                            compiler_memory
+                                .write_bytes(buffer_alloc_addr, &instance.memory)
+                                .unwrap();
                             self.result_addr.map(Value::I32)
                         }
                         None => panic!("Trying to get result and memory but there is no app"),
@@ -94,22 +105,23 @@ impl<'a> ImportDispatcher for CompilerDispatcher<'a> {
                     // Copy the Roc source code from the test into the compiler Wasm instance
                     // fn test_copy_input_string(src_buffer_addr: *mut u8);
                     assert_eq!(arguments.len(), 1);
-                    let src_buffer_addr = arguments[0].expect_i32().unwrap() as usize;
-                    let len = self.src.len();
-                    compiler_memory[src_buffer_addr..][..len].copy_from_slice(self.src.as_bytes());
+                    let src_buffer_addr = arguments[0].expect_i32().unwrap() as u32;
+                    compiler_memory
+                        .write_bytes(src_buffer_addr, self.src.as_bytes())
+                        .unwrap();
                     None
                 }
                 "test_copy_output_string" => {
                     // The REPL now has a string representing the answer. Make it available to the test code.
                     // fn test_copy_output_string(output_ptr: *const u8, output_len: usize);
                     assert_eq!(arguments.len(), 2);
-                    let output_ptr = arguments[0].expect_i32().unwrap() as usize;
-                    let output_len = arguments[1].expect_i32().unwrap() as usize;
-                    match std::str::from_utf8(&compiler_memory[output_ptr..][..output_len]) {
+                    let output_ptr = arguments[0].expect_i32().unwrap() as u32;
+                    let output_len = arguments[1].expect_i32().unwrap() as u32;
+                    match compiler_memory.read_str(output_ptr, output_len) {
                         Ok(answer) => {
                             self.answer = answer.into();
                         }
-                        Err(e) => panic!("{:?}", e),
+                        Err(e) => panic!("{}", e),
                     }
                     None
                 }
@@ -120,6 +132,10 @@ impl<'a> ImportDispatcher for CompilerDispatcher<'a> {
             unknown()
         }
     }
+
+    fn exit_status(&self) -> Option<i32> {
+        self.wasi.exit_status
+    }
 }
 
 fn run(src: &'static str) -> Result<String, String> {
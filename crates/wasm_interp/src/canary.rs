@@ -0,0 +1,187 @@
+//! Canary mode, for catching use-after-free in the allocator glue of Roc programs, e.g. a
+//! refcount that hits zero too early.
+//!
+//! It follows the allocations that the program makes through the `roc_alloc`, `roc_realloc` and
+//! `roc_dealloc` imports. Memory is zeroed when it's allocated, so a program that reads memory
+//! it never wrote gets the same result on every run. Memory is filled with a canary pattern when
+//! it's freed, and a load from it traps, until it's allocated again. Freeing the same allocation
+//! twice traps too.
+//!
+//! The allocator behind the imports has to keep its own bookkeeping outside of the memory it
+//! hands out, since freed memory is overwritten.
+use roc_wasm_module::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+/// The bytes written over freed memory, repeated from the start of each freed allocation
+pub const CANARY: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+const ALLOCATOR_MODULE: &str = "env";
+
+/// A load from freed memory, or a second free of the same allocation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CanaryTrap {
+    UseAfterFree {
+        /// Address of the first byte loaded
+        addr: u32,
+        /// Number of bytes loaded
+        size: u32,
+        /// The freed allocation that the load touched
+        freed: Range<u32>,
+    },
+    DoubleFree {
+        freed: Range<u32>,
+    },
+}
+
+impl fmt::Display for CanaryTrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanaryTrap::UseAfterFree { addr, size, freed } => write!(
+                f,
+                "Use after free: load of {} bytes at {:#x}, from the allocation at {:#x}..{:#x} which was freed by roc_dealloc",
+                size, addr, freed.start, freed.end
+            ),
+            CanaryTrap::DoubleFree { freed } => write!(
+                f,
+                "Double free: roc_dealloc was called on the allocation at {:#x}..{:#x}, which was already freed",
+                freed.start, freed.end
+            ),
+        }
+    }
+}
+
+/// The allocations made through the imports, and the freed memory that mustn't be read
+#[derive(Debug, Default)]
+pub(crate) struct Canaries {
+    /// The size of each allocation that hasn't been freed, by address
+    live: BTreeMap<u32, u32>,
+    /// The end of each freed allocation, by address. They never overlap.
+    freed: BTreeMap<u32, u32>,
+}
+
+impl Canaries {
+    /// Update the allocations after a call to an import, if it's one of the allocator's.
+    /// The arguments have already been checked against the import's type.
+    pub fn after_import(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        return_value: Option<Value>,
+        memory: &mut [u8],
+    ) -> Result<(), CanaryTrap> {
+        if module_name != ALLOCATOR_MODULE {
+            return Ok(());
+        }
+
+        match (function_name, arguments, return_value) {
+            ("roc_alloc", &[Value::I32(size), _], Some(Value::I32(ptr))) => {
+                self.allocate(ptr as u32, 0, size as u32, memory);
+            }
+            (
+                "roc_realloc",
+                &[Value::I32(old_ptr), Value::I32(new_size), Value::I32(old_size), _],
+                Some(Value::I32(new_ptr)),
+            ) => {
+                let (old_ptr, new_ptr) = (old_ptr as u32, new_ptr as u32);
+                let (old_size, new_size) = (old_size as u32, new_size as u32);
+
+                if new_ptr != old_ptr {
+                    self.live.remove(&old_ptr);
+                    self.poison(old_ptr, old_ptr.saturating_add(old_size), memory);
+                }
+                // The contents were copied, so only the bytes after them are new
+                self.allocate(new_ptr, old_size.min(new_size), new_size, memory);
+            }
+            ("roc_dealloc", &[Value::I32(ptr), _], _) => {
+                let ptr = ptr as u32;
+                match self.live.remove(&ptr) {
+                    Some(size) => self.poison(ptr, ptr.saturating_add(size), memory),
+                    None => {
+                        if let Some(&end) = self.freed.get(&ptr) {
+                            return Err(CanaryTrap::DoubleFree { freed: ptr..end });
+                        }
+                        // Allocated before canary mode was turned on, so its size isn't known
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Check a load of `size` bytes at `addr`
+    pub fn check_load(&self, addr: usize, size: usize) -> Result<(), CanaryTrap> {
+        let end = addr as u64 + size as u64;
+
+        // Only the freed allocation that starts last before the end of the load can overlap it,
+        // since they don't overlap each other
+        let last_before_end = if end > u32::MAX as u64 {
+            self.freed.range(..).next_back()
+        } else {
+            self.freed.range(..end as u32).next_back()
+        };
+
+        match last_before_end {
+            Some((&start, &freed_end)) if addr < freed_end as usize => {
+                Err(CanaryTrap::UseAfterFree {
+                    addr: addr as u32,
+                    size: size as u32,
+                    freed: start..freed_end,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record an allocation of `size` bytes at `ptr`, and zero it from `keep` bytes onwards
+    fn allocate(&mut self, ptr: u32, keep: u32, size: u32, memory: &mut [u8]) {
+        let end = ptr.saturating_add(size);
+
+        self.unpoison(ptr, end);
+        self.live.insert(ptr, size);
+
+        if let Some(bytes) = memory.get_mut(ptr.saturating_add(keep) as usize..end as usize) {
+            bytes.fill(0);
+        }
+    }
+
+    fn poison(&mut self, start: u32, end: u32, memory: &mut [u8]) {
+        if start >= end {
+            return;
+        }
+
+        self.unpoison(start, end);
+        self.freed.insert(start, end);
+
+        if let Some(bytes) = memory.get_mut(start as usize..end as usize) {
+            for (byte, canary) in bytes.iter_mut().zip(CANARY.iter().cycle()) {
+                *byte = *canary;
+            }
+        }
+    }
+
+    /// Allow loads from `start..end` again, trimming any freed allocations that overlap it
+    fn unpoison(&mut self, start: u32, end: u32) {
+        let overlapping: Vec<(u32, u32)> = self
+            .freed
+            .range(..end)
+            .rev()
+            .take_while(|(_, &freed_end)| freed_end > start)
+            .map(|(&freed_start, &freed_end)| (freed_start, freed_end))
+            .collect();
+
+        for (freed_start, freed_end) in overlapping {
+            self.freed.remove(&freed_start);
+            if freed_start < start {
+                self.freed.insert(freed_start, start);
+            }
+            if freed_end > end {
+                self.freed.insert(end, freed_end);
+            }
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use roc_wasm_module::opcodes::OpCode;
+use roc_wasm_module::parse::{Parse, ParseError, SkipBytes};
+use roc_wasm_module::sections::SectionId;
+use std::fmt;
+
+use crate::LoadError;
+
+/// A WebAssembly proposal beyond the MVP, that a module may need
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Proposal {
+    Simd,
+    RelaxedSimd,
+    ExtendedConst,
+    SignExtension,
+    SaturatingFloatToInt,
+    BulkMemory,
+    ReferenceTypes,
+    TailCall,
+    Threads,
+    Exceptions,
+}
+
+impl Proposal {
+    pub const ALL: [Proposal; 10] = [
+        Proposal::Simd,
+        Proposal::RelaxedSimd,
+        Proposal::ExtendedConst,
+        Proposal::SignExtension,
+        Proposal::SaturatingFloatToInt,
+        Proposal::BulkMemory,
+        Proposal::ReferenceTypes,
+        Proposal::TailCall,
+        Proposal::Threads,
+        Proposal::Exceptions,
+    ];
+
+    /// The proposal's name, as used by other tools like wasm-tools and wasmtime
+    pub fn name(self) -> &'static str {
+        match self {
+            Proposal::Simd => "simd",
+            Proposal::RelaxedSimd => "relaxed-simd",
+            Proposal::ExtendedConst => "extended-const",
+            Proposal::SignExtension => "sign-extension",
+            Proposal::SaturatingFloatToInt => "saturating-float-to-int",
+            Proposal::BulkMemory => "bulk-memory",
+            Proposal::ReferenceTypes => "reference-types",
+            Proposal::TailCall => "tail-call",
+            Proposal::Threads => "threads",
+            Proposal::Exceptions => "exceptions",
+        }
+    }
+}
+
+impl fmt::Display for Proposal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Which WebAssembly proposals an interpreter supports. Modules are checked against them before
+/// they're decoded, so that a module that needs something else is rejected with a
+/// [LoadError::Unsupported] saying what it needs, rather than failing part way through a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub simd: bool,
+    pub relaxed_simd: bool,
+    pub extended_const: bool,
+    pub sign_extension: bool,
+    pub saturating_float_to_int: bool,
+    pub bulk_memory: bool,
+    pub reference_types: bool,
+    pub tail_call: bool,
+    pub threads: bool,
+    pub exceptions: bool,
+}
+
+impl Capabilities {
+    /// What this build of the interpreter can run. SIMD depends on the `simd` feature.
+    pub const fn of_this_build() -> Self {
+        Capabilities {
+            simd: cfg!(feature = "simd"),
+            relaxed_simd: false,
+            extended_const: false,
+            sign_extension: false,
+            saturating_float_to_int: false,
+            bulk_memory: false,
+            reference_types: false,
+            tail_call: false,
+            threads: false,
+            exceptions: false,
+        }
+    }
+
+    pub fn supports(&self, proposal: Proposal) -> bool {
+        match proposal {
+            Proposal::Simd => self.simd,
+            Proposal::RelaxedSimd => self.relaxed_simd,
+            Proposal::ExtendedConst => self.extended_const,
+            Proposal::SignExtension => self.sign_extension,
+            Proposal::SaturatingFloatToInt => self.saturating_float_to_int,
+            Proposal::BulkMemory => self.bulk_memory,
+            Proposal::ReferenceTypes => self.reference_types,
+            Proposal::TailCall => self.tail_call,
+            Proposal::Threads => self.threads,
+            Proposal::Exceptions => self.exceptions,
+        }
+    }
+
+    /// The proposals that are supported
+    pub fn supported(&self) -> impl Iterator<Item = Proposal> + '_ {
+        Proposal::ALL
+            .into_iter()
+            .filter(|proposal| self.supports(*proposal))
+    }
+
+    /// Check that the module doesn't use any proposal that isn't supported. This only looks for
+    /// instructions and constant expressions from other proposals. Anything else that's wrong with
+    /// the module is left for the decoder to report, with its own error.
+    pub fn check_module(&self, module_bytes: &[u8]) -> Result<(), LoadError> {
+        let mut scan = Scan {
+            capabilities: self,
+            bytes: module_bytes,
+            section: SectionId::Custom,
+        };
+
+        match scan.module() {
+            Ok(()) => Ok(()),
+            Err(Stop::Unsupported(error)) => Err(error),
+            // A module that can't be scanned is invalid, and decoding it will say why
+            Err(Stop::Invalid) => Ok(()),
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::of_this_build()
+    }
+}
+
+/// Why a scan stopped before the end of the module
+enum Stop {
+    Unsupported(LoadError),
+    Invalid,
+}
+
+impl From<ParseError> for Stop {
+    fn from(_: ParseError) -> Self {
+        Stop::Invalid
+    }
+}
+
+struct Scan<'c, 'b> {
+    capabilities: &'c Capabilities,
+    bytes: &'b [u8],
+    section: SectionId,
+}
+
+impl<'c, 'b> Scan<'c, 'b> {
+    fn module(&mut self) -> Result<(), Stop> {
+        let mut cursor = 8; // skip the magic number and version
+
+        while cursor < self.bytes.len() {
+            let id = u8::parse((), self.bytes, &mut cursor)?;
+            let size = u32::parse((), self.bytes, &mut cursor)? as usize;
+            let end = cursor.saturating_add(size).min(self.bytes.len());
+
+            if let Some(section) = SectionId::from_byte(id) {
+                self.section = section;
+                match section {
+                    SectionId::Global => self.globals(cursor)?,
+                    SectionId::Element => self.elements(cursor)?,
+                    SectionId::Code => self.code(cursor)?,
+                    SectionId::Data => self.data(cursor)?,
+                    _ => {}
+                }
+            }
+
+            cursor = end;
+        }
+
+        Ok(())
+    }
+
+    fn globals(&self, mut cursor: usize) -> Result<(), Stop> {
+        let count = u32::parse((), self.bytes, &mut cursor)?;
+        for _ in 0..count {
+            cursor += 2; // value type and mutability
+            self.const_expr(&mut cursor)?;
+        }
+        Ok(())
+    }
+
+    fn elements(&self, mut cursor: usize) -> Result<(), Stop> {
+        let count = u32::parse((), self.bytes, &mut cursor)?;
+        for _ in 0..count {
+            // Other formats are left to the decoder, which only supports the MVP one
+            if u8::parse((), self.bytes, &mut cursor)? != 0 {
+                break;
+            }
+            self.const_expr(&mut cursor)?;
+            let n_elems = u32::parse((), self.bytes, &mut cursor)?;
+            for _ in 0..n_elems {
+                u32::skip_bytes(self.bytes, &mut cursor)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn data(&self, mut cursor: usize) -> Result<(), Stop> {
+        let count = u32::parse((), self.bytes, &mut cursor)?;
+        for _ in 0..count {
+            match u32::parse((), self.bytes, &mut cursor)? {
+                0 => self.const_expr(&mut cursor)?,
+                1 => {} // passive
+                2 => {
+                    u32::skip_bytes(self.bytes, &mut cursor)?; // memory index
+                    self.const_expr(&mut cursor)?;
+                }
+                _ => break,
+            }
+            let len = u32::parse((), self.bytes, &mut cursor)?;
+            cursor += len as usize;
+        }
+        Ok(())
+    }
+
+    fn code(&self, mut cursor: usize) -> Result<(), Stop> {
+        let count = u32::parse((), self.bytes, &mut cursor)?;
+        for _ in 0..count {
+            let size = u32::parse((), self.bytes, &mut cursor)? as usize;
+            let body_end = cursor.saturating_add(size).min(self.bytes.len());
+
+            let local_groups = u32::parse((), self.bytes, &mut cursor)?;
+            for _ in 0..local_groups {
+                u32::skip_bytes(self.bytes, &mut cursor)?;
+                cursor += 1; // value type
+            }
+
+            while cursor < body_end {
+                let proposal = instruction_proposal(self.bytes, cursor)?;
+                self.require(proposal, cursor)?;
+                OpCode::skip_bytes(self.bytes, &mut cursor)?;
+            }
+
+            cursor = body_end;
+        }
+        Ok(())
+    }
+
+    /// Check a constant expression, leaving the cursor after its `end`
+    fn const_expr(&self, cursor: &mut usize) -> Result<(), Stop> {
+        loop {
+            let opcode_byte = peek_u8(self.bytes, *cursor)?;
+            if opcode_byte == OpCode::END as u8 {
+                *cursor += 1;
+                return Ok(());
+            }
+
+            let proposal = match OpCode::from_byte(opcode_byte) {
+                Some(
+                    OpCode::I32ADD
+                    | OpCode::I32SUB
+                    | OpCode::I32MUL
+                    | OpCode::I64ADD
+                    | OpCode::I64SUB
+                    | OpCode::I64MUL,
+                ) => Some(Proposal::ExtendedConst),
+                _ => instruction_proposal(self.bytes, *cursor)?,
+            };
+            self.require(proposal, *cursor)?;
+            OpCode::skip_bytes(self.bytes, cursor)?;
+        }
+    }
+
+    /// Stop if the instruction at `offset` needs a proposal that isn't supported
+    fn require(&self, proposal: Option<Proposal>, offset: usize) -> Result<(), Stop> {
+        match proposal {
+            Some(proposal) if !self.capabilities.supports(proposal) => {
+                Err(Stop::Unsupported(LoadError::Unsupported {
+                    proposal,
+                    section: self.section,
+                    offset,
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The proposal that the instruction at `cursor` comes from, or `None` if it's from the MVP
+fn instruction_proposal(bytes: &[u8], cursor: usize) -> Result<Option<Proposal>, ParseError> {
+    let proposal = match peek_u8(bytes, cursor)? {
+        0x06..=0x0a | 0x18 | 0x19 | 0x1f => Some(Proposal::Exceptions),
+        0x12 | 0x13 => Some(Proposal::TailCall),
+        0x1c | 0x25 | 0x26 | 0xd0..=0xd2 => Some(Proposal::ReferenceTypes),
+        0xc0..=0xc4 => Some(Proposal::SignExtension),
+        0xfc => {
+            let mut op_cursor = cursor + 1;
+            match u32::parse((), bytes, &mut op_cursor)? {
+                0x00..=0x07 => Some(Proposal::SaturatingFloatToInt),
+                0x08..=0x0e => Some(Proposal::BulkMemory),
+                _ => Some(Proposal::ReferenceTypes),
+            }
+        }
+        0xfd => {
+            let mut op_cursor = cursor + 1;
+            match u32::parse((), bytes, &mut op_cursor)? {
+                0x100..=0x113 => Some(Proposal::RelaxedSimd),
+                _ => Some(Proposal::Simd),
+            }
+        }
+        0xfe => Some(Proposal::Threads),
+        _ => None,
+    };
+    Ok(proposal)
+}
+
+fn peek_u8(bytes: &[u8], cursor: usize) -> Result<u8, ParseError> {
+    u8::parse((), bytes, &mut { cursor })
+}
@@ -0,0 +1,103 @@
+//! Combine several import dispatchers into one, e.g. WASI, the host's own `env` functions,
+//! and test doubles for the rest. Each import goes to the first dispatcher that provides it.
+use crate::{ImportDispatcher, Memory};
+use roc_wasm_module::{GlobalType, Value};
+
+/// What to do with an imported function that the import dispatcher doesn't provide
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingImports {
+    /// Instantiate the module anyway, and trap if the function is ever called
+    #[default]
+    Trap,
+    /// Refuse to instantiate the module
+    Error,
+}
+
+/// Tries each of its dispatchers in order, passing every import to the first one that provides it.
+/// Imported globals come from the first dispatcher that has a value for them.
+#[derive(Default)]
+pub struct ChainDispatcher<'a> {
+    dispatchers: Vec<Box<dyn ImportDispatcher + 'a>>,
+    missing_imports: MissingImports,
+}
+
+impl<'a> ChainDispatcher<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a dispatcher to the end of the chain, to be tried after the ones already in it
+    pub fn with<D: ImportDispatcher + 'a>(mut self, dispatcher: D) -> Self {
+        self.push(dispatcher);
+        self
+    }
+
+    pub fn push<D: ImportDispatcher + 'a>(&mut self, dispatcher: D) {
+        self.dispatchers.push(Box::new(dispatcher));
+    }
+
+    /// Choose what happens to imported functions that none of the dispatchers provide
+    pub fn on_missing(mut self, missing_imports: MissingImports) -> Self {
+        self.missing_imports = missing_imports;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.dispatchers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dispatchers.is_empty()
+    }
+}
+
+impl<'a> ImportDispatcher for ChainDispatcher<'a> {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut Memory<'_>,
+    ) -> Option<Value> {
+        match self
+            .dispatchers
+            .iter_mut()
+            .find(|d| d.provides(module_name, function_name))
+        {
+            Some(dispatcher) => dispatcher.dispatch(module_name, function_name, arguments, memory),
+            None => panic!(
+                "None of the dispatchers in the chain provide {}.{}",
+                module_name, function_name
+            ),
+        }
+    }
+
+    fn global(
+        &mut self,
+        module_name: &str,
+        global_name: &str,
+        global_type: GlobalType,
+    ) -> Option<Value> {
+        self.dispatchers
+            .iter_mut()
+            .find_map(|d| d.global(module_name, global_name, global_type))
+    }
+
+    fn provides(&self, module_name: &str, function_name: &str) -> bool {
+        self.dispatchers
+            .iter()
+            .any(|d| d.provides(module_name, function_name))
+    }
+
+    fn missing_imports(&self) -> MissingImports {
+        self.missing_imports
+    }
+
+    fn exit_status(&self) -> Option<i32> {
+        self.dispatchers.iter().find_map(|d| d.exit_status())
+    }
+
+    fn host_error(&self) -> Option<&str> {
+        self.dispatchers.iter().find_map(|d| d.host_error())
+    }
+}
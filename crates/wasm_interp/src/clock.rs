@@ -0,0 +1,128 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The clocks a WASI program can read, by their number in `clock_time_get` and `clock_res_get`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockId {
+    /// Wall-clock time, in nanoseconds since the Unix epoch
+    Realtime,
+    /// Time that only moves forward, from an arbitrary starting point
+    Monotonic,
+    ProcessCpuTime,
+    ThreadCpuTime,
+}
+
+impl ClockId {
+    pub fn from_u32(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(ClockId::Realtime),
+            1 => Some(ClockId::Monotonic),
+            2 => Some(ClockId::ProcessCpuTime),
+            3 => Some(ClockId::ThreadCpuTime),
+            _ => None,
+        }
+    }
+}
+
+/// Where a [WasiDispatcher](crate::WasiDispatcher) gets the time from.
+/// Embedders can supply their own, like a [ManualClock] for deterministic tests of code that
+/// depends on the time. Each dispatcher has its own clock, so instances don't affect each other.
+pub trait Clock {
+    /// The time on this clock in nanoseconds, or `None` if the clock isn't supported
+    fn time(&mut self, clock_id: ClockId) -> Option<u64>;
+
+    /// The resolution of this clock in nanoseconds, or `None` if the clock isn't supported
+    fn resolution(&self, clock_id: ClockId) -> Option<u64>;
+}
+
+/// The host's real time. This is the default clock.
+/// The monotonic clock starts at zero when this is created. CPU time clocks aren't supported.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn time(&mut self, clock_id: ClockId) -> Option<u64> {
+        match clock_id {
+            ClockId::Realtime => {
+                // A host clock set before 1970 reads as the epoch, rather than failing
+                let since_epoch = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                Some(nanos(since_epoch))
+            }
+            ClockId::Monotonic => Some(nanos(self.start.elapsed())),
+            ClockId::ProcessCpuTime | ClockId::ThreadCpuTime => None,
+        }
+    }
+
+    fn resolution(&self, clock_id: ClockId) -> Option<u64> {
+        match clock_id {
+            ClockId::Realtime | ClockId::Monotonic => Some(1),
+            ClockId::ProcessCpuTime | ClockId::ThreadCpuTime => None,
+        }
+    }
+}
+
+/// Synthetic time that only changes when it's told to, for deterministic tests.
+/// Clones share the same time, so keep one to [advance](ManualClock::advance) while the
+/// dispatcher has another. CPU time clocks read the same as the monotonic clock.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    realtime: Rc<Cell<u64>>,
+    monotonic: Rc<Cell<u64>>,
+}
+
+impl ManualClock {
+    /// A clock whose wall-clock time starts at this many nanoseconds since the Unix epoch.
+    /// The monotonic clock starts at zero.
+    pub fn new(realtime: u64) -> Self {
+        ManualClock {
+            realtime: Rc::new(Cell::new(realtime)),
+            monotonic: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Move both clocks forward
+    pub fn advance(&self, duration: Duration) {
+        let step = nanos(duration);
+        self.realtime.set(self.realtime.get().saturating_add(step));
+        self.monotonic
+            .set(self.monotonic.get().saturating_add(step));
+    }
+
+    /// Set the wall-clock time, in nanoseconds since the Unix epoch. Like the host's clock, this
+    /// can go backwards. The monotonic clock isn't affected.
+    pub fn set_realtime(&self, realtime: u64) {
+        self.realtime.set(realtime);
+    }
+}
+
+impl Clock for ManualClock {
+    fn time(&mut self, clock_id: ClockId) -> Option<u64> {
+        match clock_id {
+            ClockId::Realtime => Some(self.realtime.get()),
+            ClockId::Monotonic | ClockId::ProcessCpuTime | ClockId::ThreadCpuTime => {
+                Some(self.monotonic.get())
+            }
+        }
+    }
+
+    fn resolution(&self, _clock_id: ClockId) -> Option<u64> {
+        Some(1)
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
@@ -0,0 +1,196 @@
+use roc_wasm_module::opcodes::OpCode;
+use roc_wasm_module::parse::{Parse, ParseError, SkipBytes};
+use roc_wasm_module::{ValueType, WasmModule};
+use std::cell::{Cell, RefCell};
+
+use crate::Error;
+
+/// Where each `block` and `if` in a module's code ends, so that branches can jump straight there.
+/// A function's body is decoded the first time a branch needs it, and the result is kept for
+/// every [Instance](crate::Instance) that shares the table, so instantiating the same module
+/// again doesn't repeat the work.
+#[derive(Debug)]
+pub struct ControlTable {
+    /// The blocks of each function decoded so far. A function's blocks are together, in the
+    /// order they appear in the code, so they're sorted by address.
+    blocks: RefCell<Vec<BlockTargets>>,
+    /// Range of each function's blocks, once it has been decoded. Indexed like the Code section.
+    functions: Vec<Cell<Option<(u32, u32)>>>,
+    /// Where each function starts in the Code section, and its size, to tell whether the table is
+    /// for a module. Block addresses are only meaningful in the module they came from.
+    function_offsets: Vec<u32>,
+    code_size: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlockTargets {
+    /// Address of the block's first instruction, just after its block type
+    start: u32,
+    /// Address just after the `else`, for an `if` that has one
+    pub else_addr: Option<u32>,
+    /// Address just after the block's `end`
+    pub end: u32,
+    /// Number of values the block results in, which a branch out of it keeps
+    pub arity: usize,
+}
+
+impl ControlTable {
+    /// An empty table for the module. Nothing is decoded until it's needed.
+    pub fn new(module: &WasmModule) -> Self {
+        let function_count = module.code.function_offsets.len();
+        ControlTable {
+            blocks: RefCell::new(Vec::new()),
+            functions: vec![Cell::new(None); function_count],
+            function_offsets: module.code.function_offsets.to_vec(),
+            code_size: module.code.bytes.len(),
+        }
+    }
+
+    /// Whether the table was made for this module, or another one with the same layout of code
+    pub fn is_for(&self, module: &WasmModule) -> bool {
+        self.code_size == module.code.bytes.len()
+            && self.function_offsets[..] == module.code.function_offsets[..]
+    }
+
+    /// The targets of the block whose first instruction is at `start`, in the function with
+    /// this index in the Code section (not counting imports)
+    pub(crate) fn get(
+        &self,
+        module: &WasmModule,
+        internal_fn_index: usize,
+        start: usize,
+    ) -> Result<BlockTargets, Error> {
+        let (from, to) = match self.functions[internal_fn_index].get() {
+            Some(range) => range,
+            None => {
+                let range = self.decode(module, internal_fn_index)?;
+                self.functions[internal_fn_index].set(Some(range));
+                range
+            }
+        };
+
+        let blocks = self.blocks.borrow();
+        let fn_blocks = &blocks[from as usize..to as usize];
+        match fn_blocks.binary_search_by_key(&(start as u32), |block| block.start) {
+            Ok(index) => Ok(fn_blocks[index]),
+            Err(_) => Err(Error::InvalidCode {
+                offset: start + module.code.section_offset as usize,
+                message: format!(
+                    "There's no block starting here in function {}, so the control table must be for a different module",
+                    internal_fn_index
+                ),
+            }),
+        }
+    }
+
+    /// Add a function's blocks to the table, returning their range
+    fn decode(&self, module: &WasmModule, internal_fn_index: usize) -> Result<(u32, u32), Error> {
+        let mut blocks = self.blocks.borrow_mut();
+        let from = blocks.len();
+        let fn_offset = module.code.function_offsets[internal_fn_index] as usize;
+
+        match decode_function(module, fn_offset, &mut blocks) {
+            Ok(()) => Ok((from as u32, blocks.len() as u32)),
+            Err(e) => {
+                blocks.truncate(from);
+                Err(Error::InvalidCode {
+                    offset: e.offset + module.code.section_offset as usize,
+                    message: e.message,
+                })
+            }
+        }
+    }
+}
+
+/// Add the `block`s and `if`s in a function body to `blocks`
+fn decode_function(
+    module: &WasmModule,
+    fn_offset: usize,
+    blocks: &mut Vec<BlockTargets>,
+) -> Result<(), ParseError> {
+    use OpCode::*;
+
+    let bytes = &module.code.bytes;
+    let mut cursor = fn_offset;
+    let size = u32::parse((), bytes, &mut cursor)? as usize;
+    let fn_end = (cursor + size).min(bytes.len());
+
+    let local_groups = u32::parse((), bytes, &mut cursor)?;
+    for _ in 0..local_groups {
+        <(u32, ValueType)>::parse((), bytes, &mut cursor)?;
+    }
+
+    // Index in `blocks` of each enclosing block, or None for loops and the function body,
+    // which are never branched forward out of
+    let mut open_blocks = Vec::with_capacity(8);
+    open_blocks.push(None);
+
+    while !open_blocks.is_empty() {
+        if cursor >= fn_end {
+            return Err(ParseError {
+                offset: fn_offset,
+                message: "This function body has no `end`".into(),
+            });
+        }
+
+        let op_addr = cursor;
+        OpCode::skip_bytes(bytes, &mut cursor)?;
+        if cursor > fn_end {
+            return Err(ParseError {
+                offset: op_addr,
+                message: "This instruction goes past the end of the function body".into(),
+            });
+        }
+
+        match OpCode::from(bytes[op_addr]) {
+            BLOCK | IF => {
+                let arity = block_arity(module, bytes[op_addr + 1]).ok_or_else(|| ParseError {
+                    offset: op_addr + 1,
+                    message: format!("Invalid block type 0x{:02x}", bytes[op_addr + 1]),
+                })?;
+                open_blocks.push(Some(blocks.len()));
+                blocks.push(BlockTargets {
+                    start: cursor as u32,
+                    else_addr: None,
+                    end: 0,
+                    arity,
+                });
+            }
+            LOOP => {
+                open_blocks.push(None);
+            }
+            ELSE => match open_blocks.last() {
+                Some(Some(index)) => blocks[*index].else_addr = Some(cursor as u32),
+                _ => {
+                    return Err(ParseError {
+                        offset: op_addr,
+                        message: "Found `else` outside of an `if`".into(),
+                    })
+                }
+            },
+            END => {
+                if let Some(Some(index)) = open_blocks.pop() {
+                    blocks[index].end = cursor as u32;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of results for a block type: none, a value type, or the index of a signature.
+/// Only signature indices that fit in one byte are supported, like in [OpCode::skip_bytes].
+fn block_arity(module: &WasmModule, block_type: u8) -> Option<usize> {
+    if block_type == ValueType::VOID {
+        Some(0)
+    } else if ValueType::from_byte(block_type).is_some() {
+        Some(1)
+    } else if block_type < 0x40 && (block_type as usize) < module.types.len() {
+        let (_, result) = module.types.look_up(block_type as u32);
+        Some(usize::from(result.is_some()))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,243 @@
+use bitvec::vec::BitVec;
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::opcodes::OpCode;
+use roc_wasm_module::parse::{Parse, ParseError, SkipBytes};
+use roc_wasm_module::{ValueType, WasmModule};
+use std::fmt::{self, Write};
+use std::iter;
+use std::ops::Range;
+
+use crate::profile::function_name;
+
+/// Which instructions of the module have been executed, and how many times each function was
+/// called
+#[derive(Debug)]
+pub(crate) struct Coverage<'a> {
+    /// One bit per byte of the Code section, set for the first byte of each executed instruction
+    executed: BitVec,
+    /// Indexed by function index (imports first)
+    calls: Vec<'a, u64>,
+}
+
+impl<'a> Coverage<'a> {
+    pub fn new(arena: &'a Bump, module: &WasmModule<'a>) -> Self {
+        let num_functions = module.import.function_count() + module.code.function_count as usize;
+        Coverage {
+            executed: BitVec::repeat(false, module.code.bytes.len()),
+            calls: Vec::from_iter_in(iter::repeat(0).take(num_functions), arena),
+        }
+    }
+
+    pub fn count_call(&mut self, fn_index: usize) {
+        self.calls[fn_index] += 1;
+    }
+
+    /// Mark the instruction at this address in the Code section as executed
+    pub fn mark_instruction(&mut self, program_counter: usize) {
+        self.executed.set(program_counter, true);
+    }
+
+    pub fn reset(&mut self) {
+        self.executed.fill(false);
+        for calls in self.calls.iter_mut() {
+            *calls = 0;
+        }
+    }
+
+    pub fn report(&self, module: &WasmModule<'a>) -> CoverageReport {
+        let import_count = module.import.function_count();
+        let section_offset = module.code.section_offset;
+
+        let functions = module
+            .code
+            .function_offsets
+            .iter()
+            .enumerate()
+            .map(|(internal_index, fn_offset)| {
+                let fn_index = import_count + internal_index;
+                let (instruction_offsets, end) =
+                    instruction_offsets(module, *fn_offset as usize).unwrap_or_default();
+
+                FunctionCoverage {
+                    fn_index: fn_index as u32,
+                    name: function_name(module, fn_index, import_count),
+                    calls: self.calls[fn_index],
+                    instructions: instruction_offsets
+                        .into_iter()
+                        .map(|offset| (offset as u32 + section_offset, self.executed[offset]))
+                        .collect(),
+                    end: end as u32 + section_offset,
+                }
+            })
+            .collect();
+
+        CoverageReport { functions }
+    }
+}
+
+/// The address of each instruction in a function body, and the address just after the body.
+/// An invalid body is listed up to the first instruction that can't be decoded.
+fn instruction_offsets(
+    module: &WasmModule,
+    fn_offset: usize,
+) -> Result<(std::vec::Vec<usize>, usize), ParseError> {
+    let bytes = &module.code.bytes;
+    let mut cursor = fn_offset;
+    let size = u32::parse((), bytes, &mut cursor)? as usize;
+    let fn_end = (cursor + size).min(bytes.len());
+
+    let local_groups = u32::parse((), bytes, &mut cursor)?;
+    for _ in 0..local_groups {
+        <(u32, ValueType)>::parse((), bytes, &mut cursor)?;
+    }
+
+    let mut offsets = std::vec::Vec::new();
+    while cursor < fn_end {
+        let op_addr = cursor;
+        if OpCode::skip_bytes(bytes, &mut cursor).is_err() || cursor > fn_end {
+            return Ok((offsets, op_addr));
+        }
+        offsets.push(op_addr);
+    }
+
+    Ok((offsets, fn_end))
+}
+
+/// Coverage of one function defined in the module, as reported by `Instance::coverage_report`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    pub fn_index: u32,
+    pub name: String,
+    /// Number of times the function was called
+    pub calls: u64,
+    /// The file offset of each instruction in the function's body, and whether it was executed
+    pub instructions: std::vec::Vec<(u32, bool)>,
+    /// The file offset just after the function's body
+    pub end: u32,
+}
+
+impl FunctionCoverage {
+    pub fn executed_count(&self) -> usize {
+        self.instructions
+            .iter()
+            .filter(|(_, executed)| *executed)
+            .count()
+    }
+
+    /// The byte ranges of the file whose instructions were all executed, with neighbouring
+    /// instructions merged into one range
+    pub fn executed_ranges(&self) -> std::vec::Vec<Range<u32>> {
+        let mut ranges: std::vec::Vec<Range<u32>> = std::vec::Vec::new();
+
+        for (index, (offset, executed)) in self.instructions.iter().enumerate() {
+            if !executed {
+                continue;
+            }
+            let end = match self.instructions.get(index + 1) {
+                Some((next_offset, _)) => *next_offset,
+                None => self.end,
+            };
+            match ranges.last_mut() {
+                Some(range) if range.end == *offset => range.end = end,
+                _ => ranges.push(*offset..end),
+            }
+        }
+
+        ranges
+    }
+}
+
+/// Coverage of every function defined in the module, in the order of the Code section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub functions: std::vec::Vec<FunctionCoverage>,
+}
+
+impl CoverageReport {
+    pub fn get(&self, name: &str) -> Option<&FunctionCoverage> {
+        self.functions.iter().find(|entry| entry.name == name)
+    }
+
+    /// The report in the lcov tracefile format, for tools like genhtml and CI coverage services.
+    /// Wasm has no lines, so each instruction's file offset stands in for a line number.
+    pub fn to_lcov(&self, source_file: &str) -> String {
+        let mut lcov = String::new();
+
+        writeln!(lcov, "TN:").unwrap();
+        writeln!(lcov, "SF:{}", source_file).unwrap();
+
+        for function in self.functions.iter() {
+            if let Some((offset, _)) = function.instructions.first() {
+                writeln!(lcov, "FN:{},{}", offset, function.name).unwrap();
+            }
+        }
+        for function in self.functions.iter() {
+            if !function.instructions.is_empty() {
+                writeln!(lcov, "FNDA:{},{}", function.calls, function.name).unwrap();
+            }
+        }
+
+        let has_instructions = self.functions.iter().filter(|f| !f.instructions.is_empty());
+        let functions_hit = has_instructions.clone().filter(|f| f.calls > 0).count();
+        writeln!(lcov, "FNF:{}", has_instructions.count()).unwrap();
+        writeln!(lcov, "FNH:{}", functions_hit).unwrap();
+
+        let mut instructions_found = 0;
+        let mut instructions_hit = 0;
+        for function in self.functions.iter() {
+            for (offset, executed) in function.instructions.iter() {
+                writeln!(lcov, "DA:{},{}", offset, u8::from(*executed)).unwrap();
+                instructions_found += 1;
+                instructions_hit += usize::from(*executed);
+            }
+        }
+        writeln!(lcov, "LF:{}", instructions_found).unwrap();
+        writeln!(lcov, "LH:{}", instructions_hit).unwrap();
+        writeln!(lcov, "end_of_record").unwrap();
+
+        lcov
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>14} {:>10}  {:>6}  name",
+            "covered", "instructions", "calls", "index"
+        )?;
+
+        let mut total = 0;
+        let mut total_executed = 0;
+        for function in self.functions.iter() {
+            let executed = function.executed_count();
+            total += function.instructions.len();
+            total_executed += executed;
+
+            writeln!(
+                f,
+                "{:>8} {:>14} {:>10}  {:>6}  {}",
+                percentage(executed, function.instructions.len()),
+                format!("{}/{}", executed, function.instructions.len()),
+                function.calls,
+                function.fn_index,
+                function.name
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{:>8} {:>14}              total",
+            percentage(total_executed, total),
+            format!("{}/{}", total_executed, total),
+        )
+    }
+}
+
+fn percentage(part: usize, whole: usize) -> String {
+    if whole == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", 100.0 * part as f64 / whole as f64)
+    }
+}
@@ -0,0 +1,717 @@
+//! Link several WebAssembly modules together, resolving each module's imports against the exports
+//! of the modules that were instantiated before it.
+//!
+//! Every module keeps its own [Instance]. A call to a function imported from another module runs in
+//! that module's instance. A memory shared between modules is handed over to whichever instance is
+//! running, and imported globals are copied in and out around calls between instances.
+//! Imported functions from modules outside the group go to the group's [ImportDispatcher].
+use bumpalo::Bump;
+use roc_wasm_module::sections::{ImportDesc, Limits};
+use roc_wasm_module::{ConstExpr, ExportType, Value, ValueType, WasmModule};
+use std::collections::BTreeMap;
+
+use crate::capabilities::Capabilities;
+use crate::instance::{table_lookup, table_size, Action, Instance};
+use crate::{CallError, Error, FunctionType, ImportDispatcher, LoadError, Memory, MissingImports};
+
+/// Stand-in dispatcher for linked instances, whose import calls are handled by the group
+#[derive(Debug)]
+pub(crate) struct Deferred;
+
+impl ImportDispatcher for Deferred {
+    fn dispatch(&mut self, _: &str, _: &str, _: &[Value], _: &mut Memory<'_>) -> Option<Value> {
+        unreachable!("Imports of a linked module are resolved by its InstanceGroup")
+    }
+}
+
+/// What a function index resolves to, once imports are followed through to the end
+#[derive(Debug, Clone, Copy)]
+enum FunctionLink<'a> {
+    /// A Wasm function defined in one of the group's instances
+    Wasm { member: usize, fn_index: usize },
+    /// A function provided by the group's import dispatcher
+    Host {
+        module_name: &'a str,
+        function_name: &'a str,
+    },
+}
+
+#[derive(Debug)]
+struct Member<'a> {
+    name: String,
+    instance: Instance<'a, Deferred>,
+    /// What each imported function resolves to
+    functions: Vec<FunctionLink<'a>>,
+    /// The member whose memory this one uses. Itself, unless the memory is imported.
+    memory_owner: usize,
+    /// The member whose function table this one uses. Itself, unless the table is imported.
+    table_owner: usize,
+    /// Elements that the element segments of other modules wrote into this member's table, as
+    /// the member and index of each function. They replace any from the member's own segments.
+    table_elements: BTreeMap<u32, (usize, u32)>,
+    /// The member and global index that each imported global refers to
+    globals: Vec<(usize, usize)>,
+}
+
+/// A set of WebAssembly modules linked together by name.
+///
+/// Modules are added with [instantiate](InstanceGroup::instantiate), and can import functions,
+/// memories, globals and tables from any module added before them, using its name as the import's
+/// module name. Calls to other imported functions go to the import dispatcher.
+///
+/// Each module is initialized in the order the spec gives: its imports are resolved, its globals
+/// get their initial values, its element segments are written to its table and its data segments
+/// to its memory, and then its start function runs, if it has one.
+#[derive(Debug)]
+pub struct InstanceGroup<'a, I: ImportDispatcher> {
+    arena: &'a Bump,
+    members: Vec<Member<'a>>,
+    /// For each member that owns a memory, the member whose instance currently holds its bytes
+    memory_holders: Vec<usize>,
+    /// Import dispatcher from user code, for imports that are not resolved within the group
+    pub import_dispatcher: I,
+    is_debug_mode: bool,
+}
+
+impl<'a, I: ImportDispatcher> InstanceGroup<'a, I> {
+    pub fn new(arena: &'a Bump, import_dispatcher: I, is_debug_mode: bool) -> Self {
+        InstanceGroup {
+            arena,
+            members: Vec::new(),
+            memory_holders: Vec::new(),
+            import_dispatcher,
+            is_debug_mode,
+        }
+    }
+
+    pub fn instantiate_bytes(&mut self, name: &str, module_bytes: &[u8]) -> Result<(), LoadError> {
+        Capabilities::of_this_build().check_module(module_bytes)?;
+        let module = WasmModule::preload(self.arena, module_bytes, false)
+            .map_err(|e| LoadError::invalid_module(module_bytes, e))?;
+        self.instantiate(name, self.arena.alloc(module))
+            .map_err(LoadError::Trap)
+    }
+
+    /// Add a module to the group under `name`, linking its imports to the modules already in it.
+    pub fn instantiate(&mut self, name: &str, module: &'a WasmModule<'a>) -> Result<(), String> {
+        if self.find_member(name).is_some() {
+            return Err(format!(
+                "There is already a module named `{}` in this group",
+                name
+            ));
+        }
+
+        let index = self.members.len();
+        let mut functions = Vec::new();
+        let mut globals = Vec::new();
+        let mut imported_globals = Vec::new();
+        let mut memory_owner = index;
+        let mut table_owner = index;
+
+        for import in module.import.imports.iter() {
+            let provider = match self.find_member(import.module) {
+                Some(provider) => provider,
+                None => {
+                    if import.is_function() {
+                        let is_missing = self.import_dispatcher.missing_imports()
+                            == MissingImports::Error
+                            && !self.import_dispatcher.provides(import.module, import.name);
+                        if is_missing {
+                            return Err(format!(
+                                "`{}` imports {}.{}, which is not in the group, and the import dispatcher doesn't provide it",
+                                name, import.module, import.name
+                            ));
+                        }
+                        functions.push(FunctionLink::Host {
+                            module_name: import.module,
+                            function_name: import.name,
+                        });
+                        continue;
+                    }
+                    return Err(format!(
+                        "`{}` imports {}.{}, which is not a function, so it must come from a module in the group",
+                        name, import.module, import.name
+                    ));
+                }
+            };
+
+            match &import.description {
+                ImportDesc::Func { signature_index } => {
+                    let export_index = self.find_export(provider, import.name, ExportType::Func)?;
+                    let link = self.function_link(provider, export_index);
+                    if let FunctionLink::Wasm { member, fn_index } = link {
+                        let expected = FunctionType::look_up(module, *signature_index);
+                        if self.function_signature(member, fn_index) != expected {
+                            return Err(format!(
+                                "`{}` imports {}.{} with a different signature from the one it's exported with",
+                                name, import.module, import.name
+                            ));
+                        }
+                    }
+                    functions.push(link);
+                }
+                ImportDesc::Mem { limits } => {
+                    self.find_export(provider, import.name, ExportType::Mem)?;
+                    memory_owner = self.members[provider].memory_owner;
+                    let min_pages = match limits {
+                        Limits::Min(pages) | Limits::MinMax(pages, _) => *pages,
+                    };
+                    let holder = self.memory_holders[memory_owner];
+                    let pages = self.members[holder].instance.memory_pages();
+                    if pages < min_pages {
+                        return Err(format!(
+                            "`{}` needs at least {} pages of memory, but {}.{} only has {}",
+                            name, min_pages, import.module, import.name, pages
+                        ));
+                    }
+                }
+                ImportDesc::Global { ty } => {
+                    let export_index =
+                        self.find_export(provider, import.name, ExportType::Global)?;
+                    let (owner, owner_index) =
+                        match self.members[provider].globals.get(export_index) {
+                            Some(imported) => *imported,
+                            None => (provider, export_index),
+                        };
+                    let owner_instance = &self.members[owner].instance;
+                    let value = owner_instance.globals[owner_index];
+                    if ValueType::from(value) != ty.value_type {
+                        return Err(format!(
+                            "`{}` imports {}.{} as {:?}, but it is {:?}",
+                            name,
+                            import.module,
+                            import.name,
+                            ty.value_type,
+                            ValueType::from(value)
+                        ));
+                    }
+                    if owner_instance.global_types[owner_index].is_mutable != ty.is_mutable {
+                        return Err(format!(
+                            "`{}` imports {}.{} as {}, but it is {}",
+                            name,
+                            import.module,
+                            import.name,
+                            mutability(ty.is_mutable),
+                            mutability(!ty.is_mutable)
+                        ));
+                    }
+                    imported_globals.push(value);
+                    globals.push((owner, owner_index));
+                }
+                ImportDesc::Table { .. } => {
+                    self.find_export(provider, import.name, ExportType::Table)?;
+                    table_owner = self.members[provider].table_owner;
+                }
+            }
+        }
+
+        let instance = Instance::for_linked_module(
+            self.arena,
+            module,
+            &imported_globals,
+            Deferred,
+            self.is_debug_mode,
+        )?;
+        let start_function = instance.start_function()?;
+
+        // An imported table was created by another module, but still needs this module's elements
+        if table_owner != index {
+            self.write_elements(name, index, module, table_owner)?;
+        }
+
+        // Likewise for an imported memory and this module's data
+        if memory_owner != index {
+            let holder = self.memory_holders[memory_owner];
+            let loaded = module
+                .data
+                .load_into(&mut self.members[holder].instance.memory);
+            if let Err(message) = loaded {
+                self.forget_elements(index, table_owner);
+                return Err(message);
+            }
+        }
+
+        self.members.push(Member {
+            name: name.to_string(),
+            instance,
+            functions,
+            memory_owner,
+            table_owner,
+            table_elements: BTreeMap::new(),
+            globals,
+        });
+        self.memory_holders.push(index);
+
+        if let Some(fn_index) = start_function {
+            if let Err(message) = self.call_function(index, fn_index, &[]) {
+                // The module is left out of the group, but what its start function did to
+                // shared memories and globals stays, as it would in any other runtime
+                self.acquire_memory(memory_owner);
+                self.forget_elements(index, table_owner);
+                self.members.pop();
+                self.memory_holders.pop();
+                return Err(format!(
+                    "The start function of `{}` failed:\n{}",
+                    name, message
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the element segments of a module that's being instantiated into the table it imports.
+    /// They are all checked before any are written, so nothing is written if one doesn't fit.
+    fn write_elements(
+        &mut self,
+        name: &str,
+        index: usize,
+        module: &WasmModule<'a>,
+        table_owner: usize,
+    ) -> Result<(), String> {
+        // Element segments are parsed with constant offsets. Skip empty ones, like the placeholder
+        // for a module without an Element section.
+        let segments: Vec<(u32, &[u32])> = module
+            .element
+            .segments
+            .iter()
+            .filter_map(|segment| match segment.offset {
+                ConstExpr::I32(offset) if !segment.fn_indices.is_empty() => {
+                    Some((offset as u32, &segment.fn_indices[..]))
+                }
+                _ => None,
+            })
+            .collect();
+        if segments.is_empty() {
+            return Ok(());
+        }
+
+        let size = table_size(self.members[table_owner].instance.module);
+        for (offset, fn_indices) in segments.iter() {
+            let end = *offset as u64 + fn_indices.len() as u64;
+            if end > size as u64 {
+                return Err(format!(
+                    "`{}` has an element segment for elements {}..{} of its imported table, but the table only has {} elements",
+                    name, offset, end, size
+                ));
+            }
+        }
+
+        let owner = &mut self.members[table_owner];
+        for (offset, fn_indices) in segments {
+            for (i, fn_index) in fn_indices.iter().enumerate() {
+                owner
+                    .table_elements
+                    .insert(offset + i as u32, (index, *fn_index));
+            }
+        }
+        // The owner's own indirect calls now need to see these elements too
+        owner.instance.shared_table = true;
+
+        Ok(())
+    }
+
+    /// Remove the elements that a module wrote into another module's table, when it fails to
+    /// instantiate. They would refer to a module that isn't in the group.
+    fn forget_elements(&mut self, index: usize, table_owner: usize) {
+        if table_owner != index {
+            self.members[table_owner]
+                .table_elements
+                .retain(|_, (member, _)| *member != index);
+        }
+    }
+
+    /// Call a function exported by one of the modules in the group
+    pub fn call_export<A>(
+        &mut self,
+        module_name: &str,
+        fn_name: &str,
+        arg_values: A,
+    ) -> Result<Option<Value>, CallError>
+    where
+        A: IntoIterator<Item = Value>,
+    {
+        let member = self.find_member(module_name).ok_or_else(|| {
+            CallError::InvalidCall(format!(
+                "There is no module named `{}` in this group",
+                module_name
+            ))
+        })?;
+        let export_index = self
+            .find_export(member, fn_name, ExportType::Func)
+            .map_err(CallError::InvalidCall)?;
+        let args: Vec<Value> = arg_values.into_iter().collect();
+
+        self.call_function(member, export_index, &args)
+            .map_err(CallError::Trap)
+    }
+
+    /// Call a function of one of the members, following it through any imports
+    fn call_function(
+        &mut self,
+        member: usize,
+        fn_index: usize,
+        args: &[Value],
+    ) -> Result<Option<Value>, String> {
+        let result = match self.function_link(member, fn_index) {
+            FunctionLink::Wasm { member, fn_index } => self.run(member, fn_index, args),
+            FunctionLink::Host {
+                module_name,
+                function_name,
+            } => {
+                self.check_provided(module_name, function_name)?;
+                self.acquire_memory(member);
+                let return_value = self.import_dispatcher.dispatch(
+                    module_name,
+                    function_name,
+                    args,
+                    &mut Memory::new(&mut self.members[member].instance.memory),
+                );
+                self.check_exit()?;
+                #[cfg(feature = "canary")]
+                self.members[member]
+                    .instance
+                    .canary_after_import(module_name, function_name, args, return_value)
+                    .map_err(|trap| trap.to_string())?;
+                Ok(return_value)
+            }
+        };
+
+        if result.is_err() {
+            for member in self.members.iter_mut() {
+                member.instance.clear_call_stack();
+            }
+        }
+
+        result
+    }
+
+    /// The memory used by a module in the group, which may be shared with other modules
+    pub fn memory(&self, module_name: &str) -> Option<&[u8]> {
+        let member = self.find_member(module_name)?;
+        let holder = self.memory_holders[self.members[member].memory_owner];
+        Some(&self.members[holder].instance.memory)
+    }
+
+    /// Current value of a global exported by a module in the group
+    pub fn global(&self, module_name: &str, global_name: &str) -> Option<Value> {
+        let (owner, owner_index) = self.find_global(module_name, global_name)?;
+        Some(self.members[owner].instance.globals[owner_index])
+    }
+
+    /// Change the value of a global exported by a module in the group.
+    /// Every module that imports it sees the new value.
+    pub fn set_global(
+        &mut self,
+        module_name: &str,
+        global_name: &str,
+        value: Value,
+    ) -> Result<(), String> {
+        let (owner, owner_index) = self.find_global(module_name, global_name).ok_or_else(|| {
+            format!(
+                "Module `{}` doesn't export a global called `{}`",
+                module_name, global_name
+            )
+        })?;
+        let owner_instance = &mut self.members[owner].instance;
+        let ty = owner_instance.global_types[owner_index];
+        if !ty.is_mutable {
+            return Err(format!(
+                "The global {}.{} is immutable",
+                module_name, global_name
+            ));
+        }
+        if ValueType::from(value) != ty.value_type {
+            return Err(format!(
+                "The global {}.{} is {:?}, so it can't be set to {:?}",
+                module_name, global_name, ty.value_type, value
+            ));
+        }
+        owner_instance.globals[owner_index] = value;
+        Ok(())
+    }
+
+    /// The member and index of the global that an export refers to, following any imports
+    fn find_global(&self, module_name: &str, global_name: &str) -> Option<(usize, usize)> {
+        let member = self.find_member(module_name)?;
+        let export_index = self.members[member]
+            .instance
+            .exported_global_index(global_name)?;
+        Some(match self.members[member].globals.get(export_index) {
+            Some(imported) => *imported,
+            None => (member, export_index),
+        })
+    }
+
+    fn find_member(&self, name: &str) -> Option<usize> {
+        self.members.iter().position(|member| member.name == name)
+    }
+
+    fn find_export(&self, member: usize, name: &str, ty: ExportType) -> Result<usize, String> {
+        let Member {
+            name: member_name,
+            instance,
+            ..
+        } = &self.members[member];
+        instance
+            .module
+            .export
+            .exports
+            .iter()
+            .find(|ex| ex.ty == ty && ex.name == name)
+            .map(|ex| ex.index as usize)
+            .ok_or_else(|| {
+                format!(
+                    "Module `{}` doesn't export a {:?} called `{}`",
+                    member_name, ty, name
+                )
+            })
+    }
+
+    /// Follow a function index through any imports, to the function that actually gets called
+    fn function_link(&self, member: usize, fn_index: usize) -> FunctionLink<'a> {
+        let Member {
+            instance,
+            functions,
+            ..
+        } = &self.members[member];
+        if fn_index < instance.import_count {
+            functions[fn_index]
+        } else {
+            FunctionLink::Wasm { member, fn_index }
+        }
+    }
+
+    fn function_signature(&self, member: usize, fn_index: usize) -> FunctionType {
+        self.members[member].instance.function_type(fn_index)
+    }
+
+    /// Find the function at `element_index` in the table of `owner`, and check that it has the
+    /// type the caller expects. Returns the member whose function it is, and its index there.
+    /// That's the member that wrote the element, which may have imported the function from
+    /// somewhere else.
+    fn shared_table_lookup(
+        &self,
+        owner: usize,
+        element_index: u32,
+        expected: &FunctionType,
+    ) -> Result<(usize, u32), Error> {
+        let Member {
+            instance: owner_instance,
+            table_elements,
+            ..
+        } = &self.members[owner];
+        let (member, fn_index) = match table_elements.get(&element_index) {
+            Some(element) => *element,
+            None => {
+                let fn_index = table_lookup(
+                    owner_instance.module,
+                    element_index,
+                    owner_instance.resource_limits.max_table_elements,
+                )?;
+                (owner, fn_index)
+            }
+        };
+        let actual = self.function_signature(member, fn_index as usize);
+        if actual != *expected {
+            return Err(Error::IndirectCallSignature {
+                table_index: 0,
+                element_index,
+                fn_index,
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok((member, fn_index))
+    }
+
+    /// Run a Wasm function in one of the instances, until it returns
+    fn run(
+        &mut self,
+        member: usize,
+        fn_index: usize,
+        args: &[Value],
+    ) -> Result<Option<Value>, String> {
+        let FunctionType {
+            params: param_types,
+            result: ret_type,
+        } = self.function_signature(member, fn_index);
+        let arg_types = args.iter().map(|arg| ValueType::from(*arg));
+        if !arg_types.eq(param_types.iter().copied()) {
+            return Err(format!(
+                "Type mismatch calling func[{}] in `{}`. Expected {:?} but got {:?}",
+                fn_index, self.members[member].name, param_types, args
+            ));
+        }
+
+        self.acquire_memory(member);
+        self.load_globals(member);
+
+        let instance = &mut self.members[member].instance;
+        let module = instance.module;
+        for arg in args {
+            instance.value_store.push(*arg);
+        }
+        instance
+            .enter_function(fn_index)
+            .map_err(|e| instance.error_message(&e, module))?;
+
+        loop {
+            let instance = &mut self.members[member].instance;
+            let action = instance
+                .execute_next_instruction(module)
+                .map_err(|e| instance.error_message(&e, module))?;
+
+            match action {
+                Action::Continue => {}
+                Action::Break => break,
+                Action::CallImport(import_fn_index) => {
+                    let import_index = instance.function_imports[import_fn_index];
+                    let n_args = match module.import.imports[import_index].description {
+                        ImportDesc::Func { signature_index } => {
+                            module.types.look_up(signature_index).0.len()
+                        }
+                        _ => unreachable!(),
+                    };
+                    let link = self.members[member].functions[import_fn_index];
+                    self.call_link(member, link, n_args)?;
+                }
+                Action::CallImportedTable {
+                    element_index,
+                    signature_index,
+                } => {
+                    let owner = self.members[member].table_owner;
+                    let expected = FunctionType::look_up(module, signature_index);
+                    let (fn_member, fn_index) = self
+                        .shared_table_lookup(owner, element_index, &expected)
+                        .map_err(|e| self.members[member].instance.error_message(&e, module))?;
+
+                    let link = self.function_link(fn_member, fn_index as usize);
+                    self.call_link(member, link, expected.params.len())?;
+                }
+            }
+        }
+
+        let instance = &mut self.members[member].instance;
+        let return_value = ret_type.map(|_| instance.value_store.pop());
+        self.flush_globals(member);
+
+        Ok(return_value)
+    }
+
+    /// Take the arguments of an import call off the caller's stack, make the call,
+    /// and push the return value back.
+    fn call_link(
+        &mut self,
+        caller: usize,
+        link: FunctionLink<'a>,
+        n_args: usize,
+    ) -> Result<(), String> {
+        let value_store = &mut self.members[caller].instance.value_store;
+        let mut args = vec![Value::I32(0); n_args];
+        for arg in args.iter_mut().rev() {
+            *arg = value_store.pop();
+        }
+
+        let return_value = match link {
+            FunctionLink::Host {
+                module_name,
+                function_name,
+            } => {
+                self.check_provided(module_name, function_name)?;
+                let return_value = self.import_dispatcher.dispatch(
+                    module_name,
+                    function_name,
+                    &args,
+                    &mut Memory::new(&mut self.members[caller].instance.memory),
+                );
+                self.check_exit()?;
+                #[cfg(feature = "canary")]
+                self.members[caller]
+                    .instance
+                    .canary_after_import(module_name, function_name, &args, return_value)
+                    .map_err(|trap| trap.to_string())?;
+                return_value
+            }
+            FunctionLink::Wasm { member, fn_index } => {
+                self.flush_globals(caller);
+                let return_value = self.run(member, fn_index, &args)?;
+                self.acquire_memory(caller);
+                self.load_globals(caller);
+                return_value
+            }
+        };
+
+        if let Some(value) = return_value {
+            self.members[caller].instance.value_store.push(value);
+        }
+
+        Ok(())
+    }
+
+    /// Trap on a call to an imported function that the import dispatcher doesn't provide
+    fn check_provided(&self, module_name: &str, function_name: &str) -> Result<(), String> {
+        if self.import_dispatcher.provides(module_name, function_name) {
+            Ok(())
+        } else {
+            Err(format!(
+                "The imported function {}.{} was called, but the import dispatcher doesn't provide it",
+                module_name, function_name
+            ))
+        }
+    }
+
+    /// End the call if the program asked to exit, or the dispatcher failed.
+    /// See [ImportDispatcher::exit_status] and [ImportDispatcher::host_error].
+    fn check_exit(&self) -> Result<(), String> {
+        if let Some(status) = self.import_dispatcher.exit_status() {
+            return Err(format!("The program exited with status {}", status));
+        }
+        match self.import_dispatcher.host_error() {
+            Some(message) => Err(format!("The import dispatcher failed: {}", message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Move the bytes of a member's memory into its instance, if another instance has them
+    fn acquire_memory(&mut self, member: usize) {
+        let owner = self.members[member].memory_owner;
+        let holder = self.memory_holders[owner];
+        if holder != member {
+            let (low, high) = (holder.min(member), holder.max(member));
+            let (left, right) = self.members.split_at_mut(high);
+            std::mem::swap(
+                &mut left[low].instance.memory,
+                &mut right[0].instance.memory,
+            );
+            self.memory_holders[owner] = member;
+        }
+    }
+
+    /// Update a member's copies of its imported globals, before it runs
+    fn load_globals(&mut self, member: usize) {
+        for i in 0..self.members[member].globals.len() {
+            let (owner, owner_index) = self.members[member].globals[i];
+            let value = self.members[owner].instance.globals[owner_index];
+            self.members[member].instance.globals[i] = value;
+        }
+    }
+
+    /// Write a member's copies of its imported globals back to the modules they came from,
+    /// once it stops running
+    fn flush_globals(&mut self, member: usize) {
+        for i in 0..self.members[member].globals.len() {
+            let (owner, owner_index) = self.members[member].globals[i];
+            let value = self.members[member].instance.globals[i];
+            self.members[owner].instance.globals[owner_index] = value;
+        }
+    }
+}
+
+fn mutability(is_mutable: bool) -> &'static str {
+    if is_mutable {
+        "mutable"
+    } else {
+        "immutable"
+    }
+}
@@ -1,27 +1,89 @@
+use bitvec::vec::BitVec;
 use bumpalo::{collections::Vec, Bump};
 use std::fmt::{self, Write};
+use std::io;
 use std::iter::{self, once, Iterator};
+use std::rc::Rc;
 
 use roc_wasm_module::opcodes::OpCode;
-use roc_wasm_module::parse::{Parse, SkipBytes};
-use roc_wasm_module::sections::{ImportDesc, MemorySection, SignatureParamsIter};
-use roc_wasm_module::{ExportType, WasmModule};
+use roc_wasm_module::parse::{Parse, ParseError};
+use roc_wasm_module::sections::{Import, ImportDesc, Limits, MemorySection, SignatureParamsIter};
+use roc_wasm_module::{ExportType, GlobalType, WasmModule};
 use roc_wasm_module::{Value, ValueType};
 
+#[cfg(feature = "canary")]
+use crate::canary::{Canaries, CanaryTrap};
+use crate::capabilities::Capabilities;
+use crate::control::{BlockTargets, ControlTable};
+use crate::coverage::{Coverage, CoverageReport};
 use crate::frame::Frame;
+use crate::memory::{self, Memory, MemoryError};
+use crate::pool::MemoryImage;
+use crate::profile::{ProfileReport, Profiler};
+use crate::trace::{TraceWindow, Tracer};
+use crate::typed::{self, ExportInfo, ExportKind, WasmParams, WasmResult};
 use crate::value_store::ValueStore;
-use crate::{Error, ImportDispatcher};
+#[cfg(feature = "watchpoints")]
+use crate::watch::{Watchpoint, WatchpointHit, Watchpoints};
+use crate::{
+    CallError, Error, FunctionType, ImportDispatcher, LoadError, MemoryErrorMode, MissingImports,
+    ResourceLimits, RocPanic, RocPanicKind,
+};
+
+/// How much memory to show around an out-of-bounds access, in [MemoryErrorMode::Explain]
+const MEMORY_DUMP_BYTES: u64 = 64;
+
+/// The import that Roc programs call when they panic, unless the host renames it
+const ROC_PANIC_IMPORT: (&str, &str) = ("env", "roc_panic");
 
 #[derive(Debug)]
 pub enum Action {
     Continue,
     Break,
+    /// Call to an imported function, left for an [InstanceGroup](crate::InstanceGroup) to resolve.
+    /// The arguments are still on the value stack.
+    CallImport(usize),
+    /// Indirect call through a table shared with other modules, left for an [InstanceGroup](crate::InstanceGroup) to resolve.
+    /// The arguments are still on the value stack.
+    CallImportedTable {
+        element_index: u32,
+        signature_index: u32,
+    },
+}
+
+/// The outcome of running part of a call with [Instance::run_for]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunResult {
+    /// The call returned, with this value
+    Finished(Option<Value>),
+    /// The call ran out of instructions and can be resumed with another [Instance::run_for]
+    Yielded(Progress),
+}
+
+/// How far a call has got, when it yields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Instructions executed since the call began
+    pub instructions_executed: u64,
+    /// The function being executed
+    pub fn_index: usize,
+    /// Number of function calls in progress, including the one that began the call
+    pub call_depth: usize,
+}
+
+/// A call to an exported function that has been entered but not finished
+#[derive(Debug, Clone, Copy)]
+struct PendingCall {
+    return_type: Option<ValueType>,
+    /// Depth of the value stack before the arguments were pushed
+    stack_base: usize,
+    instructions_executed: u64,
 }
 
 #[derive(Debug, Clone, Copy)]
 enum BlockType {
     Loop(usize),         // Loop block, with start address to loop back to
-    Normal,              // Block created by `block` instruction
+    Normal(usize),       // Block created by `block` or `if`, with start address to look up its end
     Locals(usize),       // Special "block" for locals. Holds function index for debug
     FunctionBody(usize), // Special block surrounding the function body. Holds function index for debug
 }
@@ -32,13 +94,6 @@ struct Block {
     vstack: usize,
 }
 
-#[derive(Debug, Clone)]
-struct BranchCacheEntry {
-    addr: u32,
-    argument: u32,
-    target: u32,
-}
-
 #[derive(Debug)]
 pub struct Instance<'a, I: ImportDispatcher> {
     pub(crate) module: &'a WasmModule<'a>,
@@ -52,20 +107,65 @@ pub struct Instance<'a, I: ImportDispatcher> {
     pub(crate) value_store: ValueStore<'a>,
     /// Values of any global variables
     pub(crate) globals: Vec<'a, Value>,
+    /// Type and mutability of each global, including imported ones
+    pub(crate) global_types: Vec<'a, GlobalType>,
     /// Index in the code section of the current instruction
     pub(crate) program_counter: usize,
     /// One entry per nested block. For loops, stores the address of the first instruction.
     blocks: Vec<'a, Block>,
-    /// Cache for branching instructions, split into buckets for each function.
-    branch_cache: Vec<'a, Vec<'a, BranchCacheEntry>>,
-    /// Number of imports in the module
-    import_count: usize,
+    /// Where each block ends, decoded once for the module
+    control: Rc<ControlTable>,
+    /// Number of imported functions in the module
+    pub(crate) import_count: usize,
+    /// Position in the import section of each imported function
+    pub(crate) function_imports: Vec<'a, usize>,
+    /// Whether the function table is shared with other modules in an [InstanceGroup](crate::InstanceGroup),
+    /// because it's imported or other modules' element segments have written to it
+    pub(crate) shared_table: bool,
+    /// Whether calls to imports are left to an [InstanceGroup](crate::InstanceGroup) rather than the dispatcher
+    pub(crate) defer_imports: bool,
+    /// Depths of `previous_frames` where an [InstanceGroup](crate::InstanceGroup) entered a function.
+    /// Returning from that function hands control back to the group.
+    entry_depths: Vec<'a, usize>,
     /// Import dispatcher from user code
     pub import_dispatcher: I,
     /// Temporary storage for import arguments
     import_arguments: Vec<'a, Value>,
-    /// temporary storage for output using the --debug option
+    /// Whether to print every instruction executed, for the --debug option
+    is_debug_mode: bool,
+    /// Text of the current instruction, in debug mode or when the instruction is traced
     debug_string: Option<String>,
+    /// Writes executed instructions to a trace, if tracing is enabled
+    tracer: Option<Tracer<'a>>,
+    /// Per-function execution counters, if profiling is enabled
+    profiler: Option<Profiler<'a>>,
+    /// Which instructions have been executed, if coverage is enabled
+    coverage: Option<Coverage<'a>>,
+    /// Host-imposed limit on memory size, in pages. Applies on top of the module's own maximum.
+    memory_limit_pages: u32,
+    /// Host-imposed limits on call depth, stack size, and table size
+    pub(crate) resource_limits: ResourceLimits,
+    /// Number of instructions left to execute, if they're limited. See [Instance::set_fuel].
+    fuel: Option<u64>,
+    /// Memory ranges to check loads and stores against
+    #[cfg(feature = "watchpoints")]
+    watchpoints: Watchpoints,
+    /// Allocations made through the Roc allocator imports, if canary mode is on
+    #[cfg(feature = "canary")]
+    canaries: Option<Canaries>,
+    /// How much detail to report when a load or store is out of bounds
+    memory_error_mode: MemoryErrorMode,
+    /// A call started by [Instance::begin_call] that hasn't finished yet
+    pending_call: Option<PendingCall>,
+    /// The import that is handled as a Roc panic rather than passed to the dispatcher
+    panic_import: Option<(&'a str, &'a str)>,
+    /// The panic that ended the most recent call, if it ended that way
+    roc_panic: Option<RocPanic>,
+    /// The status that the program exited with during the most recent call, if it exited
+    exit_status: Option<i32>,
+    /// Pages of memory written since the last [restore_image](Instance::restore_image), so that
+    /// the next one only has to copy those back. `None` until the first restore.
+    written_pages: Option<BitVec>,
 }
 
 impl<'a, I: ImportDispatcher> Instance<'a, I> {
@@ -81,40 +181,139 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         G: IntoIterator<Item = Value>,
     {
         let mem_bytes = memory_pages * MemorySection::PAGE_SIZE;
+        let globals = Vec::from_iter_in(globals, arena);
+        let global_types = Vec::from_iter_in(
+            globals.iter().map(|value| GlobalType {
+                value_type: ValueType::from(*value),
+                is_mutable: true,
+            }),
+            arena,
+        );
+        let module: &WasmModule = arena.alloc(WasmModule::new(arena));
         Instance {
-            module: arena.alloc(WasmModule::new(arena)),
+            module,
             memory: Vec::from_iter_in(iter::repeat(0).take(mem_bytes as usize), arena),
             current_frame: Frame::new(),
             previous_frames: Vec::new_in(arena),
             value_store: ValueStore::new(arena),
-            globals: Vec::from_iter_in(globals, arena),
+            globals,
+            global_types,
             program_counter,
             blocks: Vec::new_in(arena),
-            branch_cache: bumpalo::vec![in arena; bumpalo::vec![in arena]],
+            control: Rc::new(ControlTable::new(module)),
             import_count: 0,
+            function_imports: Vec::new_in(arena),
+            shared_table: false,
+            defer_imports: false,
+            entry_depths: Vec::new_in(arena),
             import_dispatcher,
             import_arguments: Vec::new_in(arena),
+            is_debug_mode: true,
             debug_string: Some(String::new()),
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            memory_limit_pages: MemorySection::MAX_PAGES,
+            resource_limits: ResourceLimits::default(),
+            fuel: None,
+            #[cfg(feature = "watchpoints")]
+            watchpoints: Watchpoints::default(),
+            #[cfg(feature = "canary")]
+            canaries: None,
+            memory_error_mode: MemoryErrorMode::Trap,
+            pending_call: None,
+            panic_import: Some(ROC_PANIC_IMPORT),
+            roc_panic: None,
+            exit_status: None,
+            written_pages: None,
         }
     }
 
+    /// Decode and instantiate a module. Bytes that aren't a valid module are reported as
+    /// [LoadError::InvalidModule], rather than causing a panic, and a module that needs a proposal
+    /// this build doesn't support as [LoadError::Unsupported].
     pub fn from_bytes(
         arena: &'a Bump,
         module_bytes: &[u8],
         import_dispatcher: I,
         is_debug_mode: bool,
+    ) -> Result<Self, LoadError> {
+        Capabilities::of_this_build().check_module(module_bytes)?;
+        let module = WasmModule::preload(arena, module_bytes, false)
+            .map_err(|e| LoadError::invalid_module(module_bytes, e))?;
+        Self::for_module(arena, arena.alloc(module), import_dispatcher, is_debug_mode)
+            .map_err(LoadError::Trap)
+    }
+
+    /// Instantiate a module that was built in memory, like the output of gen_wasm, without
+    /// serializing and parsing it again. It should be finished, after dead code elimination.
+    pub fn from_module(
+        arena: &'a Bump,
+        mut module: WasmModule<'a>,
+        import_dispatcher: I,
+        is_debug_mode: bool,
     ) -> Result<Self, std::string::String> {
-        let module =
-            WasmModule::preload(arena, module_bytes, false).map_err(|e| format!("{:?}", e))?;
+        module.prepare_to_run(arena);
         Self::for_module(arena, arena.alloc(module), import_dispatcher, is_debug_mode)
     }
 
+    /// Instantiate a module on its own. Imported functions are called through the import
+    /// dispatcher, which also provides the values of imported globals.
     pub fn for_module(
         arena: &'a Bump,
         module: &'a WasmModule<'a>,
         import_dispatcher: I,
         is_debug_mode: bool,
     ) -> Result<Self, std::string::String> {
+        let control = Rc::new(ControlTable::new(module));
+        Self::with_control_table(arena, module, control, import_dispatcher, is_debug_mode)
+    }
+
+    /// Like [for_module](Instance::for_module), but using a [ControlTable] that was already made
+    /// for this module, e.g. by an earlier instance. Useful when instantiating the same module
+    /// many times over, like in a loop of tests.
+    pub fn with_control_table(
+        arena: &'a Bump,
+        module: &'a WasmModule<'a>,
+        control: Rc<ControlTable>,
+        mut import_dispatcher: I,
+        is_debug_mode: bool,
+    ) -> Result<Self, std::string::String> {
+        if !control.is_for(module) {
+            return Err("This control table was made for a different module".into());
+        }
+
+        // We don't handle imported memories and tables on their own.
+        // They need an InstanceGroup to link them to another module's exports.
+        let all_imports_supported =
+            module.import.imports.iter().all(|imp| {
+                imp.is_function() || matches!(imp.description, ImportDesc::Global { .. })
+            });
+        if !all_imports_supported {
+            return Err("This Wasm interpreter doesn't support imported memories or tables, except in an InstanceGroup".into());
+        }
+
+        let mut imported_globals = std::vec::Vec::new();
+        for import in module.import.imports.iter() {
+            if let ImportDesc::Global { ty } = import.description {
+                let value = import_dispatcher
+                    .global(import.module, import.name, ty)
+                    .ok_or_else(|| {
+                        format!(
+                            "The import dispatcher has no value for the imported global {}.{}",
+                            import.module, import.name
+                        )
+                    })?;
+                if ValueType::from(value) != ty.value_type {
+                    return Err(format!(
+                        "The imported global {}.{} should be {:?}, but the import dispatcher gave {:?}",
+                        import.module, import.name, ty.value_type, value
+                    ));
+                }
+                imported_globals.push(value);
+            }
+        }
+
         let mem_bytes = module.memory.min_bytes().map_err(|e| {
             format!(
                 "Error parsing Memory section at offset {:#x}:\n{}",
@@ -124,15 +323,276 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         let mut memory = Vec::from_iter_in(iter::repeat(0).take(mem_bytes as usize), arena);
         module.data.load_into(&mut memory)?;
 
-        let globals = module.global.initial_values(arena);
+        let mut instance = Self::new_help(
+            arena,
+            module,
+            control,
+            memory,
+            &imported_globals,
+            import_dispatcher,
+            is_debug_mode,
+        )?;
+
+        if instance.import_dispatcher.missing_imports() == MissingImports::Error {
+            if let Some(import) = instance.missing_function_imports().next() {
+                return Err(format!(
+                    "The import dispatcher doesn't provide the imported function {}.{}",
+                    import.module, import.name
+                ));
+            }
+        }
+
+        // The start function runs last, once the memory and table have their initial contents
+        if let Some(fn_index) = instance.start_function()? {
+            instance.run_start(fn_index)?;
+        }
 
-        // We don't handle non-function import types (memories, tables, and globals),
-        // and it's nice for lookups to assume they're all functions, so let's assert that.
-        let all_imports_are_functions = module.import.imports.iter().all(|imp| imp.is_function());
-        assert!(
-            all_imports_are_functions,
-            "This Wasm interpreter doesn't support non-function imports"
+        Ok(instance)
+    }
+
+    /// The function in the module's Start section, if it has one, checking that it has no
+    /// parameters or results
+    pub(crate) fn start_function(&self) -> Result<Option<usize>, std::string::String> {
+        let fn_index = match self.module.start.function_index {
+            Some(fn_index) => fn_index as usize,
+            None => return Ok(None),
+        };
+
+        let fn_count = self.import_count + self.module.code.function_offsets.len();
+        if fn_index >= fn_count {
+            return Err(format!(
+                "The start function is func[{}], but the module has {} functions",
+                fn_index, fn_count
+            ));
+        }
+
+        let ty = self.function_type(fn_index);
+        if !ty.params.is_empty() || ty.result.is_some() {
+            return Err(format!(
+                "The start function func[{}] should have no parameters or results, but its type is {}",
+                fn_index, ty
+            ));
+        }
+
+        Ok(Some(fn_index))
+    }
+
+    /// Call the start function of an instance that isn't in an [InstanceGroup](crate::InstanceGroup)
+    fn run_start(&mut self, fn_index: usize) -> Result<(), std::string::String> {
+        let module = self.module;
+
+        if fn_index < self.import_count {
+            let import = &module.import.imports[self.function_imports[fn_index]];
+            if !self.import_dispatcher.provides(import.module, import.name) {
+                return Err(format!(
+                    "The start function is the imported function {}.{}, but the import dispatcher doesn't provide it",
+                    import.module, import.name
+                ));
+            }
+            self.import_dispatcher.dispatch(
+                import.module,
+                import.name,
+                &[],
+                &mut Memory::new(&mut self.memory),
+            );
+            self.note_memory_write_by_host();
+            if let Some(status) = self.import_dispatcher.exit_status() {
+                self.exit_status = Some(status);
+                return Err(format!(
+                    "The program exited with status {} while running the start function",
+                    status
+                ));
+            }
+            if let Some(message) = self.import_dispatcher.host_error() {
+                return Err(format!(
+                    "The import dispatcher failed while running the start function: {}",
+                    message
+                ));
+            }
+            return Ok(());
+        }
+
+        self.program_counter = {
+            let internal_fn_index = fn_index - self.import_count;
+            let mut cursor = module.code.function_offsets[internal_fn_index] as usize;
+            let _start_fn_byte_length = u32::parse((), &module.code.bytes, &mut cursor);
+            cursor
+        };
+
+        self.call_export_help_after_arg_load(module, fn_index, 0, None)
+            .map(|_| ())
+            .map_err(|message| format!("The start function failed:\n{}", message))
+    }
+
+    /// The [ControlTable] of this instance's module, to share with other instances of it
+    pub fn control_table(&self) -> Rc<ControlTable> {
+        Rc::clone(&self.control)
+    }
+
+    /// A copy of the memory and globals, to [restore](Instance::restore_image) later
+    pub(crate) fn memory_image(&self) -> MemoryImage {
+        MemoryImage {
+            memory: self.memory.as_slice().into(),
+            globals: self.globals.as_slice().into(),
+        }
+    }
+
+    /// Put the memory and globals back the way they were in `image`, which must have been taken
+    /// from an instance of the same module. Any unfinished call is abandoned, so the next call
+    /// runs as if this instance was new.
+    /// The first restore copies the whole image. After that, the instance keeps track of the
+    /// pages that running code and imports write to, and only copies those back from the image,
+    /// which all the instances can share. Writes made through [Instance::memory] aren't tracked.
+    pub(crate) fn restore_image(&mut self, image: &MemoryImage) {
+        self.cancel_call();
+        self.value_store.truncate(0);
+
+        let page_size = MemorySection::PAGE_SIZE as usize;
+        match self.written_pages.as_mut() {
+            Some(written_pages) if self.memory.len() >= image.memory.len() => {
+                // Pages that memory.grow added aren't in the image, so they're dropped
+                self.memory.truncate(image.memory.len());
+                for page in written_pages.iter_ones() {
+                    let start = page * page_size;
+                    if start >= image.memory.len() {
+                        break;
+                    }
+                    let end = (start + page_size).min(image.memory.len());
+                    self.memory[start..end].copy_from_slice(&image.memory[start..end]);
+                }
+                written_pages.fill(false);
+            }
+            _ => {
+                // Keeps the allocation, so only the first restore of a grown memory allocates
+                self.memory.clear();
+                self.memory.extend_from_slice(&image.memory);
+                let pages = image.memory.len() / page_size;
+                self.written_pages = Some(BitVec::repeat(false, pages));
+            }
+        }
+
+        self.globals.copy_from_slice(&image.globals);
+        // Allocations from before aren't in the restored memory
+        #[cfg(feature = "canary")]
+        if let Some(canaries) = self.canaries.as_mut() {
+            *canaries = Canaries::default();
+        }
+        self.roc_panic = None;
+        self.exit_status = None;
+    }
+
+    /// Remember that running code wrote `size` bytes at `addr`, if writes are being tracked
+    pub(crate) fn note_memory_write(&mut self, addr: usize, size: usize) {
+        if let Some(written_pages) = self.written_pages.as_mut() {
+            let page_size = MemorySection::PAGE_SIZE as usize;
+            let first = addr / page_size;
+            let last = (addr + size.max(1) - 1) / page_size;
+            if written_pages.len() <= last {
+                written_pages.resize(last + 1, false);
+            }
+            written_pages[first..=last].fill(true);
+        }
+    }
+
+    /// Remember that something outside of the module, like an import, could have written
+    /// anywhere in the memory, if writes are being tracked
+    fn note_memory_write_by_host(&mut self) {
+        if let Some(written_pages) = self.written_pages.as_mut() {
+            let pages = self.memory.len() / MemorySection::PAGE_SIZE as usize;
+            written_pages.resize(pages.max(written_pages.len()), false);
+            written_pages.fill(true);
+        }
+    }
+
+    /// The imported functions that the import dispatcher doesn't provide. Calling one of them traps.
+    /// The panic import doesn't count, since calls to it never reach the dispatcher.
+    pub fn missing_function_imports(&self) -> impl Iterator<Item = &'a Import<'a>> + '_ {
+        let module = self.module;
+        self.function_imports
+            .iter()
+            .map(move |&i| &module.import.imports[i])
+            .filter(move |import| {
+                self.panic_import != Some((import.module, import.name))
+                    && !self.import_dispatcher.provides(import.module, import.name)
+            })
+    }
+
+    /// Create an instance for an [InstanceGroup](crate::InstanceGroup), which resolves its imports.
+    /// An imported memory starts out empty, and its data segments are not loaded.
+    /// `imported_globals` are the current values of the globals it imports, in order.
+    pub(crate) fn for_linked_module(
+        arena: &'a Bump,
+        module: &'a WasmModule<'a>,
+        imported_globals: &[Value],
+        import_dispatcher: I,
+        is_debug_mode: bool,
+    ) -> Result<Self, std::string::String> {
+        let imports_memory = module
+            .import
+            .imports
+            .iter()
+            .any(|imp| matches!(imp.description, ImportDesc::Mem { .. }));
+
+        let memory = if imports_memory || module.memory.count == 0 {
+            Vec::new_in(arena)
+        } else {
+            let mem_bytes = module.memory.min_bytes().map_err(|e| {
+                format!(
+                    "Error parsing Memory section at offset {:#x}:\n{}",
+                    e.offset, e.message
+                )
+            })?;
+            let mut memory = Vec::from_iter_in(iter::repeat(0).take(mem_bytes as usize), arena);
+            module.data.load_into(&mut memory)?;
+            memory
+        };
+
+        let control = Rc::new(ControlTable::new(module));
+        let mut instance = Self::new_help(
+            arena,
+            module,
+            control,
+            memory,
+            imported_globals,
+            import_dispatcher,
+            is_debug_mode,
+        )?;
+        instance.defer_imports = true;
+        Ok(instance)
+    }
+
+    fn new_help(
+        arena: &'a Bump,
+        module: &'a WasmModule<'a>,
+        control: Rc<ControlTable>,
+        memory: Vec<'a, u8>,
+        imported_globals: &[Value],
+        import_dispatcher: I,
+        is_debug_mode: bool,
+    ) -> Result<Self, std::string::String> {
+        let parse_error = |e: ParseError| {
+            format!(
+                "Error parsing Global section at offset {:#x}:\n{}",
+                e.offset, e.message
+            )
+        };
+        // Imported globals come first in the index space
+        let globals = module
+            .global
+            .initial_values(arena, imported_globals)
+            .map_err(parse_error)?;
+        let mut global_types = Vec::from_iter_in(
+            module
+                .import
+                .imports
+                .iter()
+                .filter_map(|imp| match imp.description {
+                    ImportDesc::Global { ty } => Some(ty),
+                    _ => None,
+                }),
+            arena,
         );
+        global_types.extend(module.global.types(arena).map_err(parse_error)?);
 
         let value_store = ValueStore::new(arena);
 
@@ -142,12 +602,22 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             None
         };
 
-        let import_count = module.import.imports.len();
-        let branch_cache = {
-            let num_functions = import_count + module.code.function_count as usize;
-            let empty_caches_iter = iter::repeat(Vec::new_in(arena)).take(num_functions);
-            Vec::from_iter_in(empty_caches_iter, arena)
-        };
+        let function_imports = Vec::from_iter_in(
+            module
+                .import
+                .imports
+                .iter()
+                .enumerate()
+                .filter(|(_, imp)| imp.is_function())
+                .map(|(i, _)| i),
+            arena,
+        );
+        let import_count = function_imports.len();
+        let shared_table = module
+            .import
+            .imports
+            .iter()
+            .any(|imp| matches!(imp.description, ImportDesc::Table { .. }));
 
         Ok(Instance {
             module,
@@ -156,36 +626,414 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             previous_frames: Vec::new_in(arena),
             value_store,
             globals,
+            global_types,
             program_counter: usize::MAX,
             blocks: Vec::new_in(arena),
-            branch_cache,
+            control,
             import_count,
+            function_imports,
+            shared_table,
+            defer_imports: false,
+            entry_depths: Vec::new_in(arena),
             import_dispatcher,
             import_arguments: Vec::new_in(arena),
+            is_debug_mode,
             debug_string,
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            memory_limit_pages: MemorySection::MAX_PAGES,
+            resource_limits: ResourceLimits::default(),
+            fuel: None,
+            #[cfg(feature = "watchpoints")]
+            watchpoints: Watchpoints::default(),
+            #[cfg(feature = "canary")]
+            canaries: None,
+            memory_error_mode: MemoryErrorMode::Trap,
+            pending_call: None,
+            panic_import: Some(ROC_PANIC_IMPORT),
+            roc_panic: None,
+            exit_status: None,
+            written_pages: None,
         })
     }
 
-    pub fn call_export<A>(&mut self, fn_name: &str, arg_values: A) -> Result<Option<Value>, String>
+    /// Start counting calls and executed instructions for each function.
+    /// Any previously collected counts are discarded.
+    pub fn enable_profiling(&mut self) {
+        match self.profiler.as_mut() {
+            Some(profiler) => profiler.reset(),
+            None => self.profiler = Some(Profiler::new(self.memory.bump(), self.module)),
+        }
+    }
+
+    pub fn disable_profiling(&mut self) {
+        self.profiler = None;
+    }
+
+    /// Report the counts collected since profiling was enabled, keyed by function name.
+    /// Returns `None` if profiling is not enabled.
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        self.profiler
+            .as_ref()
+            .map(|profiler| profiler.report(self.module))
+    }
+
+    /// Start recording which instructions are executed and how often each function is called.
+    /// Any previously recorded coverage is discarded.
+    pub fn enable_coverage(&mut self) {
+        match self.coverage.as_mut() {
+            Some(coverage) => coverage.reset(),
+            None => self.coverage = Some(Coverage::new(self.memory.bump(), self.module)),
+        }
+    }
+
+    pub fn disable_coverage(&mut self) {
+        self.coverage = None;
+    }
+
+    /// Report the coverage recorded since it was enabled, for every function in the module.
+    /// Returns `None` if coverage is not enabled.
+    pub fn coverage_report(&self) -> Option<CoverageReport> {
+        self.coverage
+            .as_ref()
+            .map(|coverage| coverage.report(self.module))
+    }
+
+    /// Write a line to `writer` for each executed instruction in the window, with its file offset,
+    /// function, immediates, and the value on top of the stack afterwards.
+    /// Useful for finding where the interpreter's behaviour diverges from another runtime's.
+    pub fn enable_trace<W: io::Write + 'a>(&mut self, writer: W, window: TraceWindow) {
+        self.tracer = Some(Tracer::new(Box::new(writer), window));
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.tracer = None;
+    }
+
+    /// Current size of the memory, in pages of 64kiB
+    pub fn memory_pages(&self) -> u32 {
+        (self.memory.len() / MemorySection::PAGE_SIZE as usize) as u32
+    }
+
+    /// Limit how far the memory can grow, in pages of 64kiB.
+    /// `memory.grow` returns -1 rather than exceed this, even if the module itself would allow it.
+    /// Memory that has already been allocated is never shrunk.
+    pub fn set_memory_limit(&mut self, max_pages: u32) {
+        self.memory_limit_pages = max_pages.min(MemorySection::MAX_PAGES);
+    }
+
+    /// Limit the call depth, stack size, and table size of calls to this instance.
+    /// Useful for running untrusted modules, which could otherwise recurse until the host runs out of memory.
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.resource_limits = limits;
+    }
+
+    /// Limit the total number of instructions this instance executes, across all calls.
+    /// Each instruction consumes one unit of fuel, and a call that needs more than is left
+    /// ends with an error. `None` removes the limit.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    /// The fuel left, if it's limited
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Watch a range of memory for loads or stores, returning the watchpoint's index
+    #[cfg(feature = "watchpoints")]
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        self.watchpoints.add(watchpoint)
+    }
+
+    /// Remove every watchpoint, and forget what they caught
+    #[cfg(feature = "watchpoints")]
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Every load and store that hit a watchpoint, oldest first, including the one that trapped
+    #[cfg(feature = "watchpoints")]
+    pub fn watchpoint_hits(&self) -> &[WatchpointHit] {
+        &self.watchpoints.hits
+    }
+
+    /// Turn canary mode on or off, to catch use-after-free in the program's allocator glue.
+    /// Memory allocated by the `env.roc_alloc` and `env.roc_realloc` imports is zeroed. Memory
+    /// freed by `env.roc_dealloc` is filled with [CANARY](crate::CANARY), and loading from it
+    /// traps, as does freeing it again. Turning it off forgets every freed allocation.
+    #[cfg(feature = "canary")]
+    pub fn set_canary_mode(&mut self, enabled: bool) {
+        self.canaries = enabled.then(Canaries::default);
+    }
+
+    /// Follow a call to an import, if canary mode is on and it's one of the Roc allocator's
+    #[cfg(feature = "canary")]
+    pub(crate) fn canary_after_import(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        return_value: Option<Value>,
+    ) -> Result<(), CanaryTrap> {
+        match self.canaries.as_mut() {
+            Some(canaries) => canaries.after_import(
+                module_name,
+                function_name,
+                arguments,
+                return_value,
+                &mut self.memory,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Choose how much detail to report when a load or store is out of bounds
+    pub fn set_memory_error_mode(&mut self, mode: MemoryErrorMode) {
+        self.memory_error_mode = mode;
+    }
+
+    /// Choose which import Roc code calls to panic, with a RocStr pointer and a tag id as arguments.
+    /// The default is `env.roc_panic`. Calls to it end with an error instead of reaching the
+    /// dispatcher, and the decoded panic is available from [Instance::roc_panic].
+    /// `None` passes every import to the dispatcher.
+    pub fn set_panic_import(&mut self, panic_import: Option<(&'a str, &'a str)>) {
+        self.panic_import = panic_import;
+    }
+
+    /// The Roc panic that ended the most recent call, if it ended that way
+    pub fn roc_panic(&self) -> Option<&RocPanic> {
+        self.roc_panic.as_ref()
+    }
+
+    /// The status that the program exited with, if the most recent call ended that way.
+    /// See [ImportDispatcher::exit_status].
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit_status
+    }
+
+    /// Grow the memory by `grow_pages`, with the same semantics as the `memory.grow` instruction.
+    /// Returns the previous size in pages, or `None` if the memory can't grow that much.
+    pub fn grow_memory(&mut self, grow_pages: u32) -> Option<u32> {
+        let module_max_pages = self.module.memory.max_pages().ok().flatten();
+        self.grow_memory_help(module_max_pages, grow_pages)
+    }
+
+    fn grow_memory_help(&mut self, module_max_pages: Option<u32>, grow_pages: u32) -> Option<u32> {
+        let old_pages = self.memory_pages();
+        if grow_pages == 0 {
+            return Some(old_pages);
+        }
+
+        let max_pages = match module_max_pages {
+            Some(module_max) => module_max.min(self.memory_limit_pages),
+            None => self.memory_limit_pages,
+        };
+        let new_pages = old_pages.checked_add(grow_pages)?;
+        if new_pages > max_pages {
+            return None;
+        }
+
+        // Fail gracefully rather than aborting, if the host is out of memory
+        let grow_bytes = grow_pages as usize * MemorySection::PAGE_SIZE as usize;
+        self.memory.try_reserve_exact(grow_bytes).ok()?;
+        self.memory.extend(iter::repeat(0).take(grow_bytes));
+
+        Some(old_pages)
+    }
+
+    pub fn call_export<A>(
+        &mut self,
+        fn_name: &str,
+        arg_values: A,
+    ) -> Result<Option<Value>, CallError>
     where
         A: IntoIterator<Item = Value>,
     {
-        let (fn_index, param_type_iter, ret_type) =
-            self.call_export_help_before_arg_load(self.module, fn_name)?;
+        let (fn_index, param_type_iter, ret_type) = self
+            .call_export_help_before_arg_load(self.module, fn_name)
+            .map_err(CallError::InvalidCall)?;
         let n_args = param_type_iter.len();
+        self.push_export_args(fn_name, arg_values, param_type_iter)
+            .map_err(CallError::InvalidCall)?;
 
-        for (i, (value, expected_type)) in arg_values.into_iter().zip(param_type_iter).enumerate() {
-            let actual_type = ValueType::from(value);
-            if actual_type != expected_type {
-                return Err(format!(
-                    "Type mismatch on argument {} of {}. Expected {:?} but got {:?}",
-                    i, fn_name, expected_type, value
-                ));
-            }
-            self.value_store.push(value);
+        self.call_export_help_after_arg_load(self.module, fn_index, n_args, ret_type)
+    }
+
+    /// Set up a call to an exported function without running any of it, so that it can be run
+    /// a few instructions at a time with [Instance::run_for]. This abandons any unfinished call.
+    pub fn begin_call<A>(&mut self, fn_name: &str, arg_values: A) -> Result<(), CallError>
+    where
+        A: IntoIterator<Item = Value>,
+    {
+        let (fn_index, param_type_iter, ret_type) = self
+            .call_export_help_before_arg_load(self.module, fn_name)
+            .map_err(CallError::InvalidCall)?;
+        let n_args = param_type_iter.len();
+        self.push_export_args(fn_name, arg_values, param_type_iter)
+            .map_err(CallError::InvalidCall)?;
+
+        let call = self.enter_export(self.module, fn_index, n_args, ret_type);
+        self.pending_call = Some(call);
+        Ok(())
+    }
+
+    /// Execute at most `n_instructions` of the call started by [Instance::begin_call].
+    /// This lets a host that can't block for long, like a UI thread, run a long computation
+    /// in slices, and cancel it by simply not resuming it.
+    pub fn run_for(&mut self, n_instructions: u64) -> Result<RunResult, CallError> {
+        let mut call = self.pending_call.take().ok_or_else(|| {
+            CallError::InvalidCall(
+                "There is no call in progress. Use begin_call first.".to_string(),
+            )
+        })?;
+
+        let result = self.run_call(self.module, &mut call, Some(n_instructions));
+
+        if let Ok(RunResult::Yielded(_)) = result {
+            self.pending_call = Some(call);
         }
+        result
+    }
 
-        self.call_export_help_after_arg_load(self.module, fn_index, n_args, ret_type)
+    /// Whether there is a call started by [Instance::begin_call] that hasn't finished yet
+    pub fn is_call_pending(&self) -> bool {
+        self.pending_call.is_some()
+    }
+
+    /// Abandon the call started by [Instance::begin_call], if it hasn't finished.
+    /// Anything it already wrote to memory or globals stays there.
+    pub fn cancel_call(&mut self) {
+        if let Some(call) = self.pending_call.take() {
+            self.previous_frames.clear();
+            self.blocks.clear();
+            self.value_store.truncate(call.stack_base);
+        }
+    }
+
+    /// Everything the module exports, with the type of each export
+    pub fn exports(&self) -> std::vec::Vec<ExportInfo<'a>> {
+        self.module
+            .export
+            .exports
+            .iter()
+            .map(|ex| {
+                let kind = match ex.ty {
+                    ExportType::Func => {
+                        let FunctionType { params, result } = self.function_type(ex.index as usize);
+                        ExportKind::Func { params, result }
+                    }
+                    ExportType::Table => ExportKind::Table,
+                    ExportType::Mem => ExportKind::Memory,
+                    ExportType::Global => {
+                        ExportKind::Global(ValueType::from(self.globals[ex.index as usize]))
+                    }
+                };
+                ExportInfo {
+                    name: ex.name,
+                    kind,
+                }
+            })
+            .collect()
+    }
+
+    /// Current value of an exported global
+    pub fn global(&self, name: &str) -> Option<Value> {
+        let index = self.exported_global_index(name)?;
+        Some(self.globals[index])
+    }
+
+    /// Change the value of an exported global, which must be mutable and keep the same type
+    pub fn set_global(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let index = self
+            .exported_global_index(name)
+            .ok_or_else(|| format!("There is no exported global named '{}'", name))?;
+        let ty = self.global_types[index];
+        if !ty.is_mutable {
+            return Err(format!("The global '{}' is immutable", name));
+        }
+        if ValueType::from(value) != ty.value_type {
+            return Err(format!(
+                "The global '{}' is {:?}, so it can't be set to {:?}",
+                name, ty.value_type, value
+            ));
+        }
+        self.globals[index] = value;
+        Ok(())
+    }
+
+    pub(crate) fn exported_global_index(&self, name: &str) -> Option<usize> {
+        self.module
+            .export
+            .exports
+            .iter()
+            .find(|ex| ex.ty == ExportType::Global && ex.name == name)
+            .map(|ex| ex.index as usize)
+    }
+
+    /// Call an exported function with Rust values rather than [Value]s, e.g.
+    /// `instance.call_typed::<(i32, i64), f64>("average", (1, 2))`.
+    /// The types are checked against the function's signature before it's called.
+    pub fn call_typed<P, R>(&mut self, fn_name: &str, args: P) -> Result<R, CallError>
+    where
+        P: WasmParams,
+        R: WasmResult,
+    {
+        let fn_index = self
+            .module
+            .export
+            .exports
+            .iter()
+            .find(|ex| ex.ty == ExportType::Func && ex.name == fn_name)
+            .map(|ex| ex.index as usize)
+            .ok_or_else(|| {
+                CallError::InvalidCall(format!("There is no exported function named '{}'", fn_name))
+            })?;
+
+        let FunctionType { params, result } = self.function_type(fn_index);
+        let arg_types = P::value_types();
+        if arg_types != params {
+            return Err(CallError::InvalidCall(format!(
+                "'{}' takes {} but was called with {}",
+                fn_name,
+                typed::type_list(&params),
+                typed::type_list(&arg_types)
+            )));
+        }
+        if R::value_type() != result {
+            return Err(CallError::InvalidCall(format!(
+                "'{}' returns {} but {} was expected",
+                fn_name,
+                typed::result_name(result),
+                typed::result_name(R::value_type())
+            )));
+        }
+
+        let return_value = self.call_export(fn_name, args.into_values())?;
+
+        R::from_result(return_value).ok_or_else(|| {
+            CallError::Trap(format!(
+                "'{}' returned {:?} instead of {}",
+                fn_name,
+                return_value,
+                typed::result_name(result)
+            ))
+        })
+    }
+
+    /// Parameter and result types of a function, whether it's imported or defined in the module
+    pub(crate) fn function_type(&self, fn_index: usize) -> FunctionType {
+        let signature_index = if fn_index < self.import_count {
+            let import_index = self.function_imports[fn_index];
+            match self.module.import.imports[import_index].description {
+                ImportDesc::Func { signature_index } => signature_index,
+                _ => unreachable!(),
+            }
+        } else {
+            self.module.function.signatures[fn_index - self.import_count]
+        };
+        FunctionType::look_up(self.module, signature_index)
     }
 
     pub fn call_export_from_cli(
@@ -193,7 +1041,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         module: &WasmModule<'a>,
         fn_name: &str,
         arg_strings: &'a [&'a [u8]],
-    ) -> Result<Option<Value>, String> {
+    ) -> Result<Option<Value>, CallError> {
         // We have two different mechanisms for handling CLI arguments!
         // 1. Basic numbers:
         //      e.g. `roc_wasm_interp fibonacci 12`
@@ -207,9 +1055,19 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
         // Implement the "basic numbers" CLI
         // Check if the called Wasm function takes numeric arguments, and if so, try to parse them from the CLI.
-        let (fn_index, param_type_iter, ret_type) =
-            self.call_export_help_before_arg_load(module, fn_name)?;
+        let (fn_index, param_type_iter, ret_type) = self
+            .call_export_help_before_arg_load(module, fn_name)
+            .map_err(CallError::InvalidCall)?;
         let n_args = param_type_iter.len();
+        // Any more strings are for a WASI program to read, but there must be one for each parameter
+        if arg_strings.len().saturating_sub(1) < n_args {
+            return Err(CallError::InvalidCall(format!(
+                "{} takes {} arguments, but was called with {}",
+                fn_name,
+                n_args,
+                arg_strings.len().saturating_sub(1)
+            )));
+        }
         for (value_bytes, value_type) in arg_strings
             .iter()
             .skip(1) // first string is the .wasm filename
@@ -217,11 +1075,13 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         {
             use ValueType::*;
             let value_str = String::from_utf8_lossy(value_bytes);
+            let invalid = |e: &dyn fmt::Display| CallError::InvalidCall(e.to_string());
             let value = match value_type {
-                I32 => Value::I32(value_str.parse::<i32>().map_err(|e| e.to_string())?),
-                I64 => Value::I64(value_str.parse::<i64>().map_err(|e| e.to_string())?),
-                F32 => Value::F32(value_str.parse::<f32>().map_err(|e| e.to_string())?),
-                F64 => Value::F64(value_str.parse::<f64>().map_err(|e| e.to_string())?),
+                I32 => Value::I32(value_str.parse::<i32>().map_err(|e| invalid(&e))?),
+                I64 => Value::I64(value_str.parse::<i64>().map_err(|e| invalid(&e))?),
+                F32 => Value::F32(value_str.parse::<f32>().map_err(|e| invalid(&e))?),
+                F64 => Value::F64(value_str.parse::<f64>().map_err(|e| invalid(&e))?),
+                V128 => Value::V128(value_str.parse::<u128>().map_err(|e| invalid(&e))?),
             };
             self.value_store.push(value);
         }
@@ -229,11 +1089,50 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         self.call_export_help_after_arg_load(module, fn_index, n_args, ret_type)
     }
 
+    /// Check the arguments of a call to an exported function against its parameters, then push
+    /// them onto the value stack. Nothing is pushed if they don't match.
+    fn push_export_args<A>(
+        &mut self,
+        fn_name: &str,
+        arg_values: A,
+        param_types: SignatureParamsIter,
+    ) -> Result<(), String>
+    where
+        A: IntoIterator<Item = Value>,
+    {
+        let arg_values: std::vec::Vec<Value> = arg_values.into_iter().collect();
+        if arg_values.len() != param_types.len() {
+            return Err(format!(
+                "{} takes {} arguments, but was called with {}",
+                fn_name,
+                param_types.len(),
+                arg_values.len()
+            ));
+        }
+
+        for (i, (value, expected_type)) in arg_values.iter().zip(param_types).enumerate() {
+            let actual_type = ValueType::from(*value);
+            if actual_type != expected_type {
+                return Err(format!(
+                    "Type mismatch on argument {} of {}. Expected {:?} but got {:?}",
+                    i, fn_name, expected_type, value
+                ));
+            }
+        }
+
+        for value in arg_values {
+            self.value_store.push(value);
+        }
+        Ok(())
+    }
+
     fn call_export_help_before_arg_load<'m>(
         &mut self,
         module: &'m WasmModule<'a>,
         fn_name: &str,
     ) -> Result<(usize, SignatureParamsIter<'m>, Option<ValueType>), String> {
+        self.cancel_call();
+
         let fn_index = {
             let mut export_iter = module.export.exports.iter();
             export_iter
@@ -281,7 +1180,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             module.types.look_up(signature_index)
         };
 
-        if self.debug_string.is_some() {
+        if self.is_debug_mode {
             println!(
                 "Calling export func[{}] '{}' at address {:#x}",
                 fn_index,
@@ -299,7 +1198,27 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         fn_index: usize,
         n_args: usize,
         return_type: Option<ValueType>,
-    ) -> Result<Option<Value>, String> {
+    ) -> Result<Option<Value>, CallError> {
+        let mut call = self.enter_export(module, fn_index, n_args, return_type);
+
+        match self.run_call(module, &mut call, None)? {
+            RunResult::Finished(return_value) => Ok(return_value),
+            RunResult::Yielded(_) => unreachable!("Calls without a budget run to the end"),
+        }
+    }
+
+    /// Set up the frame and blocks for a call to an exported function, whose arguments
+    /// are already on the value stack
+    fn enter_export(
+        &mut self,
+        module: &WasmModule<'a>,
+        fn_index: usize,
+        n_args: usize,
+        return_type: Option<ValueType>,
+    ) -> PendingCall {
+        let stack_base = self.value_store.depth() - n_args;
+        self.roc_panic = None;
+        self.exit_status = None;
         self.previous_frames.clear();
         self.blocks.clear();
         self.blocks.push(Block {
@@ -320,32 +1239,200 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             ty: BlockType::FunctionBody(fn_index),
             vstack: self.value_store.depth(),
         });
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.count_call(fn_index);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.count_call(fn_index);
+        }
+
+        PendingCall {
+            return_type,
+            stack_base,
+            instructions_executed: 0,
+        }
+    }
+
+    /// Execute instructions until the call returns, or until `budget` instructions have run
+    fn run_call(
+        &mut self,
+        module: &WasmModule<'a>,
+        call: &mut PendingCall,
+        budget: Option<u64>,
+    ) -> Result<RunResult, CallError> {
+        let mut remaining = budget;
 
         loop {
+            if remaining == Some(0) {
+                return Ok(RunResult::Yielded(Progress {
+                    instructions_executed: call.instructions_executed,
+                    fn_index: self.current_frame.fn_index,
+                    call_depth: self.previous_frames.len() + 1,
+                }));
+            }
+
             match self.execute_next_instruction(module) {
                 Ok(Action::Continue) => {}
                 Ok(Action::Break) => {
+                    call.instructions_executed += 1;
                     break;
                 }
+                Ok(Action::CallImport(_) | Action::CallImportedTable { .. }) => {
+                    unreachable!("Imports are only deferred for an InstanceGroup")
+                }
                 Err(e) => {
-                    let file_offset = self.program_counter + module.code.section_offset as usize;
-                    let mut message = e.to_string_at(file_offset);
-                    self.debug_stack_trace(&mut message).unwrap();
-                    return Err(message);
+                    if let Error::Exit(status) = e {
+                        self.exit_status = Some(status);
+                    }
+                    let message = self.error_message(&e, module);
+                    if let Error::RocPanic {
+                        message: panic_message,
+                        kind,
+                    } = e
+                    {
+                        let mut backtrace = String::new();
+                        self.debug_stack_trace(&mut backtrace).unwrap();
+                        let panic = RocPanic {
+                            message: panic_message,
+                            kind,
+                            backtrace,
+                        };
+                        self.roc_panic = Some(panic.clone());
+                        return Err(CallError::RocPanic { panic, message });
+                    }
+                    return Err(CallError::Trap(message));
                 }
             };
+
+            call.instructions_executed += 1;
+            if let Some(n) = remaining.as_mut() {
+                *n -= 1;
+            }
+
+            // Yield before the next instruction, so the host can see what the access did
+            #[cfg(feature = "watchpoints")]
+            if self.watchpoints.take_pause() && remaining.is_some() {
+                remaining = Some(0);
+            }
         }
 
-        let return_value = if !self.value_store.is_empty() {
-            Some(self.value_store.pop())
-        } else {
-            None
+        let return_value = call.return_type.map(|_| self.value_store.pop());
+
+        // Drop the function's locals, so they don't end up as the return value of the next call
+        self.value_store.truncate(call.stack_base);
+
+        Ok(RunResult::Finished(return_value))
+    }
+
+    /// Describe an error at the current instruction, with a stack trace
+    pub(crate) fn error_message(&self, e: &Error, module: &WasmModule<'a>) -> String {
+        let code_addr = match e {
+            Error::MemoryOutOfBounds { op_addr, .. } => *op_addr,
+            #[cfg(feature = "canary")]
+            Error::Canary { op_addr, .. } => *op_addr,
+            _ => self.program_counter,
         };
+        let file_offset = code_addr + module.code.section_offset as usize;
+        let mut message = e.to_string_at(file_offset);
+        if self.memory_error_mode == MemoryErrorMode::Explain {
+            if let Error::MemoryOutOfBounds {
+                op_addr,
+                addr,
+                size,
+                memory_size,
+            } = e
+            {
+                self.explain_memory_error(
+                    &mut message,
+                    module,
+                    *op_addr,
+                    *addr,
+                    *size,
+                    *memory_size,
+                )
+                .unwrap();
+            }
+        }
+        self.debug_stack_trace(&mut message).unwrap();
+        message
+    }
 
-        Ok(return_value)
+    /// Details of an out-of-bounds access, for debugging miscompiled memory operations
+    /// --------------
+    /// access   4 bytes at 0xfffe
+    /// memory   0x10000 bytes (1 pages)
+    /// function func[12], instruction at +0x2a
+    /// 00fff0  00 00 00 00 00 00 00 00 00 00 00 00 2a 00 00 00
+    /// --------------
+    fn explain_memory_error(
+        &self,
+        buffer: &mut String,
+        module: &WasmModule<'a>,
+        op_addr: usize,
+        addr: u64,
+        size: usize,
+        memory_size: usize,
+    ) -> fmt::Result {
+        writeln!(buffer, "access   {} bytes at {:#x}", size, addr)?;
+        writeln!(
+            buffer,
+            "memory   {:#x} bytes ({} pages)",
+            memory_size,
+            memory_size / MemorySection::PAGE_SIZE as usize
+        )?;
+
+        let fn_index = self.current_frame.fn_index;
+        let fn_offset = module.code.function_offsets[fn_index - self.import_count] as usize;
+        writeln!(
+            buffer,
+            "function func[{}], instruction at +{:#x}",
+            fn_index,
+            op_addr - fn_offset
+        )?;
+
+        // Only dump memory when the access is just past the end, rather than a wild pointer
+        let dump_end = memory_size as u64;
+        if memory_size == 0 || addr > dump_end + MEMORY_DUMP_BYTES {
+            return Ok(());
+        }
+        let dump_start = dump_end.saturating_sub(MEMORY_DUMP_BYTES) & !0xf;
+        for line_start in (dump_start..dump_end).step_by(16) {
+            let line_end = (line_start + 16).min(dump_end);
+            write!(buffer, "{:06x} ", line_start)?;
+            for byte in &self.memory[line_start as usize..line_end as usize] {
+                write!(buffer, " {:02x}", byte)?;
+            }
+            writeln!(buffer)?;
+        }
+
+        Ok(())
     }
 
-    fn fetch_immediate_u32(&mut self, module: &WasmModule<'a>) -> u32 {
+    /// Abandon any calls in progress, e.g. after an error in another instance of a group
+    pub(crate) fn clear_call_stack(&mut self) {
+        self.entry_depths.clear();
+        self.previous_frames.clear();
+        self.blocks.clear();
+        self.value_store.truncate(0);
+    }
+
+    /// Start running a function on behalf of an [InstanceGroup](crate::InstanceGroup).
+    /// The arguments must already be on the value stack.
+    /// If this instance is in the middle of a call that went out to another module,
+    /// that call is resumed once the new one returns.
+    pub(crate) fn enter_function(&mut self, fn_index: usize) -> Result<(), Error> {
+        let module = self.module;
+        if self.entry_depths.is_empty() {
+            self.previous_frames.clear();
+            self.blocks.clear();
+        }
+        // do_call pushes the current frame, which we get back when this function returns
+        self.entry_depths.push(self.previous_frames.len() + 1);
+        self.do_call(fn_index, module)?;
+        Ok(())
+    }
+
+    pub(crate) fn fetch_immediate_u32(&mut self, module: &WasmModule<'a>) -> u32 {
         let x = u32::parse((), &module.code.bytes, &mut self.program_counter).unwrap();
         if let Some(debug_string) = self.debug_string.as_mut() {
             write!(debug_string, "{} ", x).unwrap();
@@ -382,6 +1469,13 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
         // self.debug_values_and_blocks("end do_return");
 
+        if self.entry_depths.last() == Some(&self.previous_frames.len()) {
+            // Return to the InstanceGroup that entered this function
+            self.entry_depths.pop();
+            self.current_frame = self.previous_frames.pop().unwrap();
+            return Action::Break;
+        }
+
         if let Some(caller_frame) = self.previous_frames.pop() {
             self.current_frame = caller_frame;
             Action::Continue
@@ -391,17 +1485,32 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         }
     }
 
-    fn get_load_address(&mut self, module: &WasmModule<'a>) -> Result<u32, Error> {
+    fn get_load_address(&mut self, module: &WasmModule<'a>, size: usize) -> Result<usize, Error> {
+        let op_addr = self.program_counter - 1;
         // Alignment is not used in the execution steps from the spec! Maybe it's just an optimization hint?
         // https://webassembly.github.io/spec/core/exec/instructions.html#memory-instructions
         // Also note: in the text format we can specify the useless `align=` but not the useful `offset=`!
         let _alignment = self.fetch_immediate_u32(module);
         let offset = self.fetch_immediate_u32(module);
         let base_addr = self.value_store.pop_u32()?;
-        Ok(base_addr + offset)
+        let addr = self.check_memory_access(op_addr, base_addr, offset, size)?;
+        #[cfg(feature = "watchpoints")]
+        self.check_watchpoints(module, op_addr, addr, size, None)?;
+        #[cfg(feature = "canary")]
+        if let Some(canaries) = self.canaries.as_ref() {
+            canaries
+                .check_load(addr, size)
+                .map_err(|trap| Error::Canary { op_addr, trap })?;
+        }
+        Ok(addr)
     }
 
-    fn get_store_addr_value(&mut self, module: &WasmModule<'a>) -> Result<(usize, Value), Error> {
+    fn get_store_addr_value(
+        &mut self,
+        module: &WasmModule<'a>,
+        size: usize,
+    ) -> Result<(usize, Value), Error> {
+        let op_addr = self.program_counter - 1;
         // Alignment is not used in the execution steps from the spec! Maybe it's just an optimization hint?
         // https://webassembly.github.io/spec/core/exec/instructions.html#memory-instructions
         // Also note: in the text format we can specify the useless `align=` but not the useful `offset=`!
@@ -409,17 +1518,61 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         let offset = self.fetch_immediate_u32(module);
         let value = self.value_store.pop();
         let base_addr = self.value_store.pop_u32()?;
-        let addr = (base_addr + offset) as usize;
+        let addr = self.check_memory_access(op_addr, base_addr, offset, size)?;
+        #[cfg(feature = "watchpoints")]
+        self.check_watchpoints(module, op_addr, addr, size, Some(value))?;
+        self.note_memory_write(addr, size);
         Ok((addr, value))
     }
 
-    fn write_debug<T: fmt::Debug>(&mut self, value: T) {
+    #[cfg(feature = "watchpoints")]
+    fn check_watchpoints(
+        &mut self,
+        module: &WasmModule<'a>,
+        op_addr: usize,
+        addr: usize,
+        size: usize,
+        stored: Option<Value>,
+    ) -> Result<(), Error> {
+        let file_offset = op_addr + module.code.section_offset as usize;
+        self.watchpoints
+            .check(addr, size, stored, self.current_frame.fn_index, file_offset)
+            .map_err(Error::Watchpoint)
+    }
+
+    /// The effective address of a load or store, if all `size` bytes of it are inside the memory.
+    /// The address is computed in 64 bits, so that it can't wrap around like it would in a u32.
+    pub(crate) fn check_memory_access(
+        &self,
+        op_addr: usize,
+        base_addr: u32,
+        offset: u32,
+        size: usize,
+    ) -> Result<usize, Error> {
+        let addr = base_addr as u64 + offset as u64;
+        if addr + size as u64 <= self.memory.len() as u64 {
+            Ok(addr as usize)
+        } else {
+            Err(Error::MemoryOutOfBounds {
+                op_addr,
+                addr,
+                size,
+                memory_size: self.memory.len(),
+            })
+        }
+    }
+
+    pub(crate) fn write_debug<T: fmt::Debug>(&mut self, value: T) {
         if let Some(debug_string) = self.debug_string.as_mut() {
             std::write!(debug_string, "{:?} ", value).unwrap();
         }
     }
 
-    fn do_break(&mut self, relative_blocks_outward: u32, module: &WasmModule<'a>) {
+    fn do_break(
+        &mut self,
+        relative_blocks_outward: u32,
+        module: &WasmModule<'a>,
+    ) -> Result<Action, Error> {
         let block_index = self.blocks.len() - 1 - relative_blocks_outward as usize;
         let Block { ty, vstack } = self.blocks[block_index];
         match ty {
@@ -427,66 +1580,41 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 self.blocks.truncate(block_index + 1);
                 self.value_store.truncate(vstack);
                 self.program_counter = start_addr;
+                Ok(Action::Continue)
+            }
+            BlockType::Normal(start_addr) => {
+                // Keep the block's results, and throw away anything else it left on the stack
+                let targets = self.block_targets(module, start_addr)?;
+                let results_start = self.value_store.depth() - targets.arity;
+                for i in 0..targets.arity {
+                    let value = *self.value_store.get(results_start + i).unwrap();
+                    self.value_store.set(vstack + i, value);
+                }
+                self.value_store.truncate(vstack + targets.arity);
+                self.blocks.truncate(block_index);
+                self.program_counter = targets.end as usize;
+                Ok(Action::Continue)
             }
-            BlockType::FunctionBody(_) | BlockType::Normal => {
-                self.break_forward(relative_blocks_outward, module);
-                self.value_store.truncate(vstack);
-            }
+            BlockType::FunctionBody(_) => Ok(self.do_return()),
             BlockType::Locals(_) => unreachable!(),
         }
     }
 
-    // Break to an outer block, going forward in the program
-    fn break_forward(&mut self, relative_blocks_outward: u32, module: &WasmModule<'a>) {
-        use OpCode::*;
-
-        let addr = self.program_counter as u32;
-        let cache_result = self.branch_cache[self.current_frame.fn_index]
-            .iter()
-            .find(|entry| entry.addr == addr && entry.argument == relative_blocks_outward);
-
-        let mut depth = self.blocks.len();
-        let target_block_depth = depth - (relative_blocks_outward + 1) as usize;
-
-        if let Some(entry) = cache_result {
-            self.program_counter = entry.target as usize;
-        } else {
-            loop {
-                let skipped_op = OpCode::from(module.code.bytes[self.program_counter]);
-                OpCode::skip_bytes(&module.code.bytes, &mut self.program_counter).unwrap();
-                match skipped_op {
-                    BLOCK | LOOP | IF => {
-                        depth += 1;
-                    }
-                    END => {
-                        depth -= 1;
-                        if depth == target_block_depth {
-                            break;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            self.branch_cache[self.current_frame.fn_index].push(BranchCacheEntry {
-                addr,
-                argument: relative_blocks_outward,
-                target: self.program_counter as u32,
-            });
-        }
-        self.blocks.truncate(target_block_depth);
+    fn block_targets(
+        &self,
+        module: &WasmModule<'a>,
+        start_addr: usize,
+    ) -> Result<BlockTargets, Error> {
+        let internal_fn_index = self.current_frame.fn_index - self.import_count;
+        self.control.get(module, internal_fn_index, start_addr)
     }
 
-    fn do_call(
-        &mut self,
-        expected_signature: Option<u32>,
-        fn_index: usize,
-        module: &WasmModule<'a>,
-    ) -> Result<(), Error> {
+    fn do_call(&mut self, fn_index: usize, module: &WasmModule<'a>) -> Result<Action, Error> {
         // self.debug_values_and_blocks(&format!("start do_call {}", fn_index));
 
         let (signature_index, opt_import) = if fn_index < self.import_count {
             // Imported non-Wasm function
-            let import = &module.import.imports[fn_index];
+            let import = &module.import.imports[self.function_imports[fn_index]];
             let sig = match import.description {
                 ImportDesc::Func { signature_index } => signature_index,
                 _ => unreachable!(),
@@ -498,21 +1626,22 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             (sig, None)
         };
 
-        if let Some(expected) = expected_signature {
-            assert_eq!(
-                expected, signature_index,
-                "Indirect function call failed. Expected signature {} but found {}",
-                expected, signature_index,
-            );
-        }
-
         let (arg_type_iter, ret_type) = module.types.look_up(signature_index);
         let n_args = arg_type_iter.len();
         if self.debug_string.is_some() {
             self.debug_call(n_args, ret_type);
         }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.count_call(fn_index);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.count_call(fn_index);
+        }
 
-        if let Some(import) = opt_import {
+        if opt_import.is_some() && self.defer_imports {
+            // The InstanceGroup takes the arguments from the stack and pushes the return value
+            return Ok(Action::CallImport(fn_index));
+        } else if let Some(import) = opt_import {
             self.import_arguments.clear();
             self.import_arguments
                 .extend(std::iter::repeat(Value::I64(0)).take(n_args));
@@ -525,12 +1654,47 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 self.import_arguments[i] = arg;
             }
 
+            let is_roc_panic = self.panic_import == Some((import.module, import.name))
+                && matches!(self.import_arguments[..], [Value::I32(_), Value::I32(_)]);
+            if is_roc_panic {
+                return Err(self.decode_roc_panic());
+            }
+
+            if !self.import_dispatcher.provides(import.module, import.name) {
+                return Err(Error::MissingImport {
+                    module_name: import.module.to_string(),
+                    function_name: import.name.to_string(),
+                });
+            }
+
             let optional_return_val = self.import_dispatcher.dispatch(
                 import.module,
                 import.name,
                 &self.import_arguments,
-                &mut self.memory,
+                &mut Memory::new(&mut self.memory),
             );
+            self.note_memory_write_by_host();
+            if let Some(status) = self.import_dispatcher.exit_status() {
+                return Err(Error::Exit(status));
+            }
+            if let Some(message) = self.import_dispatcher.host_error() {
+                return Err(Error::Host(message.to_string()));
+            }
+            #[cfg(feature = "canary")]
+            if let Some(canaries) = self.canaries.as_mut() {
+                canaries
+                    .after_import(
+                        import.module,
+                        import.name,
+                        &self.import_arguments,
+                        optional_return_val,
+                        &mut self.memory,
+                    )
+                    .map_err(|trap| Error::Canary {
+                        op_addr: self.program_counter,
+                        trap,
+                    })?;
+            }
             if let Some(return_val) = optional_return_val {
                 self.value_store.push(return_val);
             }
@@ -538,6 +1702,11 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 write!(debug_string, " {}.{}", import.module, import.name).unwrap();
             }
         } else {
+            let limit = self.resource_limits.max_call_depth;
+            if self.previous_frames.len() + 2 > limit {
+                return Err(Error::CallDepthExceeded { limit });
+            }
+
             let return_addr = self.program_counter;
             // set PC to start of function bytes
             let internal_fn_index = fn_index - self.import_count;
@@ -568,10 +1737,45 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 ty: BlockType::FunctionBody(fn_index),
                 vstack: self.value_store.depth(),
             });
+
+            let depth = self.value_store.depth();
+            let limit = self.resource_limits.max_stack_values;
+            if depth > limit {
+                return Err(Error::StackLimitExceeded { depth, limit });
+            }
         }
         // self.debug_values_and_blocks("end do_call");
 
-        Ok(())
+        Ok(Action::Continue)
+    }
+
+    /// Read the arguments of a call to the panic import: a pointer to a RocStr, and a tag id
+    fn decode_roc_panic(&self) -> Error {
+        let (str_addr, tag_id) = match self.import_arguments[..] {
+            [Value::I32(str_addr), Value::I32(tag_id)] => (str_addr as u32, tag_id as u32),
+            _ => unreachable!("the panic import is only intercepted with this signature"),
+        };
+        let message_bytes = match memory::roc_str_bytes(&self.memory, str_addr) {
+            Ok(bytes) => bytes,
+            Err(MemoryError::OutOfBounds {
+                addr,
+                len,
+                memory_size,
+            }) => {
+                return Error::MemoryOutOfBounds {
+                    op_addr: self.program_counter,
+                    addr: addr as u64,
+                    size: len as usize,
+                    memory_size,
+                }
+            }
+            Err(error) => unreachable!("{}", error),
+        };
+
+        Error::RocPanic {
+            message: String::from_utf8_lossy(message_bytes).into_owned(),
+            kind: RocPanicKind::from(tag_id),
+        }
     }
 
     fn debug_call(&mut self, n_args: usize, return_type: Option<ValueType>) {
@@ -600,15 +1804,42 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
     ) -> Result<Action, Error> {
         use OpCode::*;
 
+        if let Some(fuel) = self.fuel.as_mut() {
+            if *fuel == 0 {
+                return Err(Error::OutOfFuel);
+            }
+            *fuel -= 1;
+        }
+
         let file_offset = self.program_counter as u32 + module.code.section_offset;
         let op_code = OpCode::from(module.code.bytes[self.program_counter]);
         self.program_counter += 1;
 
+        let fn_index = self.current_frame.fn_index;
+        let is_traced = match self.tracer.as_mut() {
+            Some(tracer) => {
+                let is_traced = tracer.next_instruction_is_traced();
+                if !self.is_debug_mode {
+                    // Borrow the tracer's buffer, so that the instruction's immediates get written to it
+                    self.debug_string = is_traced.then(|| std::mem::take(&mut tracer.buffer));
+                }
+                is_traced
+            }
+            None => false,
+        };
+
         if let Some(debug_string) = self.debug_string.as_mut() {
             debug_string.clear();
             self.write_debug(op_code);
         }
 
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.count_instruction(self.current_frame.fn_index);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.mark_instruction(self.program_counter - 1);
+        }
+
         let mut action = Action::Continue;
         let mut implicit_return = false;
 
@@ -618,9 +1849,9 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             }
             NOP => {}
             BLOCK => {
-                self.fetch_immediate_u32(module); // blocktype (ignored)
+                self.fetch_immediate_u32(module); // blocktype (in the control table)
                 self.blocks.push(Block {
-                    ty: BlockType::Normal,
+                    ty: BlockType::Normal(self.program_counter),
                     vstack: self.value_store.depth(),
                 });
             }
@@ -632,52 +1863,23 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 });
             }
             IF => {
-                self.fetch_immediate_u32(module); // blocktype (ignored)
+                self.fetch_immediate_u32(module); // blocktype (in the control table)
+                let start_addr = self.program_counter;
                 let condition = self.value_store.pop_i32()?;
                 self.blocks.push(Block {
-                    ty: BlockType::Normal,
+                    ty: BlockType::Normal(start_addr),
                     vstack: self.value_store.depth(),
                 });
                 if condition == 0 {
-                    let addr = self.program_counter as u32;
-                    let cache_result = self.branch_cache[self.current_frame.fn_index]
-                        .iter()
-                        .find(|entry| entry.addr == addr);
-                    if let Some(entry) = cache_result {
-                        self.program_counter = entry.target as usize;
-                    } else {
-                        let target_depth = self.blocks.len();
-                        let mut depth = target_depth;
-                        loop {
-                            let skipped_op = OpCode::from(module.code.bytes[self.program_counter]);
-                            OpCode::skip_bytes(&module.code.bytes, &mut self.program_counter)
-                                .unwrap();
-                            match skipped_op {
-                                BLOCK | LOOP | IF => {
-                                    depth += 1;
-                                }
-                                END => {
-                                    if depth == target_depth {
-                                        // `if` without `else`
-                                        self.blocks.pop();
-                                        break;
-                                    } else {
-                                        depth -= 1;
-                                    }
-                                }
-                                ELSE => {
-                                    if depth == target_depth {
-                                        break;
-                                    }
-                                }
-                                _ => {}
-                            }
+                    let targets = self.block_targets(module, start_addr)?;
+                    match targets.else_addr {
+                        Some(else_addr) => {
+                            self.program_counter = else_addr as usize;
+                        }
+                        None => {
+                            self.blocks.pop();
+                            self.program_counter = targets.end as usize;
                         }
-                        self.branch_cache[self.current_frame.fn_index].push(BranchCacheEntry {
-                            addr,
-                            argument: 0,
-                            target: self.program_counter as u32,
-                        });
                     }
                 }
             }
@@ -685,7 +1887,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 // We only reach this point when we finish executing the "then" block of an IF statement
                 // (For a false condition, we would have skipped past the ELSE when we saw the IF)
                 // We don't want to execute the ELSE block, so we skip it, just like `br 0` would.
-                self.do_break(0, module);
+                action = self.do_break(0, module)?;
             }
             END => {
                 if self.blocks.len() == (self.current_frame.body_block_index + 1) {
@@ -698,13 +1900,13 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             }
             BR => {
                 let relative_blocks_outward = self.fetch_immediate_u32(module);
-                self.do_break(relative_blocks_outward, module);
+                action = self.do_break(relative_blocks_outward, module)?;
             }
             BRIF => {
                 let relative_blocks_outward = self.fetch_immediate_u32(module);
                 let condition = self.value_store.pop_i32()?;
                 if condition != 0 {
-                    self.do_break(relative_blocks_outward, module);
+                    action = self.do_break(relative_blocks_outward, module)?;
                 }
             }
             BRTABLE => {
@@ -719,14 +1921,14 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 }
                 let fallback = self.fetch_immediate_u32(module);
                 let relative_blocks_outward = selected.unwrap_or(fallback);
-                self.do_break(relative_blocks_outward, module);
+                action = self.do_break(relative_blocks_outward, module)?;
             }
             RETURN => {
                 action = self.do_return();
             }
             CALL => {
                 let fn_index = self.fetch_immediate_u32(module) as usize;
-                self.do_call(None, fn_index, module)?;
+                action = self.do_call(fn_index, module)?;
             }
             CALLINDIRECT => {
                 let expected_signature = self.fetch_immediate_u32(module);
@@ -734,21 +1936,37 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 let element_index = self.value_store.pop_u32()?;
 
                 // So far, all compilers seem to be emitting MVP-compatible code. (Rust, Zig, Roc...)
-                assert_eq!(
-                    table_index, 0,
-                    "Table index {} not supported at file offset {:#x}. This interpreter only supports Wasm MVP.",
-                    table_index, file_offset
-                );
+                if table_index != 0 {
+                    return Err(Error::UnsupportedTable(table_index));
+                }
 
-                // Dereference the function pointer (look up the element index in the function table)
-                let fn_index = module.element.lookup(element_index).unwrap_or_else(|| {
-                    panic!(
-                        "Indirect function call failed. There is no function with element index {}",
-                        element_index
-                    )
-                });
+                if self.shared_table && self.defer_imports {
+                    // Other modules may have written to the table, so the group looks it up
+                    action = Action::CallImportedTable {
+                        element_index,
+                        signature_index: expected_signature,
+                    };
+                } else {
+                    // Dereference the function pointer (look up the element index in the function table)
+                    let fn_index = table_lookup(
+                        module,
+                        element_index,
+                        self.resource_limits.max_table_elements,
+                    )?;
+                    let expected = FunctionType::look_up(module, expected_signature);
+                    let actual = self.function_type(fn_index as usize);
+                    if actual != expected {
+                        return Err(Error::IndirectCallSignature {
+                            table_index,
+                            element_index,
+                            fn_index,
+                            expected,
+                            actual,
+                        });
+                    }
 
-                self.do_call(Some(expected_signature), fn_index as usize, module)?;
+                    action = self.do_call(fn_index as usize, module)?;
+                }
             }
             DROP => {
                 self.value_store.pop();
@@ -791,149 +2009,149 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 self.globals[index as usize] = self.value_store.pop();
             }
             I32LOAD => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 4)?;
                 let mut bytes = [0; 4];
                 bytes.copy_from_slice(&self.memory[addr..][..4]);
                 let value = i32::from_le_bytes(bytes);
                 self.value_store.push(Value::I32(value));
             }
             I64LOAD => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 8)?;
                 let mut bytes = [0; 8];
                 bytes.copy_from_slice(&self.memory[addr..][..8]);
                 let value = i64::from_le_bytes(bytes);
                 self.value_store.push(Value::I64(value));
             }
             F32LOAD => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 4)?;
                 let mut bytes = [0; 4];
                 bytes.copy_from_slice(&self.memory[addr..][..4]);
                 let value = f32::from_le_bytes(bytes);
                 self.value_store.push(Value::F32(value));
             }
             F64LOAD => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 8)?;
                 let mut bytes = [0; 8];
                 bytes.copy_from_slice(&self.memory[addr..][..8]);
                 let value = f64::from_le_bytes(bytes);
                 self.value_store.push(Value::F64(value));
             }
             I32LOAD8S => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 1)?;
                 let mut bytes = [0; 1];
                 bytes.copy_from_slice(&self.memory[addr..][..1]);
                 let value = i8::from_le_bytes(bytes);
                 self.value_store.push(Value::I32(value as i32));
             }
             I32LOAD8U => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 1)?;
                 let value = self.memory[addr];
                 self.value_store.push(Value::I32(value as i32));
             }
             I32LOAD16S => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 2)?;
                 let mut bytes = [0; 2];
                 bytes.copy_from_slice(&self.memory[addr..][..2]);
                 let value = i16::from_le_bytes(bytes);
                 self.value_store.push(Value::I32(value as i32));
             }
             I32LOAD16U => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 2)?;
                 let mut bytes = [0; 2];
                 bytes.copy_from_slice(&self.memory[addr..][..2]);
                 let value = u16::from_le_bytes(bytes);
                 self.value_store.push(Value::I32(value as i32));
             }
             I64LOAD8S => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 1)?;
                 let mut bytes = [0; 1];
                 bytes.copy_from_slice(&self.memory[addr..][..1]);
                 let value = i8::from_le_bytes(bytes);
                 self.value_store.push(Value::I64(value as i64));
             }
             I64LOAD8U => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 1)?;
                 let value = self.memory[addr];
                 self.value_store.push(Value::I64(value as i64));
             }
             I64LOAD16S => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 2)?;
                 let mut bytes = [0; 2];
                 bytes.copy_from_slice(&self.memory[addr..][..2]);
                 let value = i16::from_le_bytes(bytes);
                 self.value_store.push(Value::I64(value as i64));
             }
             I64LOAD16U => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 2)?;
                 let mut bytes = [0; 2];
                 bytes.copy_from_slice(&self.memory[addr..][..2]);
                 let value = u16::from_le_bytes(bytes);
                 self.value_store.push(Value::I64(value as i64));
             }
             I64LOAD32S => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 4)?;
                 let mut bytes = [0; 4];
                 bytes.copy_from_slice(&self.memory[addr..][..4]);
                 let value = i32::from_le_bytes(bytes);
                 self.value_store.push(Value::I64(value as i64));
             }
             I64LOAD32U => {
-                let addr = self.get_load_address(module)? as usize;
+                let addr = self.get_load_address(module, 4)?;
                 let mut bytes = [0; 4];
                 bytes.copy_from_slice(&self.memory[addr..][..4]);
                 let value = u32::from_le_bytes(bytes);
                 self.value_store.push(Value::I64(value as i64));
             }
             I32STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 4)?;
                 let unwrapped = value.expect_i32().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..4];
                 target.copy_from_slice(&unwrapped.to_le_bytes());
             }
             I64STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 8)?;
                 let unwrapped = value.expect_i64().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..8];
                 target.copy_from_slice(&unwrapped.to_le_bytes());
             }
             F32STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 4)?;
                 let unwrapped = value.expect_f32().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..4];
                 target.copy_from_slice(&unwrapped.to_le_bytes());
             }
             F64STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 8)?;
                 let unwrapped = value.expect_f64().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..8];
                 target.copy_from_slice(&unwrapped.to_le_bytes());
             }
             I32STORE8 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 1)?;
                 let unwrapped = value.expect_i32().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..1];
                 target.copy_from_slice(&unwrapped.to_le_bytes()[..1]);
             }
             I32STORE16 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 2)?;
                 let unwrapped = value.expect_i32().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..2];
                 target.copy_from_slice(&unwrapped.to_le_bytes()[..2]);
             }
             I64STORE8 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 1)?;
                 let unwrapped = value.expect_i64().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..1];
                 target.copy_from_slice(&unwrapped.to_le_bytes()[..1]);
             }
             I64STORE16 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 2)?;
                 let unwrapped = value.expect_i64().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..2];
                 target.copy_from_slice(&unwrapped.to_le_bytes()[..2]);
             }
             I64STORE32 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
+                let (addr, value) = self.get_store_addr_value(module, 4)?;
                 let unwrapped = value.expect_i64().map_err(Error::from)?;
                 let target = &mut self.memory[addr..][..4];
                 target.copy_from_slice(&unwrapped.to_le_bytes()[..4]);
@@ -941,29 +2159,19 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             CURRENTMEMORY => {
                 let memory_index = self.fetch_immediate_u32(module);
                 assert_eq!(memory_index, 0);
-                let size = self.memory.len() as i32 / MemorySection::PAGE_SIZE as i32;
-                self.value_store.push(Value::I32(size));
+                let size = self.memory_pages();
+                self.value_store.push(Value::I32(size as i32));
             }
             GROWMEMORY => {
                 let memory_index = self.fetch_immediate_u32(module);
                 assert_eq!(memory_index, 0);
-                let old_bytes = self.memory.len() as u32;
-                let old_pages = old_bytes / MemorySection::PAGE_SIZE;
                 let grow_pages = self.value_store.pop_u32()?;
-                let grow_bytes = grow_pages * MemorySection::PAGE_SIZE;
-                let new_bytes = old_bytes + grow_bytes;
-
-                let success = match module.memory.max_bytes().unwrap() {
-                    Some(max_bytes) => new_bytes <= max_bytes,
-                    None => true,
+                let module_max_pages = module.memory.max_pages().unwrap();
+                let result = match self.grow_memory_help(module_max_pages, grow_pages) {
+                    Some(old_pages) => old_pages as i32,
+                    None => -1,
                 };
-                if success {
-                    self.memory
-                        .extend(iter::repeat(0).take(grow_bytes as usize));
-                    self.value_store.push(Value::I32(old_pages as i32));
-                } else {
-                    self.value_store.push(Value::I32(-1));
-                }
+                self.value_store.push(Value::I32(result));
             }
             I32CONST => {
                 let value = i32::parse((), &module.code.bytes, &mut self.program_counter).unwrap();
@@ -1692,9 +2900,21 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 self.value_store
                     .push(Value::F64(f64::from_ne_bytes(x.to_ne_bytes())));
             }
+
+            #[cfg(feature = "simd")]
+            SIMDPREFIX => self.execute_simd(module)?,
+            #[cfg(not(feature = "simd"))]
+            SIMDPREFIX => {
+                let simd_op = self.fetch_immediate_u32(module);
+                return Err(Error::UnsupportedSimdOp(simd_op));
+            }
         }
 
-        if let Some(debug_string) = &self.debug_string {
+        if is_traced {
+            self.write_trace(module, file_offset, fn_index);
+        }
+
+        if let Some(debug_string) = self.debug_string.as_ref().filter(|_| self.is_debug_mode) {
             if matches!(op_code, CALL | CALLINDIRECT) {
                 eprintln!("\n{:06x} {}", file_offset, debug_string);
             } else {
@@ -1717,6 +2937,23 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         Ok(action)
     }
 
+    fn write_trace(&mut self, module: &WasmModule<'a>, file_offset: u32, fn_index: usize) {
+        let tracer = match self.tracer.as_mut() {
+            Some(tracer) => tracer,
+            None => return,
+        };
+        if !self.is_debug_mode {
+            // Give the buffer back
+            tracer.buffer = self.debug_string.take().unwrap_or_default();
+        } else if let Some(debug_string) = &self.debug_string {
+            tracer.buffer.clone_from(debug_string);
+        }
+
+        let base = self.current_frame.locals_start + self.current_frame.locals_count;
+        let stack_top = self.value_store.get_slice(base).last();
+        tracer.write_instruction(module, self.import_count, file_offset, fn_index, stack_top);
+    }
+
     #[allow(dead_code)]
     fn debug_values_and_blocks(&self, label: &str) {
         eprintln!("\n========== {} ==========", label);
@@ -1768,16 +3005,31 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         // For the current frame, show the program counter value
         let mut execution_addrs = {
             // for each previous_frame, find return address of the *next* frame
-            let return_addrs = next_frames.clone().map(|f| f.return_addr);
             // roll back to the CALL instruction before that return address, it's more meaningful.
-            let call_addrs = return_addrs.map(|ra| self.debug_return_addr_to_call_addr(ra));
+            // Frames that were entered from another instance in a group were not called from here.
+            let call_addrs = next_frames.clone().enumerate().map(|(i, f)| {
+                if self.entry_depths.contains(&(i + 1)) {
+                    0
+                } else {
+                    self.debug_return_addr_to_call_addr(f.return_addr)
+                }
+            });
             // For the current frame, show the program_counter
             call_addrs.chain(once(self.program_counter))
         };
 
         let mut frame_ends = next_frames.map(|f| f.locals_start);
 
-        for frame in frames {
+        for (i, frame) in frames.enumerate() {
+            let execution_addr = execution_addrs.next().unwrap();
+            let frame_end = frame_ends
+                .next()
+                .unwrap_or_else(|| self.value_store.depth());
+            if self.entry_depths.contains(&(i + 1)) {
+                // Placeholder frame, saved when the instance was entered from outside
+                continue;
+            }
+
             let Frame {
                 fn_index,
                 locals_count,
@@ -1787,7 +3039,8 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
             let arg_count = {
                 let signature_index = if *fn_index < self.import_count {
-                    match self.module.import.imports[*fn_index].description {
+                    let import_index = self.function_imports[*fn_index];
+                    match self.module.import.imports[import_index].description {
                         ImportDesc::Func { signature_index } => signature_index,
                         _ => unreachable!(),
                     }
@@ -1799,7 +3052,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
             // Function and address match wasm-objdump formatting, for easy copy & find
             writeln!(buffer, "func[{}]", fn_index)?;
-            writeln!(buffer, "  address  {:06x}", execution_addrs.next().unwrap())?;
+            writeln!(buffer, "  address  {:06x}", execution_addr)?;
 
             write!(buffer, "  args     ")?;
             for local_index in 0..*locals_count {
@@ -1813,9 +3066,6 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             }
 
             write!(buffer, "\n  stack    [")?;
-            let frame_end = frame_ends
-                .next()
-                .unwrap_or_else(|| self.value_store.depth());
             let stack_start = locals_start + locals_count;
             for i in stack_start..frame_end {
                 let value = self.value_store.get(i).unwrap();
@@ -1848,3 +3098,58 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         call_addr
     }
 }
+
+/// Dereference a function pointer, by looking up an element index in the module's function table.
+/// The table may be imported, in which case `module` is the one that owns it.
+pub(crate) fn table_lookup(
+    module: &WasmModule,
+    element_index: u32,
+    max_table_elements: u32,
+) -> Result<u32, Error> {
+    let table_index = 0;
+    // Finding the table's size means searching the imports, so skip it on the fast path if we can
+    if max_table_elements < u32::MAX {
+        let table_size = table_size(module);
+        if table_size > max_table_elements {
+            return Err(Error::TableLimitExceeded {
+                table_size,
+                limit: max_table_elements,
+            });
+        }
+    }
+
+    if let Some(fn_index) = module.element.lookup(element_index) {
+        return Ok(fn_index);
+    }
+
+    let table_size = table_size(module);
+    if element_index < table_size {
+        Err(Error::IndirectCallNull {
+            table_index,
+            element_index,
+        })
+    } else {
+        Err(Error::IndirectCallOutOfBounds {
+            table_index,
+            element_index,
+            table_size,
+        })
+    }
+}
+
+pub(crate) fn table_size(module: &WasmModule) -> u32 {
+    let imported_table = module
+        .import
+        .imports
+        .iter()
+        .find_map(|imp| match &imp.description {
+            ImportDesc::Table { ty } => Some(ty),
+            _ => None,
+        });
+    let table_type = imported_table.unwrap_or(&module.table.function_table);
+    let min_size = match table_type.limits {
+        Limits::Min(min) | Limits::MinMax(min, _) => min,
+    };
+    // Some modules declare a smaller table than their element segments need, so trust the segments too
+    min_size.max(module.element.max_table_index())
+}
@@ -1,25 +1,106 @@
+#[cfg(feature = "canary")]
+mod canary;
+mod capabilities;
+mod chain;
+mod clock;
+mod control;
+mod coverage;
 mod frame;
+mod group;
 mod instance;
+mod memory;
+mod pool;
+mod profile;
+mod replay;
+mod roc_value;
+#[cfg(feature = "simd")]
+mod simd;
 mod tests;
+mod trace;
+mod typed;
 mod value_store;
 pub mod wasi;
+#[cfg(feature = "watchpoints")]
+mod watch;
 
 // Main external interface
-pub use instance::Instance;
-pub use wasi::{WasiDispatcher, WasiFile};
+#[cfg(feature = "canary")]
+pub use canary::CANARY;
+pub use capabilities::{Capabilities, Proposal};
+pub use chain::{ChainDispatcher, MissingImports};
+pub use clock::{Clock, ClockId, ManualClock, SystemClock};
+pub use control::ControlTable;
+pub use coverage::{CoverageReport, FunctionCoverage};
+pub use group::InstanceGroup;
+pub use instance::{Instance, Progress, RunResult};
+pub use memory::{Memory, MemoryError};
+pub use pool::InstancePool;
+pub use profile::{FunctionCounters, ProfileEntry, ProfileReport};
+pub use replay::{ImportCall, ImportLog, MemoryWrite, RecordingDispatcher, ReplayDispatcher};
+pub use roc_value::{RocLayout, RocValue};
+pub use trace::TraceWindow;
+pub use typed::{ExportInfo, ExportKind, WasmParams, WasmResult, WasmValue};
+pub use wasi::{
+    run_wasi, ChannelReader, ExitStatus, LineCallback, WasiCall, WasiDispatcher, WasiFile,
+};
+#[cfg(feature = "watchpoints")]
+pub use watch::{WatchAccess, WatchAction, Watchpoint, WatchpointHit};
 
+use roc_wasm_module::parse::ParseError;
+use roc_wasm_module::sections::SectionId;
 pub use roc_wasm_module::Value;
-use roc_wasm_module::ValueType;
+use roc_wasm_module::{GlobalType, ValueType, WasmModule};
+use std::fmt;
 
 pub trait ImportDispatcher {
     /// Dispatch a call from WebAssembly to your own code, based on module and function name.
+    /// `memory` is the memory of the module that made the call.
     fn dispatch(
         &mut self,
         module_name: &str,
         function_name: &str,
         arguments: &[Value],
-        memory: &mut [u8],
+        memory: &mut Memory<'_>,
     ) -> Option<Value>;
+
+    /// Provide the initial value of a global imported from outside of WebAssembly, like a
+    /// `__stack_pointer` for the module to use. It must have the type given in `global_type`.
+    /// Modules that import globals can't be instantiated without this.
+    fn global(
+        &mut self,
+        _module_name: &str,
+        _global_name: &str,
+        _global_type: GlobalType,
+    ) -> Option<Value> {
+        None
+    }
+
+    /// Whether [dispatch](ImportDispatcher::dispatch) can handle calls to this function.
+    /// An imported function that isn't provided is handled according to
+    /// [missing_imports](ImportDispatcher::missing_imports), and never reaches the dispatcher.
+    fn provides(&self, _module_name: &str, _function_name: &str) -> bool {
+        true
+    }
+
+    /// What to do with the imported functions that this dispatcher doesn't provide
+    fn missing_imports(&self) -> MissingImports {
+        MissingImports::Trap
+    }
+
+    /// The status that the program asked to exit with, e.g. by calling WASI's `proc_exit`.
+    /// This is checked after every call to [dispatch](ImportDispatcher::dispatch), and once it's
+    /// set, the running call ends with an error instead of returning to the program.
+    fn exit_status(&self) -> Option<i32> {
+        None
+    }
+
+    /// Why the dispatcher can't go on answering calls, e.g. because a replayed log no longer
+    /// matches what the program does. Like [exit_status](ImportDispatcher::exit_status), this is
+    /// checked after every call to [dispatch](ImportDispatcher::dispatch), and once it's set, the
+    /// running call ends with an error.
+    fn host_error(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl Default for DefaultImportDispatcher<'_> {
@@ -48,7 +129,7 @@ impl<'a> ImportDispatcher for DefaultImportDispatcher<'a> {
         module_name: &str,
         function_name: &str,
         arguments: &[Value],
-        memory: &mut [u8],
+        memory: &mut Memory<'_>,
     ) -> Option<Value> {
         if module_name == wasi::MODULE_NAME {
             self.wasi.dispatch(function_name, arguments, memory)
@@ -59,6 +140,192 @@ impl<'a> ImportDispatcher for DefaultImportDispatcher<'a> {
             );
         }
     }
+
+    fn provides(&self, module_name: &str, _function_name: &str) -> bool {
+        module_name == wasi::MODULE_NAME
+    }
+
+    fn exit_status(&self) -> Option<i32> {
+        self.wasi.exit_status
+    }
+}
+
+/// How much detail to report when the program loads or stores outside of its memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryErrorMode {
+    /// Trap with a short message, like other WebAssembly runtimes
+    #[default]
+    Trap,
+    /// Also report the address, the memory size, where in the function it happened,
+    /// and a hex dump of the end of the memory
+    Explain,
+}
+
+/// Caps on how much of the host's resources a call can use, for running untrusted modules.
+/// A call that goes over one of them ends with an error, rather than exhausting the host's memory.
+/// The default is no limits. See also [Instance::set_memory_limit].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Maximum number of function calls in progress, including the one that began the call
+    pub max_call_depth: usize,
+    /// Maximum number of values on the stack, including the locals of every call in progress.
+    /// Checked whenever a function is called. Validation stops any one function from growing the
+    /// stack without bound, so this caps the total.
+    pub max_stack_values: usize,
+    /// Maximum number of elements in the function table that `call_indirect` can use
+    pub max_table_elements: u32,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            max_call_depth: usize::MAX,
+            max_stack_values: usize::MAX,
+            max_table_elements: u32::MAX,
+        }
+    }
+}
+
+/// What kind of failure a Roc program reported when it called `roc_panic`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RocPanicKind {
+    /// Something went wrong inside Roc itself, like an integer overflow
+    RocFailure,
+    /// The program called `crash`
+    UserCrash,
+    /// A tag id that Roc doesn't use
+    Unknown(u32),
+}
+
+impl From<u32> for RocPanicKind {
+    fn from(tag_id: u32) -> Self {
+        match tag_id {
+            0 => RocPanicKind::RocFailure,
+            1 => RocPanicKind::UserCrash,
+            _ => RocPanicKind::Unknown(tag_id),
+        }
+    }
+}
+
+/// A call to the panic import, decoded from the arguments and memory of the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RocPanic {
+    pub message: String,
+    pub kind: RocPanicKind,
+    /// The WebAssembly stack at the time of the call
+    pub backtrace: String,
+}
+
+/// Why a module couldn't be loaded. Neither kind of error leaves anything behind, so an embedder
+/// like the REPL can report it and carry on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The bytes are not a module that this interpreter can decode. None of it was run.
+    InvalidModule {
+        /// The section with the problem, or `None` if it's in the file header or the module as a
+        /// whole, e.g. a missing section
+        section: Option<SectionId>,
+        /// Position of the problem in the module's bytes
+        offset: usize,
+        message: String,
+    },
+    /// The module needs a WebAssembly proposal that this build of the interpreter doesn't support.
+    /// See [Capabilities]. None of it was run.
+    Unsupported {
+        proposal: Proposal,
+        /// The section where the proposal is first used
+        section: SectionId,
+        /// Position of the first instruction from the proposal in the module's bytes
+        offset: usize,
+    },
+    /// The module was decoded, but instantiating it failed, e.g. because of a missing import or a
+    /// data segment that doesn't fit in memory
+    Trap(String),
+}
+
+impl LoadError {
+    pub(crate) fn invalid_module(module_bytes: &[u8], error: ParseError) -> Self {
+        LoadError::InvalidModule {
+            section: SectionId::at_offset(module_bytes, error.offset),
+            offset: error.offset,
+            message: error.message,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::InvalidModule {
+                section: Some(section),
+                offset,
+                message,
+            } => write!(
+                f,
+                "Invalid module: {} (in the {:?} section, at offset {:#x})",
+                message, section, offset
+            ),
+            LoadError::InvalidModule {
+                section: None,
+                offset,
+                message,
+            } => write!(f, "Invalid module: {} (at offset {:#x})", message, offset),
+            LoadError::Unsupported {
+                proposal,
+                section,
+                offset,
+            } => {
+                write!(
+                    f,
+                    "This module needs the `{}` WebAssembly proposal (first used in the {:?} section, at offset {:#x}), which this build of the interpreter doesn't support",
+                    proposal, section, offset
+                )?;
+                if *proposal == Proposal::Simd {
+                    write!(f, ". Build it with the `simd` feature to enable it.")?;
+                }
+                Ok(())
+            }
+            LoadError::Trap(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<LoadError> for String {
+    fn from(error: LoadError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Why a call to an exported function failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    /// The call couldn't start, because there is no such export or the arguments don't match its
+    /// parameters. None of it was run.
+    InvalidCall(String),
+    /// The program called the panic import. See [Instance::set_panic_import].
+    RocPanic {
+        panic: RocPanic,
+        /// Where the panic was called, and the WebAssembly stack at the time
+        message: String,
+    },
+    /// The program trapped, or an import failed. The message includes a WebAssembly stack trace.
+    Trap(String),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallError::InvalidCall(message)
+            | CallError::RocPanic { message, .. }
+            | CallError::Trap(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<CallError> for String {
+    fn from(error: CallError) -> Self {
+        error.to_string()
+    }
 }
 
 /// Errors that can happen while interpreting the program
@@ -68,6 +335,115 @@ pub(crate) enum Error {
     Type(ValueType, ValueType),
     StackEmpty,
     UnreachableOp,
+    MemoryOutOfBounds {
+        /// Index in the code section of the load or store instruction
+        op_addr: usize,
+        addr: u64,
+        size: usize,
+        memory_size: usize,
+    },
+    /// The program called the panic import. See [Instance::set_panic_import].
+    RocPanic {
+        message: String,
+        kind: RocPanicKind,
+    },
+    /// An instruction from the SIMD proposal that this build can't execute
+    UnsupportedSimdOp(u32),
+    /// A SIMD instruction with a lane index past the end of its vectors
+    InvalidLane {
+        lane: u8,
+        n_lanes: usize,
+    },
+    /// `call_indirect` on a table other than table 0. Only Wasm MVP is supported.
+    UnsupportedTable(u32),
+    /// `call_indirect` with an element index past the end of the table
+    IndirectCallOutOfBounds {
+        table_index: u32,
+        element_index: u32,
+        table_size: u32,
+    },
+    /// `call_indirect` to an element of the table that no element segment initialized
+    IndirectCallNull {
+        table_index: u32,
+        element_index: u32,
+    },
+    /// `call_indirect` to a function whose type is not the one the instruction expects
+    IndirectCallSignature {
+        table_index: u32,
+        element_index: u32,
+        fn_index: u32,
+        expected: FunctionType,
+        actual: FunctionType,
+    },
+    /// A call to an imported function that the import dispatcher doesn't provide
+    MissingImport {
+        module_name: String,
+        function_name: String,
+    },
+    /// A call that would go deeper than [ResourceLimits::max_call_depth]
+    CallDepthExceeded {
+        limit: usize,
+    },
+    /// A call that would put more values on the stack than [ResourceLimits::max_stack_values]
+    StackLimitExceeded {
+        depth: usize,
+        limit: usize,
+    },
+    /// `call_indirect` on a table bigger than [ResourceLimits::max_table_elements]
+    TableLimitExceeded {
+        table_size: u32,
+        limit: u32,
+    },
+    /// The instance ran as many instructions as [Instance::set_fuel] allowed
+    OutOfFuel,
+    /// A load or store hit a watchpoint that traps
+    #[cfg(feature = "watchpoints")]
+    Watchpoint(watch::WatchpointHit),
+    /// A load from memory freed by the Roc allocator, or a second free, in canary mode
+    #[cfg(feature = "canary")]
+    Canary {
+        /// Index in the code section of the load instruction, or of the call to the allocator
+        op_addr: usize,
+        trap: canary::CanaryTrap,
+    },
+    /// The program asked to exit. See [ImportDispatcher::exit_status].
+    Exit(i32),
+    /// The import dispatcher failed. See [ImportDispatcher::host_error].
+    Host(String),
+    /// A function body that can't be decoded, found when branching inside it
+    InvalidCode {
+        /// File offset of the problem
+        offset: usize,
+        message: String,
+    },
+}
+
+/// The parameter and result types of a function, compared structurally rather than by signature
+/// index, so that identical types from different modules (or duplicate entries) are equal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FunctionType {
+    pub params: Vec<ValueType>,
+    pub result: Option<ValueType>,
+}
+
+impl FunctionType {
+    pub fn look_up(module: &WasmModule, signature_index: u32) -> Self {
+        let (params, result) = module.types.look_up(signature_index);
+        FunctionType {
+            params: params.collect(),
+            result,
+        }
+    }
+}
+
+impl fmt::Display for FunctionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let results: &[ValueType] = match &self.result {
+            Some(result) => std::slice::from_ref(result),
+            None => &[],
+        };
+        write!(f, "{:?} -> {:?}", self.params, results)
+    }
 }
 
 impl Error {
@@ -91,6 +467,134 @@ impl Error {
                     file_offset
                 )
             }
+            Error::MemoryOutOfBounds { .. } => {
+                format!(
+                    "WebAssembly out of bounds memory access at file offset {:#x}.\n",
+                    file_offset
+                )
+            }
+            Error::RocPanic { message, kind } => {
+                let description = match kind {
+                    RocPanicKind::RocFailure => "Roc failed".to_string(),
+                    RocPanicKind::UserCrash => "User crash".to_string(),
+                    RocPanicKind::Unknown(tag_id) => format!("Roc panic with tag {}", tag_id),
+                };
+                format!(
+                    "{} with message: \"{}\"\nThe panic was called at file offset {:#x}.\n",
+                    description, message, file_offset
+                )
+            }
+            Error::UnsupportedSimdOp(simd_op) => {
+                let reason = if cfg!(feature = "simd") {
+                    "it's not an instruction I know"
+                } else {
+                    "this interpreter was built without the `simd` feature"
+                };
+                format!(
+                    "ERROR: I can't execute the SIMD instruction 0xfd {:#04x} at file offset {:#x}, because {}.\n",
+                    simd_op, file_offset, reason
+                )
+            }
+            Error::InvalidLane { lane, n_lanes } => {
+                format!(
+                    "ERROR: I found a SIMD instruction for lane {} at file offset {:#x}, but its vectors only have {} lanes.\n",
+                    lane, file_offset, n_lanes
+                )
+            }
+            Error::UnsupportedTable(table_index) => {
+                format!(
+                    "ERROR: I found a call_indirect to table {} at file offset {:#x}, but this interpreter only supports Wasm MVP, which has a single table.\n",
+                    table_index, file_offset
+                )
+            }
+            Error::IndirectCallOutOfBounds {
+                table_index,
+                element_index,
+                table_size,
+            } => {
+                format!(
+                    "ERROR: I found a call_indirect to element {} of table {} at file offset {:#x}, but the table only has {} elements.\n",
+                    element_index, table_index, file_offset, table_size
+                )
+            }
+            Error::IndirectCallNull {
+                table_index,
+                element_index,
+            } => {
+                format!(
+                    "ERROR: I found a call_indirect to element {} of table {} at file offset {:#x}, but that element is null.\n",
+                    element_index, table_index, file_offset
+                )
+            }
+            Error::IndirectCallSignature {
+                table_index,
+                element_index,
+                fn_index,
+                expected,
+                actual,
+            } => {
+                format!(
+                    "ERROR: I found a call_indirect with the wrong function type at file offset {:#x}. Expected {}, but element {} of table {} is func[{}], which has type {}.\n",
+                    file_offset, expected, element_index, table_index, fn_index, actual
+                )
+            }
+            Error::MissingImport {
+                module_name,
+                function_name,
+            } => {
+                format!(
+                    "ERROR: I found a call to the imported function {}.{} at file offset {:#x}, but the import dispatcher doesn't provide it.\n",
+                    module_name, function_name, file_offset
+                )
+            }
+            Error::CallDepthExceeded { limit } => {
+                format!(
+                    "ERROR: I reached the limit of {} nested calls at file offset {:#x}.\n",
+                    limit, file_offset
+                )
+            }
+            Error::StackLimitExceeded { depth, limit } => {
+                format!(
+                    "ERROR: I reached the limit of {} values on the stack at file offset {:#x}. The stack has {} values.\n",
+                    limit, file_offset, depth
+                )
+            }
+            Error::TableLimitExceeded { table_size, limit } => {
+                format!(
+                    "ERROR: I found a call_indirect at file offset {:#x}, but the table has {} elements, more than the limit of {}.\n",
+                    file_offset, table_size, limit
+                )
+            }
+            Error::OutOfFuel => {
+                format!(
+                    "ERROR: I ran out of fuel before executing the instruction at file offset {:#x}.\n",
+                    file_offset
+                )
+            }
+            #[cfg(feature = "watchpoints")]
+            Error::Watchpoint(hit) => format!("{}.\n", hit),
+            #[cfg(feature = "canary")]
+            Error::Canary { trap, .. } => {
+                format!("{}, at file offset {:#x}.\n", trap, file_offset)
+            }
+            Error::Exit(status) => {
+                format!(
+                    "The program exited with status {} at file offset {:#x}.\n",
+                    status, file_offset
+                )
+            }
+            Error::Host(message) => {
+                format!(
+                    "ERROR: The import dispatcher failed at file offset {:#x}: {}\n",
+                    file_offset, message
+                )
+            }
+            Error::InvalidCode { offset, message } => {
+                format!(
+                    "ERROR: I found a branch at file offset {:#x}, but I couldn't decode the function it's in. {} (at file offset {:#x})\n",
+                    file_offset, message, offset
+                )
+            }
         }
     }
 }
@@ -2,16 +2,36 @@ use bumpalo::{collections::Vec, Bump};
 use clap::ArgAction;
 use clap::{Arg, Command};
 use std::fs;
-use std::io;
+use std::io::{self, BufWriter};
 use std::iter::once;
 use std::process;
 
-use roc_wasm_interp::{DefaultImportDispatcher, Instance};
-use roc_wasm_module::WasmModule;
+use roc_wasm_interp::{
+    Capabilities, DefaultImportDispatcher, ImportDispatcher, ImportLog, Instance, MemoryErrorMode,
+    RecordingDispatcher, ReplayDispatcher, RocLayout, TraceWindow, WasiFile,
+};
+use roc_wasm_module::sections::SectionId;
+use roc_wasm_module::{Value, WasmModule};
 
 pub const FLAG_FUNCTION: &str = "function";
+pub const FLAG_ARG: &str = "arg";
+pub const FLAG_DIR: &str = "dir";
+pub const FLAG_ENV: &str = "env";
+pub const FLAG_FUEL: &str = "fuel";
 pub const FLAG_DEBUG: &str = "debug";
 pub const FLAG_HEX: &str = "hex";
+pub const FLAG_ROC_RESULT: &str = "roc-result";
+pub const FLAG_PROFILE: &str = "profile";
+pub const FLAG_COVERAGE: &str = "coverage";
+pub const FLAG_EXPLAIN_MEMORY_ERRORS: &str = "explain-memory-errors";
+pub const FLAG_RECORD: &str = "record";
+pub const FLAG_REPLAY: &str = "replay";
+pub const FLAG_STDIN: &str = "stdin";
+pub const FLAG_TRACE: &str = "trace";
+pub const FLAG_TRACE_SKIP: &str = "trace-skip";
+pub const FLAG_TRACE_COUNT: &str = "trace-count";
+pub const FLAG_WASI_AUDIT: &str = "wasi-audit";
+pub const FLAG_WASI_ALLOW: &str = "wasi-allow";
 pub const WASM_FILE: &str = "WASM_FILE";
 pub const ARGS_FOR_APP: &str = "ARGS_FOR_APP";
 
@@ -22,10 +42,39 @@ fn main() -> io::Result<()> {
 
     let flag_function = Arg::new(FLAG_FUNCTION)
         .long(FLAG_FUNCTION)
+        .alias("invoke")
         .help("Call a specific function exported from the WebAssembly module")
         .default_value("_start")
         .required(false);
 
+    let flag_arg = Arg::new(FLAG_ARG)
+        .long(FLAG_ARG)
+        .help("Pass an argument to the called function, parsed according to its parameter type. Can be repeated.\nWithout this, the function's arguments are parsed from ARGS_FOR_APP.")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .required(false);
+
+    let flag_dir = Arg::new(FLAG_DIR)
+        .long(FLAG_DIR)
+        .help("Give the app a WASI preopened directory, where it can open files. Can be repeated.\nThe app sees it under the same path as it's given here.")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .required(false);
+
+    let flag_env = Arg::new(FLAG_ENV)
+        .long(FLAG_ENV)
+        .help("Set a WASI environment variable for the app, as KEY=VALUE. Can be repeated.")
+        .value_parser(parse_env_var)
+        .action(ArgAction::Append)
+        .required(false);
+
+    let flag_fuel = Arg::new(FLAG_FUEL)
+        .long(FLAG_FUEL)
+        .help("Stop with an error after executing this many instructions, e.g. to catch infinite loops")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64))
+        .required(false);
+
     let flag_debug = Arg::new(FLAG_DEBUG)
         .long(FLAG_DEBUG)
         .help("Print a log of every instruction executed, for debugging purposes.")
@@ -38,6 +87,86 @@ fn main() -> io::Result<()> {
         .action(ArgAction::SetTrue)
         .required(false);
 
+    let flag_roc_result = Arg::new(FLAG_ROC_RESULT)
+        .long(FLAG_ROC_RESULT)
+        .help("Treat the value returned by the called function as the address of a Roc value of the given type, e.g. `Result (List Str) I64`, and print that value.")
+        .takes_value(true)
+        .value_parser(|s: &str| s.parse::<RocLayout>())
+        .conflicts_with(FLAG_HEX)
+        .required(false);
+
+    let flag_profile = Arg::new(FLAG_PROFILE)
+        .long(FLAG_PROFILE)
+        .help("Count calls and executed instructions per function, and print a report to stderr when done.")
+        .action(ArgAction::SetTrue)
+        .required(false);
+
+    let flag_coverage = Arg::new(FLAG_COVERAGE)
+        .long(FLAG_COVERAGE)
+        .help("Write an lcov report of the executed instructions to the given file. Each instruction's file offset is used as its line number, and functions are named from the name section.")
+        .takes_value(true)
+        .required(false);
+
+    let flag_explain_memory_errors = Arg::new(FLAG_EXPLAIN_MEMORY_ERRORS)
+        .long(FLAG_EXPLAIN_MEMORY_ERRORS)
+        .help("On an out of bounds load or store, show the address, the memory size, and a hex dump of the end of the memory.")
+        .action(ArgAction::SetTrue)
+        .required(false);
+
+    let flag_record = Arg::new(FLAG_RECORD)
+        .long(FLAG_RECORD)
+        .help("Save the results of all calls to imported functions to the given file, so they can be replayed later.")
+        .takes_value(true)
+        .required(false);
+
+    let flag_replay = Arg::new(FLAG_REPLAY)
+        .long(FLAG_REPLAY)
+        .help("Instead of calling imported functions, use the results saved by --record in the given file.")
+        .takes_value(true)
+        .conflicts_with(FLAG_RECORD)
+        .required(false);
+
+    let flag_stdin = Arg::new(FLAG_STDIN)
+        .long(FLAG_STDIN)
+        .help("Read the app's standard input from the given file instead of the terminal.")
+        .takes_value(true)
+        .required(false);
+
+    let flag_trace = Arg::new(FLAG_TRACE)
+        .long(FLAG_TRACE)
+        .help("Write the file offset, function, immediates, and top of the stack of each executed instruction to the given file, or to stderr for `-`.\nOnly the instructions in the window set by --trace-skip and --trace-count are written.")
+        .takes_value(true)
+        .required(false);
+
+    let flag_trace_skip = Arg::new(FLAG_TRACE_SKIP)
+        .long(FLAG_TRACE_SKIP)
+        .help("Number of instructions to execute before the first one written by --trace")
+        .value_parser(clap::value_parser!(u64))
+        .default_value("0")
+        .requires(FLAG_TRACE)
+        .required(false);
+
+    let flag_trace_count = Arg::new(FLAG_TRACE_COUNT)
+        .long(FLAG_TRACE_COUNT)
+        .help("Maximum number of instructions written by --trace")
+        .value_parser(clap::value_parser!(u64))
+        .default_value("100000")
+        .requires(FLAG_TRACE)
+        .required(false);
+
+    let flag_wasi_audit = Arg::new(FLAG_WASI_AUDIT)
+        .long(FLAG_WASI_AUDIT)
+        .help("Print every WASI call the app makes to stderr, with its arguments and result.")
+        .action(ArgAction::SetTrue)
+        .required(false);
+
+    let flag_wasi_allow = Arg::new(FLAG_WASI_ALLOW)
+        .long(FLAG_WASI_ALLOW)
+        .help("Only let the app call the given WASI function, e.g. `fd_write`. Can be repeated.\nCalls to any other WASI function return the `notcapable` error code without being run.")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .required(false);
+
     let wasm_file_to_run = Arg::new(WASM_FILE)
         .help("The .wasm file to run")
         .required(true);
@@ -50,8 +179,24 @@ fn main() -> io::Result<()> {
     let app = Command::new("roc_wasm_interp")
         .about("Run the given .wasm file")
         .arg(flag_function)
+        .arg(flag_arg)
+        .arg(flag_dir)
+        .arg(flag_env)
+        .arg(flag_fuel)
         .arg(flag_debug)
         .arg(flag_hex)
+        .arg(flag_roc_result)
+        .arg(flag_profile)
+        .arg(flag_coverage)
+        .arg(flag_explain_memory_errors)
+        .arg(flag_record)
+        .arg(flag_replay)
+        .arg(flag_stdin)
+        .arg(flag_trace)
+        .arg(flag_trace_skip)
+        .arg(flag_trace_count)
+        .arg(flag_wasi_audit)
+        .arg(flag_wasi_allow)
         .arg(wasm_file_to_run)
         .trailing_var_arg(true)
         .arg(args_for_app);
@@ -60,8 +205,32 @@ fn main() -> io::Result<()> {
 
     let matches = app.get_matches();
     let start_fn_name = matches.get_one::<String>(FLAG_FUNCTION).unwrap();
+    let fn_arg_strings = matches.get_many::<String>(FLAG_ARG);
+    let preopen_dirs = matches.get_many::<String>(FLAG_DIR).unwrap_or_default();
+    let env_vars = matches
+        .get_many::<(String, String)>(FLAG_ENV)
+        .unwrap_or_default();
+    let fuel = matches.get_one::<u64>(FLAG_FUEL).copied();
     let is_debug_mode = matches.get_flag(FLAG_DEBUG);
     let is_hex_format = matches.get_flag(FLAG_HEX);
+    let roc_result = matches.get_one::<RocLayout>(FLAG_ROC_RESULT);
+    let is_profile_mode = matches.get_flag(FLAG_PROFILE);
+    let coverage_path = matches.get_one::<String>(FLAG_COVERAGE);
+    let memory_error_mode = if matches.get_flag(FLAG_EXPLAIN_MEMORY_ERRORS) {
+        MemoryErrorMode::Explain
+    } else {
+        MemoryErrorMode::Trap
+    };
+    let record_path = matches.get_one::<String>(FLAG_RECORD);
+    let replay_path = matches.get_one::<String>(FLAG_REPLAY);
+    let stdin_path = matches.get_one::<String>(FLAG_STDIN);
+    let trace_path = matches.get_one::<String>(FLAG_TRACE);
+    let trace_window = TraceWindow {
+        skip: *matches.get_one::<u64>(FLAG_TRACE_SKIP).unwrap(),
+        count: *matches.get_one::<u64>(FLAG_TRACE_COUNT).unwrap(),
+    };
+    let is_wasi_audit = matches.get_flag(FLAG_WASI_AUDIT);
+    let wasi_allowlist = matches.get_many::<String>(FLAG_WASI_ALLOW);
     let start_arg_strings = matches.get_many::<String>(ARGS_FOR_APP).unwrap_or_default();
     let wasm_path = matches.get_one::<String>(WASM_FILE).unwrap();
     // WASI expects the .wasm file to be argv[0]
@@ -69,6 +238,14 @@ fn main() -> io::Result<()> {
         .chain(start_arg_strings)
         .map(|s| s.as_bytes());
     let wasi_argv = Vec::from_iter_in(wasi_argv_iter, &arena);
+    // Arguments for the called function, if they're given separately from the app's argv
+    let fn_argv = match fn_arg_strings {
+        Some(strings) => {
+            let iter = once(wasm_path).chain(strings).map(|s| s.as_bytes());
+            Vec::from_iter_in(iter, &arena)
+        }
+        None => wasi_argv.clone(),
+    };
 
     // Load the WebAssembly binary file
 
@@ -76,41 +253,100 @@ fn main() -> io::Result<()> {
 
     // Parse the binary data
 
+    if let Err(e) = Capabilities::of_this_build().check_module(&module_bytes) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
     let require_relocatable = false;
     let module = match WasmModule::preload(&arena, &module_bytes, require_relocatable) {
         Ok(m) => m,
         Err(e) => {
             eprintln!("I couldn't parse this WebAssembly module! There's something wrong at byte offset {:#x}.",  e.offset);
+            if let Some(section) = SectionId::at_offset(&module_bytes, e.offset) {
+                eprintln!("That's in the {:?} section.", section);
+            }
             eprintln!("{}", e.message);
             eprintln!("If you think this could be a code generation problem in the Roc compiler, see crates/compiler/gen_wasm/README.md for debugging tips.");
             process::exit(1);
         }
     };
 
-    // Create an execution instance
+    // Create an execution instance and run it
 
-    let dispatcher = DefaultImportDispatcher::new(&wasi_argv);
-    let mut inst =
-        Instance::for_module(&arena, &module, dispatcher, is_debug_mode).unwrap_or_else(|e| {
-            eprintln!("{}", e);
-            process::exit(2);
-        });
+    let mut default_dispatcher = DefaultImportDispatcher::new(&wasi_argv);
+    if let Some(path) = stdin_path {
+        let input = fs::read(path)?;
+        default_dispatcher.wasi.set_stdin(WasiFile::input(input));
+    }
+    for (key, value) in env_vars {
+        default_dispatcher.wasi.set_env(key, value);
+    }
+    for path in preopen_dirs {
+        default_dispatcher.wasi.preopen_host_dir(path, path);
+    }
+    if is_wasi_audit {
+        default_dispatcher
+            .wasi
+            .set_audit(|call| eprintln!("[wasi] {}", call));
+    }
+    if let Some(names) = wasi_allowlist {
+        default_dispatcher
+            .wasi
+            .allow_only(names.map(String::as_str));
+    }
+    let trace_writer: Option<Box<dyn io::Write>> = match trace_path.map(String::as_str) {
+        Some("-") => Some(Box::new(io::stderr())),
+        Some(path) => Some(Box::new(BufWriter::new(fs::File::create(path)?))),
+        None => None,
+    };
+    let options = RunOptions {
+        start_fn_name,
+        is_debug_mode,
+        is_profile_mode,
+        coverage: coverage_path.map(|path| (path.as_str(), wasm_path.as_str())),
+        memory_error_mode,
+        fuel,
+        roc_result,
+        trace: trace_writer.map(|writer| (writer, trace_window)),
+    };
 
-    // Run
+    let (result, exit_status) = if let Some(path) = record_path {
+        let recorder = RecordingDispatcher::new(default_dispatcher);
+        let (result, recorder) = run_module(&arena, &module, recorder, &fn_argv, options);
+        fs::write(path, recorder.log.to_string())?;
+        (result, recorder.exit_status())
+    } else if let Some(path) = replay_path {
+        let log = ImportLog::parse(&fs::read_to_string(path)?).unwrap_or_else(|e| {
+            eprintln!("I couldn't parse the replay log {}: {}", path, e);
+            process::exit(1);
+        });
+        let replayer = ReplayDispatcher::new(log);
+        let (result, replayer) = run_module(&arena, &module, replayer, &fn_argv, options);
+        (result, replayer.exit_status())
+    } else {
+        let (result, dispatcher) =
+            run_module(&arena, &module, default_dispatcher, &fn_argv, options);
+        (result, dispatcher.exit_status())
+    };
 
-    let result = inst.call_export_from_cli(&module, start_fn_name, &wasi_argv);
+    // A WASI program that calls `proc_exit` exits with the status it passed
+    if let Some(status) = exit_status {
+        process::exit(status);
+    }
 
     // Print out return value, if any
 
     match result {
-        Ok(Some(val)) => {
+        Ok(RunOutput::Roc(roc_value)) => println!("{}", roc_value),
+        Ok(RunOutput::Wasm(Some(val))) => {
             if is_hex_format {
                 println!("{:#x?}", val)
             } else {
                 println!("{:?}", val)
             }
         }
-        Ok(None) => {}
+        Ok(RunOutput::Wasm(None)) => {}
         Err(e) => {
             eprintln!("{}", e);
             process::exit(3);
@@ -119,3 +355,122 @@ fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+struct RunOptions<'s> {
+    start_fn_name: &'s str,
+    is_debug_mode: bool,
+    is_profile_mode: bool,
+    /// Where to write the coverage report, and the source file to name in it
+    coverage: Option<(&'s str, &'s str)>,
+    memory_error_mode: MemoryErrorMode,
+    fuel: Option<u64>,
+    /// The type of the Roc value that the called function returns the address of
+    roc_result: Option<&'s RocLayout>,
+    trace: Option<(Box<dyn io::Write>, TraceWindow)>,
+}
+
+/// What the called function returned
+enum RunOutput {
+    Wasm(Option<Value>),
+    /// The Roc value at the address it returned, formatted as Roc code
+    Roc(String),
+}
+
+/// Run the module, returning the import dispatcher so that its state can be inspected afterwards
+fn run_module<'a, I: ImportDispatcher>(
+    arena: &'a Bump,
+    module: &WasmModule<'a>,
+    dispatcher: I,
+    fn_argv: &'a [&'a [u8]],
+    options: RunOptions,
+) -> (Result<RunOutput, String>, I) {
+    let mut inst = Instance::for_module(arena, module, dispatcher, options.is_debug_mode)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        });
+
+    if options.is_profile_mode {
+        inst.enable_profiling();
+    }
+    if options.coverage.is_some() {
+        inst.enable_coverage();
+    }
+    inst.set_memory_error_mode(options.memory_error_mode);
+    inst.set_fuel(options.fuel);
+    if let Some((writer, window)) = options.trace {
+        inst.enable_trace(writer, window);
+    }
+
+    let result = inst
+        .call_export_from_cli(module, options.start_fn_name, fn_argv)
+        .map_err(String::from);
+
+    if let Some(report) = inst.profile_report() {
+        eprint!("{}", report);
+    }
+    if let (Some(report), Some((path, source_file))) = (inst.coverage_report(), options.coverage) {
+        if let Err(e) = fs::write(path, report.to_lcov(source_file)) {
+            eprintln!("I couldn't write the coverage report to {}: {}", path, e);
+        }
+    }
+
+    let output = result.and_then(|value| match (options.roc_result, value) {
+        (Some(layout), Some(Value::I32(addr))) => layout
+            .read(&inst.memory, addr as u32)
+            .map(|roc_value| RunOutput::Roc(roc_value.to_string()))
+            .map_err(|e| format!("I couldn't decode the returned Roc value: {}", e)),
+        (Some(_), Some(value)) => Err(format!(
+            "The function returned {:?}, but --{} needs it to return an address (i32)",
+            value, FLAG_ROC_RESULT
+        )),
+        (_, value) => Ok(RunOutput::Wasm(value)),
+    });
+
+    (output, inst.import_dispatcher)
+}
+
+/// Parse a KEY=VALUE environment variable from the command line
+fn parse_env_var(var: &str) -> Result<(String, String), String> {
+    match var.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, but found `{}`", var)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_env_var;
+
+    #[test]
+    fn env_var() {
+        assert_eq!(
+            parse_env_var("HOME=/root"),
+            Ok(("HOME".to_string(), "/root".to_string()))
+        );
+    }
+
+    #[test]
+    fn env_var_value_may_be_empty_or_contain_equals() {
+        assert_eq!(
+            parse_env_var("EMPTY="),
+            Ok(("EMPTY".to_string(), String::new()))
+        );
+        assert_eq!(
+            parse_env_var("OPTS=a=1,b=2"),
+            Ok(("OPTS".to_string(), "a=1,b=2".to_string()))
+        );
+    }
+
+    #[test]
+    fn env_var_needs_a_key_and_equals() {
+        assert_eq!(
+            parse_env_var("HOME"),
+            Err("expected KEY=VALUE, but found `HOME`".to_string())
+        );
+        assert_eq!(
+            parse_env_var("=value"),
+            Err("expected KEY=VALUE, but found `=value`".to_string())
+        );
+    }
+}
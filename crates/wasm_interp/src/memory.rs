@@ -0,0 +1,234 @@
+//! A view of a module's memory for host functions, with reads and writes that check their bounds
+//! instead of panicking, and helpers to decode the values that Roc programs pass by address.
+use crate::roc_value::{RocLayout, RocValue};
+use std::fmt;
+use std::ops::Range;
+
+/// Size of a `RocStr` or `RocList` in 32-bit memory: elements, length and capacity
+pub(crate) const ROC_STR_SIZE: u32 = 12;
+
+/// Why a host function couldn't read or write the memory it was given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError {
+    /// Some of the bytes are past the end of memory, or the address calculation overflowed
+    OutOfBounds {
+        addr: u32,
+        len: u32,
+        memory_size: usize,
+    },
+    /// Bytes that were read as a string are not valid UTF-8
+    InvalidUtf8 { addr: u32, len: u32 },
+    /// The tag id of a tag union is not one of its tags
+    InvalidTagId { addr: u32, tag_id: u8 },
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryError::OutOfBounds {
+                addr,
+                len,
+                memory_size,
+            } => write!(
+                f,
+                "Out of bounds memory access: {} bytes at address {:#x}, but the memory size is {:#x}",
+                len, addr, memory_size
+            ),
+            MemoryError::InvalidUtf8 { addr, len } => write!(
+                f,
+                "The {} bytes at address {:#x} are not a valid UTF-8 string",
+                len, addr
+            ),
+            MemoryError::InvalidTagId { addr, tag_id } => write!(
+                f,
+                "The tag id {} at address {:#x} is not one of the tags of the union",
+                tag_id, addr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+impl From<MemoryError> for String {
+    fn from(error: MemoryError) -> Self {
+        error.to_string()
+    }
+}
+
+/// The bytes at `addr..addr + len`, if they are all in memory
+pub(crate) fn get_bytes(memory: &[u8], addr: u32, len: u32) -> Result<&[u8], MemoryError> {
+    let start = addr as usize;
+    start
+        .checked_add(len as usize)
+        .and_then(|end| memory.get(start..end))
+        .ok_or(MemoryError::OutOfBounds {
+            addr,
+            len,
+            memory_size: memory.len(),
+        })
+}
+
+/// The bytes of the `RocStr` at `addr`, whether it's a small string stored inline or not
+pub(crate) fn roc_str_bytes(memory: &[u8], addr: u32) -> Result<&[u8], MemoryError> {
+    let str_bytes = get_bytes(memory, addr, ROC_STR_SIZE)?;
+
+    // Small strings are stored inline, with the length in the last byte and its high bit set
+    let last_byte = str_bytes[ROC_STR_SIZE as usize - 1];
+    if last_byte >= 0x80 {
+        let len = (last_byte & 0x7f).min(ROC_STR_SIZE as u8 - 1);
+        Ok(&str_bytes[..len as usize])
+    } else {
+        let (elements, len) = roc_list_header(str_bytes);
+        get_bytes(memory, elements, len)
+    }
+}
+
+/// The address of the elements and the length of a `RocList` or large `RocStr`
+pub(crate) fn roc_list_header(bytes: &[u8]) -> (u32, u32) {
+    let elements = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+    // The high bit of the length marks a seamless slice
+    let len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) & (i32::MAX as u32);
+    (elements, len)
+}
+
+macro_rules! read_write_le {
+    ($read: ident, $write: ident, $t: ty) => {
+        pub fn $read(&self, addr: u32) -> Result<$t, MemoryError> {
+            let bytes = self.read_bytes(addr, std::mem::size_of::<$t>() as u32)?;
+            Ok(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+        }
+
+        pub fn $write(&mut self, addr: u32, value: $t) -> Result<(), MemoryError> {
+            self.write_bytes(addr, &value.to_le_bytes())
+        }
+    };
+}
+
+/// The memory of the module that called an import, as passed to
+/// [ImportDispatcher::dispatch](crate::ImportDispatcher::dispatch).
+/// Addresses and lengths are `u32`, like the pointers the module passes as arguments.
+pub struct Memory<'a> {
+    bytes: &'a mut [u8],
+    /// Every range that was handed out for writing, so that a
+    /// [RecordingDispatcher](crate::RecordingDispatcher) only has to log those
+    written: Vec<Range<usize>>,
+}
+
+impl<'a> Memory<'a> {
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        Memory {
+            bytes,
+            written: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// All of memory, for host functions that need more than the typed reads and writes
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    /// All of memory, to be written in place. The whole of it counts as written.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.written.push(0..self.bytes.len());
+        self.bytes
+    }
+
+    pub fn read_bytes(&self, addr: u32, len: u32) -> Result<&[u8], MemoryError> {
+        get_bytes(self.bytes, addr, len)
+    }
+
+    /// The bytes at `addr..addr + len`, to be written in place, e.g. by a reader
+    pub fn bytes_mut(&mut self, addr: u32, len: u32) -> Result<&mut [u8], MemoryError> {
+        let memory_size = self.bytes.len();
+        let start = addr as usize;
+        let range = start
+            .checked_add(len as usize)
+            .filter(|end| *end <= memory_size)
+            .map(|end| start..end)
+            .ok_or(MemoryError::OutOfBounds {
+                addr,
+                len,
+                memory_size,
+            })?;
+
+        if !range.is_empty() {
+            self.written.push(range.clone());
+        }
+        Ok(&mut self.bytes[range])
+    }
+
+    /// The parts of memory that were handed out for writing since this view was created,
+    /// sorted and with overlapping or adjacent ranges merged
+    pub fn written_ranges(&self) -> Vec<Range<usize>> {
+        let mut sorted = self.written.clone();
+        sorted.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(sorted.len());
+        for range in sorted {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        merged
+    }
+
+    pub fn write_bytes(&mut self, addr: u32, bytes: &[u8]) -> Result<(), MemoryError> {
+        self.bytes_mut(addr, bytes.len() as u32)?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    read_write_le!(read_u8, write_u8, u8);
+    read_write_le!(read_u32, write_u32, u32);
+    read_write_le!(read_i32, write_i32, i32);
+    read_write_le!(read_u64, write_u64, u64);
+    read_write_le!(read_i64, write_i64, i64);
+    read_write_le!(read_f32, write_f32, f32);
+    read_write_le!(read_f64, write_f64, f64);
+
+    /// The UTF-8 string at `addr..addr + len`
+    pub fn read_str(&self, addr: u32, len: u32) -> Result<&str, MemoryError> {
+        let bytes = self.read_bytes(addr, len)?;
+        std::str::from_utf8(bytes).map_err(|_| MemoryError::InvalidUtf8 { addr, len })
+    }
+
+    /// The contents of the `RocStr` at `addr`
+    pub fn read_roc_str(&self, addr: u32) -> Result<&str, MemoryError> {
+        let bytes = roc_str_bytes(self.bytes, addr)?;
+        std::str::from_utf8(bytes).map_err(|_| MemoryError::InvalidUtf8 {
+            addr,
+            len: bytes.len() as u32,
+        })
+    }
+
+    /// The bytes of the elements of the `RocList` at `addr`, whose elements are `element_size`
+    /// bytes each
+    pub fn read_roc_list(&self, addr: u32, element_size: u32) -> Result<&[u8], MemoryError> {
+        let header = self.read_bytes(addr, ROC_STR_SIZE)?;
+        let (elements, len) = roc_list_header(header);
+        let byte_len = len
+            .checked_mul(element_size)
+            .ok_or(MemoryError::OutOfBounds {
+                addr: elements,
+                len: u32::MAX,
+                memory_size: self.len(),
+            })?;
+        self.read_bytes(elements, byte_len)
+    }
+
+    /// The Roc value at `addr`, whose type is described by `layout`
+    pub fn read_roc_value(&self, addr: u32, layout: &RocLayout) -> Result<RocValue, MemoryError> {
+        layout.read(self.bytes, addr)
+    }
+}
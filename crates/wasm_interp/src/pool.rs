@@ -0,0 +1,244 @@
+//! Running many small calls to one module on several threads, like `roc test` running every test
+//! in a package, or a fuzzer running one input after another.
+//!
+//! The module is decoded once, and all the instances share it without copying it. Each thread
+//! instantiates it as soon as it starts, and keeps that instance for as long as the pool lives.
+//! An instance stays on the thread that made it, since its import dispatcher doesn't have to be
+//! `Send` (the WASI one isn't), and only the jobs and their results go between threads.
+//! Before each job, the instance's memory and globals are reset from an image taken right after
+//! instantiation, which all the threads share and none of them write to. Only the pages of memory
+//! that the previous job wrote to are copied back from the image, so every job starts from the
+//! same state without paying for a copy of the whole memory.
+use crate::capabilities::Capabilities;
+use crate::{CallError, ImportDispatcher, Instance, LoadError, Value};
+use bumpalo::Bump;
+use roc_wasm_module::WasmModule;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The state that running code can change: the memory and globals of an instance
+#[derive(Debug, Clone)]
+pub(crate) struct MemoryImage {
+    pub memory: Arc<[u8]>,
+    pub globals: Arc<[Value]>,
+}
+
+type JobResult = Result<Option<Value>, CallError>;
+
+/// A decoded module that instances on any thread can use.
+/// The module is only ever read once it's decoded, and reading it doesn't touch the arena it was
+/// decoded into, so sharing it between threads is safe even though the arena isn't `Sync`.
+struct SharedModule {
+    /// Points into `_arena`, which is boxed so that it doesn't move along with this struct
+    module: &'static WasmModule<'static>,
+    _arena: Box<Bump>,
+}
+
+// SAFETY: see above. The arena is only allocated from while decoding, in `SharedModule::decode`.
+unsafe impl Send for SharedModule {}
+unsafe impl Sync for SharedModule {}
+
+impl SharedModule {
+    fn decode(module_bytes: &[u8]) -> Result<Self, LoadError> {
+        Capabilities::of_this_build().check_module(module_bytes)?;
+
+        let arena = Box::new(Bump::new());
+        // SAFETY: the reference doesn't outlive the arena, since it's dropped along with it
+        let arena_ref: &'static Bump = unsafe { &*(&*arena as *const Bump) };
+        let module = WasmModule::preload(arena_ref, module_bytes, false)
+            .map_err(|e| LoadError::invalid_module(module_bytes, e))?;
+
+        Ok(SharedModule {
+            module: arena_ref.alloc(module),
+            _arena: arena,
+        })
+    }
+
+    fn module(&self) -> &WasmModule<'_> {
+        self.module
+    }
+}
+
+struct Job {
+    index: usize,
+    export: String,
+    args: Vec<Value>,
+    results: Sender<(usize, JobResult)>,
+}
+
+/// Threads that each keep an instance of the same module, ready to call its exports.
+/// Dropping the pool waits for the jobs that are already running to finish.
+pub struct InstancePool {
+    jobs: Mutex<Option<Sender<Job>>>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl InstancePool {
+    /// Decode the module once, and instantiate it on each of `threads` threads (at least one),
+    /// with an import dispatcher from `new_dispatcher`. Each thread keeps its dispatcher from one
+    /// job to the next, so it shouldn't keep anything from a job that the next one could see.
+    /// The module is instantiated here first, to report an error before starting any threads.
+    pub fn new<I, D>(
+        module_bytes: impl AsRef<[u8]>,
+        threads: usize,
+        new_dispatcher: D,
+    ) -> Result<Self, LoadError>
+    where
+        I: ImportDispatcher,
+        D: Fn() -> I + Send + Sync + 'static,
+    {
+        let shared = Arc::new(SharedModule::decode(module_bytes.as_ref())?);
+        let image = {
+            let arena = Bump::new();
+            let instance = Instance::for_module(&arena, shared.module(), new_dispatcher(), false)
+                .map_err(LoadError::Trap)?;
+            instance.memory_image()
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let new_dispatcher = Arc::new(new_dispatcher);
+
+        let threads = (0..threads.max(1))
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                let image = image.clone();
+                let new_dispatcher = Arc::clone(&new_dispatcher);
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || work(&shared, &image, &*new_dispatcher, &receiver))
+            })
+            .collect();
+
+        Ok(InstancePool {
+            jobs: Mutex::new(Some(sender)),
+            threads,
+        })
+    }
+
+    /// Number of threads, which is also the number of instances
+    pub fn size(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Call an export for each job, with the arguments that go with it, and return the results
+    /// in the same order as the jobs. Jobs start as soon as they come out of the iterator, so it
+    /// can produce them lazily. A job that makes the interpreter panic has an `Err` result, and
+    /// its thread gets a fresh instance for the next job.
+    pub fn run<J, S, A>(&self, jobs: J) -> Vec<Result<Option<Value>, CallError>>
+    where
+        J: IntoIterator<Item = (S, A)>,
+        S: Into<String>,
+        A: IntoIterator<Item = Value>,
+    {
+        // The pool only stops taking jobs when it's dropped
+        let sender = self.lock_jobs().clone().unwrap();
+        let (results_sender, results_receiver) = mpsc::channel();
+
+        let mut job_count = 0;
+        for (index, (export, args)) in jobs.into_iter().enumerate() {
+            let job = Job {
+                index,
+                export: export.into(),
+                args: args.into_iter().collect(),
+                results: results_sender.clone(),
+            };
+            // If every thread has somehow stopped, the job is reported as lost below
+            let _ = sender.send(job);
+            job_count += 1;
+        }
+
+        // The receiver stops once every job has sent its result, and dropped its sender
+        drop(results_sender);
+        let mut results: Vec<Option<JobResult>> = vec![None; job_count];
+        for (index, result) in results_receiver {
+            results[index] = Some(result);
+        }
+
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| Err(CallError::Trap("The job was lost".to_string())))
+            })
+            .collect()
+    }
+
+    fn lock_jobs(&self) -> std::sync::MutexGuard<'_, Option<Sender<Job>>> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for InstancePool {
+    fn drop(&mut self) {
+        // Closing the channel stops each thread once it's waiting for another job
+        self.lock_jobs().take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// One of the pool's threads. Runs jobs until the pool is dropped, replacing its instance after
+/// a job panics, since it could have been left in any state.
+fn work<I, D>(
+    shared: &SharedModule,
+    image: &MemoryImage,
+    new_dispatcher: &D,
+    jobs: &Mutex<Receiver<Job>>,
+) where
+    I: ImportDispatcher,
+    D: Fn() -> I,
+{
+    loop {
+        let arena = Bump::new();
+        let mut instance = Instance::for_module(&arena, shared.module(), new_dispatcher(), false)
+            .map_err(|e| CallError::Trap(format!("Failed to instantiate the module: {}", e)));
+
+        loop {
+            // Only one thread waits on the channel at a time. The others wait for the lock.
+            let next_job = jobs
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .recv();
+            let job = match next_job {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+
+            let (result, panicked) = match instance.as_mut() {
+                Ok(instance) => {
+                    let run = panic::catch_unwind(AssertUnwindSafe(|| {
+                        instance.restore_image(image);
+                        instance.call_export(&job.export, job.args)
+                    }));
+                    match run {
+                        Ok(result) => (result, false),
+                        Err(payload) => (Err(CallError::Trap(panic_message(&*payload))), true),
+                    }
+                }
+                Err(message) => (Err(message.clone()), false),
+            };
+
+            // The caller of `run` is waiting for every result, so it's still there to receive it
+            let _ = job.results.send((job.index, result));
+
+            if panicked {
+                break;
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "unknown cause"
+    };
+    format!("The interpreter panicked: {}", message)
+}
@@ -0,0 +1,159 @@
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::{ExportType, WasmModule};
+use std::fmt;
+use std::iter;
+
+/// Execution counters for a single function
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionCounters {
+    /// Number of times the function was called
+    pub calls: u64,
+    /// Number of instructions executed in the body of the function itself, excluding callees.
+    /// Always zero for imported functions, since their code is not WebAssembly.
+    pub instructions: u64,
+}
+
+/// Counters for every function in the module, indexed by function index (imports first)
+#[derive(Debug)]
+pub(crate) struct Profiler<'a> {
+    counters: Vec<'a, FunctionCounters>,
+}
+
+impl<'a> Profiler<'a> {
+    pub fn new(arena: &'a Bump, module: &WasmModule<'a>) -> Self {
+        let num_functions = module.import.function_count() + module.code.function_count as usize;
+        let counters = Vec::from_iter_in(
+            iter::repeat(FunctionCounters::default()).take(num_functions),
+            arena,
+        );
+        Profiler { counters }
+    }
+
+    pub fn count_call(&mut self, fn_index: usize) {
+        self.counters[fn_index].calls += 1;
+    }
+
+    pub fn count_instruction(&mut self, fn_index: usize) {
+        self.counters[fn_index].instructions += 1;
+    }
+
+    pub fn reset(&mut self) {
+        for counters in self.counters.iter_mut() {
+            *counters = FunctionCounters::default();
+        }
+    }
+
+    pub fn report(&self, module: &WasmModule<'a>) -> ProfileReport {
+        let import_count = module.import.function_count();
+
+        let mut functions: std::vec::Vec<ProfileEntry> = self
+            .counters
+            .iter()
+            .enumerate()
+            .filter(|(_, counters)| counters.calls > 0 || counters.instructions > 0)
+            .map(|(fn_index, counters)| ProfileEntry {
+                fn_index: fn_index as u32,
+                name: function_name(module, fn_index, import_count),
+                counters: *counters,
+            })
+            .collect();
+
+        // Hottest functions first
+        functions.sort_by(|a, b| {
+            b.counters
+                .instructions
+                .cmp(&a.counters.instructions)
+                .then(b.counters.calls.cmp(&a.counters.calls))
+                .then(a.fn_index.cmp(&b.fn_index))
+        });
+
+        ProfileReport { functions }
+    }
+}
+
+/// Find a human-readable name for a function.
+/// Prefers the name section, then falls back to import and export names.
+/// `import_count` is the number of imported functions.
+pub(crate) fn function_name(module: &WasmModule, fn_index: usize, import_count: usize) -> String {
+    let fn_index_u32 = fn_index as u32;
+
+    if let Some((_, name)) = module
+        .names
+        .function_names
+        .iter()
+        .find(|(index, _)| *index == fn_index_u32)
+    {
+        return name.to_string();
+    }
+
+    if fn_index < import_count {
+        // Only imported functions have function indices
+        let import = module
+            .import
+            .imports
+            .iter()
+            .filter(|import| import.is_function())
+            .nth(fn_index);
+        if let Some(import) = import {
+            return format!("{}.{}", import.module, import.name);
+        }
+    }
+
+    if let Some(export) = module
+        .export
+        .exports
+        .iter()
+        .find(|ex| ex.ty == ExportType::Func && ex.index == fn_index_u32)
+    {
+        return export.name.to_string();
+    }
+
+    format!("func[{}]", fn_index)
+}
+
+/// Profiling counters for one function, as reported by `Instance::profile_report`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileEntry {
+    pub fn_index: u32,
+    pub name: String,
+    pub counters: FunctionCounters,
+}
+
+/// Execution profile of all functions that were called at least once,
+/// sorted by the number of instructions they executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub functions: std::vec::Vec<ProfileEntry>,
+}
+
+impl ProfileReport {
+    pub fn get(&self, name: &str) -> Option<&FunctionCounters> {
+        self.functions
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| &entry.counters)
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>14} {:>10}  {:>6}  name",
+            "instructions", "calls", "index"
+        )?;
+        for ProfileEntry {
+            fn_index,
+            name,
+            counters,
+        } in self.functions.iter()
+        {
+            writeln!(
+                f,
+                "{:>14} {:>10}  {:>6}  {}",
+                counters.instructions, counters.calls, fn_index, name
+            )?;
+        }
+        Ok(())
+    }
+}
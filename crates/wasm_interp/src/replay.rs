@@ -0,0 +1,352 @@
+//! Record and replay the results of imported function calls.
+//!
+//! Wrapping a dispatcher in a [RecordingDispatcher] logs everything the host did in response to
+//! each import call. A [ReplayDispatcher] can then feed that log back to the program without the
+//! original host, so that a failure in CI can be reproduced and debugged locally.
+use crate::{wasi, ImportDispatcher, Memory, MissingImports};
+use roc_wasm_module::{GlobalType, Value};
+use std::fmt;
+
+/// A region of memory that the host wrote to during an import call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Everything needed to replay one call to an imported function
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportCall {
+    pub module_name: String,
+    pub function_name: String,
+    pub arguments: Vec<Value>,
+    pub memory_writes: Vec<MemoryWrite>,
+    pub return_value: Option<Value>,
+}
+
+/// A log of import calls, in the order they were made.
+///
+/// It is saved as text, with one keyword-prefixed line per item, e.g.
+/// ```text
+/// call wasi_snapshot_preview1 fd_write
+/// arg i32 1
+/// arg i32 8
+/// write 0 05000000
+/// return i32 0
+/// ```
+/// Floats are stored as their raw bits in hex, so that they round-trip exactly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportLog {
+    pub calls: Vec<ImportCall>,
+}
+
+impl fmt::Display for ImportLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for call in self.calls.iter() {
+            writeln!(f, "call {} {}", call.module_name, call.function_name)?;
+            for arg in call.arguments.iter() {
+                writeln!(f, "arg {}", ValueText(*arg))?;
+            }
+            for write in call.memory_writes.iter() {
+                write!(f, "write {} ", write.offset)?;
+                for byte in write.bytes.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                writeln!(f)?;
+            }
+            if let Some(value) = call.return_value {
+                writeln!(f, "return {}", ValueText(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ImportLog {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut calls: Vec<ImportCall> = Vec::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("Line {}: {}: {:?}", line_index + 1, message, line);
+            let mut words = line.split_whitespace();
+
+            let keyword = match words.next() {
+                Some(k) => k,
+                None => continue,
+            };
+
+            if keyword == "call" {
+                match (words.next(), words.next()) {
+                    (Some(module_name), Some(function_name)) => calls.push(ImportCall {
+                        module_name: module_name.to_string(),
+                        function_name: function_name.to_string(),
+                        arguments: vec![],
+                        memory_writes: vec![],
+                        return_value: None,
+                    }),
+                    _ => return Err(error("expected a module name and a function name")),
+                }
+                continue;
+            }
+
+            let call = calls
+                .last_mut()
+                .ok_or_else(|| error("expected a `call` line first"))?;
+
+            match keyword {
+                "arg" => {
+                    let value = parse_value(&mut words).ok_or_else(|| error("invalid value"))?;
+                    call.arguments.push(value);
+                }
+                "return" => {
+                    let value = parse_value(&mut words).ok_or_else(|| error("invalid value"))?;
+                    call.return_value = Some(value);
+                }
+                "write" => {
+                    let write = parse_memory_write(&mut words)
+                        .ok_or_else(|| error("invalid memory write"))?;
+                    call.memory_writes.push(write);
+                }
+                _ => return Err(error("unknown keyword")),
+            }
+        }
+
+        Ok(ImportLog { calls })
+    }
+}
+
+struct ValueText(Value);
+
+impl fmt::Display for ValueText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::I32(x) => write!(f, "i32 {}", x),
+            Value::I64(x) => write!(f, "i64 {}", x),
+            Value::F32(x) => write!(f, "f32 {:#x}", x.to_bits()),
+            Value::F64(x) => write!(f, "f64 {:#x}", x.to_bits()),
+            Value::V128(x) => write!(f, "v128 {:#x}", x),
+        }
+    }
+}
+
+fn parse_value<'t>(words: &mut impl Iterator<Item = &'t str>) -> Option<Value> {
+    let ty = words.next()?;
+    let text = words.next()?;
+    let hex = |t: &str| t.strip_prefix("0x").map(String::from);
+
+    match ty {
+        "i32" => text.parse().ok().map(Value::I32),
+        "i64" => text.parse().ok().map(Value::I64),
+        "f32" => u32::from_str_radix(&hex(text)?, 16)
+            .ok()
+            .map(|bits| Value::F32(f32::from_bits(bits))),
+        "f64" => u64::from_str_radix(&hex(text)?, 16)
+            .ok()
+            .map(|bits| Value::F64(f64::from_bits(bits))),
+        "v128" => u128::from_str_radix(&hex(text)?, 16).ok().map(Value::V128),
+        _ => None,
+    }
+}
+
+fn parse_memory_write<'t>(words: &mut impl Iterator<Item = &'t str>) -> Option<MemoryWrite> {
+    let offset = words.next()?.parse().ok()?;
+    let hex = words.next()?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(hex.len() / 2);
+    for i in (0..hex.len()).step_by(2) {
+        bytes.push(u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?);
+    }
+
+    Some(MemoryWrite { offset, bytes })
+}
+
+/// Passes import calls on to another dispatcher, logging the results and the parts of memory
+/// it wrote to. Only the ranges that [Memory] handed out for writing are logged, so a host that
+/// writes through [Memory::as_bytes_mut] has all of memory logged for that call.
+pub struct RecordingDispatcher<I: ImportDispatcher> {
+    pub inner: I,
+    pub log: ImportLog,
+}
+
+impl<I: ImportDispatcher> RecordingDispatcher<I> {
+    pub fn new(inner: I) -> Self {
+        RecordingDispatcher {
+            inner,
+            log: ImportLog::default(),
+        }
+    }
+}
+
+impl<I: ImportDispatcher> ImportDispatcher for RecordingDispatcher<I> {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut Memory<'_>,
+    ) -> Option<Value> {
+        let return_value = self
+            .inner
+            .dispatch(module_name, function_name, arguments, memory);
+
+        let memory_writes = memory
+            .written_ranges()
+            .into_iter()
+            .map(|range| MemoryWrite {
+                offset: range.start as u32,
+                bytes: memory.as_bytes()[range].to_vec(),
+            })
+            .collect();
+
+        self.log.calls.push(ImportCall {
+            module_name: module_name.to_string(),
+            function_name: function_name.to_string(),
+            arguments: arguments.to_vec(),
+            memory_writes,
+            return_value,
+        });
+
+        return_value
+    }
+
+    fn global(
+        &mut self,
+        module_name: &str,
+        global_name: &str,
+        global_type: GlobalType,
+    ) -> Option<Value> {
+        self.inner.global(module_name, global_name, global_type)
+    }
+
+    fn provides(&self, module_name: &str, function_name: &str) -> bool {
+        self.inner.provides(module_name, function_name)
+    }
+
+    fn missing_imports(&self) -> MissingImports {
+        self.inner.missing_imports()
+    }
+
+    fn exit_status(&self) -> Option<i32> {
+        self.inner.exit_status()
+    }
+
+    fn host_error(&self) -> Option<&str> {
+        self.inner.host_error()
+    }
+}
+
+/// Answers import calls from a log, without calling the host at all.
+///
+/// If the program makes a call that doesn't match the next one in the log, it's no longer doing
+/// the same thing as when the log was recorded. The replay stops there, and the running call
+/// ends with an error. See [ImportDispatcher::host_error].
+pub struct ReplayDispatcher {
+    log: ImportLog,
+    next_call: usize,
+    /// Set when the log's call to WASI's `proc_exit` is replayed
+    exit_status: Option<i32>,
+    /// Why the replay stopped, if it did
+    error: Option<String>,
+}
+
+impl ReplayDispatcher {
+    pub fn new(log: ImportLog) -> Self {
+        ReplayDispatcher {
+            log,
+            next_call: 0,
+            exit_status: None,
+            error: None,
+        }
+    }
+
+    /// Number of logged calls that have not been replayed yet
+    pub fn remaining_calls(&self) -> usize {
+        self.log.calls.len() - self.next_call
+    }
+}
+
+impl ImportDispatcher for ReplayDispatcher {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut Memory<'_>,
+    ) -> Option<Value> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let call_index = self.next_call;
+        let call = match self.log.calls.get(call_index) {
+            Some(call) => call,
+            None => {
+                self.error = Some(format!(
+                    "Replay ran out of logged calls. Call #{} is {}.{}{:?}",
+                    call_index, module_name, function_name, arguments
+                ));
+                return None;
+            }
+        };
+
+        let is_same_call = call.module_name == module_name
+            && call.function_name == function_name
+            && call.arguments.len() == arguments.len()
+            && call
+                .arguments
+                .iter()
+                .zip(arguments.iter())
+                .all(|(a, b)| is_same_value(*a, *b));
+
+        if !is_same_call {
+            self.error = Some(format!(
+                "Replay diverged from the log at call #{}.\nExpected: {}.{}{:?}\nActual:   {}.{}{:?}",
+                call_index,
+                call.module_name,
+                call.function_name,
+                call.arguments,
+                module_name,
+                function_name,
+                arguments
+            ));
+            return None;
+        }
+
+        for write in call.memory_writes.iter() {
+            if let Err(e) = memory.write_bytes(write.offset, &write.bytes) {
+                self.error = Some(format!(
+                    "Replay can't apply the memory writes of call #{}: {}",
+                    call_index, e
+                ));
+                return None;
+            }
+        }
+
+        if call.module_name == wasi::MODULE_NAME && call.function_name == "proc_exit" {
+            self.exit_status = call.arguments.first().and_then(|arg| arg.expect_i32().ok());
+        }
+
+        self.next_call += 1;
+        call.return_value
+    }
+
+    fn exit_status(&self) -> Option<i32> {
+        self.exit_status
+    }
+
+    fn host_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Compare floats by their bits, so that a NaN argument matches itself
+fn is_same_value(a: Value, b: Value) -> bool {
+    match (a, b) {
+        (Value::F32(x), Value::F32(y)) => x.to_bits() == y.to_bits(),
+        (Value::F64(x), Value::F64(y)) => x.to_bits() == y.to_bits(),
+        _ => a == b,
+    }
+}
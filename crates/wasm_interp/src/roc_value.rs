@@ -0,0 +1,292 @@
+//! Decoding the Roc values that a program leaves in memory, like the result of an exported
+//! function that returns a pointer, and printing them in Roc syntax, e.g. `Ok ["a", "b"]`.
+//!
+//! Memory doesn't say what type a value has, so the caller describes it with a [RocLayout].
+//! Sizes and alignments are the ones Roc uses for 32-bit WebAssembly.
+use crate::memory::{get_bytes, roc_list_header, roc_str_bytes, MemoryError, ROC_STR_SIZE};
+use std::fmt;
+use std::str::FromStr;
+
+/// The type of a Roc value in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RocLayout {
+    /// The empty record, `{}`, which takes up no memory
+    Unit,
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+    Str,
+    List(Box<RocLayout>),
+    Result {
+        ok: Box<RocLayout>,
+        err: Box<RocLayout>,
+    },
+}
+
+impl RocLayout {
+    pub fn list(element: RocLayout) -> Self {
+        RocLayout::List(Box::new(element))
+    }
+
+    pub fn result(ok: RocLayout, err: RocLayout) -> Self {
+        RocLayout::Result {
+            ok: Box::new(ok),
+            err: Box::new(err),
+        }
+    }
+
+    /// Size in bytes, including the padding at the end, so it's also the distance between list
+    /// elements
+    pub fn size(&self) -> u32 {
+        match self {
+            RocLayout::Unit => 0,
+            RocLayout::Bool | RocLayout::U8 | RocLayout::I8 => 1,
+            RocLayout::U16 | RocLayout::I16 => 2,
+            RocLayout::U32 | RocLayout::I32 | RocLayout::F32 => 4,
+            RocLayout::U64 | RocLayout::I64 | RocLayout::F64 => 8,
+            RocLayout::Str | RocLayout::List(_) => ROC_STR_SIZE,
+            RocLayout::Result { .. } => {
+                let tag_offset = self.tag_offset();
+                round_up(tag_offset + 1, self.alignment())
+            }
+        }
+    }
+
+    pub fn alignment(&self) -> u32 {
+        match self {
+            RocLayout::Result { ok, err } => ok.alignment().max(err.alignment()),
+            RocLayout::Unit => 1,
+            RocLayout::Str | RocLayout::List(_) => 4,
+            _ => self.size(),
+        }
+    }
+
+    /// Where the tag id of a tag union is, after the biggest payload
+    fn tag_offset(&self) -> u32 {
+        match self {
+            RocLayout::Result { ok, err } => ok.size().max(err.size()),
+            _ => 0,
+        }
+    }
+
+    /// Decode the value at `addr`
+    pub fn read(&self, memory: &[u8], addr: u32) -> Result<RocValue, MemoryError> {
+        let bytes = get_bytes(memory, addr, self.size())?;
+
+        let value = match self {
+            RocLayout::Unit => RocValue::Unit,
+            RocLayout::Bool => RocValue::Bool(bytes[0] != 0),
+            RocLayout::U8 => RocValue::Int(bytes[0] as i128),
+            RocLayout::I8 => RocValue::Int(bytes[0] as i8 as i128),
+            RocLayout::U16 => RocValue::Int(u16::from_le_bytes(le(bytes)) as i128),
+            RocLayout::I16 => RocValue::Int(i16::from_le_bytes(le(bytes)) as i128),
+            RocLayout::U32 => RocValue::Int(u32::from_le_bytes(le(bytes)) as i128),
+            RocLayout::I32 => RocValue::Int(i32::from_le_bytes(le(bytes)) as i128),
+            RocLayout::U64 => RocValue::Int(u64::from_le_bytes(le(bytes)) as i128),
+            RocLayout::I64 => RocValue::Int(i64::from_le_bytes(le(bytes)) as i128),
+            RocLayout::F32 => RocValue::F32(f32::from_le_bytes(le(bytes))),
+            RocLayout::F64 => RocValue::F64(f64::from_le_bytes(le(bytes))),
+            RocLayout::Str => {
+                let str_bytes = roc_str_bytes(memory, addr)?;
+                let string =
+                    std::str::from_utf8(str_bytes).map_err(|_| MemoryError::InvalidUtf8 {
+                        addr,
+                        len: str_bytes.len() as u32,
+                    })?;
+                RocValue::Str(string.to_string())
+            }
+            RocLayout::List(element) => {
+                let (elements, len) = roc_list_header(bytes);
+                let stride = element.size();
+
+                // Check the whole list is in memory before decoding any of it
+                let byte_len = len.checked_mul(stride).ok_or(MemoryError::OutOfBounds {
+                    addr: elements,
+                    len: u32::MAX,
+                    memory_size: memory.len(),
+                })?;
+                get_bytes(memory, elements, byte_len)?;
+
+                let items = (0..len)
+                    .map(|index| element.read(memory, elements + index * stride))
+                    .collect::<Result<Vec<_>, _>>()?;
+                RocValue::List(items)
+            }
+            RocLayout::Result { ok, err } => {
+                // Tags are numbered in alphabetical order
+                let tag_offset = self.tag_offset();
+                match bytes[tag_offset as usize] {
+                    0 => RocValue::Err(Box::new(err.read(memory, addr)?)),
+                    1 => RocValue::Ok(Box::new(ok.read(memory, addr)?)),
+                    tag_id => {
+                        return Err(MemoryError::InvalidTagId {
+                            addr: addr + tag_offset,
+                            tag_id,
+                        })
+                    }
+                }
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+fn le<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes[..N].try_into().unwrap()
+}
+
+fn round_up(size: u32, alignment: u32) -> u32 {
+    (size + alignment - 1) / alignment * alignment
+}
+
+/// Parses the way Roc writes types, e.g. `Result (List Str) I64`, or `{}` for [RocLayout::Unit]
+impl FromStr for RocLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spaced = s.replace('(', " ( ").replace(')', " ) ");
+        let mut tokens = spaced.split_whitespace().peekable();
+
+        let layout = parse_layout(&mut tokens)?;
+
+        match tokens.next() {
+            None => Ok(layout),
+            Some(token) => Err(format!("unexpected `{}` after the end of the type", token)),
+        }
+    }
+}
+
+type Tokens<'s> = std::iter::Peekable<std::str::SplitWhitespace<'s>>;
+
+/// A type, including a type constructor applied to its arguments
+fn parse_layout(tokens: &mut Tokens) -> Result<RocLayout, String> {
+    match tokens.peek() {
+        Some(&"List") => {
+            tokens.next();
+            Ok(RocLayout::list(parse_argument(tokens)?))
+        }
+        Some(&"Result") => {
+            tokens.next();
+            let ok = parse_argument(tokens)?;
+            let err = parse_argument(tokens)?;
+            Ok(RocLayout::result(ok, err))
+        }
+        _ => parse_argument(tokens),
+    }
+}
+
+/// A type without arguments, or one in parentheses
+fn parse_argument(tokens: &mut Tokens) -> Result<RocLayout, String> {
+    let layout = match tokens.next() {
+        Some("(") => {
+            let layout = parse_layout(tokens)?;
+            return match tokens.next() {
+                Some(")") => Ok(layout),
+                _ => Err("expected a `)`".to_string()),
+            };
+        }
+        Some("{}") => RocLayout::Unit,
+        Some("Bool") => RocLayout::Bool,
+        Some("U8") => RocLayout::U8,
+        Some("I8") => RocLayout::I8,
+        Some("U16") => RocLayout::U16,
+        Some("I16") => RocLayout::I16,
+        Some("U32") => RocLayout::U32,
+        Some("I32") => RocLayout::I32,
+        Some("U64") => RocLayout::U64,
+        Some("I64") => RocLayout::I64,
+        Some("F32") => RocLayout::F32,
+        Some("F64") => RocLayout::F64,
+        Some("Str") => RocLayout::Str,
+        Some(name @ ("List" | "Result")) => {
+            return Err(format!(
+                "`{}` needs parentheses when it's an argument",
+                name
+            ))
+        }
+        Some(name) => return Err(format!("unknown type `{}`", name)),
+        None => return Err("expected a type".to_string()),
+    };
+
+    Ok(layout)
+}
+
+/// A Roc value decoded from memory. It's displayed the way Roc code would write it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RocValue {
+    Unit,
+    Bool(bool),
+    /// An integer of any width
+    Int(i128),
+    F32(f32),
+    F64(f64),
+    Str(String),
+    List(Vec<RocValue>),
+    Ok(Box<RocValue>),
+    Err(Box<RocValue>),
+}
+
+impl RocValue {
+    /// Tags with payloads need parentheses when they're a tag's payload themselves
+    fn fmt_payload(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RocValue::Ok(_) | RocValue::Err(_) => write!(f, "({})", self),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+impl fmt::Display for RocValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RocValue::Unit => write!(f, "{{}}"),
+            RocValue::Bool(true) => write!(f, "Bool.true"),
+            RocValue::Bool(false) => write!(f, "Bool.false"),
+            RocValue::Int(int) => write!(f, "{}", int),
+            RocValue::F32(float) => write!(f, "{:?}", float),
+            RocValue::F64(float) => write!(f, "{:?}", float),
+            RocValue::Str(string) => {
+                write!(f, "\"")?;
+                for c in string.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if c.is_control() => write!(f, "\\u({:x})", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            RocValue::List(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            RocValue::Ok(payload) => {
+                write!(f, "Ok ")?;
+                payload.fmt_payload(f)
+            }
+            RocValue::Err(payload) => {
+                write!(f, "Err ")?;
+                payload.fmt_payload(f)
+            }
+        }
+    }
+}
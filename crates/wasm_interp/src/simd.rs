@@ -0,0 +1,843 @@
+//! Instructions from the SIMD proposal, which operate on 128-bit vectors.
+//! https://github.com/WebAssembly/spec/blob/main/proposals/simd/SIMD.md
+//!
+//! Vectors are stored as [Value::V128], with lane 0 in the least significant bits,
+//! which is the same as their byte order in memory.
+
+use roc_wasm_module::opcodes::SimdOp;
+use roc_wasm_module::parse::Parse;
+use roc_wasm_module::{Value, WasmModule};
+use std::array;
+use std::ops::Mul;
+
+use crate::instance::Instance;
+use crate::{Error, ImportDispatcher};
+
+macro_rules! lanes {
+    ($to_lanes: ident, $from_lanes: ident, $ty: ty, $n: literal) => {
+        fn $to_lanes(v: u128) -> [$ty; $n] {
+            const SIZE: usize = 16 / $n;
+            let bytes = v.to_le_bytes();
+            array::from_fn(|i| <$ty>::from_le_bytes(bytes[i * SIZE..][..SIZE].try_into().unwrap()))
+        }
+
+        fn $from_lanes(lanes: [$ty; $n]) -> u128 {
+            const SIZE: usize = 16 / $n;
+            let mut bytes = [0; 16];
+            for (i, lane) in lanes.iter().enumerate() {
+                bytes[i * SIZE..][..SIZE].copy_from_slice(&lane.to_le_bytes());
+            }
+            u128::from_le_bytes(bytes)
+        }
+    };
+}
+
+lanes!(i8x16, from_i8x16, i8, 16);
+lanes!(u8x16, from_u8x16, u8, 16);
+lanes!(i16x8, from_i16x8, i16, 8);
+lanes!(u16x8, from_u16x8, u16, 8);
+lanes!(i32x4, from_i32x4, i32, 4);
+lanes!(u32x4, from_u32x4, u32, 4);
+lanes!(i64x2, from_i64x2, i64, 2);
+lanes!(u64x2, from_u64x2, u64, 2);
+lanes!(f32x4, from_f32x4, f32, 4);
+lanes!(f64x2, from_f64x2, f64, 2);
+
+fn zip<T: Copy, const N: usize>(a: [T; N], b: [T; N], f: impl Fn(T, T) -> T) -> [T; N] {
+    array::from_fn(|i| f(a[i], b[i]))
+}
+
+/// Half of the lanes, starting at `start`, converted to a type twice as wide
+fn widen<T: Copy, U, const N: usize, const M: usize>(
+    lanes: [T; N],
+    start: usize,
+    f: impl Fn(T) -> U,
+) -> [U; M] {
+    array::from_fn(|i| f(lanes[start + i]))
+}
+
+/// Half of the lanes of each vector, starting at `start`, widened and multiplied
+fn extmul<T: Copy, U: Mul<Output = U>, const N: usize, const M: usize>(
+    a: [T; N],
+    b: [T; N],
+    start: usize,
+    f: impl Fn(T) -> U,
+) -> [U; M] {
+    array::from_fn(|i| f(a[start + i]) * f(b[start + i]))
+}
+
+/// Each pair of neighbouring lanes combined into one lane twice as wide
+fn pairwise<T: Copy, U, const N: usize, const M: usize>(
+    lanes: [T; N],
+    f: impl Fn(T, T) -> U,
+) -> [U; M] {
+    array::from_fn(|i| f(lanes[2 * i], lanes[2 * i + 1]))
+}
+
+/// The lanes of both vectors, converted to a type half as wide
+fn narrow<T: Copy, U, const N: usize, const M: usize>(
+    a: [T; N],
+    b: [T; N],
+    f: impl Fn(T) -> U,
+) -> [U; M] {
+    array::from_fn(|i| if i < N { f(a[i]) } else { f(b[i - N]) })
+}
+
+fn compare<T: Copy, const N: usize>(a: [T; N], b: [T; N], f: impl Fn(T, T) -> bool) -> [bool; N] {
+    array::from_fn(|i| f(a[i], b[i]))
+}
+
+/// A vector with all bits set in the lanes that are true, and clear in the others
+fn mask<const N: usize>(lanes: [bool; N]) -> u128 {
+    let lane_bits = 128 / N;
+    let ones = u128::MAX >> (128 - lane_bits);
+    lanes
+        .iter()
+        .enumerate()
+        .filter(|(_, is_true)| **is_true)
+        .fold(0, |v, (i, _)| v | (ones << (i * lane_bits)))
+}
+
+/// An i32 with bit `i` set if the top bit of lane `i` is set
+fn bitmask<T: Copy + Default + PartialOrd, const N: usize>(lanes: [T; N]) -> i32 {
+    lanes
+        .iter()
+        .enumerate()
+        .filter(|(_, lane)| **lane < T::default())
+        .fold(0, |bits, (i, _)| bits | (1 << i))
+}
+
+fn all_true<T: Copy + Default + PartialEq, const N: usize>(lanes: [T; N]) -> bool {
+    lanes.iter().all(|lane| *lane != T::default())
+}
+
+/// Float operations that WebAssembly defines differently from Rust
+trait WasmFloat: Copy {
+    fn nearest(self) -> Self;
+    fn wasm_min(self, other: Self) -> Self;
+    fn wasm_max(self, other: Self) -> Self;
+}
+
+macro_rules! wasm_float {
+    ($ty: ty) => {
+        impl WasmFloat for $ty {
+            /// https://webassembly.github.io/spec/core/exec/numerics.html#op-fnearest
+            fn nearest(self) -> Self {
+                let rounded = self.round(); // "Rounds half-way cases away from 0.0"
+                if (self - rounded).abs() == 0.5 && rounded % 2.0 != 0.0 {
+                    (rounded - self.signum()).copysign(self)
+                } else {
+                    rounded
+                }
+            }
+
+            /// https://webassembly.github.io/spec/core/exec/numerics.html#op-fmin
+            fn wasm_min(self, other: Self) -> Self {
+                if self.is_nan() || other.is_nan() {
+                    <$ty>::NAN
+                } else if self == other {
+                    // -0.0 is less than 0.0
+                    if self.is_sign_negative() {
+                        self
+                    } else {
+                        other
+                    }
+                } else if self < other {
+                    self
+                } else {
+                    other
+                }
+            }
+
+            /// https://webassembly.github.io/spec/core/exec/numerics.html#op-fmax
+            fn wasm_max(self, other: Self) -> Self {
+                if self.is_nan() || other.is_nan() {
+                    <$ty>::NAN
+                } else if self == other {
+                    if self.is_sign_positive() {
+                        self
+                    } else {
+                        other
+                    }
+                } else if self > other {
+                    self
+                } else {
+                    other
+                }
+            }
+        }
+    };
+}
+
+wasm_float!(f32);
+wasm_float!(f64);
+
+impl<'a, I: ImportDispatcher> Instance<'a, I> {
+    /// Execute the instruction after a [SIMDPREFIX](roc_wasm_module::opcodes::OpCode::SIMDPREFIX)
+    pub(crate) fn execute_simd(&mut self, module: &WasmModule<'a>) -> Result<(), Error> {
+        use SimdOp::*;
+
+        let op_addr = self.program_counter - 1;
+        let simd_op = u32::parse((), &module.code.bytes, &mut self.program_counter).unwrap();
+        let op = SimdOp::from_u32(simd_op).ok_or(Error::UnsupportedSimdOp(simd_op))?;
+        self.write_debug(op);
+
+        match op {
+            V128LOAD => {
+                let bytes: [u8; 16] = self.load_bytes(module, op_addr)?;
+                self.push_v128(u128::from_le_bytes(bytes));
+            }
+            V128LOAD8X8S => {
+                let bytes: [u8; 8] = self.load_bytes(module, op_addr)?;
+                self.push_v128(from_i16x8(bytes.map(|b| b as i8 as i16)));
+            }
+            V128LOAD8X8U => {
+                let bytes: [u8; 8] = self.load_bytes(module, op_addr)?;
+                self.push_v128(from_u16x8(bytes.map(|b| b as u16)));
+            }
+            V128LOAD16X4S => {
+                let bytes: [u8; 8] = self.load_bytes(module, op_addr)?;
+                let lanes = i16x8(u64::from_le_bytes(bytes) as u128);
+                self.push_v128(from_i32x4(array::from_fn(|i| lanes[i] as i32)));
+            }
+            V128LOAD16X4U => {
+                let bytes: [u8; 8] = self.load_bytes(module, op_addr)?;
+                let lanes = u16x8(u64::from_le_bytes(bytes) as u128);
+                self.push_v128(from_u32x4(array::from_fn(|i| lanes[i] as u32)));
+            }
+            V128LOAD32X2S => {
+                let bytes: [u8; 8] = self.load_bytes(module, op_addr)?;
+                let lanes = i32x4(u64::from_le_bytes(bytes) as u128);
+                self.push_v128(from_i64x2(array::from_fn(|i| lanes[i] as i64)));
+            }
+            V128LOAD32X2U => {
+                let bytes: [u8; 8] = self.load_bytes(module, op_addr)?;
+                let lanes = u32x4(u64::from_le_bytes(bytes) as u128);
+                self.push_v128(from_u64x2(array::from_fn(|i| lanes[i] as u64)));
+            }
+            V128LOAD8SPLAT => {
+                let bytes: [u8; 1] = self.load_bytes(module, op_addr)?;
+                self.push_v128(from_u8x16([bytes[0]; 16]));
+            }
+            V128LOAD16SPLAT => {
+                let bytes = self.load_bytes(module, op_addr)?;
+                self.push_v128(from_u16x8([u16::from_le_bytes(bytes); 8]));
+            }
+            V128LOAD32SPLAT => {
+                let bytes = self.load_bytes(module, op_addr)?;
+                self.push_v128(from_u32x4([u32::from_le_bytes(bytes); 4]));
+            }
+            V128LOAD64SPLAT => {
+                let bytes = self.load_bytes(module, op_addr)?;
+                self.push_v128(from_u64x2([u64::from_le_bytes(bytes); 2]));
+            }
+            V128LOAD32ZERO => {
+                let bytes = self.load_bytes(module, op_addr)?;
+                self.push_v128(u32::from_le_bytes(bytes) as u128);
+            }
+            V128LOAD64ZERO => {
+                let bytes = self.load_bytes(module, op_addr)?;
+                self.push_v128(u64::from_le_bytes(bytes) as u128);
+            }
+            V128STORE => {
+                let offset = self.fetch_memarg(module);
+                let v = self.value_store.pop_v128()?;
+                let base_addr = self.value_store.pop_u32()?;
+                let addr = self.check_memory_access(op_addr, base_addr, offset, 16)?;
+                self.note_memory_write(addr, 16);
+                self.memory[addr..][..16].copy_from_slice(&v.to_le_bytes());
+            }
+            V128LOAD8LANE => self.load_lane(module, op_addr, 1)?,
+            V128LOAD16LANE => self.load_lane(module, op_addr, 2)?,
+            V128LOAD32LANE => self.load_lane(module, op_addr, 4)?,
+            V128LOAD64LANE => self.load_lane(module, op_addr, 8)?,
+            V128STORE8LANE => self.store_lane(module, op_addr, 1)?,
+            V128STORE16LANE => self.store_lane(module, op_addr, 2)?,
+            V128STORE32LANE => self.store_lane(module, op_addr, 4)?,
+            V128STORE64LANE => self.store_lane(module, op_addr, 8)?,
+
+            V128CONST => {
+                let bytes = self.fetch_immediate_bytes(module);
+                self.push_v128(u128::from_le_bytes(bytes));
+            }
+            I8X16SHUFFLE => {
+                let indices: [u8; 16] = self.fetch_immediate_bytes(module);
+                // The lanes of both vectors are numbered together
+                if let Some(&lane) = indices.iter().find(|i| **i >= 32) {
+                    return Err(Error::InvalidLane { lane, n_lanes: 32 });
+                }
+                let b = self.value_store.pop_v128()?.to_le_bytes();
+                let a = self.value_store.pop_v128()?.to_le_bytes();
+                let result = indices.map(|i| {
+                    let i = i as usize;
+                    if i < 16 {
+                        a[i]
+                    } else {
+                        b[i - 16]
+                    }
+                });
+                self.push_v128(from_u8x16(result));
+            }
+            I8X16SWIZZLE => self.binop(|a, s| {
+                let a = u8x16(a);
+                from_u8x16(u8x16(s).map(|i| a.get(i as usize).copied().unwrap_or(0)))
+            })?,
+
+            I8X16SPLAT => {
+                let x = self.value_store.pop_i32()?;
+                self.push_v128(from_i8x16([x as i8; 16]));
+            }
+            I16X8SPLAT => {
+                let x = self.value_store.pop_i32()?;
+                self.push_v128(from_i16x8([x as i16; 8]));
+            }
+            I32X4SPLAT => {
+                let x = self.value_store.pop_i32()?;
+                self.push_v128(from_i32x4([x; 4]));
+            }
+            I64X2SPLAT => {
+                let x = self.value_store.pop_i64()?;
+                self.push_v128(from_i64x2([x; 2]));
+            }
+            F32X4SPLAT => {
+                let x = self.value_store.pop_f32()?;
+                self.push_v128(from_f32x4([x; 4]));
+            }
+            F64X2SPLAT => {
+                let x = self.value_store.pop_f64()?;
+                self.push_v128(from_f64x2([x; 2]));
+            }
+
+            I8X16EXTRACTLANES => {
+                let lane = self.fetch_lane(module, 16)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::I32(i8x16(v)[lane] as i32));
+            }
+            I8X16EXTRACTLANEU => {
+                let lane = self.fetch_lane(module, 16)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::I32(u8x16(v)[lane] as i32));
+            }
+            I16X8EXTRACTLANES => {
+                let lane = self.fetch_lane(module, 8)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::I32(i16x8(v)[lane] as i32));
+            }
+            I16X8EXTRACTLANEU => {
+                let lane = self.fetch_lane(module, 8)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::I32(u16x8(v)[lane] as i32));
+            }
+            I32X4EXTRACTLANE => {
+                let lane = self.fetch_lane(module, 4)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::I32(i32x4(v)[lane]));
+            }
+            I64X2EXTRACTLANE => {
+                let lane = self.fetch_lane(module, 2)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::I64(i64x2(v)[lane]));
+            }
+            F32X4EXTRACTLANE => {
+                let lane = self.fetch_lane(module, 4)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::F32(f32x4(v)[lane]));
+            }
+            F64X2EXTRACTLANE => {
+                let lane = self.fetch_lane(module, 2)?;
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::F64(f64x2(v)[lane]));
+            }
+
+            I8X16REPLACELANE => {
+                let lane = self.fetch_lane(module, 16)?;
+                let x = self.value_store.pop_i32()?;
+                let mut lanes = i8x16(self.value_store.pop_v128()?);
+                lanes[lane] = x as i8;
+                self.push_v128(from_i8x16(lanes));
+            }
+            I16X8REPLACELANE => {
+                let lane = self.fetch_lane(module, 8)?;
+                let x = self.value_store.pop_i32()?;
+                let mut lanes = i16x8(self.value_store.pop_v128()?);
+                lanes[lane] = x as i16;
+                self.push_v128(from_i16x8(lanes));
+            }
+            I32X4REPLACELANE => {
+                let lane = self.fetch_lane(module, 4)?;
+                let x = self.value_store.pop_i32()?;
+                let mut lanes = i32x4(self.value_store.pop_v128()?);
+                lanes[lane] = x;
+                self.push_v128(from_i32x4(lanes));
+            }
+            I64X2REPLACELANE => {
+                let lane = self.fetch_lane(module, 2)?;
+                let x = self.value_store.pop_i64()?;
+                let mut lanes = i64x2(self.value_store.pop_v128()?);
+                lanes[lane] = x;
+                self.push_v128(from_i64x2(lanes));
+            }
+            F32X4REPLACELANE => {
+                let lane = self.fetch_lane(module, 4)?;
+                let x = self.value_store.pop_f32()?;
+                let mut lanes = f32x4(self.value_store.pop_v128()?);
+                lanes[lane] = x;
+                self.push_v128(from_f32x4(lanes));
+            }
+            F64X2REPLACELANE => {
+                let lane = self.fetch_lane(module, 2)?;
+                let x = self.value_store.pop_f64()?;
+                let mut lanes = f64x2(self.value_store.pop_v128()?);
+                lanes[lane] = x;
+                self.push_v128(from_f64x2(lanes));
+            }
+
+            I8X16EQ => self.binop(|a, b| mask(compare(u8x16(a), u8x16(b), |x, y| x == y)))?,
+            I8X16NE => self.binop(|a, b| mask(compare(u8x16(a), u8x16(b), |x, y| x != y)))?,
+            I8X16LTS => self.binop(|a, b| mask(compare(i8x16(a), i8x16(b), |x, y| x < y)))?,
+            I8X16LTU => self.binop(|a, b| mask(compare(u8x16(a), u8x16(b), |x, y| x < y)))?,
+            I8X16GTS => self.binop(|a, b| mask(compare(i8x16(a), i8x16(b), |x, y| x > y)))?,
+            I8X16GTU => self.binop(|a, b| mask(compare(u8x16(a), u8x16(b), |x, y| x > y)))?,
+            I8X16LES => self.binop(|a, b| mask(compare(i8x16(a), i8x16(b), |x, y| x <= y)))?,
+            I8X16LEU => self.binop(|a, b| mask(compare(u8x16(a), u8x16(b), |x, y| x <= y)))?,
+            I8X16GES => self.binop(|a, b| mask(compare(i8x16(a), i8x16(b), |x, y| x >= y)))?,
+            I8X16GEU => self.binop(|a, b| mask(compare(u8x16(a), u8x16(b), |x, y| x >= y)))?,
+
+            I16X8EQ => self.binop(|a, b| mask(compare(u16x8(a), u16x8(b), |x, y| x == y)))?,
+            I16X8NE => self.binop(|a, b| mask(compare(u16x8(a), u16x8(b), |x, y| x != y)))?,
+            I16X8LTS => self.binop(|a, b| mask(compare(i16x8(a), i16x8(b), |x, y| x < y)))?,
+            I16X8LTU => self.binop(|a, b| mask(compare(u16x8(a), u16x8(b), |x, y| x < y)))?,
+            I16X8GTS => self.binop(|a, b| mask(compare(i16x8(a), i16x8(b), |x, y| x > y)))?,
+            I16X8GTU => self.binop(|a, b| mask(compare(u16x8(a), u16x8(b), |x, y| x > y)))?,
+            I16X8LES => self.binop(|a, b| mask(compare(i16x8(a), i16x8(b), |x, y| x <= y)))?,
+            I16X8LEU => self.binop(|a, b| mask(compare(u16x8(a), u16x8(b), |x, y| x <= y)))?,
+            I16X8GES => self.binop(|a, b| mask(compare(i16x8(a), i16x8(b), |x, y| x >= y)))?,
+            I16X8GEU => self.binop(|a, b| mask(compare(u16x8(a), u16x8(b), |x, y| x >= y)))?,
+
+            I32X4EQ => self.binop(|a, b| mask(compare(u32x4(a), u32x4(b), |x, y| x == y)))?,
+            I32X4NE => self.binop(|a, b| mask(compare(u32x4(a), u32x4(b), |x, y| x != y)))?,
+            I32X4LTS => self.binop(|a, b| mask(compare(i32x4(a), i32x4(b), |x, y| x < y)))?,
+            I32X4LTU => self.binop(|a, b| mask(compare(u32x4(a), u32x4(b), |x, y| x < y)))?,
+            I32X4GTS => self.binop(|a, b| mask(compare(i32x4(a), i32x4(b), |x, y| x > y)))?,
+            I32X4GTU => self.binop(|a, b| mask(compare(u32x4(a), u32x4(b), |x, y| x > y)))?,
+            I32X4LES => self.binop(|a, b| mask(compare(i32x4(a), i32x4(b), |x, y| x <= y)))?,
+            I32X4LEU => self.binop(|a, b| mask(compare(u32x4(a), u32x4(b), |x, y| x <= y)))?,
+            I32X4GES => self.binop(|a, b| mask(compare(i32x4(a), i32x4(b), |x, y| x >= y)))?,
+            I32X4GEU => self.binop(|a, b| mask(compare(u32x4(a), u32x4(b), |x, y| x >= y)))?,
+
+            I64X2EQ => self.binop(|a, b| mask(compare(i64x2(a), i64x2(b), |x, y| x == y)))?,
+            I64X2NE => self.binop(|a, b| mask(compare(i64x2(a), i64x2(b), |x, y| x != y)))?,
+            I64X2LTS => self.binop(|a, b| mask(compare(i64x2(a), i64x2(b), |x, y| x < y)))?,
+            I64X2GTS => self.binop(|a, b| mask(compare(i64x2(a), i64x2(b), |x, y| x > y)))?,
+            I64X2LES => self.binop(|a, b| mask(compare(i64x2(a), i64x2(b), |x, y| x <= y)))?,
+            I64X2GES => self.binop(|a, b| mask(compare(i64x2(a), i64x2(b), |x, y| x >= y)))?,
+
+            F32X4EQ => self.binop(|a, b| mask(compare(f32x4(a), f32x4(b), |x, y| x == y)))?,
+            F32X4NE => self.binop(|a, b| mask(compare(f32x4(a), f32x4(b), |x, y| x != y)))?,
+            F32X4LT => self.binop(|a, b| mask(compare(f32x4(a), f32x4(b), |x, y| x < y)))?,
+            F32X4GT => self.binop(|a, b| mask(compare(f32x4(a), f32x4(b), |x, y| x > y)))?,
+            F32X4LE => self.binop(|a, b| mask(compare(f32x4(a), f32x4(b), |x, y| x <= y)))?,
+            F32X4GE => self.binop(|a, b| mask(compare(f32x4(a), f32x4(b), |x, y| x >= y)))?,
+
+            F64X2EQ => self.binop(|a, b| mask(compare(f64x2(a), f64x2(b), |x, y| x == y)))?,
+            F64X2NE => self.binop(|a, b| mask(compare(f64x2(a), f64x2(b), |x, y| x != y)))?,
+            F64X2LT => self.binop(|a, b| mask(compare(f64x2(a), f64x2(b), |x, y| x < y)))?,
+            F64X2GT => self.binop(|a, b| mask(compare(f64x2(a), f64x2(b), |x, y| x > y)))?,
+            F64X2LE => self.binop(|a, b| mask(compare(f64x2(a), f64x2(b), |x, y| x <= y)))?,
+            F64X2GE => self.binop(|a, b| mask(compare(f64x2(a), f64x2(b), |x, y| x >= y)))?,
+
+            V128NOT => self.unop(|a| !a)?,
+            V128AND => self.binop(|a, b| a & b)?,
+            V128ANDNOT => self.binop(|a, b| a & !b)?,
+            V128OR => self.binop(|a, b| a | b)?,
+            V128XOR => self.binop(|a, b| a ^ b)?,
+            V128BITSELECT => {
+                let c = self.value_store.pop_v128()?;
+                let b = self.value_store.pop_v128()?;
+                let a = self.value_store.pop_v128()?;
+                self.push_v128((a & c) | (b & !c));
+            }
+            V128ANYTRUE => {
+                let v = self.value_store.pop_v128()?;
+                self.value_store.push(Value::I32((v != 0) as i32));
+            }
+
+            I8X16ABS => self.unop(|a| from_i8x16(i8x16(a).map(i8::wrapping_abs)))?,
+            I8X16NEG => self.unop(|a| from_i8x16(i8x16(a).map(i8::wrapping_neg)))?,
+            I8X16POPCNT => self.unop(|a| from_u8x16(u8x16(a).map(|x| x.count_ones() as u8)))?,
+            I8X16ALLTRUE => self.summarize(|a| all_true(u8x16(a)) as i32)?,
+            I8X16BITMASK => self.summarize(|a| bitmask(i8x16(a)))?,
+            I8X16SHL => self.shift(|a, n| from_i8x16(i8x16(a).map(|x| x.wrapping_shl(n))))?,
+            I8X16SHRS => self.shift(|a, n| from_i8x16(i8x16(a).map(|x| x.wrapping_shr(n))))?,
+            I8X16SHRU => self.shift(|a, n| from_u8x16(u8x16(a).map(|x| x.wrapping_shr(n))))?,
+            I8X16ADD => self.binop(|a, b| from_i8x16(zip(i8x16(a), i8x16(b), i8::wrapping_add)))?,
+            I8X16ADDSATS => {
+                self.binop(|a, b| from_i8x16(zip(i8x16(a), i8x16(b), i8::saturating_add)))?
+            }
+            I8X16ADDSATU => {
+                self.binop(|a, b| from_u8x16(zip(u8x16(a), u8x16(b), u8::saturating_add)))?
+            }
+            I8X16SUB => self.binop(|a, b| from_i8x16(zip(i8x16(a), i8x16(b), i8::wrapping_sub)))?,
+            I8X16SUBSATS => {
+                self.binop(|a, b| from_i8x16(zip(i8x16(a), i8x16(b), i8::saturating_sub)))?
+            }
+            I8X16SUBSATU => {
+                self.binop(|a, b| from_u8x16(zip(u8x16(a), u8x16(b), u8::saturating_sub)))?
+            }
+            I8X16MINS => self.binop(|a, b| from_i8x16(zip(i8x16(a), i8x16(b), i8::min)))?,
+            I8X16MINU => self.binop(|a, b| from_u8x16(zip(u8x16(a), u8x16(b), u8::min)))?,
+            I8X16MAXS => self.binop(|a, b| from_i8x16(zip(i8x16(a), i8x16(b), i8::max)))?,
+            I8X16MAXU => self.binop(|a, b| from_u8x16(zip(u8x16(a), u8x16(b), u8::max)))?,
+            I8X16AVGRU => self.binop(|a, b| {
+                from_u8x16(zip(u8x16(a), u8x16(b), |x, y| {
+                    ((x as u16 + y as u16 + 1) / 2) as u8
+                }))
+            })?,
+
+            I16X8ABS => self.unop(|a| from_i16x8(i16x8(a).map(i16::wrapping_abs)))?,
+            I16X8NEG => self.unop(|a| from_i16x8(i16x8(a).map(i16::wrapping_neg)))?,
+            I16X8ALLTRUE => self.summarize(|a| all_true(u16x8(a)) as i32)?,
+            I16X8BITMASK => self.summarize(|a| bitmask(i16x8(a)))?,
+            I16X8SHL => self.shift(|a, n| from_i16x8(i16x8(a).map(|x| x.wrapping_shl(n))))?,
+            I16X8SHRS => self.shift(|a, n| from_i16x8(i16x8(a).map(|x| x.wrapping_shr(n))))?,
+            I16X8SHRU => self.shift(|a, n| from_u16x8(u16x8(a).map(|x| x.wrapping_shr(n))))?,
+            I16X8ADD => {
+                self.binop(|a, b| from_i16x8(zip(i16x8(a), i16x8(b), i16::wrapping_add)))?
+            }
+            I16X8ADDSATS => {
+                self.binop(|a, b| from_i16x8(zip(i16x8(a), i16x8(b), i16::saturating_add)))?
+            }
+            I16X8ADDSATU => {
+                self.binop(|a, b| from_u16x8(zip(u16x8(a), u16x8(b), u16::saturating_add)))?
+            }
+            I16X8SUB => {
+                self.binop(|a, b| from_i16x8(zip(i16x8(a), i16x8(b), i16::wrapping_sub)))?
+            }
+            I16X8SUBSATS => {
+                self.binop(|a, b| from_i16x8(zip(i16x8(a), i16x8(b), i16::saturating_sub)))?
+            }
+            I16X8SUBSATU => {
+                self.binop(|a, b| from_u16x8(zip(u16x8(a), u16x8(b), u16::saturating_sub)))?
+            }
+            I16X8MUL => {
+                self.binop(|a, b| from_i16x8(zip(i16x8(a), i16x8(b), i16::wrapping_mul)))?
+            }
+            I16X8MINS => self.binop(|a, b| from_i16x8(zip(i16x8(a), i16x8(b), i16::min)))?,
+            I16X8MINU => self.binop(|a, b| from_u16x8(zip(u16x8(a), u16x8(b), u16::min)))?,
+            I16X8MAXS => self.binop(|a, b| from_i16x8(zip(i16x8(a), i16x8(b), i16::max)))?,
+            I16X8MAXU => self.binop(|a, b| from_u16x8(zip(u16x8(a), u16x8(b), u16::max)))?,
+            I16X8AVGRU => self.binop(|a, b| {
+                from_u16x8(zip(u16x8(a), u16x8(b), |x, y| {
+                    ((x as u32 + y as u32 + 1) / 2) as u16
+                }))
+            })?,
+
+            I32X4ABS => self.unop(|a| from_i32x4(i32x4(a).map(i32::wrapping_abs)))?,
+            I32X4NEG => self.unop(|a| from_i32x4(i32x4(a).map(i32::wrapping_neg)))?,
+            I32X4ALLTRUE => self.summarize(|a| all_true(u32x4(a)) as i32)?,
+            I32X4BITMASK => self.summarize(|a| bitmask(i32x4(a)))?,
+            I32X4SHL => self.shift(|a, n| from_i32x4(i32x4(a).map(|x| x.wrapping_shl(n))))?,
+            I32X4SHRS => self.shift(|a, n| from_i32x4(i32x4(a).map(|x| x.wrapping_shr(n))))?,
+            I32X4SHRU => self.shift(|a, n| from_u32x4(u32x4(a).map(|x| x.wrapping_shr(n))))?,
+            I32X4ADD => {
+                self.binop(|a, b| from_i32x4(zip(i32x4(a), i32x4(b), i32::wrapping_add)))?
+            }
+            I32X4SUB => {
+                self.binop(|a, b| from_i32x4(zip(i32x4(a), i32x4(b), i32::wrapping_sub)))?
+            }
+            I32X4MUL => {
+                self.binop(|a, b| from_i32x4(zip(i32x4(a), i32x4(b), i32::wrapping_mul)))?
+            }
+            I32X4MINS => self.binop(|a, b| from_i32x4(zip(i32x4(a), i32x4(b), i32::min)))?,
+            I32X4MINU => self.binop(|a, b| from_u32x4(zip(u32x4(a), u32x4(b), u32::min)))?,
+            I32X4MAXS => self.binop(|a, b| from_i32x4(zip(i32x4(a), i32x4(b), i32::max)))?,
+            I32X4MAXU => self.binop(|a, b| from_u32x4(zip(u32x4(a), u32x4(b), u32::max)))?,
+
+            I64X2ABS => self.unop(|a| from_i64x2(i64x2(a).map(i64::wrapping_abs)))?,
+            I64X2NEG => self.unop(|a| from_i64x2(i64x2(a).map(i64::wrapping_neg)))?,
+            I64X2ALLTRUE => self.summarize(|a| all_true(u64x2(a)) as i32)?,
+            I64X2BITMASK => self.summarize(|a| bitmask(i64x2(a)))?,
+            I64X2SHL => self.shift(|a, n| from_i64x2(i64x2(a).map(|x| x.wrapping_shl(n))))?,
+            I64X2SHRS => self.shift(|a, n| from_i64x2(i64x2(a).map(|x| x.wrapping_shr(n))))?,
+            I64X2SHRU => self.shift(|a, n| from_u64x2(u64x2(a).map(|x| x.wrapping_shr(n))))?,
+            I64X2ADD => {
+                self.binop(|a, b| from_i64x2(zip(i64x2(a), i64x2(b), i64::wrapping_add)))?
+            }
+            I64X2SUB => {
+                self.binop(|a, b| from_i64x2(zip(i64x2(a), i64x2(b), i64::wrapping_sub)))?
+            }
+            I64X2MUL => {
+                self.binop(|a, b| from_i64x2(zip(i64x2(a), i64x2(b), i64::wrapping_mul)))?
+            }
+
+            F32X4ABS => self.unop(|a| from_f32x4(f32x4(a).map(f32::abs)))?,
+            F32X4NEG => self.unop(|a| from_f32x4(f32x4(a).map(|x| -x)))?,
+            F32X4SQRT => self.unop(|a| from_f32x4(f32x4(a).map(f32::sqrt)))?,
+            F32X4CEIL => self.unop(|a| from_f32x4(f32x4(a).map(f32::ceil)))?,
+            F32X4FLOOR => self.unop(|a| from_f32x4(f32x4(a).map(f32::floor)))?,
+            F32X4TRUNC => self.unop(|a| from_f32x4(f32x4(a).map(f32::trunc)))?,
+            F32X4NEAREST => self.unop(|a| from_f32x4(f32x4(a).map(f32::nearest)))?,
+            F32X4ADD => self.binop(|a, b| from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x + y)))?,
+            F32X4SUB => self.binop(|a, b| from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x - y)))?,
+            F32X4MUL => self.binop(|a, b| from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x * y)))?,
+            F32X4DIV => self.binop(|a, b| from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x / y)))?,
+            F32X4MIN => self.binop(|a, b| from_f32x4(zip(f32x4(a), f32x4(b), f32::wasm_min)))?,
+            F32X4MAX => self.binop(|a, b| from_f32x4(zip(f32x4(a), f32x4(b), f32::wasm_max)))?,
+            F32X4PMIN => self.binop(|a, b| {
+                from_f32x4(zip(f32x4(a), f32x4(b), |x, y| if y < x { y } else { x }))
+            })?,
+            F32X4PMAX => self.binop(|a, b| {
+                from_f32x4(zip(f32x4(a), f32x4(b), |x, y| if x < y { y } else { x }))
+            })?,
+
+            F64X2ABS => self.unop(|a| from_f64x2(f64x2(a).map(f64::abs)))?,
+            F64X2NEG => self.unop(|a| from_f64x2(f64x2(a).map(|x| -x)))?,
+            F64X2SQRT => self.unop(|a| from_f64x2(f64x2(a).map(f64::sqrt)))?,
+            F64X2CEIL => self.unop(|a| from_f64x2(f64x2(a).map(f64::ceil)))?,
+            F64X2FLOOR => self.unop(|a| from_f64x2(f64x2(a).map(f64::floor)))?,
+            F64X2TRUNC => self.unop(|a| from_f64x2(f64x2(a).map(f64::trunc)))?,
+            F64X2NEAREST => self.unop(|a| from_f64x2(f64x2(a).map(f64::nearest)))?,
+            F64X2ADD => self.binop(|a, b| from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x + y)))?,
+            F64X2SUB => self.binop(|a, b| from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x - y)))?,
+            F64X2MUL => self.binop(|a, b| from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x * y)))?,
+            F64X2DIV => self.binop(|a, b| from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x / y)))?,
+            F64X2MIN => self.binop(|a, b| from_f64x2(zip(f64x2(a), f64x2(b), f64::wasm_min)))?,
+            F64X2MAX => self.binop(|a, b| from_f64x2(zip(f64x2(a), f64x2(b), f64::wasm_max)))?,
+            F64X2PMIN => self.binop(|a, b| {
+                from_f64x2(zip(f64x2(a), f64x2(b), |x, y| if y < x { y } else { x }))
+            })?,
+            F64X2PMAX => self.binop(|a, b| {
+                from_f64x2(zip(f64x2(a), f64x2(b), |x, y| if x < y { y } else { x }))
+            })?,
+
+            F32X4DEMOTEF64X2ZERO => self.unop(|a| {
+                let a = f64x2(a);
+                from_f32x4([a[0] as f32, a[1] as f32, 0.0, 0.0])
+            })?,
+            F64X2PROMOTELOWF32X4 => self.unop(|a| from_f64x2(widen(f32x4(a), 0, |x| x as f64)))?,
+
+            I8X16NARROWI16X8S => self.binop(|a, b| {
+                from_i8x16(narrow(i16x8(a), i16x8(b), |x| {
+                    x.clamp(i8::MIN as i16, i8::MAX as i16) as i8
+                }))
+            })?,
+            I8X16NARROWI16X8U => self.binop(|a, b| {
+                from_u8x16(narrow(i16x8(a), i16x8(b), |x| {
+                    x.clamp(0, u8::MAX as i16) as u8
+                }))
+            })?,
+            I16X8NARROWI32X4S => self.binop(|a, b| {
+                from_i16x8(narrow(i32x4(a), i32x4(b), |x| {
+                    x.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+                }))
+            })?,
+            I16X8NARROWI32X4U => self.binop(|a, b| {
+                from_u16x8(narrow(i32x4(a), i32x4(b), |x| {
+                    x.clamp(0, u16::MAX as i32) as u16
+                }))
+            })?,
+
+            I16X8EXTADDPAIRWISEI8X16S => {
+                self.unop(|a| from_i16x8(pairwise(i8x16(a), |x, y| x as i16 + y as i16)))?
+            }
+            I16X8EXTADDPAIRWISEI8X16U => {
+                self.unop(|a| from_u16x8(pairwise(u8x16(a), |x, y| x as u16 + y as u16)))?
+            }
+            I32X4EXTADDPAIRWISEI16X8S => {
+                self.unop(|a| from_i32x4(pairwise(i16x8(a), |x, y| x as i32 + y as i32)))?
+            }
+            I32X4EXTADDPAIRWISEI16X8U => {
+                self.unop(|a| from_u32x4(pairwise(u16x8(a), |x, y| x as u32 + y as u32)))?
+            }
+
+            I16X8Q15MULRSATS => self.binop(|a, b| {
+                from_i16x8(zip(i16x8(a), i16x8(b), |x, y| {
+                    let product = (x as i32 * y as i32 + 0x4000) >> 15;
+                    product.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+                }))
+            })?,
+
+            I16X8EXTENDLOWI8X16S => self.unop(|a| from_i16x8(widen(i8x16(a), 0, |x| x as i16)))?,
+            I16X8EXTENDHIGHI8X16S => self.unop(|a| from_i16x8(widen(i8x16(a), 8, |x| x as i16)))?,
+            I16X8EXTENDLOWI8X16U => self.unop(|a| from_u16x8(widen(u8x16(a), 0, |x| x as u16)))?,
+            I16X8EXTENDHIGHI8X16U => self.unop(|a| from_u16x8(widen(u8x16(a), 8, |x| x as u16)))?,
+            I32X4EXTENDLOWI16X8S => self.unop(|a| from_i32x4(widen(i16x8(a), 0, |x| x as i32)))?,
+            I32X4EXTENDHIGHI16X8S => self.unop(|a| from_i32x4(widen(i16x8(a), 4, |x| x as i32)))?,
+            I32X4EXTENDLOWI16X8U => self.unop(|a| from_u32x4(widen(u16x8(a), 0, |x| x as u32)))?,
+            I32X4EXTENDHIGHI16X8U => self.unop(|a| from_u32x4(widen(u16x8(a), 4, |x| x as u32)))?,
+            I64X2EXTENDLOWI32X4S => self.unop(|a| from_i64x2(widen(i32x4(a), 0, |x| x as i64)))?,
+            I64X2EXTENDHIGHI32X4S => self.unop(|a| from_i64x2(widen(i32x4(a), 2, |x| x as i64)))?,
+            I64X2EXTENDLOWI32X4U => self.unop(|a| from_u64x2(widen(u32x4(a), 0, |x| x as u64)))?,
+            I64X2EXTENDHIGHI32X4U => self.unop(|a| from_u64x2(widen(u32x4(a), 2, |x| x as u64)))?,
+
+            // The products can't overflow, since the lanes are widened first
+            I16X8EXTMULLOWI8X16S => {
+                self.binop(|a, b| from_i16x8(extmul(i8x16(a), i8x16(b), 0, |x| x as i16)))?
+            }
+            I16X8EXTMULHIGHI8X16S => {
+                self.binop(|a, b| from_i16x8(extmul(i8x16(a), i8x16(b), 8, |x| x as i16)))?
+            }
+            I16X8EXTMULLOWI8X16U => {
+                self.binop(|a, b| from_u16x8(extmul(u8x16(a), u8x16(b), 0, |x| x as u16)))?
+            }
+            I16X8EXTMULHIGHI8X16U => {
+                self.binop(|a, b| from_u16x8(extmul(u8x16(a), u8x16(b), 8, |x| x as u16)))?
+            }
+            I32X4EXTMULLOWI16X8S => {
+                self.binop(|a, b| from_i32x4(extmul(i16x8(a), i16x8(b), 0, |x| x as i32)))?
+            }
+            I32X4EXTMULHIGHI16X8S => {
+                self.binop(|a, b| from_i32x4(extmul(i16x8(a), i16x8(b), 4, |x| x as i32)))?
+            }
+            I32X4EXTMULLOWI16X8U => {
+                self.binop(|a, b| from_u32x4(extmul(u16x8(a), u16x8(b), 0, |x| x as u32)))?
+            }
+            I32X4EXTMULHIGHI16X8U => {
+                self.binop(|a, b| from_u32x4(extmul(u16x8(a), u16x8(b), 4, |x| x as u32)))?
+            }
+            I64X2EXTMULLOWI32X4S => {
+                self.binop(|a, b| from_i64x2(extmul(i32x4(a), i32x4(b), 0, |x| x as i64)))?
+            }
+            I64X2EXTMULHIGHI32X4S => {
+                self.binop(|a, b| from_i64x2(extmul(i32x4(a), i32x4(b), 2, |x| x as i64)))?
+            }
+            I64X2EXTMULLOWI32X4U => {
+                self.binop(|a, b| from_u64x2(extmul(u32x4(a), u32x4(b), 0, |x| x as u64)))?
+            }
+            I64X2EXTMULHIGHI32X4U => {
+                self.binop(|a, b| from_u64x2(extmul(u32x4(a), u32x4(b), 2, |x| x as u64)))?
+            }
+
+            I32X4DOTI16X8S => self.binop(|a, b| {
+                let (a, b) = (i16x8(a), i16x8(b));
+                let product = |i: usize| a[i] as i32 * b[i] as i32;
+                // Only wraps when all four lanes are i16::MIN
+                from_i32x4(array::from_fn(|i| {
+                    product(2 * i).wrapping_add(product(2 * i + 1))
+                }))
+            })?,
+
+            // Rust's float to int casts saturate, and convert NaN to 0, just like trunc_sat
+            I32X4TRUNCSATF32X4S => self.unop(|a| from_i32x4(f32x4(a).map(|x| x as i32)))?,
+            I32X4TRUNCSATF32X4U => self.unop(|a| from_u32x4(f32x4(a).map(|x| x as u32)))?,
+            I32X4TRUNCSATF64X2SZERO => self.unop(|a| {
+                let a = f64x2(a);
+                from_i32x4([a[0] as i32, a[1] as i32, 0, 0])
+            })?,
+            I32X4TRUNCSATF64X2UZERO => self.unop(|a| {
+                let a = f64x2(a);
+                from_u32x4([a[0] as u32, a[1] as u32, 0, 0])
+            })?,
+            F32X4CONVERTI32X4S => self.unop(|a| from_f32x4(i32x4(a).map(|x| x as f32)))?,
+            F32X4CONVERTI32X4U => self.unop(|a| from_f32x4(u32x4(a).map(|x| x as f32)))?,
+            F64X2CONVERTLOWI32X4S => self.unop(|a| from_f64x2(widen(i32x4(a), 0, |x| x as f64)))?,
+            F64X2CONVERTLOWI32X4U => self.unop(|a| from_f64x2(widen(u32x4(a), 0, |x| x as f64)))?,
+        }
+
+        Ok(())
+    }
+
+    fn push_v128(&mut self, v: u128) {
+        self.value_store.push(Value::V128(v));
+    }
+
+    /// Returns the offset of a memory instruction. The alignment is only a hint, so it's ignored.
+    fn fetch_memarg(&mut self, module: &WasmModule<'a>) -> u32 {
+        let _alignment = self.fetch_immediate_u32(module);
+        self.fetch_immediate_u32(module)
+    }
+
+    /// Returns the lane index of an instruction, checking it against the number of lanes
+    fn fetch_lane(&mut self, module: &WasmModule<'a>, n_lanes: usize) -> Result<usize, Error> {
+        let lane = module.code.bytes[self.program_counter];
+        self.program_counter += 1;
+        self.write_debug(lane);
+        if (lane as usize) < n_lanes {
+            Ok(lane as usize)
+        } else {
+            Err(Error::InvalidLane { lane, n_lanes })
+        }
+    }
+
+    fn fetch_immediate_bytes(&mut self, module: &WasmModule<'a>) -> [u8; 16] {
+        let bytes: [u8; 16] = module.code.bytes[self.program_counter..][..16]
+            .try_into()
+            .unwrap();
+        self.program_counter += 16;
+        self.write_debug(u128::from_le_bytes(bytes));
+        bytes
+    }
+
+    fn load_bytes<const N: usize>(
+        &mut self,
+        module: &WasmModule<'a>,
+        op_addr: usize,
+    ) -> Result<[u8; N], Error> {
+        let offset = self.fetch_memarg(module);
+        let base_addr = self.value_store.pop_u32()?;
+        let addr = self.check_memory_access(op_addr, base_addr, offset, N)?;
+        Ok(self.memory[addr..][..N].try_into().unwrap())
+    }
+
+    /// Replace one lane of a vector with `size` bytes from memory
+    fn load_lane(
+        &mut self,
+        module: &WasmModule<'a>,
+        op_addr: usize,
+        size: usize,
+    ) -> Result<(), Error> {
+        let offset = self.fetch_memarg(module);
+        let lane = self.fetch_lane(module, 16 / size)?;
+        let mut bytes = self.value_store.pop_v128()?.to_le_bytes();
+        let base_addr = self.value_store.pop_u32()?;
+        let addr = self.check_memory_access(op_addr, base_addr, offset, size)?;
+        bytes[lane * size..][..size].copy_from_slice(&self.memory[addr..][..size]);
+        self.push_v128(u128::from_le_bytes(bytes));
+        Ok(())
+    }
+
+    /// Store one lane of a vector, which is `size` bytes wide
+    fn store_lane(
+        &mut self,
+        module: &WasmModule<'a>,
+        op_addr: usize,
+        size: usize,
+    ) -> Result<(), Error> {
+        let offset = self.fetch_memarg(module);
+        let lane = self.fetch_lane(module, 16 / size)?;
+        let bytes = self.value_store.pop_v128()?.to_le_bytes();
+        let base_addr = self.value_store.pop_u32()?;
+        let addr = self.check_memory_access(op_addr, base_addr, offset, size)?;
+        self.note_memory_write(addr, size);
+        self.memory[addr..][..size].copy_from_slice(&bytes[lane * size..][..size]);
+        Ok(())
+    }
+
+    fn unop(&mut self, f: impl FnOnce(u128) -> u128) -> Result<(), Error> {
+        let a = self.value_store.pop_v128()?;
+        self.push_v128(f(a));
+        Ok(())
+    }
+
+    fn binop(&mut self, f: impl FnOnce(u128, u128) -> u128) -> Result<(), Error> {
+        let b = self.value_store.pop_v128()?;
+        let a = self.value_store.pop_v128()?;
+        self.push_v128(f(a, b));
+        Ok(())
+    }
+
+    /// Shift every lane by an i32 count, which wraps around at the width of the lane
+    fn shift(&mut self, f: impl FnOnce(u128, u32) -> u128) -> Result<(), Error> {
+        let n = self.value_store.pop_u32()?;
+        let a = self.value_store.pop_v128()?;
+        self.push_v128(f(a, n));
+        Ok(())
+    }
+
+    /// Reduce a vector to an i32, like `all_true` and `bitmask`
+    fn summarize(&mut self, f: impl FnOnce(u128) -> i32) -> Result<(), Error> {
+        let a = self.value_store.pop_v128()?;
+        self.value_store.push(Value::I32(f(a)));
+        Ok(())
+    }
+}
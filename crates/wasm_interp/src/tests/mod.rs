@@ -1,18 +1,43 @@
 #![cfg(test)]
 
 mod test_basics;
+#[cfg(feature = "canary")]
+mod test_canary;
+mod test_capabilities;
+mod test_chain;
 mod test_convert;
+mod test_coverage;
 mod test_f32;
 mod test_f64;
+mod test_globals;
+mod test_host_memory;
 mod test_i32;
 mod test_i64;
+mod test_invalid_module;
+mod test_limits;
+mod test_linking;
 mod test_mem;
+mod test_panic;
+mod test_pool;
+mod test_replay;
+mod test_roc_value;
+mod test_run_for;
+mod test_run_wasi;
+#[cfg(feature = "simd")]
+mod test_simd;
+mod test_start;
+mod test_trace;
+mod test_typed;
+mod test_wasi;
+#[cfg(feature = "watchpoints")]
+mod test_watch;
 
-use crate::{DefaultImportDispatcher, Instance};
+use crate::{DefaultImportDispatcher, ImportDispatcher, Instance, Memory};
 use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::sections::{Import, ImportDesc};
 use roc_wasm_module::{
-    opcodes::OpCode, Export, ExportType, SerialBuffer, Serialize, Signature, Value, ValueType,
-    WasmModule,
+    opcodes::OpCode, Export, ExportType, GlobalType, SerialBuffer, Serialize, Signature, Value,
+    ValueType, WasmModule,
 };
 
 pub fn default_state(arena: &Bump) -> Instance<DefaultImportDispatcher> {
@@ -47,6 +72,11 @@ pub fn const_value(buf: &mut Vec<'_, u8>, value: Value) {
             buf.push(OpCode::F64CONST as u8);
             buf.encode_f64(x);
         }
+        V128(x) => {
+            buf.push(OpCode::SIMDPREFIX as u8);
+            buf.encode_u32(roc_wasm_module::opcodes::SimdOp::V128CONST as u32);
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
     }
 }
 
@@ -128,6 +158,77 @@ pub fn create_exported_function_no_locals<'a, F>(
     module.code.function_offsets.push(offset as u32);
 }
 
+/// Provides every imported global as an i32 zero, and no imported functions
+pub struct ZeroGlobalsDispatcher;
+
+impl ImportDispatcher for ZeroGlobalsDispatcher {
+    fn dispatch(&mut self, _: &str, _: &str, _: &[Value], _: &mut Memory<'_>) -> Option<Value> {
+        unreachable!()
+    }
+
+    fn global(&mut self, _: &str, _: &str, _: GlobalType) -> Option<Value> {
+        Some(Value::I32(0))
+    }
+}
+
+/// Import an i32 global `env.offset`. Unlike an imported function, it has no function index.
+pub fn import_i32_global(module: &mut WasmModule<'_>) {
+    module.import.imports.push(Import {
+        module: "env",
+        name: "offset",
+        description: ImportDesc::Global {
+            ty: GlobalType {
+                value_type: ValueType::I32,
+                is_mutable: false,
+            },
+        },
+    });
+}
+
+/// A module with a function `count_down(n: i32) -> i32` that loops n times, then returns 42
+pub fn count_down_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "count_down", signature, |buf| {
+        // loop <void>
+        buf.push(OpCode::LOOP as u8);
+        buf.push(ValueType::VOID);
+        //   n = n - 1
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32SUB as u8);
+        buf.push(OpCode::TEELOCAL as u8);
+        buf.encode_u32(0);
+        //   br_if 0 (if n != 0)
+        buf.push(OpCode::BRIF as u8);
+        buf.encode_u32(0);
+        // end
+        buf.push(OpCode::END as u8);
+        // n + 42
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(42);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    module
+}
+
+/// Write a RocStr or RocList header at `addr`, with a capacity of `len`
+pub fn write_roc_header(memory: &mut Memory, addr: u32, elements: u32, len: u32) {
+    memory.write_u32(addr, elements).unwrap();
+    memory.write_u32(addr + 4, len).unwrap();
+    memory.write_u32(addr + 8, len & i32::MAX as u32).unwrap();
+}
+
 pub fn create_exported_function_with_locals<'a, F>(
     module: &mut WasmModule<'a>,
     name: &'a str,
@@ -5,12 +5,12 @@ use crate::tests::{
     const_value, create_exported_function_no_locals, create_exported_function_with_locals,
     default_state,
 };
-use crate::{DefaultImportDispatcher, ImportDispatcher, Instance};
+use crate::{DefaultImportDispatcher, ImportDispatcher, Instance, Memory};
 use bumpalo::{collections::Vec, Bump};
-use roc_wasm_module::sections::{Import, ImportDesc};
+use roc_wasm_module::sections::{Import, ImportDesc, Limits};
 use roc_wasm_module::{
-    opcodes::OpCode, sections::ElementSegment, Export, ExportType, SerialBuffer, Serialize,
-    Signature, Value, ValueType, WasmModule,
+    opcodes::OpCode, sections::ElementSegment, ConstExpr, Export, ExportType, GlobalType,
+    SerialBuffer, Serialize, Signature, Value, ValueType, WasmModule,
 };
 
 #[test]
@@ -497,7 +497,7 @@ impl ImportDispatcher for TestDispatcher {
         module_name: &str,
         function_name: &str,
         arguments: &[Value],
-        _memory: &mut [u8],
+        _memory: &mut Memory<'_>,
     ) -> Option<Value> {
         assert_eq!(module_name, "env");
         assert_eq!(function_name, "increment_state");
@@ -506,6 +506,250 @@ impl ImportDispatcher for TestDispatcher {
         self.internal_state += val;
         Some(Value::I32(self.internal_state))
     }
+
+    fn global(&mut self, module_name: &str, global_name: &str, _: GlobalType) -> Option<Value> {
+        match (module_name, global_name) {
+            ("env", "offset") => Some(Value::I32(0)),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_br_keeps_block_result() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        // block (result i32)
+        buf.push(OpCode::BLOCK as u8);
+        buf.push(ValueType::I32 as u8);
+
+        //     i32.const 111
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(111);
+
+        //     i32.const 222
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(222);
+
+        //     br 0
+        buf.push(OpCode::BR as u8);
+        buf.encode_u32(0);
+
+        // end
+        buf.push(OpCode::END as u8);
+
+        // i32.const 333
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(333);
+
+        // i32.add
+        buf.push(OpCode::I32ADD as u8);
+
+        // end function
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let result = inst.call_export("test", []).unwrap().unwrap();
+
+    // The branch keeps only the block's result, and drops the 111 underneath it
+    assert_eq!(result, Value::I32(555));
+}
+
+#[test]
+fn test_br_out_of_function_body() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        // block
+        buf.push(OpCode::BLOCK as u8);
+        buf.push(ValueType::VOID);
+
+        //     i32.const 111
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(111);
+
+        //     br 1
+        buf.push(OpCode::BR as u8);
+        buf.encode_u32(1);
+
+        // end
+        buf.push(OpCode::END as u8);
+
+        // unreachable
+        buf.push(OpCode::UNREACHABLE as u8);
+
+        // end function
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let result = inst.call_export("test", []).unwrap().unwrap();
+
+    assert_eq!(result, Value::I32(111));
+}
+
+#[test]
+fn test_shared_control_table() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        // block
+        buf.push(OpCode::BLOCK as u8);
+        buf.push(ValueType::VOID);
+
+        //     local.get 0
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+
+        //     if
+        buf.push(OpCode::IF as u8);
+        buf.push(ValueType::VOID);
+
+        //     end
+        buf.push(OpCode::END as u8);
+
+        //     i32.const 111
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(111);
+
+        //     local.set 0
+        buf.push(OpCode::SETLOCAL as u8);
+        buf.encode_u32(0);
+
+        //     br 0
+        buf.push(OpCode::BR as u8);
+        buf.encode_u32(0);
+
+        // end
+        buf.push(OpCode::END as u8);
+
+        // local.get 0
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+
+        // end function
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut first =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(
+        first.call_export("test", [Value::I32(0)]),
+        Ok(Some(Value::I32(111)))
+    );
+
+    // The second instance uses the blocks that the first one decoded
+    let mut second = Instance::with_control_table(
+        &arena,
+        &module,
+        first.control_table(),
+        DefaultImportDispatcher::default(),
+        false,
+    )
+    .unwrap();
+    for condition in [0, 1, 0] {
+        assert_eq!(
+            second.call_export("test", [Value::I32(condition)]),
+            Ok(Some(Value::I32(111)))
+        );
+    }
+}
+
+#[test]
+fn test_control_table_for_another_module() {
+    let arena = Bump::new();
+    let signature = || Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+
+    let mut first_module = WasmModule::new(&arena);
+    create_exported_function_no_locals(&mut first_module, "test", signature(), |buf| {
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut second_module = WasmModule::new(&arena);
+    create_exported_function_no_locals(&mut second_module, "test", signature(), |buf| {
+        buf.push(OpCode::BLOCK as u8);
+        buf.push(ValueType::VOID);
+        buf.push(OpCode::END as u8);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(2);
+        buf.push(OpCode::END as u8);
+    });
+
+    let first = Instance::for_module(
+        &arena,
+        &first_module,
+        DefaultImportDispatcher::default(),
+        false,
+    )
+    .unwrap();
+    let second = Instance::with_control_table(
+        &arena,
+        &second_module,
+        first.control_table(),
+        DefaultImportDispatcher::default(),
+        false,
+    );
+    assert_eq!(
+        second.err(),
+        Some("This control table was made for a different module".to_string())
+    );
+}
+
+#[test]
+fn test_branch_in_invalid_code() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        // block with a block type that doesn't exist
+        buf.push(OpCode::BLOCK as u8);
+        buf.push(0x55);
+
+        //     br 0
+        buf.push(OpCode::BR as u8);
+        buf.encode_u32(0);
+
+        // end
+        buf.push(OpCode::END as u8);
+
+        // end function
+        buf.push(OpCode::END as u8);
+    });
+
+    // Instantiating doesn't decode the code, but branching does
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let message = inst.call_export("test", []).unwrap_err().to_string();
+
+    assert!(message.contains("Invalid block type 0x55"), "{}", message);
 }
 
 #[test]
@@ -573,6 +817,114 @@ fn test_call_import() {
     assert_eq!(return_val, Value::I32(234));
 }
 
+/// A module whose function 1, exported as `test`, calls the imported function 0 twice
+fn two_increments_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    // Function 0 is an import
+    module.import.imports.push(Import {
+        module: "env",
+        name: "increment_state",
+        description: ImportDesc::Func { signature_index: 0 },
+    });
+    module.types.insert(Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    });
+
+    // Function 1 calls the import twice
+    module.code.function_count = 1;
+    module.code.function_offsets.push(0);
+    module.add_function_signature(Signature {
+        param_types: Vec::new_in(arena),
+        ret_type: Some(ValueType::I32),
+    });
+    module.export.append(Export {
+        name: "test",
+        ty: ExportType::Func,
+        index: 1,
+    });
+    module.names.append_function(1, "two_increments");
+    [
+        0, // no locals
+        OpCode::I32CONST as u8,
+        11,
+        OpCode::CALL as u8,
+        0,
+        OpCode::I32CONST as u8,
+        12,
+        OpCode::CALL as u8,
+        0,
+        OpCode::I32ADD as u8,
+        OpCode::END as u8,
+    ]
+    .serialize(&mut module.code.bytes);
+
+    module
+}
+
+#[test]
+fn test_profile_counts_calls_and_instructions() {
+    let arena = Bump::new();
+    let module = two_increments_module(&arena);
+
+    let import_dispatcher = TestDispatcher { internal_state: 0 };
+    let mut inst = Instance::for_module(&arena, &module, import_dispatcher, false).unwrap();
+    assert_eq!(inst.profile_report(), None);
+
+    inst.enable_profiling();
+    inst.call_export("test", []).unwrap();
+    inst.call_export("test", []).unwrap();
+
+    let report = inst.profile_report().unwrap();
+    let names: std::vec::Vec<&str> = report.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["two_increments", "env.increment_state"]);
+
+    let caller = report.get("two_increments").unwrap();
+    assert_eq!(caller.calls, 2);
+    assert_eq!(caller.instructions, 12);
+
+    let import = report.get("env.increment_state").unwrap();
+    assert_eq!(import.calls, 4);
+    assert_eq!(import.instructions, 0);
+
+    // Re-enabling starts over
+    inst.enable_profiling();
+    assert_eq!(inst.profile_report().unwrap().functions, []);
+}
+
+#[test]
+fn test_profile_names_functions_after_an_imported_global() {
+    let arena = Bump::new();
+    let mut module = two_increments_module(&arena);
+
+    // Imports that aren't functions have no function index
+    module.import.imports.insert(
+        0,
+        Import {
+            module: "env",
+            name: "offset",
+            description: ImportDesc::Global {
+                ty: GlobalType {
+                    value_type: ValueType::I32,
+                    is_mutable: false,
+                },
+            },
+        },
+    );
+
+    let import_dispatcher = TestDispatcher { internal_state: 0 };
+    let mut inst = Instance::for_module(&arena, &module, import_dispatcher, false).unwrap();
+    inst.enable_profiling();
+    inst.call_export("test", []).unwrap();
+
+    let report = inst.profile_report().unwrap();
+    let names: std::vec::Vec<&str> = report.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["two_increments", "env.increment_state"]);
+    assert_eq!(report.functions[0].fn_index, 1);
+    assert_eq!(report.get("env.increment_state").unwrap().calls, 2);
+}
+
 #[test]
 fn test_call_return_no_args() {
     let arena = Bump::new();
@@ -702,29 +1054,54 @@ fn test_call_return_with_args() {
 
 #[test]
 fn test_call_indirect_ok() {
-    let result = test_call_indirect_help(0, 0);
-    assert_eq!(result, Value::I32(111));
+    let result = test_call_indirect_help(0, 1);
+    assert_eq!(result, Ok(Some(Value::I32(111))));
 }
 
 #[test]
-#[should_panic(expected = "Expected signature")]
 fn test_call_indirect_wrong_signature() {
-    test_call_indirect_help(0, 1);
+    let message = test_call_indirect_help(0, 2).unwrap_err();
+    assert!(
+        message.contains(
+            "Expected [] -> [I32], but element 2 of table 0 is func[2], which has type [] -> [F32]"
+        ),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_call_indirect_null() {
+    // Nothing initializes element 0, like Roc's null function pointer
+    let message = test_call_indirect_help(0, 0).unwrap_err();
+    assert!(
+        message.contains("element 0 of table 0") && message.contains("that element is null"),
+        "{}",
+        message
+    );
+
+    // Inside the table's declared size but past the end of the element segment
+    let message = test_call_indirect_help(0, 3).unwrap_err();
+    assert!(message.contains("that element is null"), "{}", message);
 }
 
 #[test]
-#[should_panic(expected = "element index")]
 fn test_call_indirect_index_out_of_bounds() {
-    test_call_indirect_help(0, 2);
+    let message = test_call_indirect_help(0, 4).unwrap_err();
+    assert!(
+        message.contains("element 4 of table 0") && message.contains("only has 4 elements"),
+        "{}",
+        message
+    );
 }
 
 #[test]
-#[should_panic(expected = "Table index")]
 fn test_call_indirect_unsupported_table() {
-    test_call_indirect_help(1, 0);
+    let message = test_call_indirect_help(1, 1).unwrap_err();
+    assert!(message.contains("call_indirect to table 1"), "{}", message);
 }
 
-fn test_call_indirect_help(table_index: u32, elem_index: u32) -> Value {
+fn test_call_indirect_help(table_index: u32, elem_index: u32) -> Result<Option<Value>, String> {
     let arena = Bump::new();
     let mut module = WasmModule::new(&arena);
 
@@ -763,10 +1140,13 @@ fn test_call_indirect_help(table_index: u32, elem_index: u32) -> Value {
         buf.append_u8(OpCode::END as u8);
     });
 
-    // Put functions 1 and 2 in the function table
-    module.element.segments.push(ElementSegment::new(&arena));
-    assert_eq!(module.element.get_or_insert_fn(1), 0);
-    assert_eq!(module.element.get_or_insert_fn(2), 1);
+    // A table of 4 elements, with functions 1 and 2 at elements 1 and 2, and the rest null
+    module.table.function_table.limits = Limits::Min(4);
+    let mut segment = ElementSegment::new(&arena);
+    segment.offset = ConstExpr::I32(1);
+    module.element.segments.push(segment);
+    assert_eq!(module.element.get_or_insert_fn(1), 1);
+    assert_eq!(module.element.get_or_insert_fn(2), 2);
 
     if false {
         let mut outfile_buf = Vec::new_in(&arena);
@@ -783,7 +1163,7 @@ fn test_call_indirect_help(table_index: u32, elem_index: u32) -> Value {
         is_debug_mode,
     )
     .unwrap();
-    inst.call_export(start_fn_name, []).unwrap().unwrap()
+    inst.call_export(start_fn_name, []).map_err(String::from)
 }
 
 // #[test]
@@ -992,3 +1372,85 @@ fn test_f64const() {
     state.execute_next_instruction(&module).unwrap();
     assert_eq!(state.value_store.pop(), Value::F64(12345.67890))
 }
+
+#[cfg(not(feature = "simd"))]
+#[test]
+fn test_simd_without_feature() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: Vec::new_in(&arena),
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        buf.push(OpCode::SIMDPREFIX as u8);
+        buf.encode_u32(0xae); // i32x4.add
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let error = inst.call_export("test", []).unwrap_err().to_string();
+
+    assert!(error.contains("0xfd 0xae"), "{}", error);
+    assert!(error.contains("`simd` feature"), "{}", error);
+}
+
+/// A module like the output of dead code elimination, where an eliminated import at index 0
+/// is only a count of dummy functions to insert when serializing.
+fn module_with_dead_import_dummy(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    let signature = Signature {
+        param_types: Vec::new_in(arena),
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        const_value(buf, Value::I32(42));
+        buf.push(OpCode::END as u8);
+    });
+
+    module.code.dead_import_dummy_count = 1;
+    module.function.signatures.insert(0, 0);
+    for export in module.export.exports.iter_mut() {
+        export.index += 1;
+    }
+    module.export.append(Export {
+        name: "dead",
+        ty: ExportType::Func,
+        index: 0,
+    });
+
+    module
+}
+
+#[test]
+fn test_from_module() {
+    let arena = Bump::new();
+    let module = module_with_dead_import_dummy(&arena);
+
+    let mut inst =
+        Instance::from_module(&arena, module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(inst.call_export("test", []), Ok(Some(Value::I32(42))));
+}
+
+#[test]
+fn test_from_module_matches_serialized() {
+    let arena = Bump::new();
+
+    let mut bytes = Vec::new_in(&arena);
+    module_with_dead_import_dummy(&arena).serialize(&mut bytes);
+    let mut serialized_inst =
+        Instance::from_bytes(&arena, &bytes, DefaultImportDispatcher::default(), false).unwrap();
+
+    let module = module_with_dead_import_dummy(&arena);
+    let mut inst =
+        Instance::from_module(&arena, module, DefaultImportDispatcher::default(), false).unwrap();
+
+    // The error for the dummy's `unreachable` has its address in the serialized file
+    let error = inst.call_export("dead", []).unwrap_err();
+    assert_eq!(error, serialized_inst.call_export("dead", []).unwrap_err());
+    assert!(error.to_string().contains("unreachable"), "{}", error);
+}
@@ -0,0 +1,212 @@
+use super::create_exported_function_no_locals;
+use crate::{ImportDispatcher, Instance, Memory, CANARY};
+use bumpalo::Bump;
+use roc_wasm_module::sections::{Import, ImportDesc, MemorySection};
+use roc_wasm_module::{opcodes::OpCode, SerialBuffer, Signature, Value, ValueType, WasmModule};
+
+const HEAP_START: u32 = 0x1000;
+
+/// A bump allocator for the Roc allocator imports, which reuses the last freed allocation
+#[derive(Default)]
+struct TestAllocator {
+    next: u32,
+    last_freed: Option<u32>,
+}
+
+impl ImportDispatcher for TestAllocator {
+    fn dispatch(
+        &mut self,
+        _module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        _memory: &mut Memory<'_>,
+    ) -> Option<Value> {
+        match function_name {
+            "roc_alloc" => {
+                let size = arguments[0].expect_i32().unwrap() as u32;
+                let ptr = match self.last_freed.take() {
+                    Some(ptr) => ptr,
+                    None => {
+                        let ptr = HEAP_START + self.next;
+                        self.next += size;
+                        ptr
+                    }
+                };
+                Some(Value::I32(ptr as i32))
+            }
+            "roc_dealloc" => {
+                self.last_freed = Some(arguments[0].expect_i32().unwrap() as u32);
+                None
+            }
+            _ => panic!("unexpected import {}", function_name),
+        }
+    }
+}
+
+/// A module with functions `alloc(size) -> ptr`, `free(ptr)` and `load(addr) -> i32`
+fn test_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+    module.memory = MemorySection::new(arena, MemorySection::PAGE_SIZE);
+
+    let alloc_signature = module.types.insert(Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32, ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    });
+    let dealloc_signature = module.types.insert(Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32, ValueType::I32],
+        ret_type: None,
+    });
+    for (name, signature_index) in [
+        ("roc_alloc", alloc_signature),
+        ("roc_dealloc", dealloc_signature),
+    ] {
+        module.import.imports.push(Import {
+            module: "env",
+            name,
+            description: ImportDesc::Func { signature_index },
+        });
+    }
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "alloc", signature, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(4); // alignment
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::END as u8);
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "free", signature, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(4); // alignment
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(1);
+        buf.push(OpCode::END as u8);
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "load", signature, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32LOAD as u8);
+        buf.encode_u32(2); // alignment
+        buf.encode_u32(0); // offset
+        buf.push(OpCode::END as u8);
+    });
+
+    module
+}
+
+fn alloc(inst: &mut Instance<TestAllocator>, size: i32) -> i32 {
+    match inst.call_export("alloc", [Value::I32(size)]) {
+        Ok(Some(Value::I32(ptr))) => ptr,
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn test_canary_use_after_free() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst = Instance::for_module(&arena, &module, TestAllocator::default(), false).unwrap();
+    inst.set_canary_mode(true);
+
+    let ptr = alloc(&mut inst, 16);
+    let other = alloc(&mut inst, 8);
+    inst.call_export("free", [Value::I32(ptr)]).unwrap();
+
+    let freed = &inst.memory[ptr as usize..][..16];
+    assert_eq!(freed, CANARY.repeat(4));
+
+    // Loads from other allocations are fine, even right next to the freed one
+    assert_eq!(
+        inst.call_export("load", [Value::I32(other)]),
+        Ok(Some(Value::I32(0)))
+    );
+
+    let error = inst
+        .call_export("load", [Value::I32(ptr + 14)])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains(
+            "Use after free: load of 4 bytes at 0x100e, from the allocation at 0x1000..0x1010"
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_canary_reallocated_memory_is_zeroed() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst = Instance::for_module(&arena, &module, TestAllocator::default(), false).unwrap();
+    inst.set_canary_mode(true);
+
+    let ptr = alloc(&mut inst, 8);
+    inst.call_export("free", [Value::I32(ptr)]).unwrap();
+
+    // The allocator hands out the same memory again, which can be loaded from once more
+    assert_eq!(alloc(&mut inst, 8), ptr);
+    assert_eq!(
+        inst.call_export("load", [Value::I32(ptr)]),
+        Ok(Some(Value::I32(0)))
+    );
+    assert_eq!(
+        inst.call_export("load", [Value::I32(ptr + 4)]),
+        Ok(Some(Value::I32(0)))
+    );
+}
+
+#[test]
+fn test_canary_double_free() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst = Instance::for_module(&arena, &module, TestAllocator::default(), false).unwrap();
+    inst.set_canary_mode(true);
+
+    let ptr = alloc(&mut inst, 8);
+    inst.call_export("free", [Value::I32(ptr)]).unwrap();
+
+    let error = inst
+        .call_export("free", [Value::I32(ptr)])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("Double free: roc_dealloc was called on the allocation at 0x1000..0x1008"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_canary_mode_off() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst = Instance::for_module(&arena, &module, TestAllocator::default(), false).unwrap();
+
+    let ptr = alloc(&mut inst, 8);
+    inst.memory[ptr as usize] = 42;
+    inst.call_export("free", [Value::I32(ptr)]).unwrap();
+
+    // Freed memory is left alone
+    assert_eq!(
+        inst.call_export("load", [Value::I32(ptr)]),
+        Ok(Some(Value::I32(42)))
+    );
+}
@@ -0,0 +1,123 @@
+use super::create_exported_function_no_locals;
+use crate::{Capabilities, DefaultImportDispatcher, Instance, LoadError, Proposal};
+use bumpalo::Bump;
+use roc_wasm_module::sections::SectionId;
+use roc_wasm_module::{opcodes::OpCode, Signature, ValueType, WasmModule};
+
+/// A module with a function `f() -> i32` made of the given instructions
+fn function_module(instructions: &[u8]) -> std::vec::Vec<u8> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "f", signature, |buf| {
+        buf.extend_from_slice(instructions);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    bytes
+}
+
+fn load(module_bytes: &[u8]) -> Result<(), LoadError> {
+    let arena = Bump::new();
+    Instance::from_bytes(
+        &arena,
+        module_bytes,
+        DefaultImportDispatcher::default(),
+        false,
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn test_capabilities_of_this_build() {
+    let capabilities = Capabilities::of_this_build();
+    assert_eq!(capabilities.simd, cfg!(feature = "simd"));
+    assert_eq!(
+        capabilities.supported().collect::<Vec<_>>(),
+        if cfg!(feature = "simd") {
+            vec![Proposal::Simd]
+        } else {
+            vec![]
+        }
+    );
+}
+
+#[test]
+fn test_mvp_module_is_supported() {
+    let bytes = function_module(&[OpCode::I32CONST as u8, 42]);
+    assert_eq!(Capabilities::of_this_build().check_module(&bytes), Ok(()));
+    assert_eq!(load(&bytes), Ok(()));
+}
+
+#[test]
+fn test_relaxed_simd_is_unsupported() {
+    // i8x16.relaxed_swizzle, with nothing on the stack. It's rejected before it's validated.
+    let bytes = function_module(&[0xfd, 0x80, 0x02]);
+    let error = load(&bytes).unwrap_err();
+
+    match &error {
+        LoadError::Unsupported {
+            proposal: Proposal::RelaxedSimd,
+            section: SectionId::Code,
+            offset,
+        } => assert_eq!(bytes[*offset], 0xfd),
+        _ => panic!("{:?}", error),
+    }
+    assert!(
+        error
+            .to_string()
+            .contains("needs the `relaxed-simd` WebAssembly proposal"),
+        "{}",
+        error
+    );
+
+    // A build that says it supports it lets the module through to the decoder
+    let capabilities = Capabilities {
+        relaxed_simd: true,
+        ..Capabilities::of_this_build()
+    };
+    assert_eq!(capabilities.check_module(&bytes), Ok(()));
+}
+
+#[test]
+fn test_sign_extension_is_unsupported() {
+    let bytes = function_module(&[OpCode::I32CONST as u8, 42, 0xc0]);
+    assert!(matches!(
+        load(&bytes),
+        Err(LoadError::Unsupported {
+            proposal: Proposal::SignExtension,
+            section: SectionId::Code,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_extended_const_is_unsupported() {
+    #[rustfmt::skip]
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Global section with one immutable i32, initialized to (i32.add (i32.const 1) (i32.const 2))
+        SectionId::Global as u8, 9,
+        1, ValueType::I32 as u8, 0,
+        OpCode::I32CONST as u8, 1,
+        OpCode::I32CONST as u8, 2,
+        OpCode::I32ADD as u8,
+        OpCode::END as u8,
+    ];
+
+    assert_eq!(
+        load(&bytes),
+        Err(LoadError::Unsupported {
+            proposal: Proposal::ExtendedConst,
+            section: SectionId::Global,
+            offset: 17,
+        })
+    );
+}
@@ -0,0 +1,222 @@
+use super::{const_value, create_exported_function_no_locals};
+use crate::{
+    ChainDispatcher, ImportDispatcher, Instance, InstanceGroup, Memory, MissingImports,
+    RecordingDispatcher,
+};
+use bumpalo::Bump;
+use roc_wasm_module::sections::{Import, ImportDesc};
+use roc_wasm_module::{
+    opcodes::OpCode, GlobalType, SerialBuffer, Signature, Value, ValueType, WasmModule,
+};
+
+/// Provides a single `env` function of type i32 -> i32, which multiplies its argument
+struct Multiplier {
+    name: &'static str,
+    factor: i32,
+}
+
+impl ImportDispatcher for Multiplier {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        _memory: &mut Memory<'_>,
+    ) -> Option<Value> {
+        assert_eq!((module_name, function_name), ("env", self.name));
+        Some(Value::I32(arguments[0].expect_i32().unwrap() * self.factor))
+    }
+
+    fn global(
+        &mut self,
+        _module_name: &str,
+        global_name: &str,
+        _global_type: GlobalType,
+    ) -> Option<Value> {
+        (global_name == self.name).then_some(Value::I32(self.factor))
+    }
+
+    fn provides(&self, module_name: &str, function_name: &str) -> bool {
+        module_name == "env" && function_name == self.name
+    }
+}
+
+fn double() -> Multiplier {
+    Multiplier {
+        name: "double",
+        factor: 2,
+    }
+}
+
+fn triple() -> Multiplier {
+    Multiplier {
+        name: "triple",
+        factor: 3,
+    }
+}
+
+/// A module that imports `env.double`, `env.triple` and `env.log`, and exports
+/// `six() = double(triple(1))` and `log(n) = log(n)`
+fn module_bytes() -> std::vec::Vec<u8> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let i32_to_i32 = || Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    for name in ["double", "triple", "log"] {
+        let signature_index = module.types.insert(i32_to_i32());
+        module.import.imports.push(Import {
+            module: "env",
+            name,
+            description: ImportDesc::Func { signature_index },
+        });
+    }
+
+    let void_to_i32 = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "six", void_to_i32, |buf| {
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(1);
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::END as u8);
+    });
+    create_exported_function_no_locals(&mut module, "log", i32_to_i32(), |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(2);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    bytes
+}
+
+#[test]
+fn test_chain_dispatches_to_provider() {
+    let arena = Bump::new();
+    let bytes = module_bytes();
+    let chain = ChainDispatcher::new().with(double()).with(triple());
+
+    let mut inst = Instance::from_bytes(&arena, &bytes, chain, false).unwrap();
+
+    assert_eq!(inst.call_export("six", []), Ok(Some(Value::I32(6))));
+}
+
+#[test]
+fn test_chain_first_provider_wins() {
+    let mut chain = ChainDispatcher::new()
+        .with(Multiplier {
+            name: "double",
+            factor: 2,
+        })
+        .with(Multiplier {
+            name: "double",
+            factor: 100,
+        });
+    let mut memory = [];
+
+    assert_eq!(
+        chain.dispatch(
+            "env",
+            "double",
+            &[Value::I32(5)],
+            &mut Memory::new(&mut memory)
+        ),
+        Some(Value::I32(10))
+    );
+    assert_eq!(
+        chain.global(
+            "env",
+            "double",
+            GlobalType {
+                value_type: ValueType::I32,
+                is_mutable: false
+            }
+        ),
+        Some(Value::I32(2))
+    );
+    assert!(!chain.provides("env", "triple"));
+}
+
+#[test]
+fn test_missing_import_traps_when_called() {
+    let arena = Bump::new();
+    let bytes = module_bytes();
+    let recorder = RecordingDispatcher::new(ChainDispatcher::new().with(double()).with(triple()));
+
+    let mut inst = Instance::from_bytes(&arena, &bytes, recorder, false).unwrap();
+    assert_eq!(
+        inst.missing_function_imports()
+            .map(|import| import.name)
+            .collect::<std::vec::Vec<_>>(),
+        ["log"]
+    );
+
+    // Functions that only call provided imports still work
+    assert_eq!(inst.call_export("six", []), Ok(Some(Value::I32(6))));
+
+    let error = inst
+        .call_export("log", [Value::I32(1)])
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("env.log"), "{}", error);
+    assert!(error.contains("doesn't provide"), "{}", error);
+
+    // The missing import never reached the dispatcher
+    let names: std::vec::Vec<_> = inst
+        .import_dispatcher
+        .log
+        .calls
+        .iter()
+        .map(|call| call.function_name.as_str())
+        .collect();
+    assert_eq!(names, ["triple", "double"]);
+}
+
+#[test]
+fn test_missing_import_error() {
+    let arena = Bump::new();
+    let bytes = module_bytes();
+    let chain = ChainDispatcher::new()
+        .with(double())
+        .with(triple())
+        .on_missing(MissingImports::Error);
+
+    let error = Instance::from_bytes(&arena, &bytes, chain, false)
+        .err()
+        .unwrap()
+        .to_string();
+
+    assert!(error.contains("env.log"), "{}", error);
+}
+
+#[test]
+fn test_missing_import_error_in_group() {
+    let arena = Bump::new();
+    let bytes = module_bytes();
+
+    let chain = ChainDispatcher::new()
+        .with(double())
+        .on_missing(MissingImports::Error);
+    let mut group = InstanceGroup::new(&arena, chain, false);
+    let error = group.instantiate_bytes("app", &bytes).unwrap_err();
+    assert!(error.to_string().contains("env.triple"), "{}", error);
+
+    let chain = ChainDispatcher::new().with(double()).with(triple());
+    let mut group = InstanceGroup::new(&arena, chain, false);
+    group.instantiate_bytes("app", &bytes).unwrap();
+    assert_eq!(group.call_export("app", "six", []), Ok(Some(Value::I32(6))));
+    let error = group
+        .call_export("app", "log", [Value::I32(1)])
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("env.log"), "{}", error);
+}
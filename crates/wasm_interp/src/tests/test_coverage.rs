@@ -0,0 +1,146 @@
+use super::{
+    const_value, create_exported_function_no_locals, import_i32_global, ZeroGlobalsDispatcher,
+};
+use crate::{DefaultImportDispatcher, Instance};
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::{opcodes::OpCode, Signature, Value, ValueType, WasmModule};
+
+/// A module with `pick(x: i32) -> i32`, which returns 1 if x is true and 2 otherwise,
+/// and `unused() -> i32`, which is never called
+fn test_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    let sig = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "pick", sig, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::IF as u8);
+        buf.push(ValueType::I32 as u8);
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::ELSE as u8);
+        const_value(buf, Value::I32(2));
+        buf.push(OpCode::END as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let sig = Signature {
+        param_types: Vec::new_in(arena),
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "unused", sig, |buf| {
+        const_value(buf, Value::I32(3));
+        buf.push(OpCode::END as u8);
+    });
+
+    module.names.append_function(0, "pick");
+    module.names.append_function(1, "unused");
+    module
+}
+
+#[test]
+fn test_coverage_of_branches() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(inst.coverage_report(), None);
+
+    inst.enable_coverage();
+    inst.call_export("pick", [Value::I32(1)]).unwrap();
+    inst.call_export("pick", [Value::I32(1)]).unwrap();
+
+    let report = inst.coverage_report().unwrap();
+    let pick = report.get("pick").unwrap();
+    assert_eq!(pick.calls, 2);
+    assert_eq!(
+        pick.instructions,
+        [
+            (6, true),   // local.get
+            (8, true),   // if
+            (10, true),  // i32.const 1
+            (12, true),  // else
+            (13, false), // i32.const 2
+            (15, false), // end of the if
+            (16, true),  // end of the function
+        ]
+    );
+    assert_eq!(pick.executed_ranges(), [6..13, 16..17]);
+
+    let unused = report.get("unused").unwrap();
+    assert_eq!(unused.calls, 0);
+    assert_eq!(unused.executed_count(), 0);
+    assert_eq!(unused.executed_ranges(), []);
+
+    // Taking the other branch covers the rest
+    inst.call_export("pick", [Value::I32(0)]).unwrap();
+    let report = inst.coverage_report().unwrap();
+    let ranges = report.get("pick").unwrap().executed_ranges();
+    assert_eq!(ranges.len(), 1);
+    assert_eq!(ranges[0], 6..17);
+
+    // Re-enabling starts over
+    inst.enable_coverage();
+    let report = inst.coverage_report().unwrap();
+    assert_eq!(report.get("pick").unwrap().executed_count(), 0);
+}
+
+#[test]
+fn test_coverage_after_an_imported_global() {
+    let arena = Bump::new();
+    let mut module = test_module(&arena);
+    import_i32_global(&mut module);
+    let mut inst = Instance::for_module(&arena, &module, ZeroGlobalsDispatcher, false).unwrap();
+
+    inst.enable_coverage();
+    inst.call_export("pick", [Value::I32(1)]).unwrap();
+
+    let report = inst.coverage_report().unwrap();
+    let pick = report.get("pick").unwrap();
+    assert_eq!(pick.fn_index, 0);
+    assert_eq!(pick.calls, 1);
+    assert_eq!(pick.executed_ranges(), [6..13, 16..17]);
+    assert_eq!(report.get("unused").unwrap().fn_index, 1);
+}
+
+#[test]
+fn test_coverage_lcov() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    inst.enable_coverage();
+    inst.call_export("pick", [Value::I32(0)]).unwrap();
+
+    let lcov = inst.coverage_report().unwrap().to_lcov("app.wasm");
+    assert_eq!(
+        lcov,
+        [
+            "TN:",
+            "SF:app.wasm",
+            "FN:6,pick",
+            "FN:23,unused",
+            "FNDA:1,pick",
+            "FNDA:0,unused",
+            "FNF:2",
+            "FNH:1",
+            "DA:6,1",
+            "DA:8,1",
+            "DA:10,0",
+            "DA:12,0",
+            "DA:13,1",
+            "DA:15,1",
+            "DA:16,1",
+            "DA:23,0",
+            "DA:25,0",
+            "LF:9",
+            "LH:5",
+            "end_of_record",
+            "",
+        ]
+        .join("\n")
+    );
+}
@@ -0,0 +1,195 @@
+use super::{const_value, create_exported_function_no_locals};
+use crate::{DefaultImportDispatcher, ImportDispatcher, Instance, InstanceGroup, Memory};
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::sections::{Import, ImportDesc};
+use roc_wasm_module::{
+    opcodes::OpCode, ConstExpr, Export, ExportType, Global, GlobalType, Signature, Value,
+    ValueType, WasmModule,
+};
+
+/// Provides `env.__stack_pointer` and nothing else
+struct StackDispatcher;
+
+impl ImportDispatcher for StackDispatcher {
+    fn dispatch(&mut self, _: &str, _: &str, _: &[Value], _: &mut Memory<'_>) -> Option<Value> {
+        unreachable!()
+    }
+
+    fn global(&mut self, module_name: &str, global_name: &str, _: GlobalType) -> Option<Value> {
+        match (module_name, global_name) {
+            ("env", "__stack_pointer") => Some(Value::I32(1024)),
+            _ => None,
+        }
+    }
+}
+
+fn global_type(value_type: ValueType, is_mutable: bool) -> GlobalType {
+    GlobalType {
+        value_type,
+        is_mutable,
+    }
+}
+
+fn import_global<'a>(
+    module: &mut WasmModule<'a>,
+    module_name: &'a str,
+    name: &'a str,
+    ty: GlobalType,
+) {
+    module.import.imports.push(Import {
+        module: module_name,
+        name,
+        description: ImportDesc::Global { ty },
+    });
+}
+
+fn export_global<'a>(module: &mut WasmModule<'a>, name: &'a str, index: u32) {
+    module.export.append(Export {
+        name,
+        ty: ExportType::Global,
+        index,
+    });
+}
+
+/// A module that imports a stack pointer, remembers where the stack started,
+/// and has a function `alloca16` that moves the stack pointer down by 16 bytes.
+fn stack_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+    import_global(
+        &mut module,
+        "env",
+        "__stack_pointer",
+        global_type(ValueType::I32, true),
+    );
+    module.global.append(Global {
+        ty: global_type(ValueType::I32, false),
+        init: ConstExpr::GlobalGet(0),
+    });
+    export_global(&mut module, "sp", 0);
+    export_global(&mut module, "stack_base", 1);
+
+    let sig = Signature {
+        param_types: Vec::new_in(arena),
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "alloca16", sig, |buf| {
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        const_value(buf, Value::I32(16));
+        buf.push(OpCode::I32SUB as u8);
+        buf.push(OpCode::SETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::END as u8);
+    });
+    module
+}
+
+#[test]
+fn test_imported_global_from_dispatcher() {
+    let arena = Bump::new();
+    let module = stack_module(&arena);
+    let mut inst = Instance::for_module(&arena, &module, StackDispatcher, false).unwrap();
+
+    assert_eq!(inst.global("stack_base"), Some(Value::I32(1024)));
+    assert_eq!(inst.call_export("alloca16", []), Ok(Some(Value::I32(1008))));
+    assert_eq!(inst.global("sp"), Some(Value::I32(1008)));
+
+    assert_eq!(inst.set_global("sp", Value::I32(2048)), Ok(()));
+    assert_eq!(inst.call_export("alloca16", []), Ok(Some(Value::I32(2032))));
+    assert_eq!(inst.global("stack_base"), Some(Value::I32(1024)));
+}
+
+#[test]
+fn test_set_global_errors() {
+    let arena = Bump::new();
+    let module = stack_module(&arena);
+    let mut inst = Instance::for_module(&arena, &module, StackDispatcher, false).unwrap();
+
+    assert!(inst.set_global("stack_base", Value::I32(0)).is_err());
+    assert!(inst.set_global("sp", Value::I64(0)).is_err());
+    assert!(inst.set_global("nope", Value::I32(0)).is_err());
+    assert_eq!(inst.global("nope"), None);
+    assert_eq!(inst.global("sp"), Some(Value::I32(1024)));
+}
+
+#[test]
+fn test_imported_global_not_provided() {
+    let arena = Bump::new();
+    let module = stack_module(&arena);
+    let result = Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false);
+    assert!(result.is_err());
+}
+
+/// A module exporting a mutable `counter`, and `get` to read it
+fn counter_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+    module.global.append(Global {
+        ty: global_type(ValueType::I32, true),
+        init: ConstExpr::I32(10),
+    });
+    export_global(&mut module, "counter", 0);
+    let sig = Signature {
+        param_types: Vec::new_in(arena),
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "get", sig, |buf| {
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::END as u8);
+    });
+    module
+}
+
+#[test]
+fn test_group_reexported_global() {
+    let arena = Bump::new();
+    let platform = counter_module(&arena);
+
+    // The app re-exports the platform's counter, and starts its own global from it
+    let mut app = WasmModule::new(&arena);
+    import_global(
+        &mut app,
+        "platform",
+        "counter",
+        global_type(ValueType::I32, true),
+    );
+    app.global.append(Global {
+        ty: global_type(ValueType::I32, false),
+        init: ConstExpr::GlobalGet(0),
+    });
+    export_global(&mut app, "counter", 0);
+    export_global(&mut app, "initial", 1);
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    assert_eq!(group.global("app", "initial"), Some(Value::I32(10)));
+    assert_eq!(group.set_global("app", "counter", Value::I32(5)), Ok(()));
+    assert_eq!(group.global("platform", "counter"), Some(Value::I32(5)));
+    assert_eq!(
+        group.call_export("platform", "get", []),
+        Ok(Some(Value::I32(5)))
+    );
+    assert!(group.set_global("app", "initial", Value::I32(0)).is_err());
+}
+
+#[test]
+fn test_group_global_mutability_mismatch() {
+    let arena = Bump::new();
+    let platform = counter_module(&arena);
+
+    let mut app = WasmModule::new(&arena);
+    import_global(
+        &mut app,
+        "platform",
+        "counter",
+        global_type(ValueType::I32, false),
+    );
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    assert!(group.instantiate("app", &app).is_err());
+}
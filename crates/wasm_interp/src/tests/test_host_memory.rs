@@ -0,0 +1,120 @@
+use super::write_roc_header;
+use crate::{Memory, MemoryError};
+
+#[test]
+fn test_read_write_numbers() {
+    let mut bytes = [0; 16];
+    let mut memory = Memory::new(&mut bytes);
+
+    memory.write_u32(0, 0x1234_5678).unwrap();
+    memory.write_i32(4, -2).unwrap();
+    memory.write_f64(8, 1.5).unwrap();
+
+    assert_eq!(memory.read_u8(0), Ok(0x78));
+    assert_eq!(memory.read_u32(0), Ok(0x1234_5678));
+    assert_eq!(memory.read_i32(4), Ok(-2));
+    assert_eq!(memory.read_u64(0), Ok(0xffff_fffe_1234_5678));
+    assert_eq!(memory.read_f64(8), Ok(1.5));
+    assert_eq!(&bytes[0..4], &[0x78, 0x56, 0x34, 0x12]);
+}
+
+#[test]
+fn test_out_of_bounds() {
+    let mut bytes = [0; 16];
+    let mut memory = Memory::new(&mut bytes);
+
+    let error = MemoryError::OutOfBounds {
+        addr: 14,
+        len: 4,
+        memory_size: 16,
+    };
+    assert_eq!(memory.read_u32(14), Err(error.clone()));
+    assert_eq!(memory.write_u32(14, 1), Err(error));
+    assert_eq!(memory.read_bytes(16, 0), Ok(&[][..]));
+
+    // The address calculation doesn't wrap around
+    assert!(memory.read_bytes(u32::MAX, 2).is_err());
+    assert!(memory.write_bytes(u32::MAX, &[1, 2]).is_err());
+
+    // Nothing was written
+    assert_eq!(bytes, [0; 16]);
+}
+
+#[test]
+fn test_written_ranges() {
+    let mut bytes = [0; 32];
+    let mut memory = Memory::new(&mut bytes);
+
+    memory.write_u32(20, 1).unwrap();
+    memory.write_u32(4, 1).unwrap();
+    memory.write_u32(8, 1).unwrap();
+    memory.write_u64(6, 1).unwrap();
+    memory.write_bytes(0, &[]).unwrap();
+    assert!(memory.write_u32(30, 1).is_err());
+    memory.read_u32(24).unwrap();
+
+    // Overlapping and adjacent writes are merged, and failed writes and reads don't count
+    assert_eq!(memory.written_ranges(), [4..14, 20..24]);
+
+    memory.as_bytes_mut();
+    let ranges = memory.written_ranges();
+    assert_eq!((ranges.len(), ranges[0].clone()), (1, 0..32));
+}
+
+#[test]
+fn test_read_str() {
+    let mut bytes = *b"hello\xff";
+    let memory = Memory::new(&mut bytes);
+
+    assert_eq!(memory.read_str(1, 4), Ok("ello"));
+    assert_eq!(
+        memory.read_str(4, 2),
+        Err(MemoryError::InvalidUtf8 { addr: 4, len: 2 })
+    );
+}
+
+#[test]
+fn test_read_roc_str() {
+    let mut bytes = [0; 64];
+    let mut memory = Memory::new(&mut bytes);
+
+    // Small strings are stored inline
+    memory.write_bytes(0, b"small").unwrap();
+    memory.write_u8(11, 0x80 | 5).unwrap();
+    assert_eq!(memory.read_roc_str(0), Ok("small"));
+
+    memory
+        .write_bytes(32, b"a string too big to be small")
+        .unwrap();
+    write_roc_header(&mut memory, 12, 32, 28);
+    assert_eq!(memory.read_roc_str(12), Ok("a string too big to be small"));
+
+    // A seamless slice has the high bit of its length set
+    write_roc_header(&mut memory, 12, 34, 6 | 0x8000_0000);
+    assert_eq!(memory.read_roc_str(12), Ok("string"));
+
+    write_roc_header(&mut memory, 12, 60, 28);
+    assert!(matches!(
+        memory.read_roc_str(12),
+        Err(MemoryError::OutOfBounds { addr: 60, .. })
+    ));
+}
+
+#[test]
+fn test_read_roc_list() {
+    let mut bytes = [0; 32];
+    let mut memory = Memory::new(&mut bytes);
+
+    for (i, n) in [10u32, 20, 30].into_iter().enumerate() {
+        memory.write_u32(16 + 4 * i as u32, n).unwrap();
+    }
+    write_roc_header(&mut memory, 0, 16, 3);
+
+    let elements = memory.read_roc_list(0, 4).unwrap();
+    assert_eq!(elements.len(), 12);
+    assert_eq!(&elements[4..8], &20u32.to_le_bytes());
+
+    // The length of the elements in bytes overflows
+    write_roc_header(&mut memory, 0, 16, 0x4000_0000);
+    assert!(memory.read_roc_list(0, 8).is_err());
+}
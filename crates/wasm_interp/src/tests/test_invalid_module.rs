@@ -0,0 +1,197 @@
+use super::create_exported_function_no_locals;
+use crate::{DefaultImportDispatcher, Instance, LoadError};
+use bumpalo::{collections::Vec, Bump};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use roc_wasm_module::sections::{DataMode, DataSegment, MemorySection, SectionId};
+use roc_wasm_module::{
+    opcodes::OpCode, ConstExpr, Global, GlobalType, Signature, Value, ValueType, WasmModule,
+};
+
+/// A module that uses most of the sections: one page of memory with a data segment,
+/// a mutable global, and a function `get(addr: i32) -> i32` that adds the global to a load.
+fn valid_module_bytes() -> std::vec::Vec<u8> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+    module.data.append_segment(DataSegment {
+        mode: DataMode::Active {
+            offset: ConstExpr::I32(16),
+        },
+        init: Vec::from_iter_in([1, 2, 3, 4], &arena),
+    });
+    module.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I32,
+            is_mutable: true,
+        },
+        init: ConstExpr::I32(100),
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "get", signature, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::I32LOAD as u8);
+        buf.push(2); // alignment
+        buf.push(0); // offset
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    bytes
+}
+
+/// Try to load the bytes, making sure that any problem comes back as an error
+fn try_load(bytes: &[u8]) {
+    let arena = Bump::new();
+    let module = match WasmModule::preload(&arena, bytes, false) {
+        Ok(module) => module,
+        Err(_) => return,
+    };
+
+    // Don't allocate gigabytes of memory just because a byte changed
+    let small_memory =
+        matches!(module.memory.min_bytes(), Ok(n) if n <= 16 * MemorySection::PAGE_SIZE);
+    if small_memory {
+        let _ = Instance::from_bytes(&arena, bytes, DefaultImportDispatcher::default(), false);
+    }
+}
+
+#[test]
+fn test_valid_module() {
+    let bytes = valid_module_bytes();
+    let arena = Bump::new();
+    let mut inst =
+        Instance::from_bytes(&arena, &bytes, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(
+        inst.call_export("get", [Value::I32(16)]),
+        Ok(Some(Value::I32(0x04030201 + 100)))
+    );
+}
+
+#[test]
+fn test_truncated_module() {
+    let bytes = valid_module_bytes();
+    for len in 0..bytes.len() {
+        try_load(&bytes[..len]);
+    }
+}
+
+#[test]
+fn test_mutated_module() {
+    let bytes = valid_module_bytes();
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+
+    for _ in 0..20_000 {
+        let mut mutated = bytes.clone();
+        for _ in 0..rng.gen_range(1..=4) {
+            let index = rng.gen_range(0..mutated.len());
+            mutated[index] = rng.gen();
+        }
+        try_load(&mutated);
+    }
+}
+
+#[test]
+fn test_random_bytes() {
+    let mut rng = StdRng::seed_from_u64(0xbad);
+    let header = &valid_module_bytes()[..8];
+
+    for _ in 0..5_000 {
+        let len = rng.gen_range(0..200);
+        let mut bytes = header.to_vec();
+        bytes.extend((0..len).map(|_| rng.gen::<u8>()));
+        try_load(&bytes);
+    }
+}
+
+#[test]
+fn test_invalid_module_error() {
+    let mut bytes = valid_module_bytes();
+
+    // Make the function's return type invalid. The Type section comes right after the header,
+    // and the signature is 0x60, param count, param, result count, result.
+    let type_section = 8;
+    assert_eq!(bytes[type_section], SectionId::Type as u8);
+    let signature_start = type_section
+        + bytes[type_section..]
+            .iter()
+            .position(|&b| b == 0x60)
+            .unwrap();
+    let ret_type = signature_start + 4;
+    assert_eq!(bytes[ret_type], ValueType::I32 as u8);
+    bytes[ret_type] = 0x55;
+
+    let arena = Bump::new();
+    let result = Instance::from_bytes(&arena, &bytes, DefaultImportDispatcher::default(), false);
+    match result {
+        Err(LoadError::InvalidModule {
+            section,
+            offset,
+            message,
+        }) => {
+            assert_eq!(section, Some(SectionId::Type));
+            assert_eq!(offset, ret_type);
+            assert!(message.contains("0x55"), "{}", message);
+        }
+        Err(e) => panic!("Expected an invalid module error, got {:?}", e),
+        Ok(_) => panic!("Expected an invalid module error"),
+    }
+}
+
+#[test]
+fn test_section_past_end_of_file() {
+    let bytes = valid_module_bytes();
+    let truncated = &bytes[..bytes.len() - 1];
+
+    let arena = Bump::new();
+    let error = Instance::from_bytes(&arena, truncated, DefaultImportDispatcher::default(), false)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(error, LoadError::InvalidModule { .. }),
+        "{:?}",
+        error
+    );
+    assert!(error.to_string().starts_with("Invalid module"), "{}", error);
+}
+
+#[test]
+fn test_trap_while_instantiating() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+    module.data.append_segment(DataSegment {
+        mode: DataMode::Active {
+            offset: ConstExpr::I32(MemorySection::PAGE_SIZE as i32 - 2),
+        },
+        init: Vec::from_iter_in([1, 2, 3, 4], &arena),
+    });
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "nothing", signature, |buf| {
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+
+    let error = Instance::from_bytes(&arena, &bytes, DefaultImportDispatcher::default(), false)
+        .err()
+        .unwrap();
+    match error {
+        LoadError::Trap(message) => assert!(message.contains("doesn't fit"), "{}", message),
+        e => panic!("Expected a trap, got {:?}", e),
+    }
+}
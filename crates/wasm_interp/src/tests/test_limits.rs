@@ -0,0 +1,188 @@
+use super::{create_exported_function_no_locals, create_exported_function_with_locals};
+use crate::{DefaultImportDispatcher, Instance, ResourceLimits};
+use bumpalo::Bump;
+use roc_wasm_module::sections::{ConstExpr, ElementSegment, Limits};
+use roc_wasm_module::{opcodes::OpCode, SerialBuffer, Signature, Value, ValueType, WasmModule};
+
+/// A module with a function `depth(n: i32) -> i32` that recurses n times, then returns n.
+/// Each call has 10 locals besides its argument.
+fn recursive_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    let locals = [(10, ValueType::I32)];
+    create_exported_function_with_locals(&mut module, "depth", signature, &locals, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32EQZ as u8);
+        buf.push(OpCode::IF as u8);
+        buf.push(ValueType::I32 as u8);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::ELSE as u8);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32SUB as u8);
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    module
+}
+
+#[test]
+fn test_unlimited_by_default() {
+    let arena = Bump::new();
+    let module = recursive_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.call_export("depth", [Value::I32(10_000)]),
+        Ok(Some(Value::I32(10_000)))
+    );
+}
+
+#[test]
+fn test_call_depth_limit() {
+    let arena = Bump::new();
+    let module = recursive_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_resource_limits(ResourceLimits {
+        max_call_depth: 10,
+        ..Default::default()
+    });
+
+    // The exported call and 9 more
+    assert_eq!(
+        inst.call_export("depth", [Value::I32(9)]),
+        Ok(Some(Value::I32(9)))
+    );
+
+    let message = inst
+        .call_export("depth", [Value::I32(10)])
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("limit of 10 nested calls"), "{}", message);
+
+    // The instance can still be used after the error
+    assert_eq!(
+        inst.call_export("depth", [Value::I32(3)]),
+        Ok(Some(Value::I32(3)))
+    );
+}
+
+#[test]
+fn test_stack_limit() {
+    let arena = Bump::new();
+    let module = recursive_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_resource_limits(ResourceLimits {
+        max_stack_values: 100,
+        ..Default::default()
+    });
+
+    // 11 values for each call's argument and locals
+    assert_eq!(
+        inst.call_export("depth", [Value::I32(8)]),
+        Ok(Some(Value::I32(8)))
+    );
+
+    let message = inst
+        .call_export("depth", [Value::I32(9)])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        message.contains("limit of 100 values on the stack") && message.contains("has 110 values"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_table_limit() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = || Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "test", signature(), |buf| {
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::CALLINDIRECT as u8);
+        buf.encode_u32(0); // signature index
+        buf.encode_u32(0); // table index
+        buf.push(OpCode::END as u8);
+    });
+    create_exported_function_no_locals(&mut module, "callee", signature(), |buf| {
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(111);
+        buf.push(OpCode::END as u8);
+    });
+
+    module.table.function_table.limits = Limits::Min(1000);
+    let mut segment = ElementSegment::new(&arena);
+    segment.offset = ConstExpr::I32(1);
+    module.element.segments.push(segment);
+    assert_eq!(module.element.get_or_insert_fn(1), 1);
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(inst.call_export("test", []), Ok(Some(Value::I32(111))));
+
+    inst.set_resource_limits(ResourceLimits {
+        max_table_elements: 100,
+        ..Default::default()
+    });
+    let message = inst.call_export("test", []).unwrap_err().to_string();
+    assert!(
+        message.contains("the table has 1000 elements, more than the limit of 100"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_fuel() {
+    let arena = Bump::new();
+    let module = recursive_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(inst.fuel(), None);
+
+    inst.set_fuel(Some(1000));
+    assert_eq!(
+        inst.call_export("depth", [Value::I32(3)]),
+        Ok(Some(Value::I32(3)))
+    );
+    let fuel_left = inst.fuel().unwrap();
+    assert!(fuel_left < 1000, "{}", fuel_left);
+
+    inst.set_fuel(Some(10));
+    let message = inst
+        .call_export("depth", [Value::I32(100)])
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("ran out of fuel"), "{}", message);
+    assert_eq!(inst.fuel(), Some(0));
+
+    inst.set_fuel(None);
+    assert_eq!(
+        inst.call_export("depth", [Value::I32(100)]),
+        Ok(Some(Value::I32(100)))
+    );
+}
@@ -0,0 +1,756 @@
+use super::{const_value, create_exported_function_no_locals};
+use crate::{DefaultImportDispatcher, ImportDispatcher, InstanceGroup, Memory};
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::sections::{
+    DataMode, DataSegment, ElementSegment, Import, ImportDesc, Limits, MemorySection, TableType,
+};
+use roc_wasm_module::{
+    opcodes::OpCode, ConstExpr, Export, ExportType, Global, GlobalType, SerialBuffer, Signature,
+    Value, ValueType, WasmModule,
+};
+
+fn signature<'a>(
+    arena: &'a Bump,
+    params: &[ValueType],
+    ret_type: Option<ValueType>,
+) -> Signature<'a> {
+    Signature {
+        param_types: Vec::from_iter_in(params.iter().copied(), arena),
+        ret_type,
+    }
+}
+
+fn import_function<'a>(
+    module: &mut WasmModule<'a>,
+    module_name: &'a str,
+    name: &'a str,
+    sig: Signature<'a>,
+) {
+    let signature_index = module.types.insert(sig);
+    module.import.imports.push(Import {
+        module: module_name,
+        name,
+        description: ImportDesc::Func { signature_index },
+    });
+}
+
+fn call(buf: &mut Vec<'_, u8>, fn_index: u32) {
+    buf.push(OpCode::CALL as u8);
+    buf.encode_u32(fn_index);
+}
+
+/// A module exporting `add(a, b) = a + b`
+fn adder_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+    let sig = signature(
+        arena,
+        &[ValueType::I32, ValueType::I32],
+        Some(ValueType::I32),
+    );
+    create_exported_function_no_locals(&mut module, "add", sig, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+    module
+}
+
+#[test]
+fn test_link_function() {
+    let arena = Bump::new();
+    let adder = adder_module(&arena);
+
+    let mut user = WasmModule::new(&arena);
+    let add_sig = signature(
+        &arena,
+        &[ValueType::I32, ValueType::I32],
+        Some(ValueType::I32),
+    );
+    import_function(&mut user, "adder", "add", add_sig);
+    let test_sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut user, "test", test_sig, |buf| {
+        const_value(buf, Value::I32(2));
+        const_value(buf, Value::I32(3));
+        call(buf, 0);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("adder", &adder).unwrap();
+    group.instantiate("user", &user).unwrap();
+
+    assert_eq!(
+        group.call_export("user", "test", []),
+        Ok(Some(Value::I32(5)))
+    );
+    assert_eq!(
+        group.call_export("adder", "add", [Value::I32(40), Value::I32(2)]),
+        Ok(Some(Value::I32(42)))
+    );
+}
+
+#[test]
+fn test_link_missing_export() {
+    let arena = Bump::new();
+    let adder = adder_module(&arena);
+
+    let mut user = WasmModule::new(&arena);
+    let sig = signature(
+        &arena,
+        &[ValueType::I32, ValueType::I32],
+        Some(ValueType::I32),
+    );
+    import_function(&mut user, "adder", "subtract", sig);
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("adder", &adder).unwrap();
+    let result = group.instantiate("user", &user);
+
+    assert!(result.unwrap_err().contains("subtract"));
+}
+
+#[test]
+fn test_link_signature_mismatch() {
+    let arena = Bump::new();
+    let adder = adder_module(&arena);
+
+    let mut user = WasmModule::new(&arena);
+    let sig = signature(
+        &arena,
+        &[ValueType::I64, ValueType::I64],
+        Some(ValueType::I64),
+    );
+    import_function(&mut user, "adder", "add", sig);
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("adder", &adder).unwrap();
+    let result = group.instantiate("user", &user);
+
+    assert!(result.unwrap_err().contains("signature"));
+}
+
+struct Doubler;
+
+impl ImportDispatcher for Doubler {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        _memory: &mut Memory<'_>,
+    ) -> Option<Value> {
+        assert_eq!((module_name, function_name), ("env", "double"));
+        Some(Value::I32(arguments[0].expect_i32().unwrap() * 2))
+    }
+}
+
+#[test]
+fn test_link_host_import_through_another_module() {
+    let arena = Bump::new();
+    let i32_to_i32 = || signature(&arena, &[ValueType::I32], Some(ValueType::I32));
+
+    // The platform re-exports one of its own imports, and calls it from another function
+    let mut platform = WasmModule::new(&arena);
+    import_function(&mut platform, "env", "double", i32_to_i32());
+    platform.export.append(Export {
+        name: "double",
+        ty: ExportType::Func,
+        index: 0,
+    });
+    create_exported_function_no_locals(&mut platform, "quadruple", i32_to_i32(), |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        call(buf, 0);
+        call(buf, 0);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut app = WasmModule::new(&arena);
+    import_function(&mut app, "platform", "double", i32_to_i32());
+    import_function(&mut app, "platform", "quadruple", i32_to_i32());
+    create_exported_function_no_locals(&mut app, "test", i32_to_i32(), |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        call(buf, 0);
+        call(buf, 1);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut group = InstanceGroup::new(&arena, Doubler, false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    assert_eq!(
+        group.call_export("app", "test", [Value::I32(3)]),
+        Ok(Some(Value::I32(24)))
+    );
+}
+
+#[test]
+fn test_link_memory() {
+    let arena = Bump::new();
+
+    // The platform owns the memory and has a function that writes to it
+    let mut platform = WasmModule::new(&arena);
+    platform.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+    platform.export.append(Export {
+        name: "memory",
+        ty: ExportType::Mem,
+        index: 0,
+    });
+    let store_sig = signature(&arena, &[ValueType::I32, ValueType::I32], None);
+    create_exported_function_no_locals(&mut platform, "store", store_sig, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(1);
+        buf.push(OpCode::I32STORE as u8);
+        buf.push(2); // alignment
+        buf.push(0); // offset
+        buf.push(OpCode::END as u8);
+    });
+
+    // The app reads back what the platform wrote, and has some data of its own
+    let mut app = WasmModule::new(&arena);
+    app.import.imports.push(Import {
+        module: "platform",
+        name: "memory",
+        description: ImportDesc::Mem {
+            limits: Limits::Min(1),
+        },
+    });
+    let store_sig = signature(&arena, &[ValueType::I32, ValueType::I32], None);
+    import_function(&mut app, "platform", "store", store_sig);
+    app.data.append_segment(DataSegment {
+        mode: DataMode::Active {
+            offset: ConstExpr::I32(100),
+        },
+        init: bumpalo::vec![in &arena; 7, 0, 0, 0],
+    });
+    let test_sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut app, "test", test_sig, |buf| {
+        const_value(buf, Value::I32(16));
+        const_value(buf, Value::I32(42));
+        call(buf, 0);
+        const_value(buf, Value::I32(16));
+        buf.push(OpCode::I32LOAD as u8);
+        buf.push(2); // alignment
+        buf.push(0); // offset
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    assert_eq!(
+        group.call_export("app", "test", []),
+        Ok(Some(Value::I32(42)))
+    );
+
+    let memory = group.memory("platform").unwrap();
+    assert_eq!(memory.len(), MemorySection::PAGE_SIZE as usize);
+    assert_eq!(&memory[16..20], &[42, 0, 0, 0]);
+    assert_eq!(&memory[100..104], &[7, 0, 0, 0]);
+    assert_eq!(group.memory("app"), Some(memory));
+}
+
+#[test]
+fn test_link_global() {
+    let arena = Bump::new();
+
+    // The platform has a counter, and a function that increments it
+    let mut platform = WasmModule::new(&arena);
+    platform.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I32,
+            is_mutable: true,
+        },
+        init: ConstExpr::I32(10),
+    });
+    platform.export.append(Export {
+        name: "counter",
+        ty: ExportType::Global,
+        index: 0,
+    });
+    let increment_sig = signature(&arena, &[], None);
+    create_exported_function_no_locals(&mut platform, "increment", increment_sig, |buf| {
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::SETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::END as u8);
+    });
+    let get_sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut platform, "get", get_sig, |buf| {
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::END as u8);
+    });
+
+    // The app doubles the counter, then has the platform increment it
+    let mut app = WasmModule::new(&arena);
+    app.import.imports.push(Import {
+        module: "platform",
+        name: "counter",
+        description: ImportDesc::Global {
+            ty: GlobalType {
+                value_type: ValueType::I32,
+                is_mutable: true,
+            },
+        },
+    });
+    let increment_sig = signature(&arena, &[], None);
+    import_function(&mut app, "platform", "increment", increment_sig);
+    let test_sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut app, "test", test_sig, |buf| {
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        const_value(buf, Value::I32(2));
+        buf.push(OpCode::I32MUL as u8);
+        buf.push(OpCode::SETGLOBAL as u8);
+        buf.push(0);
+        call(buf, 0);
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    assert_eq!(
+        group.call_export("app", "test", []),
+        Ok(Some(Value::I32(21)))
+    );
+    assert_eq!(
+        group.call_export("platform", "get", []),
+        Ok(Some(Value::I32(21)))
+    );
+}
+
+#[test]
+fn test_link_table() {
+    assert_eq!(
+        test_link_table_help(&[ValueType::I32, ValueType::I32], 0),
+        Ok(Some(Value::I32(42)))
+    );
+}
+
+#[test]
+fn test_link_table_wrong_signature() {
+    let message = test_link_table_help(&[ValueType::I32], 0).unwrap_err();
+    assert!(
+        message.contains("Expected [I32] -> [I32], but element 0 of table 0 is func[0], which has type [I32, I32] -> [I32]"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_link_table_null() {
+    let message = test_link_table_help(&[ValueType::I32, ValueType::I32], 1).unwrap_err();
+    assert!(
+        message.contains("element 1 of table 0") && message.contains("that element is null"),
+        "{}",
+        message
+    );
+}
+
+fn test_link_table_help(
+    call_params: &[ValueType],
+    element_index: i32,
+) -> Result<Option<Value>, String> {
+    let arena = Bump::new();
+
+    // The platform has a function table with `add` in it, and room for one more function
+    let mut platform = adder_module(&arena);
+    platform.table.function_table = TableType {
+        ref_type: platform.table.function_table.ref_type,
+        limits: Limits::Min(2),
+    };
+    platform.export.append(Export {
+        name: "table",
+        ty: ExportType::Table,
+        index: 0,
+    });
+    platform.element.segments.push(ElementSegment::new(&arena));
+    assert_eq!(platform.element.get_or_insert_fn(0), 0);
+
+    // The app calls `add` through a function pointer into the platform's table
+    let mut app = WasmModule::new(&arena);
+    app.import.imports.push(Import {
+        module: "platform",
+        name: "table",
+        description: ImportDesc::Table {
+            ty: TableType {
+                ref_type: platform.table.function_table.ref_type,
+                limits: Limits::Min(2),
+            },
+        },
+    });
+    let call_sig_index = app
+        .types
+        .insert(signature(&arena, call_params, Some(ValueType::I32)));
+    let test_sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut app, "test", test_sig, |buf| {
+        for (i, _) in call_params.iter().enumerate() {
+            const_value(buf, Value::I32(20 + 2 * i as i32));
+        }
+        const_value(buf, Value::I32(element_index));
+        buf.push(OpCode::CALLINDIRECT as u8);
+        buf.encode_u32(call_sig_index);
+        buf.push(0); // table index
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    group.call_export("app", "test", []).map_err(String::from)
+}
+
+#[test]
+fn test_link_error_can_be_recovered_from() {
+    let arena = Bump::new();
+
+    let mut platform = WasmModule::new(&arena);
+    let sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut platform, "crash", sig, |buf| {
+        buf.push(OpCode::UNREACHABLE as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut app = WasmModule::new(&arena);
+    let sig = signature(&arena, &[], Some(ValueType::I32));
+    import_function(&mut app, "platform", "crash", sig);
+    let sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut app, "test", sig, |buf| {
+        call(buf, 0);
+        buf.push(OpCode::END as u8);
+    });
+    let sig = signature(&arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut app, "ok", sig, |buf| {
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    let error = group
+        .call_export("app", "test", [])
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("unreachable"));
+    assert_eq!(group.call_export("app", "ok", []), Ok(Some(Value::I32(1))));
+}
+
+/// A platform with `add` at element 0 of its table, and a function that calls element 1 with
+/// 20 and 22, for an app to fill in
+fn platform_with_open_table(arena: &Bump) -> WasmModule<'_> {
+    let mut platform = adder_module(arena);
+    platform.table.function_table = TableType {
+        ref_type: platform.table.function_table.ref_type,
+        limits: Limits::Min(2),
+    };
+    platform.export.append(Export {
+        name: "table",
+        ty: ExportType::Table,
+        index: 0,
+    });
+    platform.element.segments.push(ElementSegment::new(arena));
+    platform.element.get_or_insert_fn(0);
+
+    let binary_sig = platform.types.insert(signature(
+        arena,
+        &[ValueType::I32, ValueType::I32],
+        Some(ValueType::I32),
+    ));
+    let test_sig = signature(arena, &[], Some(ValueType::I32));
+    create_exported_function_no_locals(&mut platform, "call_element_1", test_sig, |buf| {
+        const_value(buf, Value::I32(20));
+        const_value(buf, Value::I32(22));
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::CALLINDIRECT as u8);
+        buf.encode_u32(binary_sig);
+        buf.push(0); // table index
+        buf.push(OpCode::END as u8);
+    });
+    platform
+}
+
+/// An app that imports the platform's table and puts its `sub` function in it at each offset.
+/// With `use_platform`, it also imports the platform's memory and `call_element_1`.
+fn app_with_elements<'a>(arena: &'a Bump, offsets: &[i32], use_platform: bool) -> WasmModule<'a> {
+    let mut app = WasmModule::new(arena);
+    if use_platform {
+        app.import.imports.push(Import {
+            module: "platform",
+            name: "memory",
+            description: ImportDesc::Mem {
+                limits: Limits::Min(1),
+            },
+        });
+        let test_sig = signature(arena, &[], Some(ValueType::I32));
+        import_function(&mut app, "platform", "call_element_1", test_sig);
+    }
+    app.import.imports.push(Import {
+        module: "platform",
+        name: "table",
+        description: ImportDesc::Table {
+            ty: TableType {
+                ref_type: app.table.function_table.ref_type,
+                limits: Limits::Min(2),
+            },
+        },
+    });
+    let sub_sig = signature(
+        arena,
+        &[ValueType::I32, ValueType::I32],
+        Some(ValueType::I32),
+    );
+    let sub_index = app.import.function_count() as u32;
+    create_exported_function_no_locals(&mut app, "sub", sub_sig, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(1);
+        buf.push(OpCode::I32SUB as u8);
+        buf.push(OpCode::END as u8);
+    });
+    for offset in offsets {
+        app.element.segments.push(ElementSegment {
+            offset: ConstExpr::I32(*offset),
+            fn_indices: bumpalo::vec![in arena; sub_index],
+        });
+    }
+    app
+}
+
+#[test]
+fn test_link_table_elements_from_importer() {
+    let arena = Bump::new();
+    let platform = platform_with_open_table(&arena);
+    let app = app_with_elements(&arena, &[1], false);
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+
+    let message = group
+        .call_export("platform", "call_element_1", [])
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("that element is null"), "{}", message);
+
+    // The platform's own indirect call finds the app's function
+    group.instantiate("app", &app).unwrap();
+    assert_eq!(
+        group.call_export("platform", "call_element_1", []),
+        Ok(Some(Value::I32(-2)))
+    );
+}
+
+#[test]
+fn test_link_table_elements_out_of_bounds() {
+    let arena = Bump::new();
+    let platform = platform_with_open_table(&arena);
+    let app = app_with_elements(&arena, &[1, 2], false);
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+
+    let message = group.instantiate("app", &app).unwrap_err();
+    assert_eq!(
+        message,
+        "`app` has an element segment for elements 2..3 of its imported table, but the table only has 2 elements"
+    );
+
+    // Nothing was written, not even the segment that fits
+    let message = group
+        .call_export("platform", "call_element_1", [])
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("that element is null"), "{}", message);
+}
+
+#[test]
+fn test_link_start_function() {
+    let arena = Bump::new();
+
+    let mut platform = platform_with_open_table(&arena);
+    platform.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+    platform.export.append(Export {
+        name: "memory",
+        ty: ExportType::Mem,
+        index: 0,
+    });
+
+    // The app's start function runs after its table elements and data are in place, so it can
+    // call its own function through the platform, and read its own data
+    let mut app = app_with_elements(&arena, &[1], true);
+    app.data.append_segment(DataSegment {
+        mode: DataMode::Active {
+            offset: ConstExpr::I32(100),
+        },
+        init: bumpalo::vec![in &arena; 5, 0, 0, 0],
+    });
+    let start_sig = signature(&arena, &[], None);
+    create_exported_function_no_locals(&mut app, "start", start_sig, |buf| {
+        // memory[16] = call_element_1() + memory[100]
+        const_value(buf, Value::I32(16));
+        call(buf, 0);
+        const_value(buf, Value::I32(100));
+        buf.push(OpCode::I32LOAD as u8);
+        buf.push(2); // alignment
+        buf.push(0); // offset
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::I32STORE as u8);
+        buf.push(2); // alignment
+        buf.push(0); // offset
+        buf.push(OpCode::END as u8);
+    });
+    app.start.function_index = Some(2);
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    let memory = group.memory("platform").unwrap();
+    assert_eq!(&memory[16..20], &3i32.to_le_bytes());
+}
+
+#[test]
+fn test_link_start_function_fails() {
+    let arena = Bump::new();
+    let platform = platform_with_open_table(&arena);
+
+    let mut app = app_with_elements(&arena, &[1], false);
+    let start_sig = signature(&arena, &[], None);
+    create_exported_function_no_locals(&mut app, "start", start_sig, |buf| {
+        buf.push(OpCode::UNREACHABLE as u8);
+        buf.push(OpCode::END as u8);
+    });
+    app.start.function_index = Some(1);
+    let fixed_app = app_with_elements(&arena, &[1], false);
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+
+    let message = group.instantiate("app", &app).unwrap_err();
+    assert!(
+        message.starts_with("The start function of `app` failed:"),
+        "{}",
+        message
+    );
+    assert!(message.contains("unreachable"), "{}", message);
+
+    // The app isn't in the group, and the elements it wrote are gone with it
+    assert!(group
+        .call_export("app", "sub", [Value::I32(1), Value::I32(1)])
+        .is_err());
+    let message = group
+        .call_export("platform", "call_element_1", [])
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("that element is null"), "{}", message);
+
+    // A module with the same name can be instantiated after all
+    group.instantiate("app", &fixed_app).unwrap();
+    assert_eq!(
+        group.call_export("platform", "call_element_1", []),
+        Ok(Some(Value::I32(-2)))
+    );
+}
+
+#[test]
+fn test_link_reentrant_calls() {
+    let arena = Bump::new();
+    let i32_to_i32 = || signature(&arena, &[ValueType::I32], Some(ValueType::I32));
+
+    // The platform calls whatever the app puts at element 1 of its table
+    let mut platform = WasmModule::new(&arena);
+    platform.table.function_table = TableType {
+        ref_type: platform.table.function_table.ref_type,
+        limits: Limits::Min(2),
+    };
+    platform.export.append(Export {
+        name: "table",
+        ty: ExportType::Table,
+        index: 0,
+    });
+    let relay_sig_index = platform.types.insert(i32_to_i32());
+    create_exported_function_no_locals(&mut platform, "relay", i32_to_i32(), |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::CALLINDIRECT as u8);
+        buf.encode_u32(relay_sig_index);
+        buf.push(0); // table index
+        buf.push(OpCode::END as u8);
+    });
+
+    // sum(n) = if n == 0 then 0 else n + relay(n - 1)
+    // Every relay enters the app again while its caller is still waiting for the result.
+    let mut app = WasmModule::new(&arena);
+    app.import.imports.push(Import {
+        module: "platform",
+        name: "table",
+        description: ImportDesc::Table {
+            ty: TableType {
+                ref_type: app.table.function_table.ref_type,
+                limits: Limits::Min(2),
+            },
+        },
+    });
+    import_function(&mut app, "platform", "relay", i32_to_i32());
+    create_exported_function_no_locals(&mut app, "sum", i32_to_i32(), |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::I32EQZ as u8);
+        buf.push(OpCode::IF as u8);
+        buf.push(ValueType::I32 as u8);
+        const_value(buf, Value::I32(0));
+        buf.push(OpCode::ELSE as u8);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::I32SUB as u8);
+        call(buf, 0);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+        buf.push(OpCode::END as u8);
+    });
+    app.element.segments.push(ElementSegment {
+        offset: ConstExpr::I32(1),
+        fn_indices: bumpalo::vec![in &arena; 1],
+    });
+
+    let mut group = InstanceGroup::new(&arena, DefaultImportDispatcher::default(), false);
+    group.instantiate("platform", &platform).unwrap();
+    group.instantiate("app", &app).unwrap();
+
+    assert_eq!(
+        group.call_export("app", "sum", [Value::I32(4)]),
+        Ok(Some(Value::I32(10)))
+    );
+    // Each entry into the app returned to the right place, leaving nothing behind
+    assert_eq!(
+        group.call_export("platform", "relay", [Value::I32(3)]),
+        Ok(Some(Value::I32(6)))
+    );
+    assert_eq!(
+        group.call_export("app", "sum", [Value::I32(0)]),
+        Ok(Some(Value::I32(0)))
+    );
+}
@@ -1,10 +1,10 @@
-use super::create_exported_function_no_locals;
-use crate::{DefaultImportDispatcher, Instance};
+use super::{const_value, create_exported_function_no_locals};
+use crate::{DefaultImportDispatcher, Instance, MemoryErrorMode};
 use bumpalo::{collections::Vec, Bump};
 use roc_wasm_module::{
     opcodes::OpCode,
-    sections::{DataMode, DataSegment, MemorySection},
-    ConstExpr, SerialBuffer, Signature, Value, ValueType, WasmModule,
+    sections::{DataMode, DataSegment, Limits, MemorySection},
+    ConstExpr, SerialBuffer, Serialize, Signature, Value, ValueType, WasmModule,
 };
 
 #[test]
@@ -49,6 +49,82 @@ fn test_growmemory() {
     assert_eq!(state.memory.len(), 5 * MemorySection::PAGE_SIZE as usize);
 }
 
+/// Execute `memory.grow` and return its result, along with the new memory size in pages
+fn test_growmemory_limits(
+    existing_pages: u32,
+    module_max_pages: Option<u32>,
+    host_limit_pages: Option<u32>,
+    grow_pages: i32,
+) -> (i32, u32) {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let limits = match module_max_pages {
+        Some(max) => Limits::MinMax(existing_pages, max),
+        None => Limits::Min(existing_pages),
+    };
+    module.memory = MemorySection {
+        count: 1,
+        bytes: Vec::new_in(&arena),
+    };
+    limits.serialize(&mut module.memory.bytes);
+
+    let pc = 0;
+    module.code.bytes.push(OpCode::I32CONST as u8);
+    module.code.bytes.encode_i32(grow_pages);
+    module.code.bytes.push(OpCode::GROWMEMORY as u8);
+    module.code.bytes.encode_i32(0);
+
+    let mut state = Instance::new(
+        &arena,
+        existing_pages,
+        pc,
+        [],
+        DefaultImportDispatcher::default(),
+    );
+    if let Some(limit) = host_limit_pages {
+        state.set_memory_limit(limit);
+    }
+    state.execute_next_instruction(&module).unwrap();
+    state.execute_next_instruction(&module).unwrap();
+
+    let result = state.value_store.pop_i32().unwrap();
+    (result, state.memory_pages())
+}
+
+#[test]
+fn test_growmemory_returns_old_size() {
+    assert_eq!(test_growmemory_limits(3, None, None, 2), (3, 5));
+    assert_eq!(test_growmemory_limits(3, Some(5), None, 2), (3, 5));
+}
+
+#[test]
+fn test_growmemory_zero() {
+    assert_eq!(test_growmemory_limits(3, None, None, 0), (3, 3));
+    assert_eq!(test_growmemory_limits(3, Some(3), Some(1), 0), (3, 3));
+}
+
+#[test]
+fn test_growmemory_beyond_module_max() {
+    assert_eq!(test_growmemory_limits(3, Some(4), None, 2), (-1, 3));
+}
+
+#[test]
+fn test_growmemory_beyond_host_limit() {
+    assert_eq!(test_growmemory_limits(3, None, Some(4), 2), (-1, 3));
+    assert_eq!(test_growmemory_limits(3, Some(10), Some(4), 1), (3, 4));
+}
+
+#[test]
+fn test_growmemory_huge() {
+    // -1 is u32::MAX pages, which would overflow a naive byte count
+    assert_eq!(test_growmemory_limits(3, None, None, -1), (-1, 3));
+    assert_eq!(
+        test_growmemory_limits(3, None, None, MemorySection::MAX_PAGES as i32),
+        (-1, 3)
+    );
+}
+
 fn test_load(load_op: OpCode, ty: ValueType, data: &[u8], addr: u32, offset: u32) -> Value {
     let arena = Bump::new();
     let mut module = WasmModule::new(&arena);
@@ -261,24 +337,7 @@ fn test_store<'a>(
     create_exported_function_no_locals(module, start_fn_name, signature, |buf| {
         buf.append_u8(OpCode::I32CONST as u8);
         buf.encode_u32(addr);
-        match value {
-            Value::I32(x) => {
-                buf.append_u8(OpCode::I32CONST as u8);
-                buf.encode_i32(x);
-            }
-            Value::I64(x) => {
-                buf.append_u8(OpCode::I64CONST as u8);
-                buf.encode_i64(x);
-            }
-            Value::F32(x) => {
-                buf.append_u8(OpCode::F32CONST as u8);
-                buf.encode_f32(x);
-            }
-            Value::F64(x) => {
-                buf.append_u8(OpCode::F64CONST as u8);
-                buf.encode_f64(x);
-            }
-        }
+        const_value(buf, value);
         buf.append_u8(store_op as u8);
         buf.encode_u32(0); // align
         buf.encode_u32(offset);
@@ -446,3 +505,99 @@ fn test_i64store32() {
         &[0xf0, 0xde, 0xbc, 0x9a, 0x00, 0x00, 0x00, 0x00]
     );
 }
+
+/// A module with one page of memory and a function `load(addr: i32) -> i32`
+/// that loads from `addr + offset`
+fn out_of_bounds_module(arena: &Bump, offset: u32) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+    module.memory = MemorySection::new(arena, MemorySection::PAGE_SIZE);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "load", signature, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32LOAD as u8);
+        buf.encode_u32(2); // alignment
+        buf.encode_u32(offset);
+        buf.push(OpCode::END as u8);
+    });
+
+    module
+}
+
+#[test]
+fn test_load_out_of_bounds_trap() {
+    let arena = Bump::new();
+    let module = out_of_bounds_module(&arena, 0);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    // The last 4 bytes are fine
+    assert_eq!(
+        inst.call_export("load", [Value::I32(0xfffc)]),
+        Ok(Some(Value::I32(0)))
+    );
+
+    let message = inst
+        .call_export("load", [Value::I32(0xfffe)])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        message.starts_with("WebAssembly out of bounds memory access at file offset"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("access   "), "{}", message);
+}
+
+#[test]
+fn test_load_out_of_bounds_explain() {
+    let arena = Bump::new();
+    let module = out_of_bounds_module(&arena, 0);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_memory_error_mode(MemoryErrorMode::Explain);
+    inst.memory[0xfff0] = 0x2a;
+
+    let message = inst
+        .call_export("load", [Value::I32(0xfffe)])
+        .unwrap_err()
+        .to_string();
+    let lines: std::vec::Vec<&str> = message.lines().collect();
+    assert_eq!(
+        &lines[1..9],
+        &[
+            "access   4 bytes at 0xfffe",
+            "memory   0x10000 bytes (1 pages)",
+            // After the padded 5-byte function size, the locals count and local.get 0
+            "function func[0], instruction at +0x8",
+            "00ffc0  00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+            "00ffd0  00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+            "00ffe0  00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+            "00fff0  2a 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+            "-------------------",
+        ]
+    );
+}
+
+#[test]
+fn test_load_out_of_bounds_wild_pointer() {
+    let arena = Bump::new();
+    // The offset is added without wrapping around to the start of the memory
+    let module = out_of_bounds_module(&arena, 0x10);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_memory_error_mode(MemoryErrorMode::Explain);
+
+    let message = inst
+        .call_export("load", [Value::I32(-4)])
+        .unwrap_err()
+        .to_string();
+    let lines: std::vec::Vec<&str> = message.lines().collect();
+    assert_eq!(lines[1], "access   4 bytes at 0x10000000c");
+    // No memory dump, since the address is nowhere near the memory
+    assert_eq!(lines[4], "-------------------");
+}
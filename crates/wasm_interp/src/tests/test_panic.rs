@@ -0,0 +1,141 @@
+use super::create_exported_function_no_locals;
+use crate::{CallError, DefaultImportDispatcher, Instance, RocPanicKind};
+use bumpalo::Bump;
+use roc_wasm_module::sections::{Import, ImportDesc, MemorySection};
+use roc_wasm_module::{opcodes::OpCode, Signature, Value, ValueType, WasmModule};
+
+const SMALL_STR_ADDR: usize = 0x100;
+const BIG_STR_ADDR: usize = 0x200;
+const BIG_STR_BYTES_ADDR: usize = 0x300;
+
+/// A module with a function `fail(str_ptr, tag_id)` that calls the panic import `module_name.name`
+fn panic_module<'a>(arena: &'a Bump, module_name: &'a str, name: &'a str) -> WasmModule<'a> {
+    let mut module = WasmModule::new(arena);
+    module.memory = MemorySection::new(arena, MemorySection::PAGE_SIZE);
+
+    let signature_index = module.types.insert(Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32, ValueType::I32],
+        ret_type: None,
+    });
+    module.import.imports.push(Import {
+        module: module_name,
+        name,
+        description: ImportDesc::Func { signature_index },
+    });
+
+    let sig = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32, ValueType::I32],
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "fail", sig, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(1);
+        buf.push(OpCode::CALL as u8);
+        buf.push(0);
+        buf.push(OpCode::UNREACHABLE as u8);
+        buf.push(OpCode::END as u8);
+    });
+    module
+}
+
+/// Write a small string and a heap-allocated string into memory, in the 32-bit RocStr layout
+fn write_strings(memory: &mut [u8]) {
+    let small = b"oops";
+    memory[SMALL_STR_ADDR..][..small.len()].copy_from_slice(small);
+    memory[SMALL_STR_ADDR + 11] = 0x80 | small.len() as u8;
+
+    let big = b"this message is too long to be a small string";
+    memory[BIG_STR_BYTES_ADDR..][..big.len()].copy_from_slice(big);
+    let words = [
+        BIG_STR_BYTES_ADDR as u32,
+        big.len() as u32,
+        big.len() as u32,
+    ];
+    for (i, word) in words.iter().enumerate() {
+        memory[BIG_STR_ADDR + 4 * i..][..4].copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+fn fail_args(str_addr: usize, tag_id: i32) -> [Value; 2] {
+    [Value::I32(str_addr as i32), Value::I32(tag_id)]
+}
+
+#[test]
+fn test_roc_panic_small_str() {
+    let arena = Bump::new();
+    let module = panic_module(&arena, "env", "roc_panic");
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    write_strings(&mut inst.memory);
+
+    let error = inst
+        .call_export("fail", fail_args(SMALL_STR_ADDR, 0))
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with(r#"Roc failed with message: "oops""#));
+
+    let roc_panic = inst.roc_panic().unwrap();
+    assert_eq!(roc_panic.message, "oops");
+    assert_eq!(roc_panic.kind, RocPanicKind::RocFailure);
+    assert!(roc_panic.backtrace.contains("func[1]"));
+
+    // The caller gets the same panic in the error, without asking the instance for it
+    match error {
+        CallError::RocPanic { panic, .. } => assert_eq!(&panic, roc_panic),
+        other => panic!("Expected a Roc panic, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_roc_panic_big_str() {
+    let arena = Bump::new();
+    let module = panic_module(&arena, "env", "roc_panic");
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    write_strings(&mut inst.memory);
+
+    let error = inst
+        .call_export("fail", fail_args(BIG_STR_ADDR, 1))
+        .unwrap_err()
+        .to_string();
+    assert!(error.starts_with("User crash with message: \"this message is too long"));
+
+    let roc_panic = inst.roc_panic().unwrap();
+    assert_eq!(
+        roc_panic.message,
+        "this message is too long to be a small string"
+    );
+    assert_eq!(roc_panic.kind, RocPanicKind::UserCrash);
+}
+
+#[test]
+fn test_roc_panic_renamed_import() {
+    let arena = Bump::new();
+    let module = panic_module(&arena, "env", "send_panic_msg_to_rust");
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_panic_import(Some(("env", "send_panic_msg_to_rust")));
+    write_strings(&mut inst.memory);
+
+    assert!(inst
+        .call_export("fail", fail_args(SMALL_STR_ADDR, 7))
+        .is_err());
+    assert_eq!(inst.roc_panic().unwrap().kind, RocPanicKind::Unknown(7));
+}
+
+#[test]
+fn test_roc_panic_str_out_of_bounds() {
+    let arena = Bump::new();
+    let module = panic_module(&arena, "env", "roc_panic");
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    let addr = MemorySection::PAGE_SIZE as usize - 4;
+    let error = inst.call_export("fail", fail_args(addr, 0)).unwrap_err();
+    assert!(matches!(error, CallError::Trap(_)), "{:?}", error);
+    assert!(error.to_string().contains("out of bounds"));
+    assert_eq!(inst.roc_panic(), None);
+}
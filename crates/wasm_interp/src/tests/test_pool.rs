@@ -0,0 +1,174 @@
+use super::create_exported_function_no_locals;
+use crate::{DefaultImportDispatcher, Instance, InstancePool, LoadError};
+use bumpalo::Bump;
+use roc_wasm_module::sections::MemorySection;
+use roc_wasm_module::{opcodes::OpCode, SerialBuffer, Signature, Value, ValueType, WasmModule};
+
+/// A module with functions `triple(n: i32) -> i32`, `bump() -> i32`, which adds one to the number
+/// at address 0 and returns it, and `grow() -> i32`, which grows the memory by a page and
+/// returns the old size
+fn pool_module_bytes() -> std::vec::Vec<u8> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "triple", signature, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(3);
+        buf.push(OpCode::I32MUL as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "bump", signature, |buf| {
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::I32LOAD as u8);
+        buf.encode_u32(2); // alignment
+        buf.encode_u32(0); // offset
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::I32STORE as u8);
+        buf.encode_u32(2); // alignment
+        buf.encode_u32(0); // offset
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::I32LOAD as u8);
+        buf.encode_u32(2); // alignment
+        buf.encode_u32(0); // offset
+        buf.push(OpCode::END as u8);
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "grow", signature, |buf| {
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::GROWMEMORY as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    bytes
+}
+
+fn new_pool(threads: usize) -> InstancePool {
+    InstancePool::new(
+        pool_module_bytes(),
+        threads,
+        DefaultImportDispatcher::default,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_pool_keeps_job_order() {
+    let pool = new_pool(4);
+    assert_eq!(pool.size(), 4);
+
+    let results = pool.run((0..100).map(|n| ("triple", [Value::I32(n)])));
+
+    let expected: std::vec::Vec<_> = (0..100).map(|n| Ok(Some(Value::I32(3 * n)))).collect();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn test_pool_resets_between_jobs() {
+    let pool = new_pool(2);
+
+    // Without a reset, a thread that ran several jobs would count past 1, or grow past 2 pages
+    let jobs = (0..20).map(|n| (if n % 2 == 0 { "bump" } else { "grow" }, []));
+    let results = pool.run(jobs);
+
+    for (n, result) in results.into_iter().enumerate() {
+        assert_eq!(result, Ok(Some(Value::I32(1))), "job {}", n);
+    }
+
+    // The pool can be used again
+    assert_eq!(pool.run([("bump", [])]), std::vec![Ok(Some(Value::I32(1)))]);
+}
+
+#[test]
+fn test_pool_job_errors() {
+    let pool = new_pool(1);
+
+    let results = pool.run(std::vec![
+        ("triple", std::vec![Value::I32(2)]),
+        ("missing", std::vec![]),
+        ("triple", std::vec![Value::I64(2)]),
+        ("triple", std::vec![Value::I32(5)]),
+    ]);
+
+    assert_eq!(results[0], Ok(Some(Value::I32(6))));
+    assert!(results[1].is_err());
+    assert!(results[2].is_err());
+    assert_eq!(results[3], Ok(Some(Value::I32(15))));
+}
+
+#[test]
+fn test_pool_invalid_module() {
+    let result = InstancePool::new(std::vec![0, 1, 2, 3], 2, DefaultImportDispatcher::default);
+    assert!(matches!(result, Err(LoadError::InvalidModule { .. })));
+}
+
+#[test]
+fn test_pool_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<InstancePool>();
+
+    let pool = &new_pool(2);
+    let results = std::thread::scope(|scope| {
+        let handles: std::vec::Vec<_> = (0..3)
+            .map(|n| scope.spawn(move || pool.run([("triple", [Value::I32(n)])])))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<std::vec::Vec<_>>()
+    });
+
+    assert_eq!(results[2], std::vec![Ok(Some(Value::I32(6)))]);
+}
+
+#[test]
+fn test_restore_only_copies_written_pages() {
+    let arena = Bump::new();
+    let bytes = pool_module_bytes();
+    let mut instance =
+        Instance::from_bytes(&arena, &bytes, DefaultImportDispatcher::default(), false).unwrap();
+    instance.grow_memory(1).unwrap();
+    let image = instance.memory_image();
+
+    // The first restore copies everything, and starts keeping track of writes
+    instance.restore_image(&image);
+
+    // Writes from outside the module aren't tracked, so the restore leaves this one alone. It's
+    // on a different page from the one that `bump` writes to.
+    let untracked_addr = MemorySection::PAGE_SIZE as usize + 8;
+    instance.memory[untracked_addr] = 42;
+
+    assert_eq!(instance.call_export("bump", []), Ok(Some(Value::I32(1))));
+    assert_eq!(instance.call_export("grow", []), Ok(Some(Value::I32(2))));
+    instance.restore_image(&image);
+
+    assert_eq!(instance.memory.len(), image.memory.len());
+    assert_eq!(instance.memory[0..4], [0; 4]);
+    assert_eq!(instance.memory[untracked_addr], 42);
+    assert_eq!(instance.call_export("bump", []), Ok(Some(Value::I32(1))));
+}
@@ -0,0 +1,189 @@
+use super::{const_value, create_exported_function_no_locals};
+use crate::{
+    ImportDispatcher, ImportLog, Instance, Memory, MemoryWrite, RecordingDispatcher,
+    ReplayDispatcher,
+};
+use bumpalo::Bump;
+use roc_wasm_module::sections::{Import, ImportDesc};
+use roc_wasm_module::{opcodes::OpCode, SerialBuffer, Signature, Value, ValueType, WasmModule};
+
+/// A host that writes its arguments into memory and returns their sum
+struct TestHost;
+
+impl ImportDispatcher for TestHost {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut Memory<'_>,
+    ) -> Option<Value> {
+        assert_eq!((module_name, function_name), ("env", "sum"));
+        let a = arguments[0].expect_i32().unwrap();
+        let b = arguments[1].expect_i32().unwrap();
+        memory.write_i32(4, a).unwrap();
+        memory.write_i32(12, b).unwrap();
+        Some(Value::I32(a + b))
+    }
+}
+
+#[test]
+fn test_record_and_replay() {
+    let arguments = [Value::I32(0x123), Value::I32(-1)];
+    let mut recorder = RecordingDispatcher::new(TestHost);
+    let mut recorded_memory = vec![0; 32];
+
+    let recorded_result = recorder.dispatch(
+        "env",
+        "sum",
+        &arguments,
+        &mut Memory::new(&mut recorded_memory),
+    );
+    assert_eq!(recorded_result, Some(Value::I32(0x122)));
+
+    let text = recorder.log.to_string();
+    let log = ImportLog::parse(&text).unwrap();
+    assert_eq!(log, recorder.log);
+    assert_eq!(log.calls[0].memory_writes.len(), 2);
+
+    let mut replayer = ReplayDispatcher::new(log);
+    let mut replayed_memory = vec![0; 32];
+    let replayed_result = replayer.dispatch(
+        "env",
+        "sum",
+        &arguments,
+        &mut Memory::new(&mut replayed_memory),
+    );
+
+    assert_eq!(replayed_result, recorded_result);
+    assert_eq!(replayed_memory, recorded_memory);
+    assert_eq!(replayer.remaining_calls(), 0);
+}
+
+#[test]
+fn test_log_floats_round_trip() {
+    let text = "call env f\narg f32 0x7fc00001\narg f64 0x3ff0000000000000\nreturn f64 0x8000000000000000\n";
+    let log = ImportLog::parse(text).unwrap();
+
+    assert_eq!(log.calls[0].arguments[1], Value::F64(1.0));
+    assert_eq!(log.to_string(), text);
+}
+
+#[test]
+fn test_replay_diverged() {
+    let log = ImportLog::parse("call env sum\narg i32 1\narg i32 2\nreturn i32 3\n").unwrap();
+    let mut replayer = ReplayDispatcher::new(log);
+
+    let result = replayer.dispatch(
+        "env",
+        "sum",
+        &[Value::I32(1), Value::I32(5)],
+        &mut Memory::new(&mut [0; 8]),
+    );
+
+    assert_eq!(result, None);
+    let error = replayer.host_error().unwrap();
+    assert!(
+        error.starts_with("Replay diverged from the log at call #0"),
+        "{}",
+        error
+    );
+    assert_eq!(replayer.remaining_calls(), 1);
+}
+
+#[test]
+fn test_replay_out_of_bounds_write() {
+    let log = ImportLog::parse("call env sum\nwrite 6 01020304\nreturn i32 3\n").unwrap();
+    let mut replayer = ReplayDispatcher::new(log);
+
+    let result = replayer.dispatch("env", "sum", &[], &mut Memory::new(&mut [0; 8]));
+
+    assert_eq!(result, None);
+    let error = replayer.host_error().unwrap();
+    assert!(error.contains("memory writes of call #0"), "{}", error);
+}
+
+#[test]
+fn test_replay_ran_out_of_calls() {
+    let mut replayer = ReplayDispatcher::new(ImportLog::default());
+
+    replayer.dispatch("env", "sum", &[], &mut Memory::new(&mut []));
+
+    let error = replayer.host_error().unwrap();
+    assert!(
+        error.starts_with("Replay ran out of logged calls"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_record_only_written_ranges() {
+    let mut recorder = RecordingDispatcher::new(TestHost);
+    let mut memory = vec![0; 1 << 16];
+
+    recorder.dispatch(
+        "env",
+        "sum",
+        &[Value::I32(1), Value::I32(2)],
+        &mut Memory::new(&mut memory),
+    );
+
+    assert_eq!(
+        recorder.log.calls[0].memory_writes,
+        [
+            MemoryWrite {
+                offset: 4,
+                bytes: vec![1, 0, 0, 0]
+            },
+            MemoryWrite {
+                offset: 12,
+                bytes: vec![2, 0, 0, 0]
+            },
+        ]
+    );
+}
+
+/// A module that imports `env.sum` and exports `run() = sum(1, 5)`
+fn sum_module_bytes() -> std::vec::Vec<u8> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature_index = module.types.insert(Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32, ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    });
+    module.import.imports.push(Import {
+        module: "env",
+        name: "sum",
+        description: ImportDesc::Func { signature_index },
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "run", signature, |buf| {
+        const_value(buf, Value::I32(1));
+        const_value(buf, Value::I32(5));
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    bytes
+}
+
+#[test]
+fn test_replay_divergence_ends_the_call() {
+    let arena = Bump::new();
+    let bytes = sum_module_bytes();
+    let log = ImportLog::parse("call env sum\narg i32 1\narg i32 2\nreturn i32 3\n").unwrap();
+
+    let mut inst = Instance::from_bytes(&arena, &bytes, ReplayDispatcher::new(log), false).unwrap();
+    let error = inst.call_export("run", []).unwrap_err().to_string();
+
+    assert!(error.contains("Replay diverged"), "{}", error);
+}
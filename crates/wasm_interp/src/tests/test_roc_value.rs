@@ -0,0 +1,146 @@
+use super::write_roc_header;
+use crate::{Memory, MemoryError, RocLayout, RocValue};
+
+fn write_small_str(memory: &mut Memory, addr: u32, s: &str) {
+    memory.write_bytes(addr, s.as_bytes()).unwrap();
+    memory.write_u8(addr + 11, 0x80 | s.len() as u8).unwrap();
+}
+
+#[test]
+fn test_parse_layout() {
+    assert_eq!("I64".parse(), Ok(RocLayout::I64));
+    assert_eq!("{}".parse(), Ok(RocLayout::Unit));
+    assert_eq!(
+        "Result (List Str) {}".parse(),
+        Ok(RocLayout::result(
+            RocLayout::list(RocLayout::Str),
+            RocLayout::Unit
+        ))
+    );
+    assert_eq!(
+        "List (Result U8 (List I16))".parse(),
+        Ok(RocLayout::list(RocLayout::result(
+            RocLayout::U8,
+            RocLayout::list(RocLayout::I16)
+        )))
+    );
+
+    assert!("List".parse::<RocLayout>().is_err());
+    assert!("List List Str".parse::<RocLayout>().is_err());
+    assert!("(Str".parse::<RocLayout>().is_err());
+    assert!("Str Str".parse::<RocLayout>().is_err());
+    assert!("Dict Str Str".parse::<RocLayout>().is_err());
+}
+
+#[test]
+fn test_layout_size() {
+    let result = |ok, err| RocLayout::result(ok, err);
+
+    assert_eq!(RocLayout::list(RocLayout::U8).size(), 12);
+    // The tag id goes after the biggest payload, and the whole union is padded to its alignment
+    assert_eq!(result(RocLayout::Str, RocLayout::Unit).size(), 16);
+    assert_eq!(result(RocLayout::I64, RocLayout::U8).size(), 16);
+    assert_eq!(result(RocLayout::U16, RocLayout::U8).size(), 4);
+    assert_eq!(result(RocLayout::Unit, RocLayout::Unit).size(), 1);
+}
+
+#[test]
+fn test_read_list_of_str() {
+    let mut bytes = [0; 128];
+    let mut memory = Memory::new(&mut bytes);
+
+    write_small_str(&mut memory, 32, "short");
+    memory
+        .write_bytes(96, b"a string too big to be small")
+        .unwrap();
+    write_roc_header(&mut memory, 44, 96, 28);
+    write_small_str(&mut memory, 56, "a\"b\nc");
+    write_roc_header(&mut memory, 0, 32, 2);
+
+    let layout = RocLayout::list(RocLayout::Str);
+    let value = memory.read_roc_value(0, &layout).unwrap();
+
+    assert_eq!(
+        value,
+        RocValue::List(vec![
+            RocValue::Str("short".to_string()),
+            RocValue::Str("a string too big to be small".to_string()),
+        ])
+    );
+    assert_eq!(
+        value.to_string(),
+        r#"["short", "a string too big to be small"]"#
+    );
+
+    write_roc_header(&mut memory, 0, 32, 3);
+    let value = memory.read_roc_value(0, &layout).unwrap();
+    assert!(value.to_string().ends_with(r#", "a\"b\nc"]"#), "{}", value);
+}
+
+#[test]
+fn test_read_result() {
+    let mut bytes = [0; 64];
+    let mut memory = Memory::new(&mut bytes);
+    let layout: RocLayout = "Result I64 Str".parse().unwrap();
+
+    memory.write_i64(0, -42).unwrap();
+    memory.write_u8(12, 1).unwrap();
+    assert_eq!(
+        memory.read_roc_value(0, &layout).unwrap().to_string(),
+        "Ok -42"
+    );
+
+    write_small_str(&mut memory, 0, "oops");
+    memory.write_u8(12, 0).unwrap();
+    assert_eq!(
+        memory.read_roc_value(0, &layout).unwrap().to_string(),
+        r#"Err "oops""#
+    );
+
+    memory.write_u8(12, 2).unwrap();
+    assert_eq!(
+        memory.read_roc_value(0, &layout),
+        Err(MemoryError::InvalidTagId {
+            addr: 12,
+            tag_id: 2
+        })
+    );
+}
+
+#[test]
+fn test_read_nested() {
+    let mut bytes = [0; 96];
+    let mut memory = Memory::new(&mut bytes);
+    let layout: RocLayout = "List (Result (Result F64 {}) Bool)".parse().unwrap();
+
+    // The inner result is 16 bytes, with its tag id at 8. The outer one is 24 bytes, with its
+    // tag id at 16.
+    memory.write_f64(32, 1.5).unwrap();
+    memory.write_u8(40, 1).unwrap();
+    memory.write_u8(48, 1).unwrap();
+    memory.write_u8(56, 1).unwrap();
+    memory.write_u8(72, 0).unwrap();
+    write_roc_header(&mut memory, 0, 32, 2);
+
+    assert_eq!(
+        memory.read_roc_value(0, &layout).unwrap().to_string(),
+        "[Ok (Ok 1.5), Err Bool.true]"
+    );
+}
+
+#[test]
+fn test_read_list_out_of_bounds() {
+    let mut bytes = [0; 32];
+    let mut memory = Memory::new(&mut bytes);
+
+    write_roc_header(&mut memory, 0, 16, 5);
+
+    assert_eq!(
+        memory.read_roc_value(0, &RocLayout::list(RocLayout::U32)),
+        Err(MemoryError::OutOfBounds {
+            addr: 16,
+            len: 20,
+            memory_size: 32
+        })
+    );
+}
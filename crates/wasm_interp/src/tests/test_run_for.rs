@@ -0,0 +1,102 @@
+use super::count_down_module;
+use crate::{CallError, DefaultImportDispatcher, Instance, RunResult};
+use bumpalo::Bump;
+use roc_wasm_module::Value;
+
+#[test]
+fn test_run_for_yields_and_finishes() {
+    let arena = Bump::new();
+    let module = count_down_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    inst.begin_call("count_down", [Value::I32(100)]).unwrap();
+    assert!(inst.is_call_pending());
+
+    let mut slices = 0;
+    let result = loop {
+        match inst.run_for(50).unwrap() {
+            RunResult::Yielded(progress) => {
+                slices += 1;
+                assert_eq!(progress.instructions_executed, slices * 50);
+                assert_eq!(progress.fn_index, 0);
+                assert_eq!(progress.call_depth, 1);
+            }
+            RunResult::Finished(value) => break value,
+        }
+    };
+
+    // 5 instructions per iteration, so it can't fit in fewer slices than this
+    assert_eq!(slices, 10);
+    assert_eq!(result, Some(Value::I32(42)));
+    assert!(!inst.is_call_pending());
+    assert!(inst.run_for(50).is_err());
+
+    // The stack is back to how it was, so a normal call works as usual
+    assert_eq!(
+        inst.call_export("count_down", [Value::I32(3)]),
+        Ok(Some(Value::I32(42)))
+    );
+}
+
+#[test]
+fn test_run_for_cancel() {
+    let arena = Bump::new();
+    let module = count_down_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    inst.begin_call("count_down", [Value::I32(1_000_000)])
+        .unwrap();
+    assert!(matches!(inst.run_for(10), Ok(RunResult::Yielded(_))));
+
+    inst.cancel_call();
+    assert!(!inst.is_call_pending());
+    assert!(inst.run_for(10).is_err());
+
+    // Starting a new call also abandons an unfinished one
+    inst.begin_call("count_down", [Value::I32(1_000_000)])
+        .unwrap();
+    assert!(matches!(inst.run_for(10), Ok(RunResult::Yielded(_))));
+    assert_eq!(
+        inst.call_export("count_down", [Value::I32(2)]),
+        Ok(Some(Value::I32(42)))
+    );
+    assert!(!inst.is_call_pending());
+}
+
+#[test]
+fn test_run_for_type_mismatch() {
+    let arena = Bump::new();
+    let module = count_down_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert!(inst.begin_call("count_down", [Value::I64(1)]).is_err());
+    assert!(!inst.is_call_pending());
+}
+
+#[test]
+fn test_wrong_number_of_args() {
+    let arena = Bump::new();
+    let module = count_down_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.call_export("count_down", []),
+        Err(CallError::InvalidCall(
+            "count_down takes 1 arguments, but was called with 0".to_string()
+        ))
+    );
+    assert!(inst
+        .begin_call("count_down", [Value::I32(1), Value::I32(2)])
+        .is_err());
+    assert!(!inst.is_call_pending());
+
+    // Nothing was left on the stack, so a good call still works
+    assert_eq!(
+        inst.call_export("count_down", [Value::I32(1)]),
+        Ok(Some(Value::I32(42)))
+    );
+}
@@ -0,0 +1,126 @@
+use super::create_exported_function_no_locals;
+use crate::{run_wasi, wasi, ExitStatus};
+use bumpalo::Bump;
+use roc_wasm_module::sections::{Import, ImportDesc, MemorySection};
+use roc_wasm_module::{opcodes::OpCode, SerialBuffer, Signature, ValueType, WasmModule};
+
+/// A WASI program whose `_start` runs `write_instructions`, with `proc_exit` as function 0
+/// and `args_sizes_get` and `environ_sizes_get` as functions 1 and 2
+fn wasi_program_bytes<F>(write_instructions: F) -> std::vec::Vec<u8>
+where
+    F: FnOnce(&mut bumpalo::collections::Vec<'_, u8>),
+{
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+
+    let exit_signature = module.types.insert(Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32],
+        ret_type: None,
+    });
+    let sizes_signature = module.types.insert(Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32, ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    });
+    for (name, signature_index) in [
+        ("proc_exit", exit_signature),
+        ("args_sizes_get", sizes_signature),
+        ("environ_sizes_get", sizes_signature),
+    ] {
+        module.import.imports.push(Import {
+            module: wasi::MODULE_NAME,
+            name,
+            description: ImportDesc::Func { signature_index },
+        });
+    }
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "_start", signature, write_instructions);
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    bytes
+}
+
+/// Exit with the count written by `args_sizes_get` or `environ_sizes_get`
+fn exit_with_count(buf: &mut bumpalo::collections::Vec<'_, u8>, sizes_get_index: u32) {
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(0);
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(4);
+    buf.push(OpCode::CALL as u8);
+    buf.encode_u32(sizes_get_index);
+    buf.push(OpCode::DROP as u8);
+
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(0);
+    buf.push(OpCode::I32LOAD as u8);
+    buf.encode_u32(2); // alignment
+    buf.encode_u32(0); // offset
+    buf.push(OpCode::CALL as u8);
+    buf.encode_u32(0);
+    buf.push(OpCode::UNREACHABLE as u8);
+    buf.push(OpCode::END as u8);
+}
+
+#[test]
+fn test_run_wasi_returns_from_start() {
+    let bytes = wasi_program_bytes(|buf| buf.push(OpCode::END as u8));
+
+    let status = run_wasi(&bytes, &[b"app"], &[], &[]).unwrap();
+
+    assert_eq!(status, ExitStatus(0));
+    assert!(status.success());
+}
+
+#[test]
+fn test_run_wasi_proc_exit() {
+    let bytes = wasi_program_bytes(|buf| {
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(42);
+        buf.push(OpCode::CALL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::UNREACHABLE as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let status = run_wasi(&bytes, &[b"app"], &[], &[]).unwrap();
+
+    assert_eq!(status, ExitStatus(42));
+    assert!(!status.success());
+}
+
+#[test]
+fn test_run_wasi_args() {
+    let bytes = wasi_program_bytes(|buf| exit_with_count(buf, 1));
+
+    let status = run_wasi(&bytes, &[b"app", b"one", b"two"], &[], &[]).unwrap();
+
+    assert_eq!(status, ExitStatus(3));
+}
+
+#[test]
+fn test_run_wasi_env() {
+    let bytes = wasi_program_bytes(|buf| exit_with_count(buf, 2));
+    let env = [("HOME", "/home/roc"), ("LANG", "C"), ("HOME", "/root")];
+
+    let status = run_wasi(&bytes, &[b"app"], &env, &[]).unwrap();
+
+    // Setting a variable again replaces it
+    assert_eq!(status, ExitStatus(2));
+}
+
+#[test]
+fn test_run_wasi_trap() {
+    let bytes = wasi_program_bytes(|buf| {
+        buf.push(OpCode::UNREACHABLE as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let error = run_wasi(&bytes, &[b"app"], &[], &[]).unwrap_err();
+
+    assert!(error.contains("unreachable"), "{}", error);
+}
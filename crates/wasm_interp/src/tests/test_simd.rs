@@ -0,0 +1,713 @@
+#![cfg(test)]
+
+//! Test cases adapted from the SIMD proposal's spec tests
+//! https://github.com/WebAssembly/simd/tree/main/test/core/simd
+
+use super::{const_value, create_exported_function_no_locals};
+use crate::{DefaultImportDispatcher, Instance};
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::opcodes::{OpCode, SimdOp, SimdOp::*};
+use roc_wasm_module::sections::MemorySection;
+use roc_wasm_module::{SerialBuffer, Signature, Value, ValueType, WasmModule};
+
+fn lanes(lane_bits: usize, lanes: impl DoubleEndedIterator<Item = u128>) -> Value {
+    Value::V128(lanes.rev().fold(0, |v, lane| (v << lane_bits) | lane))
+}
+
+fn i8x16(x: [i8; 16]) -> Value {
+    lanes(8, x.iter().map(|lane| *lane as u8 as u128))
+}
+
+fn i16x8(x: [i16; 8]) -> Value {
+    lanes(16, x.iter().map(|lane| *lane as u16 as u128))
+}
+
+fn i32x4(x: [i32; 4]) -> Value {
+    lanes(32, x.iter().map(|lane| *lane as u32 as u128))
+}
+
+fn i64x2(x: [i64; 2]) -> Value {
+    lanes(64, x.iter().map(|lane| *lane as u64 as u128))
+}
+
+fn f32x4(x: [f32; 4]) -> Value {
+    lanes(32, x.iter().map(|lane| lane.to_bits() as u128))
+}
+
+fn f64x2(x: [f64; 2]) -> Value {
+    lanes(64, x.iter().map(|lane| lane.to_bits() as u128))
+}
+
+fn simd_op(buf: &mut Vec<'_, u8>, op: SimdOp) {
+    buf.push(OpCode::SIMDPREFIX as u8);
+    buf.encode_u32(op as u32);
+}
+
+/// A module with one page of memory, exporting a function called "test" with no arguments
+fn simd_module<'a, F>(arena: &'a Bump, ret_type: ValueType, write_instructions: F) -> WasmModule<'a>
+where
+    F: FnOnce(&mut Vec<'a, u8>),
+{
+    let mut module = WasmModule::new(arena);
+    module.memory = MemorySection::new(arena, MemorySection::PAGE_SIZE);
+    let signature = Signature {
+        param_types: Vec::new_in(arena),
+        ret_type: Some(ret_type),
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        write_instructions(buf);
+        buf.push(OpCode::END as u8);
+    });
+    module
+}
+
+fn run_simd_module(module: &WasmModule) -> Result<Option<Value>, String> {
+    let arena = Bump::new();
+    let mut inst =
+        Instance::for_module(&arena, module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.call_export("test", []).map_err(String::from)
+}
+
+fn test_simd_example<A>(op: SimdOp, immediates: &[u8], args: A, expected: Value)
+where
+    A: IntoIterator<Item = Value>,
+{
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::from(expected), |buf| {
+        for arg in args {
+            const_value(buf, arg);
+        }
+        simd_op(buf, op);
+        buf.extend_from_slice(immediates);
+    });
+
+    let result = run_simd_module(&module).unwrap().unwrap();
+
+    assert_eq!(result, expected, "{:?}", op);
+}
+
+fn test_simd_unop(op: SimdOp, arg: Value, expected: Value) {
+    test_simd_example(op, &[], [arg], expected)
+}
+
+fn test_simd_binop(op: SimdOp, arg1: Value, arg2: Value, expected: Value) {
+    test_simd_example(op, &[], [arg1, arg2], expected)
+}
+
+#[test]
+fn test_v128_const_and_extract_lane() {
+    let v = i32x4([1, -2, 3, -4]);
+    test_simd_example(I32X4EXTRACTLANE, &[0], [v], Value::I32(1));
+    test_simd_example(I32X4EXTRACTLANE, &[3], [v], Value::I32(-4));
+    test_simd_example(I64X2EXTRACTLANE, &[1], [i64x2([5, -6])], Value::I64(-6));
+    test_simd_example(
+        F64X2EXTRACTLANE,
+        &[1],
+        [f64x2([1.5, -2.5])],
+        Value::F64(-2.5),
+    );
+}
+
+#[test]
+fn test_splat_and_extract_lane_sign() {
+    let arg = [Value::I32(0xff)];
+    test_simd_example(I8X16SPLAT, &[], arg, i8x16([-1; 16]));
+
+    let v = i8x16([-1; 16]);
+    test_simd_example(I8X16EXTRACTLANES, &[15], [v], Value::I32(-1));
+    test_simd_example(I8X16EXTRACTLANEU, &[15], [v], Value::I32(255));
+
+    let v = i16x8([-1; 8]);
+    test_simd_example(I16X8EXTRACTLANES, &[7], [v], Value::I32(-1));
+    test_simd_example(I16X8EXTRACTLANEU, &[7], [v], Value::I32(65535));
+
+    test_simd_example(F32X4SPLAT, &[], [Value::F32(-1.5)], f32x4([-1.5; 4]));
+}
+
+#[test]
+fn test_replace_lane() {
+    test_simd_example(
+        I16X8REPLACELANE,
+        &[2],
+        [i16x8([0; 8]), Value::I32(0x12345)],
+        i16x8([0, 0, 0x2345, 0, 0, 0, 0, 0]),
+    );
+    test_simd_example(
+        F32X4REPLACELANE,
+        &[3],
+        [f32x4([1.0; 4]), Value::F32(-0.0)],
+        f32x4([1.0, 1.0, 1.0, -0.0]),
+    );
+}
+
+#[test]
+fn test_i8x16_arith() {
+    let a = i8x16([127, -128, 1, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let b = i8x16([1, -1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    let add = i8x16([-128, 127, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16ADD, a, b, add);
+
+    let add_sat_s = i8x16([127, -128, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16ADDSATS, a, b, add_sat_s);
+
+    // As unsigned: 127+1, 128+255, 1+1, 255+1
+    let add_sat_u = i8x16([-128, -1, 2, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16ADDSATU, a, b, add_sat_u);
+
+    let sub_sat_u = i8x16([126, 0, 0, -2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16SUBSATU, a, b, sub_sat_u);
+
+    let neg = i8x16([-127, -128, -1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_unop(I8X16NEG, a, neg);
+
+    let abs = i8x16([127, -128, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_unop(I8X16ABS, a, abs);
+
+    let avgr_u = i8x16([64, -64, 1, -128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16AVGRU, a, b, avgr_u);
+
+    let popcnt = i8x16([7, 1, 1, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_unop(I8X16POPCNT, a, popcnt);
+}
+
+#[test]
+fn test_min_max_signedness() {
+    let a = i8x16([-1; 16]);
+    let b = i8x16([1; 16]);
+    test_simd_binop(I8X16MINS, a, b, a);
+    test_simd_binop(I8X16MINU, a, b, b);
+    test_simd_binop(I8X16MAXS, a, b, b);
+    test_simd_binop(I8X16MAXU, a, b, a);
+
+    let a = i32x4([-1, 2, i32::MIN, 0]);
+    let b = i32x4([1, -2, i32::MAX, 0]);
+    test_simd_binop(I32X4MINS, a, b, i32x4([-1, -2, i32::MIN, 0]));
+    test_simd_binop(I32X4MAXU, a, b, i32x4([-1, -2, i32::MIN, 0]));
+}
+
+#[test]
+fn test_wrapping_mul() {
+    test_simd_binop(
+        I16X8MUL,
+        i16x8([0x100, -1, 3, 0, 0, 0, 0, 0]),
+        i16x8([0x100, -1, -3, 0, 0, 0, 0, 0]),
+        i16x8([0, 1, -9, 0, 0, 0, 0, 0]),
+    );
+    test_simd_binop(
+        I32X4MUL,
+        i32x4([0x10000, -1, 3, 0]),
+        i32x4([0x10000, -1, -3, 0]),
+        i32x4([0, 1, -9, 0]),
+    );
+    test_simd_binop(
+        I64X2MUL,
+        i64x2([i64::MAX, -2]),
+        i64x2([2, 3]),
+        i64x2([-2, -6]),
+    );
+}
+
+#[test]
+fn test_shifts() {
+    let v = i32x4([1, -2, i32::MIN, 0x7fff_ffff]);
+
+    // The shift count wraps around at the lane width
+    test_simd_example(I32X4SHL, &[], [v, Value::I32(33)], i32x4([2, -4, 0, -2]));
+    test_simd_example(
+        I32X4SHRS,
+        &[],
+        [v, Value::I32(1)],
+        i32x4([0, -1, -0x4000_0000, 0x3fff_ffff]),
+    );
+    test_simd_example(
+        I32X4SHRU,
+        &[],
+        [v, Value::I32(1)],
+        i32x4([0, 0x7fff_ffff, 0x4000_0000, 0x3fff_ffff]),
+    );
+
+    let v = i8x16([-128; 16]);
+    test_simd_example(I8X16SHRS, &[], [v, Value::I32(9)], i8x16([-64; 16]));
+    test_simd_example(I8X16SHRU, &[], [v, Value::I32(7)], i8x16([1; 16]));
+
+    let v = i64x2([1, -1]);
+    test_simd_example(I64X2SHL, &[], [v, Value::I32(63)], i64x2([i64::MIN; 2]));
+}
+
+#[test]
+fn test_integer_comparisons() {
+    let a = i8x16([-1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let b = i8x16([1, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let lt_s = i8x16([-1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let lt_u = i8x16([0, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16LTS, a, b, lt_s);
+    test_simd_binop(I8X16LTU, a, b, lt_u);
+    test_simd_binop(
+        I8X16EQ,
+        a,
+        b,
+        i8x16([0, 0, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1]),
+    );
+
+    let a = i16x8([-1, 2, 3, 4, 5, 6, 7, 8]);
+    let b = i16x8([1, 2, 3, 4, 5, 6, 7, 9]);
+    test_simd_binop(I16X8GEU, a, b, i16x8([-1, -1, -1, -1, -1, -1, -1, 0]));
+    test_simd_binop(I16X8GES, a, b, i16x8([0, -1, -1, -1, -1, -1, -1, 0]));
+
+    let a = i64x2([-1, 2]);
+    let b = i64x2([1, 2]);
+    test_simd_binop(I64X2LTS, a, b, i64x2([-1, 0]));
+    test_simd_binop(I64X2NE, a, b, i64x2([-1, 0]));
+}
+
+#[test]
+fn test_float_comparisons() {
+    let a = f32x4([0.0, f32::NAN, 1.0, -1.0]);
+    let b = f32x4([-0.0, f32::NAN, 2.0, -1.0]);
+    test_simd_binop(F32X4EQ, a, b, i32x4([-1, 0, 0, -1]));
+    test_simd_binop(F32X4NE, a, b, i32x4([0, -1, -1, 0]));
+    test_simd_binop(F32X4LT, a, b, i32x4([0, 0, -1, 0]));
+    test_simd_binop(F32X4GE, a, b, i32x4([-1, 0, 0, -1]));
+
+    let a = f64x2([f64::NAN, 1.0]);
+    let b = f64x2([1.0, 1.0]);
+    test_simd_binop(F64X2LE, a, b, i64x2([0, -1]));
+}
+
+#[test]
+fn test_bitwise() {
+    let a = i32x4([0b1100, -1, 0, 0x0f0f_0f0f]);
+    let b = i32x4([0b1010, 0, -1, 0x00ff_00ff]);
+    test_simd_binop(V128AND, a, b, i32x4([0b1000, 0, 0, 0x000f_000f]));
+    test_simd_binop(V128OR, a, b, i32x4([0b1110, -1, -1, 0x0fff_0fff]));
+    test_simd_binop(V128XOR, a, b, i32x4([0b0110, -1, -1, 0x0ff0_0ff0]));
+    test_simd_binop(V128ANDNOT, a, b, i32x4([0b0100, -1, 0, 0x0f00_0f00]));
+    test_simd_unop(V128NOT, a, i32x4([!0b1100, 0, -1, !0x0f0f_0f0f]));
+
+    // Take the bits from the first argument where the mask is set, and from the second where it's clear
+    test_simd_example(
+        V128BITSELECT,
+        &[],
+        [
+            i32x4([0xAAAA_AAAAu32 as i32; 4]),
+            i32x4([0xBBBB_BBBBu32 as i32; 4]),
+            i32x4([0x00FF_FF00; 4]),
+        ],
+        i32x4([0xBBAA_AABBu32 as i32; 4]),
+    );
+}
+
+#[test]
+fn test_reductions() {
+    let some = i32x4([0, 0, 1, 0]);
+    let all = i32x4([1, -1, 0x100, i32::MIN]);
+    let none = i32x4([0; 4]);
+
+    test_simd_unop(V128ANYTRUE, some, Value::I32(1));
+    test_simd_unop(V128ANYTRUE, none, Value::I32(0));
+    test_simd_unop(I32X4ALLTRUE, some, Value::I32(0));
+    test_simd_unop(I32X4ALLTRUE, all, Value::I32(1));
+
+    // 0x100 is all true as an i32x4, but not as an i8x16
+    test_simd_unop(I8X16ALLTRUE, all, Value::I32(0));
+
+    test_simd_unop(I32X4BITMASK, all, Value::I32(0b1010));
+    test_simd_unop(
+        I8X16BITMASK,
+        i8x16([-1, 0, -128, 127, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, -1]),
+        Value::I32(0b1000_0000_0000_0101),
+    );
+}
+
+#[test]
+fn test_shuffle_and_swizzle() {
+    let a = i8x16([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+    let b = i8x16([
+        16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+    ]);
+
+    let indices = [31, 0, 30, 1, 29, 2, 28, 3, 27, 4, 26, 5, 25, 6, 24, 7];
+    let shuffled = i8x16([31, 0, 30, 1, 29, 2, 28, 3, 27, 4, 26, 5, 25, 6, 24, 7]);
+    test_simd_example(I8X16SHUFFLE, &indices, [a, b], shuffled);
+
+    // Out of range indices select zero
+    let s = i8x16([15, 16, -1, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 100]);
+    let swizzled = i8x16([15, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 0]);
+    test_simd_binop(I8X16SWIZZLE, a, s, swizzled);
+}
+
+#[test]
+fn test_float_arith() {
+    let a = f32x4([1.5, -2.0, 0.25, 9.0]);
+    let b = f32x4([0.5, 4.0, 0.25, -3.0]);
+    test_simd_binop(F32X4ADD, a, b, f32x4([2.0, 2.0, 0.5, 6.0]));
+    test_simd_binop(F32X4SUB, a, b, f32x4([1.0, -6.0, 0.0, 12.0]));
+    test_simd_binop(F32X4MUL, a, b, f32x4([0.75, -8.0, 0.0625, -27.0]));
+    test_simd_binop(F32X4DIV, a, b, f32x4([3.0, -0.5, 1.0, -3.0]));
+    test_simd_unop(F32X4NEG, a, f32x4([-1.5, 2.0, -0.25, -9.0]));
+    test_simd_unop(F32X4ABS, a, f32x4([1.5, 2.0, 0.25, 9.0]));
+
+    test_simd_unop(F64X2SQRT, f64x2([4.0, 2.25]), f64x2([2.0, 1.5]));
+}
+
+#[test]
+fn test_float_rounding() {
+    let v = f32x4([2.5, -0.5, 3.5, -1.7]);
+    test_simd_unop(F32X4NEAREST, v, f32x4([2.0, -0.0, 4.0, -2.0]));
+    test_simd_unop(F32X4CEIL, v, f32x4([3.0, -0.0, 4.0, -1.0]));
+    test_simd_unop(F32X4FLOOR, v, f32x4([2.0, -1.0, 3.0, -2.0]));
+    test_simd_unop(F32X4TRUNC, v, f32x4([2.0, -0.0, 3.0, -1.0]));
+
+    test_simd_unop(F64X2NEAREST, f64x2([0.5, -4.5]), f64x2([0.0, -4.0]));
+}
+
+#[test]
+fn test_float_min_max() {
+    let a = f32x4([0.0, -0.0, f32::NAN, 1.0]);
+    let b = f32x4([-0.0, 0.0, 1.0, 2.0]);
+
+    let min = run_binop_f32x4(F32X4MIN, a, b);
+    assert_eq!(min[0].to_bits(), (-0.0f32).to_bits());
+    assert_eq!(min[1].to_bits(), (-0.0f32).to_bits());
+    assert!(min[2].is_nan());
+    assert_eq!(min[3], 1.0);
+
+    let max = run_binop_f32x4(F32X4MAX, a, b);
+    assert_eq!(max[0].to_bits(), 0.0f32.to_bits());
+    assert_eq!(max[1].to_bits(), 0.0f32.to_bits());
+    assert!(max[2].is_nan());
+    assert_eq!(max[3], 2.0);
+
+    // The pseudo-min and pseudo-max are `b < a ? b : a` and `a < b ? b : a`
+    let pmin = run_binop_f32x4(F32X4PMIN, a, b);
+    assert_eq!(pmin[0].to_bits(), 0.0f32.to_bits());
+    assert!(pmin[2].is_nan());
+
+    let pmax = run_binop_f32x4(F32X4PMAX, a, b);
+    assert_eq!(pmax[1].to_bits(), (-0.0f32).to_bits());
+    assert!(pmax[2].is_nan());
+    assert_eq!(pmax[3], 2.0);
+}
+
+/// NaN isn't equal to itself, so the lanes need to be checked separately
+fn run_binop_f32x4(op: SimdOp, a: Value, b: Value) -> [f32; 4] {
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::V128, |buf| {
+        const_value(buf, a);
+        const_value(buf, b);
+        simd_op(buf, op);
+    });
+    let result = run_simd_module(&module).unwrap().unwrap();
+    let bits = result.expect_v128().unwrap();
+    std::array::from_fn(|i| f32::from_bits((bits >> (32 * i)) as u32))
+}
+
+/// Store `value` at address 16, then run `write_load` to load something back
+fn test_load_example<F>(value: Value, write_load: F, expected: Value)
+where
+    F: FnOnce(&mut Vec<'_, u8>),
+{
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::from(expected), |buf| {
+        const_value(buf, Value::I32(16));
+        const_value(buf, value);
+        simd_op(buf, V128STORE);
+        buf.extend_from_slice(&[4, 0]); // align, offset
+        write_load(buf);
+    });
+
+    let result = run_simd_module(&module).unwrap().unwrap();
+
+    assert_eq!(result, expected);
+}
+
+fn load(buf: &mut Vec<'_, u8>, op: SimdOp, offset: u8) {
+    const_value(buf, Value::I32(16));
+    simd_op(buf, op);
+    buf.extend_from_slice(&[0, offset]);
+}
+
+#[test]
+fn test_v128_load_store() {
+    let v = i32x4([1, 2, 3, -4]);
+    test_load_example(v, |buf| load(buf, V128LOAD, 0), v);
+
+    // The first byte after the stored vector is zero
+    test_load_example(v, |buf| load(buf, V128LOAD, 4), i32x4([2, 3, -4, 0]));
+}
+
+#[test]
+fn test_v128_load_extend() {
+    let v = i8x16([-1, 2, -3, 4, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_load_example(
+        v,
+        |buf| load(buf, V128LOAD8X8S, 0),
+        i16x8([-1, 2, -3, 4, 5, 6, 7, 8]),
+    );
+    test_load_example(
+        v,
+        |buf| load(buf, V128LOAD8X8U, 0),
+        i16x8([255, 2, 253, 4, 5, 6, 7, 8]),
+    );
+
+    let v = i32x4([-1, 7, 0, 0]);
+    test_load_example(v, |buf| load(buf, V128LOAD32X2S, 0), i64x2([-1, 7]));
+    test_load_example(
+        v,
+        |buf| load(buf, V128LOAD32X2U, 0),
+        i64x2([0xffff_ffff, 7]),
+    );
+}
+
+#[test]
+fn test_v128_load_splat_and_zero() {
+    let v = i32x4([0x0403_0201, 0x0807_0605, 0, 0]);
+    test_load_example(v, |buf| load(buf, V128LOAD8SPLAT, 1), i8x16([2; 16]));
+    test_load_example(
+        v,
+        |buf| load(buf, V128LOAD32SPLAT, 4),
+        i32x4([0x0807_0605; 4]),
+    );
+    test_load_example(
+        v,
+        |buf| load(buf, V128LOAD32ZERO, 4),
+        i32x4([0x0807_0605, 0, 0, 0]),
+    );
+    test_load_example(
+        v,
+        |buf| load(buf, V128LOAD64ZERO, 0),
+        i32x4([0x0403_0201, 0x0807_0605, 0, 0]),
+    );
+}
+
+#[test]
+fn test_v128_load_store_lane() {
+    let v = i16x8([1, 2, 3, 4, 5, 6, 7, 8]);
+
+    // Load the i16 at address 20 into lane 7 of a zero vector
+    test_load_example(
+        v,
+        |buf| {
+            const_value(buf, Value::I32(20));
+            const_value(buf, i16x8([0; 8]));
+            simd_op(buf, V128LOAD16LANE);
+            buf.extend_from_slice(&[1, 0, 7]); // align, offset, lane
+        },
+        i16x8([0, 0, 0, 0, 0, 0, 0, 3]),
+    );
+
+    // Store lane 1 of an i32x4 over the first 4 bytes, then load the whole vector
+    test_load_example(
+        v,
+        |buf| {
+            const_value(buf, Value::I32(16));
+            const_value(buf, i32x4([-1, -2, -3, -4]));
+            simd_op(buf, V128STORE32LANE);
+            buf.extend_from_slice(&[2, 0, 1]);
+            load(buf, V128LOAD, 0);
+        },
+        i16x8([-2, -1, 3, 4, 5, 6, 7, 8]),
+    );
+}
+
+#[test]
+fn test_v128_load_out_of_bounds() {
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::V128, |buf| {
+        const_value(buf, Value::I32(MemorySection::PAGE_SIZE as i32 - 8));
+        simd_op(buf, V128LOAD);
+        buf.extend_from_slice(&[0, 0]);
+    });
+
+    let error = run_simd_module(&module).unwrap_err();
+
+    assert!(error.contains("out of bounds"), "{}", error);
+}
+
+#[test]
+fn test_unknown_op() {
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::V128, |buf| {
+        const_value(buf, i16x8([0; 8]));
+        buf.push(OpCode::SIMDPREFIX as u8);
+        buf.encode_u32(0x200);
+    });
+
+    let error = run_simd_module(&module).unwrap_err();
+
+    assert!(error.contains("0xfd 0x200"), "{}", error);
+    assert!(error.contains("not an instruction I know"), "{}", error);
+}
+
+#[test]
+fn test_invalid_lane() {
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::V128, |buf| {
+        const_value(buf, i8x16([0; 16]));
+        const_value(buf, i8x16([0; 16]));
+        simd_op(buf, I8X16SHUFFLE);
+        buf.extend_from_slice(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 32]);
+    });
+
+    let error = run_simd_module(&module).unwrap_err();
+
+    assert!(error.contains("for lane 32"), "{}", error);
+    assert!(error.contains("only have 32 lanes"), "{}", error);
+
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::I32, |buf| {
+        const_value(buf, i32x4([0; 4]));
+        simd_op(buf, I32X4EXTRACTLANE);
+        buf.push(4);
+    });
+
+    let error = run_simd_module(&module).unwrap_err();
+
+    assert!(error.contains("for lane 4"), "{}", error);
+    assert!(error.contains("only have 4 lanes"), "{}", error);
+}
+
+#[test]
+fn test_narrow() {
+    let a = i16x8([300, -300, 127, -128, 1, -1, 0, 0]);
+    let b = i16x8([0; 8]);
+    let narrow_s = i8x16([127, -128, 127, -128, 1, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16NARROWI16X8S, a, b, narrow_s);
+    // As unsigned: 255, 0, 127, 0, 1, 0
+    let narrow_u = i8x16([-1, 0, 127, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    test_simd_binop(I8X16NARROWI16X8U, a, b, narrow_u);
+
+    let a = i32x4([70000, -70000, 5, -5]);
+    let b = i32x4([1, 2, 3, 4]);
+    let narrow_s = i16x8([32767, -32768, 5, -5, 1, 2, 3, 4]);
+    test_simd_binop(I16X8NARROWI32X4S, a, b, narrow_s);
+    let narrow_u = i16x8([-1, 0, 5, 0, 1, 2, 3, 4]);
+    test_simd_binop(I16X8NARROWI32X4U, a, b, narrow_u);
+}
+
+#[test]
+fn test_extend() {
+    let v = i8x16([-1, 2, -3, 4, 5, 6, 7, 8, -9, 10, -11, 12, 13, 14, 15, -16]);
+    test_simd_unop(I16X8EXTENDLOWI8X16S, v, i16x8([-1, 2, -3, 4, 5, 6, 7, 8]));
+    test_simd_unop(
+        I16X8EXTENDHIGHI8X16S,
+        v,
+        i16x8([-9, 10, -11, 12, 13, 14, 15, -16]),
+    );
+    test_simd_unop(I16X8EXTENDLOWI8X16U, v, i16x8([255, 2, 253, 4, 5, 6, 7, 8]));
+
+    let v = i16x8([-1, 2, 3, 4, 5, 6, 7, -8]);
+    test_simd_unop(I32X4EXTENDLOWI16X8S, v, i32x4([-1, 2, 3, 4]));
+    test_simd_unop(I32X4EXTENDHIGHI16X8U, v, i32x4([5, 6, 7, 65528]));
+
+    let v = i32x4([1, 2, -1, -2]);
+    test_simd_unop(I64X2EXTENDHIGHI32X4S, v, i64x2([-1, -2]));
+    test_simd_unop(I64X2EXTENDHIGHI32X4U, v, i64x2([0xffff_ffff, 0xffff_fffe]));
+}
+
+#[test]
+fn test_extmul() {
+    let a = i8x16([-128, 127, -1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let b = i8x16([-128, 127, 3, -4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let extmul_s = i16x8([16384, 16129, -3, -8, 0, 0, 0, 0]);
+    test_simd_binop(I16X8EXTMULLOWI8X16S, a, b, extmul_s);
+    // As unsigned: 128 * 128, 127 * 127, 255 * 3, 2 * 252
+    let extmul_u = i16x8([16384, 16129, 765, 504, 0, 0, 0, 0]);
+    test_simd_binop(I16X8EXTMULLOWI8X16U, a, b, extmul_u);
+    test_simd_binop(I16X8EXTMULHIGHI8X16S, a, b, i16x8([0; 8]));
+
+    let a = i32x4([0, 0, i32::MIN, -1]);
+    let b = i32x4([0, 0, i32::MIN, 5]);
+    test_simd_binop(I64X2EXTMULHIGHI32X4S, a, b, i64x2([1 << 62, -5]));
+    test_simd_binop(
+        I64X2EXTMULHIGHI32X4U,
+        a,
+        b,
+        i64x2([1 << 62, 0xffff_ffff * 5]),
+    );
+}
+
+#[test]
+fn test_extadd_pairwise_dot_and_q15mulr() {
+    let v = i8x16([127, 127, -128, -128, 1, -2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let sums_s = i16x8([254, -256, -1, 0, 0, 0, 0, 0]);
+    test_simd_unop(I16X8EXTADDPAIRWISEI8X16S, v, sums_s);
+    let sums_u = i16x8([254, 256, 255, 0, 0, 0, 0, 0]);
+    test_simd_unop(I16X8EXTADDPAIRWISEI8X16U, v, sums_u);
+
+    let v = i16x8([-1, -1, 1, 2, 0, 0, 0, 0]);
+    test_simd_unop(I32X4EXTADDPAIRWISEI16X8U, v, i32x4([131070, 3, 0, 0]));
+
+    let a = i16x8([1, 2, 3, 4, i16::MIN, i16::MIN, 0, 0]);
+    let b = i16x8([5, 6, 7, 8, i16::MIN, i16::MIN, 0, 0]);
+    // The only sum that doesn't fit in an i32 wraps around
+    test_simd_binop(I32X4DOTI16X8S, a, b, i32x4([17, 53, i32::MIN, 0]));
+
+    let a = i16x8([i16::MIN, 0x4000, -0x4000, 1, 0, 0, 0, 0]);
+    let b = i16x8([i16::MIN, 0x4000, 0x4000, 1, 0, 0, 0, 0]);
+    let q15mulr = i16x8([i16::MAX, 0x2000, -0x2000, 0, 0, 0, 0, 0]);
+    test_simd_binop(I16X8Q15MULRSATS, a, b, q15mulr);
+}
+
+#[test]
+fn test_float_int_conversions() {
+    let v = f32x4([1.9, -1.9, f32::NAN, 5e9]);
+    test_simd_unop(I32X4TRUNCSATF32X4S, v, i32x4([1, -1, 0, i32::MAX]));
+    test_simd_unop(I32X4TRUNCSATF32X4U, v, i32x4([1, 0, 0, -1]));
+
+    let v = f64x2([-3.7, 1e10]);
+    test_simd_unop(I32X4TRUNCSATF64X2SZERO, v, i32x4([-3, i32::MAX, 0, 0]));
+    test_simd_unop(I32X4TRUNCSATF64X2UZERO, v, i32x4([0, -1, 0, 0]));
+
+    let v = i32x4([-1, 2, 0, 16777217]);
+    test_simd_unop(F32X4CONVERTI32X4S, v, f32x4([-1.0, 2.0, 0.0, 16777216.0]));
+    test_simd_unop(
+        F32X4CONVERTI32X4U,
+        v,
+        f32x4([4294967296.0, 2.0, 0.0, 16777216.0]),
+    );
+    test_simd_unop(F64X2CONVERTLOWI32X4S, v, f64x2([-1.0, 2.0]));
+    test_simd_unop(F64X2CONVERTLOWI32X4U, v, f64x2([4294967295.0, 2.0]));
+
+    test_simd_unop(
+        F32X4DEMOTEF64X2ZERO,
+        f64x2([1.5, 1e300]),
+        f32x4([1.5, f32::INFINITY, 0.0, 0.0]),
+    );
+    test_simd_unop(
+        F64X2PROMOTELOWF32X4,
+        f32x4([1.5, -0.25, 9.0, 9.0]),
+        f64x2([1.5, -0.25]),
+    );
+}
+
+#[test]
+fn test_skip_simd_instructions() {
+    // Branching over a block has to skip the immediates of every instruction in it
+    let arena = Bump::new();
+    let module = simd_module(&arena, ValueType::I32, |buf| {
+        buf.push(OpCode::BLOCK as u8);
+        buf.push(ValueType::VOID);
+        buf.push(OpCode::BR as u8);
+        buf.push(0);
+        const_value(buf, i32x4([0; 4]));
+        const_value(buf, i32x4([0; 4]));
+        simd_op(buf, I8X16SHUFFLE);
+        buf.extend_from_slice(&[0; 16]);
+        simd_op(buf, I8X16EXTRACTLANES);
+        buf.push(0);
+        buf.push(OpCode::DROP as u8);
+        const_value(buf, Value::I32(0));
+        const_value(buf, i32x4([0; 4]));
+        simd_op(buf, V128STORE64LANE);
+        buf.extend_from_slice(&[3, 0, 1]);
+        simd_op(buf, I32X4ADD); // a sub-opcode that takes 2 bytes
+        buf.push(OpCode::END as u8);
+        const_value(buf, Value::I32(42));
+    });
+
+    let result = run_simd_module(&module).unwrap();
+
+    assert_eq!(result, Some(Value::I32(42)));
+}
@@ -0,0 +1,127 @@
+use super::{const_value, create_exported_function_no_locals};
+use crate::{DefaultImportDispatcher, Instance};
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::sections::{DataMode, DataSegment, MemorySection};
+use roc_wasm_module::{
+    opcodes::OpCode, ConstExpr, Global, GlobalType, Signature, Value, ValueType, WasmModule,
+};
+
+fn signature<'a>(arena: &'a Bump, ret_type: Option<ValueType>) -> Signature<'a> {
+    Signature {
+        param_types: Vec::new_in(arena),
+        ret_type,
+    }
+}
+
+/// A module whose start function doubles the number in its data segment and saves it in a global
+fn doubling_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+    module.memory = MemorySection::new(arena, MemorySection::PAGE_SIZE);
+    module.data.append_segment(DataSegment {
+        mode: DataMode::Active {
+            offset: ConstExpr::I32(8),
+        },
+        init: bumpalo::vec![in arena; 21, 0, 0, 0],
+    });
+    module.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I32,
+            is_mutable: true,
+        },
+        init: ConstExpr::I32(1),
+    });
+
+    create_exported_function_no_locals(&mut module, "start", signature(arena, None), |buf| {
+        const_value(buf, Value::I32(8));
+        buf.push(OpCode::I32LOAD as u8);
+        buf.push(2); // alignment
+        buf.push(0); // offset
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::I32SHL as u8);
+        buf.push(OpCode::SETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::END as u8);
+    });
+    let sig = signature(arena, Some(ValueType::I32));
+    create_exported_function_no_locals(&mut module, "get", sig, |buf| {
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.push(0);
+        buf.push(OpCode::END as u8);
+    });
+    module.start.function_index = Some(0);
+
+    module
+}
+
+fn instantiation_error<'a>(arena: &'a Bump, module: &'a WasmModule<'a>) -> String {
+    match Instance::for_module(arena, module, DefaultImportDispatcher::default(), false) {
+        Ok(_) => panic!("Expected the module to fail to instantiate"),
+        Err(error) => error,
+    }
+}
+
+#[test]
+fn test_start_runs_after_globals_and_data() {
+    let arena = Bump::new();
+    let module = doubling_module(&arena);
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(inst.call_export("get", []), Ok(Some(Value::I32(42))));
+}
+
+#[test]
+fn test_start_section_round_trip() {
+    let arena = Bump::new();
+    let mut bytes = Vec::new_in(&arena);
+    doubling_module(&arena).serialize(&mut bytes);
+
+    let module = WasmModule::preload(&arena, &bytes, false).unwrap();
+    assert_eq!(module.start.function_index, Some(0));
+
+    let mut inst =
+        Instance::from_bytes(&arena, &bytes, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(inst.call_export("get", []), Ok(Some(Value::I32(42))));
+}
+
+#[test]
+fn test_start_trap_fails_instantiation() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    create_exported_function_no_locals(&mut module, "start", signature(&arena, None), |buf| {
+        buf.push(OpCode::UNREACHABLE as u8);
+        buf.push(OpCode::END as u8);
+    });
+    module.start.function_index = Some(0);
+
+    let error = instantiation_error(&arena, &module);
+    assert!(error.contains("The start function failed"), "{}", error);
+    assert!(error.contains("unreachable"), "{}", error);
+}
+
+#[test]
+fn test_start_with_result() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    let sig = signature(&arena, Some(ValueType::I32));
+    create_exported_function_no_locals(&mut module, "start", sig, |buf| {
+        const_value(buf, Value::I32(1));
+        buf.push(OpCode::END as u8);
+    });
+    module.start.function_index = Some(0);
+
+    let error = instantiation_error(&arena, &module);
+    assert_eq!(
+        error,
+        "The start function func[0] should have no parameters or results, but its type is [] -> [I32]"
+    );
+
+    module.start.function_index = Some(1);
+    let error = instantiation_error(&arena, &module);
+    assert_eq!(
+        error,
+        "The start function is func[1], but the module has 1 functions"
+    );
+}
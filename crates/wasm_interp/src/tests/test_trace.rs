@@ -0,0 +1,126 @@
+use super::{count_down_module, import_i32_global, ZeroGlobalsDispatcher};
+use crate::{DefaultImportDispatcher, Instance, TraceWindow};
+use bumpalo::Bump;
+use roc_wasm_module::Value;
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+/// A trace writer that the test can still read from after giving it to the instance
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<std::vec::Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn lines(&self) -> std::vec::Vec<String> {
+        let bytes = self.0.borrow();
+        String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .collect::<std::vec::Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_trace_every_instruction() {
+    let arena = Bump::new();
+    let module = count_down_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let trace = SharedBuffer::default();
+    inst.enable_trace(trace.clone(), TraceWindow::default());
+
+    assert_eq!(
+        inst.call_export("count_down", [Value::I32(1)]),
+        Ok(Some(Value::I32(42)))
+    );
+
+    let lines = trace.lines();
+    let instructions: std::vec::Vec<_> = lines
+        .iter()
+        .map(|line| {
+            // Leave out the file offset, which depends on where the code section is
+            let (_, rest) = line.split_once(' ').unwrap();
+            rest
+        })
+        .collect();
+
+    assert_eq!(
+        instructions,
+        [
+            "count_down LOOP 64 -",
+            "count_down GETLOCAL 0 I32(1)",
+            "count_down I32CONST 1 I32(1)",
+            "count_down I32SUB I32(0)",
+            "count_down TEELOCAL 0 I32(0)",
+            "count_down BRIF 0 -",
+            "count_down END -",
+            "count_down GETLOCAL 0 I32(0)",
+            "count_down I32CONST 42 I32(2a)",
+            "count_down I32ADD I32(2a)",
+            "count_down END I32(2a)",
+        ]
+    );
+}
+
+#[test]
+fn test_trace_names_functions_after_an_imported_global() {
+    // The global is the first import, but count_down is still function 0
+    let arena = Bump::new();
+    let mut module = count_down_module(&arena);
+    import_i32_global(&mut module);
+    let mut inst = Instance::for_module(&arena, &module, ZeroGlobalsDispatcher, false).unwrap();
+    let trace = SharedBuffer::default();
+    inst.enable_trace(trace.clone(), TraceWindow::default());
+
+    inst.call_export("count_down", [Value::I32(1)]).unwrap();
+
+    let lines = trace.lines();
+    assert_eq!(lines.len(), 11);
+    assert!(lines.iter().all(|line| line.contains(" count_down ")));
+}
+
+#[test]
+fn test_trace_window() {
+    let arena = Bump::new();
+    let module = count_down_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let trace = SharedBuffer::default();
+    let window = TraceWindow { skip: 6, count: 3 };
+    inst.enable_trace(trace.clone(), window);
+
+    assert_eq!(
+        inst.call_export("count_down", [Value::I32(1000)]),
+        Ok(Some(Value::I32(42)))
+    );
+
+    // The second iteration of the loop
+    let lines = trace.lines();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].ends_with("count_down GETLOCAL 0 I32(3e7)"));
+    assert!(lines[1].ends_with("count_down I32CONST 1 I32(1)"));
+    assert!(lines[2].ends_with("count_down I32SUB I32(3e6)"));
+    assert_eq!(lines[3], "Trace window ended after 9 instructions");
+
+    // Tracing doesn't leave anything behind that changes how the instance runs
+    inst.disable_trace();
+    assert_eq!(
+        inst.call_export("count_down", [Value::I32(3)]),
+        Ok(Some(Value::I32(42)))
+    );
+    assert_eq!(trace.lines().len(), 4);
+}
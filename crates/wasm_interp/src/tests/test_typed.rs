@@ -0,0 +1,133 @@
+use super::create_exported_function_no_locals;
+use crate::{CallError, DefaultImportDispatcher, ExportInfo, ExportKind, Instance};
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::sections::MemorySection;
+use roc_wasm_module::{
+    opcodes::OpCode, ConstExpr, Export, ExportType, Global, GlobalType, Signature, ValueType,
+    WasmModule,
+};
+
+/// A module with a memory, a global, and functions `mix(i32, i64) -> f64` and `nop()`
+fn test_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    module.memory = MemorySection::new(arena, MemorySection::PAGE_SIZE);
+    module.export.append(Export {
+        name: "memory",
+        ty: ExportType::Mem,
+        index: 0,
+    });
+
+    module.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I64,
+            is_mutable: false,
+        },
+        init: ConstExpr::I64(7),
+    });
+    module.export.append(Export {
+        name: "seven",
+        ty: ExportType::Global,
+        index: 0,
+    });
+
+    let mix_sig = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32, ValueType::I64],
+        ret_type: Some(ValueType::F64),
+    };
+    create_exported_function_no_locals(&mut module, "mix", mix_sig, |buf| {
+        // f64(a) + f64(b)
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(0);
+        buf.push(OpCode::F64CONVERTSI32 as u8);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.push(1);
+        buf.push(OpCode::F64CONVERTSI64 as u8);
+        buf.push(OpCode::F64ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let nop_sig = Signature {
+        param_types: Vec::new_in(arena),
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "nop", nop_sig, |buf| {
+        buf.push(OpCode::END as u8);
+    });
+
+    module
+}
+
+#[test]
+fn test_exports() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.exports(),
+        vec![
+            ExportInfo {
+                name: "memory",
+                kind: ExportKind::Memory,
+            },
+            ExportInfo {
+                name: "seven",
+                kind: ExportKind::Global(ValueType::I64),
+            },
+            ExportInfo {
+                name: "mix",
+                kind: ExportKind::Func {
+                    params: vec![ValueType::I32, ValueType::I64],
+                    result: Some(ValueType::F64),
+                },
+            },
+            ExportInfo {
+                name: "nop",
+                kind: ExportKind::Func {
+                    params: vec![],
+                    result: None,
+                },
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_call_typed() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(inst.call_typed::<(i32, i64), f64>("mix", (2, 40)), Ok(42.0));
+    assert_eq!(inst.call_typed::<(), ()>("nop", ()), Ok(()));
+}
+
+#[test]
+fn test_call_typed_errors() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.call_typed::<(i32, i32), f64>("mix", (2, 40)),
+        Err(CallError::InvalidCall(
+            "'mix' takes (i32, i64) but was called with (i32, i32)".to_string()
+        ))
+    );
+    assert_eq!(
+        inst.call_typed::<(i32, i64), i32>("mix", (2, 40)),
+        Err(CallError::InvalidCall(
+            "'mix' returns f64 but i32 was expected".to_string()
+        ))
+    );
+    assert_eq!(
+        inst.call_typed::<(), ()>("seven", ()),
+        Err(CallError::InvalidCall(
+            "There is no exported function named 'seven'".to_string()
+        ))
+    );
+}
@@ -0,0 +1,591 @@
+use crate::wasi::Errno;
+use crate::{ChannelReader, LineCallback, ManualClock, Memory, WasiDispatcher, WasiFile};
+use roc_wasm_module::Value;
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const STDIN: i32 = 0;
+const STDOUT: i32 = 1;
+const STDERR: i32 = 2;
+
+/// Call `fd_write` with one iovec for each of the strings
+fn fd_write(wasi: &mut WasiDispatcher, fd: i32, strings: &[&str]) -> Option<Value> {
+    let mut memory = vec![0; 1024];
+    let ptr_nwritten: i32 = 0;
+    let ptr_iovs = 8;
+    let mut ptr_data = ptr_iovs + 8 * strings.len();
+
+    for (i, s) in strings.iter().enumerate() {
+        let ptr_iov = ptr_iovs + 8 * i;
+        memory[ptr_iov..][..4].copy_from_slice(&(ptr_data as u32).to_le_bytes());
+        memory[ptr_iov + 4..][..4].copy_from_slice(&(s.len() as u32).to_le_bytes());
+        memory[ptr_data..][..s.len()].copy_from_slice(s.as_bytes());
+        ptr_data += s.len();
+    }
+
+    let arguments = [
+        Value::I32(fd),
+        Value::I32(ptr_iovs as i32),
+        Value::I32(strings.len() as i32),
+        Value::I32(ptr_nwritten),
+    ];
+    wasi.dispatch("fd_write", &arguments, &mut Memory::new(&mut memory))
+}
+
+#[test]
+fn test_fd_write_in_memory() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdout(WasiFile::WriteOnly(vec![]));
+    wasi.set_stderr(WasiFile::WriteOnly(vec![]));
+
+    fd_write(&mut wasi, STDOUT, &["Hello, ", "World!\n"]);
+    fd_write(&mut wasi, STDERR, &["oops"]);
+
+    assert_eq!(wasi.file_contents(1), Some("Hello, World!\n".as_bytes()));
+    assert_eq!(wasi.file_contents(2), Some("oops".as_bytes()));
+}
+
+#[test]
+fn test_fd_write_line_callback() {
+    let lines = Rc::new(RefCell::new(Vec::<String>::new()));
+
+    let mut wasi = WasiDispatcher::default();
+    let captured = lines.clone();
+    wasi.set_stdout(WasiFile::Sink(Box::new(LineCallback::new(move |line| {
+        captured
+            .borrow_mut()
+            .push(String::from_utf8_lossy(line).into_owned())
+    }))));
+
+    let result = fd_write(&mut wasi, STDOUT, &["first\nsec", "ond\n", "third"]);
+    assert_eq!(result, Some(Value::I32(0)));
+    assert_eq!(*lines.borrow(), ["first", "second"]);
+
+    // The unterminated line is passed on when the file is closed
+    drop(wasi);
+    assert_eq!(*lines.borrow(), ["first", "second", "third"]);
+}
+
+/// A new empty directory for a test to open files in
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("roc_wasm_interp_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+const RIGHTS_FD_READ: i64 = 1 << 1;
+const RIGHTS_FD_WRITE: i64 = 1 << 6;
+const OFLAGS_CREAT: i32 = 1;
+const OFLAGS_TRUNC: i32 = 8;
+
+/// Call `path_open` in the directory `dir_fd`, and return the new file descriptor if it worked
+fn path_open(
+    wasi: &mut WasiDispatcher,
+    dir_fd: i32,
+    path: &str,
+    oflags: i32,
+    rights: i64,
+) -> Result<i32, i32> {
+    let mut memory = vec![0; 256];
+    let ptr_opened_fd = 0;
+    let ptr_path = 8;
+    memory[ptr_path..][..path.len()].copy_from_slice(path.as_bytes());
+
+    let arguments = [
+        Value::I32(dir_fd),
+        Value::I32(0),
+        Value::I32(ptr_path as i32),
+        Value::I32(path.len() as i32),
+        Value::I32(oflags),
+        Value::I64(rights),
+        Value::I64(0),
+        Value::I32(0),
+        Value::I32(ptr_opened_fd),
+    ];
+    match wasi.dispatch("path_open", &arguments, &mut Memory::new(&mut memory)) {
+        Some(Value::I32(0)) => Ok(i32::from_le_bytes(memory[0..4].try_into().unwrap())),
+        Some(Value::I32(errno)) => Err(errno),
+        other => panic!("path_open returned {:?}", other),
+    }
+}
+
+fn fd_close(wasi: &mut WasiDispatcher, fd: i32) -> Option<Value> {
+    wasi.dispatch("fd_close", &[Value::I32(fd)], &mut Memory::new(&mut []))
+}
+
+#[test]
+fn test_path_open_read() {
+    let dir = temp_dir("path_open_read");
+    fs::write(dir.join("greeting.txt"), "Hello, World!").unwrap();
+
+    let mut wasi = WasiDispatcher::default();
+    let dir_fd = wasi.preopen_host_dir("sandbox", &dir) as i32;
+
+    let fd = path_open(&mut wasi, dir_fd, "greeting.txt", 0, RIGHTS_FD_READ).unwrap();
+    assert_eq!(fd, dir_fd + 1);
+    assert_eq!(
+        fd_read(&mut wasi, fd, &[100]),
+        (Some(Value::I32(0)), b"Hello, World!".to_vec())
+    );
+
+    assert_eq!(fd_close(&mut wasi, fd), Some(Value::I32(0)));
+    assert_eq!(
+        fd_close(&mut wasi, fd),
+        Some(Value::I32(Errno::Badf as i32))
+    );
+    assert_eq!(
+        fd_read(&mut wasi, fd, &[100]).0,
+        Some(Value::I32(Errno::Badf as i32))
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_path_open_write() {
+    let dir = temp_dir("path_open_write");
+    fs::write(dir.join("out.txt"), "old contents, to be truncated").unwrap();
+
+    let mut wasi = WasiDispatcher::default();
+    let dir_fd = wasi.preopen_host_dir("sandbox", &dir) as i32;
+
+    for name in ["out.txt", "./new.txt"] {
+        let oflags = OFLAGS_CREAT | OFLAGS_TRUNC;
+        let fd = path_open(&mut wasi, dir_fd, name, oflags, RIGHTS_FD_WRITE).unwrap();
+        assert_eq!(
+            fd_write(&mut wasi, fd, &["Hello, ", "World!"]),
+            Some(Value::I32(0))
+        );
+        assert_eq!(fd_close(&mut wasi, fd), Some(Value::I32(0)));
+        assert_eq!(fs::read_to_string(dir.join(name)).unwrap(), "Hello, World!");
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_path_open_errors() {
+    let dir = temp_dir("path_open_errors");
+    fs::create_dir(dir.join("subdir")).unwrap();
+
+    let mut wasi = WasiDispatcher::default();
+    let dir_fd = wasi.preopen_host_dir("sandbox", &dir) as i32;
+    let no_host_dir_fd = wasi.preopen_dir("/data") as i32;
+
+    let mut open = |fd, path| path_open(&mut wasi, fd, path, 0, RIGHTS_FD_READ);
+
+    assert_eq!(open(dir_fd, "missing.txt"), Err(Errno::Noent as i32));
+    assert_eq!(open(dir_fd, "subdir"), Err(Errno::Isdir as i32));
+    // Nothing outside of the directory can be opened
+    assert_eq!(open(dir_fd, "../escape.txt"), Err(Errno::Notcapable as i32));
+    assert_eq!(
+        open(dir_fd, "subdir/../../escape.txt"),
+        Err(Errno::Notcapable as i32)
+    );
+    assert_eq!(open(dir_fd, "/etc/hosts"), Err(Errno::Notcapable as i32));
+    // A directory that's only a name has no files
+    assert_eq!(
+        open(no_host_dir_fd, "anything.txt"),
+        Err(Errno::Notcapable as i32)
+    );
+    assert_eq!(open(STDOUT, "anything.txt"), Err(Errno::Badf as i32));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_fdstat_of_preopened_dir() {
+    let mut wasi = WasiDispatcher::default();
+    let dir_fd = wasi.preopen_host_dir("sandbox", std::env::temp_dir()) as i32;
+    let mut memory = vec![0xaa; 24];
+
+    let arguments = [Value::I32(dir_fd), Value::I32(0)];
+    let result = wasi.dispatch("fd_fdstat_get", &arguments, &mut Memory::new(&mut memory));
+    assert_eq!(result, Some(Value::I32(0)));
+    // A directory, with no flags, that allows everything
+    assert_eq!(memory[0], 3);
+    assert_eq!(memory[1..8], [0; 7]);
+    assert_eq!(memory[8..24], [0xff; 16]);
+}
+
+/// Call `fd_read` with one iovec for each of the buffer sizes, and return the bytes read
+fn fd_read(wasi: &mut WasiDispatcher, fd: i32, iov_lens: &[usize]) -> (Option<Value>, Vec<u8>) {
+    let mut memory = vec![0; 1024];
+    let ptr_nread: i32 = 0;
+    let ptr_iovs = 8;
+    let mut ptr_data = ptr_iovs + 8 * iov_lens.len();
+
+    for (i, len) in iov_lens.iter().enumerate() {
+        let ptr_iov = ptr_iovs + 8 * i;
+        memory[ptr_iov..][..4].copy_from_slice(&(ptr_data as u32).to_le_bytes());
+        memory[ptr_iov + 4..][..4].copy_from_slice(&(*len as u32).to_le_bytes());
+        ptr_data += len;
+    }
+
+    let arguments = [
+        Value::I32(fd),
+        Value::I32(ptr_iovs as i32),
+        Value::I32(iov_lens.len() as i32),
+        Value::I32(ptr_nread),
+    ];
+    let result = wasi.dispatch("fd_read", &arguments, &mut Memory::new(&mut memory));
+
+    let n_read = u32::from_le_bytes(memory[0..4].try_into().unwrap()) as usize;
+    let ptr_first_buffer = ptr_iovs + 8 * iov_lens.len();
+    let bytes = memory[ptr_first_buffer..][..n_read].to_vec();
+    (result, bytes)
+}
+
+#[test]
+fn test_fd_read_input_until_eof() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdin(WasiFile::input(b"Hello, World!".to_vec()));
+
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[5, 3]),
+        (Some(Value::I32(0)), b"Hello, W".to_vec())
+    );
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[100]),
+        (Some(Value::I32(0)), b"orld!".to_vec())
+    );
+
+    // End of file is a successful read of zero bytes, as many times as the program asks
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[100]),
+        (Some(Value::I32(0)), vec![])
+    );
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[100]),
+        (Some(Value::I32(0)), vec![])
+    );
+}
+
+#[test]
+fn test_fd_read_short_read_fills_one_iovec() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdin(WasiFile::input(b"abc".to_vec()));
+
+    // The first buffer isn't filled, so the second one is left alone
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[4, 4]),
+        (Some(Value::I32(0)), b"abc".to_vec())
+    );
+}
+
+#[test]
+fn test_fd_read_channel() {
+    let (sender, receiver) = mpsc::channel();
+
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdin(WasiFile::Source(Box::new(ChannelReader::new(receiver))));
+
+    let input_thread = thread::spawn(move || {
+        sender.send(b"first line\n".to_vec()).unwrap();
+        sender.send(vec![]).unwrap();
+        sender.send(b"second".to_vec()).unwrap();
+    });
+
+    // Each read returns what has arrived so far, without waiting for a full buffer
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[100]),
+        (Some(Value::I32(0)), b"first line\n".to_vec())
+    );
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[4]),
+        (Some(Value::I32(0)), b"seco".to_vec())
+    );
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[100]),
+        (Some(Value::I32(0)), b"nd".to_vec())
+    );
+
+    input_thread.join().unwrap();
+    assert_eq!(
+        fd_read(&mut wasi, STDIN, &[100]),
+        (Some(Value::I32(0)), vec![])
+    );
+}
+
+#[test]
+fn test_fd_read_from_output_file() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdout(WasiFile::WriteOnly(vec![]));
+
+    let (result, _) = fd_read(&mut wasi, STDOUT, &[100]);
+    assert_eq!(result, Some(Value::I32(Errno::Badf as i32)));
+}
+
+#[test]
+fn test_bad_address() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdout(WasiFile::WriteOnly(vec![]));
+    let mut memory = vec![0; 16];
+
+    // An iovec whose bytes are past the end of memory
+    memory[0..4].copy_from_slice(&1000u32.to_le_bytes());
+    memory[4..8].copy_from_slice(&4u32.to_le_bytes());
+    let arguments = [
+        Value::I32(STDOUT),
+        Value::I32(0),
+        Value::I32(1),
+        Value::I32(8),
+    ];
+    let result = wasi.dispatch("fd_write", &arguments, &mut Memory::new(&mut memory));
+    assert_eq!(result, Some(Value::I32(Errno::Fault as i32)));
+
+    let arguments = [Value::I32(0), Value::I32(14)];
+    let result = wasi.dispatch("args_sizes_get", &arguments, &mut Memory::new(&mut memory));
+    assert_eq!(result, Some(Value::I32(Errno::Fault as i32)));
+}
+
+#[test]
+fn test_iovecs_at_the_end_of_the_address_space() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdin(WasiFile::ReadOnly(b"input".to_vec()));
+    wasi.set_stdout(WasiFile::WriteOnly(vec![]));
+    let mut memory = vec![0; 16];
+
+    for (function_name, fd) in [("fd_write", STDOUT), ("fd_read", STDIN)] {
+        let arguments = [
+            Value::I32(fd),
+            Value::I32(-8),
+            Value::I32(i32::MAX),
+            Value::I32(8),
+        ];
+        let result = wasi.dispatch(function_name, &arguments, &mut Memory::new(&mut memory));
+        assert_eq!(
+            result,
+            Some(Value::I32(Errno::Fault as i32)),
+            "{}",
+            function_name
+        );
+    }
+}
+
+#[test]
+fn test_environ_get() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_env("HOME", "/home/roc");
+    wasi.set_env("HOMEPAGE", "roc-lang.org");
+    wasi.set_env("HOME", "/root");
+    let mut memory = vec![0; 64];
+
+    let arguments = [Value::I32(0), Value::I32(4)];
+    let result = wasi.dispatch(
+        "environ_sizes_get",
+        &arguments,
+        &mut Memory::new(&mut memory),
+    );
+    assert_eq!(result, Some(Value::I32(0)));
+    assert_eq!(memory[0..4], 2u32.to_le_bytes());
+    assert_eq!(memory[4..8], 33u32.to_le_bytes());
+
+    let arguments = [Value::I32(0), Value::I32(8)];
+    let result = wasi.dispatch("environ_get", &arguments, &mut Memory::new(&mut memory));
+    assert_eq!(result, Some(Value::I32(0)));
+    assert_eq!(memory[0..4], 8u32.to_le_bytes());
+    assert_eq!(memory[4..8], 19u32.to_le_bytes());
+    assert_eq!(&memory[8..41], b"HOME=/root\0HOMEPAGE=roc-lang.org\0");
+}
+
+#[test]
+fn test_c_strings_and_stats_at_the_end_of_the_address_space() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_env("HOME", "/root");
+    let fd = wasi.preopen_dir("/data") as i32;
+    let mut memory = vec![0; 16];
+
+    let calls = [
+        ("environ_get", vec![Value::I32(0), Value::I32(-4)]),
+        ("environ_get", vec![Value::I32(-4), Value::I32(4)]),
+        ("fd_fdstat_get", vec![Value::I32(STDOUT), Value::I32(-1)]),
+        ("fd_prestat_get", vec![Value::I32(fd), Value::I32(-4)]),
+    ];
+    for (function_name, arguments) in calls {
+        let result = wasi.dispatch(function_name, &arguments, &mut Memory::new(&mut memory));
+        assert_eq!(
+            result,
+            Some(Value::I32(Errno::Fault as i32)),
+            "{}({:?})",
+            function_name,
+            arguments
+        );
+    }
+}
+
+#[test]
+fn test_preopen_dir() {
+    let mut wasi = WasiDispatcher::default();
+    let fd = wasi.preopen_dir("/data") as i32;
+    assert_eq!(fd, 3);
+    let mut memory = vec![0; 16];
+
+    let arguments = [Value::I32(fd), Value::I32(0)];
+    let result = wasi.dispatch("fd_prestat_get", &arguments, &mut Memory::new(&mut memory));
+    assert_eq!(result, Some(Value::I32(0)));
+    assert_eq!(memory[0..4], 0u32.to_le_bytes());
+    assert_eq!(memory[4..8], 5u32.to_le_bytes());
+
+    let arguments = [Value::I32(fd), Value::I32(8), Value::I32(5)];
+    let result = wasi.dispatch(
+        "fd_prestat_dir_name",
+        &arguments,
+        &mut Memory::new(&mut memory),
+    );
+    assert_eq!(result, Some(Value::I32(0)));
+    assert_eq!(&memory[8..13], b"/data");
+
+    // The program stops looking for preopened directories at the first Badf
+    for fd in [STDOUT, fd + 1] {
+        let arguments = [Value::I32(fd), Value::I32(0)];
+        let result = wasi.dispatch("fd_prestat_get", &arguments, &mut Memory::new(&mut memory));
+        assert_eq!(result, Some(Value::I32(Errno::Badf as i32)));
+    }
+}
+
+#[test]
+fn test_proc_exit() {
+    let mut wasi = WasiDispatcher::default();
+    let mut memory = vec![];
+
+    let result = wasi.dispatch("proc_exit", &[Value::I32(3)], &mut Memory::new(&mut memory));
+    assert_eq!(result, None);
+    assert_eq!(wasi.exit_status, Some(3));
+}
+
+/// Call `clock_time_get`, and return the time it wrote if it succeeded
+fn clock_time_get(wasi: &mut WasiDispatcher, clock_id: i32) -> Result<u64, Option<Value>> {
+    let mut memory = vec![0; 8];
+    let arguments = [Value::I32(clock_id), Value::I64(1), Value::I32(0)];
+    match wasi.dispatch("clock_time_get", &arguments, &mut Memory::new(&mut memory)) {
+        Some(Value::I32(0)) => Ok(u64::from_le_bytes(memory.try_into().unwrap())),
+        result => Err(result),
+    }
+}
+
+#[test]
+fn test_clock_manual() {
+    const REALTIME: i32 = 0;
+    const MONOTONIC: i32 = 1;
+
+    let clock = ManualClock::new(1_700_000_000_000_000_000);
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_clock(clock.clone());
+
+    assert_eq!(
+        clock_time_get(&mut wasi, REALTIME),
+        Ok(1_700_000_000_000_000_000)
+    );
+    assert_eq!(clock_time_get(&mut wasi, MONOTONIC), Ok(0));
+
+    clock.advance(Duration::from_millis(1500));
+    assert_eq!(
+        clock_time_get(&mut wasi, REALTIME),
+        Ok(1_700_000_001_500_000_000)
+    );
+    assert_eq!(clock_time_get(&mut wasi, MONOTONIC), Ok(1_500_000_000));
+
+    // Setting the wall-clock time doesn't move the monotonic clock
+    clock.set_realtime(0);
+    assert_eq!(clock_time_get(&mut wasi, REALTIME), Ok(0));
+    assert_eq!(clock_time_get(&mut wasi, MONOTONIC), Ok(1_500_000_000));
+
+    // Another dispatcher has its own time
+    let mut other = WasiDispatcher::default();
+    other.set_clock(ManualClock::default());
+    assert_eq!(clock_time_get(&mut other, MONOTONIC), Ok(0));
+}
+
+#[test]
+fn test_clock_system() {
+    let mut wasi = WasiDispatcher::default();
+
+    let first = clock_time_get(&mut wasi, 1).unwrap();
+    let second = clock_time_get(&mut wasi, 1).unwrap();
+    assert!(first <= second);
+    assert!(clock_time_get(&mut wasi, 0).unwrap() > 1_600_000_000_000_000_000);
+
+    // CPU time isn't available from the system clock, and there's no clock 4
+    for clock_id in [2, 4] {
+        assert_eq!(
+            clock_time_get(&mut wasi, clock_id),
+            Err(Some(Value::I32(Errno::Inval as i32)))
+        );
+    }
+
+    let mut memory = vec![0; 8];
+    let arguments = [Value::I32(1), Value::I32(0)];
+    let result = wasi.dispatch("clock_res_get", &arguments, &mut Memory::new(&mut memory));
+    assert_eq!(result, Some(Value::I32(0)));
+    assert_eq!(memory, 1u64.to_le_bytes());
+}
+
+#[test]
+fn test_audit() {
+    let calls = Rc::new(RefCell::new(Vec::<String>::new()));
+
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdout(WasiFile::WriteOnly(vec![]));
+    let captured = calls.clone();
+    wasi.set_audit(move |call| captured.borrow_mut().push(call.to_string()));
+
+    fd_write(&mut wasi, STDOUT, &["hi"]);
+    wasi.dispatch("proc_exit", &[Value::I32(1)], &mut Memory::new(&mut []));
+
+    assert_eq!(
+        *calls.borrow(),
+        [
+            "fd_write(I32(1), I32(8), I32(1), I32(0)) -> I32(0)",
+            "proc_exit(I32(1))",
+        ]
+    );
+}
+
+#[test]
+fn test_allowlist() {
+    let calls = Rc::new(RefCell::new(Vec::<(String, bool)>::new()));
+
+    let mut wasi = WasiDispatcher::default();
+    wasi.set_stdout(WasiFile::WriteOnly(vec![]));
+    wasi.set_stderr(WasiFile::WriteOnly(vec![]));
+    wasi.allow_only(["fd_write"]);
+    let captured = calls.clone();
+    wasi.set_audit(move |call| {
+        captured
+            .borrow_mut()
+            .push((call.function_name.to_string(), call.allowed))
+    });
+
+    let result = fd_write(&mut wasi, STDOUT, &["allowed"]);
+    assert_eq!(result, Some(Value::I32(Errno::Success as i32)));
+    assert_eq!(wasi.file_contents(1), Some("allowed".as_bytes()));
+
+    // A denied call isn't run, even if its arguments are bad
+    let mut memory = vec![];
+    let result = wasi.dispatch(
+        "random_get",
+        &[Value::I32(1000), Value::I32(8)],
+        &mut Memory::new(&mut memory),
+    );
+    assert_eq!(result, Some(Value::I32(Errno::Notcapable as i32)));
+
+    let result = wasi.dispatch("proc_exit", &[Value::I32(3)], &mut Memory::new(&mut memory));
+    assert_eq!(result, None);
+    assert_eq!(wasi.exit_status, None);
+
+    assert_eq!(
+        *calls.borrow(),
+        [
+            ("fd_write".to_string(), true),
+            ("random_get".to_string(), false),
+            ("proc_exit".to_string(), false),
+        ]
+    );
+}
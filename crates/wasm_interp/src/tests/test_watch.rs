@@ -0,0 +1,221 @@
+use super::create_exported_function_no_locals;
+use crate::{
+    DefaultImportDispatcher, Instance, RunResult, WatchAccess, WatchAction, Watchpoint,
+    WatchpointHit,
+};
+use bumpalo::Bump;
+use roc_wasm_module::sections::MemorySection;
+use roc_wasm_module::{opcodes::OpCode, SerialBuffer, Signature, Value, ValueType, WasmModule};
+
+/// A module with a function `poke(addr: i32, value: i32) -> i32` that stores the value at the
+/// address, then loads it back and adds 1 to it
+fn test_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+    module.memory = MemorySection::new(arena, MemorySection::PAGE_SIZE);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32, ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "poke", signature, |buf| {
+        // i32.store addr value
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(1);
+        buf.push(OpCode::I32STORE as u8);
+        buf.encode_u32(2); // alignment
+        buf.encode_u32(0); // offset
+
+        // i32.load addr
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32LOAD as u8);
+        buf.encode_u32(2);
+        buf.encode_u32(0);
+        // + 1
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    module
+}
+
+#[test]
+fn test_watchpoint_trap_on_store() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    inst.add_watchpoint(Watchpoint {
+        range: 0x100..0x104,
+        access: WatchAccess::Write,
+        action: WatchAction::Trap,
+    });
+
+    // Accesses outside the range don't hit it
+    assert_eq!(
+        inst.call_export("poke", [Value::I32(0x104), Value::I32(5)]),
+        Ok(Some(Value::I32(6)))
+    );
+    assert!(inst.watchpoint_hits().is_empty());
+
+    // An access that overlaps the range does, and traps before the value is stored
+    let message = inst
+        .call_export("poke", [Value::I32(0x102), Value::I32(7)])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        message.contains("Store of I32(7) to 4 bytes at 0x102"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("in func[0], hit watchpoint 0"),
+        "{}",
+        message
+    );
+    assert_eq!(&inst.memory[0x102..0x106], &[0, 0, 5, 0]);
+
+    let hits = inst.watchpoint_hits();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].stored, Some(Value::I32(7)));
+    assert_eq!((hits[0].addr, hits[0].size), (0x102, 4));
+
+    inst.clear_watchpoints();
+    assert!(inst.watchpoint_hits().is_empty());
+    assert_eq!(
+        inst.call_export("poke", [Value::I32(0x102), Value::I32(7)]),
+        Ok(Some(Value::I32(8)))
+    );
+}
+
+#[test]
+fn test_watchpoint_read_or_write() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    let reads = inst.add_watchpoint(Watchpoint {
+        range: 0x100..0x101,
+        access: WatchAccess::Read,
+        action: WatchAction::Pause,
+    });
+    let all = inst.add_watchpoint(Watchpoint {
+        range: 0x100..0x101,
+        access: WatchAccess::ReadWrite,
+        action: WatchAction::Pause,
+    });
+
+    // Pausing only affects calls made with run_for, so this one just carries on
+    assert_eq!(
+        inst.call_export("poke", [Value::I32(0x100), Value::I32(3)]),
+        Ok(Some(Value::I32(4)))
+    );
+
+    let hits: Vec<(usize, Option<Value>)> = inst
+        .watchpoint_hits()
+        .iter()
+        .map(|hit| (hit.watchpoint, hit.stored))
+        .collect();
+    assert_eq!(
+        hits,
+        [(all, Some(Value::I32(3))), (reads, None), (all, None)]
+    );
+}
+
+#[test]
+fn test_watchpoint_pause() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    inst.add_watchpoint(Watchpoint {
+        range: 0x200..0x208,
+        access: WatchAccess::Write,
+        action: WatchAction::Pause,
+    });
+
+    inst.begin_call("poke", [Value::I32(0x204), Value::I32(0x2a)])
+        .unwrap();
+
+    // It yields right after the store, with the value already in memory
+    match inst.run_for(100).unwrap() {
+        RunResult::Yielded(progress) => assert_eq!(progress.instructions_executed, 3),
+        result => panic!("{:?}", result),
+    }
+    assert_eq!(&inst.memory[0x204..0x208], &[0x2a, 0, 0, 0]);
+    assert_eq!(inst.watchpoint_hits().len(), 1);
+
+    assert_eq!(
+        inst.run_for(100),
+        Ok(RunResult::Finished(Some(Value::I32(0x2b))))
+    );
+}
+
+#[test]
+fn test_watchpoint_trap_on_load() {
+    let arena = Bump::new();
+    let module = test_module(&arena);
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    // Ends right where the access starts, so the store doesn't touch it
+    inst.add_watchpoint(Watchpoint {
+        range: 0x2fc..0x300,
+        access: WatchAccess::ReadWrite,
+        action: WatchAction::Pause,
+    });
+    let loads = inst.add_watchpoint(Watchpoint {
+        range: 0x303..0x304,
+        access: WatchAccess::Read,
+        action: WatchAction::Trap,
+    });
+
+    // The store isn't a read, so it goes ahead, and the load traps
+    let message = inst
+        .call_export("poke", [Value::I32(0x300), Value::I32(9)])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        message.contains("Load from 4 bytes at 0x300"),
+        "{}",
+        message
+    );
+    assert_eq!(&inst.memory[0x300..0x304], &[9, 0, 0, 0]);
+
+    let hits = inst.watchpoint_hits();
+    assert_eq!(hits.len(), 1);
+    assert_eq!((hits[0].watchpoint, hits[0].stored), (loads, None));
+}
+
+#[test]
+fn test_watchpoint_hit_report() {
+    let store = WatchpointHit {
+        watchpoint: 2,
+        stored: Some(Value::I64(-1)),
+        addr: 0x1_0010,
+        size: 8,
+        fn_index: 14,
+        file_offset: 0x5a3,
+    };
+    assert_eq!(
+        store.to_string(),
+        "Store of I64(-1) to 8 bytes at 0x10010, by the instruction at file offset 0x5a3 in func[14], hit watchpoint 2"
+    );
+
+    let load = WatchpointHit {
+        stored: None,
+        size: 1,
+        ..store
+    };
+    assert_eq!(
+        load.to_string(),
+        "Load from 1 bytes at 0x10010, by the instruction at file offset 0x5a3 in func[14], hit watchpoint 2"
+    );
+}
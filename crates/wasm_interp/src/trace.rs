@@ -0,0 +1,117 @@
+use roc_wasm_module::{Value, WasmModule};
+use std::fmt;
+use std::io;
+
+use crate::profile::function_name;
+
+/// Which executed instructions to write to the trace, counting from the start of tracing.
+/// Tracing a long-running program in full would produce far more output than anyone can read,
+/// so the trace stops after `count` instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceWindow {
+    /// Number of instructions to execute before the first one that is written
+    pub skip: u64,
+    /// Maximum number of instructions to write
+    pub count: u64,
+}
+
+impl Default for TraceWindow {
+    fn default() -> Self {
+        TraceWindow {
+            skip: 0,
+            count: 100_000,
+        }
+    }
+}
+
+/// Writes a line for each executed instruction within the window:
+/// its file offset, the function it belongs to, the instruction and its immediates,
+/// and the value on top of the current function's part of the stack after executing it.
+pub(crate) struct Tracer<'a> {
+    writer: Box<dyn io::Write + 'a>,
+    window: TraceWindow,
+    /// Instructions executed since tracing was enabled
+    instructions_executed: u64,
+    /// Name of the function of the last traced instruction, to avoid looking it up every time
+    fn_name: Option<(usize, String)>,
+    /// Reused for the text of each traced instruction
+    pub buffer: String,
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(writer: Box<dyn io::Write + 'a>, window: TraceWindow) -> Self {
+        Tracer {
+            writer,
+            window,
+            instructions_executed: 0,
+            fn_name: None,
+            buffer: String::new(),
+        }
+    }
+
+    /// Count the next instruction, and check whether it should be written to the trace
+    pub fn next_instruction_is_traced(&mut self) -> bool {
+        let index = self.instructions_executed;
+        self.instructions_executed += 1;
+        index >= self.window.skip && index - self.window.skip < self.window.count
+    }
+
+    pub fn write_instruction(
+        &mut self,
+        module: &WasmModule,
+        import_count: usize,
+        file_offset: u32,
+        fn_index: usize,
+        stack_top: Option<&Value>,
+    ) {
+        let fn_name = match &self.fn_name {
+            Some((index, name)) if *index == fn_index => name,
+            _ => {
+                let name = function_name(module, fn_index, import_count);
+                &self.fn_name.insert((fn_index, name)).1
+            }
+        };
+        let instruction = self.buffer.trim_end();
+
+        // A trace is a debugging aid, so a failure to write it shouldn't stop the program.
+        let result = match stack_top {
+            Some(value) => writeln!(
+                self.writer,
+                "{:06x} {:20} {:32} {:x?}",
+                file_offset, fn_name, instruction, value
+            ),
+            None => writeln!(
+                self.writer,
+                "{:06x} {:20} {:32} -",
+                file_offset, fn_name, instruction
+            ),
+        };
+
+        let is_last = self.instructions_executed == self.window.skip + self.window.count;
+        let result = result.and_then(|()| {
+            if is_last {
+                writeln!(
+                    self.writer,
+                    "Trace window ended after {} instructions",
+                    self.instructions_executed
+                )?;
+                self.writer.flush()
+            } else {
+                Ok(())
+            }
+        });
+
+        if result.is_err() {
+            self.window.count = 0;
+        }
+    }
+}
+
+impl fmt::Debug for Tracer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("window", &self.window)
+            .field("instructions_executed", &self.instructions_executed)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,135 @@
+//! Rust types for the arguments and results of exported functions, so that they can be called
+//! without building [Value]s by hand. See [Instance::call_typed](crate::Instance::call_typed).
+use roc_wasm_module::{Value, ValueType};
+
+/// What kind of thing a module exports, and its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportKind {
+    Func {
+        params: Vec<ValueType>,
+        result: Option<ValueType>,
+    },
+    Table,
+    Memory,
+    Global(ValueType),
+}
+
+/// One of a module's exports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportInfo<'a> {
+    pub name: &'a str,
+    pub kind: ExportKind,
+}
+
+/// A Rust type that corresponds to a Wasm value type
+pub trait WasmValue: Sized {
+    const VALUE_TYPE: ValueType;
+    fn into_value(self) -> Value;
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+macro_rules! wasm_value {
+    ($ty:ty, $variant:ident, $wasm_ty:ty) => {
+        impl WasmValue for $ty {
+            const VALUE_TYPE: ValueType = ValueType::$variant;
+
+            fn into_value(self) -> Value {
+                Value::$variant(self as $wasm_ty)
+            }
+
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    Value::$variant(x) => Some(x as $ty),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+wasm_value!(i32, I32, i32);
+wasm_value!(u32, I32, i32);
+wasm_value!(i64, I64, i64);
+wasm_value!(u64, I64, i64);
+wasm_value!(f32, F32, f32);
+wasm_value!(f64, F64, f64);
+
+/// The arguments of a function: a tuple of [WasmValue]s, or `()` for none
+pub trait WasmParams {
+    fn value_types() -> Vec<ValueType>;
+    fn into_values(self) -> Vec<Value>;
+}
+
+macro_rules! wasm_params {
+    ($($name:ident),*) => {
+        impl<$($name: WasmValue),*> WasmParams for ($($name,)*) {
+            fn value_types() -> Vec<ValueType> {
+                vec![$($name::VALUE_TYPE),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_values(self) -> Vec<Value> {
+                let ($($name,)*) = self;
+                vec![$($name.into_value()),*]
+            }
+        }
+    };
+}
+
+wasm_params!();
+wasm_params!(A);
+wasm_params!(A, B);
+wasm_params!(A, B, C);
+wasm_params!(A, B, C, D);
+wasm_params!(A, B, C, D, E);
+wasm_params!(A, B, C, D, E, F);
+
+/// The result of a function: a [WasmValue], or `()` for none
+pub trait WasmResult: Sized {
+    fn value_type() -> Option<ValueType>;
+    fn from_result(result: Option<Value>) -> Option<Self>;
+}
+
+impl WasmResult for () {
+    fn value_type() -> Option<ValueType> {
+        None
+    }
+
+    fn from_result(result: Option<Value>) -> Option<Self> {
+        match result {
+            None => Some(()),
+            Some(_) => None,
+        }
+    }
+}
+
+impl<T: WasmValue> WasmResult for T {
+    fn value_type() -> Option<ValueType> {
+        Some(T::VALUE_TYPE)
+    }
+
+    fn from_result(result: Option<Value>) -> Option<Self> {
+        result.and_then(T::from_value)
+    }
+}
+
+/// Formats types like Wasm text format, e.g. "(i32, f64)"
+pub(crate) fn type_list(types: &[ValueType]) -> String {
+    let names: Vec<&str> = types.iter().map(|ty| type_name(*ty)).collect();
+    format!("({})", names.join(", "))
+}
+
+/// Formats a function's result type, e.g. "f64" or "nothing"
+pub(crate) fn result_name(ty: Option<ValueType>) -> &'static str {
+    ty.map_or("nothing", type_name)
+}
+
+pub(crate) fn type_name(ty: ValueType) -> &'static str {
+    match ty {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::F32 => "f32",
+        ValueType::F64 => "f64",
+        ValueType::V128 => "v128",
+    }
+}
@@ -1,14 +1,65 @@
+use crate::clock::{Clock, ClockId, SystemClock};
+use crate::{DefaultImportDispatcher, Instance, Memory, MemoryError};
+use bumpalo::Bump;
 use rand::prelude::*;
 use roc_wasm_module::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::io::{self, Read, StderrLock, StdoutLock, Write};
-use std::process::exit;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::Receiver;
 
 pub const MODULE_NAME: &str = "wasi_snapshot_preview1";
 
 pub struct WasiDispatcher<'a> {
     pub args: &'a [&'a [u8]],
+    /// Environment variables, each one `KEY=VALUE`
+    pub env: Vec<Vec<u8>>,
     pub rng: ThreadRng,
     pub files: Vec<WasiFile>,
+    /// The status passed to `proc_exit`, once the program has called it
+    pub exit_status: Option<i32>,
+    /// Where `clock_time_get` gets the time from. The host's real time by default.
+    pub clock: Box<dyn Clock>,
+    /// Called after every WASI call, including denied ones, e.g. to log what a program does
+    pub audit: Option<AuditHook>,
+    /// The only WASI functions the program may call, if it's restricted. Others are denied.
+    pub allowlist: Option<HashSet<String>>,
+}
+
+/// See [WasiDispatcher::set_audit]
+pub type AuditHook = Box<dyn FnMut(&WasiCall)>;
+
+/// A WASI call that the program made, for auditing
+#[derive(Debug)]
+pub struct WasiCall<'c> {
+    pub function_name: &'c str,
+    pub arguments: &'c [Value],
+    /// The value returned to the program. For most functions this is an [Errno].
+    pub result: Option<Value>,
+    /// False if the function isn't in the allowlist, so it wasn't run
+    pub allowed: bool,
+}
+
+impl fmt::Display for WasiCall<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.function_name)?;
+        for (i, arg) in self.arguments.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}", arg)?;
+        }
+        write!(f, ")")?;
+        if let Some(result) = self.result {
+            write!(f, " -> {:?}", result)?;
+        }
+        if !self.allowed {
+            write!(f, " (denied)")?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for WasiDispatcher<'_> {
@@ -17,17 +68,134 @@ impl Default for WasiDispatcher<'_> {
     }
 }
 
+/// A file descriptor's backing storage.
+/// Embedders can replace the default stdin/stdout/stderr (`HostSystemFile`) to feed input or
+/// capture output without any process-level redirection, e.g. with an in-memory buffer,
+/// a `ChannelReader` or a `LineCallback`.
 pub enum WasiFile {
     ReadOnly(Vec<u8>),
     WriteOnly(Vec<u8>),
     ReadWrite(Vec<u8>),
     HostSystemFile,
+    /// Write-only output, passed on to any sink supplied by the embedder
+    Sink(Box<dyn Write>),
+    /// Read-only input, pulled from any reader supplied by the embedder,
+    /// e.g. a byte buffer from `WasiFile::input` or a `ChannelReader`
+    Source(Box<dyn Read>),
+    /// A directory that the program is told about when it starts, with the name it should use
+    /// for it. Files in it can be opened if it's backed by a directory on the host.
+    PreopenDir {
+        name: String,
+        host_path: Option<PathBuf>,
+    },
+    /// A file on the host that the program opened in a preopened directory
+    HostFile(File),
+    /// A file descriptor that the program closed
+    Closed,
+}
+
+impl WasiFile {
+    /// Input that yields the given bytes once and then reaches end of file
+    pub fn input(bytes: Vec<u8>) -> Self {
+        WasiFile::Source(Box::new(io::Cursor::new(bytes)))
+    }
 }
 
 enum WriteLock<'a> {
     StdOut(StdoutLock<'a>),
     Stderr(StderrLock<'a>),
     RegularFile(&'a mut Vec<u8>),
+    Sink(&'a mut dyn Write),
+}
+
+/// A `Write` sink that calls a function for each complete line of output, without the newline.
+/// Any unterminated line at the end is passed to the callback when this is dropped.
+pub struct LineCallback<F: FnMut(&[u8])> {
+    callback: F,
+    partial_line: Vec<u8>,
+}
+
+impl<F: FnMut(&[u8])> LineCallback<F> {
+    pub fn new(callback: F) -> Self {
+        LineCallback {
+            callback,
+            partial_line: Vec::new(),
+        }
+    }
+}
+
+impl<F: FnMut(&[u8])> Write for LineCallback<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(newline_index) = rest.iter().position(|&b| b == b'\n') {
+            let (line, after) = rest.split_at(newline_index);
+            if self.partial_line.is_empty() {
+                (self.callback)(line);
+            } else {
+                self.partial_line.extend_from_slice(line);
+                (self.callback)(&self.partial_line);
+                self.partial_line.clear();
+            }
+            rest = &after[1..];
+        }
+        self.partial_line.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: FnMut(&[u8])> Drop for LineCallback<F> {
+    fn drop(&mut self) {
+        if !self.partial_line.is_empty() {
+            (self.callback)(&self.partial_line);
+        }
+    }
+}
+
+/// A `Read` source fed with chunks of bytes from another thread, for input that arrives while
+/// the program is running. Reads block until the next chunk arrives, and reach end of file
+/// once every `Sender` has been dropped.
+pub struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    pub fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        ChannelReader {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.position == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let remaining = &self.chunk[self.position..];
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+        Ok(len)
+    }
 }
 
 /// Implementation of WASI syscalls
@@ -38,12 +206,108 @@ impl<'a> WasiDispatcher<'a> {
     pub fn new(args: &'a [&'a [u8]]) -> Self {
         WasiDispatcher {
             args,
+            env: Vec::new(),
             rng: thread_rng(),
             files: vec![
                 WasiFile::HostSystemFile,
                 WasiFile::HostSystemFile,
                 WasiFile::HostSystemFile,
             ],
+            exit_status: None,
+            clock: Box::new(SystemClock::default()),
+            audit: None,
+            allowlist: None,
+        }
+    }
+
+    /// Set an environment variable, replacing any earlier value
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        let var = [key.as_bytes(), b"=", value.as_bytes()].concat();
+        let prefix_len = key.len() + 1;
+        match self
+            .env
+            .iter_mut()
+            .find(|existing| existing.get(..prefix_len) == Some(&var[..prefix_len]))
+        {
+            Some(existing) => *existing = var,
+            None => self.env.push(var),
+        }
+    }
+
+    /// Give the program a preopened directory with this name, returning its file descriptor.
+    /// The program can see the name, but can't open anything in it.
+    pub fn preopen_dir(&mut self, name: &str) -> usize {
+        self.files.push(WasiFile::PreopenDir {
+            name: name.to_string(),
+            host_path: None,
+        });
+        self.files.len() - 1
+    }
+
+    /// Give the program a preopened directory with this name, where it can open the files under
+    /// `host_path`. Paths that lead out of it, through `..` or from the root, are refused, but
+    /// symbolic links inside it are followed wherever they go.
+    pub fn preopen_host_dir(&mut self, name: &str, host_path: impl Into<PathBuf>) -> usize {
+        self.files.push(WasiFile::PreopenDir {
+            name: name.to_string(),
+            host_path: Some(host_path.into()),
+        });
+        self.files.len() - 1
+    }
+
+    /// Replace the file used for standard input
+    pub fn set_stdin(&mut self, file: WasiFile) {
+        self.files[0] = file;
+    }
+
+    /// Replace the file used for standard output
+    pub fn set_stdout(&mut self, file: WasiFile) {
+        self.files[1] = file;
+    }
+
+    /// Replace the file used for standard error
+    pub fn set_stderr(&mut self, file: WasiFile) {
+        self.files[2] = file;
+    }
+
+    /// Replace the clock that the program reads the time from, e.g. with a
+    /// [ManualClock](crate::ManualClock) to control the time it sees
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+    }
+
+    /// Call `audit` after every WASI call the program makes, with its arguments and result
+    pub fn set_audit(&mut self, audit: impl FnMut(&WasiCall) + 'static) {
+        self.audit = Some(Box::new(audit));
+    }
+
+    /// Only let the program call these WASI functions. Calls to any other function aren't run,
+    /// and return [Errno::Notcapable] instead. `proc_exit` has no return value, so denying it
+    /// just means the program carries on, usually to the `unreachable` that follows the call.
+    pub fn allow_only<'n, I: IntoIterator<Item = &'n str>>(&mut self, function_names: I) {
+        self.allowlist = Some(function_names.into_iter().map(String::from).collect());
+    }
+
+    /// Whether the program may call this WASI function
+    pub fn is_allowed(&self, function_name: &str) -> bool {
+        match &self.allowlist {
+            Some(allowlist) => allowlist.contains(function_name),
+            None => true,
+        }
+    }
+
+    /// The bytes written to an in-memory file, if it is one
+    pub fn file_contents(&self, fd: usize) -> Option<&[u8]> {
+        match self.files.get(fd)? {
+            WasiFile::ReadOnly(content)
+            | WasiFile::WriteOnly(content)
+            | WasiFile::ReadWrite(content) => Some(content),
+            WasiFile::HostSystemFile
+            | WasiFile::Sink(_)
+            | WasiFile::Source(_)
+            | WasiFile::PreopenDir { .. }
+            | WasiFile::HostFile(_)
+            | WasiFile::Closed => None,
         }
     }
 
@@ -51,50 +315,139 @@ impl<'a> WasiDispatcher<'a> {
         &mut self,
         function_name: &str,
         arguments: &[Value],
-        memory: &mut [u8],
+        memory: &mut Memory<'_>,
     ) -> Option<Value> {
-        let success_code = Some(Value::I32(Errno::Success as i32));
+        let allowed = self.is_allowed(function_name);
+
+        let result = if !allowed {
+            match function_name {
+                "proc_exit" => None,
+                _ => Some(Value::I32(Errno::Notcapable as i32)),
+            }
+        } else {
+            // Like a system call, a pointer to memory that the program doesn't have is a bad address
+            self.dispatch_help(function_name, arguments, memory)
+                .unwrap_or(Some(Value::I32(Errno::Fault as i32)))
+        };
+
+        if let Some(audit) = self.audit.as_mut() {
+            audit(&WasiCall {
+                function_name,
+                arguments,
+                result,
+                allowed,
+            });
+        }
+
+        result
+    }
+
+    fn dispatch_help(
+        &mut self,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut Memory<'_>,
+    ) -> Result<Option<Value>, MemoryError> {
+        let success_code = Ok(Some(Value::I32(Errno::Success as i32)));
         match function_name {
             "args_get" => {
                 // uint8_t ** argv,
-                let mut ptr_ptr_argv = arguments[0].expect_i32().unwrap() as usize;
+                let ptr_ptr_argv = arguments[0].expect_i32().unwrap() as u32;
                 // uint8_t * argv_buf
-                let mut ptr_argv_buf = arguments[1].expect_i32().unwrap() as usize;
-
-                for arg in self.args {
-                    write_u32(memory, ptr_ptr_argv, ptr_argv_buf as u32);
-                    let bytes_target = &mut memory[ptr_argv_buf..][..arg.len()];
-                    bytes_target.copy_from_slice(arg);
-                    memory[ptr_argv_buf + arg.len()] = 0; // C string zero termination
-                    ptr_argv_buf += arg.len() + 1;
-                    ptr_ptr_argv += 4;
-                }
+                let ptr_argv_buf = arguments[1].expect_i32().unwrap() as u32;
 
+                write_c_strings(
+                    memory,
+                    self.args.iter().copied(),
+                    ptr_ptr_argv,
+                    ptr_argv_buf,
+                )?;
                 success_code
             }
             "args_sizes_get" => {
                 // (i32, i32) -> i32
 
                 // number of string arguments
-                let ptr_argc = arguments[0].expect_i32().unwrap() as usize;
+                let ptr_argc = arguments[0].expect_i32().unwrap() as u32;
                 // size of string arguments buffer
-                let ptr_argv_buf_size = arguments[1].expect_i32().unwrap() as usize;
+                let ptr_argv_buf_size = arguments[1].expect_i32().unwrap() as u32;
+
+                let (argc, argv_buf_size) = c_strings_size(self.args.iter().copied());
+                memory.write_u32(ptr_argc, argc)?;
+                memory.write_u32(ptr_argv_buf_size, argv_buf_size)?;
 
-                let argc = self.args.len() as u32;
-                write_u32(memory, ptr_argc, argc);
+                success_code
+            }
+            "environ_get" => {
+                // uint8_t ** environ
+                let ptr_ptr_environ = arguments[0].expect_i32().unwrap() as u32;
+                // uint8_t * environ_buf
+                let ptr_environ_buf = arguments[1].expect_i32().unwrap() as u32;
+
+                let vars = self.env.iter().map(Vec::as_slice);
+                write_c_strings(memory, vars, ptr_ptr_environ, ptr_environ_buf)?;
+                success_code
+            }
+            "environ_sizes_get" => {
+                // number of environment variables
+                let ptr_count = arguments[0].expect_i32().unwrap() as u32;
+                // size of environment variables buffer
+                let ptr_environ_buf_size = arguments[1].expect_i32().unwrap() as u32;
 
-                let argv_buf_size: u32 = self.args.iter().map(|a| 1 + a.len() as u32).sum();
-                write_u32(memory, ptr_argv_buf_size, argv_buf_size);
+                let (count, environ_buf_size) = c_strings_size(self.env.iter().map(Vec::as_slice));
+                memory.write_u32(ptr_count, count)?;
+                memory.write_u32(ptr_environ_buf_size, environ_buf_size)?;
 
                 success_code
             }
-            "environ_get" => todo!("WASI {}({:?})", function_name, arguments),
-            "environ_sizes_get" => todo!("WASI {}({:?})", function_name, arguments),
-            "clock_res_get" => success_code, // this dummy implementation seems to be good enough for some functions
-            "clock_time_get" => success_code,
+            "clock_res_get" => {
+                // The clock to query
+                let clock_id = arguments[0].expect_i32().unwrap() as u32;
+                // ptr to a u64 where the resolution in nanoseconds will be written
+                let ptr_resolution = arguments[1].expect_i32().unwrap() as u32;
+
+                let resolution = ClockId::from_u32(clock_id)
+                    .and_then(|clock_id| self.clock.resolution(clock_id));
+                match resolution {
+                    Some(resolution) => {
+                        memory.write_u64(ptr_resolution, resolution)?;
+                        success_code
+                    }
+                    None => Ok(Some(Value::I32(Errno::Inval as i32))),
+                }
+            }
+            "clock_time_get" => {
+                // The clock to query
+                let clock_id = arguments[0].expect_i32().unwrap() as u32;
+                // arguments[1] is the precision the program wants, which any clock satisfies here
+                // ptr to a u64 where the time in nanoseconds will be written
+                let ptr_time = arguments[2].expect_i32().unwrap() as u32;
+
+                let time =
+                    ClockId::from_u32(clock_id).and_then(|clock_id| self.clock.time(clock_id));
+                match time {
+                    Some(time) => {
+                        memory.write_u64(ptr_time, time)?;
+                        success_code
+                    }
+                    None => Ok(Some(Value::I32(Errno::Inval as i32))),
+                }
+            }
             "fd_advise" => todo!("WASI {}({:?})", function_name, arguments),
             "fd_allocate" => todo!("WASI {}({:?})", function_name, arguments),
-            "fd_close" => todo!("WASI {}({:?})", function_name, arguments),
+            "fd_close" => {
+                // file descriptor
+                let fd = arguments[0].expect_i32().unwrap() as usize;
+
+                // Dropping a host file closes it
+                match self.files.get_mut(fd) {
+                    None | Some(WasiFile::Closed) => Ok(Some(Value::I32(Errno::Badf as i32))),
+                    Some(file) => {
+                        *file = WasiFile::Closed;
+                        success_code
+                    }
+                }
+            }
             "fd_datasync" => todo!("WASI {}({:?})", function_name, arguments),
             "fd_fdstat_get" => {
                 // (i32, i32) -> i32
@@ -102,7 +455,7 @@ impl<'a> WasiDispatcher<'a> {
                 // file descriptor
                 let fd = arguments[0].expect_i32().unwrap() as usize;
                 // ptr to a wasi_fdstat_t
-                let stat_mut_ptr = arguments[1].expect_i32().unwrap() as usize;
+                let stat_mut_ptr = arguments[1].expect_i32().unwrap() as u32;
 
                 match fd {
                     1 => {
@@ -116,13 +469,27 @@ impl<'a> WasiDispatcher<'a> {
                         //     .fs_rights_base = 0
 
                         const WASI_FILETYPE_CHARACTER_DEVICE: u8 = 2;
-                        memory[stat_mut_ptr] = WASI_FILETYPE_CHARACTER_DEVICE;
+                        memory.write_u8(stat_mut_ptr, WASI_FILETYPE_CHARACTER_DEVICE)?;
+                        let ptr_rest = offset_addr(memory, stat_mut_ptr, 1)?;
+                        memory.bytes_mut(ptr_rest, 23)?.fill(0);
+                    }
+                    _ => {
+                        // wasi-libc looks up what a directory's files may be opened for, before
+                        // it opens one. Everything is allowed, the host decides what succeeds.
+                        const WASI_FILETYPE_DIRECTORY: u8 = 3;
+                        const WASI_FILETYPE_REGULAR_FILE: u8 = 4;
+                        let filetype = match self.files.get(fd) {
+                            Some(WasiFile::PreopenDir { .. }) => WASI_FILETYPE_DIRECTORY,
+                            Some(WasiFile::HostFile(_)) => WASI_FILETYPE_REGULAR_FILE,
+                            _ => todo!("WASI {}({:?})", function_name, arguments),
+                        };
 
-                        for b in memory[stat_mut_ptr + 1..stat_mut_ptr + 24].iter_mut() {
-                            *b = 0;
-                        }
+                        // filetype: u8, flags: u16, rights_base: u64, rights_inheriting: u64
+                        let stat = memory.bytes_mut(stat_mut_ptr, 24)?;
+                        stat.fill(0);
+                        stat[0] = filetype;
+                        stat[8..].fill(0xff);
                     }
-                    _ => todo!("WASI {}({:?})", function_name, arguments),
                 }
 
                 success_code
@@ -139,18 +506,34 @@ impl<'a> WasiDispatcher<'a> {
                 // ptr_buf: Where the metadata will be written
                 //  preopen type: 4 bytes, where 0=dir is the only one supported, it seems
                 //  preopen name length: 4 bytes
-                let ptr_buf = arguments[1].expect_i32().unwrap() as usize;
-                memory[ptr_buf..][..8].copy_from_slice(&0u64.to_le_bytes());
-                if fd < self.files.len() {
-                    success_code
-                } else {
-                    println!("WASI warning: file descriptor {} does not exist", fd);
-                    Some(Value::I32(Errno::Badf as i32))
+                let ptr_buf = arguments[1].expect_i32().unwrap() as u32;
+
+                // The program asks about each fd after stdio until it gets Badf
+                match self.files.get(fd) {
+                    Some(WasiFile::PreopenDir { name, .. }) => {
+                        memory.write_u32(ptr_buf, 0)?;
+                        let ptr_name_len = offset_addr(memory, ptr_buf, 4)?;
+                        memory.write_u32(ptr_name_len, name.len() as u32)?;
+                        success_code
+                    }
+                    _ => Ok(Some(Value::I32(Errno::Badf as i32))),
                 }
             }
             "fd_prestat_dir_name" => {
-                // We're not giving names to any of our files so just return success
-                success_code
+                // The preopened file descriptor to query
+                let fd = arguments[0].expect_i32().unwrap() as usize;
+                // Where to write the name, which is not zero terminated
+                let ptr_path = arguments[1].expect_i32().unwrap() as u32;
+                let path_len = arguments[2].expect_i32().unwrap() as u32;
+
+                match self.files.get(fd) {
+                    Some(WasiFile::PreopenDir { name, .. }) => {
+                        let len = path_len.min(name.len() as u32);
+                        memory.write_bytes(ptr_path, &name.as_bytes()[..len as usize])?;
+                        success_code
+                    }
+                    _ => Ok(Some(Value::I32(Errno::Badf as i32))),
+                }
             }
             "fd_pwrite" => todo!("WASI {}({:?})", function_name, arguments),
             "fd_read" => {
@@ -159,52 +542,30 @@ impl<'a> WasiDispatcher<'a> {
                 // file descriptor
                 let fd = arguments[0].expect_i32().unwrap() as usize;
                 // Array of IO vectors
-                let ptr_iovs = arguments[1].expect_i32().unwrap() as usize;
+                let ptr_iovs = arguments[1].expect_i32().unwrap() as u32;
                 // Length of array
                 let iovs_len = arguments[2].expect_i32().unwrap();
                 // Out param: number of bytes read
-                let ptr_nread = arguments[3].expect_i32().unwrap() as usize;
+                let ptr_nread = arguments[3].expect_i32().unwrap() as u32;
 
-                // https://man7.org/linux/man-pages/man2/readv.2.html
-                // struct iovec {
-                //     void  *iov_base;    /* Starting address */
-                //     size_t iov_len;     /* Number of bytes to transfer */
-                // };
-
-                let mut n_read: usize = 0;
-                match self.files.get(fd) {
+                let read_result = match self.files.get_mut(fd) {
                     Some(ReadOnly(content) | ReadWrite(content)) => {
-                        for _ in 0..iovs_len {
-                            let iov_base = read_u32(memory, ptr_iovs) as usize;
-                            let iov_len = read_i32(memory, ptr_iovs + 4) as usize;
-                            let remaining = content.len() - n_read;
-                            let len = remaining.min(iov_len);
-                            if len == 0 {
-                                break;
-                            }
-                            memory[iov_base..][..len].copy_from_slice(&content[n_read..][..len]);
-                            n_read += len;
-                        }
+                        read_iovs(&mut content.as_slice(), memory, ptr_iovs, iovs_len)?
                     }
                     Some(HostSystemFile) if fd == 0 => {
-                        let mut stdin = io::stdin();
-                        for _ in 0..iovs_len {
-                            let iov_base = read_u32(memory, ptr_iovs) as usize;
-                            let iov_len = read_i32(memory, ptr_iovs + 4) as usize;
-                            match stdin.read(&mut memory[iov_base..][..iov_len]) {
-                                Ok(n) => {
-                                    n_read += n;
-                                }
-                                Err(_) => {
-                                    break;
-                                }
-                            }
-                        }
+                        read_iovs(&mut io::stdin().lock(), memory, ptr_iovs, iovs_len)?
                     }
-                    _ => return Some(Value::I32(Errno::Badf as i32)),
+                    Some(Source(source)) => read_iovs(source.as_mut(), memory, ptr_iovs, iovs_len)?,
+                    Some(HostFile(file)) => read_iovs(file, memory, ptr_iovs, iovs_len)?,
+                    _ => return Ok(Some(Value::I32(Errno::Badf as i32))),
+                };
+
+                let n_read = match read_result {
+                    Ok(n_read) => n_read,
+                    Err(_) => return Ok(Some(Value::I32(Errno::Io as i32))),
                 };
 
-                memory[ptr_nread..][..4].copy_from_slice(&(n_read as u32).to_le_bytes());
+                memory.write_u32(ptr_nread, n_read as u32)?;
                 success_code
             }
             "fd_readdir" => todo!("WASI {}({:?})", function_name, arguments),
@@ -218,11 +579,11 @@ impl<'a> WasiDispatcher<'a> {
                 // file descriptor
                 let fd = arguments[0].expect_i32().unwrap() as usize;
                 // Array of IO vectors
-                let ptr_iovs = arguments[1].expect_i32().unwrap() as usize;
+                let ptr_iovs = arguments[1].expect_i32().unwrap() as u32;
                 // Length of array
                 let iovs_len = arguments[2].expect_i32().unwrap();
                 // Out param: number of bytes written
-                let ptr_nwritten = arguments[3].expect_i32().unwrap() as usize;
+                let ptr_nwritten = arguments[3].expect_i32().unwrap() as u32;
 
                 // Grab a lock for stdout/stderr before the loop rather than re-acquiring over and over.
                 // Not really necessary for other files, but it's easier to use the same structure.
@@ -230,12 +591,14 @@ impl<'a> WasiDispatcher<'a> {
                     Some(HostSystemFile) => match fd {
                         1 => WriteLock::StdOut(io::stdout().lock()),
                         2 => WriteLock::Stderr(io::stderr().lock()),
-                        _ => return Some(Value::I32(Errno::Inval as i32)),
+                        _ => return Ok(Some(Value::I32(Errno::Inval as i32))),
                     },
                     Some(WriteOnly(content) | ReadWrite(content)) => {
                         WriteLock::RegularFile(content)
                     }
-                    _ => return Some(Value::I32(Errno::Badf as i32)),
+                    Some(Sink(sink)) => WriteLock::Sink(sink.as_mut()),
+                    Some(HostFile(file)) => WriteLock::Sink(file),
+                    _ => return Ok(Some(Value::I32(Errno::Badf as i32))),
                 };
 
                 let mut n_written: i32 = 0;
@@ -247,9 +610,9 @@ impl<'a> WasiDispatcher<'a> {
                     //     void  *iov_base;    /* Starting address */
                     //     size_t iov_len;     /* Number of bytes to transfer */
                     // };
-                    let ptr_iov = ptr_iovs + (8 * i as usize); // index into the array of iovec's
-                    let iov_base = read_u32(memory, ptr_iov) as usize;
-                    let iov_len = read_i32(memory, ptr_iov + 4);
+                    let ptr_iov = iovec_addr(memory, ptr_iovs, i as u32)?;
+                    let iov_base = memory.read_u32(ptr_iov)?;
+                    let iov_len = memory.read_i32(offset_addr(memory, ptr_iov, 4)?)?;
                     if iov_len < 0 {
                         // I found negative-length iov's when I implemented this in JS for the web REPL (see wasi.js)
                         // I'm not sure why, but this solution worked, and it's the same WASI libc - there's only one.
@@ -257,12 +620,13 @@ impl<'a> WasiDispatcher<'a> {
                         negative_length_count += 1;
                         continue;
                     }
-                    let bytes = &memory[iov_base..][..iov_len as usize];
+                    let bytes = memory.read_bytes(iov_base, iov_len as u32)?;
 
                     write_result = match &mut write_lock {
                         WriteLock::StdOut(stdout) => stdout.write_all(bytes),
                         WriteLock::Stderr(stderr) => stderr.write_all(bytes),
                         WriteLock::RegularFile(content) => content.write_all(bytes),
+                        WriteLock::Sink(sink) => sink.write_all(bytes),
                     };
                     if write_result.is_err() {
                         break;
@@ -270,7 +634,7 @@ impl<'a> WasiDispatcher<'a> {
                     n_written += bytes.len() as i32;
                 }
 
-                write_i32(memory, ptr_nwritten, n_written);
+                memory.write_i32(ptr_nwritten, n_written)?;
                 if negative_length_count > 0 {
                     // Let's see if we ever get this message. If not, we can remove this negative-length stuff.
                     eprintln!(
@@ -281,14 +645,54 @@ impl<'a> WasiDispatcher<'a> {
 
                 match write_result {
                     Ok(()) => success_code,
-                    Err(_) => Some(Value::I32(Errno::Io as i32)),
+                    Err(_) => Ok(Some(Value::I32(Errno::Io as i32))),
                 }
             }
             "path_create_directory" => todo!("WASI {}({:?})", function_name, arguments),
             "path_filestat_get" => todo!("WASI {}({:?})", function_name, arguments),
             "path_filestat_set_times" => todo!("WASI {}({:?})", function_name, arguments),
             "path_link" => todo!("WASI {}({:?})", function_name, arguments),
-            "path_open" => todo!("WASI {}({:?})", function_name, arguments),
+            "path_open" => {
+                // The preopened directory that the path is in
+                let fd = arguments[0].expect_i32().unwrap() as usize;
+                // arguments[1] says whether to follow symbolic links, which are always followed
+                let ptr_path = arguments[2].expect_i32().unwrap() as u32;
+                let path_len = arguments[3].expect_i32().unwrap() as u32;
+                // Whether to create, truncate, etc.
+                let oflags = arguments[4].expect_i32().unwrap() as u16;
+                // What the file is opened for, reading or writing
+                let rights = arguments[5].expect_i64().unwrap() as u64;
+                // arguments[6] is what files opened through this one may do, but a file can't
+                // open anything
+                let fdflags = arguments[7].expect_i32().unwrap() as u16;
+                // Out param: the new file descriptor
+                let ptr_opened_fd = arguments[8].expect_i32().unwrap() as u32;
+
+                let dir = match self.files.get(fd) {
+                    Some(WasiFile::PreopenDir {
+                        host_path: Some(dir),
+                        ..
+                    }) => dir,
+                    Some(WasiFile::PreopenDir {
+                        host_path: None, ..
+                    }) => return Ok(Some(Value::I32(Errno::Notcapable as i32))),
+                    _ => return Ok(Some(Value::I32(Errno::Badf as i32))),
+                };
+                let path = match std::str::from_utf8(memory.read_bytes(ptr_path, path_len)?) {
+                    Ok(path) => path,
+                    Err(_) => return Ok(Some(Value::I32(Errno::Ilseq as i32))),
+                };
+
+                match open_in_dir(dir, path, oflags, rights, fdflags) {
+                    Ok(file) => {
+                        // If this is a bad address, the file is dropped, which closes it again
+                        memory.write_u32(ptr_opened_fd, self.files.len() as u32)?;
+                        self.files.push(WasiFile::HostFile(file));
+                        success_code
+                    }
+                    Err(errno) => Ok(Some(Value::I32(errno as i32))),
+                }
+            }
             "path_readlink" => todo!("WASI {}({:?})", function_name, arguments),
             "path_remove_directory" => todo!("WASI {}({:?})", function_name, arguments),
             "path_rename" => todo!("WASI {}({:?})", function_name, arguments),
@@ -296,18 +700,20 @@ impl<'a> WasiDispatcher<'a> {
             "path_unlink_file" => todo!("WASI {}({:?})", function_name, arguments),
             "poll_oneoff" => todo!("WASI {}({:?})", function_name, arguments),
             "proc_exit" => {
+                // The instance sees this and ends the call, since the program can't continue
                 let exit_code = arguments[0].expect_i32().unwrap();
-                exit(exit_code);
+                self.exit_status = Some(exit_code);
+                Ok(None)
             }
             "proc_raise" => todo!("WASI {}({:?})", function_name, arguments),
             "sched_yield" => todo!("WASI {}({:?})", function_name, arguments),
             "random_get" => {
                 // A pointer to a buffer where the random bytes will be written
-                let ptr_buf = arguments[0].expect_i32().unwrap() as usize;
+                let ptr_buf = arguments[0].expect_i32().unwrap() as u32;
                 // The number of bytes that will be written
-                let buf_len = arguments[1].expect_i32().unwrap() as usize;
-                for i in 0..buf_len {
-                    memory[ptr_buf + i] = self.rng.gen();
+                let buf_len = arguments[1].expect_i32().unwrap() as u32;
+                for byte in memory.bytes_mut(ptr_buf, buf_len)?.iter_mut() {
+                    *byte = self.rng.gen();
                 }
                 success_code
             }
@@ -319,24 +725,192 @@ impl<'a> WasiDispatcher<'a> {
     }
 }
 
-fn read_u32(memory: &[u8], addr: usize) -> u32 {
-    let mut bytes = [0; 4];
-    bytes.copy_from_slice(&memory[addr..][..4]);
-    u32::from_le_bytes(bytes)
+/// How a WASI program ended, like the exit status of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(pub i32);
+
+impl ExitStatus {
+    pub fn success(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Run a WASI program's `_start` with the [DefaultImportDispatcher], the way a shell runs a
+/// command. `args` is its `argv`, starting with the program's name, `env` its environment
+/// variables, and `preopens` the names of its preopened directories.
+///
+/// The exit status is the one passed to `proc_exit`, or the value `_start` returns if it returns
+/// an I32, or otherwise 0. A trap is an error.
+pub fn run_wasi(
+    module_bytes: &[u8],
+    args: &[&[u8]],
+    env: &[(&str, &str)],
+    preopens: &[&str],
+) -> Result<ExitStatus, String> {
+    let arena = Bump::new();
+
+    let mut dispatcher = DefaultImportDispatcher::new(args);
+    for (key, value) in env {
+        dispatcher.wasi.set_env(key, value);
+    }
+    for name in preopens {
+        dispatcher.wasi.preopen_dir(name);
+    }
+
+    let is_debug_mode = false;
+    let mut instance = Instance::from_bytes(&arena, module_bytes, dispatcher, is_debug_mode)?;
+
+    match instance.call_export("_start", []) {
+        Ok(Some(Value::I32(status))) => Ok(ExitStatus(status)),
+        Ok(_) => Ok(ExitStatus(0)),
+        Err(e) => instance
+            .exit_status()
+            .map(ExitStatus)
+            .ok_or_else(|| e.into()),
+    }
+}
+
+/// Write zero-terminated strings to `ptr_buf`, and a pointer to each one to the array at
+/// `ptr_ptrs`, like `args_get` and `environ_get` do
+fn write_c_strings<'s, I: Iterator<Item = &'s [u8]>>(
+    memory: &mut Memory<'_>,
+    strings: I,
+    mut ptr_ptrs: u32,
+    mut ptr_buf: u32,
+) -> Result<(), MemoryError> {
+    for string in strings {
+        memory.write_u32(ptr_ptrs, ptr_buf)?;
+        memory.write_bytes(ptr_buf, string)?;
+        let ptr_end = offset_addr(memory, ptr_buf, string.len() as u32)?;
+        memory.write_u8(ptr_end, 0)?; // C string zero termination
+        ptr_buf = offset_addr(memory, ptr_end, 1)?;
+        ptr_ptrs = offset_addr(memory, ptr_ptrs, 4)?;
+    }
+    Ok(())
 }
 
-fn read_i32(memory: &[u8], addr: usize) -> i32 {
-    let mut bytes = [0; 4];
-    bytes.copy_from_slice(&memory[addr..][..4]);
-    i32::from_le_bytes(bytes)
+/// The number of strings, and the size of the buffer that `write_c_strings` needs for them
+fn c_strings_size<'s, I: Iterator<Item = &'s [u8]>>(strings: I) -> (u32, u32) {
+    strings.fold((0, 0), |(count, size), string| {
+        (count + 1, size + string.len() as u32 + 1)
+    })
 }
 
-fn write_u32(memory: &mut [u8], addr: usize, value: u32) {
-    memory[addr..][..4].copy_from_slice(&value.to_le_bytes());
+/// Open a file for `path_open`, unless its path leads out of `dir`
+fn open_in_dir(
+    dir: &Path,
+    path: &str,
+    oflags: u16,
+    rights: u64,
+    fdflags: u16,
+) -> Result<File, Errno> {
+    const OFLAGS_CREAT: u16 = 1 << 0;
+    const OFLAGS_DIRECTORY: u16 = 1 << 1;
+    const OFLAGS_EXCL: u16 = 1 << 2;
+    const OFLAGS_TRUNC: u16 = 1 << 3;
+    const FDFLAGS_APPEND: u16 = 1 << 0;
+    const RIGHTS_FD_READ: u64 = 1 << 1;
+    const RIGHTS_FD_WRITE: u64 = 1 << 6;
+
+    let path = Path::new(path);
+    let stays_in_dir = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !stays_in_dir {
+        return Err(Errno::Notcapable);
+    }
+    if oflags & OFLAGS_DIRECTORY != 0 {
+        // Directories can only be preopened
+        return Err(Errno::Notsup);
+    }
+
+    let read = rights & RIGHTS_FD_READ != 0;
+    let write = rights & RIGHTS_FD_WRITE != 0;
+    let append = fdflags & FDFLAGS_APPEND != 0;
+    let create = oflags & OFLAGS_CREAT != 0;
+
+    let mut options = OpenOptions::new();
+    options
+        .read(read || !(write || append))
+        .write(write)
+        .append(append)
+        .truncate(oflags & OFLAGS_TRUNC != 0)
+        .create(create)
+        .create_new(create && oflags & OFLAGS_EXCL != 0);
+
+    let file = options.open(dir.join(path)).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Errno::Noent,
+        io::ErrorKind::PermissionDenied => Errno::Access,
+        io::ErrorKind::AlreadyExists => Errno::Exist,
+        io::ErrorKind::InvalidInput => Errno::Inval,
+        _ => Errno::Io,
+    })?;
+
+    match file.metadata() {
+        Ok(metadata) if metadata.is_dir() => Err(Errno::Isdir),
+        Ok(_) => Ok(file),
+        Err(_) => Err(Errno::Io),
+    }
 }
 
-fn write_i32(memory: &mut [u8], addr: usize, value: i32) {
-    memory[addr..][..4].copy_from_slice(&value.to_le_bytes());
+/// The address `offset` bytes after `addr`. One past the end of the 32-bit address space is a bad
+/// address, like any other that's outside of memory.
+fn offset_addr(memory: &Memory<'_>, addr: u32, offset: u32) -> Result<u32, MemoryError> {
+    addr.checked_add(offset).ok_or(MemoryError::OutOfBounds {
+        addr,
+        len: offset,
+        memory_size: memory.len(),
+    })
+}
+
+/// The address of the `index`th IO vector in the array at `ptr_iovs`. Each one is 8 bytes.
+fn iovec_addr(memory: &Memory<'_>, ptr_iovs: u32, index: u32) -> Result<u32, MemoryError> {
+    let offset = index.checked_mul(8).ok_or(MemoryError::OutOfBounds {
+        addr: ptr_iovs,
+        len: u32::MAX,
+        memory_size: memory.len(),
+    })?;
+    offset_addr(memory, ptr_iovs, offset)
+}
+
+/// Fill an array of IO vectors from `reader`, like `readv`. Returns the total number of bytes
+/// read, where zero means end of file. A short read ends the call without blocking on the
+/// remaining vectors, so that interactive input is passed on as soon as it is available.
+/// An IO vector outside of memory is an error, even if the ones before it were already filled.
+fn read_iovs(
+    reader: &mut dyn Read,
+    memory: &mut Memory<'_>,
+    ptr_iovs: u32,
+    iovs_len: i32,
+) -> Result<io::Result<usize>, MemoryError> {
+    // https://man7.org/linux/man-pages/man2/readv.2.html
+    // struct iovec {
+    //     void  *iov_base;    /* Starting address */
+    //     size_t iov_len;     /* Number of bytes to transfer */
+    // };
+    let mut n_read = 0;
+    for i in 0..iovs_len.max(0) as u32 {
+        let ptr_iov = iovec_addr(memory, ptr_iovs, i)?;
+        let iov_base = memory.read_u32(ptr_iov)?;
+        let iov_len = memory.read_u32(offset_addr(memory, ptr_iov, 4)?)?;
+        let buf = memory.bytes_mut(iov_base, iov_len)?;
+
+        let n = loop {
+            match reader.read(buf) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // Report what we already have, the error will come up again on the next call
+                Err(_) if n_read > 0 => return Ok(Ok(n_read)),
+                Err(e) => return Ok(Err(e)),
+            }
+        };
+
+        n_read += n;
+        if n < iov_len as usize {
+            break;
+        }
+    }
+    Ok(Ok(n_read))
 }
 
 /// Error codes returned by functions.
@@ -0,0 +1,131 @@
+use roc_wasm_module::Value;
+use std::fmt;
+use std::ops::Range;
+
+/// Which accesses to a watched range of memory are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// What happens when a watchpoint is hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAction {
+    /// End the call before the access, with an error describing it and a stack trace
+    Trap,
+    /// Let the access happen. A call started by [Instance::begin_call](crate::Instance::begin_call)
+    /// then yields from [run_for](crate::Instance::run_for) right after it, so that the memory can
+    /// be inspected before carrying on. Other calls just carry on.
+    Pause,
+}
+
+/// A range of memory to watch for loads or stores, for tracking down memory corruption.
+/// Only the load and store instructions are checked, not bulk memory instructions or imports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// Addresses in the instance's memory. An access is reported if any of its bytes are in here.
+    pub range: Range<u32>,
+    pub access: WatchAccess,
+    pub action: WatchAction,
+}
+
+/// A load or store that touched a watched range of memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchpointHit {
+    /// The index of the watchpoint, as returned by [Instance::add_watchpoint](crate::Instance::add_watchpoint)
+    pub watchpoint: usize,
+    /// The value stored, or `None` for a load
+    pub stored: Option<Value>,
+    /// Address of the first byte accessed
+    pub addr: u32,
+    /// Number of bytes accessed
+    pub size: u32,
+    /// The function the instruction is in
+    pub fn_index: usize,
+    /// File offset of the load or store instruction
+    pub file_offset: usize,
+}
+
+impl fmt::Display for WatchpointHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stored {
+            Some(value) => write!(f, "Store of {:?} to", value)?,
+            None => write!(f, "Load from")?,
+        }
+        write!(
+            f,
+            " {} bytes at {:#x}, by the instruction at file offset {:#x} in func[{}], hit watchpoint {}",
+            self.size, self.addr, self.file_offset, self.fn_index, self.watchpoint
+        )
+    }
+}
+
+/// The watchpoints of an instance, and what they've caught
+#[derive(Debug, Default)]
+pub(crate) struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    pub hits: Vec<WatchpointHit>,
+    /// Whether a [WatchAction::Pause] watchpoint was hit by the last instruction
+    pause: bool,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, watchpoint: Watchpoint) -> usize {
+        self.watchpoints.push(watchpoint);
+        self.watchpoints.len() - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.watchpoints.clear();
+        self.hits.clear();
+        self.pause = false;
+    }
+
+    /// Record an access if it hits a watchpoint. Returns the hit if it should trap.
+    pub fn check(
+        &mut self,
+        addr: usize,
+        size: usize,
+        stored: Option<Value>,
+        fn_index: usize,
+        file_offset: usize,
+    ) -> Result<(), WatchpointHit> {
+        let access = addr as u64..addr as u64 + size as u64;
+
+        for (index, watchpoint) in self.watchpoints.iter().enumerate() {
+            let kind_matches = match watchpoint.access {
+                WatchAccess::Read => stored.is_none(),
+                WatchAccess::Write => stored.is_some(),
+                WatchAccess::ReadWrite => true,
+            };
+            let overlaps = access.start < watchpoint.range.end as u64
+                && (watchpoint.range.start as u64) < access.end;
+
+            if kind_matches && overlaps {
+                let hit = WatchpointHit {
+                    watchpoint: index,
+                    stored,
+                    addr: addr as u32,
+                    size: size as u32,
+                    fn_index,
+                    file_offset,
+                };
+                self.hits.push(hit.clone());
+
+                match watchpoint.action {
+                    WatchAction::Trap => return Err(hit),
+                    WatchAction::Pause => self.pause = true,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the last instruction hit a [WatchAction::Pause] watchpoint, resetting it
+    pub fn take_pause(&mut self) -> bool {
+        std::mem::take(&mut self.pause)
+    }
+}
@@ -0,0 +1,14 @@
+#![no_main]
+use bumpalo::Bump;
+use libfuzzer_sys::fuzz_target;
+use roc_wasm_module::WasmModule;
+
+fuzz_target!(|data: &[u8]| {
+    // Decoding arbitrary bytes should return an error, never panic
+    let arena = Bump::new();
+    if let Ok(module) = WasmModule::preload(&arena, data, false) {
+        // Some sections are only decoded when they're used
+        let _ = module.memory.max_bytes();
+        let _ = module.global.initial_values(&arena, &[]);
+    }
+});
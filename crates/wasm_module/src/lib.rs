@@ -19,8 +19,9 @@ use self::parse::{Parse, ParseError};
 use self::sections::{
     CodeSection, DataSection, ElementSection, ExportSection, FunctionSection, GlobalSection,
     ImportDesc, ImportSection, MemorySection, NameSection, OpaqueSection, Section, SectionId,
-    TableSection, TypeSection,
+    StartSection, TableSection, TypeSection,
 };
+use self::serialize::MAX_SIZE_ENCODED_U32;
 pub use self::serialize::{SerialBuffer, Serialize};
 
 pub const STACK_POINTER_GLOBAL_ID: u32 = 0;
@@ -37,7 +38,7 @@ pub struct WasmModule<'a> {
     pub memory: MemorySection<'a>,
     pub global: GlobalSection<'a>,
     pub export: ExportSection<'a>,
-    pub start: OpaqueSection<'a>,
+    pub start: StartSection,
     pub element: ElementSection<'a>,
     pub code: CodeSection<'a>,
     pub data: DataSection<'a>,
@@ -59,7 +60,7 @@ impl<'a> WasmModule<'a> {
             memory: MemorySection::new(arena, 0),
             global: GlobalSection::new(arena),
             export: ExportSection::new(arena),
-            start: OpaqueSection::new(),
+            start: StartSection::new(),
             element: ElementSection::new(arena),
             code: CodeSection::new(arena),
             data: DataSection::new(arena),
@@ -78,6 +79,14 @@ impl<'a> WasmModule<'a> {
 
     /// Serialize the module to bytes
     pub fn serialize<T: SerialBuffer>(&self, buffer: &mut T) {
+        self.serialize_up_to_code(buffer);
+        self.code.serialize(buffer);
+        self.data.serialize(buffer);
+        self.names.serialize(buffer);
+    }
+
+    /// Serialize the header and all the sections that come before the Code section
+    fn serialize_up_to_code<T: SerialBuffer>(&self, buffer: &mut T) {
         buffer.append_u8(0);
         buffer.append_slice("asm".as_bytes());
         buffer.write_unencoded_u32(Self::WASM_VERSION);
@@ -93,9 +102,19 @@ impl<'a> WasmModule<'a> {
         self.export.serialize(buffer);
         self.start.serialize(buffer);
         self.element.serialize(buffer);
-        self.code.serialize(buffer);
-        self.data.serialize(buffer);
-        self.names.serialize(buffer);
+    }
+
+    /// Get a module that was built in memory ready to run in an interpreter, without serializing
+    /// it and parsing it again. Call it once the module is finished, after dead code elimination.
+    /// The dummy functions for eliminated imports are put into the Code section, and code
+    /// addresses are made to match the file that `serialize` would write.
+    pub fn prepare_to_run(&mut self, arena: &'a Bump) {
+        self.code.insert_dead_import_dummies(arena);
+
+        let mut buffer = std::vec::Vec::with_capacity(self.size());
+        self.serialize_up_to_code(&mut buffer);
+        let section_header_size = std::mem::size_of::<SectionId>() + MAX_SIZE_ENCODED_U32;
+        self.code.section_offset = (buffer.len() + section_header_size) as u32;
     }
 
     /// Module size in bytes (assuming no linker data)
@@ -120,8 +139,8 @@ impl<'a> WasmModule<'a> {
         bytes: &[u8],
         require_relocatable: bool,
     ) -> Result<Self, ParseError> {
-        let is_valid_magic_number = &bytes[0..4] == "\0asm".as_bytes();
-        let is_valid_version = bytes[4..8] == Self::WASM_VERSION.to_le_bytes();
+        let is_valid_magic_number = bytes.get(0..4) == Some("\0asm".as_bytes());
+        let is_valid_version = bytes.get(4..8) == Some(&Self::WASM_VERSION.to_le_bytes()[..]);
         if !is_valid_magic_number || !is_valid_version {
             return Err(ParseError {
                 offset: 0,
@@ -139,7 +158,7 @@ impl<'a> WasmModule<'a> {
         let memory = MemorySection::parse(arena, bytes, &mut cursor)?;
         let global = GlobalSection::parse(arena, bytes, &mut cursor)?;
         let export = ExportSection::parse(arena, bytes, &mut cursor)?;
-        let start = OpaqueSection::parse((arena, SectionId::Start), bytes, &mut cursor)?;
+        let start = StartSection::parse((), bytes, &mut cursor)?;
         let element = ElementSection::parse(arena, bytes, &mut cursor)?;
         let _data_count = OpaqueSection::parse((arena, SectionId::DataCount), bytes, &mut cursor)?;
         let code = CodeSection::parse(arena, bytes, &mut cursor)?;
@@ -222,13 +241,14 @@ impl<'a> WasmModule<'a> {
         let fn_index_min = import_count as u32 + self.code.dead_import_dummy_count;
         let fn_index_max = called_fns.len() as u32;
 
-        // All functions exported to JS must be kept alive
+        // All functions exported to JS must be kept alive, and so must the start function
         let exported_fns = self
             .export
             .exports
             .iter()
             .filter(|ex| ex.ty == ExportType::Func)
-            .map(|ex| ex.index);
+            .map(|ex| ex.index)
+            .chain(self.start.function_index);
 
         // The ElementSection lists all functions whose "address" is taken.
         // Find their signatures so we can trace all possible indirect calls.
@@ -320,8 +340,10 @@ impl<'a> WasmModule<'a> {
         // Live function indices are unchanged, so no relocations are needed.
         //
         let mut buffer = Vec::with_capacity_in(self.code.bytes.len(), arena);
+        let mut function_offsets = Vec::with_capacity_in(self.code.function_offsets.len(), arena);
         self.code.function_count.serialize(&mut buffer);
         for (i, fn_index) in (fn_index_min..fn_index_max).enumerate() {
+            function_offsets.push(buffer.len() as u32);
             if live_flags[fn_index as usize] {
                 let code_start = self.code.function_offsets[i] as usize;
                 let code_end = if i < self.code.function_offsets.len() - 1 {
@@ -336,6 +358,7 @@ impl<'a> WasmModule<'a> {
         }
 
         self.code.bytes = buffer;
+        self.code.function_offsets = function_offsets;
     }
 
     fn trace_live_functions<I: Iterator<Item = u32>>(
@@ -635,10 +658,23 @@ pub enum ValueType {
     I64 = 0x7e,
     F32 = 0x7d,
     F64 = 0x7c,
+    V128 = 0x7b,
 }
 
 impl ValueType {
     pub const VOID: u8 = 0x40;
+
+    /// The value type with this encoding, if there is one
+    pub fn from_byte(x: u8) -> Option<Self> {
+        match x {
+            0x7f => Some(Self::I32),
+            0x7e => Some(Self::I64),
+            0x7d => Some(Self::F32),
+            0x7c => Some(Self::F64),
+            0x7b => Some(Self::V128),
+            _ => None,
+        }
+    }
 }
 
 impl Serialize for ValueType {
@@ -649,12 +685,9 @@ impl Serialize for ValueType {
 
 impl From<u8> for ValueType {
     fn from(x: u8) -> Self {
-        match x {
-            0x7f => Self::I32,
-            0x7e => Self::I64,
-            0x7d => Self::F32,
-            0x7c => Self::F64,
-            _ => internal_error!("Invalid ValueType 0x{:02x}", x),
+        match Self::from_byte(x) {
+            Some(value_type) => value_type,
+            None => internal_error!("Invalid ValueType 0x{:02x}", x),
         }
     }
 }
@@ -666,6 +699,7 @@ impl From<Value> for ValueType {
             Value::I64(_) => Self::I64,
             Value::F32(_) => Self::F32,
             Value::F64(_) => Self::F64,
+            Value::V128(_) => Self::V128,
         }
     }
 }
@@ -673,7 +707,10 @@ impl From<Value> for ValueType {
 impl Parse<()> for ValueType {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
         let byte = u8::parse((), bytes, cursor)?;
-        Ok(ValueType::from(byte))
+        ValueType::from_byte(byte).ok_or_else(|| ParseError {
+            offset: *cursor - 1,
+            message: format!("Invalid ValueType 0x{:02x}", byte),
+        })
     }
 }
 
@@ -692,6 +729,9 @@ pub enum Value {
     I64(i64),
     F32(f32),
     F64(f64),
+    /// 128 bits, interpreted by each instruction as lanes of a particular type.
+    /// Lane 0 is in the least significant bits, matching the order in little-endian memory.
+    V128(u128),
 }
 
 impl Value {
@@ -719,6 +759,12 @@ impl Value {
             _ => Err((ValueType::F64, ValueType::from(*self))),
         }
     }
+    pub fn expect_v128(&self) -> Result<u128, (ValueType, ValueType)> {
+        match self {
+            Value::V128(x) => Ok(*x),
+            _ => Err((ValueType::V128, ValueType::from(*self))),
+        }
+    }
 }
 
 impl From<u32> for Value {
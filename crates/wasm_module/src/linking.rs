@@ -1,7 +1,7 @@
 use bumpalo::collections::vec::Vec;
 use bumpalo::Bump;
 
-use super::parse::{parse_fixed_size_items, Parse, ParseError, SkipBytes};
+use super::parse::{parse_fixed_size_items, peek_u8, Parse, ParseError, SkipBytes};
 use super::sections::SectionId;
 use super::serialize::{overwrite_padded_i32, overwrite_padded_u32};
 
@@ -119,8 +119,7 @@ pub enum RelocationEntry {
 
 impl Parse<()> for RelocationEntry {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let type_id_byte = bytes[*cursor];
-        *cursor += 1;
+        let type_id_byte = u8::parse((), bytes, cursor)?;
         let offset = u32::parse((), bytes, cursor)?;
         let symbol_index = u32::parse((), bytes, cursor)?;
 
@@ -480,8 +479,7 @@ enum SymType {
 impl Parse<()> for SymType {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
         let offset = *cursor;
-        let type_id = bytes[offset];
-        *cursor += 1;
+        let type_id = u8::parse((), bytes, cursor)?;
         match type_id {
             0 => Ok(Self::Function),
             1 => Ok(Self::Data),
@@ -526,9 +524,8 @@ enum SubSectionId {
 
 impl Parse<()> for SubSectionId {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let id = bytes[*cursor];
         let offset = *cursor;
-        *cursor += 1;
+        let id = u8::parse((), bytes, cursor)?;
         match id {
             5 => Ok(Self::SegmentInfo),
             6 => Ok(Self::InitFuncs),
@@ -645,7 +642,7 @@ impl<'a> Parse<&'a Bump> for LinkingSection<'a> {
             return Ok(LinkingSection::new(arena));
         }
 
-        let linking_version = bytes[*cursor];
+        let linking_version = peek_u8(bytes, *cursor)?;
         if linking_version != LINKING_VERSION {
             return Err(ParseError {
                 offset: *cursor,
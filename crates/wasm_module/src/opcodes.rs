@@ -1,6 +1,6 @@
 use crate::Serialize;
 
-use super::parse::{Parse, ParseError, SkipBytes};
+use super::parse::{peek_u8, Parse, ParseError, SkipBytes};
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -183,6 +183,20 @@ pub enum OpCode {
     I64REINTERPRETF64 = 0xbd,
     F32REINTERPRETI32 = 0xbe,
     F64REINTERPRETI64 = 0xbf,
+
+    /// Prefix for the instructions of the SIMD proposal. See [SimdOp].
+    SIMDPREFIX = 0xfd,
+}
+
+impl OpCode {
+    /// The opcode with this encoding, if there is one
+    pub fn from_byte(x: u8) -> Option<Self> {
+        let is_valid = matches!(
+            x,
+            0x00..=0x05 | 0x0b..=0x11 | 0x1a..=0x1b | 0x20..=0x24 | 0x28..=0xbf | 0xfd
+        );
+        is_valid.then(|| unsafe { std::mem::transmute(x) })
+    }
 }
 
 impl From<u8> for OpCode {
@@ -204,6 +218,7 @@ enum OpImmediates {
     Leb64x1,
     Leb32x2,
     BrTable,
+    Simd,
 }
 
 fn immediates_for(op: OpCode) -> Result<OpImmediates, String> {
@@ -257,6 +272,8 @@ fn immediates_for(op: OpCode) -> Result<OpImmediates, String> {
             NoImmediate
         }
 
+        SIMDPREFIX => Simd,
+
         // Catch-all in case of an invalid cast from u8 to OpCode while parsing binary
         // (rustc keeps this code, I verified in Compiler Explorer)
         #[allow(unreachable_patterns)]
@@ -270,10 +287,12 @@ impl SkipBytes for OpCode {
     fn skip_bytes(bytes: &[u8], cursor: &mut usize) -> Result<(), ParseError> {
         use OpImmediates::*;
 
-        let opcode_byte: u8 = bytes[*cursor];
+        let opcode_byte: u8 = peek_u8(bytes, *cursor)?;
 
-        let opcode: OpCode = OpCode::from(opcode_byte);
-        // will return Err if transmute was invalid
+        let opcode = OpCode::from_byte(opcode_byte).ok_or_else(|| ParseError {
+            message: format!("Unknown Wasm instruction 0x{:02x}", opcode_byte),
+            offset: *cursor,
+        })?;
         let immediates = immediates_for(opcode).map_err(|message| ParseError {
             message,
             offset: *cursor,
@@ -312,11 +331,307 @@ impl SkipBytes for OpCode {
                     u32::skip_bytes(bytes, cursor)?;
                 }
             }
+            Simd => {
+                *cursor += 1;
+                let simd_op = u32::parse((), bytes, cursor)?;
+                if SimdOp::has_memarg(simd_op) {
+                    u32::skip_bytes(bytes, cursor)?;
+                    u32::skip_bytes(bytes, cursor)?;
+                }
+                *cursor += SimdOp::immediate_bytes(simd_op);
+            }
         }
         Ok(())
     }
 }
 
+macro_rules! simd_ops {
+    ($($name: ident = $value: literal,)*) => {
+        /// Instructions of the SIMD proposal, which follow [OpCode::SIMDPREFIX] as a LEB-128 u32.
+        #[repr(u32)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum SimdOp {
+            $($name = $value,)*
+        }
+
+        impl SimdOp {
+            pub fn from_u32(x: u32) -> Option<Self> {
+                match x {
+                    $($value => Some(SimdOp::$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+simd_ops! {
+    V128LOAD = 0x00,
+    V128LOAD8X8S = 0x01,
+    V128LOAD8X8U = 0x02,
+    V128LOAD16X4S = 0x03,
+    V128LOAD16X4U = 0x04,
+    V128LOAD32X2S = 0x05,
+    V128LOAD32X2U = 0x06,
+    V128LOAD8SPLAT = 0x07,
+    V128LOAD16SPLAT = 0x08,
+    V128LOAD32SPLAT = 0x09,
+    V128LOAD64SPLAT = 0x0a,
+    V128STORE = 0x0b,
+    V128CONST = 0x0c,
+    I8X16SHUFFLE = 0x0d,
+    I8X16SWIZZLE = 0x0e,
+    I8X16SPLAT = 0x0f,
+    I16X8SPLAT = 0x10,
+    I32X4SPLAT = 0x11,
+    I64X2SPLAT = 0x12,
+    F32X4SPLAT = 0x13,
+    F64X2SPLAT = 0x14,
+    I8X16EXTRACTLANES = 0x15,
+    I8X16EXTRACTLANEU = 0x16,
+    I8X16REPLACELANE = 0x17,
+    I16X8EXTRACTLANES = 0x18,
+    I16X8EXTRACTLANEU = 0x19,
+    I16X8REPLACELANE = 0x1a,
+    I32X4EXTRACTLANE = 0x1b,
+    I32X4REPLACELANE = 0x1c,
+    I64X2EXTRACTLANE = 0x1d,
+    I64X2REPLACELANE = 0x1e,
+    F32X4EXTRACTLANE = 0x1f,
+    F32X4REPLACELANE = 0x20,
+    F64X2EXTRACTLANE = 0x21,
+    F64X2REPLACELANE = 0x22,
+
+    I8X16EQ = 0x23,
+    I8X16NE = 0x24,
+    I8X16LTS = 0x25,
+    I8X16LTU = 0x26,
+    I8X16GTS = 0x27,
+    I8X16GTU = 0x28,
+    I8X16LES = 0x29,
+    I8X16LEU = 0x2a,
+    I8X16GES = 0x2b,
+    I8X16GEU = 0x2c,
+    I16X8EQ = 0x2d,
+    I16X8NE = 0x2e,
+    I16X8LTS = 0x2f,
+    I16X8LTU = 0x30,
+    I16X8GTS = 0x31,
+    I16X8GTU = 0x32,
+    I16X8LES = 0x33,
+    I16X8LEU = 0x34,
+    I16X8GES = 0x35,
+    I16X8GEU = 0x36,
+    I32X4EQ = 0x37,
+    I32X4NE = 0x38,
+    I32X4LTS = 0x39,
+    I32X4LTU = 0x3a,
+    I32X4GTS = 0x3b,
+    I32X4GTU = 0x3c,
+    I32X4LES = 0x3d,
+    I32X4LEU = 0x3e,
+    I32X4GES = 0x3f,
+    I32X4GEU = 0x40,
+    F32X4EQ = 0x41,
+    F32X4NE = 0x42,
+    F32X4LT = 0x43,
+    F32X4GT = 0x44,
+    F32X4LE = 0x45,
+    F32X4GE = 0x46,
+    F64X2EQ = 0x47,
+    F64X2NE = 0x48,
+    F64X2LT = 0x49,
+    F64X2GT = 0x4a,
+    F64X2LE = 0x4b,
+    F64X2GE = 0x4c,
+
+    V128NOT = 0x4d,
+    V128AND = 0x4e,
+    V128ANDNOT = 0x4f,
+    V128OR = 0x50,
+    V128XOR = 0x51,
+    V128BITSELECT = 0x52,
+    V128ANYTRUE = 0x53,
+
+    V128LOAD8LANE = 0x54,
+    V128LOAD16LANE = 0x55,
+    V128LOAD32LANE = 0x56,
+    V128LOAD64LANE = 0x57,
+    V128STORE8LANE = 0x58,
+    V128STORE16LANE = 0x59,
+    V128STORE32LANE = 0x5a,
+    V128STORE64LANE = 0x5b,
+    V128LOAD32ZERO = 0x5c,
+    V128LOAD64ZERO = 0x5d,
+
+    F32X4DEMOTEF64X2ZERO = 0x5e,
+    F64X2PROMOTELOWF32X4 = 0x5f,
+
+    I8X16ABS = 0x60,
+    I8X16NEG = 0x61,
+    I8X16POPCNT = 0x62,
+    I8X16ALLTRUE = 0x63,
+    I8X16BITMASK = 0x64,
+    I8X16NARROWI16X8S = 0x65,
+    I8X16NARROWI16X8U = 0x66,
+    F32X4CEIL = 0x67,
+    F32X4FLOOR = 0x68,
+    F32X4TRUNC = 0x69,
+    F32X4NEAREST = 0x6a,
+    I8X16SHL = 0x6b,
+    I8X16SHRS = 0x6c,
+    I8X16SHRU = 0x6d,
+    I8X16ADD = 0x6e,
+    I8X16ADDSATS = 0x6f,
+    I8X16ADDSATU = 0x70,
+    I8X16SUB = 0x71,
+    I8X16SUBSATS = 0x72,
+    I8X16SUBSATU = 0x73,
+    F64X2CEIL = 0x74,
+    F64X2FLOOR = 0x75,
+    I8X16MINS = 0x76,
+    I8X16MINU = 0x77,
+    I8X16MAXS = 0x78,
+    I8X16MAXU = 0x79,
+    F64X2TRUNC = 0x7a,
+    I8X16AVGRU = 0x7b,
+
+    I16X8EXTADDPAIRWISEI8X16S = 0x7c,
+    I16X8EXTADDPAIRWISEI8X16U = 0x7d,
+    I32X4EXTADDPAIRWISEI16X8S = 0x7e,
+    I32X4EXTADDPAIRWISEI16X8U = 0x7f,
+
+    I16X8ABS = 0x80,
+    I16X8NEG = 0x81,
+    I16X8Q15MULRSATS = 0x82,
+    I16X8ALLTRUE = 0x83,
+    I16X8BITMASK = 0x84,
+    I16X8NARROWI32X4S = 0x85,
+    I16X8NARROWI32X4U = 0x86,
+    I16X8EXTENDLOWI8X16S = 0x87,
+    I16X8EXTENDHIGHI8X16S = 0x88,
+    I16X8EXTENDLOWI8X16U = 0x89,
+    I16X8EXTENDHIGHI8X16U = 0x8a,
+    I16X8SHL = 0x8b,
+    I16X8SHRS = 0x8c,
+    I16X8SHRU = 0x8d,
+    I16X8ADD = 0x8e,
+    I16X8ADDSATS = 0x8f,
+    I16X8ADDSATU = 0x90,
+    I16X8SUB = 0x91,
+    I16X8SUBSATS = 0x92,
+    I16X8SUBSATU = 0x93,
+    F64X2NEAREST = 0x94,
+    I16X8MUL = 0x95,
+    I16X8MINS = 0x96,
+    I16X8MINU = 0x97,
+    I16X8MAXS = 0x98,
+    I16X8MAXU = 0x99,
+    I16X8AVGRU = 0x9b,
+    I16X8EXTMULLOWI8X16S = 0x9c,
+    I16X8EXTMULHIGHI8X16S = 0x9d,
+    I16X8EXTMULLOWI8X16U = 0x9e,
+    I16X8EXTMULHIGHI8X16U = 0x9f,
+
+    I32X4ABS = 0xa0,
+    I32X4NEG = 0xa1,
+    I32X4ALLTRUE = 0xa3,
+    I32X4BITMASK = 0xa4,
+    I32X4EXTENDLOWI16X8S = 0xa7,
+    I32X4EXTENDHIGHI16X8S = 0xa8,
+    I32X4EXTENDLOWI16X8U = 0xa9,
+    I32X4EXTENDHIGHI16X8U = 0xaa,
+    I32X4SHL = 0xab,
+    I32X4SHRS = 0xac,
+    I32X4SHRU = 0xad,
+    I32X4ADD = 0xae,
+    I32X4SUB = 0xb1,
+    I32X4MUL = 0xb5,
+    I32X4MINS = 0xb6,
+    I32X4MINU = 0xb7,
+    I32X4MAXS = 0xb8,
+    I32X4MAXU = 0xb9,
+    I32X4DOTI16X8S = 0xba,
+    I32X4EXTMULLOWI16X8S = 0xbc,
+    I32X4EXTMULHIGHI16X8S = 0xbd,
+    I32X4EXTMULLOWI16X8U = 0xbe,
+    I32X4EXTMULHIGHI16X8U = 0xbf,
+
+    I64X2ABS = 0xc0,
+    I64X2NEG = 0xc1,
+    I64X2ALLTRUE = 0xc3,
+    I64X2BITMASK = 0xc4,
+    I64X2EXTENDLOWI32X4S = 0xc7,
+    I64X2EXTENDHIGHI32X4S = 0xc8,
+    I64X2EXTENDLOWI32X4U = 0xc9,
+    I64X2EXTENDHIGHI32X4U = 0xca,
+    I64X2SHL = 0xcb,
+    I64X2SHRS = 0xcc,
+    I64X2SHRU = 0xcd,
+    I64X2ADD = 0xce,
+    I64X2SUB = 0xd1,
+    I64X2MUL = 0xd5,
+    I64X2EQ = 0xd6,
+    I64X2NE = 0xd7,
+    I64X2LTS = 0xd8,
+    I64X2GTS = 0xd9,
+    I64X2LES = 0xda,
+    I64X2GES = 0xdb,
+    I64X2EXTMULLOWI32X4S = 0xdc,
+    I64X2EXTMULHIGHI32X4S = 0xdd,
+    I64X2EXTMULLOWI32X4U = 0xde,
+    I64X2EXTMULHIGHI32X4U = 0xdf,
+
+    F32X4ABS = 0xe0,
+    F32X4NEG = 0xe1,
+    F32X4SQRT = 0xe3,
+    F32X4ADD = 0xe4,
+    F32X4SUB = 0xe5,
+    F32X4MUL = 0xe6,
+    F32X4DIV = 0xe7,
+    F32X4MIN = 0xe8,
+    F32X4MAX = 0xe9,
+    F32X4PMIN = 0xea,
+    F32X4PMAX = 0xeb,
+    F64X2ABS = 0xec,
+    F64X2NEG = 0xed,
+    F64X2SQRT = 0xef,
+    F64X2ADD = 0xf0,
+    F64X2SUB = 0xf1,
+    F64X2MUL = 0xf2,
+    F64X2DIV = 0xf3,
+    F64X2MIN = 0xf4,
+    F64X2MAX = 0xf5,
+    F64X2PMIN = 0xf6,
+    F64X2PMAX = 0xf7,
+
+    I32X4TRUNCSATF32X4S = 0xf8,
+    I32X4TRUNCSATF32X4U = 0xf9,
+    F32X4CONVERTI32X4S = 0xfa,
+    F32X4CONVERTI32X4U = 0xfb,
+    I32X4TRUNCSATF64X2SZERO = 0xfc,
+    I32X4TRUNCSATF64X2UZERO = 0xfd,
+    F64X2CONVERTLOWI32X4S = 0xfe,
+    F64X2CONVERTLOWI32X4U = 0xff,
+}
+
+impl SimdOp {
+    /// Whether the instruction has alignment and offset immediates, like other memory instructions
+    pub fn has_memarg(op: u32) -> bool {
+        matches!(op, 0x00..=0x0b | 0x54..=0x5d)
+    }
+
+    /// The number of fixed-size immediate bytes, after the memarg if there is one.
+    /// These are 16 bytes of data or shuffle indices, or a lane index.
+    pub fn immediate_bytes(op: u32) -> usize {
+        match op {
+            0x0c | 0x0d => 16,
+            0x15..=0x22 | 0x54..=0x5b => 1,
+            _ => 0,
+        }
+    }
+}
+
 impl Serialize for OpCode {
     fn serialize<T: crate::SerialBuffer>(&self, buffer: &mut T) {
         (*self as u8).serialize(buffer)
@@ -14,6 +14,50 @@ pub struct ParseError {
     pub message: String,
 }
 
+impl ParseError {
+    pub(crate) fn end_of_file(offset: usize) -> Self {
+        ParseError {
+            offset,
+            message: "Unexpected end of file".into(),
+        }
+    }
+}
+
+/// The byte at the cursor, without moving past it
+pub(crate) fn peek_u8(bytes: &[u8], cursor: usize) -> Result<u8, ParseError> {
+    bytes
+        .get(cursor)
+        .copied()
+        .ok_or_else(|| ParseError::end_of_file(cursor))
+}
+
+/// The next `len` bytes, moving the cursor past them
+pub(crate) fn parse_slice<'b>(
+    bytes: &'b [u8],
+    cursor: &mut usize,
+    len: usize,
+) -> Result<&'b [u8], ParseError> {
+    let end = cursor
+        .checked_add(len)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| ParseError::end_of_file(*cursor))?;
+    let slice = &bytes[*cursor..end];
+    *cursor = end;
+    Ok(slice)
+}
+
+/// Capacity to reserve for `count` items that are each at least one byte long.
+/// Never more than the number of bytes left, so that a corrupted count can't cause a huge allocation.
+pub(crate) fn capacity_for(count: u32, bytes: &[u8], cursor: usize) -> usize {
+    (count as usize).min(bytes.len().saturating_sub(cursor))
+}
+
+/// Up to `max_len` bytes from the cursor, for error messages
+fn bytes_at(bytes: &[u8], cursor: usize, max_len: usize) -> &[u8] {
+    let rest = bytes.get(cursor..).unwrap_or_default();
+    &rest[..rest.len().min(max_len)]
+}
+
 /// Decode an unsigned 32-bit integer from the provided buffer in LEB-128 format
 /// Return the integer itself and the offset after it ends
 fn decode_u32(bytes: &[u8]) -> Result<(u32, usize), ()> {
@@ -31,7 +75,7 @@ fn decode_u32(bytes: &[u8]) -> Result<(u32, usize), ()> {
 
 impl Parse<()> for u32 {
     fn parse(_ctx: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        match decode_u32(&bytes[*cursor..]) {
+        match decode_u32(bytes_at(bytes, *cursor, MAX_SIZE_ENCODED_U32)) {
             Ok((value, len)) => {
                 *cursor += len;
                 Ok(value)
@@ -40,7 +84,7 @@ impl Parse<()> for u32 {
                 offset: *cursor,
                 message: format!(
                     "Failed to decode u32 as LEB-128 from bytes: {:2x?}",
-                    &bytes[*cursor..][..MAX_SIZE_ENCODED_U32]
+                    bytes_at(bytes, *cursor, MAX_SIZE_ENCODED_U32)
                 ),
             }),
         }
@@ -49,7 +93,7 @@ impl Parse<()> for u32 {
 
 impl Parse<()> for u8 {
     fn parse(_ctx: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let byte = bytes[*cursor];
+        let byte = peek_u8(bytes, *cursor)?;
         *cursor += 1;
         Ok(byte)
     }
@@ -76,7 +120,7 @@ fn decode_i32(bytes: &[u8]) -> Result<(i32, usize), ()> {
 
 impl Parse<()> for i32 {
     fn parse(_ctx: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        match decode_i32(&bytes[*cursor..]) {
+        match decode_i32(bytes_at(bytes, *cursor, MAX_SIZE_ENCODED_U32)) {
             Ok((value, len)) => {
                 *cursor += len;
                 Ok(value)
@@ -85,7 +129,7 @@ impl Parse<()> for i32 {
                 offset: *cursor,
                 message: format!(
                     "Failed to decode i32 as LEB-128 from bytes: {:2x?}",
-                    &bytes[*cursor..][..MAX_SIZE_ENCODED_U32]
+                    bytes_at(bytes, *cursor, MAX_SIZE_ENCODED_U32)
                 ),
             }),
         }
@@ -113,7 +157,7 @@ fn decode_i64(bytes: &[u8]) -> Result<(i64, usize), ()> {
 
 impl Parse<()> for i64 {
     fn parse(_ctx: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        match decode_i64(&bytes[*cursor..]) {
+        match decode_i64(bytes_at(bytes, *cursor, MAX_SIZE_ENCODED_U64)) {
             Ok((value, len)) => {
                 *cursor += len;
                 Ok(value)
@@ -122,7 +166,7 @@ impl Parse<()> for i64 {
                 offset: *cursor,
                 message: format!(
                     "Failed to decode i64 as LEB-128 from bytes: {:2x?}",
-                    &bytes[*cursor..][..MAX_SIZE_ENCODED_U64]
+                    bytes_at(bytes, *cursor, MAX_SIZE_ENCODED_U64)
                 ),
             }),
         }
@@ -132,12 +176,13 @@ impl Parse<()> for i64 {
 impl<'a> Parse<&'a Bump> for &'a str {
     fn parse(arena: &'a Bump, bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
         let len = u32::parse((), bytes, cursor)?;
-        let end = *cursor + len as usize;
-        let bytes: &[u8] = &bytes[*cursor..end];
-        let copy = arena.alloc_slice_copy(bytes);
-        let s = unsafe { std::str::from_utf8_unchecked(copy) };
-        *cursor = end;
-        Ok(s)
+        let start = *cursor;
+        let bytes = parse_slice(bytes, cursor, len as usize)?;
+        let s = std::str::from_utf8(bytes).map_err(|e| ParseError {
+            offset: start + e.valid_up_to(),
+            message: "Invalid UTF-8 in name".into(),
+        })?;
+        Ok(arena.alloc_str(s))
     }
 }
 
@@ -150,7 +195,7 @@ where
     T: Parse<&'a Bump>,
 {
     let len = u32::parse((), bytes, cursor)?;
-    let mut vector: Vec<'a, T> = Vec::with_capacity_in(len as usize, arena);
+    let mut vector: Vec<'a, T> = Vec::with_capacity_in(capacity_for(len, bytes, *cursor), arena);
     for _ in 0..len {
         let item = T::parse(arena, bytes, cursor)?;
         vector.push(item);
@@ -167,7 +212,7 @@ where
     T: Parse<()>,
 {
     let len = u32::parse((), bytes, cursor)?;
-    let mut vector: Vec<'a, T> = Vec::with_capacity_in(len as usize, arena);
+    let mut vector: Vec<'a, T> = Vec::with_capacity_in(capacity_for(len, bytes, *cursor), arena);
     for _ in 0..len {
         let item = T::parse((), bytes, cursor)?;
         vector.push(item);
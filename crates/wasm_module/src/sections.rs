@@ -9,7 +9,7 @@ use crate::{Value, DUMMY_FUNCTION};
 
 use super::linking::{LinkingSection, SymInfo, WasmObjectSymbol};
 use super::opcodes::OpCode;
-use super::parse::{Parse, ParseError, SkipBytes};
+use super::parse::{capacity_for, parse_slice, peek_u8, Parse, ParseError, SkipBytes};
 use super::serialize::{SerialBuffer, Serialize, MAX_SIZE_ENCODED_U32};
 use super::ValueType;
 
@@ -61,6 +61,34 @@ impl Debug for SectionId {
     }
 }
 
+impl SectionId {
+    pub fn from_byte(x: u8) -> Option<Self> {
+        if x <= SectionId::DataCount as u8 {
+            Some(unsafe { std::mem::transmute::<u8, SectionId>(x) })
+        } else {
+            None
+        }
+    }
+
+    /// Find which section contains a position in the module's bytes, for error messages.
+    /// Returns `None` for the file header, or if the section headers can't be decoded that far.
+    pub fn at_offset(module_bytes: &[u8], offset: usize) -> Option<SectionId> {
+        let mut cursor = 8; // skip the magic number and version
+        while cursor < module_bytes.len() && cursor <= offset {
+            let id = SectionId::from_byte(module_bytes[cursor])?;
+            let section_start = cursor;
+            cursor += 1;
+            let size = u32::parse((), module_bytes, &mut cursor).ok()?;
+            let end = cursor.saturating_add(size as usize);
+            if (section_start..end).contains(&offset) {
+                return Some(id);
+            }
+            cursor = end;
+        }
+        None
+    }
+}
+
 const MAX_SIZE_SECTION_HEADER: usize = std::mem::size_of::<SectionId>() + 2 * MAX_SIZE_ENCODED_U32;
 
 // Trait to help serialize simple sections that we just store as bytes
@@ -143,13 +171,38 @@ fn parse_section(
 
     let section_size = u32::parse((), module_bytes, cursor)?;
     let count_start = *cursor;
+    let next_section_start = section_end_offset(module_bytes, count_start, section_size)?;
     let count = u32::parse((), module_bytes, cursor)?;
     let body_start = *cursor;
-    let next_section_start = count_start + section_size as usize;
+    if body_start > next_section_start {
+        return Err(ParseError {
+            offset: count_start,
+            message: format!("{:?} section is too short for its item count", expected_id),
+        });
+    }
 
     Ok((count, body_start..next_section_start))
 }
 
+/// Where a section that starts at `body_start` ends, checking that it's within the file
+fn section_end_offset(
+    module_bytes: &[u8],
+    body_start: usize,
+    size: u32,
+) -> Result<usize, ParseError> {
+    match body_start.checked_add(size as usize) {
+        Some(end) if end <= module_bytes.len() => Ok(end),
+        _ => Err(ParseError {
+            offset: body_start,
+            message: format!(
+                "Section size {} goes past the end of the file, which is {} bytes long",
+                size,
+                module_bytes.len()
+            ),
+        }),
+    }
+}
+
 pub struct SectionHeaderIndices {
     size_index: usize,
     body_index: usize,
@@ -298,6 +351,11 @@ impl<'a> TypeSection<'a> {
         self.bytes.is_empty()
     }
 
+    /// Number of signatures
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
     pub fn look_up(&'a self, sig_index: u32) -> (SignatureParamsIter<'a>, Option<ValueType>) {
         let mut offset = self.offsets[sig_index as usize];
         offset += 1; // separator
@@ -331,26 +389,45 @@ impl<'a> Parse<&'a Bump> for TypeSection<'a> {
         let (count, range) = parse_section(Self::ID, module_bytes, cursor)?;
         let mut bytes = Vec::<u8>::with_capacity_in(range.len() * 2, arena);
         *cursor = range.end;
-        bytes.extend_from_slice(&module_bytes[range]);
+        bytes.extend_from_slice(&module_bytes[range.clone()]);
 
-        let mut offsets = Vec::with_capacity_in(2 * count as usize, arena);
-        let mut i = 0;
-        while i < bytes.len() {
-            offsets.push(i);
+        // Check the signatures in module_bytes, so that errors have the right offset
+        let mut offsets = Vec::with_capacity_in(capacity_for(count, &bytes, 0), arena);
+        let mut i = range.start;
+        while i < range.end {
+            offsets.push(i - range.start);
 
-            if bytes[i] != Signature::SEPARATOR {
+            if module_bytes[i] != Signature::SEPARATOR {
                 return Err(ParseError {
                     message: "Invalid signature separator in TypeSection".into(),
-                    offset: *cursor,
+                    offset: i,
                 });
             }
             i += 1;
 
-            let n_params = u32::parse((), &bytes, &mut i).unwrap();
-            i += n_params as usize; // skip over one byte per param type
+            // Check the value types now, since look_up assumes they're valid
+            let n_params = u32::parse((), module_bytes, &mut i)?;
+            for _ in 0..n_params {
+                ValueType::parse((), module_bytes, &mut i)?;
+            }
 
-            let n_return_values = bytes[i];
-            i += 1 + n_return_values as usize;
+            let n_return_values_offset = i;
+            let n_return_values = u8::parse((), module_bytes, &mut i)?;
+            if n_return_values > 1 {
+                return Err(ParseError {
+                    message: "Multiple return values are not supported".into(),
+                    offset: n_return_values_offset,
+                });
+            }
+            for _ in 0..n_return_values {
+                ValueType::parse((), module_bytes, &mut i)?;
+            }
+        }
+        if i > range.end {
+            return Err(ParseError {
+                message: "The last signature goes past the end of the TypeSection".into(),
+                offset: range.end,
+            });
         }
 
         Ok(TypeSection {
@@ -383,8 +460,7 @@ pub enum ImportDesc {
 
 impl Parse<()> for ImportDesc {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let type_id = ImportTypeId::from(bytes[*cursor]);
-        *cursor += 1;
+        let type_id = ImportTypeId::parse((), bytes, cursor)?;
         match type_id {
             ImportTypeId::Func => {
                 let signature_index = u32::parse((), bytes, cursor)?;
@@ -445,17 +521,18 @@ enum ImportTypeId {
     Global = 3,
 }
 
-impl From<u8> for ImportTypeId {
-    fn from(x: u8) -> Self {
-        match x {
-            0 => Self::Func,
-            1 => Self::Table,
-            2 => Self::Mem,
-            3 => Self::Global,
-            _ => internal_error!(
-                "Invalid ImportTypeId {} in platform/builtins object file",
-                x
-            ),
+impl Parse<()> for ImportTypeId {
+    fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
+        let offset = *cursor;
+        match u8::parse((), bytes, cursor)? {
+            0 => Ok(Self::Func),
+            1 => Ok(Self::Table),
+            2 => Ok(Self::Mem),
+            3 => Ok(Self::Global),
+            x => Err(ParseError {
+                offset,
+                message: format!("Invalid ImportTypeId {}", x),
+            }),
         }
     }
 }
@@ -519,7 +596,7 @@ impl<'a> ImportSection<'a> {
 impl<'a> Parse<&'a Bump> for ImportSection<'a> {
     fn parse(arena: &'a Bump, module_bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
         let (count, range) = parse_section(Self::ID, module_bytes, cursor)?;
-        let mut imports = Vec::with_capacity_in(count as usize, arena);
+        let mut imports = Vec::with_capacity_in(capacity_for(count, module_bytes, *cursor), arena);
 
         let end = range.end;
 
@@ -577,7 +654,8 @@ impl<'a> Parse<&'a Bump> for FunctionSection<'a> {
     fn parse(arena: &'a Bump, module_bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
         let (count, _) = parse_section(SectionId::Function, module_bytes, cursor)?;
 
-        let mut signatures = Vec::with_capacity_in(count as usize, arena);
+        let mut signatures =
+            Vec::with_capacity_in(capacity_for(count, module_bytes, *cursor), arena);
         for _ in 0..count {
             signatures.push(u32::parse((), module_bytes, cursor)?);
         }
@@ -621,8 +699,7 @@ pub enum RefType {
 
 impl Parse<()> for RefType {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let byte = bytes[*cursor];
-        *cursor += 1;
+        let byte = u8::parse((), bytes, cursor)?;
         match byte {
             0x70 => Ok(Self::Func),
             0x6f => Ok(Self::Extern),
@@ -709,9 +786,9 @@ impl Parse<()> for TableSection {
                 })
             }
             1 => {
-                if module_bytes[range.start] != RefType::Func as u8 {
+                if RefType::parse((), module_bytes, cursor)? != RefType::Func {
                     Err(ParseError {
-                        offset: *cursor,
+                        offset: range.start,
                         message: "Only funcref tables are supported".into(),
                     })
                 } else {
@@ -781,7 +858,7 @@ impl Serialize for Limits {
 
 impl SkipBytes for Limits {
     fn skip_bytes(bytes: &[u8], cursor: &mut usize) -> Result<(), ParseError> {
-        let variant_id = bytes[*cursor];
+        let variant_id = peek_u8(bytes, *cursor)?;
         u8::skip_bytes(bytes, cursor)?; // advance past the variant byte
         u32::skip_bytes(bytes, cursor)?; // skip "min"
         if variant_id == LimitsId::MinMax as u8 {
@@ -799,12 +876,17 @@ impl Parse<()> for Limits {
         let variant_id = bytes[*cursor];
         *cursor += 1;
 
-        let min = u32::parse((), bytes, cursor).unwrap();
+        let min = u32::parse((), bytes, cursor)?;
         if variant_id == LimitsId::MinMax as u8 {
-            let max = u32::parse((), bytes, cursor).unwrap();
+            let max = u32::parse((), bytes, cursor)?;
             Ok(Limits::MinMax(min, max))
-        } else {
+        } else if variant_id == LimitsId::Min as u8 {
             Ok(Limits::Min(min))
+        } else {
+            Err(ParseError {
+                offset: *cursor,
+                message: format!("Invalid Limits variant 0x{:x}", variant_id),
+            })
         }
     }
 }
@@ -817,6 +899,8 @@ pub struct MemorySection<'a> {
 
 impl<'a> MemorySection<'a> {
     pub const PAGE_SIZE: u32 = 64 * 1024;
+    /// A 32-bit address space holds at most 4GiB, or 65536 pages
+    pub const MAX_PAGES: u32 = 65536;
 
     pub fn new(arena: &'a Bump, memory_bytes: u32) -> Self {
         if memory_bytes == 0 {
@@ -835,23 +919,43 @@ impl<'a> MemorySection<'a> {
         }
     }
 
-    pub fn min_bytes(&self) -> Result<u32, ParseError> {
+    pub fn min_pages(&self) -> Result<u32, ParseError> {
         let mut cursor = 0;
         let memory_limits = Limits::parse((), &self.bytes, &mut cursor)?;
         let min_pages = match memory_limits {
             Limits::Min(pages) | Limits::MinMax(pages, _) => pages,
         };
-        Ok(min_pages * MemorySection::PAGE_SIZE)
+        Ok(min_pages)
     }
 
-    pub fn max_bytes(&self) -> Result<Option<u32>, ParseError> {
+    pub fn max_pages(&self) -> Result<Option<u32>, ParseError> {
         let mut cursor = 0;
         let memory_limits = Limits::parse((), &self.bytes, &mut cursor)?;
-        let bytes = match memory_limits {
+        let max_pages = match memory_limits {
             Limits::Min(_) => None,
-            Limits::MinMax(_, pages) => Some(pages * MemorySection::PAGE_SIZE),
+            Limits::MinMax(_, pages) => Some(pages),
         };
-        Ok(bytes)
+        Ok(max_pages)
+    }
+
+    pub fn min_bytes(&self) -> Result<u32, ParseError> {
+        Self::pages_to_bytes(self.min_pages()?)
+    }
+
+    pub fn max_bytes(&self) -> Result<Option<u32>, ParseError> {
+        self.max_pages()?.map(Self::pages_to_bytes).transpose()
+    }
+
+    fn pages_to_bytes(pages: u32) -> Result<u32, ParseError> {
+        pages
+            .checked_mul(MemorySection::PAGE_SIZE)
+            .ok_or_else(|| ParseError {
+                offset: 0,
+                message: format!(
+                    "{} pages of memory don't fit in a 32-bit address space",
+                    pages
+                ),
+            })
     }
 }
 
@@ -885,10 +989,8 @@ impl SkipBytes for GlobalType {
 
 impl Parse<()> for GlobalType {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let value_type = ValueType::from(bytes[*cursor]);
-        *cursor += 1;
-        let is_mutable = bytes[*cursor] != 0;
-        *cursor += 1;
+        let value_type = ValueType::parse((), bytes, cursor)?;
+        let is_mutable = u8::parse((), bytes, cursor)? != 0;
         Ok(GlobalType {
             value_type,
             is_mutable,
@@ -904,6 +1006,8 @@ pub enum ConstExpr {
     I64(i64),
     F32(f32),
     F64(f64),
+    /// The value of another global, which must come before the one being initialised
+    GlobalGet(u32),
 }
 
 impl ConstExpr {
@@ -913,14 +1017,14 @@ impl ConstExpr {
             message: "Invalid ConstExpr. Expected i32.".into(),
         });
 
-        if bytes[*cursor] != OpCode::I32CONST as u8 {
+        if peek_u8(bytes, *cursor)? != OpCode::I32CONST as u8 {
             return err;
         }
         *cursor += 1;
 
         let value = u32::parse((), bytes, cursor)?;
 
-        if bytes[*cursor] != OpCode::END as u8 {
+        if peek_u8(bytes, *cursor)? != OpCode::END as u8 {
             return err;
         }
         *cursor += 1;
@@ -937,47 +1041,58 @@ impl ConstExpr {
 
     // ConstExpr and Value are separate types in case we ever need to support
     // arbitrary constant expressions, rather than just i32.const and friends.
-    fn as_value(&self) -> Value {
+    /// `globals` are the values of the globals before the one being initialised
+    fn eval(&self, globals: &[Value]) -> Option<Value> {
         match self {
-            ConstExpr::I32(x) => Value::I32(*x),
-            ConstExpr::I64(x) => Value::I64(*x),
-            ConstExpr::F32(x) => Value::F32(*x),
-            ConstExpr::F64(x) => Value::F64(*x),
+            ConstExpr::I32(x) => Some(Value::I32(*x)),
+            ConstExpr::I64(x) => Some(Value::I64(*x)),
+            ConstExpr::F32(x) => Some(Value::F32(*x)),
+            ConstExpr::F64(x) => Some(Value::F64(*x)),
+            ConstExpr::GlobalGet(index) => globals.get(*index as usize).copied(),
         }
     }
 }
 
 impl Parse<()> for ConstExpr {
     fn parse(_ctx: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let opcode = OpCode::from(bytes[*cursor]);
-        *cursor += 1;
+        // Compare the bytes rather than converting to OpCode, which is only valid for known opcodes
+        let opcode_byte = u8::parse((), bytes, cursor)?;
 
-        let result = match opcode {
-            OpCode::I32CONST => {
+        let result = match opcode_byte {
+            x if x == OpCode::I32CONST as u8 => {
                 let x = i32::parse((), bytes, cursor)?;
-                Ok(ConstExpr::I32(x))
+                ConstExpr::I32(x)
             }
-            OpCode::I64CONST => {
+            x if x == OpCode::I64CONST as u8 => {
                 let x = i64::parse((), bytes, cursor)?;
-                Ok(ConstExpr::I64(x))
+                ConstExpr::I64(x)
             }
-            OpCode::F32CONST => {
+            x if x == OpCode::F32CONST as u8 => {
                 let mut b = [0; 4];
-                b.copy_from_slice(&bytes[*cursor..][..4]);
-                Ok(ConstExpr::F32(f32::from_le_bytes(b)))
+                b.copy_from_slice(parse_slice(bytes, cursor, 4)?);
+                ConstExpr::F32(f32::from_le_bytes(b))
             }
-            OpCode::F64CONST => {
+            x if x == OpCode::F64CONST as u8 => {
                 let mut b = [0; 8];
-                b.copy_from_slice(&bytes[*cursor..][..8]);
-                Ok(ConstExpr::F64(f64::from_le_bytes(b)))
+                b.copy_from_slice(parse_slice(bytes, cursor, 8)?);
+                ConstExpr::F64(f64::from_le_bytes(b))
+            }
+            x if x == OpCode::GETGLOBAL as u8 => {
+                let index = u32::parse((), bytes, cursor)?;
+                ConstExpr::GlobalGet(index)
+            }
+            _ => {
+                return Err(ParseError {
+                    offset: *cursor - 1,
+                    message: format!(
+                        "Unsupported opcode 0x{:02x} in constant expression.",
+                        opcode_byte
+                    ),
+                })
             }
-            _ => Err(ParseError {
-                offset: *cursor,
-                message: format!("Unsupported opcode {:?} in constant expression.", opcode),
-            }),
         };
 
-        if bytes[*cursor] != OpCode::END as u8 {
+        if peek_u8(bytes, *cursor)? != OpCode::END as u8 {
             return Err(ParseError {
                 offset: *cursor,
                 message: "Expected END opcode in constant expression.".into(),
@@ -985,7 +1100,7 @@ impl Parse<()> for ConstExpr {
         }
         *cursor += 1;
 
-        result
+        Ok(result)
     }
 }
 
@@ -1008,6 +1123,10 @@ impl Serialize for ConstExpr {
                 buffer.append_u8(OpCode::F64CONST as u8);
                 buffer.encode_f64(*x);
             }
+            ConstExpr::GlobalGet(index) => {
+                buffer.append_u8(OpCode::GETGLOBAL as u8);
+                buffer.encode_u32(*index);
+            }
         }
         buffer.append_u8(OpCode::END as u8);
     }
@@ -1015,7 +1134,7 @@ impl Serialize for ConstExpr {
 
 impl SkipBytes for ConstExpr {
     fn skip_bytes(bytes: &[u8], cursor: &mut usize) -> Result<(), ParseError> {
-        while bytes[*cursor] != OpCode::END as u8 {
+        while peek_u8(bytes, *cursor)? != OpCode::END as u8 {
             OpCode::skip_bytes(bytes, cursor)?;
         }
         *cursor += 1;
@@ -1067,15 +1186,51 @@ impl<'a> GlobalSection<'a> {
         self.count += 1;
     }
 
-    pub fn initial_values<'b>(&self, arena: &'b Bump) -> Vec<'b, Value> {
+    /// Types of the globals defined in this section, not including imported ones
+    pub fn types<'b>(&self, arena: &'b Bump) -> Result<Vec<'b, GlobalType>, ParseError> {
         let mut cursor = 0;
-        let iter = (0..self.count)
-            .map(|_| {
-                GlobalType::skip_bytes(&self.bytes, &mut cursor)?;
-                ConstExpr::parse((), &self.bytes, &mut cursor).map(|x| x.as_value())
-            })
-            .filter_map(|r| r.ok());
-        Vec::from_iter_in(iter, arena)
+        let mut types = Vec::with_capacity_in(capacity_for(self.count, &self.bytes, 0), arena);
+        for _ in 0..self.count {
+            types.push(GlobalType::parse((), &self.bytes, &mut cursor)?);
+            ConstExpr::skip_bytes(&self.bytes, &mut cursor)?;
+        }
+        Ok(types)
+    }
+
+    /// Values of all the globals in the module's index space, starting with the imported ones,
+    /// whose values must be known already since initialisers can refer to them.
+    pub fn initial_values<'b>(
+        &self,
+        arena: &'b Bump,
+        imported_values: &[Value],
+    ) -> Result<Vec<'b, Value>, ParseError> {
+        let mut values = Vec::with_capacity_in(
+            imported_values.len() + capacity_for(self.count, &self.bytes, 0),
+            arena,
+        );
+        values.extend_from_slice(imported_values);
+
+        let mut cursor = 0;
+        for _ in 0..self.count {
+            let global_type = GlobalType::parse((), &self.bytes, &mut cursor)?;
+            let init_offset = cursor;
+            let init = ConstExpr::parse((), &self.bytes, &mut cursor)?;
+            match init.eval(&values) {
+                Some(value) if ValueType::from(value) == global_type.value_type => {
+                    values.push(value)
+                }
+                _ => {
+                    return Err(ParseError {
+                        offset: init_offset,
+                        message: format!(
+                            "Invalid initialiser {:?} for a global of type {:?}",
+                            init, global_type.value_type
+                        ),
+                    })
+                }
+            }
+        }
+        Ok(values)
     }
 }
 
@@ -1096,14 +1251,18 @@ pub enum ExportType {
     Global = 3,
 }
 
-impl From<u8> for ExportType {
-    fn from(x: u8) -> Self {
-        match x {
-            0 => Self::Func,
-            1 => Self::Table,
-            2 => Self::Mem,
-            3 => Self::Global,
-            _ => internal_error!("invalid ExportType {:2x?}", x),
+impl Parse<()> for ExportType {
+    fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
+        let offset = *cursor;
+        match u8::parse((), bytes, cursor)? {
+            0 => Ok(Self::Func),
+            1 => Ok(Self::Table),
+            2 => Ok(Self::Mem),
+            3 => Ok(Self::Global),
+            x => Err(ParseError {
+                offset,
+                message: format!("Invalid ExportType {:2x?}", x),
+            }),
         }
     }
 }
@@ -1119,8 +1278,7 @@ impl<'a> Parse<&'a Bump> for Export<'a> {
     fn parse(arena: &'a Bump, bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
         let name = <&'a str>::parse(arena, bytes, cursor)?;
 
-        let ty = ExportType::from(bytes[*cursor]);
-        *cursor += 1;
+        let ty = ExportType::parse((), bytes, cursor)?;
 
         let index = u32::parse((), bytes, cursor)?;
 
@@ -1167,7 +1325,7 @@ impl<'a> Parse<&'a Bump> for ExportSection<'a> {
         let (num_exports, range) = parse_section(Self::ID, module_bytes, cursor)?;
 
         let mut export_section = ExportSection {
-            exports: Vec::with_capacity_in(num_exports as usize, arena),
+            exports: Vec::with_capacity_in(capacity_for(num_exports, module_bytes, *cursor), arena),
         };
 
         while *cursor < range.end {
@@ -1189,6 +1347,69 @@ impl<'a> Serialize for ExportSection<'a> {
     }
 }
 
+/*******************************************************************
+ *
+ * Start section
+ * The function to call when the module is instantiated
+ *
+ *******************************************************************/
+
+#[derive(Debug, Default)]
+pub struct StartSection {
+    pub function_index: Option<u32>,
+}
+
+impl StartSection {
+    const ID: SectionId = SectionId::Start;
+
+    pub fn new() -> Self {
+        StartSection {
+            function_index: None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self.function_index {
+            Some(_) => 1 + MAX_SIZE_ENCODED_U32 + MAX_SIZE_ENCODED_U32,
+            None => 0,
+        }
+    }
+}
+
+impl Parse<()> for StartSection {
+    fn parse(_: (), module_bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
+        if module_bytes.get(*cursor) != Some(&(Self::ID as u8)) {
+            return Ok(StartSection::new());
+        }
+        *cursor += 1;
+
+        let section_size = u32::parse((), module_bytes, cursor)?;
+        let body_start = *cursor;
+        let next_section_start = section_end_offset(module_bytes, body_start, section_size)?;
+        let function_index = u32::parse((), module_bytes, cursor)?;
+        if *cursor != next_section_start {
+            return Err(ParseError {
+                offset: body_start,
+                message: "The Start section should contain just one function index".into(),
+            });
+        }
+
+        Ok(StartSection {
+            function_index: Some(function_index),
+        })
+    }
+}
+
+impl Serialize for StartSection {
+    fn serialize<T: SerialBuffer>(&self, buffer: &mut T) {
+        if let Some(function_index) = self.function_index {
+            let header_indices = write_section_header(buffer, Self::ID);
+            buffer.encode_u32(function_index);
+            update_section_size(buffer, header_indices);
+        }
+    }
+}
+
 /*******************************************************************
  *
  * Element section
@@ -1237,20 +1458,20 @@ impl<'a> ElementSegment<'a> {
 impl<'a> Parse<&'a Bump> for ElementSegment<'a> {
     fn parse(arena: &'a Bump, bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
         // In practice we only need the original MVP format
-        let format_id = bytes[*cursor];
-        debug_assert!(format_id == ElementSegmentFormatId::ActiveImplicitTableIndex as u8);
-        *cursor += 1;
+        let format_offset = *cursor;
+        let format_id = u8::parse((), bytes, cursor)?;
+        if format_id != ElementSegmentFormatId::ActiveImplicitTableIndex as u8 {
+            return Err(ParseError {
+                offset: format_offset,
+                message: format!("Unsupported element segment format 0x{:02x}", format_id),
+            });
+        }
 
         // The table index offset is encoded as a ConstExpr, but only I32 makes sense
-        let const_expr_opcode = bytes[*cursor];
-        debug_assert!(const_expr_opcode == OpCode::I32CONST as u8);
-        *cursor += 1;
-        let offset = u32::parse((), bytes, cursor)?;
-        debug_assert!(bytes[*cursor] == OpCode::END as u8);
-        *cursor += 1;
+        let offset = ConstExpr::parse_u32(bytes, cursor)?;
 
         let num_elems = u32::parse((), bytes, cursor)?;
-        let mut fn_indices = Vec::with_capacity_in(num_elems as usize, arena);
+        let mut fn_indices = Vec::with_capacity_in(capacity_for(num_elems, bytes, *cursor), arena);
         for _ in 0..num_elems {
             let fn_idx = u32::parse((), bytes, cursor)?;
 
@@ -1330,8 +1551,8 @@ impl<'a> ElementSection<'a> {
     /// Look up a "function pointer" (element index) and return the function index.
     pub fn lookup(&self, element_index: u32) -> Option<u32> {
         self.segments.iter().find_map(|seg| {
-            let adjusted_index = element_index as usize - seg.offset.unwrap_i32() as usize;
-            seg.fn_indices.get(adjusted_index).copied()
+            let adjusted_index = element_index.checked_sub(seg.offset.unwrap_i32() as u32)?;
+            seg.fn_indices.get(adjusted_index as usize).copied()
         })
     }
 }
@@ -1350,7 +1571,8 @@ impl<'a> Parse<&'a Bump> for ElementSection<'a> {
                 segments: bumpalo::vec![in arena; seg],
             })
         } else {
-            let mut segments = Vec::with_capacity_in(num_segments as usize, arena);
+            let mut segments =
+                Vec::with_capacity_in(capacity_for(num_segments, module_bytes, *cursor), arena);
 
             for _ in 0..num_segments {
                 let seg = ElementSegment::parse(arena, module_bytes, cursor)?;
@@ -1404,28 +1626,73 @@ impl<'a> CodeSection<'a> {
         MAX_SIZE_SECTION_HEADER + self.bytes.len()
     }
 
+    /// Put the dummy functions for eliminated imports into `bytes`, where `serialize` would
+    /// insert them, so that each function index has its code. Relocations are not updated.
+    pub fn insert_dead_import_dummies(&mut self, arena: &'a Bump) {
+        // Modules built from scratch have no function count at the start of `bytes`
+        let first_fn_start = match self.function_offsets.first() {
+            Some(offset) => *offset as usize,
+            None => self.bytes.len(),
+        };
+        if self.dead_import_dummy_count == 0 && first_fn_start > 0 {
+            return;
+        }
+
+        let dummy_count = self.dead_import_dummy_count;
+        let function_count = dummy_count + self.function_count;
+        let mut bytes = Vec::with_capacity_in(
+            MAX_SIZE_ENCODED_U32
+                + dummy_count as usize * (DUMMY_FUNCTION.len() + 1)
+                + self.bytes.len(),
+            arena,
+        );
+        let mut function_offsets = Vec::with_capacity_in(function_count as usize, arena);
+
+        function_count.serialize(&mut bytes);
+        for _ in 0..dummy_count {
+            function_offsets.push(bytes.len() as u32);
+            DUMMY_FUNCTION.serialize(&mut bytes);
+        }
+
+        let shift = bytes.len() as u32;
+        function_offsets.extend(
+            self.function_offsets
+                .iter()
+                .map(|offset| offset - first_fn_start as u32 + shift),
+        );
+        bytes.extend_from_slice(&self.bytes[first_fn_start..]);
+
+        self.function_count = function_count;
+        self.dead_import_dummy_count = 0;
+        self.bytes = bytes;
+        self.function_offsets = function_offsets;
+    }
+
     pub fn parse(
         arena: &'a Bump,
         module_bytes: &[u8],
         cursor: &mut usize,
     ) -> Result<Self, ParseError> {
-        if module_bytes[*cursor] != SectionId::Code as u8 {
+        if peek_u8(module_bytes, *cursor)? != SectionId::Code as u8 {
             return Err(ParseError {
                 offset: *cursor,
                 message: "Missing code section!".into(),
             });
         }
         *cursor += 1;
-        let section_size = u32::parse((), module_bytes, cursor)? as usize;
+        let section_size = u32::parse((), module_bytes, cursor)?;
         let section_body_start = *cursor;
+        let next_section_start =
+            section_end_offset(module_bytes, section_body_start, section_size)?;
         let function_count = u32::parse((), module_bytes, cursor)?;
-        let next_section_start = section_body_start + section_size;
 
         // `bytes` must include the function count for linker offsets to be correct.
+        let section_size = section_size as usize;
         let mut bytes = Vec::with_capacity_in(section_size + section_size / 2, arena);
         bytes.extend_from_slice(&module_bytes[section_body_start..*cursor]);
 
-        let mut function_offsets = Vec::with_capacity_in(function_count as usize, arena);
+        let mut function_offsets =
+            Vec::with_capacity_in(capacity_for(function_count, module_bytes, *cursor), arena);
 
         // While copying the code bytes, also note where each function starts & ends
         // Later we will use this for dead code elimination
@@ -1433,11 +1700,20 @@ impl<'a> CodeSection<'a> {
             let fn_start = *cursor;
             function_offsets.push((fn_start - section_body_start) as u32);
             let fn_length = u32::parse((), module_bytes, cursor)? as usize;
-            *cursor += fn_length;
+            parse_slice(&module_bytes[..next_section_start], cursor, fn_length)?;
             bytes.extend_from_slice(&module_bytes[fn_start..*cursor]);
         }
 
-        debug_assert_eq!(function_offsets.len(), function_count as usize);
+        if function_offsets.len() != function_count as usize {
+            return Err(ParseError {
+                offset: section_body_start,
+                message: format!(
+                    "The Code section says it has {} functions, but it has {}",
+                    function_count,
+                    function_offsets.len()
+                ),
+            });
+        }
 
         Ok(CodeSection {
             function_count,
@@ -1509,8 +1785,7 @@ impl Serialize for DataMode {
 
 impl Parse<()> for DataMode {
     fn parse(_: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let variant_id = bytes[*cursor];
-        *cursor += 1;
+        let variant_id = u8::parse((), bytes, cursor)?;
 
         if variant_id == Self::ACTIVE {
             let offset = ConstExpr::parse_u32(bytes, cursor)?;
@@ -1585,11 +1860,19 @@ impl<'a> DataSection<'a> {
             };
             let len32 = u32::parse((), &self.bytes, &mut cursor).map_err(|e| format!("{:?}", e))?;
             let len = len32 as usize;
-            let mut target_slice = &mut memory[start..][..len];
-            target_slice
-                .write(&self.bytes[cursor..][..len])
-                .map_err(|e| format!("{:?}", e))?;
-            cursor += len;
+            let source =
+                parse_slice(&self.bytes, &mut cursor, len).map_err(|e| format!("{:?}", e))?;
+            let memory_size = memory.len();
+            let mut target_slice = start
+                .checked_add(len)
+                .and_then(|end| memory.get_mut(start..end))
+                .ok_or_else(|| {
+                    format!(
+                        "A data segment of {} bytes at address {:#x} doesn't fit in the memory, which is {:#x} bytes",
+                        len, start, memory_size
+                    )
+                })?;
+            target_slice.write(source).map_err(|e| format!("{:?}", e))?;
         }
         Ok(())
     }
@@ -1622,10 +1905,15 @@ impl<'a> Parse<&'a Bump> for DataSection<'a> {
                 _ => {}
             }
             let segment_bytes_len = u32::parse((), module_bytes, cursor)?;
-            *cursor += segment_bytes_len as usize;
+            parse_slice(&module_bytes[..end], cursor, segment_bytes_len as usize)?;
         }
 
-        debug_assert_eq!(*cursor, end);
+        if *cursor != end {
+            return Err(ParseError {
+                offset: *cursor,
+                message: "The data segments don't fill the Data section".into(),
+            });
+        }
 
         Ok(DataSection {
             end_addr: end_addr as u32,
@@ -1672,13 +1960,13 @@ impl<'a> Parse<(&'a Bump, SectionId)> for OpaqueSection<'a> {
     ) -> Result<Self, ParseError> {
         let bytes: &[u8];
 
-        if module_bytes[*cursor] != id as u8 {
+        if module_bytes.get(*cursor) != Some(&(id as u8)) {
             bytes = &[];
         } else {
             let section_start = *cursor;
             *cursor += 1;
             let section_size = u32::parse((), module_bytes, cursor)?;
-            let next_section_start = *cursor + section_size as usize;
+            let next_section_start = section_end_offset(module_bytes, *cursor, section_size)?;
             bytes = &module_bytes[section_start..next_section_start];
             *cursor = next_section_start;
         };
@@ -1777,8 +2065,8 @@ impl<'a> Parse<&'a Bump> for NameSection<'a> {
         *cursor += 1;
 
         // Section size
-        let section_size = u32::parse((), module_bytes, cursor)? as usize;
-        let section_end = *cursor + section_size;
+        let section_size = u32::parse((), module_bytes, cursor)?;
+        let section_end = section_end_offset(module_bytes, *cursor, section_size)?;
 
         let section_name = <&'a str>::parse(arena, module_bytes, cursor)?;
         if section_name != Self::NAME {
@@ -1791,8 +2079,7 @@ impl<'a> Parse<&'a Bump> for NameSection<'a> {
         // Find function names subsection
         let mut found_function_names = false;
         for _possible_subsection_id in 0..2 {
-            let subsection_id = module_bytes[*cursor];
-            *cursor += 1;
+            let subsection_id = u8::parse((), module_bytes, cursor)?;
             let subsection_size = u32::parse((), module_bytes, cursor)?;
             if subsection_id == NameSubSections::FunctionNames as u8 {
                 found_function_names = true;
@@ -1815,7 +2102,10 @@ impl<'a> Parse<&'a Bump> for NameSection<'a> {
 
         let count = u32::parse((), module_bytes, cursor)?;
         let mut section = NameSection {
-            function_names: Vec::with_capacity_in(count as usize, arena),
+            function_names: Vec::with_capacity_in(
+                capacity_for(count, module_bytes, *cursor),
+                arena,
+            ),
         };
 
         // Function names
@@ -1920,4 +2210,51 @@ mod tests {
         }
         test_assert_types_preload(arena, &section);
     }
+
+    #[test]
+    fn test_global_initial_values() {
+        let arena = &Bump::new();
+        let mut section = GlobalSection::new(arena);
+        let globals = [
+            (ValueType::F32, ConstExpr::F32(1.5)),
+            (ValueType::I32, ConstExpr::GlobalGet(0)),
+            (ValueType::F64, ConstExpr::F64(-2.0)),
+        ];
+        for (value_type, init) in globals {
+            section.append(Global {
+                ty: GlobalType {
+                    value_type,
+                    is_mutable: true,
+                },
+                init,
+            });
+        }
+
+        let imported = [Value::I32(1024)];
+        let values = section.initial_values(arena, &imported).unwrap();
+        assert_eq!(
+            values.as_slice(),
+            &[
+                Value::I32(1024),
+                Value::F32(1.5),
+                Value::I32(1024),
+                Value::F64(-2.0)
+            ]
+        );
+
+        let types = section.types(arena).unwrap();
+        assert_eq!(types.len(), 3);
+        assert_eq!(types[2].value_type, ValueType::F64);
+
+        // The imported global is I32, but the first one defined here is F32
+        let mut mismatched = GlobalSection::new(arena);
+        mismatched.append(Global {
+            ty: GlobalType {
+                value_type: ValueType::F32,
+                is_mutable: false,
+            },
+            init: ConstExpr::GlobalGet(0),
+        });
+        assert!(mismatched.initial_values(arena, &imported).is_err());
+    }
 }